@@ -0,0 +1,95 @@
+//! Benchmarks of the steps of `create_agent` that run for every agent
+//!
+//! Run with `cargo bench` from the repository root, so the starter template is found.
+
+use coinbase_agent_kit_blueprint::create_agent::{
+    link_template, render_compose_file, render_env_file,
+};
+use coinbase_agent_kit_blueprint::docker::normalize_docker_compose;
+use coinbase_agent_kit_blueprint::envelope::{decode_params, ParamsEnvelope};
+use coinbase_agent_kit_blueprint::{CreateAgentParams, LogRetention};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use std::fs;
+use std::path::Path;
+
+const TEMPLATE_DIR: &str = "templates/starter";
+
+/// Parameters of an agent using most options, so every rendering step does work
+fn params() -> CreateAgentParams {
+    serde_json::from_value(serde_json::json!({
+        "name": "bench",
+        "agent_config": {
+            "mode": "Autonomous",
+            "model": "gpt-4o-mini",
+            "system_prompt": "You trade on Base.\nNever move more than the budget allows.",
+            "temperature": 0.2,
+            "max_tokens": 1024,
+            "schedule": { "interval_secs": 300, "active_hours": { "start": 8, "end": 20 } }
+        },
+        "action_providers": ["Wallet", "Erc20", "Weth"],
+        "budget": { "max_transaction_value": 0.1, "daily_cap": 1.0, "allowed_assets": ["eth", "usdc"] },
+        "allowed_actions": ["Transfer", "Swap"],
+        "deployment_config": {
+            "tee_enabled": false,
+            "docker_compose_path": null,
+            "http_port": 3000,
+            "egress": { "allowed_hosts": ["api.openai.com"] }
+        },
+        "api_key_config": { "openai_api_key": "sk-bench", "cdp_api_key_name": null, "cdp_api_key_private_key": null }
+    }))
+    .expect("benchmark parameters are valid")
+}
+
+fn template_copy(c: &mut Criterion) {
+    let ignore = ["node_modules".to_string(), ".yarn".to_string()];
+    c.bench_function("template_copy", |b| {
+        b.iter_batched(
+            || tempfile::tempdir().unwrap(),
+            |dir| link_template(Path::new(TEMPLATE_DIR), dir.path(), &ignore).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+fn compose(c: &mut Criterion) {
+    let template = fs::read_to_string(Path::new(TEMPLATE_DIR).join("docker-compose.yml"))
+        .expect("run benchmarks from the repository root");
+    let params = params();
+    let retention = LogRetention::default();
+
+    c.bench_function("compose_normalize", |b| {
+        b.iter(|| normalize_docker_compose(black_box(&template)).unwrap())
+    });
+    c.bench_function("compose_render", |b| {
+        b.iter(|| render_compose_file(black_box(&template), &params, &retention).unwrap())
+    });
+}
+
+fn env_render(c: &mut Criterion) {
+    let template = fs::read_to_string(Path::new(TEMPLATE_DIR).join(".env.example"))
+        .expect("run benchmarks from the repository root");
+    let params = params();
+
+    c.bench_function("env_render", |b| {
+        b.iter(|| render_env_file(black_box(&template), &params, Some("owner")).unwrap())
+    });
+}
+
+fn params_serde(c: &mut Criterion) {
+    let params = params();
+    let bare = serde_json::to_vec(&params).unwrap();
+    let envelope = ParamsEnvelope::new(params.clone()).to_bytes().unwrap();
+
+    c.bench_function("params_serialize", |b| {
+        b.iter(|| serde_json::to_vec(black_box(&params)).unwrap())
+    });
+    c.bench_function("params_deserialize", |b| {
+        b.iter(|| serde_json::from_slice::<CreateAgentParams>(black_box(&bare)).unwrap())
+    });
+    c.bench_function("params_decode_envelope", |b| {
+        b.iter(|| decode_params::<CreateAgentParams>(black_box(&envelope)).unwrap())
+    });
+}
+
+criterion_group!(benches, template_copy, compose, env_render, params_serde);
+criterion_main!(benches);
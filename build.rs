@@ -8,4 +8,15 @@ fn main() {
     // println!("cargo:rerun-if-changed=src/lib.rs");
     // println!("cargo:rerun-if-changed=src/main.rs");
     // build::blueprint_metadata::generate_json();
+
+    // Generate the gRPC management API, with a bundled protoc so none needs to be installed
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/agent_manager.proto");
+        let protoc =
+            protoc_bin_vendored::protoc_bin_path().expect("no bundled protoc for this host");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::compile_protos("proto/agent_manager.proto")
+            .expect("failed to compile proto/agent_manager.proto");
+    }
 }
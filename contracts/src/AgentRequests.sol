@@ -0,0 +1,40 @@
+// SPDX-License-Identifier: UNLICENSE
+pragma solidity >=0.8.13;
+
+/**
+ * @title AgentRequests
+ * @dev Lets EVM contracts (e.g. restaking integrations) request agents from the blueprint's
+ * operators without going through Tangle jobs. Operators running with the `evm` feature
+ * listen for these events and handle them like the `create_agent` and `deploy_agent` jobs.
+ * @dev `params` carries the same JSON-encoded parameters those jobs take.
+ */
+contract AgentRequests {
+    /// @dev Emitted for every agent creation request
+    event CreateAgentRequested(uint64 indexed requestId, address indexed requester, bytes params);
+
+    /// @dev Emitted for every agent deployment request
+    event DeployAgentRequested(uint64 indexed requestId, address indexed requester, bytes params);
+
+    /// @dev ID of the next request
+    uint64 public nextRequestId;
+
+    /**
+     * @dev Requests a new agent.
+     * @param params JSON-encoded `CreateAgentParams`.
+     * @return requestId The ID of the request.
+     */
+    function requestCreateAgent(bytes calldata params) external returns (uint64 requestId) {
+        requestId = nextRequestId++;
+        emit CreateAgentRequested(requestId, msg.sender, params);
+    }
+
+    /**
+     * @dev Requests the deployment of a created agent.
+     * @param params JSON-encoded `DeployAgentParams`.
+     * @return requestId The ID of the request.
+     */
+    function requestDeployAgent(bytes calldata params) external returns (uint64 requestId) {
+        requestId = nextRequestId++;
+        emit DeployAgentRequested(requestId, msg.sender, params);
+    }
+}
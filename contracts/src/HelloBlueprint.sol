@@ -9,6 +9,18 @@ import "tnt-core/BlueprintServiceManagerBase.sol";
  * @dev For all supported hooks, check the `BlueprintServiceManagerBase` contract.
  */
 contract HelloBlueprint is BlueprintServiceManagerBase {
+    /**
+     * @dev Emitted when an operator registers, carrying its JSON-encoded `RegistrationParams`
+     * (supported modes, max agents and per-agent price).
+     */
+    event OperatorRegistered(address indexed operator, bytes registrationInputs);
+
+    /**
+     * @dev Emitted when a service is requested, carrying its JSON-encoded `RequestParams`
+     * (the agent quota the requester expects).
+     */
+    event ServiceRequested(uint64 indexed requestId, bytes requestInputs);
+
     /**
      * @dev Hook for service operator registration. Called when a service operator
      * attempts to register with the blueprint.
@@ -25,7 +37,7 @@ contract HelloBlueprint is BlueprintServiceManagerBase {
     override
     onlyFromMaster
     {
-        // Do something with the operator's details
+        emit OperatorRegistered(operatorAddressFromPublicKey(operator.ecdsaPublicKey), registrationInputs);
     }
 
     /**
@@ -37,7 +49,7 @@ contract HelloBlueprint is BlueprintServiceManagerBase {
      */
     function onRequest(ServiceOperators.RequestParams calldata params) external payable virtual override onlyFromMaster
     {
-        // Do something with the service request
+        emit ServiceRequested(params.requestId, params.requestInputs);
     }
 
     /**
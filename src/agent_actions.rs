@@ -0,0 +1,67 @@
+use crate::codec;
+use crate::deploy_agent::get_required_ports;
+use crate::error::BlueprintError;
+use crate::helpers::{read_env_file, run_blocking};
+use crate::ownership::authorize;
+use crate::types::{AgentActionsParams, AgentActionsResult};
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+
+/// Handles the agent_actions job
+pub async fn handle_agent_actions(
+    params_bytes: Vec<u8>,
+    context: &ServiceContext,
+) -> Result<Vec<u8>, BlueprintError> {
+    // Deserialize the parameters from bytes
+    let (params, encoding): (AgentActionsParams, _) =
+        codec::decode(&params_bytes).map_err(BlueprintError::Params)?;
+
+    // Only the agent's owner (or the service owner) may read its history
+    let base_dir = context
+        .agents_base_dir()
+        .unwrap_or_else(|| "./agents".to_string());
+    let agent_dir = params.agent_id.dir(&base_dir);
+    if !agent_dir.exists() {
+        return Err(BlueprintError::Params(format!(
+            "Unknown agent: {}",
+            params.agent_id
+        )));
+    }
+    let env_path = agent_dir.join(".env");
+    let agent_env = run_blocking(move || read_env_file(&env_path))
+        .await
+        .map_err(BlueprintError::Io)?;
+    authorize(context, &params.agent_id, &agent_env).map_err(BlueprintError::Unauthorized)?;
+
+    // The agent must have been deployed locally for us to reach it
+    let (http_port, _) =
+        get_required_ports(&params.agent_id, context).map_err(BlueprintError::Params)?;
+    let http_config = context.http_config();
+    let agent = context
+        .agent_endpoint(format!("http://localhost:{}", http_port))
+        .map_err(BlueprintError::Internal)?;
+
+    logging::info!(
+        "Retrieving action history for agent {} since {:?}",
+        params.agent_id,
+        params.since
+    );
+    let actions = agent
+        .get_actions(params.since, http_config.request_timeout)
+        .await
+        .map_err(BlueprintError::Health)?;
+    logging::info!(
+        "Retrieved {} actions for agent {}",
+        actions.len(),
+        params.agent_id
+    );
+
+    // Prepare the result
+    let result = AgentActionsResult {
+        agent_id: params.agent_id,
+        actions,
+    };
+
+    // Serialize the result
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
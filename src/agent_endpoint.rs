@@ -2,6 +2,9 @@ use std::time::{Duration, Instant};
 
 use serde_json::{json, Value};
 
+use crate::config::{HealthBackoff, HttpConfig, ProxyConfig};
+use crate::types::{AgentAction, AgentWallet};
+
 /// A struct representing a deployed agent endpoint
 #[derive(Debug, Clone)]
 pub struct AgentEndpoint {
@@ -9,6 +12,8 @@ pub struct AgentEndpoint {
     pub base_url: String,
     /// HTTP client for making requests
     http_client: reqwest::Client,
+    /// Request timeout replacing the one each call is given, if overridden
+    request_timeout: Option<Duration>,
 }
 
 impl AgentEndpoint {
@@ -25,9 +30,54 @@ impl AgentEndpoint {
         Self {
             base_url: base_url.into(),
             http_client: reqwest::Client::new(),
+            request_timeout: None,
         }
     }
 
+    /// Creates a new AgentEndpoint whose HTTP client honours the operator's timeouts
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The base URL of the agent (e.g., "http://localhost:3000")
+    /// * `config` - The operator's HTTP configuration
+    ///
+    /// # Returns
+    ///
+    /// A new AgentEndpoint instance or an error if the HTTP client could not be built
+    pub fn with_config(base_url: impl Into<String>, config: &HttpConfig) -> Result<Self, String> {
+        Ok(Self::with_client(base_url, config.build_client()?))
+    }
+
+    /// Creates a new AgentEndpoint that shares an existing HTTP client
+    ///
+    /// `reqwest::Client` is reference counted, so endpoints created from the same
+    /// client share its connection pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The base URL of the agent (e.g., "http://localhost:3000")
+    /// * `http_client` - The shared HTTP client
+    ///
+    /// # Returns
+    ///
+    /// A new AgentEndpoint instance
+    pub fn with_client(base_url: impl Into<String>, http_client: reqwest::Client) -> Self {
+        Self {
+            base_url: base_url.into(),
+            http_client,
+            request_timeout: None,
+        }
+    }
+
+    /// Returns a builder for an AgentEndpoint with per-endpoint overrides
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The base URL of the agent (e.g., "http://localhost:3000")
+    pub fn builder(base_url: impl Into<String>) -> AgentEndpointBuilder {
+        AgentEndpointBuilder::new(base_url)
+    }
+
     /// Creates an AgentEndpoint from a port number (localhost)
     ///
     /// # Arguments
@@ -57,7 +107,10 @@ impl AgentEndpoint {
         blueprint_sdk::logging::info!("Sending health check request to: {}", health_url);
 
         // Build the request with timeout
-        let request = self.http_client.get(&health_url).timeout(timeout);
+        let request = self
+            .http_client
+            .get(&health_url)
+            .timeout(self.timeout(timeout));
 
         // Try to send the request and handle different error cases
         match request.send().await {
@@ -118,6 +171,9 @@ impl AgentEndpoint {
 
     /// Waits for the agent to become healthy with detailed diagnostics
     ///
+    /// The delay between attempts starts at `initial_delay` and grows by half after each
+    /// failed attempt.
+    ///
     /// # Arguments
     ///
     /// * `max_attempts` - Maximum number of health check attempts
@@ -136,8 +192,33 @@ impl AgentEndpoint {
         // Wait before first attempt
         tokio::time::sleep(initial_delay).await;
 
+        let backoff = HealthBackoff {
+            retry_delay: initial_delay,
+            factor: 1.5,
+        };
+        self.poll_health(max_attempts, timeout, backoff).await
+    }
+
+    /// Checks the agent's health until it passes, waiting between failed attempts
+    ///
+    /// # Arguments
+    ///
+    /// * `max_attempts` - Maximum number of health check attempts
+    /// * `timeout` - Maximum time to wait for each health check response
+    /// * `backoff` - Delays between attempts
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or an error message holding the last failure
+    pub async fn poll_health(
+        &self,
+        max_attempts: u32,
+        timeout: Duration,
+        backoff: HealthBackoff,
+    ) -> Result<(), String> {
         // Track start time for overall statistics
         let start_time = Instant::now();
+        let mut last_error = None;
 
         for attempt in 1..=max_attempts {
             blueprint_sdk::logging::info!(
@@ -162,11 +243,11 @@ impl AgentEndpoint {
 
                     // If this isn't the last attempt, wait before trying again
                     if attempt < max_attempts {
-                        // Increase delay with each failure using exponential backoff
-                        let delay = initial_delay.mul_f32(1.5_f32.powi(attempt as i32 - 1));
+                        let delay = backoff.delay_for(attempt);
                         blueprint_sdk::logging::info!("Waiting {:?} before next attempt", delay);
                         tokio::time::sleep(delay).await;
                     }
+                    last_error = Some(e);
                 }
             }
         }
@@ -178,10 +259,16 @@ impl AgentEndpoint {
             total_duration.as_millis()
         );
 
-        Err(format!(
-            "Agent failed to become healthy after {} attempts",
-            max_attempts
-        ))
+        Err(match last_error {
+            Some(e) => format!(
+                "Agent failed to become healthy after {} attempts: {}",
+                max_attempts, e
+            ),
+            None => format!(
+                "Agent failed to become healthy after {} attempts",
+                max_attempts
+            ),
+        })
     }
 
     /// Sends a message to the agent and gets a response
@@ -199,7 +286,7 @@ impl AgentEndpoint {
         self.http_client
             .post(&interact_url)
             .json(&json!({ "message": message }))
-            .timeout(timeout)
+            .timeout(self.timeout(timeout))
             .send()
             .await
             .map_err(|e| format!("Interaction request failed: {}", e))?
@@ -207,6 +294,217 @@ impl AgentEndpoint {
             .await
             .map_err(|e| format!("Failed to parse interaction response: {}", e))
     }
+
+    /// Retrieves the on-chain actions the agent has executed
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - Only return actions at or after this unix timestamp (seconds)
+    /// * `timeout` - Maximum time to wait for a response
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the recorded actions, oldest first, or an error
+    pub async fn get_actions(
+        &self,
+        since: Option<u64>,
+        timeout: Duration,
+    ) -> Result<Vec<AgentAction>, String> {
+        let actions_url = format!("{}/actions", self.base_url);
+        let mut request = self
+            .http_client
+            .get(&actions_url)
+            .timeout(self.timeout(timeout));
+        if let Some(since) = since {
+            request = request.query(&[("since", since)]);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Action history request failed: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!(
+                "Action history request returned error status: {}",
+                status
+            ));
+        }
+
+        let body = response
+            .json::<Value>()
+            .await
+            .map_err(|e| format!("Failed to parse action history response: {}", e))?;
+
+        let actions = body
+            .get("actions")
+            .cloned()
+            .ok_or_else(|| "Action history response is missing `actions`".to_string())?;
+
+        serde_json::from_value(actions)
+            .map_err(|e| format!("Failed to parse action history entries: {}", e))
+    }
+
+    /// Retrieves the address and network of the agent's wallet
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time to wait for a response
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the agent's wallet, or an error
+    pub async fn get_wallet(&self, timeout: Duration) -> Result<AgentWallet, String> {
+        let wallet_url = format!("{}/wallet", self.base_url);
+        let response = self
+            .http_client
+            .get(&wallet_url)
+            .timeout(self.timeout(timeout))
+            .send()
+            .await
+            .map_err(|e| format!("Wallet request failed: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("Wallet request returned error status: {}", status));
+        }
+
+        let wallet = response
+            .json::<AgentWallet>()
+            .await
+            .map_err(|e| format!("Failed to parse wallet response: {}", e))?;
+        wallet.validate()?;
+        Ok(wallet)
+    }
+
+    /// Asks the agent to request testnet funds for its wallet from the CDP faucet
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time to wait for a response
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the hashes of the faucet's transactions, or an error
+    pub async fn request_faucet_funds(&self, timeout: Duration) -> Result<Vec<String>, String> {
+        let faucet_url = format!("{}/faucet", self.base_url);
+        let response = self
+            .http_client
+            .post(&faucet_url)
+            .timeout(self.timeout(timeout))
+            .send()
+            .await
+            .map_err(|e| format!("Faucet request failed: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("Faucet request returned error status: {}", status));
+        }
+
+        let body = response
+            .json::<Value>()
+            .await
+            .map_err(|e| format!("Failed to parse faucet response: {}", e))?;
+
+        let tx_hashes = body
+            .get("tx_hashes")
+            .cloned()
+            .ok_or_else(|| "Faucet response is missing `tx_hashes`".to_string())?;
+
+        serde_json::from_value(tx_hashes)
+            .map_err(|e| format!("Failed to parse faucet transaction hashes: {}", e))
+    }
+
+    /// Returns the timeout of a request, the endpoint's override if it has one
+    fn timeout(&self, timeout: Duration) -> Duration {
+        self.request_timeout.unwrap_or(timeout)
+    }
+}
+
+/// Builder for an [`AgentEndpoint`]
+///
+/// A shared client is reused, keeping its connection pool, and a request timeout override
+/// is applied to each request. Connect timeouts and proxies can't be changed on an existing
+/// client, so overriding them builds a dedicated client from the operator's configuration
+/// (see [`AgentEndpointBuilder::config`]), keeping the settings that aren't overridden.
+#[derive(Debug, Clone)]
+pub struct AgentEndpointBuilder {
+    base_url: String,
+    http_client: Option<reqwest::Client>,
+    config: Option<HttpConfig>,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    proxy: Option<ProxyConfig>,
+}
+
+impl AgentEndpointBuilder {
+    /// Creates a new builder for the given base URL
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            http_client: None,
+            config: None,
+            connect_timeout: None,
+            request_timeout: None,
+            proxy: None,
+        }
+    }
+
+    /// Uses a shared HTTP client for the endpoint
+    pub fn http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = Some(http_client);
+        self
+    }
+
+    /// Uses the operator's HTTP configuration for the settings that aren't overridden
+    pub fn config(mut self, config: HttpConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Overrides the connect timeout for this endpoint
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Overrides the request timeout for this endpoint
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Routes this endpoint's traffic through the given proxy
+    pub fn proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Builds the AgentEndpoint
+    pub fn build(self) -> Result<AgentEndpoint, String> {
+        let dedicated = self.connect_timeout.is_some() || self.proxy.is_some();
+        let http_client = match self.http_client {
+            Some(http_client) if !dedicated => http_client,
+            _ => {
+                let mut config = self.config.unwrap_or_default();
+                if let Some(timeout) = self.connect_timeout {
+                    config.connect_timeout = timeout;
+                }
+                if let Some(timeout) = self.request_timeout {
+                    config.request_timeout = timeout;
+                }
+                if let Some(proxy) = self.proxy {
+                    config.proxy = Some(proxy);
+                }
+                config.build_client()?
+            }
+        };
+
+        let mut endpoint = AgentEndpoint::with_client(self.base_url, http_client);
+        endpoint.request_timeout = self.request_timeout;
+        Ok(endpoint)
+    }
 }
 
 /// Enum representing the type of deployment (local Docker or TEE)
@@ -0,0 +1,157 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Prefix of the name of an agent's container and TEE app
+pub const CONTAINER_PREFIX: &str = "coinbase-agent-";
+
+/// Identifier of an agent
+///
+/// IDs name the agent's directory and container, so only ASCII letters, digits and `-` are
+/// accepted, which keeps them from leaving the agents' base directory or from injecting
+/// arguments. IDs the blueprint generates are UUIDs. Deserializing validates the ID, so job
+/// parameters holding one are rejected before any handler sees them.
+#[derive(
+    Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct AgentId(String);
+
+impl AgentId {
+    /// Longest accepted ID; generated IDs are 36-character UUIDs
+    pub const MAX_LEN: usize = 64;
+
+    /// Generates a random ID
+    pub fn generate() -> Self {
+        Self::from(Uuid::new_v4())
+    }
+
+    /// Validates an ID
+    pub fn parse(id: &str) -> Result<Self, String> {
+        if id.is_empty()
+            || id.len() > Self::MAX_LEN
+            || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return Err(format!(
+                "Agent ID must be 1 to {} letters, digits or '-', got {:?}",
+                Self::MAX_LEN,
+                id
+            ));
+        }
+        Ok(Self(id.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the name of the agent's container, also used for its TEE app
+    pub fn container_name(&self) -> String {
+        container_name(&self.0)
+    }
+
+    /// Returns the agent's directory under the agents' base directory
+    pub fn dir(&self, base_dir: impl AsRef<Path>) -> PathBuf {
+        base_dir.as_ref().join(&self.0)
+    }
+}
+
+/// Returns the name of the container of an agent whose ID is already known to be valid, e.g.
+/// because it was read back from the registry or the agents' directories
+pub fn container_name(agent_id: &str) -> String {
+    format!("{}{}", CONTAINER_PREFIX, agent_id)
+}
+
+impl FromStr for AgentId {
+    type Err = String;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        Self::parse(id)
+    }
+}
+
+impl TryFrom<String> for AgentId {
+    type Error = String;
+
+    fn try_from(id: String) -> Result<Self, Self::Error> {
+        Self::parse(&id)
+    }
+}
+
+impl From<Uuid> for AgentId {
+    fn from(id: Uuid) -> Self {
+        Self(id.to_string())
+    }
+}
+
+impl From<AgentId> for String {
+    fn from(id: AgentId) -> Self {
+        id.0
+    }
+}
+
+impl fmt::Display for AgentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Deref for AgentId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for AgentId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for AgentId {
+    fn as_ref(&self) -> &Path {
+        Path::new(&self.0)
+    }
+}
+
+impl PartialEq<str> for AgentId {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for AgentId {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+#[cfg(feature = "scale")]
+impl parity_scale_codec::Encode for AgentId {
+    fn size_hint(&self) -> usize {
+        self.0.size_hint()
+    }
+
+    fn encode_to<T: parity_scale_codec::Output + ?Sized>(&self, dest: &mut T) {
+        self.0.encode_to(dest)
+    }
+}
+
+#[cfg(feature = "scale")]
+impl parity_scale_codec::EncodeLike for AgentId {}
+
+#[cfg(feature = "scale")]
+impl parity_scale_codec::Decode for AgentId {
+    fn decode<I: parity_scale_codec::Input>(
+        input: &mut I,
+    ) -> Result<Self, parity_scale_codec::Error> {
+        let id = String::decode(input)?;
+        Self::parse(&id).map_err(|_| "Invalid agent ID".into())
+    }
+}
@@ -0,0 +1,74 @@
+use crate::codec;
+use crate::deploy_agent::get_required_ports;
+use crate::error::BlueprintError;
+use crate::helpers::{check_container_status, read_env_file, run_blocking};
+use crate::ownership::authorize;
+use crate::types::{AgentStatusParams, AgentStatusResult, AllowedAction};
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+
+/// Handles the agent_status job
+pub async fn handle_agent_status(
+    params_bytes: Vec<u8>,
+    context: &ServiceContext,
+) -> Result<Vec<u8>, BlueprintError> {
+    // Deserialize the parameters from bytes
+    let (params, encoding): (AgentStatusParams, _) =
+        codec::decode(&params_bytes).map_err(BlueprintError::Params)?;
+
+    // Define base directory from context or environment
+    let base_dir = match context.agents_base_dir() {
+        Some(dir) => dir,
+        None => "./agents".to_string(),
+    };
+
+    let agent_dir = params.agent_id.dir(&base_dir);
+    if !agent_dir.exists() {
+        return Err(BlueprintError::Params(format!(
+            "Unknown agent: {}",
+            params.agent_id
+        )));
+    }
+
+    // The agent's .env is the persisted record of its policy
+    let env_path = agent_dir.join(".env");
+    let agent_env = run_blocking(move || read_env_file(&env_path))
+        .await
+        .map_err(BlueprintError::Io)?;
+    authorize(context, &params.agent_id, &agent_env).map_err(BlueprintError::Unauthorized)?;
+    let allowed_actions = agent_env
+        .get("ALLOWED_ACTIONS")
+        .map(|value| AllowedAction::from_env_value(value))
+        .transpose()
+        .map_err(BlueprintError::Io)?
+        .flatten();
+
+    let (http_port, websocket_port) = match get_required_ports(&params.agent_id, context) {
+        Ok((http_port, websocket_port)) => (Some(http_port), websocket_port),
+        Err(_) => (None, None),
+    };
+
+    // Ask docker off the async executor
+    let container_name = params.agent_id.container_name();
+    let running = run_blocking({
+        let container_name = container_name.clone();
+        move || check_container_status(&container_name)
+    })
+    .await
+    .unwrap_or_else(|e| {
+        logging::warn!("Failed to check container {}: {}", container_name, e);
+        false
+    });
+
+    // Prepare the result
+    let result = AgentStatusResult {
+        agent_id: params.agent_id,
+        http_port,
+        websocket_port,
+        running,
+        allowed_actions,
+    };
+
+    // Serialize the result
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
@@ -0,0 +1,306 @@
+use crate::agent_id::container_name;
+use crate::config::env_or;
+use crate::heartbeat::Heartbeat;
+use crate::helpers::get_restart_count;
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What went wrong with an agent
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    /// Deploying the agent failed after the request was authorized
+    DeploymentFailed,
+    /// A deployed agent stopped passing its health checks
+    AgentUnhealthy,
+    /// The agent's container keeps restarting
+    ContainerRestarting,
+    /// The health monitor restarted the agent without it recovering, and gave up
+    AgentFailed,
+}
+
+impl fmt::Display for AlertKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlertKind::DeploymentFailed => write!(f, "deployment failed"),
+            AlertKind::AgentUnhealthy => write!(f, "agent unhealthy"),
+            AlertKind::ContainerRestarting => write!(f, "container restarting"),
+            AlertKind::AgentFailed => write!(f, "agent failed"),
+        }
+    }
+}
+
+/// Alert about one agent, sent to the operator's webhook
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub agent_id: String,
+    /// Job call during which the failure happened, if any
+    pub call_id: Option<u64>,
+    /// Unix timestamp (seconds) of the alert
+    pub timestamp: u64,
+    pub detail: String,
+}
+
+impl Alert {
+    /// Creates an alert for the current time
+    pub fn new(
+        kind: AlertKind,
+        agent_id: impl Into<String>,
+        call_id: Option<u64>,
+        detail: impl Into<String>,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self {
+            kind,
+            agent_id: agent_id.into(),
+            call_id,
+            timestamp,
+            detail: detail.into(),
+        }
+    }
+
+    /// One-line description of the alert
+    pub fn summary(&self) -> String {
+        format!("Agent {}: {}: {}", self.agent_id, self.kind, self.detail)
+    }
+}
+
+/// Shape of the JSON body posted to the webhook
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WebhookFormat {
+    /// The alert's fields, plus a `text` summary
+    #[default]
+    Generic,
+    /// Slack incoming webhook message
+    Slack,
+    /// Discord webhook message
+    Discord,
+    /// PagerDuty Events API v2 trigger
+    PagerDuty,
+}
+
+impl FromStr for WebhookFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "generic" => Ok(WebhookFormat::Generic),
+            "slack" => Ok(WebhookFormat::Slack),
+            "discord" => Ok(WebhookFormat::Discord),
+            "pagerduty" => Ok(WebhookFormat::PagerDuty),
+            other => Err(format!(
+                "Unknown webhook format {}, expected generic, slack, discord or pagerduty",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for WebhookFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WebhookFormat::Generic => write!(f, "generic"),
+            WebhookFormat::Slack => write!(f, "slack"),
+            WebhookFormat::Discord => write!(f, "discord"),
+            WebhookFormat::PagerDuty => write!(f, "pagerduty"),
+        }
+    }
+}
+
+/// Webhook the operator is alerted through when agents fail
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AlertConfig {
+    pub webhook_url: String,
+    pub format: WebhookFormat,
+    /// Integration key of the PagerDuty service, required for the PagerDuty format
+    pub pagerduty_routing_key: Option<String>,
+    /// Restarts after which a container is reported as restarting repeatedly
+    pub restart_threshold: u32,
+}
+
+impl AlertConfig {
+    /// Loads the webhook from `AGENT_ALERT_WEBHOOK_URL`, `AGENT_ALERT_WEBHOOK_FORMAT`,
+    /// `AGENT_ALERT_PAGERDUTY_ROUTING_KEY` and `AGENT_ALERT_RESTART_THRESHOLD`, if a URL is set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(webhook_url) = std::env::var("AGENT_ALERT_WEBHOOK_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty())
+        else {
+            return Ok(None);
+        };
+        let format = env_or("AGENT_ALERT_WEBHOOK_FORMAT", WebhookFormat::Generic)?;
+        let pagerduty_routing_key = std::env::var("AGENT_ALERT_PAGERDUTY_ROUTING_KEY").ok();
+        if format == WebhookFormat::PagerDuty && pagerduty_routing_key.is_none() {
+            return Err(
+                "AGENT_ALERT_PAGERDUTY_ROUTING_KEY is required for PagerDuty alerts".to_string(),
+            );
+        }
+        let restart_threshold: u32 = env_or("AGENT_ALERT_RESTART_THRESHOLD", 3)?;
+        Ok(Some(Self {
+            webhook_url: webhook_url.trim().to_string(),
+            format,
+            pagerduty_routing_key,
+            restart_threshold: restart_threshold.max(1),
+        }))
+    }
+
+    /// Renders the body posted for an alert
+    pub fn payload(&self, alert: &Alert) -> Value {
+        match self.format {
+            WebhookFormat::Generic => {
+                let mut payload = json!(alert);
+                payload["text"] = Value::String(alert.summary());
+                payload
+            }
+            WebhookFormat::Slack => json!({ "text": alert.summary() }),
+            WebhookFormat::Discord => json!({ "content": alert.summary() }),
+            WebhookFormat::PagerDuty => json!({
+                "routing_key": self.pagerduty_routing_key,
+                "event_action": "trigger",
+                // Repeated alerts about the same problem update a single incident
+                "dedup_key": format!("{}/{:?}", container_name(&alert.agent_id), alert.kind),
+                "payload": {
+                    "summary": alert.summary(),
+                    "source": container_name(&alert.agent_id),
+                    "severity": match alert.kind {
+                        AlertKind::ContainerRestarting => "warning",
+                        AlertKind::AgentFailed => "critical",
+                        AlertKind::DeploymentFailed | AlertKind::AgentUnhealthy => "error",
+                    },
+                    "custom_details": alert,
+                },
+            }),
+        }
+    }
+}
+
+/// Sends an alert to the operator's webhook, if one is configured
+///
+/// Alerting is best effort: failures are logged and never fail the calling job.
+pub async fn send_alert(context: &ServiceContext, alert: Alert) {
+    let Some(config) = &context.alerts else {
+        return;
+    };
+    let client = context.http_client.clone().unwrap_or_default();
+    let result = client
+        .post(&config.webhook_url)
+        .json(&config.payload(&alert))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    match result {
+        Ok(_) => logging::info!(
+            agent_id = %alert.agent_id,
+            alert = %alert.kind,
+            "Sent alert to webhook"
+        ),
+        Err(e) => logging::warn!(
+            agent_id = %alert.agent_id,
+            alert = %alert.kind,
+            error = %e,
+            "Failed to send alert to webhook"
+        ),
+    }
+}
+
+/// Agent states seen by earlier heartbeats, so that each problem is alerted once
+#[derive(Clone, Debug, Default)]
+pub struct AlertTracker {
+    unhealthy: HashSet<String>,
+    /// Container restart count at the last alert (or first observation) per agent
+    restarts: HashMap<String, u32>,
+}
+
+impl AlertTracker {
+    /// Records the agents that are now unhealthy, returning those that were healthy before
+    pub fn newly_unhealthy(&mut self, unhealthy: &[String]) -> Vec<String> {
+        let newly: Vec<String> = unhealthy
+            .iter()
+            .filter(|agent_id| !self.unhealthy.contains(*agent_id))
+            .cloned()
+            .collect();
+        self.unhealthy = unhealthy.iter().cloned().collect();
+        newly
+    }
+
+    /// Records an agent's restart count, returning how often it restarted since the last
+    /// alert if that reached `threshold`
+    pub fn restarts_since_alert(
+        &mut self,
+        agent_id: &str,
+        restart_count: u32,
+        threshold: u32,
+    ) -> Option<u32> {
+        let baseline = self
+            .restarts
+            .entry(agent_id.to_string())
+            .or_insert(restart_count);
+        // The count starts over when the container is recreated
+        if restart_count < *baseline {
+            *baseline = restart_count;
+        }
+        let restarts = restart_count - *baseline;
+        if restarts >= threshold {
+            *baseline = restart_count;
+            Some(restarts)
+        } else {
+            None
+        }
+    }
+}
+
+/// Alerts on agents that became unhealthy or keep restarting since the last heartbeat
+pub async fn check_heartbeat(
+    context: &ServiceContext,
+    heartbeat: &Heartbeat,
+    tracker: &mut AlertTracker,
+) {
+    let Some(config) = &context.alerts else {
+        return;
+    };
+
+    for agent_id in tracker.newly_unhealthy(&heartbeat.unhealthy_agents) {
+        let alert = Alert::new(
+            AlertKind::AgentUnhealthy,
+            agent_id,
+            None,
+            "Agent failed its health check",
+        );
+        send_alert(context, alert).await;
+    }
+
+    // Only local agents have a container here; TEE agents are skipped below
+    let agent_ids = match &context.agent_registry {
+        Some(registry) => registry.agent_ids(),
+        None => return,
+    };
+    for agent_id in agent_ids {
+        let Ok(restart_count) = get_restart_count(&container_name(&agent_id)) else {
+            continue;
+        };
+        if let Some(restarts) =
+            tracker.restarts_since_alert(&agent_id, restart_count, config.restart_threshold)
+        {
+            let alert = Alert::new(
+                AlertKind::ContainerRestarting,
+                agent_id,
+                None,
+                format!(
+                    "Container restarted {} times ({} in total)",
+                    restarts, restart_count
+                ),
+            );
+            send_alert(context, alert).await;
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use crate::codec;
+use crate::error::BlueprintError;
+use crate::helpers::write_private_file;
+use crate::secrets::SealingKey;
+use crate::types::{SecretAuditParams, SecretAuditResult};
+use crate::ServiceContext;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory under the agents base directory holding the secret audit log
+const AUDIT_DIR: &str = ".audit";
+
+/// File of the secret audit log, one record per line
+const AUDIT_FILE: &str = "secrets.jsonl";
+
+/// File of the sealed checkpoint of the log's last record
+const CHECKPOINT_FILE: &str = "secrets.head";
+
+/// Bytes read at a time from the end of the log to find its last record
+const TAIL_CHUNK: u64 = 4096;
+
+/// Previous hash of the first record in the log
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Serializes appends so that every record links to the one before it
+static AUDIT_LOCK: Mutex<()> = Mutex::new(());
+
+/// What the blueprint did with secret material
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub enum SecretAccess {
+    /// Secrets were sealed into the agent's directory
+    Write,
+    /// Sealed secrets were unsealed
+    Read,
+    /// Secrets were handed to the agent's container or TEE
+    Inject,
+}
+
+/// Audit record of one access to an agent's secrets
+///
+/// Records hold the names of the secrets, never their values. Each record's `hash` covers
+/// the record and the previous record's hash, so editing or removing a record breaks the
+/// chain after it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct AuditRecord {
+    /// Position of the record in the log, starting at 0
+    pub seq: u64,
+    /// Unix timestamp (seconds) of the access
+    pub timestamp: u64,
+    pub call_id: Option<u64>,
+    /// Account that called the job, if it was recorded
+    pub caller: Option<String>,
+    pub agent_id: String,
+    pub access: SecretAccess,
+    /// Names of the secrets, e.g. `OPENAI_API_KEY` or `encrypted_env`
+    pub secrets: Vec<String>,
+    /// Where the secrets went or came from, e.g. `sealed` or `docker-compose`
+    pub target: String,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditRecord {
+    /// Hashes the record (without its own hash) chained to the previous record's hash
+    fn compute_hash(&self) -> Result<String, String> {
+        let mut unhashed = self.clone();
+        unhashed.hash = String::new();
+        let bytes = serde_json::to_vec(&unhashed)
+            .map_err(|e| format!("Failed to serialize audit record: {}", e))?;
+
+        let mut hasher = Keccak256::new();
+        hasher.update(self.prev_hash.as_bytes());
+        hasher.update(&bytes);
+        Ok(hex::encode(hasher.finalize()))
+    }
+}
+
+/// Last record of the log, sealed with the operator's key after every append
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    seq: u64,
+    hash: String,
+}
+
+fn audit_dir(context: &ServiceContext) -> PathBuf {
+    let base_dir = match context.agents_base_dir() {
+        Some(dir) => dir,
+        None => "./agents".to_string(),
+    };
+    PathBuf::from(base_dir).join(AUDIT_DIR)
+}
+
+fn audit_path(context: &ServiceContext) -> PathBuf {
+    audit_dir(context).join(AUDIT_FILE)
+}
+
+/// Appends a record of secret access to the audit log
+///
+/// Unlike the journal, a failure to audit fails the operation that touched the secrets.
+pub fn record(
+    context: &ServiceContext,
+    agent_id: &str,
+    access: SecretAccess,
+    secrets: impl IntoIterator<Item = impl Into<String>>,
+    target: &str,
+) -> Result<(), String> {
+    let mut secrets: Vec<String> = secrets.into_iter().map(Into::into).collect();
+    if secrets.is_empty() {
+        return Ok(());
+    }
+    secrets.sort();
+
+    let _guard = AUDIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = audit_path(context);
+    let last = read_last_record(&path)?;
+
+    let mut entry = AuditRecord {
+        seq: last.as_ref().map_or(0, |record| record.seq + 1),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        call_id: context.call_id,
+        caller: context.caller(),
+        agent_id: agent_id.to_string(),
+        access,
+        secrets,
+        target: target.to_string(),
+        prev_hash: last.map_or_else(|| GENESIS_HASH.to_string(), |record| record.hash),
+        hash: String::new(),
+    };
+    entry.hash = entry.compute_hash()?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create audit log: {}", e))?;
+    }
+    let mut line = serde_json::to_string(&entry)
+        .map_err(|e| format!("Failed to serialize audit record: {}", e))?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| format!("Failed to write audit log: {}", e))?;
+
+    match &context.sealing_key {
+        Some(key) => write_checkpoint(context, key, &entry),
+        None => Ok(()),
+    }
+}
+
+/// Seals the log's new last record, so records removed from its end are noticed
+fn write_checkpoint(
+    context: &ServiceContext,
+    key: &SealingKey,
+    last: &AuditRecord,
+) -> Result<(), String> {
+    let checkpoint = serde_json::to_vec(&Checkpoint {
+        seq: last.seq,
+        hash: last.hash.clone(),
+    })
+    .map_err(|e| format!("Failed to serialize audit checkpoint: {}", e))?;
+    let sealed = key.seal(&checkpoint)?;
+    write_private_file(
+        &audit_dir(context).join(CHECKPOINT_FILE),
+        hex::encode(sealed),
+    )
+}
+
+/// Reads the log's last record, without reading the records before it
+fn read_last_record(path: &Path) -> Result<Option<AuditRecord>, String> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read audit log: {}", e)),
+    };
+    let mut start = file
+        .metadata()
+        .map_err(|e| format!("Failed to read audit log: {}", e))?
+        .len();
+
+    // Read backwards until the last line is preceded by a newline or the start of the log
+    let mut tail = Vec::new();
+    while start > 0 {
+        let chunk = TAIL_CHUNK.min(start);
+        start -= chunk;
+        let mut buf = vec![0; chunk as usize];
+        file.seek(SeekFrom::Start(start))
+            .and_then(|_| file.read_exact(&mut buf))
+            .map_err(|e| format!("Failed to read audit log: {}", e))?;
+        buf.extend_from_slice(&tail);
+        tail = buf;
+
+        let content = tail.trim_ascii_end();
+        if content.contains(&b'\n') {
+            break;
+        }
+    }
+
+    let content = tail.trim_ascii_end();
+    let line = match content.iter().rposition(|byte| *byte == b'\n') {
+        Some(newline) => &content[newline + 1..],
+        None => content,
+    };
+    if line.is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_slice(line)
+        .map(Some)
+        .map_err(|e| format!("Invalid audit record: {}", e))
+}
+
+fn read_records(path: &PathBuf) -> Result<Vec<AuditRecord>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read audit log: {}", e)),
+    };
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(|e| format!("Invalid audit record: {}", e)))
+        .collect()
+}
+
+/// Returns every record in the audit log, oldest first
+pub fn records(context: &ServiceContext) -> Result<Vec<AuditRecord>, String> {
+    read_records(&audit_path(context))
+}
+
+/// Checks that the records form an unbroken hash chain from the start of the log
+pub fn verify_chain(records: &[AuditRecord]) -> Result<(), String> {
+    let mut prev_hash = GENESIS_HASH.to_string();
+    for (seq, record) in records.iter().enumerate() {
+        if record.seq != seq as u64 || record.prev_hash != prev_hash {
+            return Err(format!("Audit log is broken at record {}", seq));
+        }
+        if record.compute_hash()? != record.hash {
+            return Err(format!("Audit record {} was modified", seq));
+        }
+        prev_hash = record.hash.clone();
+    }
+    Ok(())
+}
+
+/// Checks that the log still ends at the last record sealed by the operator
+///
+/// The hash chain alone doesn't show records removed from the end of the log, or a log
+/// rewritten from its start; the checkpoint can't be forged without the operator's key.
+/// Logs are only checkpointed when the operator has a sealing key.
+pub fn verify_checkpoint(context: &ServiceContext, records: &[AuditRecord]) -> Result<(), String> {
+    let Some(key) = &context.sealing_key else {
+        return Ok(());
+    };
+    let path = audit_dir(context).join(CHECKPOINT_FILE);
+    let encoded = match fs::read_to_string(&path) {
+        Ok(encoded) => encoded,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && records.is_empty() => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err("Audit log checkpoint is missing".to_string())
+        }
+        Err(e) => return Err(format!("Failed to read audit checkpoint: {}", e)),
+    };
+    let checkpoint: Checkpoint = hex::decode(encoded.trim())
+        .map_err(|e| e.to_string())
+        .and_then(|sealed| key.unseal(&sealed))
+        .and_then(|checkpoint| serde_json::from_slice(&checkpoint).map_err(|e| e.to_string()))
+        .map_err(|e| format!("Audit log checkpoint is invalid: {}", e))?;
+    match records.last() {
+        Some(last) if last.seq == checkpoint.seq && last.hash == checkpoint.hash => Ok(()),
+        _ => Err(format!(
+            "Audit log does not end at its checkpoint, record {}",
+            checkpoint.seq
+        )),
+    }
+}
+
+/// Handles the secret_audit_log job
+///
+/// Only the service owner may read the log, so nobody can until one is configured.
+pub async fn handle_secret_audit_log(
+    params_bytes: Vec<u8>,
+    context: &ServiceContext,
+) -> Result<Vec<u8>, BlueprintError> {
+    // Deserialize the parameters from bytes
+    let (params, encoding): (SecretAuditParams, _) =
+        codec::decode(&params_bytes).map_err(BlueprintError::Params)?;
+
+    let is_owner = context
+        .service_owner
+        .as_ref()
+        .is_some_and(|owner| context.caller().as_ref() == Some(owner));
+    if !is_owner {
+        return Err(BlueprintError::Unauthorized(
+            "Only the service owner may read the secret audit log".to_string(),
+        ));
+    }
+
+    let records = records(context).map_err(BlueprintError::Io)?;
+    let chain_error = verify_chain(&records)
+        .and_then(|()| verify_checkpoint(context, &records))
+        .err();
+    let result = SecretAuditResult {
+        records: records
+            .into_iter()
+            .filter(|record| {
+                params
+                    .agent_id
+                    .as_ref()
+                    .map_or(true, |agent_id| agent_id == record.agent_id.as_str())
+            })
+            .filter(|record| params.since.map_or(true, |since| record.timestamp >= since))
+            .collect(),
+        chain_valid: chain_error.is_none(),
+        chain_error,
+    };
+
+    // Serialize the result
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
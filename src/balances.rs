@@ -0,0 +1,286 @@
+use crate::codec;
+use crate::deploy_agent::get_required_ports;
+use crate::error::BlueprintError;
+use crate::helpers::{read_env_file, run_blocking};
+use crate::ownership::authorize;
+use crate::types::{AgentBalancesResult, GetAgentBalancesParams, TokenBalance};
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Most ERC-20 tokens one `get_agent_balances` call may ask for
+pub const MAX_BALANCE_TOKENS: usize = 20;
+
+/// Public RPC endpoints of the networks agents run on, used unless the operator sets others
+const DEFAULT_RPC_URLS: &[(&str, &str)] = &[
+    ("base-sepolia", "https://sepolia.base.org"),
+    ("base-mainnet", "https://mainnet.base.org"),
+];
+
+/// Selector of ERC-20 `balanceOf(address)`
+const BALANCE_OF_SELECTOR: &str = "70a08231";
+
+/// JSON-RPC endpoints balances are read from, by network ID
+#[derive(Clone, Debug)]
+pub struct RpcEndpoints(BTreeMap<String, String>);
+
+impl Default for RpcEndpoints {
+    fn default() -> Self {
+        Self(
+            DEFAULT_RPC_URLS
+                .iter()
+                .map(|(network, url)| (network.to_string(), url.to_string()))
+                .collect(),
+        )
+    }
+}
+
+impl RpcEndpoints {
+    /// Creates endpoints from `(network ID, URL)` pairs, without the defaults
+    pub fn new(urls: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self(urls.into_iter().collect())
+    }
+
+    /// Loads the endpoints from `AGENT_RPC_URLS`, e.g.
+    /// `ethereum-mainnet=https://eth.example.com,base-mainnet=https://base.example.com`,
+    /// on top of the public endpoints of Base
+    pub fn from_env() -> Result<Self, String> {
+        let mut endpoints = Self::default();
+        let Ok(urls) = std::env::var("AGENT_RPC_URLS") else {
+            return Ok(endpoints);
+        };
+        for entry in urls.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (network, url) = entry.split_once('=').ok_or_else(|| {
+                format!(
+                    "Invalid AGENT_RPC_URLS entry {}: expected network=url",
+                    entry
+                )
+            })?;
+            let url = url.trim();
+            let parsed = reqwest::Url::parse(url)
+                .map_err(|e| format!("Invalid RPC URL for {}: {}", network.trim(), e))?;
+            if !matches!(parsed.scheme(), "http" | "https") {
+                return Err(format!(
+                    "Invalid RPC URL for {}: expected http or https",
+                    network.trim()
+                ));
+            }
+            endpoints
+                .0
+                .insert(network.trim().to_string(), url.to_string());
+        }
+        Ok(endpoints)
+    }
+
+    /// Returns the endpoint of a network, if there is one
+    pub fn get(&self, network_id: &str) -> Option<&str> {
+        self.0.get(network_id).map(String::as_str)
+    }
+}
+
+/// Checks that a token is an ERC-20 contract address, `0x` followed by 40 hex digits
+fn validate_token(token: &str) -> Result<(), String> {
+    let hex = token.strip_prefix("0x").unwrap_or_default();
+    if hex.len() != 40 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid token address: {}", token));
+    }
+    Ok(())
+}
+
+/// Converts a quantity returned by a JSON-RPC call, e.g. `0x1bc16d674ec80000`, into decimal
+fn quantity_to_decimal(quantity: &str) -> Result<String, String> {
+    let hex = quantity
+        .strip_prefix("0x")
+        .ok_or_else(|| format!("Invalid quantity: {}", quantity))?;
+    // eth_call returns a bare `0x` for addresses without code, which hold no token
+    if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid quantity: {}", quantity));
+    }
+
+    // Little-endian decimal digits, multiplied by 16 for each hex digit
+    let mut digits: Vec<u8> = vec![0];
+    for nibble in hex.chars().filter_map(|c| c.to_digit(16)) {
+        let mut carry = nibble;
+        for digit in digits.iter_mut() {
+            let value = u32::from(*digit) * 16 + carry;
+            *digit = (value % 10) as u8;
+            carry = value / 10;
+        }
+        while carry > 0 {
+            digits.push((carry % 10) as u8);
+            carry /= 10;
+        }
+    }
+    while digits.len() > 1 && digits.last() == Some(&0) {
+        digits.pop();
+    }
+    Ok(digits.iter().rev().map(|d| char::from(b'0' + d)).collect())
+}
+
+/// Sends a JSON-RPC request and returns its result
+async fn rpc_call(
+    client: &reqwest::Client,
+    url: &str,
+    method: &str,
+    params: Value,
+    timeout: Duration,
+) -> Result<Value, String> {
+    let response = client
+        .post(url)
+        .json(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|e| format!("{} request failed: {}", method, e))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!(
+            "{} request returned error status: {}",
+            method, status
+        ));
+    }
+    let mut body = response
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("Failed to parse {} response: {}", method, e))?;
+    if let Some(error) = body.get("error") {
+        return Err(format!("{} failed: {}", method, error));
+    }
+    body.get_mut("result")
+        .map(Value::take)
+        .ok_or_else(|| format!("{} response is missing `result`", method))
+}
+
+/// Reads the native and ERC-20 balances of an address through a JSON-RPC endpoint
+async fn read_balances(
+    client: &reqwest::Client,
+    url: &str,
+    address: &str,
+    tokens: &[String],
+    timeout: Duration,
+) -> Result<(String, Vec<TokenBalance>), String> {
+    let quantity = |result: Value| match result {
+        Value::String(quantity) => quantity_to_decimal(&quantity),
+        other => Err(format!("Invalid quantity: {}", other)),
+    };
+    let native = quantity(
+        rpc_call(
+            client,
+            url,
+            "eth_getBalance",
+            json!([address, "latest"]),
+            timeout,
+        )
+        .await?,
+    )?;
+
+    let call_data = format!(
+        "0x{}{:0>64}",
+        BALANCE_OF_SELECTOR,
+        address.trim_start_matches("0x").to_ascii_lowercase()
+    );
+    let mut balances = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        let result = rpc_call(
+            client,
+            url,
+            "eth_call",
+            json!([{ "to": token, "data": call_data }, "latest"]),
+            timeout,
+        )
+        .await?;
+        let balance =
+            quantity(result).map_err(|e| format!("Token {} returned no balance: {}", token, e))?;
+        balances.push(TokenBalance {
+            token: token.clone(),
+            balance,
+        });
+    }
+    Ok((native, balances))
+}
+
+/// Handles the get_agent_balances job
+///
+/// Reads the balances of a deployed agent's wallet through the operator's RPC endpoint for
+/// its network. Without one, only the native balance the agent reports itself is returned.
+/// Only the agent's owner and the service owner may read them.
+pub async fn handle_get_agent_balances(
+    params_bytes: Vec<u8>,
+    context: &ServiceContext,
+) -> Result<Vec<u8>, BlueprintError> {
+    let (params, encoding): (GetAgentBalancesParams, _) =
+        codec::decode(&params_bytes).map_err(BlueprintError::Params)?;
+    if params.tokens.len() > MAX_BALANCE_TOKENS {
+        return Err(BlueprintError::Params(format!(
+            "At most {} tokens may be queried at once",
+            MAX_BALANCE_TOKENS
+        )));
+    }
+    for token in &params.tokens {
+        validate_token(token).map_err(BlueprintError::Params)?;
+    }
+
+    let base_dir = context
+        .agents_base_dir()
+        .unwrap_or_else(|| "./agents".to_string());
+    let agent_dir = params.agent_id.dir(&base_dir);
+    if !agent_dir.exists() {
+        return Err(BlueprintError::Params(format!(
+            "Unknown agent: {}",
+            params.agent_id
+        )));
+    }
+    let env_path = agent_dir.join(".env");
+    let agent_env = run_blocking(move || read_env_file(&env_path))
+        .await
+        .map_err(BlueprintError::Io)?;
+    authorize(context, &params.agent_id, &agent_env).map_err(BlueprintError::Unauthorized)?;
+
+    // The agent must have been deployed locally for us to learn its wallet
+    let (http_port, _) =
+        get_required_ports(&params.agent_id, context).map_err(BlueprintError::Params)?;
+    let timeout = context.http_config().request_timeout;
+    let wallet = context
+        .agent_endpoint(format!("http://localhost:{}", http_port))
+        .map_err(BlueprintError::Internal)?
+        .get_wallet(timeout)
+        .await
+        .map_err(BlueprintError::Health)?;
+
+    let endpoints = context.rpc_endpoints.clone().unwrap_or_default();
+    let (native_balance, tokens) = match endpoints.get(&wallet.network_id) {
+        Some(url) => {
+            let client = context.http_client.clone().unwrap_or_default();
+            logging::info!(agent_id = %params.agent_id, network_id = %wallet.network_id, "Reading balances");
+            read_balances(&client, url, &wallet.address, &params.tokens, timeout)
+                .await
+                .map_err(BlueprintError::Health)?
+        }
+        None if params.tokens.is_empty() => {
+            let balance = wallet.balance.clone().ok_or_else(|| {
+                BlueprintError::Health(format!(
+                    "Agent {} doesn't report its balance, and no RPC endpoint is configured for {}",
+                    params.agent_id, wallet.network_id
+                ))
+            })?;
+            (balance, Vec::new())
+        }
+        None => {
+            return Err(BlueprintError::Params(format!(
+                "No RPC endpoint is configured for {}, so token balances can't be read",
+                wallet.network_id
+            )))
+        }
+    };
+
+    let result = AgentBalancesResult {
+        agent_id: params.agent_id,
+        wallet_address: wallet.address,
+        network_id: wallet.network_id,
+        native_balance,
+        tokens,
+    };
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
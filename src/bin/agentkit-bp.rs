@@ -0,0 +1,16 @@
+//! Operator CLI for a local blueprint node, see `agentkit-bp --help`
+
+use clap::Parser;
+use coinbase_agent_kit_blueprint::cli::Cli;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    match Cli::parse().run().await {
+        Ok(output) if output.is_empty() => {}
+        Ok(output) => println!("{}", output.trim_end()),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,369 @@
+use crate::types::{
+    default_action_providers, ActionProvider, AgentConfig, AgentImage, AgentMode, AllowedAction,
+    ApiKeyConfig, AutonomousSchedule, AzureOpenAIConfig, BudgetConfig, CreateAgentParams,
+    DeployAgentParams, DeploymentConfig, DeploymentTarget, EgressPolicy, LlmProvider,
+    LocalModelConfig, Secret, TeeConfig, TeeEnvironment, WalletConfig, WebSocketPort,
+};
+use std::path::PathBuf;
+
+impl CreateAgentParams {
+    /// Starts building `create_agent` parameters, e.g.
+    /// `CreateAgentParams::builder().name("trader").chat().model("gpt-4o-mini").build()`
+    pub fn builder() -> CreateAgentParamsBuilder {
+        CreateAgentParamsBuilder::default()
+    }
+}
+
+impl DeployAgentParams {
+    /// Starts building `deploy_agent` parameters for an agent
+    pub fn builder(agent_id: impl Into<String>) -> DeployAgentParamsBuilder {
+        DeployAgentParamsBuilder {
+            agent_id: agent_id.into(),
+            api_key_config: None,
+            tee: None,
+            callback_url: None,
+        }
+    }
+}
+
+/// Builder of [`CreateAgentParams`], validating them when built
+///
+/// The name, the mode and the model are required. Everything else has the defaults of the
+/// job's parameters: OpenAI, the default network, the wallet action provider and no budget.
+#[derive(Clone, Debug, Default)]
+pub struct CreateAgentParamsBuilder {
+    name: Option<String>,
+    mode: Option<AgentMode>,
+    model: Option<String>,
+    llm_provider: LlmProvider,
+    azure_openai: Option<AzureOpenAIConfig>,
+    local_model: Option<LocalModelConfig>,
+    system_prompt: Option<String>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    network_id: Option<String>,
+    schedule: Option<AutonomousSchedule>,
+    wallet: WalletConfig,
+    action_providers: Option<Vec<ActionProvider>>,
+    budget: Option<BudgetConfig>,
+    allowed_actions: Option<Vec<AllowedAction>>,
+    deployment_config: DeploymentConfig,
+    template_id: Option<String>,
+    api_key_config: ApiKeyConfig,
+    callback_url: Option<String>,
+}
+
+impl CreateAgentParamsBuilder {
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn mode(mut self, mode: AgentMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Serves the agent as a chat bot over HTTP
+    pub fn chat(self) -> Self {
+        self.mode(AgentMode::Chat)
+    }
+
+    /// Lets the agent act on its own on the given schedule
+    pub fn autonomous(mut self, schedule: AutonomousSchedule) -> Self {
+        self.schedule = Some(schedule);
+        self.mode(AgentMode::Autonomous)
+    }
+
+    /// Serves the agent as a Telegram bot
+    pub fn telegram(mut self, bot_token: impl Into<String>) -> Self {
+        self.api_key_config.telegram_bot_token = Some(Secret::new(bot_token.into()));
+        self.mode(AgentMode::Telegram)
+    }
+
+    /// Serves the agent as a Discord bot
+    pub fn discord(mut self, bot_token: impl Into<String>) -> Self {
+        self.api_key_config.discord_bot_token = Some(Secret::new(bot_token.into()));
+        self.mode(AgentMode::Discord)
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Uses OpenAI with the given key
+    pub fn openai_api_key(mut self, key: impl Into<String>) -> Self {
+        self.llm_provider = LlmProvider::OpenAI;
+        self.api_key_config.openai_api_key = Some(Secret::new(key.into()));
+        self
+    }
+
+    /// Uses Anthropic with the given key
+    pub fn anthropic_api_key(mut self, key: impl Into<String>) -> Self {
+        self.llm_provider = LlmProvider::Anthropic;
+        self.api_key_config.anthropic_api_key = Some(Secret::new(key.into()));
+        self
+    }
+
+    /// Uses an Azure OpenAI deployment with the given key
+    pub fn azure_openai(mut self, config: AzureOpenAIConfig, key: impl Into<String>) -> Self {
+        self.llm_provider = LlmProvider::AzureOpenAI;
+        self.azure_openai = Some(config);
+        self.api_key_config.azure_openai_api_key = Some(Secret::new(key.into()));
+        self
+    }
+
+    /// Uses a self-hosted model
+    pub fn local_model(mut self, config: LocalModelConfig) -> Self {
+        self.llm_provider = LlmProvider::Local;
+        self.local_model = Some(config);
+        self
+    }
+
+    pub fn system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Sets the chain of the agent's wallet, one of [`AgentConfig::SUPPORTED_NETWORKS`]
+    pub fn network(mut self, network_id: impl Into<String>) -> Self {
+        self.network_id = Some(network_id.into());
+        self
+    }
+
+    /// Sets the kind of wallet the agent holds
+    pub fn wallet(mut self, wallet: WalletConfig) -> Self {
+        self.wallet = wallet;
+        self
+    }
+
+    /// Gives the agent a smart wallet owned by the private key `secret` references, e.g.
+    /// `vault://kv/agents/owner`
+    pub fn smart_wallet(
+        mut self,
+        secret: impl Into<String>,
+        paymaster_url: Option<String>,
+    ) -> Self {
+        self.api_key_config.cdp_wallet_secret = Some(Secret::new(secret.into()));
+        self.wallet(WalletConfig::SmartWallet { paymaster_url })
+    }
+
+    /// Gives the agent the account of the private key `reference` points to instead of a CDP
+    /// wallet
+    pub fn private_key_wallet(mut self, reference: impl Into<String>) -> Self {
+        self.api_key_config.wallet_private_key = Some(Secret::new(reference.into()));
+        self.wallet(WalletConfig::PrivateKey)
+    }
+
+    /// Enables an action provider; only the wallet provider is enabled if none is
+    pub fn action_provider(mut self, provider: ActionProvider) -> Self {
+        self.action_providers
+            .get_or_insert_with(Vec::new)
+            .push(provider);
+        self
+    }
+
+    pub fn budget(mut self, budget: BudgetConfig) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Restricts the agent to these on-chain actions
+    pub fn allowed_actions(mut self, actions: impl IntoIterator<Item = AllowedAction>) -> Self {
+        self.allowed_actions = Some(actions.into_iter().collect());
+        self
+    }
+
+    pub fn target(mut self, target: DeploymentTarget) -> Self {
+        self.deployment_config.target = target;
+        self
+    }
+
+    /// Runs the agent in a default-sized TEE rather than in a local container
+    pub fn tee(self) -> Self {
+        self.target(DeploymentTarget::Tee(TeeConfig::default()))
+    }
+
+    /// Runs the agent in a local container on the given port
+    pub fn http_port(mut self, port: u16) -> Self {
+        match &mut self.deployment_config.target {
+            DeploymentTarget::Local { http_port, .. } => *http_port = Some(port),
+            target => *target = DeploymentTarget::local(Some(port)),
+        }
+        self
+    }
+
+    /// Runs the agent in a local container serving WebSockets on the given port, if any
+    pub fn websocket_port(mut self, port: WebSocketPort) -> Self {
+        match &mut self.deployment_config.target {
+            DeploymentTarget::Local { websocket_port, .. } => *websocket_port = port,
+            target => {
+                *target = DeploymentTarget::Local {
+                    http_port: None,
+                    websocket_port: port,
+                }
+            }
+        }
+        self
+    }
+
+    pub fn docker_compose_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.deployment_config.docker_compose_path = Some(path.into());
+        self
+    }
+
+    /// Creates the agent from one of the operator's templates, a pinned git repository
+    /// (`git+https://...#<ref>`) or a signed OCI artifact (`oci://...`), rather than the
+    /// starter one
+    pub fn template(mut self, template_id: impl Into<String>) -> Self {
+        self.template_id = Some(template_id.into());
+        self
+    }
+
+    /// Runs a prebuilt image rather than the template's, pinned by digest when created
+    pub fn image(mut self, reference: impl Into<String>) -> Self {
+        self.deployment_config.image = AgentImage::Image {
+            reference: reference.into(),
+        };
+        self
+    }
+
+    /// Builds the agent's image from another Dockerfile of its template
+    pub fn dockerfile(mut self, path: impl Into<String>) -> Self {
+        self.deployment_config.image = AgentImage::Dockerfile { path: path.into() };
+        self
+    }
+
+    /// Gives the agent an extra variable on top of the template's
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.deployment_config.env.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sizes the agent with one of its template's presets, e.g. `medium`
+    pub fn size(mut self, size: impl Into<String>) -> Self {
+        self.deployment_config.size = Some(size.into());
+        self
+    }
+
+    /// Limits the CPUs and memory of a local agent's container
+    pub fn resources(mut self, resources: TeeConfig) -> Self {
+        self.deployment_config.resources = Some(resources);
+        self
+    }
+
+    /// Restricts the hosts the agent may connect to
+    pub fn egress(mut self, policy: EgressPolicy) -> Self {
+        self.deployment_config.egress = Some(policy);
+        self
+    }
+
+    /// Sets the Coinbase Developer Platform key the agent's wallet is managed with
+    pub fn cdp_api_key(mut self, name: impl Into<String>, private_key: impl Into<String>) -> Self {
+        self.api_key_config.cdp_api_key_name = Some(Secret::new(name.into()));
+        self.api_key_config.cdp_api_key_private_key = Some(Secret::new(private_key.into()));
+        self
+    }
+
+    pub fn callback_url(mut self, url: impl Into<String>) -> Self {
+        self.callback_url = Some(url.into());
+        self
+    }
+
+    /// Builds the parameters, failing on missing or invalid fields
+    pub fn build(self) -> Result<CreateAgentParams, String> {
+        let params = CreateAgentParams {
+            name: self.name.ok_or("A name is required")?,
+            agent_config: AgentConfig {
+                mode: self.mode.ok_or("An agent mode is required")?,
+                model: self.model.ok_or("A model is required")?,
+                llm_provider: self.llm_provider,
+                azure_openai: self.azure_openai,
+                local_model: self.local_model,
+                system_prompt: self.system_prompt,
+                temperature: self.temperature,
+                max_tokens: self.max_tokens,
+                network_id: self
+                    .network_id
+                    .unwrap_or_else(|| AgentConfig::DEFAULT_NETWORK_ID.to_string()),
+                schedule: self.schedule,
+                wallet: self.wallet,
+            },
+            action_providers: self
+                .action_providers
+                .unwrap_or_else(default_action_providers),
+            budget: self.budget,
+            allowed_actions: self.allowed_actions,
+            deployment_config: self.deployment_config,
+            template_id: self.template_id,
+            api_key_config: self.api_key_config,
+            callback_url: self.callback_url,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+}
+
+/// Builder of [`DeployAgentParams`], validating them when built
+///
+/// The TEE fields are only set together, through [`DeployAgentParamsBuilder::tee`].
+#[derive(Clone, Debug)]
+pub struct DeployAgentParamsBuilder {
+    agent_id: String,
+    api_key_config: Option<ApiKeyConfig>,
+    tee: Option<TeeEnvironment>,
+    callback_url: Option<String>,
+}
+
+impl DeployAgentParamsBuilder {
+    /// Replaces the API keys given when the agent was created
+    pub fn api_key_config(mut self, api_key_config: ApiKeyConfig) -> Self {
+        self.api_key_config = Some(api_key_config);
+        self
+    }
+
+    /// Deploys to the TEE with an environment encrypted to its public key
+    pub fn tee(
+        mut self,
+        encrypted_env: impl Into<String>,
+        pubkey: impl Into<String>,
+        app_id: impl Into<String>,
+        salt: impl Into<String>,
+    ) -> Self {
+        self.tee = Some(TeeEnvironment {
+            encrypted_env: Secret::new(encrypted_env.into()),
+            pubkey: Secret::new(pubkey.into()),
+            app_id: app_id.into(),
+            salt: Secret::new(salt.into()),
+        });
+        self
+    }
+
+    pub fn callback_url(mut self, url: impl Into<String>) -> Self {
+        self.callback_url = Some(url.into());
+        self
+    }
+
+    /// Builds the parameters, failing on invalid fields
+    pub fn build(self) -> Result<DeployAgentParams, String> {
+        let params = DeployAgentParams {
+            agent_id: self.agent_id.parse()?,
+            api_key_config: self.api_key_config,
+            tee: self.tee,
+            callback_url: self.callback_url,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+}
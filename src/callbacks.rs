@@ -0,0 +1,289 @@
+use crate::config::HttpConfig;
+use crate::error::BlueprintError;
+use crate::rate_limit::caller_key;
+use crate::types::Secret;
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Header carrying the HMAC-SHA256 of the callback body, as `sha256=<hex>`
+pub const SIGNATURE_HEADER: &str = "X-Blueprint-Signature";
+
+/// Longest accepted callback URL
+pub const MAX_CALLBACK_URL_LEN: usize = 2048;
+
+/// Attempts to deliver a callback before giving up
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry, doubled for each further one
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Operator secret the keys signing each caller's callbacks are derived from
+///
+/// Every caller gets its own key, so a dApp holding its key can check its callbacks but
+/// can't forge those of other callers.
+#[derive(Clone)]
+pub struct CallbackConfig {
+    secret: Secret<String>,
+}
+
+impl CallbackConfig {
+    pub fn new(secret: impl Into<Secret<String>>) -> Self {
+        Self {
+            secret: secret.into(),
+        }
+    }
+
+    /// Loads the secret from `AGENT_CALLBACK_SECRET`
+    ///
+    /// Returns `None` when it is unset; callbacks are then not sent.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        Ok(std::env::var("AGENT_CALLBACK_SECRET")
+            .ok()
+            .filter(|secret| !secret.trim().is_empty())
+            .map(Self::new))
+    }
+
+    /// Returns the key signing the callbacks of a caller, named by its rate limit key
+    /// (`<service ID>/<account>`)
+    ///
+    /// The key is the hex-encoded HMAC-SHA256 of the caller under the operator's secret;
+    /// operators hand it to the caller with `agentkit-bp callback-key`.
+    pub fn caller_key(&self, caller: &str) -> String {
+        hex::encode(hmac_sha256(
+            self.secret.expose().as_bytes(),
+            caller.as_bytes(),
+        ))
+    }
+
+    /// Returns the value of the signature header of a body posted to a caller
+    pub fn sign(&self, caller: &str, body: &[u8]) -> String {
+        signature_header(&self.caller_key(caller), body)
+    }
+}
+
+/// Returns the value of the signature header of a body signed with `key`
+pub fn signature_header(key: &str, body: &[u8]) -> String {
+    format!("sha256={}", hex::encode(hmac_sha256(key.as_bytes(), body)))
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+/// Checks that a callback URL is an HTTPS URL the operator may post to
+///
+/// Hosts given as loopback, private or link-local addresses are refused, so that callers
+/// can't make the operator post to its own network.
+pub fn validate_callback_url(callback_url: &str) -> Result<(), String> {
+    if callback_url.len() > MAX_CALLBACK_URL_LEN {
+        return Err(format!(
+            "Callback URL must be at most {} characters",
+            MAX_CALLBACK_URL_LEN
+        ));
+    }
+    let url = url::Url::parse(callback_url).map_err(|e| format!("Invalid callback URL: {}", e))?;
+    if url.scheme() != "https" {
+        return Err("Callback URL must use https".to_string());
+    }
+    let internal = match url.host() {
+        None => return Err("Callback URL must have a host".to_string()),
+        Some(url::Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+        Some(url::Host::Ipv4(ip)) => is_internal(IpAddr::V4(ip)),
+        Some(url::Host::Ipv6(ip)) => is_internal(IpAddr::V6(ip)),
+    };
+    if internal {
+        return Err("Callback URL must not point to an internal address".to_string());
+    }
+    Ok(())
+}
+
+/// Returns whether an address is not publicly routable, e.g. loopback, private or CGNAT
+///
+/// IPv4-mapped IPv6 addresses are checked as the IPv4 address they carry, and the NAT64
+/// prefix is refused as a whole since it reaches IPv4 hosts through the operator's gateway.
+pub(crate) fn is_internal(ip: IpAddr) -> bool {
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    };
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // "This network" (0.0.0.0/8) and shared CGNAT space (100.64.0.0/10)
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64)
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            ip.is_loopback()
+                || ip.is_unspecified()
+                // Unique local (fc00::/7) and link-local (fe80::/10) addresses
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                // NAT64 (64:ff9b::/96)
+                || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+        }
+    }
+}
+
+/// Builds the client posting to a callback URL, pinned to the addresses its host resolves to
+///
+/// The host is resolved once and every address is checked, and the client connects only to
+/// those, so the name can't be pointed at an internal address between the check and the
+/// connection. Redirects are not followed. Through a proxy, the proxy resolves the host and
+/// has to enforce the operator's egress policy itself.
+pub async fn callback_client(
+    callback_url: &str,
+    http_config: &HttpConfig,
+) -> Result<reqwest::Client, String> {
+    let url = url::Url::parse(callback_url).map_err(|e| format!("Invalid callback URL: {}", e))?;
+    let host = url.host_str().ok_or("Callback URL must have a host")?;
+    let port = url
+        .port_or_known_default()
+        .ok_or("Callback URL must have a port")?;
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("Failed to resolve {}: {}", host, e))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("{} does not resolve to any address", host));
+    }
+    if addrs.iter().any(|addr| is_internal(addr.ip())) {
+        return Err(format!("{} resolves to an internal address", host));
+    }
+
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(http_config.connect_timeout)
+        .timeout(http_config.request_timeout)
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve_to_addrs(host, &addrs);
+    if let Some(proxy) = &http_config.proxy {
+        builder = builder.proxy(proxy.to_reqwest()?);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+/// Posts a signed callback body, failing unless the receiver answers with a success status
+pub async fn post_callback(
+    client: &reqwest::Client,
+    callback_url: &str,
+    signature: &str,
+    body: Vec<u8>,
+) -> Result<(), String> {
+    let response = client
+        .post(callback_url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, signature)
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("Callback receiver answered {}", status));
+    }
+    Ok(())
+}
+
+/// Renders the body posted when a job finishes
+///
+/// It holds the job's result on success and its error payload on failure, and a timestamp
+/// receivers can use to reject replays.
+pub fn callback_body<T: Serialize>(
+    job: &str,
+    call_id: Option<u64>,
+    outcome: Result<&T, &BlueprintError>,
+) -> Value {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let mut body = json!({
+        "job": job,
+        "call_id": call_id,
+        "timestamp": timestamp,
+    });
+    match outcome {
+        Ok(result) => {
+            body["status"] = json!("succeeded");
+            body["result"] = serde_json::to_value(result).unwrap_or(Value::Null);
+        }
+        Err(error) => {
+            body["status"] = json!("failed");
+            body["error"] = serde_json::to_value(error).unwrap_or(Value::Null);
+        }
+    }
+    body
+}
+
+/// Posts a job's outcome to the caller's callback URL in the background
+///
+/// Delivery is retried a few times; failures are only logged, never failing the job.
+pub fn notify<T: Serialize>(
+    context: &ServiceContext,
+    callback_url: &str,
+    job: &'static str,
+    outcome: Result<&T, &BlueprintError>,
+) {
+    let Some(config) = context.callbacks.clone() else {
+        logging::warn!(
+            job,
+            "Not sending callback: AGENT_CALLBACK_SECRET is not set"
+        );
+        return;
+    };
+    // Without a caller there is no key the receiver could check the callback with
+    if context.caller().is_none() {
+        logging::warn!(job, "Not sending callback: the caller is unknown");
+        return;
+    }
+    let body = match serde_json::to_vec(&callback_body(job, context.call_id, outcome)) {
+        Ok(body) => body,
+        Err(e) => {
+            logging::warn!(job, error = %e, "Failed to serialize callback");
+            return;
+        }
+    };
+    let signature = config.sign(&caller_key(context), &body);
+    let http_config = context.http_config();
+    let callback_url = callback_url.to_string();
+
+    tokio::spawn(async move {
+        let mut delay = RETRY_DELAY;
+        for attempt in 1..=MAX_ATTEMPTS {
+            let result = match callback_client(&callback_url, &http_config).await {
+                Ok(client) => post_callback(&client, &callback_url, &signature, body.clone()).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(_) => {
+                    logging::info!(job, "Sent job callback");
+                    return;
+                }
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    logging::debug!(job, attempt, error = %e, "Callback failed, retrying");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => {
+                    logging::warn!(job, error = %e, "Failed to send job callback");
+                }
+            }
+        }
+    });
+}
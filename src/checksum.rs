@@ -0,0 +1,128 @@
+use crate::helpers::{set_env_var, write_private_file};
+use crate::retention::DIAGNOSTICS_DIR;
+use crate::secrets::SEALED_SECRETS_FILE;
+use crate::templates::{TemplateManifest, MANIFEST_FILE};
+use crate::types::AgentChecksums;
+use crate::wallet::{SEALED_WALLET_FILE, WALLET_DIR};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Variable recording the checksum of the agent's compose file in its .env
+pub const COMPOSE_CHECKSUM_VAR: &str = "AGENT_COMPOSE_SHA256";
+
+/// Variable recording the checksum of the agent's other files in its .env
+pub const FILES_CHECKSUM_VAR: &str = "AGENT_FILES_SHA256";
+
+/// Compose file of an agent, checksummed on its own
+const COMPOSE_FILE: &str = "docker-compose.yml";
+
+/// Files at the top of an agent's directory that change after it is created, and so aren't
+/// part of its files' checksum
+const MUTABLE_FILES: &[&str] = &[
+    ".env",
+    SEALED_SECRETS_FILE,
+    DIAGNOSTICS_DIR,
+    COMPOSE_FILE,
+    WALLET_DIR,
+    SEALED_WALLET_FILE,
+];
+
+/// Computes the checksums of an agent's directory
+///
+/// The compose file is hashed as it is. The other files the agent was created with are
+/// hashed by relative path and content, in path order, leaving out its settings, secrets
+/// and diagnostics, and the files its template's manifest ignores (e.g. installed
+/// dependencies), at any depth.
+pub fn compute(agent_dir: &Path) -> Result<AgentChecksums, String> {
+    let compose = fs::read(agent_dir.join(COMPOSE_FILE))
+        .map_err(|e| format!("Failed to read {}: {}", COMPOSE_FILE, e))?;
+    let ignore = match fs::read_to_string(agent_dir.join(MANIFEST_FILE)) {
+        Ok(manifest) => toml::from_str::<TemplateManifest>(&manifest)
+            .map(|manifest| manifest.ignore)
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+
+    let mut hasher = Sha256::new();
+    hash_dir(agent_dir, agent_dir, &ignore, &mut hasher)?;
+    Ok(AgentChecksums {
+        compose: format!("sha256:{}", hex::encode(Sha256::digest(&compose))),
+        files: format!("sha256:{}", hex::encode(hasher.finalize())),
+    })
+}
+
+fn hash_dir(root: &Path, dir: &Path, ignore: &[String], hasher: &mut Sha256) -> Result<(), String> {
+    let mut entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if ignore.iter().any(|ignored| *ignored == name)
+            || (dir == root && MUTABLE_FILES.contains(&name.as_ref()))
+        {
+            continue;
+        }
+        let path = entry.path();
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_string_lossy();
+        let file_type = entry
+            .file_type()
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if file_type.is_dir() {
+            hash_dir(root, &path, ignore, hasher)?;
+            continue;
+        }
+        let content = if file_type.is_symlink() {
+            fs::read_link(&path).map(|target| target.to_string_lossy().into_owned().into_bytes())
+        } else {
+            fs::read(&path)
+        }
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        hasher.update(relative.as_bytes());
+        hasher.update([0]);
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+    Ok(())
+}
+
+/// Records an agent's checksums in its .env, for [`verify`]
+pub fn record(agent_dir: &Path, checksums: &AgentChecksums) -> Result<(), String> {
+    let env_path = agent_dir.join(".env");
+    let content =
+        fs::read_to_string(&env_path).map_err(|e| format!("Failed to read .env: {}", e))?;
+    let content = set_env_var(&content, COMPOSE_CHECKSUM_VAR, &checksums.compose);
+    let content = set_env_var(&content, FILES_CHECKSUM_VAR, &checksums.files);
+    write_private_file(&env_path, &content)
+}
+
+/// Checks that an agent's files are still the ones it was created with
+///
+/// Agents created before checksums were recorded pass.
+pub fn verify(agent_dir: &Path, agent_env: &HashMap<String, String>) -> Result<(), String> {
+    let (Some(compose), Some(files)) = (
+        agent_env.get(COMPOSE_CHECKSUM_VAR),
+        agent_env.get(FILES_CHECKSUM_VAR),
+    ) else {
+        return Ok(());
+    };
+    let actual = compute(agent_dir)?;
+    if actual.compose != *compose {
+        return Err(format!(
+            "{} changed since the agent was created ({} instead of {})",
+            COMPOSE_FILE, actual.compose, compose
+        ));
+    }
+    if actual.files != *files {
+        return Err(format!(
+            "The agent's files changed since it was created ({} instead of {})",
+            actual.files, files
+        ));
+    }
+    Ok(())
+}
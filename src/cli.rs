@@ -0,0 +1,320 @@
+use crate::agent_id::AgentId;
+use crate::create_agent::{remove_agent, render_compose_file, render_env_file};
+use crate::envelope::decode_params;
+use crate::mcp::{self, McpServer};
+use crate::monitor::MonitorState;
+use crate::ports::{PortAllocator, PortRange, PORTS_FILE};
+use crate::retention::LogRetention;
+use crate::scaffold;
+use crate::schema;
+use crate::status_api::{AgentLogs, AgentOverview};
+use crate::stop_agent::handle_stop_agent;
+use crate::templates::{self, Template};
+use crate::types::{AgentStopResult, CreateAgentParams, ScaffoldTemplateParams};
+use crate::{AgentRegistry, CallbackConfig, ServiceContext};
+use clap::{Parser, Subcommand};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::process::Command as TokioCommand;
+
+/// Account the CLI acts as: whoever runs it on the node already controls its agents
+const LOCAL_OPERATOR: &str = "local-operator";
+
+/// Operator CLI for inspecting and managing the agents of a local blueprint node
+#[derive(Debug, Parser)]
+#[command(name = "agentkit-bp", version)]
+pub struct Cli {
+    /// Address of the blueprint's status API
+    #[arg(long, env = "AGENT_STATUS_API_ADDR", default_value = "127.0.0.1:9090")]
+    pub api: String,
+    /// Directory holding the agents
+    #[arg(long, default_value = "./agents")]
+    pub agents_dir: PathBuf,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Lists the agents on the node with their ports, container and health
+    List,
+    /// Prints the most recent lines of an agent's container logs
+    Logs {
+        agent_id: AgentId,
+        /// Lines to print
+        #[arg(long)]
+        tail: Option<usize>,
+    },
+    /// Stops an agent's container, keeping it for a later deployment
+    Stop { agent_id: AgentId },
+    /// Removes an agent's containers and directory and frees its ports
+    Delete { agent_id: AgentId },
+    /// Checks create_agent parameters and prints the files they would generate
+    DryRun {
+        /// JSON parameters, bare or in a versioned envelope
+        params: PathBuf,
+        /// Template directory the agent's files are generated from, instead of the one the
+        /// parameters name
+        #[arg(long)]
+        template: Option<PathBuf>,
+    },
+    /// Writes a new template directory wired to the blueprint's variables
+    ScaffoldTemplate {
+        template_id: String,
+        /// Directory the template is written into; `<templates>/<template_id>` if unset
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Human-readable name of the template
+        #[arg(long)]
+        name: Option<String>,
+        /// Image the template's Dockerfile starts from
+        #[arg(long)]
+        base_image: Option<String>,
+        /// Port the agent listens on in its container
+        #[arg(long)]
+        http_port: Option<u16>,
+    },
+    /// Prints the JSON Schemas of every job's parameters and result
+    Schema {
+        /// Directory to write one file per schema into, instead of printing them
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Serves the running agents as Model Context Protocol tools over stdin and stdout
+    Mcp,
+    /// Prints the key a caller checks its job callbacks with, derived from
+    /// `AGENT_CALLBACK_SECRET`
+    CallbackKey {
+        /// Account of the caller
+        caller: String,
+        /// Service the caller calls jobs of
+        #[arg(long)]
+        service_id: Option<u64>,
+    },
+}
+
+impl Cli {
+    /// Runs the command, returning what to print
+    pub async fn run(self) -> Result<String, String> {
+        match self.command {
+            Command::List => {
+                let agents: Vec<AgentOverview> = get_json(&self.api, "/agents").await?;
+                Ok(format_agents(&agents))
+            }
+            Command::Logs { agent_id, tail } => {
+                let path = match tail {
+                    Some(tail) => format!("/agents/{}/logs?tail={}", agent_id, tail),
+                    None => format!("/agents/{}/logs", agent_id),
+                };
+                let logs: AgentLogs = get_json(&self.api, &path).await?;
+                Ok(logs.lines.join("\n"))
+            }
+            Command::Stop { agent_id } => {
+                let context = local_context(&self.agents_dir);
+                let params = serde_json::to_vec(&serde_json::json!({ "agent_id": agent_id }))
+                    .map_err(|e| format!("Failed to serialize parameters: {}", e))?;
+                let result = handle_stop_agent(params, &context)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let result: AgentStopResult = serde_json::from_slice(&result)
+                    .map_err(|e| format!("Invalid stop result: {}", e))?;
+                Ok(if result.was_running {
+                    format!("Stopped agent {}", result.agent_id)
+                } else {
+                    format!("Agent {} was not running", result.agent_id)
+                })
+            }
+            Command::Delete { agent_id } => delete_agent(&self.agents_dir, &agent_id).await,
+            Command::DryRun { params, template } => {
+                let bytes = fs::read(&params)
+                    .map_err(|e| format!("Failed to read {}: {}", params.display(), e))?;
+                dry_run(&bytes, template.as_deref())
+            }
+            Command::ScaffoldTemplate {
+                template_id,
+                out,
+                name,
+                base_image,
+                http_port,
+            } => {
+                let files = scaffold::scaffold(&ScaffoldTemplateParams {
+                    template_id: template_id.clone(),
+                    name,
+                    base_image,
+                    http_port,
+                    ..Default::default()
+                })?;
+                let dir =
+                    out.unwrap_or_else(|| Path::new(templates::TEMPLATES_DIR).join(&template_id));
+                scaffold::write(&dir, &files)?;
+                Ok(format!(
+                    "Wrote template {} to {}; check it with validate_template once the agent is in place",
+                    template_id,
+                    dir.display()
+                ))
+            }
+            Command::Schema { out: Some(dir) } => {
+                let written = schema::write_schemas(&dir)?;
+                Ok(format!("Wrote {} schemas to {}", written, dir.display()))
+            }
+            Command::Schema { out: None } => {
+                serde_json::to_string_pretty(&schema::schema_document())
+                    .map_err(|e| format!("Failed to serialize schemas: {}", e))
+            }
+            Command::Mcp => {
+                let mut context = local_context(&self.agents_dir);
+                let registry = AgentRegistry::new();
+                PortAllocator::new(
+                    PortRange::default(),
+                    self.agents_dir.join(PORTS_FILE),
+                    registry.clone(),
+                )
+                .load()?;
+                context.agent_registry = Some(registry);
+                mcp::serve_stdio(McpServer::new(context)).await?;
+                Ok(String::new())
+            }
+            Command::CallbackKey { caller, service_id } => {
+                let config =
+                    CallbackConfig::from_env()?.ok_or("AGENT_CALLBACK_SECRET is not set")?;
+                let service_id = service_id.map_or_else(|| "-".to_string(), |id| id.to_string());
+                Ok(config.caller_key(&format!("{}/{}", service_id, caller)))
+            }
+        }
+    }
+}
+
+/// Context of the jobs' handlers when run from the CLI, acting as the service owner
+fn local_context(agents_dir: &Path) -> ServiceContext {
+    ServiceContext {
+        agents_base_dir: Some(agents_dir.to_string_lossy().to_string()),
+        service_owner: Some(LOCAL_OPERATOR.to_string()),
+        api_caller: Some(LOCAL_OPERATOR.to_string()),
+        ..Default::default()
+    }
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(api: &str, path: &str) -> Result<T, String> {
+    let base = if api.starts_with("http://") || api.starts_with("https://") {
+        api.trim_end_matches('/').to_string()
+    } else {
+        format!("http://{}", api)
+    };
+    let url = format!("{}{}", base, path);
+    let response = reqwest::get(&url)
+        .await
+        .map_err(|e| format!("Failed to reach the status API at {}: {}", base, e))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("{} returned {}: {}", url, status, body));
+    }
+    response
+        .json()
+        .await
+        .map_err(|e| format!("Invalid response from {}: {}", url, e))
+}
+
+/// Formats agents as a table, one per line
+pub fn format_agents(agents: &[AgentOverview]) -> String {
+    let port = |port: Option<u16>| port.map_or("-".to_string(), |port| port.to_string());
+    let mut table = format!(
+        "{:<36}  {:>5}  {:>5}  {:<7}  {}\n",
+        "AGENT", "HTTP", "WS", "RUNNING", "HEALTH"
+    );
+    for agent in agents {
+        let health = match agent.health {
+            None => "-".to_string(),
+            Some(MonitorState::Healthy) => "healthy".to_string(),
+            Some(MonitorState::Failing {
+                consecutive_failures,
+            }) => format!("failing ({} probes)", consecutive_failures),
+            Some(MonitorState::Restarting { restarts }) => {
+                format!("restarting ({} restarts)", restarts)
+            }
+            Some(MonitorState::Failed) => "failed".to_string(),
+        };
+        let _ = writeln!(
+            table,
+            "{:<36}  {:>5}  {:>5}  {:<7}  {}",
+            agent.agent_id,
+            port(agent.http_port),
+            port(agent.websocket_port),
+            if agent.running { "yes" } else { "no" },
+            health
+        );
+    }
+    table
+}
+
+/// Removes an agent's containers, directory and port allocation
+///
+/// A running blueprint keeps its own copy of the port allocations, so delete agents while
+/// it is stopped for their ports to be reused.
+async fn delete_agent(agents_dir: &Path, agent_id: &AgentId) -> Result<String, String> {
+    let mut context = local_context(agents_dir);
+    if !crate::status_api::is_known_agent(&context, agent_id) {
+        return Err(format!("Unknown agent: {}", agent_id));
+    }
+
+    // Containers of compose deployments, sidecars included
+    let agent_dir = agent_id.dir(agents_dir);
+    if agent_dir.join("docker-compose.yml").exists() {
+        let output = TokioCommand::new("docker-compose")
+            .arg("down")
+            .current_dir(&agent_dir)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to execute docker-compose down command: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to remove the agent's containers: {}",
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ));
+        }
+    }
+    // Agents started from the warm pool don't belong to a compose project
+    let _ = TokioCommand::new("docker")
+        .args(["rm", "-f", &agent_id.container_name()])
+        .output()
+        .await;
+
+    let allocator = PortAllocator::new(
+        PortRange::default(),
+        agents_dir.join(PORTS_FILE),
+        AgentRegistry::new(),
+    );
+    allocator.load()?;
+    context.port_allocator = Some(allocator);
+    remove_agent(agent_id, &context)?;
+    Ok(format!("Deleted agent {}", agent_id))
+}
+
+/// Validates create_agent parameters and renders the files the agent would get
+///
+/// The files come from `template_dir` if set, and otherwise from the template the parameters
+/// name, which must exist; git templates are fetched if they aren't cached yet.
+pub fn dry_run(params_bytes: &[u8], template_dir: Option<&Path>) -> Result<String, String> {
+    let params: CreateAgentParams = decode_params(params_bytes)?;
+    params.validate()?;
+    let template_dir = match template_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => Template::resolve(Path::new(templates::TEMPLATES_DIR), params.template_id())?.dir,
+    };
+
+    let read_template = |name: &str| {
+        let path = template_dir.join(name);
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    };
+    let compose = render_compose_file(
+        &read_template("docker-compose.yml")?,
+        &params,
+        &LogRetention::default(),
+    )?;
+    let env = render_env_file(&read_template(".env.example")?, &params, None)?;
+    Ok(format!(
+        "Parameters are valid.\n\n# docker-compose.yml\n{}\n# .env\n{}",
+        compose, env
+    ))
+}
@@ -0,0 +1,138 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Encoding of a job's parameters, which its result is returned in as well
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Json,
+    /// SCALE, as used by Substrate tooling; only accepted with the `scale` feature
+    Scale,
+}
+
+/// Types that can travel SCALE-encoded
+///
+/// Without the `scale` feature every type qualifies and nothing is ever SCALE-encoded, so
+/// handlers can require it unconditionally.
+#[cfg(feature = "scale")]
+pub trait ScaleCodec: parity_scale_codec::Encode + parity_scale_codec::Decode {}
+
+#[cfg(feature = "scale")]
+impl<T: parity_scale_codec::Encode + parity_scale_codec::Decode> ScaleCodec for T {}
+
+/// Types that can travel SCALE-encoded
+///
+/// Without the `scale` feature every type qualifies and nothing is ever SCALE-encoded, so
+/// handlers can require it unconditionally.
+#[cfg(not(feature = "scale"))]
+pub trait ScaleCodec {}
+
+#[cfg(not(feature = "scale"))]
+impl<T> ScaleCodec for T {}
+
+/// Decodes SCALE-encoded parameters, returning `None` if they are not
+///
+/// JSON parameters always start with `{`, which no SCALE encoding of the job parameters can
+/// (they all start with a string, a vector or an option), so those are left to the JSON
+/// decoder. The whole input must be consumed.
+#[cfg(feature = "scale")]
+pub fn decode_scale<T: ScaleCodec>(bytes: &[u8]) -> Option<T> {
+    if looks_like_json(bytes) {
+        return None;
+    }
+    <T as parity_scale_codec::DecodeAll>::decode_all(&mut &bytes[..]).ok()
+}
+
+/// Decodes SCALE-encoded parameters, returning `None` if they are not
+///
+/// Always `None` without the `scale` feature.
+#[cfg(not(feature = "scale"))]
+pub fn decode_scale<T: ScaleCodec>(_bytes: &[u8]) -> Option<T> {
+    None
+}
+
+/// Decodes job parameters, trying SCALE first and falling back to JSON
+pub fn decode<T: DeserializeOwned + ScaleCodec>(bytes: &[u8]) -> Result<(T, Encoding), String> {
+    if let Some(params) = decode_scale(bytes) {
+        return Ok((params, Encoding::Scale));
+    }
+    serde_json::from_slice(bytes)
+        .map(|params| (params, Encoding::Json))
+        .map_err(|e| format!("Failed to deserialize parameters: {}", e))
+}
+
+/// Encodes a job's result in the encoding its parameters came in
+pub fn encode<T: Serialize + ScaleCodec>(value: &T, encoding: Encoding) -> Result<Vec<u8>, String> {
+    match encoding {
+        #[cfg(feature = "scale")]
+        Encoding::Scale => Ok(parity_scale_codec::Encode::encode(value)),
+        _ => serde_json::to_vec(value).map_err(|e| format!("Failed to serialize result: {}", e)),
+    }
+}
+
+#[cfg(feature = "scale")]
+fn looks_like_json(bytes: &[u8]) -> bool {
+    bytes
+        .iter()
+        .find(|byte| !byte.is_ascii_whitespace())
+        .is_some_and(|byte| *byte == b'{')
+}
+
+/// SCALE representations of the field types SCALE has no encoding for, used through
+/// `#[codec(encoded_as = "...")]`
+///
+/// Floats travel as their IEEE 754 bits, `usize` as a `u64`, and paths and JSON values as
+/// strings.
+#[cfg(feature = "scale")]
+pub mod scale {
+    use parity_scale_codec::{Decode, Encode, EncodeAsRef};
+    use std::path::PathBuf;
+
+    macro_rules! scale_as {
+        ($name:ident, $field:ty, $repr:ty, $to:expr, $from:expr) => {
+            #[derive(Encode, Decode)]
+            pub struct $name($repr);
+
+            impl From<&$field> for $name {
+                fn from(value: &$field) -> Self {
+                    Self($to(value))
+                }
+            }
+
+            impl From<$name> for $field {
+                fn from(value: $name) -> Self {
+                    $from(value.0)
+                }
+            }
+
+            impl<'a> EncodeAsRef<'a, $field> for $name {
+                type RefType = $name;
+            }
+        };
+    }
+
+    scale_as!(F64Bits, f64, u64, |v: &f64| v.to_bits(), f64::from_bits);
+    scale_as!(
+        OptionF32Bits,
+        Option<f32>,
+        Option<u32>,
+        |v: &Option<f32>| v.map(f32::to_bits),
+        |v: Option<u32>| v.map(f32::from_bits)
+    );
+    scale_as!(Usize, usize, u64, |v: &usize| *v as u64, |v: u64| v
+        as usize);
+    scale_as!(
+        OptionPath,
+        Option<PathBuf>,
+        Option<String>,
+        |v: &Option<PathBuf>| v.as_ref().map(|path| path.to_string_lossy().into_owned()),
+        |v: Option<String>| v.map(PathBuf::from)
+    );
+    scale_as!(
+        JsonText,
+        serde_json::Value,
+        String,
+        |v: &serde_json::Value| v.to_string(),
+        |v: String| serde_json::from_str(&v).unwrap_or_default()
+    );
+}
@@ -0,0 +1,448 @@
+use crate::deploy_queue::DEFAULT_MAX_CONCURRENT_DEPLOYS;
+use crate::monitor::MonitorPolicy;
+use crate::tee::PhalaCloud;
+use blueprint_sdk::tangle_subxt::subxt::utils::AccountId32;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Operator-level HTTP client and health check configuration
+#[derive(Clone, Debug)]
+pub struct HttpConfig {
+    /// Maximum time to establish a connection to an agent
+    pub connect_timeout: Duration,
+    /// Maximum time to wait for a complete response from an agent
+    pub request_timeout: Duration,
+    /// Number of health check attempts before a deployment is considered failed
+    pub health_max_attempts: u32,
+    /// Time to wait for the container to start before the first health check
+    pub health_initial_delay: Duration,
+    /// Delay between the first and second health check attempts
+    pub health_retry_delay: Duration,
+    /// Multiplier applied to the retry delay after each failed attempt
+    pub health_backoff_factor: f32,
+    /// Agents probed at once by the heartbeat, the health monitor and the status API
+    pub health_concurrency: usize,
+    /// Explicit proxy for outbound traffic; system proxy variables apply when unset
+    pub proxy: Option<ProxyConfig>,
+}
+
+/// HTTP or SOCKS proxy for outbound agent and TEE API traffic
+#[derive(Clone)]
+pub struct ProxyConfig {
+    /// Proxy URL, e.g. `http://proxy.corp:3128` or `socks5://proxy.corp:1080`
+    pub url: String,
+    /// Comma-separated hosts that bypass the proxy
+    pub no_proxy: String,
+}
+
+impl ProxyConfig {
+    /// Hosts that always bypass the proxy, since locally deployed agents listen on localhost
+    pub const DEFAULT_NO_PROXY: &'static str = "localhost,127.0.0.1,::1";
+
+    /// Creates a proxy configuration that bypasses the proxy for local agents
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            no_proxy: Self::DEFAULT_NO_PROXY.to_string(),
+        }
+    }
+
+    /// Loads the proxy from `AGENT_PROXY_URL` and `AGENT_NO_PROXY`
+    pub fn from_env() -> Option<Self> {
+        let url = std::env::var("AGENT_PROXY_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty())?;
+        let mut proxy = Self::new(url.trim());
+        if let Ok(no_proxy) = std::env::var("AGENT_NO_PROXY") {
+            proxy.no_proxy = format!("{},{}", Self::DEFAULT_NO_PROXY, no_proxy.trim());
+        }
+        Some(proxy)
+    }
+
+    /// Returns the proxy URL without its credentials, for logs and errors
+    pub fn redacted_url(&self) -> String {
+        match url::Url::parse(&self.url) {
+            Ok(mut url) => {
+                if !url.username().is_empty() || url.password().is_some() {
+                    let _ = url.set_username("");
+                    let _ = url.set_password(None);
+                }
+                url.to_string()
+            }
+            Err(_) => "<invalid URL>".to_string(),
+        }
+    }
+
+    /// Converts the configuration into a proxy for a reqwest client
+    pub fn to_reqwest(&self) -> Result<reqwest::Proxy, String> {
+        let proxy = reqwest::Proxy::all(&self.url)
+            .map_err(|e| format!("Invalid proxy URL {}: {}", self.redacted_url(), e))?;
+        Ok(proxy.no_proxy(reqwest::NoProxy::from_string(&self.no_proxy)))
+    }
+
+    /// Exports the proxy as the standard proxy environment variables
+    ///
+    /// Third-party clients such as the Phala TEE deployer build their own reqwest
+    /// client, which only picks up proxies from the environment. Variables that are
+    /// already set are left untouched. Call this at startup before spawning tasks.
+    pub fn export_to_env(&self) {
+        for var in ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY"] {
+            if std::env::var_os(var).is_none() {
+                std::env::set_var(var, &self.url);
+            }
+        }
+        if std::env::var_os("NO_PROXY").is_none() {
+            std::env::set_var("NO_PROXY", &self.no_proxy);
+        }
+    }
+}
+
+impl std::fmt::Debug for ProxyConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProxyConfig")
+            .field("url", &self.redacted_url())
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(10),
+            health_max_attempts: 10,
+            health_initial_delay: Duration::from_secs(5),
+            health_retry_delay: Duration::from_secs(3),
+            health_backoff_factor: 1.0,
+            health_concurrency: 16,
+            proxy: None,
+        }
+    }
+}
+
+impl HttpConfig {
+    /// Loads the configuration from the environment, falling back to the defaults
+    ///
+    /// # Environment
+    ///
+    /// * `AGENT_HTTP_CONNECT_TIMEOUT_SECS`
+    /// * `AGENT_HTTP_REQUEST_TIMEOUT_SECS`
+    /// * `AGENT_HEALTH_MAX_ATTEMPTS`
+    /// * `AGENT_HEALTH_INITIAL_DELAY_SECS`
+    /// * `AGENT_HEALTH_RETRY_DELAY_SECS`
+    /// * `AGENT_HEALTH_BACKOFF_FACTOR`
+    /// * `AGENT_HEALTH_CONCURRENCY`
+    /// * `AGENT_PROXY_URL` / `AGENT_NO_PROXY`
+    pub fn from_env() -> Result<Self, String> {
+        let defaults = Self::default();
+        let health_backoff_factor = env_or(
+            "AGENT_HEALTH_BACKOFF_FACTOR",
+            defaults.health_backoff_factor,
+        )?;
+        if !health_backoff_factor.is_finite() || health_backoff_factor < 1.0 {
+            return Err(format!(
+                "Invalid value for AGENT_HEALTH_BACKOFF_FACTOR: {} is not at least 1.0",
+                health_backoff_factor
+            ));
+        }
+        Ok(Self {
+            connect_timeout: Duration::from_secs(env_or(
+                "AGENT_HTTP_CONNECT_TIMEOUT_SECS",
+                defaults.connect_timeout.as_secs(),
+            )?),
+            request_timeout: Duration::from_secs(env_or(
+                "AGENT_HTTP_REQUEST_TIMEOUT_SECS",
+                defaults.request_timeout.as_secs(),
+            )?),
+            health_max_attempts: env_or("AGENT_HEALTH_MAX_ATTEMPTS", defaults.health_max_attempts)?,
+            health_initial_delay: Duration::from_secs(env_or(
+                "AGENT_HEALTH_INITIAL_DELAY_SECS",
+                defaults.health_initial_delay.as_secs(),
+            )?),
+            health_retry_delay: Duration::from_secs(env_or(
+                "AGENT_HEALTH_RETRY_DELAY_SECS",
+                defaults.health_retry_delay.as_secs(),
+            )?),
+            health_backoff_factor,
+            health_concurrency: env_or("AGENT_HEALTH_CONCURRENCY", defaults.health_concurrency)?
+                .max(1),
+            proxy: ProxyConfig::from_env(),
+        })
+    }
+
+    /// Builds an HTTP client that applies this configuration's timeouts and proxy
+    pub fn build_client(&self) -> Result<reqwest::Client, String> {
+        let mut builder = reqwest::Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.request_timeout);
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy.to_reqwest()?);
+        }
+        builder
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))
+    }
+
+    /// Returns the delay to wait after the given failed health check attempt (1-based)
+    pub fn health_retry_delay_for(&self, attempt: u32) -> Duration {
+        self.health_backoff().delay_for(attempt)
+    }
+
+    /// Returns the delays between health check attempts
+    pub fn health_backoff(&self) -> HealthBackoff {
+        HealthBackoff {
+            retry_delay: self.health_retry_delay,
+            factor: self.health_backoff_factor,
+        }
+    }
+}
+
+/// Delays between health check attempts, growing by `factor` after each failed attempt
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HealthBackoff {
+    /// Delay after the first failed attempt
+    pub retry_delay: Duration,
+    pub factor: f32,
+}
+
+impl HealthBackoff {
+    /// Returns the delay to wait after the given failed attempt (1-based)
+    ///
+    /// Factors below 1.0 count as 1.0, and delays too long to represent saturate.
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let factor = f64::from(self.factor).max(1.0);
+        let exponent = i32::try_from(attempt.saturating_sub(1)).unwrap_or(i32::MAX);
+        let secs = self.retry_delay.as_secs_f64() * factor.powi(exponent);
+        Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX)
+    }
+}
+
+/// Core settings of the service, read from an optional TOML file and the environment
+///
+/// Every key of the file can also be set through its environment variable, which takes
+/// precedence, so a file can hold the defaults of a fleet and the environment the exceptions.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceConfig {
+    /// Directory holding the agents' files (`AGENTS_BASE_DIR`, default `./agents`)
+    pub agents_base_dir: Option<String>,
+    /// Let agents be deployed to Phala's TEE (`TEE_ENABLED`)
+    pub tee_enabled: Option<bool>,
+    /// Phala Cloud API endpoint (`PHALA_CLOUD_API_ENDPOINT`), required with the TEE
+    pub phala_cloud_api_endpoint: Option<String>,
+    /// Phala Cloud API key (`PHALA_CLOUD_API_KEY`), required with the TEE
+    pub phala_cloud_api_key: Option<String>,
+    /// Owner of the service instance, who may operate on every agent (`AGENT_SERVICE_OWNER`)
+    pub service_owner: Option<String>,
+    /// `AGENT_REQUIRE_BUDGET`
+    pub require_budget: Option<bool>,
+    /// `AGENT_REPORT_LIFECYCLE`
+    pub report_lifecycle: Option<bool>,
+    /// `AGENT_SIGN_RESULTS`
+    pub sign_results: Option<bool>,
+    /// `AGENT_REQUIRE_PAYMENT`
+    pub require_payment: Option<bool>,
+    /// `AGENT_REQUIRE_CALLER_SIGNATURE`
+    pub require_caller_signature: Option<bool>,
+    /// Deployments run at once (`AGENT_MAX_CONCURRENT_DEPLOYS`)
+    pub max_concurrent_deploys: Option<usize>,
+    /// Health checks before a local deployment fails (`AGENT_HEALTH_MAX_ATTEMPTS`)
+    pub health_max_attempts: Option<u32>,
+    /// Failed probes before the health monitor restarts an agent
+    /// (`AGENT_MONITOR_FAILURE_THRESHOLD`)
+    pub monitor_failure_threshold: Option<u32>,
+    /// Restarts before the health monitor gives up on an agent (`AGENT_MONITOR_MAX_RESTARTS`)
+    pub monitor_max_restarts: Option<u32>,
+}
+
+impl ServiceConfig {
+    /// Directory holding the agents' files when none is configured
+    pub const DEFAULT_AGENTS_BASE_DIR: &'static str = "./agents";
+
+    /// Reads the configuration from a TOML file
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+    }
+
+    /// Returns the config file named by `AGENT_CONFIG_FILE`, if any
+    pub fn file_path() -> Option<PathBuf> {
+        std::env::var("AGENT_CONFIG_FILE")
+            .ok()
+            .filter(|path| !path.trim().is_empty())
+            .map(|path| PathBuf::from(path.trim()))
+    }
+
+    /// Reads the file named by `AGENT_CONFIG_FILE`, if any, and overrides it with the
+    /// environment, then validates the result
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = match Self::file_path() {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+
+        let string = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let flag = |name: &str, value: &mut Option<bool>| -> Result<(), String> {
+            if std::env::var_os(name).is_some() {
+                *value = Some(env_or(name, false)?);
+            }
+            Ok(())
+        };
+        config.agents_base_dir = string("AGENTS_BASE_DIR").or(config.agents_base_dir);
+        config.phala_cloud_api_endpoint =
+            string("PHALA_CLOUD_API_ENDPOINT").or(config.phala_cloud_api_endpoint);
+        config.phala_cloud_api_key = string("PHALA_CLOUD_API_KEY").or(config.phala_cloud_api_key);
+        config.service_owner = string("AGENT_SERVICE_OWNER").or(config.service_owner);
+        flag("TEE_ENABLED", &mut config.tee_enabled)?;
+        flag("AGENT_REQUIRE_BUDGET", &mut config.require_budget)?;
+        flag("AGENT_REPORT_LIFECYCLE", &mut config.report_lifecycle)?;
+        flag("AGENT_SIGN_RESULTS", &mut config.sign_results)?;
+        flag("AGENT_REQUIRE_PAYMENT", &mut config.require_payment)?;
+        flag(
+            "AGENT_REQUIRE_CALLER_SIGNATURE",
+            &mut config.require_caller_signature,
+        )?;
+        config.max_concurrent_deploys =
+            env_opt("AGENT_MAX_CONCURRENT_DEPLOYS")?.or(config.max_concurrent_deploys);
+        config.health_max_attempts =
+            env_opt("AGENT_HEALTH_MAX_ATTEMPTS")?.or(config.health_max_attempts);
+        config.monitor_failure_threshold =
+            env_opt("AGENT_MONITOR_FAILURE_THRESHOLD")?.or(config.monitor_failure_threshold);
+        config.monitor_max_restarts =
+            env_opt("AGENT_MONITOR_MAX_RESTARTS")?.or(config.monitor_max_restarts);
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Returns the Phala Cloud account TEE agents are deployed through, if the TEE is enabled
+    pub fn phala_cloud(&self) -> Option<PhalaCloud> {
+        if !self.tee_enabled.unwrap_or(false) {
+            return None;
+        }
+        Some(PhalaCloud {
+            api_endpoint: self.phala_cloud_api_endpoint.clone()?,
+            api_key: self.phala_cloud_api_key.clone()?,
+        })
+    }
+
+    /// Checks that the TEE settings are complete and the values well-formed
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(dir) = &self.agents_base_dir {
+            if dir.trim().is_empty() {
+                return Err("AGENTS_BASE_DIR must not be empty".to_string());
+            }
+            if Path::new(dir).is_file() {
+                return Err(format!(
+                    "AGENTS_BASE_DIR {} is a file, not a directory",
+                    dir
+                ));
+            }
+        }
+        if let Some(endpoint) = &self.phala_cloud_api_endpoint {
+            url::Url::parse(endpoint)
+                .map_err(|e| format!("Invalid PHALA_CLOUD_API_ENDPOINT {}: {}", endpoint, e))?;
+        }
+        if self.tee_enabled.unwrap_or(false) {
+            let missing: Vec<_> = [
+                ("PHALA_CLOUD_API_ENDPOINT", &self.phala_cloud_api_endpoint),
+                ("PHALA_CLOUD_API_KEY", &self.phala_cloud_api_key),
+            ]
+            .into_iter()
+            .filter(|(_, value)| value.is_none())
+            .map(|(name, _)| name)
+            .collect();
+            if !missing.is_empty() {
+                return Err(format!(
+                    "TEE_ENABLED is set, but {} {} not",
+                    missing.join(" and "),
+                    if missing.len() == 1 { "is" } else { "are" }
+                ));
+            }
+        }
+        if let Some(owner) = &self.service_owner {
+            AccountId32::from_str(owner)
+                .map_err(|e| format!("Invalid AGENT_SERVICE_OWNER {}: {}", owner, e))?;
+        }
+        if self.max_concurrent_deploys == Some(0) {
+            return Err("AGENT_MAX_CONCURRENT_DEPLOYS must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Returns whether agent lifecycle events are reported on-chain, which is off by default
+    pub fn report_lifecycle(&self) -> bool {
+        self.report_lifecycle.unwrap_or(false)
+    }
+
+    /// Returns how many deployments run at once
+    pub fn max_concurrent_deploys(&self) -> usize {
+        self.max_concurrent_deploys
+            .unwrap_or(DEFAULT_MAX_CONCURRENT_DEPLOYS)
+    }
+
+    /// Applies the health check settings to an HTTP configuration, unset ones being the
+    /// defaults
+    pub fn apply_to_http_config(&self, http_config: &mut HttpConfig) {
+        http_config.health_max_attempts = self
+            .health_max_attempts
+            .unwrap_or(HttpConfig::default().health_max_attempts);
+    }
+
+    /// Applies the health monitor settings to a monitor policy, unset ones being the defaults
+    pub fn apply_to_monitor_policy(&self, policy: &mut MonitorPolicy) {
+        let defaults = MonitorPolicy::default();
+        policy.failure_threshold = self
+            .monitor_failure_threshold
+            .unwrap_or(defaults.failure_threshold)
+            .max(1);
+        policy.max_restarts = self.monitor_max_restarts.unwrap_or(defaults.max_restarts);
+    }
+
+    /// Returns the directory holding the agents' files
+    pub fn agents_base_dir(&self) -> &str {
+        self.agents_base_dir
+            .as_deref()
+            .unwrap_or(Self::DEFAULT_AGENTS_BASE_DIR)
+    }
+}
+
+/// Reads and parses an environment variable, returning `default` when it is unset
+pub fn env_or<T: FromStr>(name: &str, default: T) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|e| format!("Invalid value for {}: {}", name, e)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Reads and parses an environment variable, returning `None` when it is unset
+pub fn env_opt<T: FromStr>(name: &str) -> Result<Option<T>, String>
+where
+    T::Err: std::fmt::Display,
+{
+    std::env::var(name)
+        .ok()
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|e| format!("Invalid value for {}: {}", name, e))
+        })
+        .transpose()
+}
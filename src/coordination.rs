@@ -0,0 +1,77 @@
+use crate::agent_id::AgentId;
+use crate::config::env_or;
+use sha3::{Digest, Keccak256};
+use uuid::{Builder, Uuid};
+
+/// Position of this operator among the operators running the service
+///
+/// Every operator processes every `JobCalled` event. With coordination enabled they
+/// derive the same agent IDs from the call, and each agent is deployed only by the
+/// operator whose shard it falls in. The others still run all validation and
+/// authorization checks, so a bad request fails on every operator alike.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoordinationConfig {
+    /// Zero-based index of this operator
+    pub operator_index: u32,
+    /// Total number of operators running the service
+    pub operator_count: u32,
+}
+
+impl CoordinationConfig {
+    /// Loads the coordination settings from the environment
+    ///
+    /// Returns `None` if `AGENT_OPERATOR_COUNT` is unset, in which case every operator
+    /// creates and deploys independently.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let operator_count: u32 = match std::env::var("AGENT_OPERATOR_COUNT") {
+            Ok(value) => value
+                .parse()
+                .map_err(|_| format!("Invalid value for AGENT_OPERATOR_COUNT: {}", value))?,
+            Err(_) => return Ok(None),
+        };
+        let operator_index: u32 = env_or("AGENT_OPERATOR_INDEX", 0)?;
+
+        let config = Self {
+            operator_index,
+            operator_count,
+        };
+        config.validate()?;
+        Ok(Some(config))
+    }
+
+    /// Validates that the index lies within the operator set
+    pub fn validate(&self) -> Result<(), String> {
+        if self.operator_count == 0 {
+            return Err("Operator count must be at least 1".to_string());
+        }
+        if self.operator_index >= self.operator_count {
+            return Err(format!(
+                "Operator index {} is out of range for {} operators",
+                self.operator_index, self.operator_count
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the index of the operator responsible for deploying an agent
+    pub fn responsible_operator(&self, agent_id: &str) -> u32 {
+        let hash = Keccak256::digest(agent_id.as_bytes());
+        let mut prefix = [0u8; 8];
+        prefix.copy_from_slice(&hash[..8]);
+        (u64::from_be_bytes(prefix) % self.operator_count as u64) as u32
+    }
+
+    /// Returns whether this operator is responsible for deploying an agent
+    pub fn is_responsible(&self, agent_id: &str) -> bool {
+        self.responsible_operator(agent_id) == self.operator_index
+    }
+}
+
+/// Derives an agent ID that every operator computes identically for the same job call
+pub fn deterministic_agent_id(call_id: u64, name: &str) -> AgentId {
+    let hash = Keccak256::digest(format!("coinbase-agent:{}:{}", call_id, name).as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash[..16]);
+    let id: Uuid = Builder::from_custom_bytes(bytes).into_uuid();
+    AgentId::from(id)
+}
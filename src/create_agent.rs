@@ -1,130 +1,699 @@
+use crate::agent_id::AgentId;
+use crate::audit::{self, SecretAccess};
+use crate::callbacks;
+use crate::checksum;
+use crate::codec;
+use crate::coordination::deterministic_agent_id;
 use crate::docker;
-use crate::types::{AgentCreationResult, CreateAgentParams};
-use crate::{AgentPortConfig, ServiceContext};
+use crate::envelope::decode_signed_job_params;
+use crate::error::BlueprintError;
+use crate::events::{self, AgentEvent};
+use crate::helpers::{
+    quote_env_value, read_env_file, run_blocking, set_env_var, write_private_file,
+};
+use crate::hooks;
+use crate::journal;
+use crate::ownership::OWNER_VAR;
+use crate::rate_limit::{self, caller_key};
+use crate::render::{self, RenderContext};
+use crate::retention::LogRetention;
+use crate::secrets::write_sealed_secrets;
+use crate::signing::sign_result;
+use crate::telemetry::StageTimer;
+use crate::templates::{self, Template};
+use crate::types::{
+    ActionProvider, AgentCreationResult, AgentImage, AgentMode, AgentTemplate, AllowedAction,
+    CreateAgentParams, DeploymentTarget, LlmProvider, Secret, TeeApp, TeeConfig, WalletConfig,
+    WebSocketPort,
+};
+use crate::wallet;
+use crate::{AgentPortConfig, QuotaReservation, ServiceContext};
 use blueprint_sdk::logging;
+use semver::Version;
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use uuid::Uuid;
 
 /// Handles the create_agent job
 pub async fn handle_create_agent(
     params_bytes: Vec<u8>,
     context: &ServiceContext,
-) -> Result<Vec<u8>, String> {
-    // Deserialize the parameters from bytes
-    let params: CreateAgentParams = match serde_json::from_slice(&params_bytes) {
-        Ok(p) => p,
-        Err(e) => return Err(format!("Failed to deserialize parameters: {}", e)),
+) -> Result<Vec<u8>, BlueprintError> {
+    rate_limit::check_call(context)?;
+
+    // Deserialize the parameters from bytes, upgrading older versions
+    let (params, encoding): (CreateAgentParams, _) =
+        decode_signed_job_params(&params_bytes, context).map_err(BlueprintError::Params)?;
+
+    let callback_url = params.callback_url.clone();
+    let result = match reserve_agents(context, &[params.agent_config.mode.clone()]) {
+        Ok(_reservation) => create_agent_from_params(params, context).await,
+        Err(e) => Err(e),
     };
+    if let Some(callback_url) = &callback_url {
+        callbacks::notify(context, callback_url, "create_agent", result.as_ref());
+    }
+    let result = result?;
+    journal::record(context, "create_agent", &result.agent_id, "created");
+    events::publish(
+        context,
+        AgentEvent::Created {
+            agent_id: result.agent_id.clone(),
+            call_id: context.call_id,
+        },
+    );
+
+    // Serialize the result in the encoding of the parameters
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
+
+/// Creates an agent's directory, configuration and compose file
+pub(crate) async fn create_agent_from_params(
+    params: CreateAgentParams,
+    context: &ServiceContext,
+) -> Result<AgentCreationResult, BlueprintError> {
+    create_agent_with_ports(params, None, context).await
+}
 
-    // Generate a unique ID for this agent
-    let agent_id = Uuid::new_v4().to_string();
-    logging::info!("Creating agent with ID: {}", agent_id);
+/// Creates an agent, giving it the ports reserved by `reserved` if set
+pub(crate) async fn create_agent_with_ports(
+    mut params: CreateAgentParams,
+    reserved: Option<&str>,
+    context: &ServiceContext,
+) -> Result<AgentCreationResult, BlueprintError> {
+    // Reject settings the agent could not run with
+    params.validate().map_err(BlueprintError::Params)?;
+    if params.deployment_config.target.is_tee() {
+        context.phala_cloud().map_err(BlueprintError::Params)?;
+    }
 
-    // Create the agent directory and copy starter template
-    let agent_dir = setup_agent_directory(&agent_id, context)?;
-    logging::info!("Created agent directory: {}", agent_dir.display());
+    // The caller reserved the agent in the service's quota (see `reserve_agents`)
+    check_owner_quota(context, 1)?;
 
-    // Create .env file with configuration
-    create_env_file(&params, &agent_dir)?;
-    logging::info!("Created environment configuration");
-
-    // Get HTTP port from params or use default 3000
-    let http_port = params.deployment_config.http_port.unwrap_or(3000);
-    let websocket_port = http_port + 1;
-
-    // Store port configuration in the context for later use during deployment
-    if let Some(agent_ports) = &context.agent_ports {
-        if let Ok(mut ports_map) = agent_ports.lock() {
-            ports_map.insert(
-                agent_id.clone(),
+    // Find the template the agent is created from, fetching git templates on first use
+    let template = run_blocking({
+        let template_id = params.template_id().to_string();
+        move || Template::resolve(Path::new(templates::TEMPLATES_DIR), &template_id)
+    })
+    .await
+    .map_err(BlueprintError::Params)?;
+    if let Some(commit) = &template.commit {
+        logging::info!(template = %template.id, commit = %commit, "Using git template");
+    }
+    template
+        .check_mode(&params.agent_config.mode)
+        .map_err(BlueprintError::Params)?;
+    template
+        .check_policies(&params)
+        .map_err(BlueprintError::Params)?;
+
+    // Expand the preset the agent asked for into the size of its container or VM
+    if let Some(size) = &params.deployment_config.size {
+        let preset = template
+            .preset(size)
+            .map_err(BlueprintError::Params)?
+            .clone();
+        match &mut params.deployment_config.target {
+            DeploymentTarget::Tee(tee) => *tee = preset,
+            DeploymentTarget::Local { .. } => params.deployment_config.resources = Some(preset),
+        }
+    }
+
+    // Agents of templates without a WebSocket server get no WebSocket port
+    if template.manifest.ports.websocket.is_none() {
+        if let DeploymentTarget::Local { websocket_port, .. } = &mut params.deployment_config.target
+        {
+            *websocket_port = WebSocketPort::Disabled;
+        }
+    }
+
+    // Let restricted agents reach what their template's runtime needs on startup
+    if let Some(egress) = &mut params.deployment_config.egress {
+        for host in &template.manifest.egress_hosts {
+            if !egress.allowed_hosts.contains(host) {
+                egress.allowed_hosts.push(host.clone());
+            }
+        }
+    }
+
+    // Check the Dockerfile the agent is built from is part of the template, and pin prebuilt
+    // images to a digest so redeploys run the same image
+    match &mut params.deployment_config.image {
+        AgentImage::Template => {}
+        AgentImage::Dockerfile { path } => {
+            if !template.dir.join(&*path).is_file() {
+                return Err(BlueprintError::Params(format!(
+                    "Template {} has no Dockerfile at {}",
+                    template.id, path
+                )));
+            }
+        }
+        AgentImage::Image { reference } => {
+            if !reference.contains('@') {
+                let unpinned = reference.clone();
+                *reference = run_blocking(move || docker::pin_image(&unpinned))
+                    .await
+                    .map_err(BlueprintError::Docker)?;
+                logging::info!(image = %reference, "Pinned agent image");
+            }
+        }
+    }
+
+    // Generate a unique ID for this agent, the same on every operator when they coordinate
+    let agent_id = match (&context.coordination, context.call_id) {
+        (Some(_), Some(call_id)) => deterministic_agent_id(call_id, &params.name),
+        _ => AgentId::generate(),
+    };
+    logging::info!(agent_id = %agent_id, phase = "setup", "Creating agent");
+
+    // Give the agent free ports, or the requested ones if nobody holds them
+    if let Some(allocator) = &context.port_allocator {
+        let ports = match reserved {
+            Some(holder) => allocator.assign_reserved(holder, &agent_id),
+            None => allocator.allocate(
+                &agent_id,
+                params.deployment_config.target.http_port(),
+                params.deployment_config.target.websocket_port(),
+            ),
+        }
+        .map_err(BlueprintError::Params)?;
+        if let DeploymentTarget::Local { http_port, .. } = &mut params.deployment_config.target {
+            *http_port = Some(ports.http_port);
+        }
+        logging::info!(
+            agent_id = %agent_id,
+            phase = "configure",
+            http_port = ports.http_port,
+            websocket_port = ports.websocket_port,
+            "Allocated agent ports"
+        );
+    } else {
+        // Get the ports from params or use the template's defaults
+        let target = &params.deployment_config.target;
+        let defaults = template.manifest.ports;
+        let http_port = target.http_port().unwrap_or(defaults.http);
+        let websocket_port = match (target.http_port(), target.websocket_port()) {
+            (None, WebSocketPort::Adjacent) => defaults.websocket,
+            (_, websocket_port) => websocket_port.resolve(http_port),
+        };
+
+        // Store port configuration in the context for later use during deployment
+        if let Some(registry) = &context.agent_registry {
+            registry.insert(
+                &agent_id,
                 AgentPortConfig {
                     http_port,
                     websocket_port,
                 },
             );
             logging::info!(
-                "Registered agent {} with ports HTTP:{}, WS:{}",
-                agent_id,
+                agent_id = %agent_id,
+                phase = "configure",
                 http_port,
-                websocket_port
+                websocket_port,
+                "Registered agent ports"
             );
         } else {
-            logging::warn!("Failed to lock agent_ports map for agent {}", agent_id);
+            logging::warn!(agent_id = %agent_id, phase = "configure", "No agent registry available in context");
         }
-    } else {
-        logging::warn!("No agent_ports map available in context");
     }
 
-    let compose_path = docker::write_docker_compose_file(&agent_dir)?;
-
-    // Prepare TEE config if enabled
-    let (tee_pubkey, tee_app_id, tee_salt) = if params.deployment_config.tee_enabled {
-        match get_tee_public_key(&agent_dir, context).await? {
-            Some((pubkey, app_id, salt)) => (Some(pubkey), Some(app_id), Some(salt)),
-            None => (None, None, None),
+    let result = create_agent_files(params, template, agent_id.clone(), context).await;
+    if result.is_err() {
+        if let Some(allocator) = &context.port_allocator {
+            if let Err(e) = allocator.release(&agent_id) {
+                logging::warn!(agent_id = %agent_id, error = %e, "Failed to release agent ports");
+            }
         }
+    }
+    result
+}
+
+/// Creates the agent's directory, configuration and compose file once its ports are settled
+async fn create_agent_files(
+    params: CreateAgentParams,
+    template: Template,
+    agent_id: AgentId,
+    context: &ServiceContext,
+) -> Result<AgentCreationResult, BlueprintError> {
+    // Define base directory from context or environment
+    let base_dir = match context.agents_base_dir() {
+        Some(dir) => dir,
+        None => "./agents".to_string(),
+    };
+
+    // Create the agent directory and copy the template, off the async executor
+    let mut timer = StageTimer::default();
+    let agent_dir = timer
+        .run_async("template_copy", &agent_id, {
+            let (agent_id, template) = (agent_id.clone(), template.clone());
+            run_blocking(move || setup_agent_directory(&agent_id, &template, Path::new(&base_dir)))
+        })
+        .await
+        .map_err(BlueprintError::Template)?;
+    logging::info!(
+        agent_id = %agent_id,
+        phase = "setup",
+        dir = %agent_dir.display(),
+        template = %template.id,
+        "Created agent directory"
+    );
+
+    // Run the template's post-create hooks in containers, before any settings are written
+    let hooks = if template.manifest.post_create.is_empty() {
+        Vec::new()
     } else {
-        (None, None, None)
+        timer
+            .run_async(
+                "post_create",
+                &agent_id,
+                hooks::run_post_create(
+                    &template.manifest.post_create,
+                    &agent_dir,
+                    &agent_id.container_name(),
+                ),
+            )
+            .await
+            .map_err(BlueprintError::Template)?
+    };
+
+    // Hooks run the template's code on the agent's directory, so make sure they left no
+    // links through which the settings and secrets written next could land elsewhere
+    run_blocking({
+        let agent_dir = agent_dir.clone();
+        move || check_no_symlinks(&agent_dir)
+    })
+    .await
+    .map_err(BlueprintError::Template)?;
+
+    // Create .env file with configuration
+    let owner = context.caller();
+    run_blocking({
+        let (params, owner, agent_dir) = (params.clone(), owner.clone(), agent_dir.clone());
+        let version = template.manifest.version.clone();
+        move || create_env_file(&params, &version, owner.as_deref(), &agent_dir)
+    })
+    .await
+    .map_err(BlueprintError::Template)?;
+    if !params.deployment_config.target.is_tee() {
+        let agent_dir = agent_dir.clone();
+        run_blocking(move || wallet::create_dir(&agent_dir))
+            .await
+            .map_err(BlueprintError::Io)?;
+    }
+
+    // Seal the API keys and tokens instead of writing them to the .env
+    let secrets = collect_secrets(&params);
+    if !secrets.is_empty() {
+        let key = context.sealing_key.as_ref().ok_or_else(|| {
+            BlueprintError::Secrets(
+                "No sealing key configured to store the agent's API keys".to_string(),
+            )
+        })?;
+        let names: Vec<String> = secrets.keys().cloned().collect();
+        run_blocking({
+            let (key, agent_dir) = (key.clone(), agent_dir.clone());
+            move || write_sealed_secrets(&agent_dir, &key, &secrets)
+        })
+        .await
+        .map_err(BlueprintError::Secrets)?;
+        audit::record(context, &agent_id, SecretAccess::Write, names, "sealed")
+            .map_err(BlueprintError::Io)?;
+    }
+    logging::info!(agent_id = %agent_id, phase = "configure", "Created environment configuration");
+
+    let compose_path = timer
+        .run_async("compose_generation", &agent_id, {
+            let (params, agent_id, agent_dir) =
+                (params.clone(), agent_id.clone(), agent_dir.clone());
+            let (template_path, retention) = (template.compose_path(), context.log_retention());
+            run_blocking(move || {
+                write_compose_file(&params, &agent_id, &template_path, &agent_dir, &retention)
+            })
+        })
+        .await
+        .map_err(BlueprintError::Template)?;
+
+    // Bind TEE agents to an app whose key their environment will be encrypted to
+    let tee = match &params.deployment_config.target {
+        DeploymentTarget::Tee(tee_config) => Some(
+            timer
+                .run_async(
+                    "tee_pubkey",
+                    &agent_id,
+                    get_tee_app(&agent_id, &agent_dir, tee_config, context),
+                )
+                .await
+                .map_err(BlueprintError::Tee)?,
+        ),
+        DeploymentTarget::Local { .. } => None,
     };
 
-    // Return the result
-    let result = AgentCreationResult {
+    // Record what the agent's files are, so they can't change unnoticed before it's deployed
+    let checksums = run_blocking({
+        let agent_dir = agent_dir.clone();
+        move || {
+            let checksums = checksum::compute(&agent_dir)?;
+            checksum::record(&agent_dir, &checksums)?;
+            Ok(checksums)
+        }
+    })
+    .await
+    .map_err(BlueprintError::Io)?;
+
+    // Build the result
+    let mut result = AgentCreationResult {
         agent_id,
         files_created: vec![
             agent_dir.join(".env").to_string_lossy().to_string(),
-            agent_dir.join("package.json").to_string_lossy().to_string(),
+            agent_dir
+                .join(templates::MANIFEST_FILE)
+                .to_string_lossy()
+                .to_string(),
             compose_path.to_string_lossy().to_string(),
         ],
-        tee_pubkey,
-        tee_app_id,
-        tee_salt,
+        tee,
+        network_id: params.agent_config.network_id,
+        system_prompt: params.agent_config.system_prompt,
+        temperature: params.agent_config.temperature,
+        max_tokens: params.agent_config.max_tokens,
+        owner,
+        image: match params.deployment_config.image {
+            AgentImage::Image { reference } => Some(reference),
+            _ => None,
+        },
+        template: Some(AgentTemplate {
+            id: template.id,
+            version: template.manifest.version.to_string(),
+            commit: template.commit,
+        }),
+        checksums: Some(checksums),
+        hooks,
+        call_id: context.call_id,
+        stage_timings: timer.into_timings(),
+        operator_signature: None,
     };
+    sign_result(context, &mut result).map_err(BlueprintError::Internal)?;
+
+    Ok(result)
+}
+
+/// Removes a created agent's directory and releases its ports
+pub(crate) fn remove_agent(agent_id: &AgentId, context: &ServiceContext) -> Result<(), String> {
+    if let Some(allocator) = &context.port_allocator {
+        allocator.release(agent_id)?;
+    } else if let Some(registry) = &context.agent_registry {
+        registry.remove(agent_id);
+    }
+
+    let base_dir = match context.agents_base_dir() {
+        Some(dir) => dir,
+        None => "./agents".to_string(),
+    };
+    let agent_dir = agent_id.dir(&base_dir);
+    if agent_dir.exists() {
+        fs::remove_dir_all(&agent_dir)
+            .map_err(|e| format!("Failed to remove agent directory: {}", e))?;
+        events::publish(
+            context,
+            AgentEvent::Deleted {
+                agent_id: agent_id.clone(),
+                call_id: context.call_id,
+            },
+        );
+    }
+
+    Ok(())
+}
+
+/// Counts the agents created so far, one directory per agent
+fn count_agents(context: &ServiceContext) -> Result<usize, String> {
+    Ok(agent_dirs(context)?.len())
+}
+
+/// Reserves room in the quota negotiated for this service for one agent per mode in `modes`
+///
+/// Hold the reservation until the agents are created, so concurrent calls can't exceed the
+/// quota between counting the agents and creating theirs. `None` if there is no quota.
+pub(crate) fn reserve_agents(
+    context: &ServiceContext,
+    modes: &[AgentMode],
+) -> Result<Option<QuotaReservation>, BlueprintError> {
+    context
+        .agent_quota
+        .as_ref()
+        .map(|quota| quota.reserve(modes, || count_agents(context).map_err(BlueprintError::Io)))
+        .transpose()
+}
 
-    // Serialize the result
-    match serde_json::to_vec(&result) {
-        Ok(bytes) => Ok(bytes),
-        Err(e) => Err(format!("Failed to serialize result: {}", e)),
+/// Counts the agents whose recorded owner is `owner`
+pub(crate) fn count_owned_agents(context: &ServiceContext, owner: &str) -> Result<usize, String> {
+    let mut count = 0;
+    for agent_dir in agent_dirs(context)? {
+        let agent_env = read_env_file(&agent_dir.join(".env"))?;
+        if agent_env.get(OWNER_VAR).map(String::as_str) == Some(owner) {
+            count += 1;
+        }
     }
+    Ok(count)
 }
 
-/// Sets up the agent directory by copying the starter template
-fn setup_agent_directory(agent_id: &str, context: &ServiceContext) -> Result<PathBuf, String> {
-    // Define base directory directly from context
-    let base_dir = match &context.agents_base_dir {
-        Some(dir) => dir.clone(),
+/// Stays within the per-owner agent limit when creating `additional` agents for the caller
+pub(crate) fn check_owner_quota(
+    context: &ServiceContext,
+    additional: usize,
+) -> Result<(), BlueprintError> {
+    if let (Some(limiter), Some(owner)) = (&context.rate_limiter, context.caller()) {
+        let owned = count_owned_agents(context, &owner).map_err(BlueprintError::Io)?;
+        limiter.check_agents(&caller_key(context), owned, additional)?;
+    }
+    Ok(())
+}
+
+/// Lists the directories of the agents created so far
+fn agent_dirs(context: &ServiceContext) -> Result<Vec<PathBuf>, String> {
+    let base_dir = match context.agents_base_dir() {
+        Some(dir) => dir,
         None => "./agents".to_string(),
     };
 
+    let entries = match fs::read_dir(&base_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read agents directory: {}", e)),
+    };
+
+    let mut dirs = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read agents directory: {}", e))?;
+        // Skip bookkeeping directories such as the journal
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if entry.path().is_dir() && !hidden {
+            dirs.push(entry.path());
+        }
+    }
+    Ok(dirs)
+}
+
+/// Writes the agent's compose file, with the sidecars its configuration asks for
+fn write_compose_file(
+    params: &CreateAgentParams,
+    agent_id: &str,
+    template_path: &Path,
+    agent_dir: &Path,
+    retention: &LogRetention,
+) -> Result<PathBuf, String> {
+    if !template_path.exists() {
+        return Err("Docker Compose template not found".to_string());
+    }
+    let template = fs::read_to_string(template_path)
+        .map_err(|e| format!("Failed to read Docker Compose template: {}", e))?;
+
+    let compose = render_compose_file(&template, params, retention)?;
+    let compose_path = agent_dir.join("docker-compose.yml");
+    fs::write(&compose_path, compose)
+        .map_err(|e| format!("Failed to write docker-compose.yml: {}", e))?;
+
+    if params.agent_config.llm_provider == LlmProvider::Local
+        && params.agent_config.local_model_config().sidecar
+    {
+        logging::info!(
+            agent_id = %agent_id,
+            phase = "compose",
+            model = %params.agent_config.model,
+            "Added Ollama sidecar to the compose file"
+        );
+    }
+    if let Some(egress) = &params.deployment_config.egress {
+        logging::info!(
+            agent_id = %agent_id,
+            phase = "compose",
+            hosts = %egress.hosts(&params.agent_config).join(","),
+            "Restricted agent egress"
+        );
+    }
+
+    Ok(compose_path)
+}
+
+/// Renders an agent's compose file from the template's
+///
+/// The template is first rendered with the agent's settings, like `.env.example` (see
+/// [`env_context`]), but a `{{NAME}}` tag inserts the reference `${NAME}` rather than the
+/// value, which docker-compose reads from the agent's .env. Values such as the system prompt
+/// can hold anything, so they never become part of the YAML. The agent's extra
+/// variables are passed to its service, once the template's services are checked against
+/// [`docker::check_compose_policy`], then the sidecars its configuration asks for are
+/// added and the logs of every service are rotated. The result is normalized, so every
+/// operator renders the same file.
+pub fn render_compose_file(
+    template: &str,
+    params: &CreateAgentParams,
+    retention: &LogRetention,
+) -> Result<String, String> {
+    let context: RenderContext = env_context(params, None)?
+        .into_iter()
+        .map(|(name, value)| {
+            let reference = if value.is_empty() {
+                value
+            } else {
+                format!("${{{}}}", name)
+            };
+            (name, reference)
+        })
+        .collect();
+    let template = render::render(template, &context)
+        .map_err(|e| format!("Failed to render docker-compose.yml: {}", e))?;
+    let mut compose: serde_yaml::Value = serde_yaml::from_str(&template)
+        .map_err(|e| format!("Failed to parse Docker compose as YAML: {}", e))?;
+
+    // Templates may come from anyone, so keep their containers away from the host
+    docker::check_compose_policy(&compose)?;
+
+    // Give the agent the extra variables it was created with
+    docker::insert_agent_env(&mut compose, params.deployment_config.env.keys())?;
+
+    // Keep the wallet of local agents across redeploys
+    if !params.deployment_config.target.is_tee() {
+        docker::insert_wallet_volume(&mut compose)?;
+    }
+
+    // Run the local model server next to the agent if requested
+    if params.agent_config.llm_provider == LlmProvider::Local
+        && params.agent_config.local_model_config().sidecar
+    {
+        docker::insert_ollama_sidecar(&mut compose, &params.agent_config.model)?;
+    }
+
+    // Limit the agent's container to the resources it was sized with
+    if let Some(resources) = &params.deployment_config.resources {
+        docker::insert_resource_limits(&mut compose, resources)?;
+    }
+
+    // Build or pull the image the caller chose instead of the template's
+    docker::insert_agent_image(&mut compose, &params.deployment_config.image)?;
+
+    // Only let the agent reach the hosts it needs, if the caller asked for it
+    if let Some(egress) = &params.deployment_config.egress {
+        docker::insert_egress_firewall(&mut compose, &egress.hosts(&params.agent_config))?;
+    }
+
+    // Don't publish a WebSocket port the agent was deployed without
+    if params.deployment_config.target.websocket_port() == WebSocketPort::Disabled {
+        docker::remove_websocket_port(&mut compose)?;
+    }
+
+    // Rotate the logs of the agent and its sidecars
+    docker::insert_log_rotation(&mut compose, retention)?;
+
+    docker::serialize_compose(compose)
+}
+
+/// Sets up the agent directory under `base_dir` by copying the template
+fn setup_agent_directory(
+    agent_id: &AgentId,
+    template: &Template,
+    base_dir: &Path,
+) -> Result<PathBuf, String> {
     // Create the base directory if it doesn't exist
-    fs::create_dir_all(&base_dir).map_err(|e| format!("Failed to create base directory: {}", e))?;
+    fs::create_dir_all(base_dir).map_err(|e| format!("Failed to create base directory: {}", e))?;
 
     // Create a directory for this agent
-    let agent_dir = PathBuf::from(&base_dir).join(agent_id);
+    let agent_dir = agent_id.dir(base_dir);
     fs::create_dir(&agent_dir).map_err(|e| format!("Failed to create agent directory: {}", e))?;
 
-    // Copy starter template
-    copy_starter_template(&agent_dir)?;
+    // Copy the template
+    copy_template(template, &agent_dir)?;
 
     Ok(agent_dir)
 }
 
-/// Copies the starter template to the agent directory
-fn copy_starter_template(agent_dir: &Path) -> Result<(), String> {
-    let template_dir = PathBuf::from("templates/starter");
-    if !template_dir.exists() {
-        return Err("Starter template directory not found".to_string());
+/// Template files that are rewritten per agent, and so are never hard-linked
+const REWRITTEN_TEMPLATE_FILES: &[&str] = &["docker-compose.yml", ".env", ".env.example"];
+
+/// How the files of a template ended up in an agent directory
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TemplateCopyStats {
+    pub linked: usize,
+    pub copied: usize,
+    pub unchanged: usize,
+}
+
+/// Copies a template to the agent directory, leaving out the files its manifest ignores
+fn copy_template(template: &Template, agent_dir: &Path) -> Result<(), String> {
+    if !template.dir.exists() {
+        return Err(format!(
+            "Template directory {} not found",
+            template.dir.display()
+        ));
     }
 
-    // Copy all files from the template directory to the agent directory
-    copy_dir_contents(&template_dir, agent_dir)?;
+    // Link or copy all files from the template directory to the agent directory, copying
+    // them all if hooks will run on them
+    let mut stats = TemplateCopyStats::default();
+    let link = template.manifest.post_create.is_empty();
+    link_dir_contents(
+        &template.dir,
+        agent_dir,
+        &template.manifest.ignore,
+        link,
+        &mut stats,
+    )?;
 
-    logging::info!("Template files copied successfully to agent directory");
+    logging::info!(
+        phase = "setup",
+        linked = stats.linked,
+        copied = stats.copied,
+        unchanged = stats.unchanged,
+        "Template files copied to agent directory"
+    );
     Ok(())
 }
 
-/// Recursively copy directory contents
-fn copy_dir_contents(src: &Path, dst: &Path) -> Result<(), String> {
+/// Recursively links or copies the files of a template into `dst`
+///
+/// Files are hard-linked, so agents share them with the template instead of each holding a
+/// copy, except for those the blueprint rewrites per agent. Where linking fails (e.g. across
+/// filesystems) files are copied, which clones them on filesystems supporting it. Files
+/// already identical in `dst` are left alone. Files and directories named in `ignore`, at
+/// any depth, are skipped, and symbolic links in `src` or `dst` are refused.
+pub fn link_template(
+    src: &Path,
+    dst: &Path,
+    ignore: &[String],
+) -> Result<TemplateCopyStats, String> {
+    let mut stats = TemplateCopyStats::default();
+    link_dir_contents(src, dst, ignore, true, &mut stats)?;
+    Ok(stats)
+}
+
+fn link_dir_contents(
+    src: &Path,
+    dst: &Path,
+    ignore: &[String],
+    link: bool,
+    stats: &mut TemplateCopyStats,
+) -> Result<(), String> {
     if !src.is_dir() {
         return Err(format!("{} is not a directory", src.display()));
     }
@@ -148,111 +717,183 @@ fn copy_dir_contents(src: &Path, dst: &Path) -> Result<(), String> {
             None => continue, // Skip entries without a valid file name
         };
 
-        // Skip node_modules directory to avoid copying large dependency trees
-        if file_name == "node_modules" || file_name == ".yarn" {
+        // Skip what the template ignores, e.g. large dependency trees
+        if ignore.iter().any(|name| file_name == name.as_str()) {
             continue;
         }
 
+        // Links could point anywhere on the operator's host, so they are never followed
+        let file_type = entry
+            .file_type()
+            .map_err(|e| format!("Failed to read {}: {}", src_path.display(), e))?;
         let dst_path = dst.join(file_name);
+        let dst_link = fs::symlink_metadata(&dst_path).is_ok_and(|meta| meta.is_symlink());
+        if file_type.is_symlink() || dst_link {
+            return Err(format!(
+                "{} is a symbolic link, which templates may not hold",
+                if dst_link { &dst_path } else { &src_path }.display()
+            ));
+        }
 
-        if src_path.is_dir() {
+        if file_type.is_dir() {
             // Create the destination directory
             fs::create_dir_all(&dst_path)
                 .map_err(|e| format!("Failed to create directory {}: {}", dst_path.display(), e))?;
 
-            // Recursively copy contents
-            copy_dir_contents(&src_path, &dst_path)?;
-        } else {
-            // Copy the file
-            fs::copy(&src_path, &dst_path).map_err(|e| {
-                format!(
-                    "Failed to copy {} to {}: {}",
-                    src_path.display(),
-                    dst_path.display(),
-                    e
-                )
-            })?;
+            // Recursively link contents
+            link_dir_contents(&src_path, &dst_path, ignore, link, stats)?;
+            continue;
+        }
+
+        if files_identical(&src_path, &dst_path) {
+            stats.unchanged += 1;
+            continue;
         }
+        if dst_path.exists() {
+            fs::remove_file(&dst_path)
+                .map_err(|e| format!("Failed to replace {}: {}", dst_path.display(), e))?;
+        }
+
+        let rewritten = REWRITTEN_TEMPLATE_FILES
+            .iter()
+            .any(|name| file_name == std::ffi::OsStr::new(name));
+        if link && !rewritten && fs::hard_link(&src_path, &dst_path).is_ok() {
+            stats.linked += 1;
+            continue;
+        }
+
+        // Copy the file
+        fs::copy(&src_path, &dst_path).map_err(|e| {
+            format!(
+                "Failed to copy {} to {}: {}",
+                src_path.display(),
+                dst_path.display(),
+                e
+            )
+        })?;
+        stats.copied += 1;
     }
 
     Ok(())
 }
 
-/// Get TEE public key for environment variable encryption using TeeDeployer
-async fn get_tee_public_key(
-    agent_dir: &Path,
-    context: &ServiceContext,
-) -> Result<Option<(String, String, String)>, String> {
-    // Get API key directly from context
-    let tee_api_key = context
-        .phala_tee_api_key
-        .as_ref()
-        .ok_or("PHALA_CLOUD_API_KEY not set")?;
-
-    // Get API endpoint from environment
-    let tee_api_endpoint = context
-        .phala_tee_api_endpoint
-        .as_ref()
-        .ok_or("PHALA_CLOUD_API_ENDPOINT not set")?;
-
-    logging::info!("Initializing TeeDeployer for public key retrieval");
+/// Fails if `dir` holds a symbolic link at any depth
+fn check_no_symlinks(dir: &Path) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let file_type = entry
+            .file_type()
+            .map_err(|e| format!("Failed to read {}: {}", entry.path().display(), e))?;
+        if file_type.is_symlink() {
+            return Err(format!(
+                "{} is a symbolic link, which agent directories may not hold",
+                entry.path().display()
+            ));
+        }
+        if file_type.is_dir() {
+            check_no_symlinks(&entry.path())?;
+        }
+    }
+    Ok(())
+}
 
-    // Initialize the TeeDeployer
-    let mut deployer = docker::init_tee_deployer(tee_api_key, tee_api_endpoint)?;
+/// Whether `dst` already holds the same content as `src`
+fn files_identical(src: &Path, dst: &Path) -> bool {
+    let (Ok(src_meta), Ok(dst_meta)) = (fs::metadata(src), fs::metadata(dst)) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if src_meta.dev() == dst_meta.dev() && src_meta.ino() == dst_meta.ino() {
+            return true;
+        }
+    }
+    src_meta.len() == dst_meta.len()
+        && matches!((fs::read(src), fs::read(dst)), (Ok(a), Ok(b)) if a == b)
+}
 
-    // Discover an available TEEPod
-    logging::info!("Discovering available TEEPods...");
-    deployer
-        .discover_teepod()
-        .await
-        .map_err(|e| format!("Failed to discover TEEPods: {}", e))?;
+/// Creates the TEE app of an agent, returning the key its environment is encrypted to
+async fn get_tee_app(
+    agent_id: &AgentId,
+    agent_dir: &Path,
+    tee_config: &TeeConfig,
+    context: &ServiceContext,
+) -> Result<TeeApp, String> {
+    let phala_cloud = context.phala_cloud()?;
 
     // Read docker-compose.yml from the agent directory
     let docker_compose_path = agent_dir.join("docker-compose.yml");
-    let docker_compose = fs::read_to_string(&docker_compose_path)
+    let docker_compose = tokio::fs::read_to_string(&docker_compose_path)
+        .await
         .map_err(|e| format!("Failed to read docker-compose.yml: {}", e))?;
 
     // Normalize the Docker Compose file to ensure consistent ordering
     let docker_compose = docker::normalize_docker_compose(&docker_compose)?;
 
-    let app_name = format!(
-        "coinbase-agent-{}",
-        agent_dir.file_name().unwrap().to_string_lossy()
-    );
+    let app_name = agent_id.container_name();
 
-    let vm_config = deployer
-        .create_vm_config(
-            &docker_compose,
-            &app_name,
-            Some(2),    // vcpu
-            Some(2048), // memory in MB
-            Some(10),   // disk size in GB
-        )
-        .map_err(|e| format!("Failed to create VM configuration: {}", e))?;
+    // Use the shared TeeDeployer and the TEEPod it discovered
+    let shared_deployer = context.tee_deployer();
+    let deployer = shared_deployer
+        .get(&phala_cloud.api_key, &phala_cloud.api_endpoint)
+        .await?;
 
     // Get the public key for this VM configuration
-    let vm_config_json = serde_json::to_value(vm_config)
-        .map_err(|e| format!("Failed to serialize VM configuration: {}", e))?;
-    logging::info!(
-        "Requesting encryption public key with VM Config: {:#?}",
-        vm_config_json
-    );
+    let vm_config_json = docker::tee_vm_config(&deployer, &docker_compose, &app_name, tee_config)?;
+    logging::info!(phase = "tee", app = %app_name, "Requesting encryption public key");
+    logging::debug!(phase = "tee", app = %app_name, vm_config = %vm_config_json, "VM configuration");
     let pubkey_response = deployer
         .get_pubkey_for_config(&vm_config_json)
         .await
         .map_err(|e| format!("Failed to get TEE public key: {}", e))?;
 
-    // Extract the pubkey and salt from the response
-    let pubkey = pubkey_response.clone().app_env_encrypt_pubkey;
-    let salt = pubkey_response.clone().app_id_salt;
+    logging::info!(
+        phase = "tee",
+        app = %app_name,
+        pubkey = %pubkey_response.app_env_encrypt_pubkey,
+        "Obtained TEE public key"
+    );
+
+    Ok(TeeApp {
+        pubkey: pubkey_response.app_env_encrypt_pubkey,
+        app_id: pubkey_response.app_id,
+        salt: pubkey_response.app_id_salt,
+    })
+}
 
-    logging::info!("Successfully obtained TEE public key: {}", pubkey);
+/// Collects the API keys and bot tokens given at creation, to be sealed at rest
+fn collect_secrets(params: &CreateAgentParams) -> HashMap<String, Secret<String>> {
+    let keys = &params.api_key_config;
+    let mut secrets: HashMap<String, Secret<String>> = [
+        ("OPENAI_API_KEY", &keys.openai_api_key),
+        ("ANTHROPIC_API_KEY", &keys.anthropic_api_key),
+        ("AZURE_OPENAI_API_KEY", &keys.azure_openai_api_key),
+        ("CDP_API_KEY_NAME", &keys.cdp_api_key_name),
+        ("CDP_API_KEY_PRIVATE_KEY", &keys.cdp_api_key_private_key),
+        ("CDP_WALLET_SECRET", &keys.cdp_wallet_secret),
+        ("WALLET_PRIVATE_KEY", &keys.wallet_private_key),
+    ]
+    .into_iter()
+    .filter_map(|(var, value)| value.as_ref().map(|value| (var.to_string(), value.clone())))
+    .collect();
 
-    Ok(Some((pubkey, pubkey_response.app_id, salt)))
+    let mode = &params.agent_config.mode;
+    if let (Some(var), Some(token)) = (mode.bot_token_env_var(), keys.bot_token(mode)) {
+        secrets.insert(var.to_string(), token.into());
+    }
+    secrets
 }
 
 /// Creates a .env file with the necessary environment variables
-fn create_env_file(params: &CreateAgentParams, agent_dir: &Path) -> Result<(), String> {
+fn create_env_file(
+    params: &CreateAgentParams,
+    template_version: &Version,
+    owner: Option<&str>,
+    agent_dir: &Path,
+) -> Result<(), String> {
     let env_file_path = agent_dir.join(".env");
     let env_template_path = agent_dir.join(".env.example");
 
@@ -260,40 +901,183 @@ fn create_env_file(params: &CreateAgentParams, agent_dir: &Path) -> Result<(), S
     let template = fs::read_to_string(&env_template_path)
         .map_err(|e| format!("Failed to read .env.example: {}", e))?;
 
-    // Create new content with actual values
-    let mut env_content = template.clone();
+    // Write the .env file
+    let env_content = render_env_file(&template, params, owner)?;
+    let env_content = set_env_var(
+        &env_content,
+        templates::TEMPLATE_VERSION_VAR,
+        &template_version.to_string(),
+    );
+    write_private_file(&env_file_path, &env_content)?;
+
+    Ok(())
+}
 
-    // Replace OpenAI API key if provided
-    if let Some(api_key) = &params.api_key_config.openai_api_key {
-        env_content = env_content.replace(
-            "OPENAI_API_KEY=your_openai_api_key_here",
-            &format!("OPENAI_API_KEY={}", api_key),
+/// Renders an agent's .env from the template's `.env.example`
+///
+/// The template is rendered with the agent's settings (see [`env_context`]), so it can place
+/// them with `{{NAME}}` tags or pick lines with `{{#if NAME}}` blocks. Every setting is then
+/// assigned on its own line, replacing the template's line for the variable (commented out
+/// or not) or appending one, so templates without tags get them as well. API keys and
+/// tokens are sealed separately and never rendered.
+pub fn render_env_file(
+    template: &str,
+    params: &CreateAgentParams,
+    owner: Option<&str>,
+) -> Result<String, String> {
+    let context: RenderContext = env_context(params, owner)?
+        .into_iter()
+        .map(|(name, value)| (name, quote_env_value(&value)))
+        .collect();
+    let mut env_content = render::render(template, &context)
+        .map_err(|e| format!("Failed to render .env.example: {}", e))?;
+    for (name, value) in &context {
+        env_content = set_env_var(&env_content, name, value);
+    }
+    Ok(env_content)
+}
+
+/// Builds the variables an agent's .env is given from its parameters
+///
+/// Settings left unset are left out, keeping the template's defaults.
+pub fn env_context(
+    params: &CreateAgentParams,
+    owner: Option<&str>,
+) -> Result<RenderContext, String> {
+    let mut context = RenderContext::new();
+    let mut set = |name: &str, value: String| {
+        context.insert(name.to_string(), value);
+    };
+
+    // Record the template, whose requirements are checked when deploying, and any image
+    // overriding its own
+    set(templates::TEMPLATE_VAR, params.template_id().to_string());
+    for (name, value) in params.deployment_config.image.to_env_vars() {
+        set(name, value);
+    }
+
+    // Set the LLM provider and model; the provider's API key is sealed separately
+    let provider = &params.agent_config.llm_provider;
+    set("LLM_PROVIDER", provider.to_string().to_lowercase());
+    set("MODEL", params.agent_config.model.clone());
+
+    // Point the agent at the Azure OpenAI deployment if configured
+    if let Some(azure) = &params.agent_config.azure_openai {
+        set("AZURE_OPENAI_ENDPOINT", azure.endpoint.clone());
+        set(
+            "AZURE_OPENAI_API_DEPLOYMENT_NAME",
+            azure.deployment_name.clone(),
         );
+        set("AZURE_OPENAI_API_VERSION", azure.api_version.clone());
     }
 
-    // Set agent mode
-    env_content = env_content.replace(
-        "AGENT_MODE=cli-chat",
-        &format!(
-            "AGENT_MODE={}",
-            params.agent_config.mode.to_string().to_lowercase()
-        ),
-    );
+    // Point the agent at the local model server's OpenAI-compatible API
+    if *provider == LlmProvider::Local {
+        set(
+            "OPENAI_BASE_URL",
+            params.agent_config.local_model_config().openai_base_url()?,
+        );
+    }
+
+    // Set generation parameters
+    if let Some(prompt) = &params.agent_config.system_prompt {
+        set("SYSTEM_PROMPT", prompt.clone());
+    }
+    if let Some(temperature) = params.agent_config.temperature {
+        set("TEMPERATURE", temperature.to_string());
+    }
+    if let Some(max_tokens) = params.agent_config.max_tokens {
+        set("MAX_TOKENS", max_tokens.to_string());
+    }
+
+    // Select how the agent is served: over HTTP, on its own schedule, or as the template's
+    // Telegram or Discord bot
+    let mode = &params.agent_config.mode;
+    let agent_mode = match mode {
+        AgentMode::Autonomous => "autonomous".to_string(),
+        AgentMode::Telegram | AgentMode::Discord
+            if params.api_key_config.bot_token(mode).is_some() =>
+        {
+            mode.to_string().to_lowercase()
+        }
+        _ => "http".to_string(),
+    };
+    set("AGENT_MODE", agent_mode);
+    if let (AgentMode::Autonomous, Some(schedule)) = (mode, &params.agent_config.schedule) {
+        set(
+            "AUTONOMOUS_INTERVAL_SECS",
+            schedule.interval_secs.to_string(),
+        );
+        if let Some(hours) = &schedule.active_hours {
+            set(
+                "AUTONOMOUS_ACTIVE_HOURS",
+                format!("{}-{}", hours.start, hours.end),
+            );
+        }
+        if let Some(max) = schedule.max_actions_per_day {
+            set("AUTONOMOUS_MAX_ACTIONS_PER_DAY", max.to_string());
+        }
+    }
 
-    // Set model name
-    env_content = env_content.replace(
-        "# MODEL=gpt-4o-mini",
-        &format!("MODEL={}", params.agent_config.model),
+    // Select the chain the agent's wallet operates on, and the kind of wallet
+    set("NETWORK_ID", params.agent_config.network_id.clone());
+    let wallet = &params.agent_config.wallet;
+    set("WALLET_TYPE", wallet.env_value().to_string());
+    if let WalletConfig::SmartWallet {
+        paymaster_url: Some(url),
+    } = wallet
+    {
+        set("PAYMASTER_URL", url.clone());
+    }
+
+    // Scope the agent to the requested action providers
+    set(
+        "ACTION_PROVIDERS",
+        ActionProvider::to_env_value(&params.action_providers),
     );
 
-    // Add HTTP port if provided
-    if let Some(port) = params.deployment_config.http_port {
-        env_content = env_content.replace("AGENT_PORT=3000", &format!("AGENT_PORT={}", port));
+    // Spending limits enforced by the agent itself
+    if let Some(budget) = &params.budget {
+        set(
+            "BUDGET_MAX_TRANSACTION_VALUE",
+            budget.max_transaction_value.to_string(),
+        );
+        set("BUDGET_DAILY_CAP", budget.daily_cap.to_string());
+        set(
+            "BUDGET_ALLOWED_ASSETS",
+            budget.allowed_assets.join(",").to_lowercase(),
+        );
     }
 
-    // Write the .env file
-    fs::write(&env_file_path, env_content)
-        .map_err(|e| format!("Failed to write .env file: {}", e))?;
+    // Restrict the on-chain actions the agent may execute
+    if let Some(actions) = &params.allowed_actions {
+        set("ALLOWED_ACTIONS", AllowedAction::to_env_value(actions));
+    }
 
-    Ok(())
+    // Record where the agent runs, for deploy_agent
+    for (name, value) in params.deployment_config.target.to_env_vars() {
+        set(name, value);
+    }
+    if let Some(port) = params.deployment_config.target.http_port() {
+        set("AGENT_PORT", port.to_string());
+    }
+
+    // Record who owns the agent, so later jobs can be restricted to them
+    if let Some(owner) = owner {
+        set(OWNER_VAR, owner.to_string());
+    }
+
+    // Add the caller's extra variables, recording their names so deploy_agent keeps them
+    let extra_env = &params.deployment_config.env;
+    if !extra_env.is_empty() {
+        set(
+            templates::EXTRA_ENV_VAR,
+            extra_env.keys().cloned().collect::<Vec<_>>().join(","),
+        );
+        for (name, value) in extra_env {
+            set(name, value.clone());
+        }
+    }
+
+    Ok(context)
 }
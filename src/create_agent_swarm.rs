@@ -0,0 +1,85 @@
+use crate::codec;
+use crate::create_agent::{
+    check_owner_quota, create_agent_from_params, remove_agent, reserve_agents,
+};
+use crate::envelope::decode_signed_job_params;
+use crate::error::BlueprintError;
+use crate::events::{self, AgentEvent};
+use crate::journal;
+use crate::rate_limit;
+use crate::types::{AgentSwarmResult, CreateAgentSwarmParams};
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+
+/// Handles the create_agent_swarm job
+pub async fn handle_create_agent_swarm(
+    params_bytes: Vec<u8>,
+    context: &ServiceContext,
+) -> Result<Vec<u8>, BlueprintError> {
+    rate_limit::check_call(context)?;
+
+    // Deserialize the parameters from bytes, upgrading older versions
+    let (params, encoding): (CreateAgentSwarmParams, _) =
+        decode_signed_job_params(&params_bytes, context).map_err(BlueprintError::Params)?;
+
+    // Expand the swarm into per-agent parameters, validating them all up front
+    let agent_params = params.agent_params().map_err(BlueprintError::Params)?;
+    for agent in &agent_params {
+        agent.validate().map_err(BlueprintError::Params)?;
+    }
+
+    // Reject the whole swarm if it doesn't fit the quota, rather than creating part of it
+    let modes = vec![params.agent_config.mode.clone(); agent_params.len()];
+    let _reservation = reserve_agents(context, &modes)?;
+    check_owner_quota(context, agent_params.len())?;
+    logging::info!(
+        swarm = %params.name,
+        agents = agent_params.len(),
+        "Creating swarm"
+    );
+
+    let mut agents = Vec::with_capacity(agent_params.len());
+    for agent in agent_params {
+        match create_agent_from_params(agent, context).await {
+            Ok(result) => {
+                journal::record(context, "create_agent_swarm", &result.agent_id, "created");
+                events::publish(
+                    context,
+                    AgentEvent::Created {
+                        agent_id: result.agent_id.clone(),
+                        call_id: context.call_id,
+                    },
+                );
+                agents.push(result);
+            }
+            Err(e) => {
+                // Don't leave a partial swarm behind
+                logging::error!(
+                    swarm = %params.name,
+                    index = agents.len(),
+                    error = %e,
+                    "Failed to create swarm agent"
+                );
+                for created in &agents {
+                    journal::record(context, "create_agent_swarm", &created.agent_id, "removed");
+                    if let Err(cleanup_error) = remove_agent(&created.agent_id, context) {
+                        logging::warn!(
+                            agent_id = %created.agent_id,
+                            error = %cleanup_error,
+                            "Failed to remove agent"
+                        );
+                    }
+                }
+                return Err(e.context(format!("Failed to create swarm {}", params.name)));
+            }
+        }
+    }
+
+    logging::info!(swarm = %params.name, agents = agents.len(), "Created swarm");
+
+    // Prepare the result
+    let result = AgentSwarmResult { agents };
+
+    // Serialize the result
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
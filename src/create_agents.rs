@@ -0,0 +1,147 @@
+use crate::callbacks;
+use crate::codec;
+use crate::create_agent::{check_owner_quota, create_agent_with_ports, reserve_agents};
+use crate::envelope::decode_signed_job_params;
+use crate::error::BlueprintError;
+use crate::events::{self, AgentEvent};
+use crate::helpers::{fan_out, run_blocking};
+use crate::journal;
+use crate::rate_limit;
+use crate::templates::{self, Template};
+use crate::types::{AgentMode, BatchCreationResult, BatchItemResult, CreateAgentsParams};
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use std::collections::BTreeSet;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Agents of a batch created at once
+const BATCH_CONCURRENCY: usize = 8;
+
+/// Handles the create_agents job
+///
+/// Unlike a swarm, every agent has its own configuration, agents are created concurrently
+/// and one failing doesn't undo the others: each gets its own result or error.
+pub async fn handle_create_agents(
+    params_bytes: Vec<u8>,
+    context: &ServiceContext,
+) -> Result<Vec<u8>, BlueprintError> {
+    rate_limit::check_call(context)?;
+
+    // Deserialize the parameters from bytes, upgrading older versions
+    let (params, encoding): (CreateAgentsParams, _) =
+        decode_signed_job_params(&params_bytes, context).map_err(BlueprintError::Params)?;
+    params.validate().map_err(BlueprintError::Params)?;
+    let count = params.agents.len();
+
+    // Check every template the batch uses and the quotas once for the whole batch, fetching
+    // git templates on first use
+    let template_ids: BTreeSet<String> = params
+        .agents
+        .iter()
+        .map(|agent| agent.template_id().to_string())
+        .collect();
+    run_blocking(move || {
+        for template_id in &template_ids {
+            Template::resolve(Path::new(templates::TEMPLATES_DIR), template_id)?;
+        }
+        Ok(())
+    })
+    .await
+    .map_err(BlueprintError::Params)?;
+    let modes: Vec<AgentMode> = params
+        .agents
+        .iter()
+        .map(|agent| agent.agent_config.mode.clone())
+        .collect();
+    let _reservation = reserve_agents(context, &modes)?;
+    check_owner_quota(context, count)?;
+
+    // Reserve ports for the agents that don't ask for specific ones in a single pass
+    let batch_id = Uuid::new_v4();
+    let holders: Vec<Option<String>> = params
+        .agents
+        .iter()
+        .enumerate()
+        .map(|(index, agent)| {
+            (context.port_allocator.is_some()
+                && agent.deployment_config.target.http_port().is_none())
+            .then(|| format!("batch-{}-{}", batch_id, index))
+        })
+        .collect();
+    if let Some(allocator) = &context.port_allocator {
+        let reserving: Vec<String> = holders.iter().flatten().cloned().collect();
+        allocator
+            .reserve_many(&reserving)
+            .map_err(BlueprintError::Params)?;
+    }
+    logging::info!(agents = count, "Creating batch of agents");
+
+    let results = fan_out(
+        params.agents.into_iter().zip(holders),
+        BATCH_CONCURRENCY,
+        |(agent, holder)| {
+            let context = context.clone();
+            async move {
+                let callback_url = agent.callback_url.clone();
+                let result = create_agent_with_ports(agent, holder.as_deref(), &context).await;
+                if let Some(callback_url) = &callback_url {
+                    callbacks::notify(&context, callback_url, "create_agents", result.as_ref());
+                }
+                // Agents that failed before taking their ports leave them reserved
+                if let (Some(holder), Some(allocator)) = (&holder, &context.port_allocator) {
+                    allocator.unreserve(holder);
+                }
+                result
+            }
+        },
+    )
+    .await;
+
+    let mut items = Vec::with_capacity(count);
+    for (index, result) in results.into_iter().enumerate() {
+        let result = result.unwrap_or_else(|| {
+            Err(BlueprintError::Internal(
+                "Agent creation task failed".to_string(),
+            ))
+        });
+        match result {
+            Ok(agent) => {
+                journal::record(context, "create_agents", &agent.agent_id, "created");
+                events::publish(
+                    context,
+                    AgentEvent::Created {
+                        agent_id: agent.agent_id.clone(),
+                        call_id: context.call_id,
+                    },
+                );
+                items.push(BatchItemResult {
+                    index,
+                    agent: Some(agent),
+                    error: None,
+                });
+            }
+            Err(e) => {
+                logging::error!(index, error = %e, "Failed to create batch agent");
+                items.push(BatchItemResult {
+                    index,
+                    agent: None,
+                    error: Some(e),
+                });
+            }
+        }
+    }
+
+    let created = items.iter().filter(|item| item.agent.is_some()).count();
+    logging::info!(created, failed = count - created, "Created batch of agents");
+
+    // Prepare the result
+    let result = BatchCreationResult {
+        created,
+        failed: count - created,
+        items,
+    };
+
+    // Serialize the result
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
@@ -1,137 +1,340 @@
+use crate::alerts::{send_alert, Alert, AlertKind};
+use crate::audit::{self, SecretAccess};
+use crate::callbacks;
+use crate::checksum;
+use crate::codec::{self, Encoding};
 use crate::docker;
-use crate::helpers::{check_agent_health, get_container_logs};
-use crate::types::{AgentDeploymentResult, DeployAgentParams};
-use crate::ServiceContext;
+use crate::envelope::decode_signed_job_params;
+use crate::error::BlueprintError;
+use crate::events::{self, AgentEvent};
+use crate::exposure;
+use crate::helpers::{
+    check_agent_health, collect_container_diagnostics, compose_command, get_container_logs,
+    parse_env_content, read_env_file, render_env_vars, run_blocking, write_private_file,
+};
+use crate::ingress::sync_ingress;
+use crate::journal;
+use crate::ownership::authorize;
+use crate::payment::verify_payment;
+use crate::rate_limit;
+use crate::retention::write_diagnostics;
+use crate::secret_scan::scan_agent_dir;
+use crate::secrets::read_sealed_secrets;
+use crate::signing::sign_result;
+use crate::telemetry::StageTimer;
+use crate::templates::{self, Template};
+use crate::types::{
+    validate_wallet_secret, AgentDeploymentResult, AgentImage, DeployAgentParams, DeploymentTarget,
+    Secret, TeeConfig, TeeEnvironment, WalletConfig,
+};
+use crate::wallet;
+use crate::warm_pool::{self, StandbyContainer};
+use crate::{AgentPortConfig, ServiceContext};
 use blueprint_sdk::logging;
-use dotenv::dotenv;
-use std::fs;
+use semver::Version;
+use std::collections::HashMap;
 use std::path::Path;
-use tokio::process::Command as TokioCommand;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Handles the deploy_agent job
 pub async fn handle_deploy_agent(
     params_bytes: Vec<u8>,
     context: &ServiceContext,
-) -> Result<Vec<u8>, String> {
-    // Deserialize the parameters from bytes
-    let params: DeployAgentParams = match serde_json::from_slice(&params_bytes) {
-        Ok(p) => p,
-        Err(e) => return Err(format!("Failed to deserialize parameters: {}", e)),
-    };
+) -> Result<Vec<u8>, BlueprintError> {
+    rate_limit::check_call(context)?;
+
+    // Deserialize the parameters from bytes, upgrading older versions
+    let (params, encoding): (DeployAgentParams, _) =
+        decode_signed_job_params(&params_bytes, context).map_err(BlueprintError::Params)?;
+    params.validate().map_err(BlueprintError::Params)?;
+
+    let callback_url = params.callback_url.clone();
+    let result = deploy_agent_from_params(params, context).await;
+    if let Some(callback_url) = &callback_url {
+        let result = result
+            .as_ref()
+            .map(|bytes| serde_json::from_slice::<serde_json::Value>(bytes).unwrap_or_default());
+        callbacks::notify(
+            context,
+            callback_url,
+            "deploy_agent",
+            result.as_ref().map_err(|e| *e),
+        );
+    }
 
+    // The result is built as JSON; re-encode it if the parameters came in as SCALE
+    match encoding {
+        Encoding::Json => result,
+        Encoding::Scale => {
+            let result: AgentDeploymentResult = serde_json::from_slice(&result?).map_err(|e| {
+                BlueprintError::Internal(format!("Failed to deserialize result: {}", e))
+            })?;
+            codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+        }
+    }
+}
+
+/// Deploys an agent once its parameters are decoded and valid
+async fn deploy_agent_from_params(
+    params: DeployAgentParams,
+    context: &ServiceContext,
+) -> Result<Vec<u8>, BlueprintError> {
     // Define base directory from context or environment
-    let base_dir = match &context.agents_base_dir {
-        Some(dir) => dir.clone(),
+    let base_dir = match context.agents_base_dir() {
+        Some(dir) => dir,
         None => "./agents".to_string(),
     };
 
     // Check if agent directory exists
-    let agent_dir = Path::new(&base_dir).join(&params.agent_id);
+    let agent_dir = params.agent_id.dir(&base_dir);
     if !agent_dir.exists() {
-        return Err(format!(
+        return Err(BlueprintError::Params(format!(
             "Agent directory does not exist: {}",
             agent_dir.display()
-        ));
+        )));
+    }
+
+    // Only the agent's owner (or the service owner) may deploy it
+    let env_path = agent_dir.join(".env");
+    let agent_env = run_blocking(move || read_env_file(&env_path))
+        .await
+        .map_err(BlueprintError::Io)?;
+    authorize(context, &params.agent_id, &agent_env).map_err(BlueprintError::Unauthorized)?;
+
+    // Enforce the operator's budget policy before anything is started; templates ship an
+    // empty budget line, so only a positive cap counts
+    let has_budget = agent_env
+        .get(BUDGET_VAR)
+        .and_then(|cap| cap.trim().parse::<f64>().ok())
+        .is_some_and(|cap| cap.is_finite() && cap > 0.0);
+    if context.require_budget.unwrap_or(false) && !has_budget {
+        return Err(BlueprintError::Params(format!(
+            "Operator policy requires a budget, but agent {} was created without one",
+            params.agent_id
+        )));
+    }
+
+    // Refuse agents created from a template version the installed one can't run
+    if let Some(template) = installed_template(&agent_env) {
+        check_template_version(&template, &agent_env).map_err(BlueprintError::Template)?;
+    }
+
+    // Refuse agents whose files changed since they were created, such as a compose file no
+    // longer matching the one a TEE key was bound to
+    run_blocking({
+        let (agent_dir, agent_env) = (agent_dir.clone(), agent_env.clone());
+        move || checksum::verify(&agent_dir, &agent_env)
+    })
+    .await
+    .map_err(BlueprintError::Template)?;
+
+    // Deploy where the agent was created for; agents created before targets were recorded
+    // run in the TEE when given an encrypted environment
+    let target = DeploymentTarget::from_env_vars(&agent_env)
+        .map_err(BlueprintError::Io)?
+        .unwrap_or_else(|| match &params.tee {
+            Some(_) => DeploymentTarget::Tee(TeeConfig::default()),
+            None => DeploymentTarget::default(),
+        });
+    match (&target, &params.tee) {
+        (DeploymentTarget::Tee(_), None) => {
+            return Err(BlueprintError::Params(format!(
+                "Agent {} runs in the TEE, so its encrypted environment is required",
+                params.agent_id
+            )));
+        }
+        (DeploymentTarget::Local { .. }, Some(_)) => {
+            return Err(BlueprintError::Params(format!(
+                "Agent {} runs locally, so it takes no encrypted TEE environment",
+                params.agent_id
+            )));
+        }
+        _ => {}
+    }
+
+    // Don't spend resources on services that haven't paid
+    if context.require_payment.unwrap_or(false) {
+        verify_payment(context, DEPLOY_AGENT_JOB_ID).await?;
+    }
+
+    // With several operators, only the one whose shard the agent falls in deploys it
+    if let Some(coordination) = &context.coordination {
+        let responsible = coordination.responsible_operator(&params.agent_id);
+        if responsible != coordination.operator_index {
+            logging::info!(
+                agent_id = %params.agent_id,
+                phase = "delegate",
+                operator = responsible,
+                "Agent is deployed by another operator, skipping deployment"
+            );
+            let mut result = AgentDeploymentResult {
+                agent_id: params.agent_id.clone(),
+                target,
+                tee_pubkey: params.tee.as_ref().map(|tee| tee.pubkey.expose().clone()),
+                tee_app_id: params.tee.as_ref().map(|tee| tee.app_id.clone()),
+                endpoint_url: None,
+                websocket_url: None,
+                container_name: None,
+                cvm_id: None,
+                deployed_at: unix_now(),
+                deployed_by: Some(responsible),
+                call_id: context.call_id,
+                stage_timings: Vec::new(),
+                public_url: None,
+                wallet_address: None,
+                network_id: None,
+                operator_signature: None,
+            };
+            journal::record(context, "deploy_agent", &params.agent_id, "delegated");
+            sign_result(context, &mut result).map_err(BlueprintError::Internal)?;
+            return serde_json::to_vec(&result).map_err(|e| {
+                BlueprintError::Internal(format!("Failed to serialize result: {}", e))
+            });
+        }
     }
 
-    // Check if this is a TEE deployment - use context directly
-    let tee_enabled = context.tee_enabled.unwrap_or(false);
+    // Wait for a deployment slot, and for any other deployment of this agent to finish
+    let _permit = match &context.deploy_queue {
+        Some(queue) => Some(queue.acquire(&params.agent_id).await),
+        None => None,
+    };
 
-    if tee_enabled {
-        // Deploy to TEE
-        deploy_to_tee(&agent_dir, &params, context).await
-    } else {
-        // Deploy locally with Docker
-        deploy_locally(&agent_dir, &params, context).await
+    let result = match (&target, &params.tee) {
+        (DeploymentTarget::Tee(tee_config), Some(tee)) => {
+            deploy_to_tee(&agent_dir, &params, tee_config, tee, context).await
+        }
+        _ => deploy_locally(&agent_dir, &params, context).await,
+    };
+
+    // Only authorized deployments alert, so callers can't flood the operator's webhook
+    if let Err(e) = &result {
+        let alert = Alert::new(
+            AlertKind::DeploymentFailed,
+            params.agent_id.clone(),
+            context.call_id,
+            e.to_string(),
+        );
+        send_alert(context, alert).await;
     }
+    result
 }
 
 /// Deploy the agent to Phala TEE using TeeDeployer
 async fn deploy_to_tee(
     agent_dir: &Path,
     params: &DeployAgentParams,
+    tee_config: &TeeConfig,
+    tee: &TeeEnvironment,
     context: &ServiceContext,
-) -> Result<Vec<u8>, String> {
-    // Get API key directly from context
-    let tee_api_key = context
-        .phala_tee_api_key
-        .as_ref()
-        .ok_or("PHALA_CLOUD_API_KEY not set")?;
-
-    // Get API endpoint from environment
-    let tee_api_endpoint = context
-        .phala_tee_api_endpoint
-        .as_ref()
-        .ok_or("PHALA_CLOUD_API_ENDPOINT not set")?;
+) -> Result<Vec<u8>, BlueprintError> {
+    let phala_cloud = context.phala_cloud().map_err(BlueprintError::Tee)?;
 
     // Read docker-compose.yml from the agent directory
     let docker_compose_path = agent_dir.join("docker-compose.yml");
-    let docker_compose = fs::read_to_string(&docker_compose_path)
-        .map_err(|e| format!("Failed to read docker-compose.yml: {}", e))?;
+    let docker_compose = tokio::fs::read_to_string(&docker_compose_path)
+        .await
+        .map_err(|e| BlueprintError::Io(format!("Failed to read docker-compose.yml: {}", e)))?;
 
     // Normalize the Docker Compose file to ensure consistent ordering
-    let docker_compose = docker::normalize_docker_compose(&docker_compose)?;
+    let docker_compose =
+        docker::normalize_docker_compose(&docker_compose).map_err(BlueprintError::Template)?;
 
-    // Log for debugging
-    logging::info!("Deploying agent to TEE with normalized Docker compose YAML");
-
-    // Initialize the TeeDeployer
-    logging::info!("Initializing TeeDeployer for deployment");
-    let mut deployer = docker::init_tee_deployer(tee_api_key, tee_api_endpoint)?;
-
-    // Discover an available TEEPod
-    logging::info!("Discovering available TEEPods...");
-    deployer
-        .discover_teepod()
+    // Use the shared TeeDeployer, discovering a TEEPod unless one was found recently
+    let shared_deployer = context.tee_deployer();
+    let mut timer = StageTimer::default();
+    let deployer = timer
+        .run_async(
+            "tee_discovery",
+            &params.agent_id,
+            shared_deployer.get(&phala_cloud.api_key, &phala_cloud.api_endpoint),
+        )
         .await
-        .map_err(|e| format!("Failed to discover TEEPods: {}", e))?;
-
-    // Get the encrypted environment variables - they are already encrypted properly
-    let encrypted_env = params.encrypted_env.as_ref().ok_or_else(|| {
-        "No encrypted environment variables provided for TEE deployment".to_string()
-    })?;
+        .map_err(BlueprintError::Tee)?;
 
     // Create VM configuration using our consistent helper function
-    logging::info!("Creating VM configuration from Docker Compose");
-    let app_name = format!("coinbase-agent-{}", params.agent_id);
-    let vm_config = deployer
-        .create_vm_config(
-            &docker_compose,
-            &app_name,
-            Some(2_u64),    // vcpu
-            Some(2048_u64), // memory in MB
-            Some(10_u64),   // disk size in GB
-        )
-        .map_err(|e| format!("Failed to deploy with VM configuration: {}", e))?;
-    let vm_config_json = serde_json::to_value(vm_config)
-        .map_err(|e| format!("Failed to serialize VM configuration: {}", e))?;
-    logging::info!(
-        "Deploying agent to TEE with VM configuration: {:#?}",
-        vm_config_json
-    );
-
-    let pubkey = params.tee_pubkey.as_ref().unwrap();
-    let salt = params.tee_salt.as_ref().unwrap();
-    let app_id = params.tee_app_id.as_ref().unwrap();
+    logging::info!(agent_id = %params.agent_id, phase = "tee", "Creating VM configuration from Docker Compose");
+    let app_name = params.agent_id.container_name();
+    let vm_config_json = docker::tee_vm_config(&deployer, &docker_compose, &app_name, tee_config)
+        .map_err(BlueprintError::Tee)?;
+    logging::debug!(agent_id = %params.agent_id, phase = "tee", vm_config = %vm_config_json, "VM configuration");
 
     // Deploy with the VM configuration and encrypted environment variables
-    logging::info!("Deploying agent to TEE with encrypted environment variables");
-    let deployment = deployer
-        .deploy_with_encrypted_env(vm_config_json, encrypted_env.clone(), &pubkey, &salt)
-        .await
-        .map_err(|e| format!("Failed to deploy to TEE: {}", e))?;
+    logging::info!(
+        agent_id = %params.agent_id,
+        phase = "tee",
+        app = %app_name,
+        "Deploying agent to TEE with encrypted environment variables"
+    );
+    audit::record(
+        context,
+        &params.agent_id,
+        SecretAccess::Inject,
+        ["encrypted_env"],
+        "tee",
+    )
+    .map_err(BlueprintError::Io)?;
+    let deployment = timer
+        .run_async(
+            "tee_deploy",
+            &params.agent_id,
+            deployer.deploy_with_encrypted_env(
+                vm_config_json,
+                tee.encrypted_env.expose().clone(),
+                tee.pubkey.expose(),
+                tee.salt.expose(),
+            ),
+        )
+        .await;
+    drop(deployer);
+    let deployment = match deployment {
+        Ok(deployment) => deployment,
+        Err(e) => {
+            // The TEEPod may be gone, so look for another one next time
+            shared_deployer.invalidate().await;
+            return Err(BlueprintError::Tee(format!(
+                "Failed to deploy to TEE: {}",
+                e
+            )));
+        }
+    };
 
-    logging::info!("TEE deployment completed. Deployment: {:#?}", deployment);
+    logging::info!(agent_id = %params.agent_id, phase = "tee", app_id = %tee.app_id, "TEE deployment completed");
+    logging::debug!(agent_id = %params.agent_id, phase = "tee", "TEE deployment: {:?}", deployment);
+    events::publish(
+        context,
+        AgentEvent::Deployed {
+            agent_id: params.agent_id.clone(),
+            call_id: context.call_id,
+            endpoint: None,
+            tee_app_id: Some(tee.app_id.clone()),
+        },
+    );
 
     // Prepare the deployment result
-    let result = AgentDeploymentResult {
+    let mut result = AgentDeploymentResult {
         agent_id: params.agent_id.clone(),
-        tee_pubkey: Some(pubkey.clone()),
-        tee_app_id: Some(app_id.clone()),
+        target: DeploymentTarget::Tee(tee_config.clone()),
+        tee_pubkey: Some(tee.pubkey.expose().clone()),
+        tee_app_id: Some(tee.app_id.clone()),
+        endpoint_url: None,
+        websocket_url: None,
+        container_name: None,
+        cvm_id: Some(deployment.id),
+        deployed_at: unix_now(),
+        deployed_by: context.coordination.as_ref().map(|c| c.operator_index),
+        call_id: context.call_id,
+        stage_timings: timer.into_timings(),
+        public_url: None,
+        wallet_address: None,
+        network_id: None,
+        operator_signature: None,
     };
+    journal::record(context, "deploy_agent", &params.agent_id, "deployed");
+    sign_result(context, &mut result).map_err(BlueprintError::Internal)?;
 
     // Serialize the result
-    serde_json::to_vec(&result).map_err(|e| format!("Failed to serialize result: {}", e))
+    serde_json::to_vec(&result)
+        .map_err(|e| BlueprintError::Internal(format!("Failed to serialize result: {}", e)))
 }
 
 /// Deploy the agent locally using Docker Compose
@@ -139,106 +342,577 @@ async fn deploy_locally(
     agent_dir: &Path,
     params: &DeployAgentParams,
     context: &ServiceContext,
-) -> Result<Vec<u8>, String> {
-    // Load .env file if it exists
-    dotenv().ok();
-
+) -> Result<Vec<u8>, BlueprintError> {
     // Create a unique container name using agent ID
-    let container_name = format!("coinbase-agent-{}", params.agent_id);
-    logging::info!("Using container name: {}", container_name);
+    let container_name = params.agent_id.container_name();
 
     // Get port configuration - strict checking from context
-    let (http_port, websocket_port) = get_required_ports(&params.agent_id, context)?;
+    let (http_port, websocket_port) =
+        get_required_ports(&params.agent_id, context).map_err(BlueprintError::Params)?;
+    let mut timer = StageTimer::default();
     logging::info!(
-        "Using ports - HTTP: {}, WebSocket: {}",
+        agent_id = %params.agent_id,
+        phase = "configure",
+        container = %container_name,
         http_port,
-        websocket_port
+        websocket_port,
+        "Deploying agent locally"
     );
 
     // Note: Container cleanup is now expected to be handled by the tests
 
-    // Create a .env file with required configurations
+    // Create a .env file with required configurations, keeping the settings chosen at creation.
+    // Secrets are kept out of it and only handed to docker-compose through its environment.
     let env_file_path = agent_dir.join(".env");
-    logging::info!("Creating .env file at: {}", env_file_path.display());
-    let env_content = create_env_content(http_port, websocket_port, &container_name, params)?;
+    let agent_env = run_blocking({
+        let env_file_path = env_file_path.clone();
+        move || read_env_file(&env_file_path)
+    })
+    .await
+    .map_err(BlueprintError::Io)?;
+    let (sealed, wallet_data) = match &context.sealing_key {
+        Some(key) => {
+            let (key, agent_dir) = (key.clone(), agent_dir.to_path_buf());
+            run_blocking(move || {
+                Ok((
+                    read_sealed_secrets(&agent_dir, &key)?,
+                    wallet::read_sealed(&agent_dir, &key)?,
+                ))
+            })
+            .await
+            .map_err(BlueprintError::Secrets)?
+        }
+        None => (HashMap::new(), None),
+    };
+    audit::record(
+        context,
+        &params.agent_id,
+        SecretAccess::Read,
+        sealed.keys().cloned(),
+        "sealed",
+    )
+    .map_err(BlueprintError::Io)?;
+    logging::info!(
+        agent_id = %params.agent_id,
+        phase = "configure",
+        path = %env_file_path.display(),
+        "Writing .env file"
+    );
+    let deployment_env = create_env_content(
+        http_port,
+        websocket_port,
+        &container_name,
+        params,
+        &agent_env,
+        &sealed,
+    )
+    .map_err(BlueprintError::Params)?;
+    let template = installed_template(&agent_env);
+    if let Some(template) = &template {
+        check_template_env(template, &deployment_env).map_err(BlueprintError::Params)?;
+    }
 
     // Write the .env file
-    fs::write(&env_file_path, env_content)
-        .map_err(|e| format!("Failed to write .env file: {}", e))?;
-    logging::info!(".env file written successfully");
+    run_blocking({
+        let content = deployment_env.content.clone();
+        move || write_private_file(&env_file_path, content)
+    })
+    .await
+    .map_err(BlueprintError::Io)?;
+
+    // Fetch secrets given as references (e.g. `vault://kv/agents/openai`) only now
+    let mut secrets = match &context.secret_resolvers {
+        Some(resolvers) => timer
+            .run_async(
+                "secret_resolution",
+                &params.agent_id,
+                resolvers.resolve_all(deployment_env.secrets),
+            )
+            .await
+            .map_err(BlueprintError::Secrets)?,
+        None => deployment_env.secrets,
+    };
+
+    // Wallet keys are given as references, so they can only be checked once resolved
+    for (var, value) in &secrets {
+        if WALLET_KEY_VARS.contains(&var.as_str()) {
+            validate_wallet_secret(value.expose())
+                .map_err(|e| BlueprintError::Params(format!("{}: {}", var, e)))?;
+        }
+    }
+
+    // Restore the wallet captured from an earlier deployment rather than creating a new one
+    if let Some(wallet_data) = wallet_data {
+        secrets.push((wallet::WALLET_DATA_VAR.to_string(), wallet_data));
+    }
+
+    // Refuse to ship keys that leaked into the files the agent image is built from
+    let known_secrets: Vec<&str> = secrets
+        .iter()
+        .map(|(_, value)| value.expose().as_str())
+        .collect();
+    let scan = timer
+        .run("secret_scan", &params.agent_id, || {
+            scan_agent_dir(agent_dir, &known_secrets)
+        })
+        .map_err(BlueprintError::Secrets)?;
+    if !scan.is_clean() {
+        return Err(BlueprintError::Secrets(format!(
+            "Deployment blocked. {}",
+            scan
+        )));
+    }
+
+    audit::record(
+        context,
+        &params.agent_id,
+        SecretAccess::Inject,
+        secrets.iter().map(|(var, _)| var.clone()),
+        "docker-compose",
+    )
+    .map_err(BlueprintError::Io)?;
 
     // Verify docker-compose.yml exists
     let compose_path = agent_dir.join("docker-compose.yml");
     if !compose_path.exists() {
-        return Err(format!(
+        return Err(BlueprintError::Template(format!(
             "docker-compose.yml not found at {}",
             compose_path.display()
-        ));
+        )));
     }
 
-    // Start the Docker container with explicit DOCKER_IMAGE env var
-    logging::info!("Starting Docker container with image: tanglenetwork/coinbase-agent:latest");
-    let mut command = TokioCommand::new("docker-compose");
-    command
-        .args(&["up", "-d"])
-        .current_dir(agent_dir)
-        .env("DOCKER_IMAGE", "tanglenetwork/coinbase-agent:latest");
+    // Post-create hooks may have edited the file, so check it again before it runs
+    run_blocking({
+        let compose_path = compose_path.clone();
+        move || docker::check_compose_file(&compose_path)
+    })
+    .await
+    .map_err(BlueprintError::Template)?;
 
-    let output = command
-        .output()
-        .await
-        .map_err(|e| format!("Failed to start Docker container: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Failed to start Docker container: {}", stderr));
-    }
-    logging::info!("Container started successfully");
+    // Start a standby container of the warm pool if one is ready, instead of creating one.
+    // Standby containers run the pool's image and publish a WebSocket port next to the HTTP
+    // one, so agents of templates with another runtime, with their own image or with another
+    // layout always get their own container. Standby containers mount a wallet directory of
+    // their own, which the agent's is linked to when claimed.
+    let own_image = agent_env.contains_key(AgentImage::IMAGE_VAR)
+        || agent_env.contains_key(AgentImage::DOCKERFILE_VAR);
+    let pooled_template = template
+        .as_ref()
+        .is_some_and(|template| template.manifest.warm_pool);
+    let standby = match &context.warm_pool {
+        Some(pool)
+            if pooled_template
+                && !own_image
+                && warm_pool::supports_compose(&compose_path)
+                && websocket_port == http_port.checked_add(1) =>
+        {
+            pool.claim(&params.agent_id, context)
+                .map_err(BlueprintError::Internal)?
+        }
+        _ => None,
+    };
+    let (http_port, websocket_port) = match standby {
+        Some(standby) => {
+            let sources = EnvSources {
+                agent_env: &agent_env,
+                sealed: &sealed,
+                secrets: &secrets,
+            };
+            start_standby_container(
+                &standby,
+                &container_name,
+                agent_dir,
+                params,
+                &sources,
+                context,
+                &mut timer,
+            )
+            .await?;
+            (standby.ports.http_port, standby.ports.websocket_port)
+        }
+        None => {
+            start_compose_container(agent_dir, params, &secrets, context, &mut timer).await?;
+            (http_port, websocket_port)
+        }
+    };
+    logging::info!(agent_id = %params.agent_id, phase = "start", "Container started");
 
     // For local deployments, use localhost
     let endpoint = format!("http://localhost:{}", http_port);
 
     // Check if the agent is healthy - this function now includes initial delay and retry logic
-    if let Err(health_error) = check_agent_health(&endpoint).await {
-        logging::error!("Agent health check failed: {}", health_error);
+    let agent = context
+        .agent_endpoint(&endpoint)
+        .map_err(BlueprintError::Internal)?;
+    if let Err(health_error) = timer
+        .run_async(
+            "health_wait",
+            &params.agent_id,
+            check_agent_health(&agent, &context.http_config()),
+        )
+        .await
+    {
+        logging::error!(
+            agent_id = %params.agent_id,
+            phase = "health",
+            error = %health_error,
+            "Agent health check failed"
+        );
 
-        // Get container logs for diagnosis - note: this is a synchronous function
-        match get_container_logs(&container_name) {
+        // Get container logs for diagnosis, off the async executor
+        let logs = run_blocking({
+            let container_name = container_name.clone();
+            move || get_container_logs(&container_name)
+        })
+        .await;
+        match logs {
             Ok(logs) => {
-                logging::error!("Container logs:");
-                // Split and log each line individually for better readability in logs
+                // Log each line individually so that pipelines keep them with the agent
                 for line in logs.lines().take(20) {
-                    logging::error!("  | {}", line);
+                    logging::error!(agent_id = %params.agent_id, phase = "health", "Container log: {}", line);
                 }
             }
-            Err(e) => logging::error!("Failed to get logs: {}", e),
+            Err(e) => logging::error!(
+                agent_id = %params.agent_id,
+                phase = "health",
+                error = %e,
+                "Failed to get container logs"
+            ),
         }
 
-        return Err(format!("Deployment failed: {}", health_error));
+        // Keep the container's state and logs next to the agent for later inspection
+        let diagnostics = run_blocking({
+            let (container_name, agent_dir) = (container_name.clone(), agent_dir.to_path_buf());
+            move || {
+                let diagnostics = collect_container_diagnostics(&container_name);
+                write_diagnostics(&agent_dir, "health-check.log", diagnostics)
+            }
+        })
+        .await;
+        match diagnostics {
+            Ok(path) => logging::info!(
+                agent_id = %params.agent_id,
+                phase = "health",
+                path = %path.display(),
+                "Wrote diagnostics"
+            ),
+            Err(e) => logging::warn!(
+                agent_id = %params.agent_id,
+                phase = "health",
+                error = %e,
+                "Failed to write diagnostics"
+            ),
+        }
+
+        events::publish(
+            context,
+            AgentEvent::Unhealthy {
+                agent_id: params.agent_id.clone(),
+                call_id: context.call_id,
+                detail: health_error.clone(),
+            },
+        );
+        return Err(BlueprintError::Health(format!(
+            "Deployment failed: {}",
+            health_error
+        )));
     }
 
-    logging::info!("Agent is healthy and ready for use at {}", endpoint);
+    // Keep probing the agent and restart it if it stops responding
+    if let Some(monitor) = &context.health_monitor {
+        monitor.watch(context, &params.agent_id, endpoint.clone());
+    }
+
+    // Report the agent's wallet, so callers can fund it. Templates predating `GET /wallet`
+    // still deploy, without it.
+    let wallet = match agent
+        .get_wallet(context.http_config().request_timeout)
+        .await
+    {
+        Ok(wallet) => Some(wallet),
+        Err(e) => {
+            logging::warn!(agent_id = %params.agent_id, phase = "health", error = %e, "Failed to get the agent's wallet");
+            None
+        }
+    };
+
+    // Seal the wallet the agent started with, so redeploys restore it. The agent is already
+    // healthy, so it keeps running if this fails; the wallet is captured again when it stops.
+    if let Some(key) = &context.sealing_key {
+        let captured = run_blocking({
+            let (key, agent_dir) = (key.clone(), agent_dir.to_path_buf());
+            move || wallet::capture(&agent_dir, &key, false)
+        })
+        .await;
+        match captured {
+            Ok(true) => {
+                logging::info!(agent_id = %params.agent_id, phase = "health", "Captured wallet data")
+            }
+            Ok(false) => logging::warn!(
+                agent_id = %params.agent_id,
+                phase = "health",
+                "Agent wrote no wallet data; its wallet won't survive a redeploy"
+            ),
+            Err(e) => {
+                logging::warn!(agent_id = %params.agent_id, phase = "health", error = %e, "Failed to capture wallet data")
+            }
+        }
+    }
+
+    // Serve the agent through the TLS proxy or the router rather than its raw port, if there is one
+    // The router only forwards HTTP, so WebSockets are still reached on the agent's own port
+    let (endpoint, websocket_url) = match &context.ingress {
+        Some(ingress) => {
+            sync_ingress(context, ingress)
+                .await
+                .map_err(BlueprintError::Docker)?;
+            (
+                ingress.agent_url(&params.agent_id),
+                websocket_port.map(|_| ingress.agent_websocket_url(&params.agent_id)),
+            )
+        }
+        None => (
+            context
+                .agent_router
+                .as_ref()
+                .and_then(|router| router.agent_url(&params.agent_id))
+                .unwrap_or(endpoint),
+            websocket_port.map(|port| format!("ws://localhost:{}", port)),
+        ),
+    };
+    logging::info!(
+        agent_id = %params.agent_id,
+        phase = "health",
+        endpoint = %endpoint,
+        "Agent is healthy and ready for use"
+    );
+
+    // Reach agents on hosts behind NAT through a tunnel; the agent works locally regardless
+    let public_url = match &context.exposure {
+        Some(exposure) => {
+            let exposed = timer
+                .run_async(
+                    "expose",
+                    &params.agent_id,
+                    exposure::expose(context, exposure, &params.agent_id, http_port),
+                )
+                .await;
+            match exposed {
+                Ok(url) => {
+                    logging::info!(agent_id = %params.agent_id, phase = "expose", url = %url, "Exposed agent");
+                    Some(url)
+                }
+                Err(e) => {
+                    logging::warn!(agent_id = %params.agent_id, phase = "expose", error = %e, "Failed to expose agent");
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+    events::publish(
+        context,
+        AgentEvent::Deployed {
+            agent_id: params.agent_id.clone(),
+            call_id: context.call_id,
+            endpoint: Some(endpoint.clone()),
+            tee_app_id: None,
+        },
+    );
 
     // Prepare the deployment result
-    let result = AgentDeploymentResult {
+    let mut result = AgentDeploymentResult {
         agent_id: params.agent_id.clone(),
+        target: DeploymentTarget::Local {
+            http_port: Some(http_port),
+            websocket_port: AgentPortConfig {
+                http_port,
+                websocket_port,
+            }
+            .websocket(),
+        },
         tee_pubkey: None,
         tee_app_id: None,
+        endpoint_url: Some(endpoint),
+        websocket_url,
+        container_name: Some(container_name),
+        cvm_id: None,
+        deployed_at: unix_now(),
+        deployed_by: context.coordination.as_ref().map(|c| c.operator_index),
+        call_id: context.call_id,
+        stage_timings: timer.into_timings(),
+        public_url,
+        wallet_address: wallet.as_ref().map(|wallet| wallet.address.clone()),
+        network_id: wallet.map(|wallet| wallet.network_id),
+        operator_signature: None,
     };
+    journal::record(context, "deploy_agent", &params.agent_id, "deployed");
+    sign_result(context, &mut result).map_err(BlueprintError::Internal)?;
 
     // Serialize the result
-    serde_json::to_vec(&result).map_err(|e| format!("Failed to serialize result: {}", e))
+    serde_json::to_vec(&result)
+        .map_err(|e| BlueprintError::Internal(format!("Failed to serialize result: {}", e)))
 }
 
-/// Get required ports from context
-fn get_required_ports(agent_id: &str, context: &ServiceContext) -> Result<(u16, u16), String> {
-    // Only get ports from the agent_ports map in context
-    if let Some(agent_ports) = &context.agent_ports {
-        if let Ok(ports_map) = agent_ports.lock() {
-            if let Some(port_config) = ports_map.get(agent_id) {
-                return Ok((port_config.http_port, port_config.websocket_port));
+/// Settings and secrets an agent's environment is built from
+struct EnvSources<'a> {
+    agent_env: &'a HashMap<String, String>,
+    sealed: &'a HashMap<String, Secret<String>>,
+    secrets: &'a [(String, Secret<String>)],
+}
+
+/// Creates and starts the agent's container with docker-compose
+async fn start_compose_container(
+    agent_dir: &Path,
+    params: &DeployAgentParams,
+    secrets: &[(String, Secret<String>)],
+    context: &ServiceContext,
+    timer: &mut StageTimer,
+) -> Result<(), BlueprintError> {
+    // Run docker-compose with explicit DOCKER_IMAGE env var, and none of the operator's
+    // environment besides what reaches Docker
+    let compose = |args: &[&str]| {
+        let mut command = compose_command(agent_dir);
+        command
+            .args(args)
+            .env("DOCKER_IMAGE", "tanglenetwork/coinbase-agent:latest")
+            .env("AGENT_BIND_ADDRESS", context.agent_bind_address())
+            .envs(secrets.iter().map(|(var, value)| (var, value.expose())));
+        command
+    };
+
+    // Pull or build the image and create the container first, so that starting it is
+    // timed on its own
+    logging::info!(
+        agent_id = %params.agent_id,
+        phase = "start",
+        image = "tanglenetwork/coinbase-agent:latest",
+        "Preparing Docker image"
+    );
+    let output = timer
+        .run_async(
+            "image_pull",
+            &params.agent_id,
+            compose(&["up", "--no-start"]).output(),
+        )
+        .await
+        .map_err(|e| {
+            BlueprintError::Docker(format!("Failed to prepare Docker container: {}", e))
+        })?;
+    if !output.status.success() {
+        return Err(BlueprintError::Docker(format!(
+            "Failed to prepare Docker container: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    logging::info!(agent_id = %params.agent_id, phase = "start", "Starting Docker container");
+    let output = timer
+        .run_async(
+            "container_start",
+            &params.agent_id,
+            compose(&["up", "-d"]).output(),
+        )
+        .await
+        .map_err(|e| BlueprintError::Docker(format!("Failed to start Docker container: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        events::publish(
+            context,
+            AgentEvent::Stopped {
+                agent_id: params.agent_id.clone(),
+                call_id: context.call_id,
+                detail: "Container failed to start".to_string(),
+            },
+        );
+        return Err(BlueprintError::Docker(format!(
+            "Failed to start Docker container: {}",
+            stderr
+        )));
+    }
+    Ok(())
+}
+
+/// Starts a standby container of the warm pool as the agent's container
+///
+/// The container keeps the ports it was created with, which the agent now holds, so its
+/// environment is rebuilt for them. A replacement is created in the background.
+async fn start_standby_container(
+    standby: &StandbyContainer,
+    container_name: &str,
+    agent_dir: &Path,
+    params: &DeployAgentParams,
+    sources: &EnvSources<'_>,
+    context: &ServiceContext,
+    timer: &mut StageTimer,
+) -> Result<(), BlueprintError> {
+    logging::info!(
+        agent_id = %params.agent_id,
+        phase = "start",
+        standby = %standby.container_name,
+        http_port = standby.ports.http_port,
+        "Starting standby container"
+    );
+    let deployment_env = create_env_content(
+        standby.ports.http_port,
+        standby.ports.websocket_port,
+        container_name,
+        params,
+        sources.agent_env,
+        sources.sealed,
+    )
+    .map_err(BlueprintError::Params)?;
+    let secret_vars: Vec<(String, String)> = sources
+        .secrets
+        .iter()
+        .map(|(var, value)| (var.clone(), value.expose().clone()))
+        .collect();
+    let env_content = deployment_env.content + &render_env_vars(&secret_vars);
+
+    let started = timer
+        .run_async(
+            "container_start",
+            &params.agent_id,
+            warm_pool::start_standby(standby, container_name, agent_dir, env_content),
+        )
+        .await;
+    if let Err(e) = started {
+        events::publish(
+            context,
+            AgentEvent::Stopped {
+                agent_id: params.agent_id.clone(),
+                call_id: context.call_id,
+                detail: "Standby container failed to start".to_string(),
+            },
+        );
+        return Err(BlueprintError::Docker(e));
+    }
+
+    // Replace the claimed container in the background
+    if let Some(pool) = context.warm_pool.clone() {
+        let context = context.clone();
+        tokio::spawn(async move {
+            if let Err(e) = pool.fill(&context).await {
+                logging::warn!(error = %e, "Failed to refill the warm pool");
             }
-        }
+        });
+    }
+    Ok(())
+}
+
+/// Get required ports from context
+pub(crate) fn get_required_ports(
+    agent_id: &str,
+    context: &ServiceContext,
+) -> Result<(u16, Option<u16>), String> {
+    // Only get ports from the agent registry in context
+    if let Some(port_config) = context
+        .agent_registry
+        .as_ref()
+        .and_then(|registry| registry.get(agent_id))
+    {
+        return Ok((port_config.http_port, port_config.websocket_port));
     }
 
     // If we get here, no ports were found
@@ -248,64 +922,320 @@ fn get_required_ports(agent_id: &str, context: &ServiceContext) -> Result<(u16,
     ))
 }
 
+/// Environment of a local deployment
+pub(crate) struct DeploymentEnv {
+    /// Contents of the agent's .env file, free of secrets
+    pub(crate) content: String,
+    /// API keys and tokens, passed to docker-compose without touching the disk
+    pub(crate) secrets: Vec<(String, Secret<String>)>,
+}
+
+/// Returns the template an agent was created from, if it is still installed
+///
+/// Agents whose template is no longer installed, or whose git template is no longer cached,
+/// are deployed without checking them against it.
+fn installed_template(agent_env: &HashMap<String, String>) -> Option<Template> {
+    let template_id = agent_env
+        .get(templates::TEMPLATE_VAR)
+        .map(String::as_str)
+        .unwrap_or(templates::DEFAULT_TEMPLATE);
+    match Template::installed(Path::new(templates::TEMPLATES_DIR), template_id) {
+        Ok(template) => Some(template),
+        Err(e) => {
+            logging::warn!(
+                template = template_id,
+                error = %e,
+                "Skipping the template checks"
+            );
+            None
+        }
+    }
+}
+
+/// Checks that the installed template is compatible with the version the agent was created
+/// from; agents created before template versions were recorded always are
+fn check_template_version(
+    template: &Template,
+    agent_env: &HashMap<String, String>,
+) -> Result<(), String> {
+    match agent_env.get(templates::TEMPLATE_VERSION_VAR) {
+        Some(version) => {
+            let version = Version::parse(version)
+                .map_err(|e| format!("Invalid {}: {}", templates::TEMPLATE_VERSION_VAR, e))?;
+            template.check_compatible(&version)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Checks that the variables the agent's template requires are set in its environment
+fn check_template_env(template: &Template, env: &DeploymentEnv) -> Result<(), String> {
+    let vars = parse_env_content(&env.content);
+    let wallet_type = vars
+        .get("WALLET_TYPE")
+        .map(String::as_str)
+        .unwrap_or(WalletConfig::default().env_value());
+    let missing = template.missing_env(wallet_type, |var| {
+        vars.get(var).is_some_and(|value| !value.is_empty())
+            || env
+                .secrets
+                .iter()
+                .any(|(name, value)| name == var && !value.expose().is_empty())
+    });
+    if !missing.is_empty() {
+        return Err(format!(
+            "Template {} requires {} to be set",
+            template.id,
+            missing.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the current unix timestamp in seconds
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// ID of the `deploy_agent` job, whose calls are checked for payment
+const DEPLOY_AGENT_JOB_ID: u8 = 1;
+
+/// Variable written by `create_agent` when the agent has a budget
+const BUDGET_VAR: &str = "BUDGET_DAILY_CAP";
+
+/// Keys owning an agent's wallet, given to it by reference
+const WALLET_KEY_VARS: &[&str] = &["CDP_WALLET_SECRET", "WALLET_PRIVATE_KEY"];
+
+/// Agent settings chosen at creation time that are carried over into the deployment .env
+const CARRIED_OVER_VARS: &[&str] = &[
+    "OPENAI_BASE_URL",
+    "AZURE_OPENAI_ENDPOINT",
+    "AZURE_OPENAI_API_DEPLOYMENT_NAME",
+    "AZURE_OPENAI_API_VERSION",
+    "SYSTEM_PROMPT",
+    "TEMPERATURE",
+    "MAX_TOKENS",
+    "ACTION_PROVIDERS",
+    "NETWORK_ID",
+    "WALLET_TYPE",
+    "PAYMASTER_URL",
+    "AUTONOMOUS_INTERVAL_SECS",
+    "AUTONOMOUS_ACTIVE_HOURS",
+    "AUTONOMOUS_MAX_ACTIONS_PER_DAY",
+    "BUDGET_MAX_TRANSACTION_VALUE",
+    "BUDGET_DAILY_CAP",
+    "BUDGET_ALLOWED_ASSETS",
+    "ALLOWED_ACTIONS",
+    templates::TEMPLATE_VAR,
+    templates::TEMPLATE_VERSION_VAR,
+    AgentImage::IMAGE_VAR,
+    AgentImage::DOCKERFILE_VAR,
+    checksum::COMPOSE_CHECKSUM_VAR,
+    checksum::FILES_CHECKSUM_VAR,
+];
+
 /// Helper function to create the environment content for the agent
-fn create_env_content(
+///
+/// `agent_env` holds the variables written by `create_agent`; the LLM provider, model
+/// and other settings chosen at creation time are carried over from it. `sealed` holds the
+/// API keys and bot tokens sealed at creation. Secrets are returned separately from the
+/// .env contents.
+pub(crate) fn create_env_content(
     port: u16,
-    websocket_port: u16,
+    websocket_port: Option<u16>,
     container_name: &str,
     params: &DeployAgentParams,
-) -> Result<String, String> {
+    agent_env: &HashMap<String, String>,
+    sealed: &HashMap<String, Secret<String>>,
+) -> Result<DeploymentEnv, String> {
     // Get API config or fail early
     let api_config = params
         .api_key_config
         .as_ref()
         .ok_or_else(|| "API key configuration is required".to_string())?;
 
-    // Get required API keys or fail
-    let openai_api_key = api_config
-        .openai_api_key
-        .as_ref()
-        .map(|s| s.to_string())
-        .or_else(|| std::env::var("OPENAI_API_KEY").ok())
-        .ok_or_else(|| "OPENAI_API_KEY not found in config or environment".to_string())?;
+    // Provider and model selected when the agent was created
+    let llm_provider = agent_env
+        .get("LLM_PROVIDER")
+        .cloned()
+        .unwrap_or_else(|| "openai".to_string());
+    let model = agent_env
+        .get("MODEL")
+        .cloned()
+        .unwrap_or_else(|| "gpt-4o-mini".to_string());
 
-    let cdp_api_key_name = api_config
-        .cdp_api_key_name
-        .as_ref()
-        .map(|s| s.to_string())
-        .or_else(|| std::env::var("CDP_API_KEY_NAME").ok())
-        .ok_or_else(|| "CDP_API_KEY_NAME not found in config or environment".to_string())?;
+    // Resolve a key of the agent's own from the deploy params, its sealed secrets or its .env
+    let resolve_agent_key = |value: &Option<Secret<String>>, var: &str| {
+        value
+            .clone()
+            .or_else(|| sealed.get(var).filter(|v| !v.expose().is_empty()).cloned())
+            .or_else(|| {
+                agent_env
+                    .get(var)
+                    .filter(|v| !v.is_empty())
+                    .map(|v| Secret::new(v.clone()))
+            })
+    };
+    // API keys may also be shared ones from the operator's environment
+    let resolve_key = |value: &Option<Secret<String>>, var: &str| {
+        resolve_agent_key(value, var).or_else(|| std::env::var(var).ok().map(Secret::new))
+    };
 
-    let cdp_api_key_private_key = api_config
-        .cdp_api_key_private_key
-        .as_ref()
-        .map(|s| s.to_string())
-        .or_else(|| std::env::var("CDP_API_KEY_PRIVATE_KEY").ok())
-        .ok_or_else(|| "CDP_API_KEY_PRIVATE_KEY not found in config or environment".to_string())?;
+    // Get the LLM API keys, requiring the one for the selected provider
+    let llm_keys = [
+        (
+            "openai",
+            "OPENAI_API_KEY",
+            resolve_key(&api_config.openai_api_key, "OPENAI_API_KEY"),
+        ),
+        (
+            "anthropic",
+            "ANTHROPIC_API_KEY",
+            resolve_key(&api_config.anthropic_api_key, "ANTHROPIC_API_KEY"),
+        ),
+        (
+            "azureopenai",
+            "AZURE_OPENAI_API_KEY",
+            resolve_key(&api_config.azure_openai_api_key, "AZURE_OPENAI_API_KEY"),
+        ),
+    ];
+    for (provider, var, value) in &llm_keys {
+        if *provider == llm_provider && value.is_none() {
+            return Err(format!("{} not found in config or environment", var));
+        }
+    }
 
-    // Validate keys are not empty
-    if cdp_api_key_name.trim().is_empty() {
-        return Err("CDP_API_KEY_NAME is empty".to_string());
+    // Autonomous and bot modes chosen at creation time are kept; everything else serves HTTP
+    let agent_mode = agent_env
+        .get("AGENT_MODE")
+        .filter(|mode| matches!(mode.as_str(), "autonomous" | "telegram" | "discord"))
+        .cloned()
+        .unwrap_or_else(|| "http".to_string());
+    let bot_tokens = [
+        (
+            "telegram",
+            "TELEGRAM_BOT_TOKEN",
+            resolve_key(&api_config.telegram_bot_token, "TELEGRAM_BOT_TOKEN"),
+        ),
+        (
+            "discord",
+            "DISCORD_BOT_TOKEN",
+            resolve_key(&api_config.discord_bot_token, "DISCORD_BOT_TOKEN"),
+        ),
+    ];
+    for (mode, var, value) in &bot_tokens {
+        if *mode == agent_mode && value.is_none() {
+            return Err(format!("{} not found in config or environment", var));
+        }
     }
-    if cdp_api_key_private_key.trim().is_empty() {
-        return Err("CDP_API_KEY_PRIVATE_KEY is empty".to_string());
+
+    // Smart wallets and private-key accounts are owned by a key of their own rather than
+    // derived from the CDP API key, which private-key accounts don't need at all. Wallet keys
+    // are never taken from the operator's environment, which would hand its wallet out
+    let wallet_type = agent_env.get("WALLET_TYPE").map(String::as_str);
+    let wallet_key = match wallet_type {
+        Some("smart_wallet") => Some((
+            "CDP_WALLET_SECRET",
+            resolve_agent_key(&api_config.cdp_wallet_secret, "CDP_WALLET_SECRET"),
+        )),
+        Some("private_key") => Some((
+            "WALLET_PRIVATE_KEY",
+            resolve_agent_key(&api_config.wallet_private_key, "WALLET_PRIVATE_KEY"),
+        )),
+        _ => None,
+    };
+    let wallet_key = match wallet_key {
+        Some((var, key)) => Some((
+            var,
+            key.ok_or_else(|| format!("{} not found in config or sealed secrets", var))?,
+        )),
+        None => None,
+    };
+
+    let mut cdp_api_keys = Vec::new();
+    for (var, config) in [
+        ("CDP_API_KEY_NAME", &api_config.cdp_api_key_name),
+        (
+            "CDP_API_KEY_PRIVATE_KEY",
+            &api_config.cdp_api_key_private_key,
+        ),
+    ] {
+        match resolve_key(config, var) {
+            // Validate keys are not empty
+            Some(key) if key.expose().trim().is_empty() => {
+                return Err(format!("{} is empty", var));
+            }
+            Some(key) => cdp_api_keys.push((var, key)),
+            None if wallet_type == Some("private_key") => {}
+            None => return Err(format!("{} not found in config or environment", var)),
+        }
     }
 
     // Build environment content with all required variables
-    let env_content = format!(
-        "PORT={port}\n\
-         WEBSOCKET_PORT={websocket_port}\n\
-         CONTAINER_NAME={container_name}\n\
-         NODE_ENV=development\n\
-         AGENT_MODE=http\n\
-         MODEL=gpt-4o-mini\n\
-         LOG_LEVEL=debug\n\
-         WEBSOCKET_URL=ws://localhost:{websocket_port}\n\
-         OPENAI_API_KEY={openai_api_key}\n\
-         CDP_API_KEY_NAME={cdp_api_key_name}\n\
-         CDP_API_KEY_PRIVATE_KEY={cdp_api_key_private_key}\n\
-         DOCKER_IMAGE=tanglenetwork/coinbase-agent:latest\n"
-    );
+    let mut env_vars: Vec<(String, String)> = vec![
+        ("PORT".to_string(), port.to_string()),
+        ("CONTAINER_NAME".to_string(), container_name.to_string()),
+        ("AGENT_MODE".to_string(), agent_mode),
+        ("LLM_PROVIDER".to_string(), llm_provider),
+        ("MODEL".to_string(), model),
+        ("LOG_LEVEL".to_string(), "debug".to_string()),
+    ];
+    if let Some(websocket_port) = websocket_port {
+        env_vars.push(("WEBSOCKET_PORT".to_string(), websocket_port.to_string()));
+        env_vars.push((
+            "WEBSOCKET_URL".to_string(),
+            format!("ws://localhost:{}", websocket_port),
+        ));
+    }
+    let mut secrets: Vec<(String, Secret<String>)> = Vec::new();
+    for (_, var, value) in llm_keys {
+        secrets.push((var.to_string(), value.unwrap_or_default()));
+    }
+    for (_, var, value) in bot_tokens {
+        if let Some(value) = value {
+            secrets.push((var.to_string(), value));
+        }
+    }
+    for (var, key) in cdp_api_keys.into_iter().chain(wallet_key) {
+        secrets.push((var.to_string(), key));
+    }
+    env_vars.push((
+        "DOCKER_IMAGE".to_string(),
+        "tanglenetwork/coinbase-agent:latest".to_string(),
+    ));
+    for var in CARRIED_OVER_VARS {
+        if let Some(value) = agent_env.get(*var) {
+            env_vars.push((var.to_string(), value.clone()));
+        }
+    }
+
+    // Keep the extra variables the agent was created with
+    if let Some(names) = agent_env.get(templates::EXTRA_ENV_VAR) {
+        env_vars.push((templates::EXTRA_ENV_VAR.to_string(), names.clone()));
+        for name in names
+            .split(',')
+            .filter(|name| !templates::is_reserved(name))
+        {
+            if let Some(value) = agent_env.get(name) {
+                env_vars.push((name.to_string(), value.clone()));
+            }
+        }
+    }
+
+    // Set what the template's runtime needs, unless the agent's settings already do
+    if let Some(template) = installed_template(agent_env) {
+        for (name, value) in template.manifest.env {
+            if !env_vars.iter().any(|(var, _)| *var == name) {
+                env_vars.push((name, value));
+            }
+        }
+    }
 
-    Ok(env_content)
+    Ok(DeploymentEnv {
+        content: render_env_vars(&env_vars),
+        secrets,
+    })
 }
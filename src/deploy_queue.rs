@@ -0,0 +1,159 @@
+use crate::config::env_or;
+use blueprint_sdk::logging;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{OwnedMutexGuard, OwnedSemaphorePermit, Semaphore};
+
+/// Deployments that run at once unless `AGENT_MAX_CONCURRENT_DEPLOYS` says otherwise
+pub const DEFAULT_MAX_CONCURRENT_DEPLOYS: usize = 4;
+
+/// Queue bounding how many deployments run at once (shared across threads)
+///
+/// Deployments of the same agent never overlap: a second one waits for the first to finish
+/// before it even takes its place in the queue.
+#[derive(Clone, Debug)]
+pub struct DeployQueue {
+    max_concurrent: Arc<AtomicUsize>,
+    slots: Arc<Semaphore>,
+    waiting: Arc<AtomicUsize>,
+    agents: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl DeployQueue {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            max_concurrent: Arc::new(AtomicUsize::new(max_concurrent)),
+            slots: Arc::new(Semaphore::new(max_concurrent)),
+            waiting: Arc::new(AtomicUsize::new(0)),
+            agents: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Creates a queue running as many deployments at once as `AGENT_MAX_CONCURRENT_DEPLOYS`
+    pub fn from_env() -> Result<Self, String> {
+        let max_concurrent = env_or(
+            "AGENT_MAX_CONCURRENT_DEPLOYS",
+            DEFAULT_MAX_CONCURRENT_DEPLOYS,
+        )?;
+        if max_concurrent == 0 {
+            return Err("AGENT_MAX_CONCURRENT_DEPLOYS must be at least 1".to_string());
+        }
+        Ok(Self::new(max_concurrent))
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent.load(Ordering::SeqCst)
+    }
+
+    /// Changes how many deployments run at once
+    ///
+    /// Running deployments are never interrupted: when the limit shrinks, the surplus slots
+    /// are taken away as those deployments finish.
+    pub fn set_max_concurrent(&self, max_concurrent: usize) {
+        let max_concurrent = max_concurrent.max(1);
+        let previous = self.max_concurrent.swap(max_concurrent, Ordering::SeqCst);
+        match max_concurrent.cmp(&previous) {
+            std::cmp::Ordering::Greater => self.slots.add_permits(max_concurrent - previous),
+            std::cmp::Ordering::Less => {
+                let slots = self.slots.clone();
+                let surplus = (previous - max_concurrent) as u32;
+                tokio::spawn(async move {
+                    // The semaphore is never closed
+                    if let Ok(retired) = slots.acquire_many_owned(surplus).await {
+                        retired.forget();
+                    }
+                });
+            }
+            std::cmp::Ordering::Equal => {}
+        }
+    }
+
+    /// Returns how many deployments are waiting for a slot
+    pub fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+
+    /// Returns how many deployments are running
+    pub fn running(&self) -> usize {
+        self.max_concurrent()
+            .saturating_sub(self.slots.available_permits())
+    }
+
+    /// Waits until the agent may be deployed, which lasts as long as the returned permit
+    pub async fn acquire(&self, agent_id: &str) -> DeployPermit {
+        let agent_lock = {
+            let mut agents = self.agents.lock().unwrap_or_else(|e| e.into_inner());
+            agents.entry(agent_id.to_string()).or_default().clone()
+        };
+        let agent_guard = match agent_lock.clone().try_lock_owned() {
+            Ok(guard) => guard,
+            Err(_) => {
+                logging::info!(
+                    agent_id = %agent_id,
+                    phase = "queue",
+                    "Agent is already being deployed, waiting for that deployment to finish"
+                );
+                agent_lock.lock_owned().await
+            }
+        };
+
+        let queued_at = Instant::now();
+        let slot = match self.slots.clone().try_acquire_owned() {
+            Ok(slot) => slot,
+            Err(_) => {
+                let position = self.waiting.fetch_add(1, Ordering::SeqCst) + 1;
+                logging::info!(
+                    agent_id = %agent_id,
+                    phase = "queue",
+                    position,
+                    running = self.running(),
+                    max_concurrent = self.max_concurrent(),
+                    "Deployment queued"
+                );
+                let slot = self.slots.clone().acquire_owned().await;
+                self.waiting.fetch_sub(1, Ordering::SeqCst);
+                // The semaphore is never closed
+                slot.expect("deployment queue closed")
+            }
+        };
+        logging::info!(
+            agent_id = %agent_id,
+            phase = "queue",
+            waited_ms = queued_at.elapsed().as_millis() as u64,
+            running = self.running(),
+            waiting = self.waiting(),
+            "Deployment started"
+        );
+
+        DeployPermit {
+            agent_id: agent_id.to_string(),
+            agents: self.agents.clone(),
+            agent_guard: Some(agent_guard),
+            _slot: slot,
+        }
+    }
+}
+
+/// Slot in the deployment queue and exclusive hold on the agent being deployed
+pub struct DeployPermit {
+    agent_id: String,
+    agents: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+    agent_guard: Option<OwnedMutexGuard<()>>,
+    _slot: OwnedSemaphorePermit,
+}
+
+impl Drop for DeployPermit {
+    fn drop(&mut self) {
+        let mut agents = self.agents.lock().unwrap_or_else(|e| e.into_inner());
+        self.agent_guard.take();
+        // Forget the agent's lock unless another deployment of it is waiting
+        if agents
+            .get(&self.agent_id)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            agents.remove(&self.agent_id);
+        }
+    }
+}
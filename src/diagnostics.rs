@@ -0,0 +1,236 @@
+use crate::agent_id::AgentId;
+use crate::codec;
+use crate::error::BlueprintError;
+use crate::helpers::{collect_container_diagnostics, read_env_file};
+use crate::journal;
+use crate::ownership::authorize;
+use crate::retention::{write_diagnostics, DIAGNOSTICS_DIR};
+use crate::storage;
+use crate::telemetry::recent_logs;
+use crate::types::{CollectDiagnosticsParams, DiagnosticsResult};
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha3::{Digest, Keccak256};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Parts of variable names whose values are never put in a bundle
+const SECRET_NAME_PARTS: &[&str] = &["KEY", "TOKEN", "SECRET", "PASSWORD", "PRIVATE"];
+
+/// Handles the collect_diagnostics job
+///
+/// Packages what support needs to investigate one agent into a `.tar.gz` bundle in the
+/// agent's diagnostics directory. Only the agent's owner and the service owner may collect it.
+pub async fn handle_collect_diagnostics(
+    params_bytes: Vec<u8>,
+    context: &ServiceContext,
+) -> Result<Vec<u8>, BlueprintError> {
+    // Deserialize the parameters from bytes
+    let (params, encoding): (CollectDiagnosticsParams, _) =
+        codec::decode(&params_bytes).map_err(BlueprintError::Params)?;
+
+    // Define base directory from context or environment
+    let base_dir = match context.agents_base_dir() {
+        Some(dir) => dir,
+        None => "./agents".to_string(),
+    };
+
+    let agent_dir = params.agent_id.dir(&base_dir);
+    if !agent_dir.exists() {
+        return Err(BlueprintError::Params(format!(
+            "Unknown agent: {}",
+            params.agent_id
+        )));
+    }
+    let agent_env = read_env_file(&agent_dir.join(".env")).map_err(BlueprintError::Io)?;
+    authorize(context, &params.agent_id, &agent_env).map_err(BlueprintError::Unauthorized)?;
+
+    logging::info!(agent_id = %params.agent_id, phase = "diagnostics", "Collecting diagnostics");
+    let files = bundle_files(context, &params.agent_id, &agent_dir)?;
+    let archive = build_archive(&files)?;
+    let archive_path =
+        write_diagnostics(&agent_dir, "bundle.tar.gz", &archive).map_err(BlueprintError::Io)?;
+    logging::info!(
+        agent_id = %params.agent_id,
+        phase = "diagnostics",
+        path = %archive_path.display(),
+        size = archive.len(),
+        "Wrote diagnostics bundle"
+    );
+    storage::upload_in_background(
+        context,
+        storage::diagnostics_key(&params.agent_id, chrono::Utc::now()),
+        archive.clone(),
+    );
+    journal::record(
+        context,
+        "collect_diagnostics",
+        &params.agent_id,
+        "diagnosed",
+    );
+
+    // Prepare the result
+    let result = DiagnosticsResult {
+        agent_id: params.agent_id,
+        archive_path: archive_path.display().to_string(),
+        size_bytes: archive.len() as u64,
+        archive_hash: hex::encode(Keccak256::digest(&archive)),
+        files: files.into_iter().map(|(name, _)| name).collect(),
+        call_id: context.call_id,
+    };
+
+    // Serialize the result
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
+
+/// Collects the files of an agent's bundle, by name inside the archive
+///
+/// Failures to query Docker end up in the files themselves, so that the bundle holds
+/// whatever could be collected.
+fn bundle_files(
+    context: &ServiceContext,
+    agent_id: &AgentId,
+    agent_dir: &Path,
+) -> Result<Vec<(String, Vec<u8>)>, BlueprintError> {
+    let container_name = agent_id.container_name();
+    let mut files = vec![
+        (
+            "container.txt".to_string(),
+            collect_container_diagnostics(&container_name).into_bytes(),
+        ),
+        (
+            "inspect.json".to_string(),
+            inspect_container(&container_name).into_bytes(),
+        ),
+    ];
+
+    // The .env is free of secrets after deployment, but older agents may still hold some
+    let agent_env = read_env_file(&agent_dir.join(".env")).map_err(BlueprintError::Io)?;
+    let mut env_vars: Vec<(&String, &String)> = agent_env.iter().collect();
+    env_vars.sort();
+    let mut env_listing = String::new();
+    for (key, value) in env_vars {
+        env_listing.push_str(key);
+        env_listing.push('=');
+        env_listing.push_str(redact_env_value(key, value));
+        env_listing.push('\n');
+    }
+    files.push(("env.txt".to_string(), env_listing.into_bytes()));
+
+    let compose_path = agent_dir.join("docker-compose.yml");
+    if compose_path.exists() {
+        let compose = fs::read(&compose_path)
+            .map_err(|e| BlueprintError::Io(format!("Failed to read docker-compose.yml: {}", e)))?;
+        files.push(("docker-compose.yml".to_string(), compose));
+    }
+
+    let health = serde_json::json!({
+        "state": context
+            .health_monitor
+            .as_ref()
+            .and_then(|monitor| monitor.state(agent_id)),
+        "history": context
+            .health_monitor
+            .as_ref()
+            .map(|monitor| monitor.history(agent_id))
+            .unwrap_or_default(),
+    });
+    let health = serde_json::to_vec_pretty(&health)
+        .map_err(|e| BlueprintError::Internal(format!("Failed to serialize health: {}", e)))?;
+    files.push(("health.json".to_string(), health));
+
+    // Earlier diagnostics, e.g. from failed health checks, but not earlier bundles
+    if let Ok(entries) = fs::read_dir(agent_dir.join(DIAGNOSTICS_DIR)) {
+        let mut earlier: Vec<_> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+            .collect();
+        earlier.sort();
+        for path in earlier {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            match fs::read(&path) {
+                Ok(content) => files.push((format!("diagnostics/{}", name), content)),
+                Err(e) => {
+                    logging::warn!(path = %path.display(), error = %e, "Failed to read diagnostics")
+                }
+            }
+        }
+    }
+
+    let mut blueprint_log = recent_logs().matching(agent_id).join("\n");
+    blueprint_log.push('\n');
+    files.push(("blueprint.log".to_string(), blueprint_log.into_bytes()));
+
+    Ok(files)
+}
+
+/// Returns the value of an environment variable as it may appear in a bundle
+pub(crate) fn redact_env_value<'a>(key: &str, value: &'a str) -> &'a str {
+    let key = key.to_ascii_uppercase();
+    if !value.is_empty() && SECRET_NAME_PARTS.iter().any(|part| key.contains(part)) {
+        "[REDACTED]"
+    } else {
+        value
+    }
+}
+
+/// Returns `docker inspect` output for a container with its environment redacted
+fn inspect_container(container_name: &str) -> String {
+    let output = match Command::new("docker")
+        .args(["inspect", container_name])
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            return format!(
+                "Docker inspect command failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            )
+        }
+        Err(e) => return format!("Failed to execute docker inspect command: {}", e),
+    };
+
+    let mut inspect: serde_json::Value = match serde_json::from_slice(&output.stdout) {
+        Ok(inspect) => inspect,
+        Err(e) => return format!("Invalid docker inspect output: {}", e),
+    };
+    for container in inspect.as_array_mut().into_iter().flatten() {
+        let env = container
+            .pointer_mut("/Config/Env")
+            .and_then(|env| env.as_array_mut());
+        for var in env.into_iter().flatten() {
+            if let Some((key, value)) = var.as_str().and_then(|var| var.split_once('=')) {
+                *var = format!("{}={}", key, redact_env_value(key, value)).into();
+            }
+        }
+    }
+    serde_json::to_string_pretty(&inspect)
+        .unwrap_or_else(|e| format!("Failed to serialize docker inspect output: {}", e))
+}
+
+/// Packs files into a gzip-compressed tarball
+pub(crate) fn build_archive(files: &[(String, Vec<u8>)]) -> Result<Vec<u8>, BlueprintError> {
+    let mtime = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o600);
+        header.set_mtime(mtime);
+        builder
+            .append_data(&mut header, name, content.as_slice())
+            .map_err(|e| BlueprintError::Io(format!("Failed to add {} to bundle: {}", name, e)))?;
+    }
+    builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .map_err(|e| BlueprintError::Io(format!("Failed to write bundle: {}", e)))
+}
@@ -1,26 +1,28 @@
+use crate::retention::LogRetention;
+use crate::templates::{Template, DEFAULT_TEMPLATE, TEMPLATES_DIR};
+use crate::types::{AgentImage, TeeConfig};
+use crate::wallet;
 use phala_tee_deploy_rs::{TeeDeployer, TeeDeployerBuilder};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
-/// Creates a Docker Compose file in the agent directory by copying the template
+/// Creates a Docker Compose file in the agent directory by copying the starter template's,
+/// from the templates directory or the one built into the binary
 ///
-/// This function copies the template docker-compose.yml and normalizes it to ensure
-/// consistent field ordering for TEE deployment.
+/// The agent service runs `image` (see [`insert_agent_image`]), and the file is normalized to
+/// ensure consistent field ordering for TEE deployment.
 ///
 /// # Arguments
 ///
 /// * `agent_dir` - Path to the agent directory
-/// * `agent_id` - Unique identifier for the agent
-/// * `http_port` - The HTTP port to expose (default: 3000)
-/// * `websocket_port` - The WebSocket port to expose (default: 3001)
-/// * `env_vars` - Additional environment variables to include (currently unused)
+/// * `image` - What the agent's container runs
 ///
 /// # Returns
 ///
 /// The path to the created Docker Compose file
-pub fn write_docker_compose_file(agent_dir: &Path) -> Result<PathBuf, String> {
+pub fn write_docker_compose_file(agent_dir: &Path, image: &AgentImage) -> Result<PathBuf, String> {
     // Define the source template path
-    let template_path = Path::new("templates/starter/docker-compose.yml");
+    let template_path = Template::load(Path::new(TEMPLATES_DIR), DEFAULT_TEMPLATE)?.compose_path();
     if !template_path.exists() {
         return Err("Docker Compose template not found".to_string());
     }
@@ -28,9 +30,12 @@ pub fn write_docker_compose_file(agent_dir: &Path) -> Result<PathBuf, String> {
     // Read the template
     let docker_compose = fs::read_to_string(template_path)
         .map_err(|e| format!("Failed to read Docker Compose template: {}", e))?;
+    let mut compose: serde_yaml::Value = serde_yaml::from_str(&docker_compose)
+        .map_err(|e| format!("Failed to parse Docker compose as YAML: {}", e))?;
 
-    // Normalize the Docker Compose file to ensure consistent ordering
-    let normalized_compose = normalize_docker_compose(&docker_compose)?;
+    // Run the requested image, normalizing the file to ensure consistent ordering
+    insert_agent_image(&mut compose, image)?;
+    let normalized_compose = serialize_compose(compose)?;
 
     // Write the Docker Compose file
     let compose_path = agent_dir.join("docker-compose.yml");
@@ -40,6 +45,606 @@ pub fn write_docker_compose_file(agent_dir: &Path) -> Result<PathBuf, String> {
     Ok(compose_path)
 }
 
+/// Sets what the agent service of a parsed Docker Compose file runs
+///
+/// A Dockerfile replaces the service's image with a build of the agent's directory, which
+/// holds a copy of the template, and a prebuilt image replaces its image and any build. The
+/// template's image is left as it is.
+pub fn insert_agent_image(
+    compose: &mut serde_yaml::Value,
+    image: &AgentImage,
+) -> Result<(), String> {
+    let agent = compose
+        .get_mut("services")
+        .and_then(|services| services.get_mut("agent"))
+        .and_then(|agent| agent.as_mapping_mut())
+        .ok_or("Docker Compose file has no agent service")?;
+    match image {
+        AgentImage::Template => {}
+        AgentImage::Dockerfile { path } => {
+            let mut build = serde_yaml::Mapping::new();
+            build.insert("context".into(), ".".into());
+            build.insert("dockerfile".into(), path.as_str().into());
+            agent.remove("image");
+            agent.insert("build".into(), build.into());
+        }
+        AgentImage::Image { reference } => {
+            agent.remove("build");
+            agent.insert("image".into(), reference.as_str().into());
+        }
+    }
+    Ok(())
+}
+
+/// Pulls an image and returns its reference pinned to the digest it resolved to
+///
+/// References already pinned by digest are returned as they are, without pulling.
+///
+/// # Arguments
+///
+/// * `reference` - A validated image reference, e.g. `ghcr.io/org/agent:1.2`
+///
+/// # Returns
+///
+/// The reference as `<repository>@sha256:<digest>`
+pub fn pin_image(reference: &str) -> Result<String, String> {
+    if reference.contains('@') {
+        return Ok(reference.to_string());
+    }
+
+    let pull = std::process::Command::new("docker")
+        .args(["pull", "--quiet", reference])
+        .output()
+        .map_err(|e| format!("Failed to run docker pull: {}", e))?;
+    if !pull.status.success() {
+        return Err(format!(
+            "Failed to pull image {}: {}",
+            reference,
+            String::from_utf8_lossy(&pull.stderr).trim()
+        ));
+    }
+
+    let inspect = std::process::Command::new("docker")
+        .args([
+            "image",
+            "inspect",
+            "--format",
+            "{{join .RepoDigests \"\\n\"}}",
+            reference,
+        ])
+        .output()
+        .map_err(|e| format!("Failed to run docker image inspect: {}", e))?;
+    if !inspect.status.success() {
+        return Err(format!(
+            "Failed to inspect image {}: {}",
+            reference,
+            String::from_utf8_lossy(&inspect.stderr).trim()
+        ));
+    }
+    select_repo_digest(reference, &String::from_utf8_lossy(&inspect.stdout))
+        .ok_or_else(|| format!("Image {} has no digest to pin it to", reference))
+}
+
+/// Picks the digest of `reference`'s repository among an image's repo digests
+pub fn select_repo_digest(reference: &str, repo_digests: &str) -> Option<String> {
+    // Drop the tag, unless the last `:` belongs to a registry's port
+    let repository = match reference.rsplit_once(':') {
+        Some((repository, tag)) if !tag.contains('/') => repository,
+        _ => reference,
+    };
+    // Docker Hub's images are listed without its host, and its official ones without their
+    // `library/` prefix
+    let repository = repository.strip_prefix("docker.io/").unwrap_or(repository);
+    let repository = repository.strip_prefix("library/").unwrap_or(repository);
+    repo_digests
+        .lines()
+        .map(str::trim)
+        .find(|digest| {
+            digest
+                .split_once('@')
+                .is_some_and(|(repo, _)| repo == repository)
+        })
+        .map(str::to_string)
+}
+
+/// Adds an Ollama sidecar service to the agent's Docker Compose file
+///
+/// The sidecar serves an OpenAI-compatible API on port 11434, pulls the requested
+/// model on startup and keeps downloaded models in a named volume.
+///
+/// # Arguments
+///
+/// * `compose_path` - Path to the agent's docker-compose.yml
+/// * `model` - The Ollama model to pull (e.g. "llama3.2")
+///
+/// # Returns
+///
+/// A Result indicating success or an error message
+pub fn add_ollama_sidecar(compose_path: &Path, model: &str) -> Result<(), String> {
+    update_compose_file(compose_path, |compose| {
+        insert_ollama_sidecar(compose, model)
+    })
+}
+
+/// Adds an Ollama sidecar pulling `model` to a parsed Docker Compose file
+///
+/// See [`add_ollama_sidecar`].
+pub fn insert_ollama_sidecar(compose: &mut serde_yaml::Value, model: &str) -> Result<(), String> {
+    let ollama_service: serde_yaml::Value = serde_yaml::from_str(&format!(
+        r#"
+image: ollama/ollama:latest
+volumes:
+  - ollama-models:/root/.ollama
+entrypoint: ["/bin/sh", "-c"]
+command: ["ollama serve & sleep 5 && ollama pull {model} && wait"]
+restart: unless-stopped
+"#
+    ))
+    .map_err(|e| format!("Failed to build Ollama service: {}", e))?;
+
+    let services = compose
+        .get_mut("services")
+        .and_then(|services| services.as_mapping_mut())
+        .ok_or("Docker Compose file has no services")?;
+    services.insert("ollama".into(), ollama_service);
+
+    // Start the agent after the sidecar
+    let agent = services
+        .get_mut("agent")
+        .and_then(|agent| agent.as_mapping_mut())
+        .ok_or("Docker Compose file has no agent service")?;
+    agent.insert(
+        "depends_on".into(),
+        serde_yaml::Value::Sequence(vec!["ollama".into()]),
+    );
+
+    // Declare the named volume holding the downloaded models
+    let root = compose
+        .as_mapping_mut()
+        .ok_or("Docker Compose file is not a mapping")?;
+    let volumes = root
+        .entry("volumes".into())
+        .or_insert_with(|| serde_yaml::Value::Mapping(Default::default()));
+    if volumes.is_null() {
+        *volumes = serde_yaml::Value::Mapping(Default::default());
+    }
+    volumes
+        .as_mapping_mut()
+        .ok_or("Docker Compose volumes is not a mapping")?
+        .insert("ollama-models".into(), serde_yaml::Value::Null);
+
+    Ok(())
+}
+
+/// Adds a firewall sidecar that limits the agent's outbound connections to `hosts`
+///
+/// The sidecar shares the agent's network namespace and installs iptables rules that only
+/// allow loopback, DNS, the compose network (e.g. an Ollama sidecar) and the addresses the
+/// hosts resolve to, refreshing them every five minutes as DNS changes.
+///
+/// # Arguments
+///
+/// * `compose_path` - Path to the agent's docker-compose.yml
+/// * `hosts` - Validated host names the agent may connect to
+///
+/// # Returns
+///
+/// A Result indicating success or an error message
+pub fn add_egress_firewall(compose_path: &Path, hosts: &[String]) -> Result<(), String> {
+    update_compose_file(compose_path, |compose| {
+        insert_egress_firewall(compose, hosts)
+    })
+}
+
+/// Service of the firewall sidecar added by [`insert_egress_firewall`]
+pub const EGRESS_FIREWALL_SERVICE: &str = "egress-firewall";
+
+/// Adds a firewall sidecar limiting the agent to `hosts` to a parsed Docker Compose file
+///
+/// See [`add_egress_firewall`].
+pub fn insert_egress_firewall(
+    compose: &mut serde_yaml::Value,
+    hosts: &[String],
+) -> Result<(), String> {
+    // `$$` escapes compose's variable interpolation
+    let script = format!(
+        "apk add --no-cache iptables >/dev/null && \
+ip6tables -A OUTPUT -o lo -j ACCEPT && ip6tables -P OUTPUT DROP && \
+while true; do \
+iptables -N egress_next 2>/dev/null || iptables -F egress_next; \
+iptables -A egress_next -o lo -j ACCEPT; \
+iptables -A egress_next -m state --state ESTABLISHED,RELATED -j ACCEPT; \
+iptables -A egress_next -p udp --dport 53 -j ACCEPT; \
+iptables -A egress_next -p tcp --dport 53 -j ACCEPT; \
+iptables -A egress_next -d 172.16.0.0/12 -j ACCEPT; \
+for host in {hosts}; do \
+for ip in $$(getent ahostsv4 $$host | awk '{{print $$1}}' | sort -u); do \
+iptables -A egress_next -d $$ip -j ACCEPT; \
+done; done; \
+iptables -I OUTPUT 1 -j egress_next; \
+iptables -D OUTPUT -j egress 2>/dev/null; \
+iptables -F egress 2>/dev/null; iptables -X egress 2>/dev/null; \
+iptables -E egress_next egress; \
+iptables -P OUTPUT DROP; \
+sleep 300; done",
+        hosts = hosts.join(" ")
+    );
+    let mut firewall_service: serde_yaml::Value = serde_yaml::from_str(
+        r#"
+image: alpine:3.20
+network_mode: "service:agent"
+cap_add:
+  - NET_ADMIN
+depends_on:
+  - agent
+entrypoint: ["/bin/sh", "-c"]
+restart: unless-stopped
+"#,
+    )
+    .map_err(|e| format!("Failed to build egress firewall service: {}", e))?;
+    firewall_service
+        .as_mapping_mut()
+        .ok_or("Egress firewall service is not a mapping")?
+        .insert(
+            "command".into(),
+            serde_yaml::Value::Sequence(vec![script.into()]),
+        );
+
+    let services = compose
+        .get_mut("services")
+        .and_then(|services| services.as_mapping_mut())
+        .ok_or("Docker Compose file has no services")?;
+    if !services.contains_key("agent") {
+        return Err("Docker Compose file has no agent service".to_string());
+    }
+    services.insert(EGRESS_FIREWALL_SERVICE.into(), firewall_service);
+
+    Ok(())
+}
+
+/// Rotates the logs of every service in the agent's Docker Compose file
+///
+/// Docker's `json-file` driver rotates each container's log once it reaches the retention's
+/// maximum size and keeps its maximum number of files, so long-running agents can't fill
+/// the disk.
+///
+/// # Arguments
+///
+/// * `compose_path` - Path to the agent's docker-compose.yml
+/// * `retention` - The operator's log retention
+///
+/// # Returns
+///
+/// A Result indicating success or an error message
+pub fn add_log_rotation(compose_path: &Path, retention: &LogRetention) -> Result<(), String> {
+    update_compose_file(compose_path, |compose| {
+        insert_log_rotation(compose, retention)
+    })
+}
+
+/// Rotates the logs of every service of a parsed Docker Compose file
+///
+/// See [`add_log_rotation`].
+pub fn insert_log_rotation(
+    compose: &mut serde_yaml::Value,
+    retention: &LogRetention,
+) -> Result<(), String> {
+    let services = compose
+        .get_mut("services")
+        .and_then(|services| services.as_mapping_mut())
+        .ok_or("Docker Compose file has no services")?;
+    for (_, service) in services.iter_mut() {
+        if let Some(service) = service.as_mapping_mut() {
+            service.insert("logging".into(), retention.compose_logging());
+        }
+    }
+    Ok(())
+}
+
+/// Passes variables of the agent's .env to the agent service of a parsed Docker Compose file
+///
+/// Each variable is added as `NAME=${NAME}`, unless the service's environment already names
+/// it. Environments given as a mapping get `NAME: ${NAME}` instead.
+pub fn insert_agent_env(
+    compose: &mut serde_yaml::Value,
+    names: impl IntoIterator<Item = impl AsRef<str>>,
+) -> Result<(), String> {
+    let agent = compose
+        .get_mut("services")
+        .and_then(|services| services.get_mut("agent"))
+        .and_then(|agent| agent.as_mapping_mut())
+        .ok_or("Docker Compose file has no agent service")?;
+    let environment = agent
+        .entry("environment".into())
+        .or_insert_with(|| serde_yaml::Value::Sequence(Vec::new()));
+    for name in names {
+        let name = name.as_ref();
+        let reference = format!("${{{}}}", name);
+        match environment {
+            serde_yaml::Value::Sequence(entries) => {
+                let named = entries.iter().any(|entry| {
+                    entry
+                        .as_str()
+                        .is_some_and(|entry| entry.split('=').next() == Some(name))
+                });
+                if !named {
+                    entries.push(format!("{}={}", name, reference).into());
+                }
+            }
+            serde_yaml::Value::Mapping(entries) => {
+                if !entries.contains_key(name) {
+                    entries.insert(name.into(), reference.into());
+                }
+            }
+            _ => return Err("The agent service's environment is malformed".to_string()),
+        }
+    }
+    Ok(())
+}
+
+/// Mounts the agent's wallet directory into the agent service of a parsed Docker Compose
+/// file, telling the agent where to write its wallet data and passing it the data to restore
+pub fn insert_wallet_volume(compose: &mut serde_yaml::Value) -> Result<(), String> {
+    let agent = compose
+        .get_mut("services")
+        .and_then(|services| services.get_mut("agent"))
+        .and_then(|agent| agent.as_mapping_mut())
+        .ok_or("Docker Compose file has no agent service")?;
+    let volume = format!("./{}:{}", wallet::WALLET_DIR, wallet::WALLET_MOUNT);
+    let volumes = agent
+        .entry("volumes".into())
+        .or_insert_with(|| serde_yaml::Value::Sequence(Vec::new()))
+        .as_sequence_mut()
+        .ok_or("The agent service's volumes are malformed")?;
+    if !volumes
+        .iter()
+        .any(|entry| entry.as_str() == Some(volume.as_str()))
+    {
+        volumes.push(volume.into());
+    }
+
+    let path = format!("{}/{}", wallet::WALLET_MOUNT, wallet::WALLET_DATA_FILE);
+    let restored = format!("${{{}}}", wallet::WALLET_DATA_VAR);
+    let environment = agent
+        .entry("environment".into())
+        .or_insert_with(|| serde_yaml::Value::Sequence(Vec::new()));
+    match environment {
+        serde_yaml::Value::Sequence(entries) => {
+            for (name, value) in [
+                (wallet::WALLET_DATA_FILE_VAR, &path),
+                (wallet::WALLET_DATA_VAR, &restored),
+            ] {
+                entries.retain(|entry| {
+                    entry
+                        .as_str()
+                        .map_or(true, |entry| entry.split('=').next() != Some(name))
+                });
+                entries.push(format!("{}={}", name, value).into());
+            }
+        }
+        serde_yaml::Value::Mapping(entries) => {
+            entries.insert(wallet::WALLET_DATA_FILE_VAR.into(), path.into());
+            entries.insert(wallet::WALLET_DATA_VAR.into(), restored.into());
+        }
+        _ => return Err("The agent service's environment is malformed".to_string()),
+    }
+    Ok(())
+}
+
+/// Limits the CPUs and memory of the agent service of a parsed Docker Compose file
+///
+/// Disk isn't limited, as Docker can't cap the size of a container's writable layer on
+/// every storage driver.
+pub fn insert_resource_limits(
+    compose: &mut serde_yaml::Value,
+    resources: &TeeConfig,
+) -> Result<(), String> {
+    let agent = compose
+        .get_mut("services")
+        .and_then(|services| services.get_mut("agent"))
+        .and_then(|agent| agent.as_mapping_mut())
+        .ok_or("Docker Compose file has no agent service")?;
+    agent.insert("cpus".into(), resources.vcpu.to_string().into());
+    agent.insert(
+        "mem_limit".into(),
+        format!("{}m", resources.memory_mb).into(),
+    );
+    Ok(())
+}
+
+/// Removes the WebSocket port mapping and variables of the agent service of a parsed Docker
+/// Compose file, for agents deployed without a WebSocket server
+pub fn remove_websocket_port(compose: &mut serde_yaml::Value) -> Result<(), String> {
+    let agent = compose
+        .get_mut("services")
+        .and_then(|services| services.get_mut("agent"))
+        .and_then(|agent| agent.as_mapping_mut())
+        .ok_or("Docker Compose file has no agent service")?;
+    for key in ["ports", "environment"] {
+        if let Some(entries) = agent
+            .get_mut(key)
+            .and_then(|entries| entries.as_sequence_mut())
+        {
+            entries.retain(|entry| {
+                !entry
+                    .as_str()
+                    .is_some_and(|entry| entry.contains("WEBSOCKET_"))
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Service settings that would let a container reach into the operator's host
+const HOST_ACCESS_KEYS: &[&str] = &["cap_add", "devices", "device_cgroup_rules", "security_opt"];
+
+/// Service settings that join another namespace, which may not be the host's
+const NAMESPACE_KEYS: &[&str] = &["network_mode", "pid", "ipc", "uts", "userns_mode"];
+
+/// Checks that a parsed Docker Compose file keeps its containers away from the operator's
+/// host
+///
+/// Templates can come from any git repository or OCI registry, and their compose file runs
+/// on the operator's host, so no service may be privileged, share the host's or another
+/// container's namespaces, add capabilities, devices or security options, or mount, build
+/// or read a host path outside the agent's directory. Paths must be relative, stay below
+/// the agent's directory and not use variables. Named volumes may not set driver options,
+/// which can bind any host path. The only exception is the `NET_ADMIN` capability of the
+/// egress firewall sidecar, which shares the agent's network namespace.
+pub fn check_compose_policy(compose: &serde_yaml::Value) -> Result<(), String> {
+    let services = compose
+        .get("services")
+        .and_then(|services| services.as_mapping())
+        .ok_or("Docker Compose file has no services")?;
+    for (name, service) in services {
+        let name = name.as_str().unwrap_or_default();
+        check_service_policy(name, service).map_err(|e| format!("Service {}: {}", name, e))?;
+    }
+
+    if let Some(volumes) = compose
+        .get("volumes")
+        .and_then(|volumes| volumes.as_mapping())
+    {
+        for (name, volume) in volumes {
+            if volume.get("driver_opts").is_some() {
+                return Err(format!(
+                    "Volume {} may not set driver options",
+                    name.as_str().unwrap_or_default()
+                ));
+            }
+        }
+    }
+    for key in ["secrets", "configs"] {
+        let Some(entries) = compose.get(key).and_then(|entries| entries.as_mapping()) else {
+            continue;
+        };
+        for (_, entry) in entries {
+            if let Some(file) = entry.get("file") {
+                check_local_path(file).map_err(|e| format!("{}: {}", key, e))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_service_policy(name: &str, service: &serde_yaml::Value) -> Result<(), String> {
+    let is_set = |value: &serde_yaml::Value| match value {
+        serde_yaml::Value::Null | serde_yaml::Value::Bool(false) => false,
+        serde_yaml::Value::Sequence(entries) => !entries.is_empty(),
+        serde_yaml::Value::Mapping(entries) => !entries.is_empty(),
+        _ => true,
+    };
+    if service.get("privileged").is_some_and(is_set) {
+        return Err("privileged containers are not allowed".to_string());
+    }
+    for key in NAMESPACE_KEYS {
+        let Some(mode) = service.get(*key) else {
+            continue;
+        };
+        let mode = mode.as_str().unwrap_or_default();
+        if mode.eq_ignore_ascii_case("host") || mode.starts_with("container:") {
+            return Err(format!("{}: {} is not allowed", key, mode));
+        }
+    }
+    for key in HOST_ACCESS_KEYS {
+        let Some(value) = service.get(*key).filter(|value| is_set(value)) else {
+            continue;
+        };
+        let firewall = name == EGRESS_FIREWALL_SERVICE
+            && *key == "cap_add"
+            && service.get("network_mode").and_then(|mode| mode.as_str()) == Some("service:agent")
+            && value
+                .as_sequence()
+                .is_some_and(|caps| caps.iter().all(|cap| cap.as_str() == Some("NET_ADMIN")));
+        if !firewall {
+            return Err(format!("{} is not allowed", key));
+        }
+    }
+
+    for volume in service
+        .get("volumes")
+        .and_then(|volumes| volumes.as_sequence())
+        .into_iter()
+        .flatten()
+    {
+        match volume {
+            // `source:target[:mode]`, where a source starting like a path is a bind mount
+            serde_yaml::Value::String(volume) => {
+                if let Some((source, _)) = volume.split_once(':') {
+                    if source.contains('$') || source.starts_with(['.', '/', '~']) {
+                        check_local_path(&source.into()).map_err(|e| format!("volumes: {}", e))?;
+                    }
+                }
+            }
+            serde_yaml::Value::Mapping(_) => match volume.get("type").and_then(|t| t.as_str()) {
+                Some("bind") => {
+                    let source = volume.get("source").ok_or("volumes: bind has no source")?;
+                    check_local_path(source).map_err(|e| format!("volumes: {}", e))?;
+                }
+                Some("volume" | "tmpfs") => {}
+                _ => return Err("volumes: only bind, volume and tmpfs mounts are allowed".into()),
+            },
+            _ => return Err("volumes are malformed".to_string()),
+        }
+    }
+
+    let context = match service.get("build") {
+        Some(serde_yaml::Value::Mapping(build)) => build.get("context"),
+        build => build,
+    };
+    if let Some(context) = context {
+        check_local_path(context).map_err(|e| format!("build: {}", e))?;
+    }
+    match service.get("env_file") {
+        None => {}
+        Some(serde_yaml::Value::Sequence(files)) => {
+            for file in files {
+                let path = file.get("path").unwrap_or(file);
+                check_local_path(path).map_err(|e| format!("env_file: {}", e))?;
+            }
+        }
+        Some(file) => check_local_path(file).map_err(|e| format!("env_file: {}", e))?,
+    }
+    Ok(())
+}
+
+/// Reads a Docker Compose file and checks it with [`check_compose_policy`]
+pub fn check_compose_file(compose_path: &Path) -> Result<(), String> {
+    let docker_compose = fs::read_to_string(compose_path)
+        .map_err(|e| format!("Failed to read docker-compose.yml: {}", e))?;
+    let compose: serde_yaml::Value = serde_yaml::from_str(&docker_compose)
+        .map_err(|e| format!("Failed to parse Docker compose as YAML: {}", e))?;
+    check_compose_policy(&compose)
+}
+
+/// Checks that a path of a Docker Compose file stays inside the agent's directory
+fn check_local_path(path: &serde_yaml::Value) -> Result<(), String> {
+    let path = path.as_str().ok_or("paths must be strings")?;
+    let inside = !path.contains('$')
+        && !path.starts_with('~')
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::CurDir | Component::Normal(_)));
+    if !inside {
+        return Err(format!("{} is outside the agent's directory", path));
+    }
+    Ok(())
+}
+
+/// Reads a Docker Compose file, applies `update` to it and writes it back normalized
+fn update_compose_file(
+    compose_path: &Path,
+    update: impl FnOnce(&mut serde_yaml::Value) -> Result<(), String>,
+) -> Result<(), String> {
+    let docker_compose = fs::read_to_string(compose_path)
+        .map_err(|e| format!("Failed to read docker-compose.yml: {}", e))?;
+    let mut yaml: serde_yaml::Value = serde_yaml::from_str(&docker_compose)
+        .map_err(|e| format!("Failed to parse Docker compose as YAML: {}", e))?;
+    update(&mut yaml)?;
+    fs::write(compose_path, serialize_compose(yaml)?)
+        .map_err(|e| format!("Failed to write docker-compose.yml: {}", e))
+}
+
 /// Normalizes a Docker Compose file by parsing it and reserializing it in a consistent format
 /// This ensures the same field ordering between different processes
 ///
@@ -52,9 +657,13 @@ pub fn write_docker_compose_file(agent_dir: &Path) -> Result<PathBuf, String> {
 /// A Result containing the normalized Docker Compose content
 pub fn normalize_docker_compose(docker_compose: &str) -> Result<String, String> {
     // Parse the Docker Compose content into a structured Value
-    let mut yaml: serde_yaml::Value = serde_yaml::from_str(docker_compose)
+    let yaml: serde_yaml::Value = serde_yaml::from_str(docker_compose)
         .map_err(|e| format!("Failed to parse Docker compose as YAML: {}", e))?;
+    serialize_compose(yaml)
+}
 
+/// Serializes a parsed Docker Compose file in the format of [`normalize_docker_compose`]
+pub fn serialize_compose(mut yaml: serde_yaml::Value) -> Result<String, String> {
     // Sort environment variables if they exist to ensure consistent ordering
     if let Some(services) = yaml.get_mut("services") {
         if let Some(agent) = services.get_mut("agent") {
@@ -93,6 +702,29 @@ pub fn init_tee_deployer(api_key: &str, api_endpoint: &str) -> Result<TeeDeploye
         .map_err(|e| format!("Failed to initialize TeeDeployer: {}", e))
 }
 
+/// Builds the VM configuration of a TEE agent, as the JSON the Phala API takes
+///
+/// The configuration must be the same when the agent's key is requested and when it is
+/// deployed, so both go through here.
+pub fn tee_vm_config(
+    deployer: &TeeDeployer,
+    docker_compose: &str,
+    app_name: &str,
+    tee_config: &TeeConfig,
+) -> Result<serde_json::Value, String> {
+    let vm_config = deployer
+        .create_vm_config(
+            docker_compose,
+            app_name,
+            Some(tee_config.vcpu),
+            Some(tee_config.memory_mb),
+            Some(tee_config.disk_gb),
+        )
+        .map_err(|e| format!("Failed to create VM configuration: {}", e))?;
+    serde_json::to_value(vm_config)
+        .map_err(|e| format!("Failed to serialize VM configuration: {}", e))
+}
+
 /// Clean up Docker containers by name pattern
 ///
 /// # Arguments
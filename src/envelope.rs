@@ -0,0 +1,292 @@
+use crate::codec::{self, Encoding, ScaleCodec};
+use crate::signing::{self, verify_caller_signature, CallerSignature};
+use crate::types::{
+    CreateAgentParams, CreateAgentSwarmParams, CreateAgentsParams, DeployAgentParams,
+};
+use crate::ServiceContext;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// Largest accepted job parameters, in bytes
+///
+/// Checked before the parameters are parsed so that oversized blobs cost nothing.
+pub const MAX_PARAMS_SIZE: usize = 256 * 1024;
+
+/// Job parameters tagged with the version of their schema
+///
+/// Callers should send parameters wrapped in an envelope so that the blueprint can keep
+/// decoding them as fields are added. Bare parameters are still accepted and treated as
+/// version 1, the schema used before envelopes were introduced.
+///
+/// Only enveloped parameters can carry the caller's signature over the payload.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ParamsEnvelope<T> {
+    pub version: u32,
+    pub payload: T,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<CallerSignature>,
+}
+
+impl<T: VersionedParams + Serialize> ParamsEnvelope<T> {
+    /// Wraps parameters in an envelope of the current version
+    pub fn new(payload: T) -> Self {
+        Self {
+            version: T::CURRENT_VERSION,
+            payload,
+            signature: None,
+        }
+    }
+
+    /// Returns the message to sign for `service_id` with the given nonce and expiry
+    ///
+    /// See [`signing::signing_message`].
+    pub fn signing_message(
+        &self,
+        service_id: Option<u64>,
+        nonce: u64,
+        expires_at: u64,
+    ) -> Result<Vec<u8>, String> {
+        let payload = serde_json::to_value(&self.payload)
+            .map_err(|e| format!("Failed to serialize parameters: {}", e))?;
+        signing::signing_message(service_id, &payload, nonce, expires_at)
+    }
+
+    /// Serializes the envelope into job parameter bytes
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        serde_json::to_vec(self).map_err(|e| format!("Failed to serialize parameters: {}", e))
+    }
+}
+
+/// Job parameters whose older schema versions can be upgraded to the current one
+pub trait VersionedParams: DeserializeOwned {
+    /// Version of the schema this type deserializes
+    const CURRENT_VERSION: u32;
+
+    /// Upgrades a payload from `version` to `version + 1`
+    fn upgrade(version: u32, payload: Value) -> Result<Value, String>;
+}
+
+/// Decodes job parameters, accepting both envelopes and bare (version 1) parameters
+pub fn decode_params<T: VersionedParams>(bytes: &[u8]) -> Result<T, String> {
+    let (version, payload, _) = open_envelope(bytes)?;
+    upgrade_params(version, payload)
+}
+
+/// Decodes job parameters after checking the caller's signature over them
+///
+/// See [`verify_caller_signature`] for when unsigned parameters are accepted.
+pub fn decode_signed_params<T: VersionedParams + ScaleCodec>(
+    bytes: &[u8],
+    context: &ServiceContext,
+) -> Result<T, String> {
+    decode_signed_job_params(bytes, context).map(|(params, _)| params)
+}
+
+/// Decodes job parameters like [`decode_signed_params`], also returning their encoding
+///
+/// SCALE-encoded parameters are tried first. They carry no envelope, so they are always of
+/// the current version and unsigned.
+pub fn decode_signed_job_params<T: VersionedParams + ScaleCodec>(
+    bytes: &[u8],
+    context: &ServiceContext,
+) -> Result<(T, Encoding), String> {
+    check_params_size(bytes)?;
+    if let Some(params) = codec::decode_scale(bytes) {
+        verify_caller_signature(context, &Value::Null, None)?;
+        return Ok((params, Encoding::Scale));
+    }
+
+    let (version, payload, signature) = open_envelope(bytes)?;
+    verify_caller_signature(context, &payload, signature.as_ref())?;
+    upgrade_params(version, payload).map(|params| (params, Encoding::Json))
+}
+
+/// Rejects job parameters larger than [`MAX_PARAMS_SIZE`]
+pub fn check_params_size(bytes: &[u8]) -> Result<(), String> {
+    if bytes.len() > MAX_PARAMS_SIZE {
+        return Err(format!(
+            "Parameters are {} bytes, at most {} are accepted",
+            bytes.len(),
+            MAX_PARAMS_SIZE
+        ));
+    }
+    Ok(())
+}
+
+/// Splits parameters into their version, payload and signature
+fn open_envelope(bytes: &[u8]) -> Result<(u32, Value, Option<CallerSignature>), String> {
+    check_params_size(bytes)?;
+    let value: Value = serde_json::from_slice(bytes)
+        .map_err(|e| format!("Failed to deserialize parameters: {}", e))?;
+
+    match value {
+        Value::Object(mut object)
+            if object.contains_key("version")
+                && object.contains_key("payload")
+                && object.len() == 2 + usize::from(object.contains_key("signature")) =>
+        {
+            let version = object
+                .get("version")
+                .and_then(Value::as_u64)
+                .and_then(|version| u32::try_from(version).ok())
+                .ok_or("Failed to deserialize parameters: invalid envelope version")?;
+            let signature = match object.remove("signature") {
+                Some(Value::Null) | None => None,
+                Some(signature) => Some(
+                    serde_json::from_value(signature)
+                        .map_err(|e| format!("Invalid caller signature: {}", e))?,
+                ),
+            };
+            Ok((
+                version,
+                object.remove("payload").unwrap_or_default(),
+                signature,
+            ))
+        }
+        bare => Ok((1, bare, None)),
+    }
+}
+
+/// Upgrades a payload of the given version to the current schema and deserializes it
+fn upgrade_params<T: VersionedParams>(version: u32, mut payload: Value) -> Result<T, String> {
+    if version == 0 || version > T::CURRENT_VERSION {
+        return Err(format!(
+            "Unsupported parameters version {}; this operator supports versions 1 to {}",
+            version,
+            T::CURRENT_VERSION
+        ));
+    }
+
+    for from in version..T::CURRENT_VERSION {
+        payload = T::upgrade(from, payload)?;
+    }
+
+    serde_json::from_value(payload).map_err(|e| format!("Failed to deserialize parameters: {}", e))
+}
+
+impl VersionedParams for CreateAgentParams {
+    /// Version 2 added the LLM provider, generation settings, network, schedule, action
+    /// providers, allowed actions and budget; version 3 replaced `tee_enabled` and `http_port`
+    /// with the deployment target
+    const CURRENT_VERSION: u32 = 3;
+
+    fn upgrade(version: u32, mut payload: Value) -> Result<Value, String> {
+        match version {
+            // Every field added in version 2 has a default
+            1 => Ok(payload),
+            2 => {
+                if let Some(config) = payload
+                    .get_mut("deployment_config")
+                    .and_then(Value::as_object_mut)
+                {
+                    upgrade_deployment_config(config)?;
+                }
+                Ok(payload)
+            }
+            _ => Err(format!("No upgrade from parameters version {}", version)),
+        }
+    }
+}
+
+/// Folds the `tee_enabled` and `http_port` of a deployment configuration into its target
+///
+/// Bare parameters are read as version 1 whatever their shape, so a configuration that
+/// already has a target is left alone.
+fn upgrade_deployment_config(config: &mut Map<String, Value>) -> Result<(), String> {
+    let tee_enabled = config.remove("tee_enabled");
+    let http_port = config.remove("http_port");
+    if config.contains_key("target") || (tee_enabled.is_none() && http_port.is_none()) {
+        return Ok(());
+    }
+    let target = match tee_enabled {
+        Some(Value::Bool(true)) => json!({ "type": "tee" }),
+        Some(Value::Bool(false)) | Some(Value::Null) | None => {
+            json!({ "type": "local", "http_port": http_port.unwrap_or(Value::Null) })
+        }
+        Some(other) => return Err(format!("Invalid tee_enabled: {}", other)),
+    };
+    config.insert("target".to_string(), target);
+    Ok(())
+}
+
+impl VersionedParams for CreateAgentsParams {
+    /// Versioned along with the parameters of each agent in the batch
+    const CURRENT_VERSION: u32 = CreateAgentParams::CURRENT_VERSION;
+
+    fn upgrade(version: u32, mut payload: Value) -> Result<Value, String> {
+        if let Some(agents) = payload.get_mut("agents").and_then(Value::as_array_mut) {
+            for agent in agents {
+                *agent = CreateAgentParams::upgrade(version, agent.take())?;
+            }
+        }
+        Ok(payload)
+    }
+}
+
+impl VersionedParams for CreateAgentSwarmParams {
+    /// Version 2 replaced `tee_enabled` and `http_port` with the deployment target
+    const CURRENT_VERSION: u32 = 2;
+
+    fn upgrade(version: u32, mut payload: Value) -> Result<Value, String> {
+        match version {
+            1 => {
+                if let Some(config) = payload
+                    .get_mut("deployment_config")
+                    .and_then(Value::as_object_mut)
+                {
+                    upgrade_deployment_config(config)?;
+                }
+                Ok(payload)
+            }
+            _ => Err(format!("No upgrade from parameters version {}", version)),
+        }
+    }
+}
+
+impl VersionedParams for DeployAgentParams {
+    /// Version 2 grouped the encrypted environment and the TEE app fields into `tee`
+    const CURRENT_VERSION: u32 = 2;
+
+    fn upgrade(version: u32, mut payload: Value) -> Result<Value, String> {
+        match version {
+            1 => {
+                if let Some(params) = payload.as_object_mut() {
+                    upgrade_tee_fields(params)?;
+                }
+                Ok(payload)
+            }
+            _ => Err(format!("No upgrade from parameters version {}", version)),
+        }
+    }
+}
+
+/// Moves the version 1 TEE fields of `deploy_agent` into its `tee` environment
+fn upgrade_tee_fields(params: &mut Map<String, Value>) -> Result<(), String> {
+    if let Some(encrypted_env) = params.remove("encrypted_env_vars") {
+        params.entry("encrypted_env").or_insert(encrypted_env);
+    }
+    let fields = [
+        ("encrypted_env", "encrypted_env"),
+        ("tee_pubkey", "pubkey"),
+        ("tee_app_id", "app_id"),
+        ("tee_salt", "salt"),
+    ];
+    let mut tee = Map::new();
+    for (old, new) in fields {
+        match params.remove(old) {
+            None | Some(Value::Null) => {}
+            Some(value) => {
+                tee.insert(new.to_string(), value);
+            }
+        }
+    }
+    if tee.is_empty() {
+        return Ok(());
+    }
+    if tee.len() < fields.len() {
+        return Err("All TEE fields must be provided together".to_string());
+    }
+    params.insert("tee".to_string(), Value::Object(tee));
+    Ok(())
+}
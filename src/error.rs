@@ -0,0 +1,126 @@
+use crate::payment::{PaymentError, PaymentRequired};
+use crate::rate_limit::QuotaExceeded;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Error of a job, reported to the caller as a structured payload
+///
+/// The payload is the error's JSON form, e.g. `{"kind": "Docker", "message": "..."}`, so
+/// callers can tell failures apart without matching on messages. Lower-level helpers still
+/// return `String` errors; handlers classify them where they call them.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(tag = "kind", content = "message")]
+pub enum BlueprintError {
+    /// The job's parameters are malformed or not allowed by the operator's policy
+    #[error("{0}")]
+    Params(String),
+    /// The caller may not operate on the agent
+    #[error("{0}")]
+    Unauthorized(String),
+    /// The caller exceeded the service's agent quota or rate limits
+    #[error("{0}")]
+    Quota(String),
+    /// The service instance is no longer paid for
+    #[error("{0}")]
+    Payment(String),
+    /// The chain could not be reached to check what the job depends on, e.g. its payment
+    #[error("{0}")]
+    Chain(String),
+    /// The agent's files could not be generated from its template
+    #[error("{0}")]
+    Template(String),
+    /// A Docker or docker-compose command failed
+    #[error("{0}")]
+    Docker(String),
+    /// The Phala TEE API failed
+    #[error("{0}")]
+    Tee(String),
+    /// The agent did not become healthy or could not be reached
+    #[error("{0}")]
+    Health(String),
+    /// Secrets could not be sealed, resolved or safely handed to the agent
+    #[error("{0}")]
+    Secrets(String),
+    /// Reading or writing the operator's files failed
+    #[error("{0}")]
+    Io(String),
+    /// Any other failure on the operator's side
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl BlueprintError {
+    pub fn message(&self) -> &str {
+        match self {
+            BlueprintError::Params(message)
+            | BlueprintError::Unauthorized(message)
+            | BlueprintError::Quota(message)
+            | BlueprintError::Payment(message)
+            | BlueprintError::Chain(message)
+            | BlueprintError::Template(message)
+            | BlueprintError::Docker(message)
+            | BlueprintError::Tee(message)
+            | BlueprintError::Health(message)
+            | BlueprintError::Secrets(message)
+            | BlueprintError::Io(message)
+            | BlueprintError::Internal(message) => message,
+        }
+    }
+
+    /// Prefixes the message with `context`, keeping the kind of error
+    pub fn context(mut self, context: impl fmt::Display) -> Self {
+        let message = match &mut self {
+            BlueprintError::Params(message)
+            | BlueprintError::Unauthorized(message)
+            | BlueprintError::Quota(message)
+            | BlueprintError::Payment(message)
+            | BlueprintError::Chain(message)
+            | BlueprintError::Template(message)
+            | BlueprintError::Docker(message)
+            | BlueprintError::Tee(message)
+            | BlueprintError::Health(message)
+            | BlueprintError::Secrets(message)
+            | BlueprintError::Io(message)
+            | BlueprintError::Internal(message) => message,
+        };
+        *message = format!("{}: {}", context, message);
+        self
+    }
+
+    /// Returns the JSON payload reported as the job's error
+    pub fn to_payload(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.to_string())
+    }
+}
+
+impl From<QuotaExceeded> for BlueprintError {
+    fn from(error: QuotaExceeded) -> Self {
+        BlueprintError::Quota(error.to_string())
+    }
+}
+
+impl From<PaymentRequired> for BlueprintError {
+    fn from(error: PaymentRequired) -> Self {
+        BlueprintError::Payment(error.to_string())
+    }
+}
+
+impl From<PaymentError> for BlueprintError {
+    fn from(error: PaymentError) -> Self {
+        match error {
+            PaymentError::Required(error) => error.into(),
+            PaymentError::ChainUnavailable { .. } => BlueprintError::Chain(error.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for BlueprintError {
+    fn from(error: std::io::Error) -> Self {
+        BlueprintError::Io(error.to_string())
+    }
+}
@@ -0,0 +1,122 @@
+use crate::agent_id::AgentId;
+use crate::ServiceContext;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Something that happened to an agent, published by job handlers and the health monitor
+///
+/// Events carry the job call that caused them, if any, so subscribers can attribute them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum AgentEvent {
+    /// The agent's directory and configuration were created
+    Created {
+        agent_id: AgentId,
+        call_id: Option<u64>,
+    },
+    /// The agent was deployed and, for local deployments, passed its health check
+    Deployed {
+        agent_id: AgentId,
+        call_id: Option<u64>,
+        endpoint: Option<String>,
+        tee_app_id: Option<String>,
+    },
+    /// The agent recovered after failing health checks
+    Healthy { agent_id: AgentId },
+    /// The agent stopped responding to health checks
+    Unhealthy {
+        agent_id: AgentId,
+        call_id: Option<u64>,
+        detail: String,
+    },
+    /// The agent's container is not running
+    Stopped {
+        agent_id: AgentId,
+        call_id: Option<u64>,
+        detail: String,
+    },
+    /// The agent's directory was removed
+    Deleted {
+        agent_id: AgentId,
+        call_id: Option<u64>,
+    },
+}
+
+impl AgentEvent {
+    pub fn agent_id(&self) -> &str {
+        match self {
+            AgentEvent::Created { agent_id, .. }
+            | AgentEvent::Deployed { agent_id, .. }
+            | AgentEvent::Healthy { agent_id }
+            | AgentEvent::Unhealthy { agent_id, .. }
+            | AgentEvent::Stopped { agent_id, .. }
+            | AgentEvent::Deleted { agent_id, .. } => agent_id,
+        }
+    }
+
+    /// Returns the name of the event, e.g. `deployed`
+    pub fn name(&self) -> &'static str {
+        match self {
+            AgentEvent::Created { .. } => "created",
+            AgentEvent::Deployed { .. } => "deployed",
+            AgentEvent::Healthy { .. } => "healthy",
+            AgentEvent::Unhealthy { .. } => "unhealthy",
+            AgentEvent::Stopped { .. } => "stopped",
+            AgentEvent::Deleted { .. } => "deleted",
+        }
+    }
+
+    /// Returns the job call that caused the event, if any
+    pub fn call_id(&self) -> Option<u64> {
+        match self {
+            AgentEvent::Created { call_id, .. }
+            | AgentEvent::Deployed { call_id, .. }
+            | AgentEvent::Unhealthy { call_id, .. }
+            | AgentEvent::Stopped { call_id, .. }
+            | AgentEvent::Deleted { call_id, .. } => *call_id,
+            AgentEvent::Healthy { .. } => None,
+        }
+    }
+}
+
+/// Broadcasts agent events to every subscribed subsystem (shared across threads)
+///
+/// Publishing never blocks; a subscriber that falls more than the bus's capacity behind
+/// misses the oldest events.
+#[derive(Clone, Debug)]
+pub struct EventBus {
+    sender: broadcast::Sender<AgentEvent>,
+}
+
+impl EventBus {
+    /// Events buffered for each subscriber
+    pub const DEFAULT_CAPACITY: usize = 256;
+
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self { sender }
+    }
+
+    /// Sends an event to the current subscribers, returning how many there are
+    pub fn publish(&self, event: AgentEvent) -> usize {
+        self.sender.send(event).unwrap_or(0)
+    }
+
+    /// Returns a receiver of every event published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<AgentEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+/// Publishes an event on the context's bus, if it has one
+pub fn publish(context: &ServiceContext, event: AgentEvent) {
+    if let Some(events) = &context.events {
+        events.publish(event);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{CreateAgentEvmEventHandler, DeployAgentEvmEventHandler, ServiceContext};
+use alloy_primitives::Address;
+use alloy_rpc_types::Log;
+use alloy_sol_types::sol;
+use blueprint_sdk::config::GadgetConfiguration;
+use blueprint_sdk::event_listeners::core::error::ProcessorError;
+use blueprint_sdk::logging;
+use blueprint_sdk::runners::core::runner::BlueprintRunner;
+
+sol! {
+    #[sol(rpc)]
+    #[derive(Debug)]
+    contract AgentRequests {
+        event CreateAgentRequested(uint64 indexed requestId, address indexed requester, bytes params);
+        event DeployAgentRequested(uint64 indexed requestId, address indexed requester, bytes params);
+
+        function requestCreateAgent(bytes calldata params) external returns (uint64 requestId);
+        function requestDeployAgent(bytes calldata params) external returns (uint64 requestId);
+    }
+}
+
+/// Length of an EVM address, in bytes
+const ADDRESS_LEN: usize = 20;
+
+/// ABI of the `AgentRequests` contract's events, used by the EVM event listeners
+pub const AGENT_REQUESTS_ABI_STRING: &str = r#"[
+  {
+    "type": "event",
+    "name": "CreateAgentRequested",
+    "anonymous": false,
+    "inputs": [
+      { "name": "requestId", "type": "uint64", "indexed": true },
+      { "name": "requester", "type": "address", "indexed": true },
+      { "name": "params", "type": "bytes", "indexed": false }
+    ]
+  },
+  {
+    "type": "event",
+    "name": "DeployAgentRequested",
+    "anonymous": false,
+    "inputs": [
+      { "name": "requestId", "type": "uint64", "indexed": true },
+      { "name": "requester", "type": "address", "indexed": true },
+      { "name": "params", "type": "bytes", "indexed": false }
+    ]
+  }
+]"#;
+
+/// Extracts the `create_agent` parameters from a `CreateAgentRequested` event, prefixed
+/// with the requester (see [`tag_request`])
+pub async fn create_agent_request_pre_processor(
+    (event, log): (AgentRequests::CreateAgentRequested, Log),
+) -> Result<Option<(Vec<u8>,)>, ProcessorError> {
+    logging::info!(
+        "Create agent request {} from {} (tx {:?})",
+        event.requestId,
+        event.requester,
+        log.transaction_hash
+    );
+    Ok(Some((tag_request(event.requester, &event.params),)))
+}
+
+/// Extracts the `deploy_agent` parameters from a `DeployAgentRequested` event, prefixed
+/// with the requester (see [`tag_request`])
+pub async fn deploy_agent_request_pre_processor(
+    (event, log): (AgentRequests::DeployAgentRequested, Log),
+) -> Result<Option<(Vec<u8>,)>, ProcessorError> {
+    logging::info!(
+        "Deploy agent request {} from {} (tx {:?})",
+        event.requestId,
+        event.requester,
+        log.transaction_hash
+    );
+    Ok(Some((tag_request(event.requester, &event.params),)))
+}
+
+/// Prefixes a request's parameters with the address that requested it
+///
+/// Pre-processors only see the event, so the requester travels to the job with its
+/// parameters, where [`requester_context`] takes it off again.
+pub fn tag_request(requester: Address, params: &[u8]) -> Vec<u8> {
+    let mut tagged = requester.to_vec();
+    tagged.extend_from_slice(params);
+    tagged
+}
+
+/// Splits tagged parameters, returning a context acting as their requester and the
+/// parameters themselves
+///
+/// The requester becomes the job's caller, so `create_agent` records it as the agent's
+/// owner and later requests are authorized and rate limited against it.
+pub fn requester_context(
+    context: &ServiceContext,
+    tagged: &[u8],
+) -> Result<(ServiceContext, Vec<u8>), String> {
+    if tagged.len() < ADDRESS_LEN {
+        return Err("EVM request is missing its requester".to_string());
+    }
+    let (requester, params) = tagged.split_at(ADDRESS_LEN);
+    let mut context = context.clone();
+    context.api_caller = Some(Address::from_slice(requester).to_string());
+    Ok((context, params.to_vec()))
+}
+
+/// Adds the EVM-triggered jobs to the runner if `AGENT_EVM_REQUESTS_ADDRESS` is set
+///
+/// The contract is read through `AGENT_EVM_RPC_URL`, or the configured HTTP RPC endpoint.
+pub fn register_evm_jobs(
+    runner: BlueprintRunner,
+    env: &GadgetConfiguration,
+    context: &ServiceContext,
+) -> Result<BlueprintRunner, String> {
+    let address = match std::env::var("AGENT_EVM_REQUESTS_ADDRESS") {
+        Ok(address) => address
+            .parse::<Address>()
+            .map_err(|e| format!("Invalid AGENT_EVM_REQUESTS_ADDRESS: {}", e))?,
+        Err(_) => return Ok(runner),
+    };
+    let rpc_url =
+        std::env::var("AGENT_EVM_RPC_URL").unwrap_or_else(|_| env.http_rpc_endpoint.clone());
+
+    logging::info!("Listening for agent requests from contract {}", address);
+    let provider = blueprint_sdk::utils::evm::get_provider_http(&rpc_url);
+    let contract = AgentRequests::new(address, provider);
+
+    Ok(runner
+        .job(CreateAgentEvmEventHandler::new(
+            contract.clone(),
+            context.clone(),
+        ))
+        .job(DeployAgentEvmEventHandler::new(contract, context.clone())))
+}
@@ -0,0 +1,448 @@
+use crate::events::AgentEvent;
+use crate::helpers::{check_container_status, run_blocking};
+use crate::types::Secret;
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::process::Command as TokioCommand;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+/// Image of the Cloudflare Tunnel connector
+pub const CLOUDFLARED_IMAGE: &str = "cloudflare/cloudflared:latest";
+
+/// Container connecting the named tunnel, shared by every agent
+const TUNNEL_CONTAINER: &str = "coinbase-agent-tunnel";
+
+/// Cloudflare API the named tunnel is managed through
+const CLOUDFLARE_API: &str = "https://api.cloudflare.com/client/v4";
+
+/// How long a quick tunnel may take to report its URL
+const QUICK_TUNNEL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Public exposure of locally deployed agents, for operator hosts behind NAT
+#[derive(Clone, Debug)]
+pub enum ExposureConfig {
+    /// One Cloudflare quick tunnel per agent, on a random `trycloudflare.com` host
+    ///
+    /// Needs no Cloudflare account, but the URL changes whenever the tunnel restarts.
+    QuickTunnel,
+    /// A named Cloudflare Tunnel serving each agent at `<agent_id>.<domain>`
+    Tunnel(CloudflareTunnel),
+}
+
+/// Named Cloudflare Tunnel whose routes and DNS records the operator manages
+#[derive(Clone, Debug)]
+pub struct CloudflareTunnel {
+    /// Base URL of the Cloudflare API
+    pub api_base: String,
+    /// API token allowed to edit the zone's DNS and the account's tunnels
+    pub api_token: Secret<String>,
+    pub account_id: String,
+    pub zone_id: String,
+    pub tunnel_id: String,
+    /// Token the connector runs the tunnel with
+    pub tunnel_token: Secret<String>,
+    /// Domain agents are served under, e.g. `agents.example.com`
+    pub domain: String,
+}
+
+impl ExposureConfig {
+    /// Loads the exposure mode from `AGENT_EXPOSURE`: `cloudflare-quick`, or `cloudflare-tunnel`
+    /// with the tunnel from the `CLOUDFLARE_*` variables and `AGENT_EXPOSURE_DOMAIN`
+    ///
+    /// Returns `None` when unset or `none`.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let required =
+            |name: &str| var(name).ok_or(format!("{} must be set for cloudflare-tunnel", name));
+        match var("AGENT_EXPOSURE").as_deref() {
+            None | Some("none") => Ok(None),
+            Some("cloudflare-quick") => Ok(Some(Self::QuickTunnel)),
+            Some("cloudflare-tunnel") => Ok(Some(Self::Tunnel(CloudflareTunnel {
+                api_base: CLOUDFLARE_API.to_string(),
+                api_token: Secret::new(required("CLOUDFLARE_API_TOKEN")?),
+                account_id: required("CLOUDFLARE_ACCOUNT_ID")?,
+                zone_id: required("CLOUDFLARE_ZONE_ID")?,
+                tunnel_id: required("CLOUDFLARE_TUNNEL_ID")?,
+                tunnel_token: Secret::new(required("CLOUDFLARE_TUNNEL_TOKEN")?),
+                domain: required("AGENT_EXPOSURE_DOMAIN")?
+                    .trim_matches('.')
+                    .to_ascii_lowercase(),
+            }))),
+            Some(other) => Err(format!(
+                "Invalid AGENT_EXPOSURE {}: expected none, cloudflare-quick or cloudflare-tunnel",
+                other
+            )),
+        }
+    }
+}
+
+/// Makes an agent reachable from the internet, returning its public URL
+pub async fn expose(
+    context: &ServiceContext,
+    config: &ExposureConfig,
+    agent_id: &str,
+    http_port: u16,
+) -> Result<String, String> {
+    match config {
+        ExposureConfig::QuickTunnel => start_quick_tunnel(agent_id, http_port).await,
+        ExposureConfig::Tunnel(tunnel) => {
+            let client = context.http_client.clone().unwrap_or_default();
+            tunnel.expose(&client, agent_id, http_port).await
+        }
+    }
+}
+
+/// Takes an agent off the internet
+pub async fn unexpose(
+    context: &ServiceContext,
+    config: &ExposureConfig,
+    agent_id: &str,
+) -> Result<(), String> {
+    match config {
+        ExposureConfig::QuickTunnel => {
+            remove_container(&quick_tunnel_container(agent_id)).await;
+            Ok(())
+        }
+        ExposureConfig::Tunnel(tunnel) => {
+            let client = context.http_client.clone().unwrap_or_default();
+            tunnel.unexpose(&client, agent_id).await
+        }
+    }
+}
+
+fn quick_tunnel_container(agent_id: &str) -> String {
+    format!("{}-{}", TUNNEL_CONTAINER, agent_id)
+}
+
+async fn remove_container(container_name: &str) {
+    let _ = TokioCommand::new("docker")
+        .args(["rm", "-f", container_name])
+        .output()
+        .await;
+}
+
+/// Returns the URL a quick tunnel reports in its logs
+pub fn parse_quick_tunnel_url(logs: &str) -> Option<String> {
+    let pattern =
+        regex::Regex::new(r"https://[a-z0-9-]+\.trycloudflare\.com").expect("valid regex");
+    pattern.find(logs).map(|url| url.as_str().to_string())
+}
+
+/// Starts a quick tunnel to the agent's HTTP port and waits for its URL
+async fn start_quick_tunnel(agent_id: &str, http_port: u16) -> Result<String, String> {
+    let container_name = quick_tunnel_container(agent_id);
+    remove_container(&container_name).await;
+    let origin = format!("http://127.0.0.1:{}", http_port);
+    let output = TokioCommand::new("docker")
+        .args([
+            "run",
+            "-d",
+            "--name",
+            &container_name,
+            "--network",
+            "host",
+            "--restart",
+            "unless-stopped",
+            CLOUDFLARED_IMAGE,
+            "tunnel",
+            "--no-autoupdate",
+            "--url",
+            &origin,
+        ])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute docker run command: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to start quick tunnel: {}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+
+    let deadline = tokio::time::Instant::now() + QUICK_TUNNEL_TIMEOUT;
+    while tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let logs = TokioCommand::new("docker")
+            .args(["logs", &container_name])
+            .output()
+            .await
+            .map_err(|e| format!("Failed to execute docker logs command: {}", e))?;
+        // cloudflared logs to stderr
+        let logs = format!(
+            "{}{}",
+            String::from_utf8_lossy(&logs.stdout),
+            String::from_utf8_lossy(&logs.stderr)
+        );
+        if let Some(url) = parse_quick_tunnel_url(&logs) {
+            return Ok(url);
+        }
+    }
+    remove_container(&container_name).await;
+    Err(format!(
+        "Quick tunnel reported no URL within {}s",
+        QUICK_TUNNEL_TIMEOUT.as_secs()
+    ))
+}
+
+/// Sets the rule routing `hostname` in a tunnel's ingress rules, or removes it when
+/// `service` is `None`, keeping the catch-all rule last
+pub fn apply_ingress_rule(
+    ingress: Vec<Value>,
+    hostname: &str,
+    service: Option<&str>,
+) -> Vec<Value> {
+    // Cloudflare requires a rule without a host name last, catching everything else
+    let (mut rules, mut catch_all): (Vec<Value>, Vec<Value>) = ingress
+        .into_iter()
+        .filter(|rule| rule.get("hostname").and_then(Value::as_str) != Some(hostname))
+        .partition(|rule| rule.get("hostname").is_some());
+    if let Some(service) = service {
+        rules.push(json!({ "hostname": hostname, "service": service }));
+    }
+    rules.push(
+        catch_all
+            .pop()
+            .unwrap_or_else(|| json!({ "service": "http_status:404" })),
+    );
+    rules
+}
+
+impl CloudflareTunnel {
+    /// Host name an agent is served at
+    pub fn hostname(&self, agent_id: &str) -> Result<String, String> {
+        // Agent IDs are alphanumeric with dashes, so only their length can make a bad label
+        if agent_id.is_empty() || agent_id.len() > 63 {
+            return Err(format!("Agent ID {} is not a valid DNS label", agent_id));
+        }
+        Ok(format!("{}.{}", agent_id.to_ascii_lowercase(), self.domain))
+    }
+
+    /// Calls the Cloudflare API, returning the `result` of a successful call
+    async fn call(
+        &self,
+        client: &reqwest::Client,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Value, String> {
+        let mut request = client
+            .request(method, format!("{}{}", self.api_base, path))
+            .bearer_auth(self.api_token.expose());
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response: Value = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach the Cloudflare API: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid Cloudflare API response: {}", e))?;
+        if response["success"] != json!(true) {
+            return Err(format!(
+                "Cloudflare API call {} failed: {}",
+                path, response["errors"]
+            ));
+        }
+        Ok(response["result"].clone())
+    }
+
+    /// Points `hostname` at the tunnel, or removes its record when `point` is false
+    async fn sync_dns(
+        &self,
+        client: &reqwest::Client,
+        hostname: &str,
+        point: bool,
+    ) -> Result<(), String> {
+        let records_path = format!("/zones/{}/dns_records", self.zone_id);
+        let existing = self
+            .call(
+                client,
+                reqwest::Method::GET,
+                &format!("{}?type=CNAME&name={}", records_path, hostname),
+                None,
+            )
+            .await?;
+        let existing = existing
+            .as_array()
+            .and_then(|records| records.first())
+            .cloned();
+        let target = format!("{}.cfargotunnel.com", self.tunnel_id);
+        let record = json!({
+            "type": "CNAME",
+            "name": hostname,
+            "content": target,
+            "proxied": true,
+            "ttl": 1,
+        });
+        let record_path = |record: &Value| {
+            format!(
+                "{}/{}",
+                records_path,
+                record["id"].as_str().unwrap_or_default()
+            )
+        };
+        match (existing, point) {
+            (None, true) => {
+                self.call(client, reqwest::Method::POST, &records_path, Some(record))
+                    .await?;
+            }
+            (Some(existing), true) if existing["content"] != json!(target) => {
+                self.call(
+                    client,
+                    reqwest::Method::PUT,
+                    &record_path(&existing),
+                    Some(record),
+                )
+                .await?;
+            }
+            (Some(existing), false) => {
+                self.call(
+                    client,
+                    reqwest::Method::DELETE,
+                    &record_path(&existing),
+                    None,
+                )
+                .await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Routes `hostname` to `service` in the tunnel's configuration, or stops routing it
+    async fn sync_route(
+        &self,
+        client: &reqwest::Client,
+        hostname: &str,
+        service: Option<&str>,
+    ) -> Result<(), String> {
+        let path = format!(
+            "/accounts/{}/cfd_tunnel/{}/configurations",
+            self.account_id, self.tunnel_id
+        );
+        let current = self.call(client, reqwest::Method::GET, &path, None).await?;
+        let mut config = match current.get("config") {
+            Some(Value::Object(config)) => config.clone(),
+            _ => serde_json::Map::new(),
+        };
+        let ingress = config
+            .get("ingress")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        config.insert(
+            "ingress".to_string(),
+            Value::Array(apply_ingress_rule(ingress, hostname, service)),
+        );
+        self.call(
+            client,
+            reqwest::Method::PUT,
+            &path,
+            Some(json!({ "config": config })),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Starts the tunnel's connector if it isn't running
+    async fn ensure_connector(&self) -> Result<(), String> {
+        if run_blocking(|| check_container_status(TUNNEL_CONTAINER)).await? {
+            return Ok(());
+        }
+        remove_container(TUNNEL_CONTAINER).await;
+        // The token is passed through the environment to keep it out of process listings
+        let output = TokioCommand::new("docker")
+            .args([
+                "run",
+                "-d",
+                "--name",
+                TUNNEL_CONTAINER,
+                "--network",
+                "host",
+                "--restart",
+                "unless-stopped",
+                "-e",
+                "TUNNEL_TOKEN",
+                CLOUDFLARED_IMAGE,
+                "tunnel",
+                "--no-autoupdate",
+                "run",
+            ])
+            .env("TUNNEL_TOKEN", self.tunnel_token.expose())
+            .output()
+            .await
+            .map_err(|e| format!("Failed to execute docker run command: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Failed to start the tunnel connector: {}",
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ));
+        }
+        logging::info!("Started Cloudflare Tunnel connector");
+        Ok(())
+    }
+
+    async fn expose(
+        &self,
+        client: &reqwest::Client,
+        agent_id: &str,
+        http_port: u16,
+    ) -> Result<String, String> {
+        let hostname = self.hostname(agent_id)?;
+        self.sync_route(
+            client,
+            &hostname,
+            Some(&format!("http://127.0.0.1:{}", http_port)),
+        )
+        .await?;
+        self.sync_dns(client, &hostname, true).await?;
+        self.ensure_connector().await?;
+        Ok(format!("https://{}", hostname))
+    }
+
+    async fn unexpose(&self, client: &reqwest::Client, agent_id: &str) -> Result<(), String> {
+        let hostname = self.hostname(agent_id)?;
+        self.sync_route(client, &hostname, None).await?;
+        self.sync_dns(client, &hostname, false).await
+    }
+}
+
+/// Takes agents off the internet once they are deleted, and stops the quick tunnels of
+/// stopped agents, whose URLs don't survive a restart anyway
+pub async fn run_exposure_cleanup(
+    context: ServiceContext,
+    config: ExposureConfig,
+    mut events: Receiver<AgentEvent>,
+) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(missed)) => {
+                logging::warn!(phase = "exposure", missed, "Exposure cleanup fell behind");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        let cleanup = matches!(
+            (&event, &config),
+            (AgentEvent::Deleted { .. }, _)
+                | (AgentEvent::Stopped { .. }, ExposureConfig::QuickTunnel)
+        );
+        if !cleanup {
+            continue;
+        }
+        let agent_id = event.agent_id();
+        if let Err(e) = unexpose(&context, &config, agent_id).await {
+            logging::warn!(agent_id = %agent_id, phase = "exposure", error = %e, "Failed to unexpose agent");
+        }
+    }
+}
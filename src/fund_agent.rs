@@ -0,0 +1,133 @@
+use crate::codec;
+use crate::deploy_agent::get_required_ports;
+use crate::error::BlueprintError;
+use crate::helpers::{read_env_file, run_blocking};
+use crate::journal;
+use crate::ownership::authorize;
+use crate::types::{AgentConfig, FundAgentParams, FundAgentResult};
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Seconds `fund_agent` waits for the funds to show by default
+pub const DEFAULT_FUND_WAIT_SECS: u64 = 60;
+
+/// Longest `fund_agent` waits for the funds to show, in seconds
+pub const MAX_FUND_WAIT_SECS: u64 = 300;
+
+/// Delay between checks of the wallet's balance
+const BALANCE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Handles the fund_agent job
+///
+/// Has a deployed agent request testnet ETH for its wallet from the CDP faucet, then waits
+/// for its balance to rise. Only the agent's owner and the service owner may fund it, since
+/// the faucet limits requests per CDP project.
+pub async fn handle_fund_agent(
+    params_bytes: Vec<u8>,
+    context: &ServiceContext,
+) -> Result<Vec<u8>, BlueprintError> {
+    let (params, encoding): (FundAgentParams, _) =
+        codec::decode(&params_bytes).map_err(BlueprintError::Params)?;
+    let wait_secs = params.wait_secs.unwrap_or(DEFAULT_FUND_WAIT_SECS);
+    if wait_secs > MAX_FUND_WAIT_SECS {
+        return Err(BlueprintError::Params(format!(
+            "wait_secs must be at most {}",
+            MAX_FUND_WAIT_SECS
+        )));
+    }
+
+    let base_dir = context
+        .agents_base_dir()
+        .unwrap_or_else(|| "./agents".to_string());
+    let agent_dir = params.agent_id.dir(&base_dir);
+    if !agent_dir.exists() {
+        return Err(BlueprintError::Params(format!(
+            "Unknown agent: {}",
+            params.agent_id
+        )));
+    }
+    let env_path = agent_dir.join(".env");
+    let agent_env = run_blocking(move || read_env_file(&env_path))
+        .await
+        .map_err(BlueprintError::Io)?;
+    authorize(context, &params.agent_id, &agent_env).map_err(BlueprintError::Unauthorized)?;
+
+    // The agent must have been deployed locally for us to reach it
+    let (http_port, _) =
+        get_required_ports(&params.agent_id, context).map_err(BlueprintError::Params)?;
+    let timeout = context.http_config().request_timeout;
+    let agent = context
+        .agent_endpoint(format!("http://localhost:{}", http_port))
+        .map_err(BlueprintError::Internal)?;
+
+    let wallet = agent
+        .get_wallet(timeout)
+        .await
+        .map_err(BlueprintError::Health)?;
+    if !AgentConfig::TESTNET_NETWORKS.contains(&wallet.network_id.as_str()) {
+        return Err(BlueprintError::Params(format!(
+            "Agent {} is on {}; faucet funds are only available on {}",
+            params.agent_id,
+            wallet.network_id,
+            AgentConfig::TESTNET_NETWORKS.join(", ")
+        )));
+    }
+
+    logging::info!(
+        agent_id = %params.agent_id,
+        address = %wallet.address,
+        network_id = %wallet.network_id,
+        "Requesting faucet funds"
+    );
+    let tx_hashes = agent
+        .request_faucet_funds(timeout)
+        .await
+        .map_err(BlueprintError::Health)?;
+    journal::record(context, "fund_agent", &params.agent_id, "requested");
+
+    // Wait for the funds to show; agents that don't report their balance can't be waited on
+    let initial = wallet.balance.as_deref().and_then(parse_balance);
+    let mut balance = wallet.balance;
+    let mut funded = false;
+    if let Some(initial) = initial {
+        let deadline = Instant::now() + Duration::from_secs(wait_secs);
+        while !funded && Instant::now() < deadline {
+            tokio::time::sleep(BALANCE_POLL_INTERVAL).await;
+            match agent.get_wallet(timeout).await {
+                Ok(current) => {
+                    funded = current
+                        .balance
+                        .as_deref()
+                        .and_then(parse_balance)
+                        .is_some_and(|current| current > initial);
+                    balance = current.balance;
+                }
+                Err(e) => {
+                    logging::warn!(agent_id = %params.agent_id, error = %e, "Failed to check the wallet's balance")
+                }
+            }
+        }
+    }
+    if !funded && wait_secs > 0 {
+        logging::warn!(
+            agent_id = %params.agent_id,
+            "Faucet funds didn't show in the wallet's balance in time"
+        );
+    }
+
+    let result = FundAgentResult {
+        agent_id: params.agent_id,
+        wallet_address: wallet.address,
+        network_id: wallet.network_id,
+        tx_hashes,
+        balance,
+        funded,
+    };
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
+
+fn parse_balance(balance: &str) -> Option<u128> {
+    balance.parse().ok()
+}
@@ -0,0 +1,190 @@
+use crate::agent_status::handle_agent_status;
+use crate::create_agent::handle_create_agent;
+use crate::deploy_agent::handle_deploy_agent;
+use crate::error::BlueprintError;
+use crate::status_api::{agent_logs, is_known_agent};
+use crate::stop_agent::handle_stop_agent;
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use std::net::SocketAddr;
+use tonic::service::Interceptor;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+/// Types generated from `proto/agent_manager.proto`
+pub mod proto {
+    tonic::include_proto!("coinbase_agent.v1");
+}
+
+use proto::agent_manager_server::{AgentManager, AgentManagerServer};
+use proto::{AgentLogsRequest, AgentLogsResponse, JobRequest, JobResponse};
+
+/// gRPC API driving the agent lifecycle without going through Tangle jobs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GrpcConfig {
+    /// Address the API listens on, e.g. `127.0.0.1:50051`
+    pub bind: SocketAddr,
+    /// Bearer token clients must send in the `authorization` header
+    pub token: String,
+}
+
+impl GrpcConfig {
+    /// Loads the listen address from `AGENT_GRPC_ADDR` and the token from `AGENT_GRPC_TOKEN`
+    ///
+    /// Returns `None` when no address is set. The API manages agents as the service owner, so
+    /// it refuses to start without a token.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(addr) = std::env::var("AGENT_GRPC_ADDR")
+            .ok()
+            .filter(|addr| !addr.trim().is_empty())
+        else {
+            return Ok(None);
+        };
+        let bind = addr
+            .trim()
+            .parse()
+            .map_err(|e| format!("Invalid AGENT_GRPC_ADDR {}: {}", addr, e))?;
+        let token = std::env::var("AGENT_GRPC_TOKEN")
+            .ok()
+            .filter(|token| !token.trim().is_empty())
+            .ok_or("AGENT_GRPC_TOKEN must be set when AGENT_GRPC_ADDR is")?;
+        Ok(Some(Self { bind, token }))
+    }
+}
+
+/// Maps a handler's error to the closest gRPC status
+pub fn to_status(error: BlueprintError) -> Status {
+    let message = error.to_payload();
+    match error {
+        BlueprintError::Params(_) => Status::invalid_argument(message),
+        BlueprintError::Unauthorized(_) => Status::permission_denied(message),
+        BlueprintError::Quota(_) => Status::resource_exhausted(message),
+        BlueprintError::Payment(_) => Status::failed_precondition(message),
+        BlueprintError::Chain(_) => Status::unavailable(message),
+        BlueprintError::Health(_) => Status::unavailable(message),
+        _ => Status::internal(message),
+    }
+}
+
+/// Implementation of the `AgentManager` service over the jobs' handlers
+#[derive(Clone)]
+pub struct AgentManagerService {
+    context: ServiceContext,
+}
+
+impl AgentManagerService {
+    /// Creates the service, acting as the service owner on every request
+    pub fn new(mut context: ServiceContext) -> Self {
+        context.call_id = None;
+        context.api_caller = context.service_owner.clone();
+        Self { context }
+    }
+}
+
+#[tonic::async_trait]
+impl AgentManager for AgentManagerService {
+    async fn create_agent(
+        &self,
+        request: Request<JobRequest>,
+    ) -> Result<Response<JobResponse>, Status> {
+        let result = handle_create_agent(request.into_inner().params, &self.context)
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(JobResponse { result }))
+    }
+
+    async fn deploy_agent(
+        &self,
+        request: Request<JobRequest>,
+    ) -> Result<Response<JobResponse>, Status> {
+        let result = handle_deploy_agent(request.into_inner().params, &self.context)
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(JobResponse { result }))
+    }
+
+    async fn stop_agent(
+        &self,
+        request: Request<JobRequest>,
+    ) -> Result<Response<JobResponse>, Status> {
+        let result = handle_stop_agent(request.into_inner().params, &self.context)
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(JobResponse { result }))
+    }
+
+    async fn get_agent_status(
+        &self,
+        request: Request<JobRequest>,
+    ) -> Result<Response<JobResponse>, Status> {
+        let result = handle_agent_status(request.into_inner().params, &self.context)
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(JobResponse { result }))
+    }
+
+    async fn get_agent_logs(
+        &self,
+        request: Request<AgentLogsRequest>,
+    ) -> Result<Response<AgentLogsResponse>, Status> {
+        let request = request.into_inner();
+        if !is_known_agent(&self.context, &request.agent_id) {
+            return Err(Status::not_found(format!(
+                "Unknown agent: {}",
+                request.agent_id
+            )));
+        }
+        let logs = agent_logs(&request.agent_id, request.tail.map(|tail| tail as usize))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(AgentLogsResponse {
+            agent_id: logs.agent_id,
+            lines: logs.lines,
+        }))
+    }
+}
+
+/// Rejects requests without the configured bearer token
+#[derive(Clone)]
+struct BearerAuth {
+    token: String,
+}
+
+impl Interceptor for BearerAuth {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let presented = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        // Compare in constant time, so the token can't be guessed byte by byte
+        let matches = presented.len() == self.token.len()
+            && presented
+                .bytes()
+                .zip(self.token.bytes())
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0;
+        if matches {
+            Ok(request)
+        } else {
+            Err(Status::unauthenticated("Missing or invalid bearer token"))
+        }
+    }
+}
+
+/// Serves the gRPC API until the process exits
+pub async fn serve(context: ServiceContext, config: GrpcConfig) -> Result<(), String> {
+    let service = AgentManagerServer::with_interceptor(
+        AgentManagerService::new(context),
+        BearerAuth {
+            token: config.token,
+        },
+    );
+    logging::info!("gRPC API listening on {}", config.bind);
+    Server::builder()
+        .add_service(service)
+        .serve(config.bind)
+        .await
+        .map_err(|e| format!("gRPC API failed: {}", e))
+}
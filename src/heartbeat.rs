@@ -0,0 +1,161 @@
+use crate::alerts::{check_heartbeat, AlertTracker};
+use crate::helpers::probe_agents;
+use crate::lifecycle::submit_remark;
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Most unhealthy agents named in a heartbeat submitted on-chain
+pub const MAX_REPORTED_UNHEALTHY: usize = 32;
+
+/// Aggregated health of the agents an operator manages, reported as its QoS heartbeat
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Heartbeat {
+    /// Unix timestamp (seconds) of the heartbeat
+    pub timestamp: u64,
+    pub total_agents: usize,
+    pub healthy_agents: usize,
+    /// Agents that failed their health check
+    pub unhealthy_agents: Vec<String>,
+    /// Unhealthy agents left out of `unhealthy_agents` to keep the heartbeat small
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub omitted_unhealthy: usize,
+}
+
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
+
+impl Heartbeat {
+    /// Aggregates per-agent health results into a heartbeat for the current time
+    pub fn from_health(results: Vec<(String, bool)>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let total_agents = results.len();
+        let unhealthy_agents: Vec<String> = results
+            .into_iter()
+            .filter(|(_, healthy)| !healthy)
+            .map(|(agent_id, _)| agent_id)
+            .collect();
+
+        Self {
+            timestamp,
+            total_agents,
+            healthy_agents: total_agents - unhealthy_agents.len(),
+            unhealthy_agents,
+            omitted_unhealthy: 0,
+        }
+    }
+
+    /// Returns the heartbeat naming at most `max` unhealthy agents, counting the others
+    pub fn capped(&self, max: usize) -> Self {
+        let mut capped = self.clone();
+        if capped.unhealthy_agents.len() > max {
+            capped.omitted_unhealthy += capped.unhealthy_agents.len() - max;
+            capped.unhealthy_agents.truncate(max);
+        }
+        capped
+    }
+
+    /// Renders the heartbeat as metrics in the Prometheus text format
+    pub fn to_metrics(&self) -> String {
+        let gauges = [
+            (
+                "agentkit_agents",
+                "Agents with known ports",
+                self.total_agents as u64,
+            ),
+            (
+                "agentkit_healthy_agents",
+                "Agents that passed their last health check",
+                self.healthy_agents as u64,
+            ),
+            (
+                "agentkit_heartbeat_timestamp_seconds",
+                "Unix time of the last heartbeat",
+                self.timestamp,
+            ),
+        ];
+        let mut metrics = String::new();
+        for (name, help, value) in gauges {
+            let _ = writeln!(metrics, "# HELP {} {}", name, help);
+            let _ = writeln!(metrics, "# TYPE {} gauge", name);
+            let _ = writeln!(metrics, "{} {}", name, value);
+        }
+        metrics
+    }
+
+    /// Returns the share of healthy agents, or 1.0 if there are none
+    pub fn health_ratio(&self) -> f64 {
+        if self.total_agents == 0 {
+            return 1.0;
+        }
+        self.healthy_agents as f64 / self.total_agents as f64
+    }
+}
+
+/// Latest heartbeat, served as QoS metrics by the status API (shared across threads)
+#[derive(Clone, Debug, Default)]
+pub struct HeartbeatMetrics(Arc<Mutex<Option<Heartbeat>>>);
+
+impl HeartbeatMetrics {
+    pub fn record(&self, heartbeat: Heartbeat) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(heartbeat);
+    }
+
+    /// Returns the latest heartbeat, if one was collected yet
+    pub fn latest(&self) -> Option<Heartbeat> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Checks the health of every agent with known ports and aggregates the results
+pub async fn collect_heartbeat(context: &ServiceContext) -> Heartbeat {
+    let agents: Vec<(String, String)> = match &context.agent_registry {
+        Some(registry) => registry
+            .snapshot()
+            .into_iter()
+            .map(|(agent_id, ports)| (agent_id, format!("http://localhost:{}", ports.http_port)))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    Heartbeat::from_health(probe_agents(context, agents).await)
+}
+
+/// Periodically collects a heartbeat until the process exits
+///
+/// Each heartbeat is published as the context's QoS metrics and, if `on_chain`, also
+/// submitted on-chain naming at most [`MAX_REPORTED_UNHEALTHY`] unhealthy agents. Failed
+/// submissions are logged and retried on the next tick. Agents that turned unhealthy or
+/// keep restarting since the previous tick are alerted on.
+pub async fn run_heartbeat(context: ServiceContext, interval: Duration, on_chain: bool) {
+    let mut ticker = tokio::time::interval(interval);
+    let mut alerts = AlertTracker::default();
+    loop {
+        ticker.tick().await;
+
+        let heartbeat = collect_heartbeat(&context).await;
+        logging::info!(
+            job = "heartbeat",
+            healthy_agents = heartbeat.healthy_agents,
+            total_agents = heartbeat.total_agents,
+            "Collected heartbeat"
+        );
+        if let Some(metrics) = &context.heartbeat_metrics {
+            metrics.record(heartbeat.clone());
+        }
+        if on_chain {
+            let capped = heartbeat.capped(MAX_REPORTED_UNHEALTHY);
+            if let Err(e) = submit_remark(&context, &capped).await {
+                logging::warn!(job = "heartbeat", error = %e, "Failed to report heartbeat");
+            }
+        }
+        check_heartbeat(&context, &heartbeat, &mut alerts).await;
+    }
+}
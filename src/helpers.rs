@@ -1,6 +1,273 @@
 use crate::agent_endpoint::AgentEndpoint;
+use crate::config::HttpConfig;
+use crate::ServiceContext;
 use blueprint_sdk::logging;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
 use std::process::Command;
+use std::sync::Arc;
+use tokio::process::Command as TokioCommand;
+use tokio::sync::Semaphore;
+use tracing::Instrument;
+
+/// Sets `key=value` in the contents of an env file
+///
+/// Replaces the first line assigning `key` (commented out or not) and appends the
+/// assignment if the key does not appear at all.
+pub fn set_env_var(env_content: &str, key: &str, value: &str) -> String {
+    let assignment = format!("{}={}", key, value);
+    let mut found = false;
+    let mut lines: Vec<String> = env_content
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start_matches('#').trim_start();
+            if !found && trimmed.starts_with(&format!("{}=", key)) {
+                found = true;
+                assignment.clone()
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+
+    if !found {
+        lines.push(assignment);
+    }
+
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+/// Quotes a value for an env file if it would not survive being written bare
+///
+/// Values containing whitespace, quotes, `#` or newlines are wrapped in double quotes
+/// with backslashes, quotes and line breaks escaped, which both dotenv and Docker
+/// Compose expand back to the original value.
+pub fn quote_env_value(value: &str) -> String {
+    let needs_quotes = value
+        .chars()
+        .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '#' | '\\'));
+    if !needs_quotes {
+        return value.to_string();
+    }
+
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Renders variables as the contents of an env file, one `KEY=VALUE` per line
+pub fn render_env_vars(env_vars: &[(String, String)]) -> String {
+    let mut content = String::new();
+    for (key, value) in env_vars {
+        content.push_str(key);
+        content.push('=');
+        content.push_str(&quote_env_value(value));
+        content.push('\n');
+    }
+    content
+}
+
+/// Variables of the operator's environment `docker-compose` is given, besides `DOCKER_*`
+const COMPOSE_ENV_VARS: &[&str] = &["PATH", "HOME"];
+
+/// Returns a `docker-compose` command run in `dir` with a cleared environment
+///
+/// Compose files interpolate any variable of the environment, so only what is needed to
+/// reach Docker is kept from the operator's, and callers add the agent's own variables.
+pub fn compose_command(dir: &Path) -> TokioCommand {
+    let mut command = TokioCommand::new("docker-compose");
+    command
+        .current_dir(dir)
+        .env_clear()
+        .envs(std::env::vars().filter(|(name, _)| {
+            COMPOSE_ENV_VARS.contains(&name.as_str()) || name.starts_with("DOCKER_")
+        }));
+    command
+}
+
+/// Runs blocking work, such as filesystem I/O or `docker` commands, on Tokio's blocking pool
+///
+/// Keeps job handlers from stalling the executor that processes other jobs. The work runs in
+/// the caller's tracing span, so its logs keep their job and agent fields.
+pub async fn run_blocking<T, F>(f: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    let span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || span.in_scope(f))
+        .await
+        .map_err(|e| format!("Blocking task failed: {}", e))?
+}
+
+/// Runs `task` on every item concurrently, at most `limit` at a time, returning the outputs
+/// in the order of the items
+///
+/// Tasks run in the caller's tracing span. A task that panics yields `None`.
+pub async fn fan_out<T, R, F, Fut>(
+    items: impl IntoIterator<Item = T>,
+    limit: usize,
+    task: F,
+) -> Vec<Option<R>>
+where
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = R> + Send + 'static,
+    R: Send + 'static,
+{
+    let slots = Arc::new(Semaphore::new(limit.max(1)));
+    let span = tracing::Span::current();
+    let handles: Vec<_> = items
+        .into_iter()
+        .map(|item| {
+            let slots = slots.clone();
+            let future = task(item);
+            tokio::spawn(
+                async move {
+                    // The semaphore is never closed
+                    let _slot = slots.acquire_owned().await.expect("fan-out slots closed");
+                    future.await
+                }
+                .instrument(span.clone()),
+            )
+        })
+        .collect();
+
+    let mut outputs = Vec::with_capacity(handles.len());
+    for handle in handles {
+        outputs.push(handle.await.ok());
+    }
+    outputs
+}
+
+/// Checks the health of agents at once, at most `health_concurrency` at a time
+///
+/// Takes `(agent_id, base_url)` pairs and returns whether each agent is healthy, in order.
+pub async fn probe_agents(
+    context: &ServiceContext,
+    agents: Vec<(String, String)>,
+) -> Vec<(String, bool)> {
+    let config = context.http_config();
+    let agent_ids: Vec<String> = agents
+        .iter()
+        .map(|(agent_id, _)| agent_id.clone())
+        .collect();
+    let results = fan_out(agents, config.health_concurrency, |(agent_id, base_url)| {
+        let agent = context.agent_endpoint(base_url);
+        async move {
+            let healthy = match agent {
+                Ok(agent) => agent.check_health(config.request_timeout).await.is_ok(),
+                Err(_) => false,
+            };
+            logging::debug!(agent_id = %agent_id, phase = "health", healthy, "Checked agent health");
+            healthy
+        }
+    })
+    .await;
+    agent_ids
+        .into_iter()
+        .zip(results)
+        .map(|(agent_id, healthy)| (agent_id, healthy.unwrap_or(false)))
+        .collect()
+}
+
+/// Writes a file that only the operator's user can read, e.g. an env file holding secrets
+///
+/// A symbolic link in place of the file is refused rather than followed, so a link left in
+/// an agent's directory can't redirect the write elsewhere.
+pub fn write_private_file(path: &Path, content: impl AsRef<[u8]>) -> Result<(), String> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+    }
+
+    let mut file = options
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // The mode only applies to new files, so tighten existing ones too
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to restrict {}: {}", path.display(), e))?;
+    }
+    std::io::Write::write_all(&mut file, content.as_ref())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Reads the variables of an env file, returning an empty map if it does not exist
+pub fn read_env_file(path: &Path) -> Result<HashMap<String, String>, String> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    Ok(parse_env_content(&content))
+}
+
+/// Parses `KEY=VALUE` lines, skipping comments and stripping quotes and trailing comments
+pub fn parse_env_content(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let value = match value.chars().next() {
+                Some('"') => unescape_double_quoted(&value[1..]),
+                Some('\'') => value[1..]
+                    .split('\'')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+                _ => value
+                    .split(" #")
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+            };
+            (key.trim().to_string(), value)
+        })
+        .collect()
+}
+
+/// Reads a double-quoted value up to its closing quote, expanding escapes
+fn unescape_double_quoted(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some('r') => unescaped.push('\r'),
+                Some(other) => unescaped.push(other),
+                None => unescaped.push('\\'),
+            },
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
 
 /// Check if a Docker container is running
 ///
@@ -11,7 +278,7 @@ use std::process::Command;
 /// - `Err(String)` if there was an error checking the container status
 pub fn check_container_status(container_name: &str) -> Result<bool, String> {
     let output = Command::new("docker")
-        .args(&[
+        .args([
             "ps",
             "-a",
             "--filter",
@@ -39,6 +306,31 @@ pub fn check_container_status(container_name: &str) -> Result<bool, String> {
     Ok(status.starts_with("Up"))
 }
 
+/// Get how often Docker has restarted a container since it was created
+///
+/// # Returns
+///
+/// - `Ok(count)` with the container's restart count
+/// - `Err(String)` if the container does not exist or could not be inspected
+pub fn get_restart_count(container_name: &str) -> Result<u32, String> {
+    let output = Command::new("docker")
+        .args(["inspect", "--format", "{{.RestartCount}}", container_name])
+        .output()
+        .map_err(|e| format!("Failed to execute docker inspect command: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Docker inspect command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .map_err(|e| format!("Invalid restart count: {}", e))
+}
+
 /// Get logs from a Docker container and check for specific error patterns
 ///
 /// # Returns
@@ -47,7 +339,7 @@ pub fn check_container_status(container_name: &str) -> Result<bool, String> {
 /// - An error message if something went wrong
 pub fn get_container_logs(container_name: &str) -> Result<String, String> {
     let output = Command::new("docker")
-        .args(&["logs", container_name])
+        .args(["logs", container_name])
         .output()
         .map_err(|e| format!("Failed to get container logs: {}", e))?;
 
@@ -72,52 +364,52 @@ pub fn get_container_logs(container_name: &str) -> Result<String, String> {
     Ok(logs)
 }
 
-/// Simplified function to check if an agent is healthy
-pub async fn check_agent_health(endpoint: &str) -> Result<(), String> {
-    logging::info!("Starting health check for endpoint: {}", endpoint);
-    let agent = AgentEndpoint::new(endpoint);
-
-    // Health check parameters
-    let max_attempts = 10;
-    let delay_between_attempts = std::time::Duration::from_secs(3);
-    let timeout = std::time::Duration::from_secs(5);
-
-    // First, give the container some time to start up
-    logging::info!("Waiting for container to initialize (5s)...");
-    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+/// Collects a container's state and logs into a report for diagnosing it
+///
+/// Failures to query Docker are written into the report rather than returned, so that
+/// whatever could be collected is kept.
+pub fn collect_container_diagnostics(container_name: &str) -> String {
+    let mut report = format!("Container: {}\n\n== State ==\n", container_name);
+    match Command::new("docker")
+        .args(["inspect", "--format", "{{json .State}}", container_name])
+        .output()
+    {
+        Ok(output) if output.status.success() => {
+            report.push_str(String::from_utf8_lossy(&output.stdout).trim_end());
+        }
+        Ok(output) => report.push_str(&format!(
+            "Docker inspect command failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )),
+        Err(e) => report.push_str(&format!("Failed to execute docker inspect command: {}", e)),
+    }
 
-    // Try each health check attempt
-    for attempt in 1..=max_attempts {
-        logging::info!("Health check attempt {}/{}", attempt, max_attempts);
+    report.push_str("\n\n== Logs ==\n");
+    match get_container_logs(container_name) {
+        Ok(logs) => report.push_str(&logs),
+        Err(e) => report.push_str(&e),
+    }
+    report
+}
 
-        match agent.check_health(timeout).await {
-            Ok(_) => {
-                logging::info!("Agent health check passed on attempt {}", attempt);
-                return Ok(());
-            }
-            Err(e) => {
-                if attempt == max_attempts {
-                    let error_msg = format!(
-                        "Agent health check failed after {} attempts: {}",
-                        max_attempts, e
-                    );
-                    logging::error!("{}", error_msg);
-                    return Err(error_msg);
-                }
+/// Simplified function to check if an agent is healthy
+///
+/// Attempts, delays and timeouts are taken from the operator's [`HttpConfig`].
+pub async fn check_agent_health(agent: &AgentEndpoint, config: &HttpConfig) -> Result<(), String> {
+    logging::info!("Starting health check for endpoint: {}", agent.base_url);
 
-                logging::warn!("Health check attempt {} failed: {}", attempt, e);
-                logging::info!(
-                    "Waiting {}s before next attempt...",
-                    delay_between_attempts.as_secs()
-                );
-                tokio::time::sleep(delay_between_attempts).await;
-            }
-        }
-    }
+    // First, give the container some time to start up
+    logging::info!(
+        "Waiting for container to initialize ({}s)...",
+        config.health_initial_delay.as_secs()
+    );
+    tokio::time::sleep(config.health_initial_delay).await;
 
-    // This should never be reached due to the return in the loop
-    Err(format!(
-        "Agent failed to become healthy after {} attempts",
-        max_attempts
-    ))
+    agent
+        .poll_health(
+            config.health_max_attempts,
+            config.request_timeout,
+            config.health_backoff(),
+        )
+        .await
 }
@@ -0,0 +1,219 @@
+use crate::types::{AgentImage, HookRun};
+use blueprint_sdk::logging;
+use serde::Deserialize;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::process::Command as TokioCommand;
+
+/// Most post-create hooks a template can have
+pub const MAX_HOOKS: usize = 8;
+
+/// Longest a hook may run for, in seconds
+pub const MAX_TIMEOUT_SECS: u64 = 1800;
+
+/// Longest log kept of each hook's output, in bytes; earlier output is dropped
+pub const MAX_LOG_LEN: usize = 16 * 1024;
+
+/// Memory and processes a hook's container may use
+const MEMORY_LIMIT: &str = "2g";
+const PIDS_LIMIT: &str = "512";
+
+/// Directory the agent's directory is mounted at in a hook's container
+const WORKDIR: &str = "/workspace";
+
+/// A step run in the agent's directory after the template is copied, e.g. installing
+/// dependencies, declared under `[[post_create]]` in the template's manifest
+///
+/// Hooks never run on the host: each runs in a throwaway container of its image, with only
+/// the agent's directory mounted, as the operator's user, without capabilities and, unless
+/// `network` is set, without network access.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostCreateHook {
+    /// Name reported in the creation result, e.g. `install`
+    pub name: String,
+    /// Image the hook runs in, e.g. `node:20-slim`
+    pub image: String,
+    /// Program and arguments, run in the agent's directory
+    pub command: Vec<String>,
+    /// Seconds the hook may run for before it is killed and creation fails
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Whether the hook may reach the network, e.g. to download dependencies
+    #[serde(default)]
+    pub network: bool,
+}
+
+fn default_timeout_secs() -> u64 {
+    300
+}
+
+impl PostCreateHook {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty()
+            || self.name.len() > 64
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "Hook names are 1 to 64 lowercase letters, digits, - or _, got {:?}",
+                self.name
+            ));
+        }
+        AgentImage::Image {
+            reference: self.image.clone(),
+        }
+        .validate()
+        .map_err(|e| format!("Hook {}: {}", self.name, e))?;
+        if self.command.is_empty() {
+            return Err(format!("Hook {} has no command", self.name));
+        }
+        if !(1..=MAX_TIMEOUT_SECS).contains(&self.timeout_secs) {
+            return Err(format!(
+                "Hook {} must time out within 1 to {} seconds",
+                self.name, MAX_TIMEOUT_SECS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Name of the container the hook runs in for an agent
+    fn container_name(&self, container_prefix: &str) -> String {
+        format!("{}-hook-{}", container_prefix, self.name)
+    }
+
+    /// Returns the arguments of the `docker run` running the hook on `agent_dir`, which must
+    /// be absolute
+    pub fn docker_args(&self, agent_dir: &Path, container_prefix: &str) -> Vec<String> {
+        let mut args: Vec<String> = [
+            "run",
+            "--rm",
+            "--name",
+            &self.container_name(container_prefix),
+            "--network",
+            if self.network { "bridge" } else { "none" },
+            "--cap-drop",
+            "ALL",
+            "--security-opt",
+            "no-new-privileges",
+            "--memory",
+            MEMORY_LIMIT,
+            "--pids-limit",
+            PIDS_LIMIT,
+            "--tmpfs",
+            "/tmp",
+            "--env",
+            "HOME=/tmp",
+            "--volume",
+            &format!("{}:{}", agent_dir.display(), WORKDIR),
+            "--workdir",
+            WORKDIR,
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        if let Some(user) = owner_of(agent_dir) {
+            args.extend(["--user".to_string(), user]);
+        }
+        args.push(self.image.clone());
+        args.extend(self.command.iter().cloned());
+        args
+    }
+}
+
+/// Returns `uid:gid` of the owner of `path`, so files hooks create belong to the operator
+#[cfg(unix)]
+fn owner_of(path: &Path) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = std::fs::metadata(path).ok()?;
+    Some(format!("{}:{}", metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn owner_of(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Runs a template's post-create hooks in order on an agent's directory
+///
+/// Stops at the first hook that fails or times out, returning an error with the end of its
+/// log. `container_prefix` names the hooks' containers, e.g. after the agent's.
+pub async fn run_post_create(
+    hooks: &[PostCreateHook],
+    agent_dir: &Path,
+    container_prefix: &str,
+) -> Result<Vec<HookRun>, String> {
+    let agent_dir = agent_dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", agent_dir.display(), e))?;
+    let mut runs = Vec::with_capacity(hooks.len());
+    for hook in hooks {
+        let run = run_hook(hook, &agent_dir, container_prefix).await?;
+        logging::info!(
+            phase = "post_create",
+            hook = %hook.name,
+            duration_ms = run.duration_ms,
+            "Ran post-create hook"
+        );
+        runs.push(run);
+    }
+    Ok(runs)
+}
+
+async fn run_hook(
+    hook: &PostCreateHook,
+    agent_dir: &Path,
+    container_prefix: &str,
+) -> Result<HookRun, String> {
+    let started = Instant::now();
+    let run = TokioCommand::new("docker")
+        .args(hook.docker_args(agent_dir, container_prefix))
+        .kill_on_drop(true)
+        .output();
+    let output = match tokio::time::timeout(Duration::from_secs(hook.timeout_secs), run).await {
+        Ok(output) => output.map_err(|e| format!("Failed to run hook {}: {}", hook.name, e))?,
+        Err(_) => {
+            // Killing the CLI leaves the container running, so remove it
+            let _ = TokioCommand::new("docker")
+                .args(["rm", "--force", &hook.container_name(container_prefix)])
+                .output()
+                .await;
+            return Err(format!(
+                "Hook {} timed out after {}s",
+                hook.name, hook.timeout_secs
+            ));
+        }
+    };
+
+    let mut log = String::from_utf8_lossy(&output.stdout).into_owned();
+    log.push_str(&String::from_utf8_lossy(&output.stderr));
+    let log = tail(&log, MAX_LOG_LEN);
+    if !output.status.success() {
+        let last_lines: Vec<&str> = log.lines().rev().take(10).collect();
+        return Err(format!(
+            "Hook {} failed with {}: {}",
+            hook.name,
+            output.status,
+            last_lines.into_iter().rev().collect::<Vec<_>>().join("\n")
+        ));
+    }
+    Ok(HookRun {
+        name: hook.name.clone(),
+        duration_ms: started.elapsed().as_millis() as u64,
+        log,
+    })
+}
+
+/// Returns the last `max_len` bytes of `log`, cut at a character boundary
+pub fn tail(log: &str, max_len: usize) -> String {
+    if log.len() <= max_len {
+        return log.to_string();
+    }
+    let mut start = log.len() - max_len;
+    while !log.is_char_boundary(start) {
+        start += 1;
+    }
+    log[start..].to_string()
+}
@@ -0,0 +1,191 @@
+use crate::config::env_or;
+use crate::helpers::compose_command;
+use crate::{AgentPortConfig, ServiceContext};
+use blueprint_sdk::logging;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory under the agents base directory holding the reverse proxy's configuration
+const INGRESS_DIR: &str = ".ingress";
+
+/// Where the TLS certificate and key are mounted in the proxy container
+const CERTS_MOUNT: &str = "/certs";
+
+/// TLS-terminating reverse proxy in front of locally deployed agents
+///
+/// The proxy is a Caddy container on the host network that routes `/agents/<id>/` to the
+/// agent's HTTP port and `/agents/<id>/ws` to its WebSocket port. Agents then only listen
+/// on localhost.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IngressConfig {
+    /// Public host name of the operator, e.g. `agents.example.com`
+    pub host: String,
+    /// Port the proxy serves HTTPS on
+    pub port: u16,
+    /// PEM certificate and key; Caddy obtains a certificate through ACME when unset
+    pub tls: Option<(PathBuf, PathBuf)>,
+}
+
+impl IngressConfig {
+    /// Loads the proxy from `AGENT_INGRESS_HOST`, `AGENT_INGRESS_PORT`,
+    /// `AGENT_INGRESS_TLS_CERT` and `AGENT_INGRESS_TLS_KEY`, if a host is set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(host) = std::env::var("AGENT_INGRESS_HOST")
+            .ok()
+            .filter(|host| !host.trim().is_empty())
+        else {
+            return Ok(None);
+        };
+        let tls = match (
+            std::env::var("AGENT_INGRESS_TLS_CERT"),
+            std::env::var("AGENT_INGRESS_TLS_KEY"),
+        ) {
+            (Ok(cert), Ok(key)) => Some((PathBuf::from(cert), PathBuf::from(key))),
+            (Err(_), Err(_)) => None,
+            _ => {
+                return Err(
+                    "AGENT_INGRESS_TLS_CERT and AGENT_INGRESS_TLS_KEY must be set together"
+                        .to_string(),
+                )
+            }
+        };
+        Ok(Some(Self {
+            host: host.trim().to_string(),
+            port: env_or("AGENT_INGRESS_PORT", 443)?,
+            tls,
+        }))
+    }
+
+    /// Returns the public endpoint of an agent behind the proxy
+    pub fn agent_url(&self, agent_id: &str) -> String {
+        match self.port {
+            443 => format!("https://{}/agents/{}/", self.host, agent_id),
+            port => format!("https://{}:{}/agents/{}/", self.host, port, agent_id),
+        }
+    }
+
+    /// Returns the public WebSocket endpoint of an agent behind the proxy
+    pub fn agent_websocket_url(&self, agent_id: &str) -> String {
+        match self.port {
+            443 => format!("wss://{}/agents/{}/ws", self.host, agent_id),
+            port => format!("wss://{}:{}/agents/{}/ws", self.host, port, agent_id),
+        }
+    }
+
+    /// Renders the Caddyfile routing to every agent in `routes`
+    pub fn render_caddyfile(&self, routes: &HashMap<String, AgentPortConfig>) -> String {
+        let mut caddyfile = format!("{}:{} {{\n", self.host, self.port);
+        if let Some((cert, key)) = &self.tls {
+            caddyfile.push_str(&format!(
+                "    tls {}/{} {}/{}\n",
+                CERTS_MOUNT,
+                file_name(cert),
+                CERTS_MOUNT,
+                file_name(key)
+            ));
+        }
+
+        // Sorted so that regenerating the file for the same agents doesn't change it
+        let mut agent_ids: Vec<&String> = routes.keys().collect();
+        agent_ids.sort();
+        for agent_id in agent_ids {
+            let ports = &routes[agent_id];
+            if let Some(websocket_port) = ports.websocket_port {
+                caddyfile.push_str(&format!(
+                    "    handle_path /agents/{}/ws* {{\n        reverse_proxy 127.0.0.1:{}\n    }}\n",
+                    agent_id, websocket_port
+                ));
+            }
+            caddyfile.push_str(&format!(
+                "    handle_path /agents/{}/* {{\n        reverse_proxy 127.0.0.1:{}\n    }}\n",
+                agent_id, ports.http_port
+            ));
+        }
+        caddyfile.push_str("    respond 404\n}\n");
+        caddyfile
+    }
+
+    /// Renders the compose file running the proxy
+    fn render_compose(&self) -> String {
+        let mut volumes = vec![
+            "      - ./Caddyfile:/etc/caddy/Caddyfile:ro".to_string(),
+            "      - caddy_data:/data".to_string(),
+        ];
+        if let Some((cert, key)) = &self.tls {
+            for path in [cert, key] {
+                volumes.push(format!(
+                    "      - {}:{}/{}:ro",
+                    path.display(),
+                    CERTS_MOUNT,
+                    file_name(path)
+                ));
+            }
+        }
+        format!(
+            "services:\n  caddy:\n    image: caddy:2\n    container_name: coinbase-agent-ingress\n    network_mode: host\n    restart: unless-stopped\n    volumes:\n{}\nvolumes:\n  caddy_data:\n",
+            volumes.join("\n")
+        )
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn ingress_dir(context: &ServiceContext) -> PathBuf {
+    let base_dir = match context.agents_base_dir() {
+        Some(dir) => dir,
+        None => "./agents".to_string(),
+    };
+    PathBuf::from(base_dir).join(INGRESS_DIR)
+}
+
+/// Routes the proxy to every agent with known ports, starting it if it isn't running
+pub async fn sync_ingress(context: &ServiceContext, config: &IngressConfig) -> Result<(), String> {
+    let routes = match &context.agent_registry {
+        Some(registry) => registry.snapshot(),
+        None => HashMap::new(),
+    };
+
+    let dir = ingress_dir(context);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create ingress directory: {}", e))?;
+    fs::write(dir.join("Caddyfile"), config.render_caddyfile(&routes))
+        .map_err(|e| format!("Failed to write Caddyfile: {}", e))?;
+    fs::write(dir.join("docker-compose.yml"), config.render_compose())
+        .map_err(|e| format!("Failed to write ingress compose file: {}", e))?;
+
+    run_compose(&dir, &["up", "-d"]).await?;
+    run_compose(
+        &dir,
+        &[
+            "exec",
+            "-T",
+            "caddy",
+            "caddy",
+            "reload",
+            "--config",
+            "/etc/caddy/Caddyfile",
+        ],
+    )
+    .await?;
+    logging::info!("Reverse proxy routes {} agents", routes.len());
+    Ok(())
+}
+
+async fn run_compose(dir: &Path, args: &[&str]) -> Result<(), String> {
+    let output = compose_command(dir)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run docker-compose: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Reverse proxy command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory under the agents base directory holding the journal, one file per call ID
+const JOURNAL_DIR: &str = ".journal";
+
+/// Off-chain action taken by a job, recorded so it can be matched to its on-chain call
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub call_id: u64,
+    /// Name of the job, e.g. `create_agent`
+    pub job: String,
+    pub agent_id: String,
+    /// What happened, e.g. `created` or `deployed`
+    pub action: String,
+    /// Unix timestamp (seconds) of the action
+    pub timestamp: u64,
+}
+
+fn journal_path(context: &ServiceContext, call_id: u64) -> PathBuf {
+    let base_dir = match context.agents_base_dir() {
+        Some(dir) => dir,
+        None => "./agents".to_string(),
+    };
+    PathBuf::from(base_dir)
+        .join(JOURNAL_DIR)
+        .join(format!("{}.jsonl", call_id))
+}
+
+/// Appends an entry for the current job call to the journal
+///
+/// Calls without an ID (e.g. from tests) are not journaled. Failures are logged and never
+/// fail the calling job.
+pub fn record(context: &ServiceContext, job: &str, agent_id: &str, action: &str) {
+    let Some(call_id) = context.call_id else {
+        return;
+    };
+
+    let entry = JournalEntry {
+        call_id,
+        job: job.to_string(),
+        agent_id: agent_id.to_string(),
+        action: action.to_string(),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+    };
+    if let Err(e) = append(&journal_path(context, call_id), &entry) {
+        logging::warn!("Failed to journal call {}: {}", call_id, e);
+    }
+}
+
+fn append(path: &PathBuf, entry: &JournalEntry) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create journal: {}", e))?;
+    }
+    let mut line =
+        serde_json::to_string(entry).map_err(|e| format!("Failed to serialize entry: {}", e))?;
+    line.push('\n');
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| format!("Failed to write journal: {}", e))
+}
+
+/// Returns the journal entries recorded for a job call, oldest first
+pub fn entries_for_call(
+    context: &ServiceContext,
+    call_id: u64,
+) -> Result<Vec<JournalEntry>, String> {
+    let path = journal_path(context, call_id);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read journal: {}", e)),
+    };
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(|e| format!("Invalid journal entry: {}", e)))
+        .collect()
+}
@@ -1,37 +1,162 @@
+use agent_endpoint::AgentEndpoint;
 use api::services::events::JobCalled;
 use blueprint_sdk::config::GadgetConfiguration;
+#[cfg(feature = "evm")]
+use blueprint_sdk::event_listeners::evm::EvmContractEventListener;
 use blueprint_sdk::event_listeners::tangle::events::TangleEventListener;
-use blueprint_sdk::event_listeners::tangle::services::{
-    services_post_processor, services_pre_processor,
-};
+use blueprint_sdk::event_listeners::tangle::services::services_post_processor;
 use blueprint_sdk::macros::contexts::{ServicesContext, TangleClientContext};
 use blueprint_sdk::tangle_subxt::tangle_testnet_runtime::api;
+use ownership::caller_pre_processor;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use tracing::Instrument;
 
 // Public modules
+pub mod agent_actions;
 pub mod agent_endpoint;
+pub mod agent_id;
+pub mod agent_status;
+pub mod alerts;
+pub mod audit;
+pub mod balances;
+pub mod builder;
+pub mod callbacks;
+pub mod checksum;
+pub mod cli;
+pub mod codec;
+pub mod config;
+pub mod coordination;
 pub mod create_agent;
+pub mod create_agent_swarm;
+pub mod create_agents;
 pub mod deploy_agent;
+pub mod deploy_queue;
+pub mod diagnostics;
 pub mod docker;
+pub mod envelope;
+pub mod error;
+pub mod events;
+#[cfg(feature = "evm")]
+pub mod evm;
+pub mod exposure;
+pub mod fund_agent;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod heartbeat;
 pub mod helpers;
+pub mod hooks;
+pub mod ingress;
+pub mod journal;
+pub mod lifecycle;
+pub mod mcp;
+pub mod monitor;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod ownership;
+pub mod payment;
+pub mod ports;
+pub mod rate_limit;
+pub mod readiness;
+pub mod registration;
+pub mod registry;
+pub mod reload;
+pub mod render;
+pub mod retention;
+pub mod router;
+pub mod scaffold;
+pub mod schema;
+pub mod secret_resolver;
+pub mod secret_scan;
+pub mod secrets;
+pub mod signing;
+pub mod status_api;
+pub mod stop_agent;
+pub mod storage;
+pub mod tee;
+pub mod telemetry;
+pub mod templates;
 pub mod types;
+pub mod validation;
+pub mod wallet;
+pub mod warm_pool;
 
 #[cfg(test)]
 mod tests;
 
+pub use agent_actions::handle_agent_actions;
+pub use agent_id::AgentId;
+pub use agent_status::handle_agent_status;
+pub use alerts::AlertConfig;
+pub use audit::handle_secret_audit_log;
+pub use balances::{handle_get_agent_balances, RpcEndpoints};
+pub use builder::{CreateAgentParamsBuilder, DeployAgentParamsBuilder};
+pub use callbacks::CallbackConfig;
+pub use config::{HealthBackoff, HttpConfig, ProxyConfig};
+pub use coordination::CoordinationConfig;
 pub use create_agent::handle_create_agent;
+pub use create_agent_swarm::handle_create_agent_swarm;
+pub use create_agents::handle_create_agents;
 pub use deploy_agent::handle_deploy_agent;
+pub use deploy_queue::DeployQueue;
+pub use diagnostics::handle_collect_diagnostics;
+pub use envelope::{
+    decode_params, decode_signed_job_params, decode_signed_params, ParamsEnvelope, VersionedParams,
+};
+pub use error::BlueprintError;
+pub use events::{AgentEvent, EventBus};
+pub use fund_agent::handle_fund_agent;
+pub use heartbeat::HeartbeatMetrics;
+pub use ingress::IngressConfig;
+pub use monitor::{HealthMonitor, MonitorPolicy};
+pub use payment::{PaymentError, PaymentRequired};
+pub use ports::{PortAllocator, PortRange};
+pub use rate_limit::{QuotaExceeded, RateLimiter, RateLimits};
+pub use registration::{AgentQuota, QuotaReservation, RegistrationParams, RequestParams};
+pub use registry::AgentRegistry;
+pub use reload::LiveConfig;
+pub use retention::LogRetention;
+pub use scaffold::handle_scaffold_template;
+pub use schema::handle_get_params_schema;
+pub use secret_resolver::{SecretResolver, SecretResolvers, SecretUri};
+pub use secrets::SealingKey;
+pub use signing::{CallerSignature, NonceRegistry, OperatorSignature, SignatureScheme};
+pub use stop_agent::handle_stop_agent;
+pub use tee::{PhalaCloud, SharedTeeDeployer};
+pub use telemetry::{LogFormat, TelemetryConfig};
+pub use templates::{handle_list_templates, handle_validate_template};
 pub use types::*;
+pub use validation::{Violation, Violations};
+pub use warm_pool::{WarmPool, WarmPoolConfig};
 
 /// Port configuration for an agent with HTTP and WebSocket ports
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AgentPortConfig {
     pub http_port: u16,
-    pub websocket_port: u16,
+    /// `None` for agents without a WebSocket server
+    pub websocket_port: Option<u16>,
+}
+
+impl AgentPortConfig {
+    /// Returns every host port the agent takes
+    pub fn ports(&self) -> impl Iterator<Item = u16> {
+        std::iter::once(self.http_port).chain(self.websocket_port)
+    }
+
+    /// Returns where the WebSocket port is relative to the HTTP port, to allocate the same
+    /// ports again
+    pub fn websocket(&self) -> WebSocketPort {
+        match self.websocket_port {
+            Some(port) if Some(port) == self.http_port.checked_add(1) => WebSocketPort::Adjacent,
+            Some(port) => WebSocketPort::Port(port),
+            None => WebSocketPort::Disabled,
+        }
+    }
 }
 
-#[derive(Clone, TangleClientContext, ServicesContext)]
+/// Shared state of the jobs; a default context has none of the optional subsystems
+#[derive(Clone, Default, TangleClientContext, ServicesContext)]
 pub struct ServiceContext {
     #[config]
     pub config: GadgetConfiguration,
@@ -39,11 +164,260 @@ pub struct ServiceContext {
     pub call_id: Option<u64>,
     // Environment variables needed for the service
     pub agents_base_dir: Option<String>,
-    pub tee_enabled: Option<bool>,
-    pub phala_tee_api_endpoint: Option<String>,
-    pub phala_tee_api_key: Option<String>,
-    // Map of agent ID to port configuration (shared across threads)
-    pub agent_ports: Option<Arc<Mutex<HashMap<String, AgentPortConfig>>>>,
+    // Phala Cloud account TEE agents are deployed through, set when the TEE is enabled
+    pub phala_cloud: Option<PhalaCloud>,
+    // TEE deployer reused across jobs, with its discovered TEEPod
+    pub tee_deployer: Option<SharedTeeDeployer>,
+    // Ports of each agent, by agent ID (shared across threads)
+    pub agent_registry: Option<AgentRegistry>,
+    // Hands out free ports from the operator's range, recording them in `agent_registry`
+    pub port_allocator: Option<PortAllocator>,
+    // HTTP timeouts and health check tuning for talking to agents
+    pub http_config: Option<HttpConfig>,
+    // Pooled HTTP client shared by every AgentEndpoint created from this context
+    pub http_client: Option<reqwest::Client>,
+    // Refuse to deploy agents that were created without a budget
+    pub require_budget: Option<bool>,
+    // Submit agent lifecycle events (running, unhealthy, stopped) on-chain
+    pub report_lifecycle: Option<bool>,
+    // Agent quota negotiated from the operator's registration and the service request
+    pub agent_quota: Option<AgentQuota>,
+    // Caller of each recent job call, keyed by call ID (filled in by the pre-processor)
+    pub job_callers: Option<Arc<Mutex<HashMap<u64, String>>>>,
+    // Owner of the service instance, who may operate on every agent
+    pub service_owner: Option<String>,
+    // Account that requests outside of job calls (e.g. the gRPC API or EVM requests) are made as
+    pub api_caller: Option<String>,
+    // Sign job results with the operator's ECDSA key
+    pub sign_results: Option<bool>,
+    // This operator's place among the service's operators, to deploy each agent only once
+    pub coordination: Option<CoordinationConfig>,
+    // Verify on-chain that the service is paid for before deploying agents
+    pub require_payment: Option<bool>,
+    // Key derived from the operator keystore, sealing agent secrets at rest
+    pub sealing_key: Option<SealingKey>,
+    // Backends resolving secret references (e.g. `vault://...`) in API key configs
+    pub secret_resolvers: Option<SecretResolvers>,
+    // Reject create and deploy requests whose params aren't signed by the service owner
+    pub require_caller_signature: Option<bool>,
+    // Nonces of the caller signatures already accepted, so signed params can't be replayed
+    pub caller_nonces: Option<NonceRegistry>,
+    // Per-caller limits on create/deploy calls and agents per owner
+    pub rate_limiter: Option<RateLimiter>,
+    // TLS-terminating reverse proxy that serves local agents under /agents/<id>/
+    pub ingress: Option<IngressConfig>,
+    // Router serving every local agent under one port at /<id>/interact and /<id>/health
+    pub agent_router: Option<router::RouterConfig>,
+    // Cloudflare Tunnel making local agents reachable from hosts behind NAT
+    pub exposure: Option<exposure::ExposureConfig>,
+    // JSON-RPC endpoints agents' balances are read from, by network; public ones if unset
+    pub rpc_endpoints: Option<RpcEndpoints>,
+    // OTLP collector receiving the spans of every job and phase
+    pub telemetry: Option<TelemetryConfig>,
+    // Webhook alerted when deployments fail or agents turn unhealthy
+    pub alerts: Option<AlertConfig>,
+    // Key signing the job outcomes posted to callers' callback URLs
+    pub callbacks: Option<CallbackConfig>,
+    // Supervision of locally deployed agents, restarting them when they stop responding
+    pub health_monitor: Option<HealthMonitor>,
+    // Broadcast of agent lifecycle events to subsystems such as on-chain reporting
+    pub events: Option<EventBus>,
+    // Latest heartbeat, served as QoS metrics at the status API's /metrics
+    pub heartbeat_metrics: Option<HeartbeatMetrics>,
+    // Rotation of agents' container logs and retention of their diagnostics
+    pub log_retention: Option<LogRetention>,
+    // Bound on concurrent deployments, also keeping deployments of one agent from overlapping
+    pub deploy_queue: Option<DeployQueue>,
+    // Stopped containers created ahead of time, which local deployments start instead
+    pub warm_pool: Option<WarmPool>,
+    // Off-host store (S3, MinIO or a mounted directory) of agent backups and diagnostics
+    pub artifact_store: Option<Arc<dyn storage::ArtifactStore>>,
+    // Settings reloaded at runtime, read instead of the agents' directory, the Phala Cloud
+    // account and the HTTP configuration above
+    pub live_config: Option<LiveConfig>,
+}
+
+impl ServiceContext {
+    /// Builds the context of a running blueprint from the environment
+    ///
+    /// The core settings come from [`config::ServiceConfig`], i.e. the TOML file named by
+    /// `AGENT_CONFIG_FILE` overridden by the environment; every optional subsystem reads its
+    /// own variables. Fails with a message naming the offending setting.
+    pub async fn from_env(env: GadgetConfiguration) -> Result<Self, String> {
+        let settings = config::ServiceConfig::from_env()?;
+        let agents_base_dir = settings.agents_base_dir().to_string();
+
+        // Load the HTTP configuration and the pooled client shared by all agent endpoints
+        let mut http_config = HttpConfig::from_env()?;
+        settings.apply_to_http_config(&mut http_config);
+        let http_client = http_config.build_client()?;
+        let health_concurrency = http_config.health_concurrency;
+
+        // Make the proxy visible to clients we don't construct ourselves (e.g. the TEE deployer)
+        if let Some(proxy) = &http_config.proxy {
+            blueprint_sdk::logging::info!(
+                "Routing outbound traffic through proxy {}",
+                proxy.redacted_url()
+            );
+            proxy.export_to_env();
+        }
+
+        // Advertise our limits at registration; the quota also depends on the service request
+        let registration = RegistrationParams::from_env()?;
+        if let Some(registration) = &registration {
+            blueprint_sdk::logging::info!(
+                "Advertising registration params: {}",
+                String::from_utf8_lossy(&registration.to_registration_inputs()?)
+            );
+        }
+
+        // Resolve secret references in API key configs through the configured backends
+        let secret_resolvers = SecretResolvers::from_env(http_client.clone()).await?;
+        blueprint_sdk::logging::info!("Secret resolvers: {:?}", secret_resolvers);
+
+        // Give agents free ports from the operator's range, keeping allocations across restarts
+        let agent_registry = AgentRegistry::new();
+        let port_allocator = PortAllocator::from_env(
+            std::path::Path::new(&agents_base_dir),
+            agent_registry.clone(),
+        )?;
+
+        // Remember which caller signatures were used, across restarts
+        let caller_nonces =
+            NonceRegistry::load(std::path::Path::new(&agents_base_dir).join(signing::NONCES_FILE))?;
+
+        // Keep agent backups and diagnostics off the host when a store is configured
+        let artifact_store = storage::from_env(http_client.clone())?;
+
+        let mut context = Self {
+            sealing_key: Some(SealingKey::from_config(&env)?),
+            config: env,
+            call_id: None,
+            agents_base_dir: Some(agents_base_dir),
+            phala_cloud: settings.phala_cloud(),
+            tee_deployer: Some(SharedTeeDeployer::default()),
+            agent_registry: Some(agent_registry),
+            port_allocator: Some(port_allocator),
+            http_config: Some(http_config.clone()),
+            http_client: Some(http_client),
+            require_budget: Some(settings.require_budget.unwrap_or(false)),
+            report_lifecycle: Some(settings.report_lifecycle()),
+            agent_quota: None,
+            job_callers: Some(Arc::new(Mutex::new(HashMap::new()))),
+            service_owner: settings.service_owner.clone(),
+            api_caller: None,
+            sign_results: Some(settings.sign_results.unwrap_or(false)),
+            coordination: CoordinationConfig::from_env()?,
+            require_payment: Some(settings.require_payment.unwrap_or(false)),
+            secret_resolvers: Some(secret_resolvers),
+            require_caller_signature: Some(settings.require_caller_signature.unwrap_or(false)),
+            caller_nonces: Some(caller_nonces),
+            rate_limiter: RateLimits::from_env()?.map(RateLimiter::new),
+            ingress: IngressConfig::from_env()?,
+            agent_router: router::RouterConfig::from_env()?,
+            exposure: exposure::ExposureConfig::from_env()?,
+            rpc_endpoints: Some(RpcEndpoints::from_env()?),
+            telemetry: TelemetryConfig::from_env()?,
+            alerts: AlertConfig::from_env()?,
+            callbacks: CallbackConfig::from_env()?,
+            health_monitor: MonitorPolicy::from_env()?.map(|mut policy| {
+                settings.apply_to_monitor_policy(&mut policy);
+                HealthMonitor::new(policy, health_concurrency)
+            }),
+            events: Some(EventBus::default()),
+            heartbeat_metrics: Some(HeartbeatMetrics::default()),
+            log_retention: Some(LogRetention::from_env()?),
+            deploy_queue: Some(DeployQueue::new(settings.max_concurrent_deploys())),
+            warm_pool: WarmPoolConfig::from_env()?.map(WarmPool::new),
+            artifact_store,
+            live_config: Some(LiveConfig::new(settings, http_config)),
+        };
+
+        // Derive the agent quota from our registration and the service's on-chain request
+        if let Some(registration) = registration {
+            let request = RequestParams::from_chain(&context).await?;
+            context.agent_quota = Some(registration.quota(request.as_ref()));
+        }
+        Ok(context)
+    }
+
+    /// Returns the account that called the current job, if it was recorded
+    ///
+    /// Requests that don't come from a job call are made as `api_caller`.
+    pub fn caller(&self) -> Option<String> {
+        if let Some(caller) = &self.api_caller {
+            return Some(caller.clone());
+        }
+        let call_id = self.call_id?;
+        let callers = self.job_callers.as_ref()?.lock().ok()?;
+        callers.get(&call_id).cloned()
+    }
+
+    /// Returns whether agent lifecycle events are reported on-chain (off unless enabled)
+    pub fn report_lifecycle(&self) -> bool {
+        self.report_lifecycle.unwrap_or(false)
+    }
+
+    /// Returns the operator's HTTP configuration, or the defaults if none was provided
+    pub fn http_config(&self) -> HttpConfig {
+        match &self.live_config {
+            Some(live) => live.get().http_config,
+            None => self.http_config.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Returns the directory holding the agents' files
+    pub fn agents_base_dir(&self) -> Option<String> {
+        match &self.live_config {
+            Some(live) => Some(live.get().settings.agents_base_dir().to_string()),
+            None => self.agents_base_dir.clone(),
+        }
+    }
+
+    /// Returns the Phala Cloud account to deploy TEE agents through
+    pub fn phala_cloud(&self) -> Result<PhalaCloud, String> {
+        match &self.live_config {
+            Some(live) => live.get().settings.phala_cloud(),
+            None => self.phala_cloud.clone(),
+        }
+        .ok_or_else(|| "TEE deployments are not enabled on this operator".to_string())
+    }
+
+    /// Returns the shared TEE deployer, or one used by this job only if none was provided
+    pub fn tee_deployer(&self) -> SharedTeeDeployer {
+        self.tee_deployer.clone().unwrap_or_default()
+    }
+
+    /// Returns the operator's log retention, or the defaults if none was provided
+    pub fn log_retention(&self) -> LogRetention {
+        self.log_retention.clone().unwrap_or_default()
+    }
+
+    /// Returns the address agents' ports are published on: only localhost when a reverse
+    /// proxy, the agent router or a tunnel fronts them
+    pub fn agent_bind_address(&self) -> &'static str {
+        if self.ingress.is_some() || self.agent_router.is_some() || self.exposure.is_some() {
+            "127.0.0.1"
+        } else {
+            "0.0.0.0"
+        }
+    }
+
+    /// Creates an AgentEndpoint for the given base URL
+    ///
+    /// Uses the shared HTTP client when one is configured, otherwise builds a
+    /// client from the operator's HTTP configuration.
+    pub fn agent_endpoint(&self, base_url: impl Into<String>) -> Result<AgentEndpoint, String> {
+        match &self.http_client {
+            Some(client) => Ok(AgentEndpoint::with_client(base_url, client.clone())),
+            None => AgentEndpoint::with_config(base_url, &self.http_config()),
+        }
+    }
+}
+
+/// Returns the span a job runs in, tagging every log line it emits with the job and call ID
+fn job_span(job: &'static str, call_id: Option<u64>) -> tracing::Span {
+    tracing::info_span!("job", otel.name = job, job, call_id)
 }
 
 /// Creates a new Coinbase Agent Kit agent
@@ -53,13 +427,16 @@ pub struct ServiceContext {
     result(result),
     event_listener(
         listener = TangleEventListener::<ServiceContext, JobCalled>,
-        pre_processor = services_pre_processor,
+        pre_processor = caller_pre_processor,
         post_processor = services_post_processor,
     ),
 )]
 pub async fn create_agent(params: Vec<u8>, context: ServiceContext) -> Result<Vec<u8>, String> {
     // Delegate to the implementation in create_agent module
-    handle_create_agent(params, &context).await
+    handle_create_agent(params, &context)
+        .instrument(job_span("create_agent", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
 }
 
 /// Deploys a previously created Coinbase Agent Kit agent
@@ -69,11 +446,304 @@ pub async fn create_agent(params: Vec<u8>, context: ServiceContext) -> Result<Ve
     result(result),
     event_listener(
         listener = TangleEventListener::<ServiceContext, JobCalled>,
-        pre_processor = services_pre_processor,
+        pre_processor = caller_pre_processor,
         post_processor = services_post_processor,
     ),
 )]
 pub async fn deploy_agent(params: Vec<u8>, context: ServiceContext) -> Result<Vec<u8>, String> {
     // Delegate to the implementation in deploy_agent module
-    handle_deploy_agent(params, &context).await
+    handle_deploy_agent(params, &context)
+        .instrument(job_span("deploy_agent", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Retrieves the on-chain action audit trail of a deployed agent
+#[blueprint_sdk::job(
+    id = 2,
+    params(params),
+    result(result),
+    event_listener(
+        listener = TangleEventListener::<ServiceContext, JobCalled>,
+        pre_processor = caller_pre_processor,
+        post_processor = services_post_processor,
+    ),
+)]
+pub async fn agent_actions(params: Vec<u8>, context: ServiceContext) -> Result<Vec<u8>, String> {
+    // Delegate to the implementation in agent_actions module
+    handle_agent_actions(params, &context)
+        .instrument(job_span("agent_actions", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Reports the status and action policy of an agent
+#[blueprint_sdk::job(
+    id = 3,
+    params(params),
+    result(result),
+    event_listener(
+        listener = TangleEventListener::<ServiceContext, JobCalled>,
+        pre_processor = caller_pre_processor,
+        post_processor = services_post_processor,
+    ),
+)]
+pub async fn agent_status(params: Vec<u8>, context: ServiceContext) -> Result<Vec<u8>, String> {
+    // Delegate to the implementation in agent_status module
+    handle_agent_status(params, &context)
+        .instrument(job_span("agent_status", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Creates several agents from one shared configuration
+#[blueprint_sdk::job(
+    id = 4,
+    params(params),
+    result(result),
+    event_listener(
+        listener = TangleEventListener::<ServiceContext, JobCalled>,
+        pre_processor = caller_pre_processor,
+        post_processor = services_post_processor,
+    ),
+)]
+pub async fn create_agent_swarm(
+    params: Vec<u8>,
+    context: ServiceContext,
+) -> Result<Vec<u8>, String> {
+    // Delegate to the implementation in create_agent_swarm module
+    handle_create_agent_swarm(params, &context)
+        .instrument(job_span("create_agent_swarm", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Returns the audit log of every access to agents' secrets
+#[blueprint_sdk::job(
+    id = 7,
+    params(params),
+    result(result),
+    event_listener(
+        listener = TangleEventListener::<ServiceContext, JobCalled>,
+        pre_processor = caller_pre_processor,
+        post_processor = services_post_processor,
+    ),
+)]
+pub async fn secret_audit_log(params: Vec<u8>, context: ServiceContext) -> Result<Vec<u8>, String> {
+    // Delegate to the implementation in audit module
+    handle_secret_audit_log(params, &context)
+        .instrument(job_span("secret_audit_log", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Packages the logs, configuration and health history of an agent for support
+#[blueprint_sdk::job(
+    id = 8,
+    params(params),
+    result(result),
+    event_listener(
+        listener = TangleEventListener::<ServiceContext, JobCalled>,
+        pre_processor = caller_pre_processor,
+        post_processor = services_post_processor,
+    ),
+)]
+pub async fn collect_diagnostics(
+    params: Vec<u8>,
+    context: ServiceContext,
+) -> Result<Vec<u8>, String> {
+    // Delegate to the implementation in diagnostics module
+    handle_collect_diagnostics(params, &context)
+        .instrument(job_span("collect_diagnostics", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Creates several independently configured agents, with one result per agent
+#[blueprint_sdk::job(
+    id = 9,
+    params(params),
+    result(result),
+    event_listener(
+        listener = TangleEventListener::<ServiceContext, JobCalled>,
+        pre_processor = caller_pre_processor,
+        post_processor = services_post_processor,
+    ),
+)]
+pub async fn create_agents(params: Vec<u8>, context: ServiceContext) -> Result<Vec<u8>, String> {
+    // Delegate to the implementation in create_agents module
+    handle_create_agents(params, &context)
+        .instrument(job_span("create_agents", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Returns the JSON Schemas and versions of the parameters this blueprint accepts
+#[blueprint_sdk::job(
+    id = 10,
+    params(params),
+    result(result),
+    event_listener(
+        listener = TangleEventListener::<ServiceContext, JobCalled>,
+        pre_processor = caller_pre_processor,
+        post_processor = services_post_processor,
+    ),
+)]
+pub async fn get_params_schema(
+    params: Vec<u8>,
+    context: ServiceContext,
+) -> Result<Vec<u8>, String> {
+    // Delegate to the implementation in schema module
+    handle_get_params_schema(params)
+        .instrument(job_span("get_params_schema", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Checks that a template can be used to create agents
+#[blueprint_sdk::job(
+    id = 11,
+    params(params),
+    result(result),
+    event_listener(
+        listener = TangleEventListener::<ServiceContext, JobCalled>,
+        pre_processor = caller_pre_processor,
+        post_processor = services_post_processor,
+    ),
+)]
+pub async fn validate_template(
+    params: Vec<u8>,
+    context: ServiceContext,
+) -> Result<Vec<u8>, String> {
+    // Delegate to the implementation in templates module
+    handle_validate_template(params, &context)
+        .instrument(job_span("validate_template", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Lists the templates agents can be created from
+#[blueprint_sdk::job(
+    id = 12,
+    params(params),
+    result(result),
+    event_listener(
+        listener = TangleEventListener::<ServiceContext, JobCalled>,
+        pre_processor = caller_pre_processor,
+        post_processor = services_post_processor,
+    ),
+)]
+pub async fn list_templates(params: Vec<u8>, context: ServiceContext) -> Result<Vec<u8>, String> {
+    // Delegate to the implementation in templates module
+    handle_list_templates(params)
+        .instrument(job_span("list_templates", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Generates the files of a new template wired to the blueprint's variables
+#[blueprint_sdk::job(
+    id = 13,
+    params(params),
+    result(result),
+    event_listener(
+        listener = TangleEventListener::<ServiceContext, JobCalled>,
+        pre_processor = caller_pre_processor,
+        post_processor = services_post_processor,
+    ),
+)]
+pub async fn scaffold_template(
+    params: Vec<u8>,
+    context: ServiceContext,
+) -> Result<Vec<u8>, String> {
+    // Delegate to the implementation in scaffold module
+    handle_scaffold_template(params, &context)
+        .instrument(job_span("scaffold_template", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Requests testnet funds for a deployed agent's wallet and waits for them to show
+#[blueprint_sdk::job(
+    id = 14,
+    params(params),
+    result(result),
+    event_listener(
+        listener = TangleEventListener::<ServiceContext, JobCalled>,
+        pre_processor = caller_pre_processor,
+        post_processor = services_post_processor,
+    ),
+)]
+pub async fn fund_agent(params: Vec<u8>, context: ServiceContext) -> Result<Vec<u8>, String> {
+    // Delegate to the implementation in fund_agent module
+    handle_fund_agent(params, &context)
+        .instrument(job_span("fund_agent", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Returns the native and ERC-20 balances of a deployed agent's wallet
+#[blueprint_sdk::job(
+    id = 15,
+    params(params),
+    result(result),
+    event_listener(
+        listener = TangleEventListener::<ServiceContext, JobCalled>,
+        pre_processor = caller_pre_processor,
+        post_processor = services_post_processor,
+    ),
+)]
+pub async fn get_agent_balances(
+    params: Vec<u8>,
+    context: ServiceContext,
+) -> Result<Vec<u8>, String> {
+    // Delegate to the implementation in balances module
+    handle_get_agent_balances(params, &context)
+        .instrument(job_span("get_agent_balances", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Creates an agent requested through the `AgentRequests` EVM contract
+#[cfg(feature = "evm")]
+#[blueprint_sdk::job(
+    id = 5,
+    params(params),
+    event_listener(
+        listener = EvmContractEventListener<ServiceContext, evm::AgentRequests::CreateAgentRequested>,
+        instance = evm::AgentRequests,
+        abi = evm::AGENT_REQUESTS_ABI_STRING,
+        pre_processor = evm::create_agent_request_pre_processor,
+    ),
+)]
+pub async fn create_agent_evm(params: Vec<u8>, context: ServiceContext) -> Result<Vec<u8>, String> {
+    // Act as the requester, then delegate to the implementation in create_agent module
+    let (context, params) = evm::requester_context(&context, &params)
+        .map_err(|e| BlueprintError::Params(e).to_payload())?;
+    handle_create_agent(params, &context)
+        .instrument(job_span("create_agent_evm", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
+}
+
+/// Deploys an agent requested through the `AgentRequests` EVM contract
+#[cfg(feature = "evm")]
+#[blueprint_sdk::job(
+    id = 6,
+    params(params),
+    event_listener(
+        listener = EvmContractEventListener<ServiceContext, evm::AgentRequests::DeployAgentRequested>,
+        instance = evm::AgentRequests,
+        abi = evm::AGENT_REQUESTS_ABI_STRING,
+        pre_processor = evm::deploy_agent_request_pre_processor,
+    ),
+)]
+pub async fn deploy_agent_evm(params: Vec<u8>, context: ServiceContext) -> Result<Vec<u8>, String> {
+    // Act as the requester, then delegate to the implementation in deploy_agent module
+    let (context, params) = evm::requester_context(&context, &params)
+        .map_err(|e| BlueprintError::Params(e).to_payload())?;
+    handle_deploy_agent(params, &context)
+        .instrument(job_span("deploy_agent_evm", context.call_id))
+        .await
+        .map_err(|e| e.to_payload())
 }
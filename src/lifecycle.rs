@@ -0,0 +1,178 @@
+use crate::events::AgentEvent;
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use blueprint_sdk::macros::contexts::TangleClientContext;
+use blueprint_sdk::tangle_subxt::tangle_testnet_runtime::api;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+/// Lifecycle state of a deployed agent
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AgentLifecycleStatus {
+    /// The agent was deployed and passed its health check
+    Running,
+    /// The agent was started but is not responding to health checks
+    Unhealthy,
+    /// The agent's container is no longer running
+    Stopped,
+}
+
+impl fmt::Display for AgentLifecycleStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentLifecycleStatus::Running => write!(f, "Running"),
+            AgentLifecycleStatus::Unhealthy => write!(f, "Unhealthy"),
+            AgentLifecycleStatus::Stopped => write!(f, "Stopped"),
+        }
+    }
+}
+
+/// Lifecycle event submitted on-chain, serialized as JSON into a `system.remark_with_event`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AgentLifecycleEvent {
+    pub agent_id: String,
+    pub status: AgentLifecycleStatus,
+    /// Job call that caused the transition, if any
+    pub call_id: Option<u64>,
+    /// Unix timestamp (seconds) of the transition
+    pub timestamp: u64,
+    pub detail: Option<String>,
+}
+
+impl AgentLifecycleEvent {
+    /// Creates an event for the current time
+    pub fn new(
+        agent_id: impl Into<String>,
+        status: AgentLifecycleStatus,
+        call_id: Option<u64>,
+        detail: Option<String>,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self {
+            agent_id: agent_id.into(),
+            status,
+            call_id,
+            timestamp,
+            detail,
+        }
+    }
+}
+
+/// Submits a lifecycle event on-chain through the operator's Tangle client
+///
+/// The remark's event lets service owners follow an agent's health from chain data alone.
+pub async fn submit_lifecycle_event(
+    context: &ServiceContext,
+    event: &AgentLifecycleEvent,
+) -> Result<(), String> {
+    submit_remark(context, event).await
+}
+
+/// Submits a JSON payload on-chain as a `system.remark_with_event` signed by the operator
+pub(crate) async fn submit_remark<T: Serialize>(
+    context: &ServiceContext,
+    payload: &T,
+) -> Result<(), String> {
+    let remark =
+        serde_json::to_vec(payload).map_err(|e| format!("Failed to serialize remark: {}", e))?;
+
+    let client = context
+        .tangle_client()
+        .await
+        .map_err(|e| format!("Failed to get Tangle client: {}", e))?;
+    let signer = context
+        .config
+        .first_sr25519_signer()
+        .map_err(|e| format!("Failed to get operator signer: {}", e))?;
+
+    let call = api::tx().system().remark_with_event(remark);
+    blueprint_sdk::tx::tangle::send(client.subxt_client(), &signer, &call)
+        .await
+        .map_err(|e| format!("Failed to submit remark: {}", e))?;
+
+    Ok(())
+}
+
+/// Reports an agent's lifecycle transition on-chain if the operator enabled it
+///
+/// Reporting is best effort: failures are logged and never fail the calling job.
+pub async fn report_lifecycle(
+    context: &ServiceContext,
+    agent_id: &str,
+    status: AgentLifecycleStatus,
+    detail: Option<String>,
+) {
+    if !context.report_lifecycle() {
+        return;
+    }
+
+    let event = AgentLifecycleEvent::new(agent_id, status, context.call_id, detail);
+    match submit_lifecycle_event(context, &event).await {
+        Ok(()) => logging::info!(
+            agent_id = %agent_id,
+            phase = "lifecycle",
+            status = %status,
+            "Reported agent status on-chain"
+        ),
+        Err(e) => logging::warn!(
+            agent_id = %agent_id,
+            phase = "lifecycle",
+            status = %status,
+            error = %e,
+            "Failed to report agent status on-chain"
+        ),
+    }
+}
+
+impl AgentLifecycleStatus {
+    /// Returns the on-chain status and detail an agent event reports, if any
+    pub fn from_event(event: &AgentEvent) -> Option<(Self, Option<String>)> {
+        match event {
+            AgentEvent::Deployed { tee_app_id, .. } => Some((
+                AgentLifecycleStatus::Running,
+                tee_app_id
+                    .as_ref()
+                    .map(|app_id| format!("Deployed to TEE app {}", app_id)),
+            )),
+            AgentEvent::Healthy { .. } => Some((AgentLifecycleStatus::Running, None)),
+            AgentEvent::Unhealthy { detail, .. } => {
+                Some((AgentLifecycleStatus::Unhealthy, Some(detail.clone())))
+            }
+            AgentEvent::Stopped { detail, .. } => {
+                Some((AgentLifecycleStatus::Stopped, Some(detail.clone())))
+            }
+            AgentEvent::Created { .. } | AgentEvent::Deleted { .. } => None,
+        }
+    }
+}
+
+/// Reports the lifecycle transitions published on the event bus on-chain until it closes
+pub async fn run_lifecycle_reporter(context: ServiceContext, mut events: Receiver<AgentEvent>) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(missed)) => {
+                logging::warn!(
+                    phase = "lifecycle",
+                    missed,
+                    "Lifecycle reporter fell behind"
+                );
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        let Some((status, detail)) = AgentLifecycleStatus::from_event(&event) else {
+            continue;
+        };
+        // Attribute the report to the call that caused the event
+        let mut context = context.clone();
+        context.call_id = event.call_id();
+        report_lifecycle(&context, event.agent_id(), status, detail).await;
+    }
+}
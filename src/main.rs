@@ -2,34 +2,200 @@ use blueprint_sdk::logging;
 use blueprint_sdk::runners::core::runner::BlueprintRunner;
 use blueprint_sdk::runners::tangle::tangle::TangleConfig;
 use coinbase_agent_kit_blueprint as blueprint;
-use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
 
-#[blueprint_sdk::main(env)]
+#[blueprint_sdk::main(env, skip_logger)]
 async fn main() {
-    // Create service context
-    let context = blueprint::ServiceContext {
-        config: env.clone(),
-        call_id: None,
-        agents_base_dir: None,
-        tee_enabled: None,
-        phala_tee_api_endpoint: None,
-        phala_tee_api_key: None,
-        agent_ports: Some(Arc::new(Mutex::new(HashMap::new()))),
-    };
+    // Log as text or, for operator log pipelines, as JSON with per-job and per-agent fields,
+    // exporting job and phase spans to an OpenTelemetry collector if one is configured
+    let telemetry = blueprint::TelemetryConfig::from_env()?;
+    let tracer_provider =
+        blueprint::telemetry::init_logging(blueprint::LogFormat::from_env()?, telemetry.as_ref())?;
+    if let Some(telemetry) = &telemetry {
+        logging::info!("Exporting traces to {}", telemetry.otlp_endpoint);
+    }
+
+    // Build the service context from the config file and the environment
+    let context = blueprint::ServiceContext::from_env(env.clone()).await?;
+    let events = context.events.clone().unwrap_or_default();
+
+    // Catch broken templates now rather than when the first agent is created from them, and
+    // write the embedded starter template out unless the templates directory overrides it
+    let templates_dir = std::path::Path::new(blueprint::templates::TEMPLATES_DIR);
+    if !blueprint::templates::self_check(templates_dir).await {
+        logging::error!(
+            "The {} template is invalid, so create_agent fails unless params name another template",
+            blueprint::templates::DEFAULT_TEMPLATE
+        );
+    }
+
+    // Back agents up off the host, restoring on a fresh host the ones it should run
+    if let Some(store) = &context.artifact_store {
+        logging::info!("Backing up agents to {}", store.location());
+        if blueprint::config::env_or("AGENT_RESTORE_AGENTS", false)? {
+            let restored = blueprint::storage::restore_missing_agents(&context).await?;
+            logging::info!("Restored {} agents from {}", restored, store.location());
+        }
+        tokio::spawn(blueprint::storage::run_backups(
+            context.clone(),
+            events.subscribe(),
+        ));
+    }
+
+    // Report agent lifecycle transitions on-chain
+    if context.report_lifecycle() {
+        tokio::spawn(blueprint::lifecycle::run_lifecycle_reporter(
+            context.clone(),
+            events.subscribe(),
+        ));
+    }
+
+    // Report the health of our agents as QoS metrics, and on-chain if asked to
+    let heartbeat_interval: u64 = blueprint::config::env_or("AGENT_HEARTBEAT_INTERVAL_SECS", 60)?;
+    let heartbeat_on_chain = blueprint::config::env_or("AGENT_HEARTBEAT_ON_CHAIN", false)?;
+    if heartbeat_interval > 0 {
+        tokio::spawn(blueprint::heartbeat::run_heartbeat(
+            context.clone(),
+            std::time::Duration::from_secs(heartbeat_interval),
+            heartbeat_on_chain,
+        ));
+    }
+
+    // Reload the operator's settings on SIGHUP or when the config file changes
+    let config_watch_secs: u64 = blueprint::config::env_or("AGENT_CONFIG_WATCH_SECS", 0)?;
+    tokio::spawn(blueprint::reload::run_config_reloader(
+        context.clone(),
+        (config_watch_secs > 0).then(|| std::time::Duration::from_secs(config_watch_secs)),
+    ));
+
+    // Keep diagnostics written into agent directories from piling up
+    tokio::spawn(blueprint::retention::run_retention_sweeper(
+        context.clone(),
+        context.log_retention(),
+    ));
+
+    // Keep stopped agent containers ready for latency-sensitive deployments
+    if let Some(pool) = context.warm_pool.clone() {
+        tokio::spawn(blueprint::warm_pool::run_warm_pool(context.clone(), pool));
+    }
+
+    // Let operators inspect agents over HTTP without crafting job calls
+    if let Some(status_api) = blueprint::status_api::StatusApiConfig::from_env()? {
+        let context = context.clone();
+        tokio::spawn(async move {
+            if let Err(e) = blueprint::status_api::serve(context, status_api).await {
+                logging::error!("{}", e);
+            }
+        });
+    }
+
+    // Take agents off the internet once they are gone
+    if let Some(exposure) = context.exposure.clone() {
+        tokio::spawn(blueprint::exposure::run_exposure_cleanup(
+            context.clone(),
+            exposure,
+            events.subscribe(),
+        ));
+    }
+
+    // Serve every local agent under one port
+    if let Some(agent_router) = context.agent_router.clone() {
+        let context = context.clone();
+        tokio::spawn(async move {
+            if let Err(e) = blueprint::router::serve(context, agent_router).await {
+                logging::error!("{}", e);
+            }
+        });
+    }
+
+    // Let off-chain orchestration systems manage agents over gRPC
+    #[cfg(feature = "grpc")]
+    if let Some(grpc) = blueprint::grpc::GrpcConfig::from_env()? {
+        let context = context.clone();
+        tokio::spawn(async move {
+            if let Err(e) = blueprint::grpc::serve(context, grpc).await {
+                logging::error!("{}", e);
+            }
+        });
+    }
+
+    // Consume create and deploy requests from NATS as well, or only from NATS
+    #[cfg(feature = "nats")]
+    if let Some(nats) = blueprint::nats::NatsConfig::from_env()? {
+        if nats.exclusive {
+            logging::info!("Consuming requests from NATS only");
+            let result = blueprint::nats::serve(context, nats).await;
+            flush_traces(tracer_provider);
+            result?;
+            logging::info!("Exiting...");
+            return Ok(());
+        }
+        let context = context.clone();
+        tokio::spawn(async move {
+            if let Err(e) = blueprint::nats::serve(context, nats).await {
+                logging::error!("{}", e);
+            }
+        });
+    }
 
     // Create event handlers from jobs
     let create_agent_job = blueprint::CreateAgentEventHandler::new(&env, context.clone()).await?;
     let deploy_agent_job = blueprint::DeployAgentEventHandler::new(&env, context.clone()).await?;
+    let agent_actions_job = blueprint::AgentActionsEventHandler::new(&env, context.clone()).await?;
+    let agent_status_job = blueprint::AgentStatusEventHandler::new(&env, context.clone()).await?;
+    let create_agent_swarm_job =
+        blueprint::CreateAgentSwarmEventHandler::new(&env, context.clone()).await?;
+    let secret_audit_log_job =
+        blueprint::SecretAuditLogEventHandler::new(&env, context.clone()).await?;
+    let collect_diagnostics_job =
+        blueprint::CollectDiagnosticsEventHandler::new(&env, context.clone()).await?;
+    let create_agents_job = blueprint::CreateAgentsEventHandler::new(&env, context.clone()).await?;
+    let get_params_schema_job =
+        blueprint::GetParamsSchemaEventHandler::new(&env, context.clone()).await?;
+    let validate_template_job =
+        blueprint::ValidateTemplateEventHandler::new(&env, context.clone()).await?;
+    let list_templates_job =
+        blueprint::ListTemplatesEventHandler::new(&env, context.clone()).await?;
+    let scaffold_template_job =
+        blueprint::ScaffoldTemplateEventHandler::new(&env, context.clone()).await?;
+    let fund_agent_job = blueprint::FundAgentEventHandler::new(&env, context.clone()).await?;
+    let get_agent_balances_job =
+        blueprint::GetAgentBalancesEventHandler::new(&env, context.clone()).await?;
 
     logging::info!("Starting event watchers for jobs...");
     let tangle_config = TangleConfig::default();
-    BlueprintRunner::new(tangle_config, env)
+    let runner = BlueprintRunner::new(tangle_config, env.clone())
         .job(create_agent_job)
         .job(deploy_agent_job)
-        .run()
-        .await?;
+        .job(agent_actions_job)
+        .job(agent_status_job)
+        .job(create_agent_swarm_job)
+        .job(secret_audit_log_job)
+        .job(collect_diagnostics_job)
+        .job(create_agents_job)
+        .job(get_params_schema_job)
+        .job(validate_template_job)
+        .job(list_templates_job)
+        .job(scaffold_template_job)
+        .job(fund_agent_job)
+        .job(get_agent_balances_job);
+
+    // Optionally accept requests emitted by the AgentRequests EVM contract as well
+    #[cfg(feature = "evm")]
+    let runner = blueprint::evm::register_evm_jobs(runner, &env, &context)?;
+
+    let result = runner.run().await;
+    flush_traces(tracer_provider);
+    result?;
 
     logging::info!("Exiting...");
     Ok(())
 }
+
+/// Flushes the spans still waiting to be exported
+fn flush_traces(provider: Option<opentelemetry_sdk::trace::TracerProvider>) {
+    if let Some(provider) = provider {
+        if let Err(e) = provider.shutdown() {
+            logging::warn!("Failed to flush traces: {}", e);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::agent_id::container_name;
+use crate::agent_status::handle_agent_status;
+use crate::deploy_agent::get_required_ports;
+use crate::helpers::{check_container_status, run_blocking};
+use crate::status_api::agent_ids;
+use crate::ServiceContext;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+/// Version of the Model Context Protocol the server speaks
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Prefix of the tools sending a message to an agent
+const MESSAGE_TOOL: &str = "message_";
+
+/// Prefix of the tools reporting an agent's status
+const STATUS_TOOL: &str = "status_";
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// Model Context Protocol server exposing the node's deployed agents as tools
+///
+/// Every agent whose container is running gets a `message_<id>` tool, relaying a message to
+/// the agent and returning its reply, and a `status_<id>` tool returning the agent_status
+/// job's result. Tools are listed afresh on every request, so the list follows deployments.
+#[derive(Clone)]
+pub struct McpServer {
+    context: ServiceContext,
+}
+
+impl McpServer {
+    pub fn new(context: ServiceContext) -> Self {
+        Self { context }
+    }
+
+    /// Handles one JSON-RPC message, returning the response unless it was a notification
+    pub async fn handle(&self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            return Some(error_response(
+                id.unwrap_or(Value::Null),
+                INVALID_REQUEST,
+                "Missing method",
+            ));
+        };
+        // Notifications such as `notifications/initialized` need no answer
+        let id = id?;
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": { "listChanged": false } },
+                "serverInfo": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            })),
+            "ping" => Ok(json!({})),
+            "tools/list" => self
+                .list_tools()
+                .await
+                .map(|tools| json!({ "tools": tools }))
+                .map_err(|e| (INTERNAL_ERROR, e)),
+            "tools/call" => self.call_tool(&params).await,
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    /// Returns the tools of every agent whose container is running
+    async fn list_tools(&self) -> Result<Vec<Value>, String> {
+        let context = self.context.clone();
+        let agent_ids = run_blocking(move || {
+            Ok(agent_ids(&context)?
+                .into_iter()
+                .filter(|agent_id| {
+                    check_container_status(&container_name(agent_id)).unwrap_or(false)
+                })
+                .collect::<Vec<_>>())
+        })
+        .await?;
+
+        let mut tools = Vec::with_capacity(2 * agent_ids.len());
+        for agent_id in agent_ids {
+            tools.push(json!({
+                "name": format!("{}{}", MESSAGE_TOOL, agent_id),
+                "description": format!(
+                    "Sends a message to the Coinbase agent {} and returns its reply",
+                    agent_id
+                ),
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "message": { "type": "string", "description": "Message for the agent" }
+                    },
+                    "required": ["message"],
+                },
+            }));
+            tools.push(json!({
+                "name": format!("{}{}", STATUS_TOOL, agent_id),
+                "description": format!(
+                    "Reports whether the Coinbase agent {} is running, its ports and the actions it may execute",
+                    agent_id
+                ),
+                "inputSchema": { "type": "object", "properties": {} },
+            }));
+        }
+        Ok(tools)
+    }
+
+    /// Calls a tool, reporting failures of the agent as tool errors rather than protocol ones
+    async fn call_tool(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let name = params
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or((INVALID_PARAMS, "Missing tool name".to_string()))?;
+        let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+
+        let result = if let Some(agent_id) = name.strip_prefix(MESSAGE_TOOL) {
+            let message = arguments
+                .get("message")
+                .and_then(Value::as_str)
+                .ok_or((INVALID_PARAMS, "Missing message argument".to_string()))?;
+            self.send_message(agent_id, message).await
+        } else if let Some(agent_id) = name.strip_prefix(STATUS_TOOL) {
+            let params = serde_json::to_vec(&json!({ "agent_id": agent_id }))
+                .map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+            handle_agent_status(params, &self.context)
+                .await
+                .map(|result| serde_json::from_slice(&result).unwrap_or_default())
+                .map_err(|e| e.to_string())
+        } else {
+            return Err((INVALID_PARAMS, format!("Unknown tool: {}", name)));
+        };
+
+        Ok(match result {
+            Ok(value) => json!({
+                "content": [{ "type": "text", "text": to_text(&value) }],
+                "isError": false,
+            }),
+            Err(e) => json!({
+                "content": [{ "type": "text", "text": e }],
+                "isError": true,
+            }),
+        })
+    }
+
+    async fn send_message(&self, agent_id: &str, message: &str) -> Result<Value, String> {
+        let (http_port, _) = get_required_ports(agent_id, &self.context)?;
+        let agent = self
+            .context
+            .agent_endpoint(format!("http://localhost:{}", http_port))?;
+        agent
+            .interact(message, self.context.http_config().request_timeout)
+            .await
+    }
+}
+
+/// Renders a tool's result: replies that are a single string are returned as is
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Object(object) if object.len() == 1 && object.values().all(Value::is_string) => {
+            object
+                .values()
+                .next()
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        }
+        other => serde_json::to_string_pretty(other).unwrap_or_default(),
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// Serves MCP over stdin and stdout, one JSON-RPC message per line, until stdin closes
+pub async fn serve_stdio(server: McpServer) -> Result<(), String> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|e| format!("Failed to read from stdin: {}", e))?
+    {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => server.handle(message).await,
+            Err(e) => Some(error_response(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+        if let Some(response) = response {
+            let mut output = response.to_string();
+            output.push('\n');
+            stdout
+                .write_all(output.as_bytes())
+                .await
+                .map_err(|e| format!("Failed to write to stdout: {}", e))?;
+            stdout
+                .flush()
+                .await
+                .map_err(|e| format!("Failed to write to stdout: {}", e))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,348 @@
+use crate::agent_id::AgentId;
+use crate::alerts::{send_alert, Alert, AlertKind};
+use crate::config::env_or;
+use crate::events::{self, AgentEvent};
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::process::Command as TokioCommand;
+use tokio::sync::Semaphore;
+use tokio::task::AbortHandle;
+
+/// Probes kept per agent in its health history
+const HEALTH_HISTORY_LEN: usize = 100;
+
+/// How deployed agents are supervised
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MonitorPolicy {
+    /// Time between health probes
+    pub interval: Duration,
+    /// Consecutive failed probes after which the container is restarted
+    pub failure_threshold: u32,
+    /// Restarts without recovery after which the agent is marked failed
+    pub max_restarts: u32,
+    /// Wait after the first restart before probing again, doubled after each further restart
+    pub restart_backoff: Duration,
+}
+
+impl Default for MonitorPolicy {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            failure_threshold: 3,
+            max_restarts: 3,
+            restart_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl MonitorPolicy {
+    /// Loads the policy from `AGENT_MONITOR_INTERVAL_SECS`, `AGENT_MONITOR_FAILURE_THRESHOLD`,
+    /// `AGENT_MONITOR_MAX_RESTARTS` and `AGENT_MONITOR_RESTART_BACKOFF_SECS`
+    ///
+    /// Returns `None` when the interval is `0`.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let defaults = Self::default();
+        let interval_secs: u64 =
+            env_or("AGENT_MONITOR_INTERVAL_SECS", defaults.interval.as_secs())?;
+        if interval_secs == 0 {
+            return Ok(None);
+        }
+        let failure_threshold: u32 = env_or(
+            "AGENT_MONITOR_FAILURE_THRESHOLD",
+            defaults.failure_threshold,
+        )?;
+        let backoff_secs: u64 = env_or(
+            "AGENT_MONITOR_RESTART_BACKOFF_SECS",
+            defaults.restart_backoff.as_secs(),
+        )?;
+        Ok(Some(Self {
+            interval: Duration::from_secs(interval_secs),
+            failure_threshold: failure_threshold.max(1),
+            max_restarts: env_or("AGENT_MONITOR_MAX_RESTARTS", defaults.max_restarts)?,
+            restart_backoff: Duration::from_secs(backoff_secs),
+        }))
+    }
+
+    /// Returns the wait after the given restart (1-based) before probing again
+    pub fn backoff_for(&self, restart: u32) -> Duration {
+        let exponent = restart.saturating_sub(1).min(16);
+        self.restart_backoff.saturating_mul(1 << exponent)
+    }
+}
+
+/// Health of a supervised agent
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MonitorState {
+    /// The last probe passed
+    Healthy,
+    /// Probes have failed, but not yet often enough to restart
+    Failing { consecutive_failures: u32 },
+    /// The container was restarted and the agent hasn't recovered yet
+    Restarting { restarts: u32 },
+    /// Restarts didn't bring the agent back; it is no longer supervised
+    Failed,
+}
+
+/// What the monitor should do after a probe
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MonitorAction {
+    None,
+    Restart,
+    GiveUp,
+}
+
+/// Probe history of one agent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AgentHealth {
+    pub state: MonitorState,
+    pub consecutive_failures: u32,
+    /// Restarts since the agent was last healthy
+    pub restarts: u32,
+}
+
+impl Default for AgentHealth {
+    fn default() -> Self {
+        Self {
+            state: MonitorState::Healthy,
+            consecutive_failures: 0,
+            restarts: 0,
+        }
+    }
+}
+
+impl AgentHealth {
+    /// Records a probe result, returning what to do about it
+    pub fn observe(&mut self, healthy: bool, policy: &MonitorPolicy) -> MonitorAction {
+        if healthy {
+            *self = Self::default();
+            return MonitorAction::None;
+        }
+
+        self.consecutive_failures += 1;
+        if self.consecutive_failures < policy.failure_threshold {
+            self.state = MonitorState::Failing {
+                consecutive_failures: self.consecutive_failures,
+            };
+            return MonitorAction::None;
+        }
+        if self.restarts >= policy.max_restarts {
+            self.state = MonitorState::Failed;
+            return MonitorAction::GiveUp;
+        }
+        self.restarts += 1;
+        self.consecutive_failures = 0;
+        self.state = MonitorState::Restarting {
+            restarts: self.restarts,
+        };
+        MonitorAction::Restart
+    }
+}
+
+/// Result of one health probe of a supervised agent
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthProbe {
+    /// Unix timestamp (seconds) of the probe
+    pub timestamp: u64,
+    pub healthy: bool,
+    /// State of the agent after the probe
+    pub state: MonitorState,
+}
+
+struct Supervised {
+    state: MonitorState,
+    history: VecDeque<HealthProbe>,
+    task: AbortHandle,
+}
+
+/// Background supervision of locally deployed agents (shared across threads)
+///
+/// Each watched agent gets a task that probes its health, restarts its container after
+/// repeated failures and marks it failed, with an alert, if restarts don't help. The tasks
+/// share a bound on how many probes run at once, so a large fleet doesn't flood the host.
+#[derive(Clone)]
+pub struct HealthMonitor {
+    policy: Arc<RwLock<MonitorPolicy>>,
+    agents: Arc<Mutex<HashMap<String, Supervised>>>,
+    probes: Arc<Semaphore>,
+}
+
+impl HealthMonitor {
+    pub fn new(policy: MonitorPolicy, max_concurrent_probes: usize) -> Self {
+        Self {
+            policy: Arc::new(RwLock::new(policy)),
+            agents: Arc::new(Mutex::new(HashMap::new())),
+            probes: Arc::new(Semaphore::new(max_concurrent_probes.max(1))),
+        }
+    }
+
+    pub fn policy(&self) -> MonitorPolicy {
+        self.policy
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Replaces the policy; supervised agents follow it from their next probe on
+    pub fn set_policy(&self, policy: MonitorPolicy) {
+        *self.policy.write().unwrap_or_else(|e| e.into_inner()) = policy;
+    }
+
+    /// Returns the health of a supervised agent
+    pub fn state(&self, agent_id: &str) -> Option<MonitorState> {
+        let agents = self.agents.lock().unwrap_or_else(|e| e.into_inner());
+        agents.get(agent_id).map(|supervised| supervised.state)
+    }
+
+    /// Returns the most recent probes of a supervised agent, oldest first
+    pub fn history(&self, agent_id: &str) -> Vec<HealthProbe> {
+        let agents = self.agents.lock().unwrap_or_else(|e| e.into_inner());
+        agents
+            .get(agent_id)
+            .map(|supervised| supervised.history.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Starts supervising an agent serving health checks at `endpoint`
+    ///
+    /// Replaces the supervision of an earlier deployment of the same agent.
+    pub fn watch(&self, context: &ServiceContext, agent_id: &AgentId, endpoint: String) {
+        // Reports from the monitor don't belong to the job that deployed the agent
+        let mut context = context.clone();
+        context.call_id = None;
+        let task = tokio::spawn(supervise(context, self.clone(), agent_id.clone(), endpoint))
+            .abort_handle();
+
+        let mut agents = self.agents.lock().unwrap_or_else(|e| e.into_inner());
+        let previous = agents.insert(
+            agent_id.to_string(),
+            Supervised {
+                state: MonitorState::Healthy,
+                history: VecDeque::new(),
+                task,
+            },
+        );
+        if let Some(previous) = previous {
+            previous.task.abort();
+        }
+    }
+
+    /// Stops supervising an agent
+    pub fn unwatch(&self, agent_id: &str) {
+        let mut agents = self.agents.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(supervised) = agents.remove(agent_id) {
+            supervised.task.abort();
+        }
+    }
+
+    /// Records a probe of an agent and the state it left the agent in
+    pub(crate) fn record_probe(&self, agent_id: &str, healthy: bool, state: MonitorState) {
+        let mut agents = self.agents.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(supervised) = agents.get_mut(agent_id) {
+            supervised.state = state;
+            if supervised.history.len() == HEALTH_HISTORY_LEN {
+                supervised.history.pop_front();
+            }
+            supervised.history.push_back(HealthProbe {
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default(),
+                healthy,
+                state,
+            });
+        }
+    }
+}
+
+async fn supervise(
+    context: ServiceContext,
+    monitor: HealthMonitor,
+    agent_id: AgentId,
+    endpoint: String,
+) {
+    let container_name = agent_id.container_name();
+    let mut health = AgentHealth::default();
+    let mut delay = monitor.policy().interval;
+
+    loop {
+        tokio::time::sleep(delay).await;
+        let policy = monitor.policy();
+        let timeout = context.http_config().request_timeout;
+        delay = policy.interval;
+
+        let healthy = {
+            // The semaphore is never closed
+            let _probe = monitor.probes.acquire().await.expect("probe slots closed");
+            match context.agent_endpoint(endpoint.as_str()) {
+                Ok(agent) => agent.check_health(timeout).await.is_ok(),
+                Err(_) => false,
+            }
+        };
+        let recovering = health.restarts > 0;
+        let action = health.observe(healthy, &policy);
+        monitor.record_probe(&agent_id, healthy, health.state);
+
+        match action {
+            MonitorAction::None if healthy && recovering => {
+                logging::info!(agent_id = %agent_id, phase = "monitor", "Agent recovered after restart");
+                events::publish(
+                    &context,
+                    AgentEvent::Healthy {
+                        agent_id: agent_id.clone(),
+                    },
+                );
+            }
+            MonitorAction::None => {}
+            MonitorAction::Restart => {
+                logging::warn!(
+                    agent_id = %agent_id,
+                    phase = "monitor",
+                    restart = health.restarts,
+                    "Agent failed {} health checks in a row, restarting its container",
+                    policy.failure_threshold
+                );
+                if let Err(e) = restart_container(&container_name).await {
+                    logging::warn!(agent_id = %agent_id, phase = "monitor", error = %e, "Failed to restart container");
+                }
+                delay = policy.backoff_for(health.restarts);
+            }
+            MonitorAction::GiveUp => {
+                let detail = format!("Agent stayed unhealthy after {} restarts", health.restarts);
+                logging::error!(agent_id = %agent_id, phase = "monitor", "{}", detail);
+                events::publish(
+                    &context,
+                    AgentEvent::Unhealthy {
+                        agent_id: agent_id.clone(),
+                        call_id: None,
+                        detail: detail.clone(),
+                    },
+                );
+                send_alert(
+                    &context,
+                    Alert::new(AlertKind::AgentFailed, agent_id.to_string(), None, detail),
+                )
+                .await;
+                return;
+            }
+        }
+    }
+}
+
+async fn restart_container(container_name: &str) -> Result<(), String> {
+    let output = TokioCommand::new("docker")
+        .args(["restart", container_name])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to execute docker restart command: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Docker restart command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
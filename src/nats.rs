@@ -0,0 +1,185 @@
+use crate::create_agent::handle_create_agent;
+use crate::deploy_agent::handle_deploy_agent;
+use crate::error::BlueprintError;
+use crate::{job_span, ServiceContext};
+use async_nats::{HeaderMap, Message};
+use blueprint_sdk::logging;
+use std::path::PathBuf;
+use tokio_stream::StreamExt;
+use tracing::Instrument;
+
+/// Header carrying the description of a failed request, as in NATS services
+pub const ERROR_HEADER: &str = "Nats-Service-Error";
+
+/// Header carrying the status code of a failed request, as in NATS services
+pub const ERROR_CODE_HEADER: &str = "Nats-Service-Error-Code";
+
+/// NATS subjects `create_agent` and `deploy_agent` requests are consumed from, for hybrid
+/// deployments that queue requests off-chain
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NatsConfig {
+    /// Server to connect to, e.g. `nats://127.0.0.1:4222`
+    pub url: String,
+    /// Requests arrive on `<subject>.create_agent` and `<subject>.deploy_agent`
+    pub subject: String,
+    /// Queue group shared by operators that should each take a share of the requests
+    pub queue_group: Option<String>,
+    /// Credentials file to authenticate with
+    pub credentials: Option<PathBuf>,
+    /// Consumes requests only from NATS, leaving the Tangle event listeners off
+    pub exclusive: bool,
+}
+
+impl NatsConfig {
+    /// Loads the server from `AGENT_NATS_URL`, the subject prefix from `AGENT_NATS_SUBJECT`
+    /// (default `agentkit`), the queue group from `AGENT_NATS_QUEUE_GROUP`, the credentials
+    /// from `AGENT_NATS_CREDENTIALS` and whether NATS replaces Tangle from `AGENT_NATS_ONLY`
+    ///
+    /// Returns `None` when no server is set.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(url) = non_empty_env("AGENT_NATS_URL") else {
+            return Ok(None);
+        };
+        let subject = non_empty_env("AGENT_NATS_SUBJECT").unwrap_or_else(|| "agentkit".into());
+        if subject.contains(['*', '>', ' ']) || subject.ends_with('.') {
+            return Err(format!(
+                "Invalid AGENT_NATS_SUBJECT {}: expected a subject without wildcards",
+                subject
+            ));
+        }
+        Ok(Some(Self {
+            url,
+            subject,
+            queue_group: non_empty_env("AGENT_NATS_QUEUE_GROUP"),
+            credentials: non_empty_env("AGENT_NATS_CREDENTIALS").map(PathBuf::from),
+            exclusive: crate::config::env_or("AGENT_NATS_ONLY", false)?,
+        }))
+    }
+
+    /// Returns the subject a job's requests arrive on
+    pub fn job_subject(&self, job: &str) -> String {
+        format!("{}.{}", self.subject, job)
+    }
+}
+
+fn non_empty_env(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Runs the handler of the job a request's subject names
+///
+/// Requests act as the service owner, like gRPC ones, so access to the subjects must be
+/// restricted on the NATS server.
+pub async fn handle_request(
+    context: &ServiceContext,
+    config: &NatsConfig,
+    subject: &str,
+    payload: Vec<u8>,
+) -> Result<Vec<u8>, BlueprintError> {
+    let mut context = context.clone();
+    context.call_id = None;
+    context.api_caller = context.service_owner.clone();
+
+    let job = subject
+        .strip_prefix(&config.subject)
+        .and_then(|job| job.strip_prefix('.'))
+        .unwrap_or_default();
+    match job {
+        "create_agent" => {
+            handle_create_agent(payload, &context)
+                .instrument(job_span("create_agent_nats", None))
+                .await
+        }
+        "deploy_agent" => {
+            handle_deploy_agent(payload, &context)
+                .instrument(job_span("deploy_agent_nats", None))
+                .await
+        }
+        _ => Err(BlueprintError::Params(format!(
+            "No job is served on subject {}",
+            subject
+        ))),
+    }
+}
+
+/// Returns the headers describing a failed request to the requester
+pub fn error_headers(error: &BlueprintError) -> HeaderMap {
+    let code = match error {
+        BlueprintError::Params(_) => 400,
+        BlueprintError::Payment(_) => 402,
+        BlueprintError::Unauthorized(_) => 403,
+        BlueprintError::Quota(_) => 429,
+        BlueprintError::Health(_) | BlueprintError::Chain(_) => 503,
+        _ => 500,
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(ERROR_HEADER, error.message());
+    headers.insert(ERROR_CODE_HEADER, code.to_string().as_str());
+    headers
+}
+
+/// Consumes requests until the connection closes
+///
+/// Each request is handled on its own task. Requests sent with `request` get the job's result
+/// as the reply, or the error's JSON payload with the [`ERROR_HEADER`] and
+/// [`ERROR_CODE_HEADER`] headers set.
+pub async fn serve(context: ServiceContext, config: NatsConfig) -> Result<(), String> {
+    let mut options = async_nats::ConnectOptions::new().name(env!("CARGO_PKG_NAME"));
+    if let Some(credentials) = &config.credentials {
+        options = options.credentials_file(credentials).await.map_err(|e| {
+            format!(
+                "Failed to read NATS credentials {}: {}",
+                credentials.display(),
+                e
+            )
+        })?;
+    }
+    let client = options
+        .connect(config.url.as_str())
+        .await
+        .map_err(|e| format!("Failed to connect to NATS at {}: {}", config.url, e))?;
+
+    let subject = config.job_subject("*");
+    let mut requests = match &config.queue_group {
+        Some(group) => client.queue_subscribe(subject.clone(), group.clone()).await,
+        None => client.subscribe(subject.clone()).await,
+    }
+    .map_err(|e| format!("Failed to subscribe to {}: {}", subject, e))?;
+    logging::info!("Consuming requests from NATS subject {}", subject);
+
+    while let Some(message) = requests.next().await {
+        let client = client.clone();
+        let context = context.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            let Message {
+                subject,
+                reply,
+                payload,
+                ..
+            } = message;
+            let result = handle_request(&context, &config, &subject, payload.to_vec()).await;
+            if let Err(e) = &result {
+                logging::warn!("NATS request on {} failed: {}", subject, e);
+            }
+            let Some(reply) = reply else {
+                return;
+            };
+            let published = match result {
+                Ok(result) => client.publish(reply, result.into()).await,
+                Err(e) => {
+                    client
+                        .publish_with_headers(reply, error_headers(&e), e.to_payload().into())
+                        .await
+                }
+            };
+            if let Err(e) = published {
+                logging::warn!("Failed to reply to NATS request on {}: {}", subject, e);
+            }
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use crate::ServiceContext;
+use blueprint_sdk::event_listeners::tangle::events::TangleEvent;
+use blueprint_sdk::event_listeners::tangle::services::services_pre_processor;
+use blueprint_sdk::logging;
+use blueprint_sdk::tangle_subxt::subxt::utils::AccountId32;
+use blueprint_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::tangle_primitives::services::field::Field;
+use blueprint_sdk::tangle_subxt::tangle_testnet_runtime::api::services::events::JobCalled;
+use std::collections::HashMap;
+
+/// Variable in the agent's .env recording the account that created it
+pub const OWNER_VAR: &str = "AGENT_OWNER";
+
+/// How many recent job calls keep their caller recorded
+const MAX_TRACKED_CALLS: u64 = 1024;
+
+/// Pre-processor that records the caller of each job before the usual services pre-processing
+///
+/// The caller is kept in the context's shared `job_callers` map, keyed by call ID, where the
+/// job looks it up through [`ServiceContext::caller`].
+pub async fn caller_pre_processor(
+    event: TangleEvent<ServiceContext, JobCalled>,
+) -> Result<Option<(u64, Vec<Field<AccountId32>>)>, blueprint_sdk::Error> {
+    let call_id = event.evt.call_id;
+    if let Some(job_callers) = &event.context.job_callers {
+        match job_callers.lock() {
+            Ok(mut callers) => {
+                callers.retain(|id, _| id + MAX_TRACKED_CALLS > call_id);
+                callers.insert(call_id, event.evt.caller.to_string());
+            }
+            Err(_) => logging::warn!("Failed to lock job_callers map for call {}", call_id),
+        }
+    }
+
+    services_pre_processor(event).await
+}
+
+/// Checks that the current caller may operate on an agent
+///
+/// Only the agent's owner and the service owner are allowed. Agents without a recorded
+/// owner, such as those created before ownership was recorded, are left to the service
+/// owner.
+pub fn authorize(
+    context: &ServiceContext,
+    agent_id: &str,
+    agent_env: &HashMap<String, String>,
+) -> Result<(), String> {
+    let caller = context
+        .caller()
+        .ok_or_else(|| format!("The caller of a job on agent {} is unknown", agent_id))?;
+    if context.service_owner.as_ref() == Some(&caller) {
+        return Ok(());
+    }
+    let Some(owner) = agent_env.get(OWNER_VAR) else {
+        return Err(format!(
+            "Agent {} has no recorded owner, so only the service owner may operate on it",
+            agent_id
+        ));
+    };
+    if &caller == owner {
+        return Ok(());
+    }
+
+    Err(format!(
+        "Caller {} is not authorized to operate on agent {}",
+        caller, agent_id
+    ))
+}
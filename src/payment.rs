@@ -0,0 +1,159 @@
+use crate::ServiceContext;
+use blueprint_sdk::macros::contexts::TangleClientContext;
+use blueprint_sdk::tangle_subxt::subxt::utils::AccountId32;
+use blueprint_sdk::tangle_subxt::tangle_testnet_runtime::api;
+use std::fmt;
+use std::str::FromStr;
+
+/// Error returned when a service has not paid for the work a job asks for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaymentRequired {
+    pub service_id: Option<u64>,
+    pub reason: String,
+}
+
+impl fmt::Display for PaymentRequired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.service_id {
+            Some(service_id) => write!(
+                f,
+                "Payment required for service {}: {}",
+                service_id, self.reason
+            ),
+            None => write!(f, "Payment required: {}", self.reason),
+        }
+    }
+}
+
+impl From<PaymentRequired> for String {
+    fn from(error: PaymentRequired) -> Self {
+        error.to_string()
+    }
+}
+
+/// Error of a payment check
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PaymentError {
+    /// The chain says the call is not paid for
+    Required(PaymentRequired),
+    /// The chain could not be read, so the payment could not be checked either way
+    ChainUnavailable { service_id: u64, reason: String },
+}
+
+impl fmt::Display for PaymentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaymentError::Required(error) => error.fmt(f),
+            PaymentError::ChainUnavailable { service_id, reason } => write!(
+                f,
+                "Could not verify the payment for service {}: {}",
+                service_id, reason
+            ),
+        }
+    }
+}
+
+impl From<PaymentRequired> for PaymentError {
+    fn from(error: PaymentRequired) -> Self {
+        PaymentError::Required(error)
+    }
+}
+
+/// Verifies on-chain that the current call of job `job_index` is paid for
+///
+/// The service instance must still be live and belong to this blueprint, and the call must
+/// either have been paid for itself (`JobPayments`) or come from a caller whose subscription
+/// to the job (`JobSubscriptionBillings`) has not ended.
+pub async fn verify_payment(context: &ServiceContext, job_index: u8) -> Result<(), PaymentError> {
+    let required = |service_id, reason: String| PaymentRequired { service_id, reason };
+
+    let settings = context
+        .config
+        .protocol_settings
+        .tangle()
+        .map_err(|e| required(None, format!("Tangle settings unavailable: {}", e)))?;
+    let service_id = settings
+        .service_id
+        .ok_or_else(|| required(None, "no service ID configured".to_string()))?;
+    let unavailable = |reason: String| PaymentError::ChainUnavailable { service_id, reason };
+
+    let client = context
+        .tangle_client()
+        .await
+        .map_err(|e| unavailable(format!("Failed to get Tangle client: {}", e)))?;
+    let storage = client
+        .subxt_client()
+        .storage()
+        .at_latest()
+        .await
+        .map_err(|e| unavailable(format!("Failed to read chain state: {}", e)))?;
+    let instance = storage
+        .fetch(&api::storage().services().instances(service_id))
+        .await
+        .map_err(|e| unavailable(format!("Failed to fetch service: {}", e)))?
+        .ok_or_else(|| {
+            required(
+                Some(service_id),
+                "the service is not active on-chain".to_string(),
+            )
+        })?;
+    if instance.blueprint != settings.blueprint_id {
+        return Err(required(
+            Some(service_id),
+            format!("the service belongs to blueprint {}", instance.blueprint),
+        )
+        .into());
+    }
+
+    // A call paid for on its own
+    if let Some(call_id) = context.call_id {
+        let payment = storage
+            .fetch(&api::storage().services().job_payments(service_id, call_id))
+            .await
+            .map_err(|e| unavailable(format!("Failed to fetch job payment: {}", e)))?;
+        if payment.is_some() {
+            return Ok(());
+        }
+    }
+
+    // A call covered by the caller's subscription
+    let caller = context
+        .caller()
+        .and_then(|caller| AccountId32::from_str(&caller).ok())
+        .ok_or_else(|| {
+            required(
+                Some(service_id),
+                "the call was not paid for and its caller is unknown".to_string(),
+            )
+        })?;
+    let billing = storage
+        .fetch(
+            &api::storage()
+                .services()
+                .job_subscription_billings((service_id, job_index), caller),
+        )
+        .await
+        .map_err(|e| unavailable(format!("Failed to fetch subscription: {}", e)))?
+        .ok_or_else(|| {
+            required(
+                Some(service_id),
+                "the call was not paid for and its caller has no subscription".to_string(),
+            )
+        })?;
+    if let Some(end_block) = billing.end_block {
+        let block = storage
+            .fetch(&api::storage().system().number())
+            .await
+            .map_err(|e| unavailable(format!("Failed to fetch block number: {}", e)))?
+            .unwrap_or_default();
+        if block >= end_block {
+            return Err(required(
+                Some(service_id),
+                format!("the caller's subscription ended at block {}", end_block),
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,272 @@
+use crate::registry::AgentRegistry;
+use crate::types::{CreateAgentParams, WebSocketPort};
+use crate::AgentPortConfig;
+use blueprint_sdk::logging;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// File under the agents base directory persisting the port allocations
+pub const PORTS_FILE: &str = ".ports.json";
+
+/// Inclusive range of host ports agents are given
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl Default for PortRange {
+    fn default() -> Self {
+        Self {
+            start: 3000,
+            end: 3999,
+        }
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| format!("Invalid port range {}: expected e.g. 3000-3999", s))?;
+        let parse = |port: &str| {
+            port.trim()
+                .parse::<u16>()
+                .map_err(|e| format!("Invalid port range {}: {}", s, e))
+        };
+        let range = Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        };
+        if range.start < CreateAgentParams::MIN_HTTP_PORT || range.end <= range.start {
+            return Err(format!(
+                "Invalid port range {}: expected at least two ports from {} up",
+                s,
+                CreateAgentParams::MIN_HTTP_PORT
+            ));
+        }
+        Ok(range)
+    }
+}
+
+/// Hands out HTTP ports, and the WebSocket ports next to them, to agents (shared across threads)
+///
+/// Allocations live in the context's [`AgentRegistry`] and are persisted to
+/// [`PORTS_FILE`], so they survive restarts. A port is only handed out if no other agent
+/// holds it and it can be bound on the host right now.
+///
+/// Ports can also be reserved for containers that are not an agent yet, such as those of the
+/// warm pool. Reservations stay out of the registry and are not persisted.
+#[derive(Clone, Debug)]
+pub struct PortAllocator {
+    range: PortRange,
+    state_path: PathBuf,
+    registry: AgentRegistry,
+    reserved: AgentRegistry,
+    // Serializes allocations, so that two agents can't both pick the same free port
+    allocating: Arc<Mutex<()>>,
+}
+
+impl PortAllocator {
+    pub fn new(range: PortRange, state_path: PathBuf, registry: AgentRegistry) -> Self {
+        Self {
+            range,
+            state_path,
+            registry,
+            reserved: AgentRegistry::new(),
+            allocating: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Creates an allocator over the range in `AGENT_PORT_RANGE`, persisting under `base_dir`,
+    /// and restores the allocations persisted there
+    pub fn from_env(base_dir: &Path, registry: AgentRegistry) -> Result<Self, String> {
+        let range = crate::config::env_or("AGENT_PORT_RANGE", PortRange::default())?;
+        let allocator = Self::new(range, base_dir.join(PORTS_FILE), registry);
+        let restored = allocator.load()?;
+        logging::info!(
+            "Allocating agent ports from {} ({} restored)",
+            range,
+            restored
+        );
+        Ok(allocator)
+    }
+
+    pub fn range(&self) -> PortRange {
+        self.range
+    }
+
+    /// Restores the persisted allocations, returning how many there were
+    pub fn load(&self) -> Result<usize, String> {
+        let content = match fs::read_to_string(&self.state_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(format!("Failed to read port allocations: {}", e)),
+        };
+        let persisted: HashMap<String, AgentPortConfig> = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid port allocations: {}", e))?;
+        let count = persisted.len();
+        self.registry.extend(persisted);
+        Ok(count)
+    }
+
+    /// Allocates ports for an agent, `requested` for HTTP if given, otherwise the first free
+    /// one in the range, and the WebSocket port `websocket` resolves to next to it
+    pub fn allocate(
+        &self,
+        agent_id: &str,
+        requested: Option<u16>,
+        websocket: WebSocketPort,
+    ) -> Result<AgentPortConfig, String> {
+        let _allocating = self.allocating.lock().unwrap_or_else(|e| e.into_inner());
+        let config = self.pick(agent_id, requested, websocket)?;
+        self.registry.insert(agent_id, config.clone());
+        self.persist()?;
+        Ok(config)
+    }
+
+    /// Reserves a free port pair for a container that is not an agent yet
+    pub fn reserve(&self, holder: &str) -> Result<AgentPortConfig, String> {
+        let _allocating = self.allocating.lock().unwrap_or_else(|e| e.into_inner());
+        let config = self.pick(holder, None, WebSocketPort::Adjacent)?;
+        self.reserved.insert(holder, config.clone());
+        Ok(config)
+    }
+
+    /// Reserves a free port pair for each holder at once, or none if there aren't enough
+    pub fn reserve_many(&self, holders: &[String]) -> Result<Vec<AgentPortConfig>, String> {
+        let _allocating = self.allocating.lock().unwrap_or_else(|e| e.into_inner());
+        let mut reserved = Vec::with_capacity(holders.len());
+        for holder in holders {
+            match self.pick(holder, None, WebSocketPort::Adjacent) {
+                Ok(config) => {
+                    self.reserved.insert(holder, config.clone());
+                    reserved.push(config);
+                }
+                Err(e) => {
+                    for holder in &holders[..reserved.len()] {
+                        self.reserved.remove(holder);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(reserved)
+    }
+
+    /// Gives the ports reserved by `holder` to an agent, releasing the ones it had
+    pub fn assign_reserved(&self, holder: &str, agent_id: &str) -> Result<AgentPortConfig, String> {
+        let _allocating = self.allocating.lock().unwrap_or_else(|e| e.into_inner());
+        let config = self
+            .reserved
+            .remove(holder)
+            .ok_or_else(|| format!("No ports reserved for {}", holder))?;
+        self.registry.insert(agent_id, config.clone());
+        self.persist()?;
+        Ok(config)
+    }
+
+    /// Drops the reservation of `holder`
+    pub fn unreserve(&self, holder: &str) {
+        self.reserved.remove(holder);
+    }
+
+    /// Picks ports for `holder` that no one else holds
+    fn pick(
+        &self,
+        holder: &str,
+        requested: Option<u16>,
+        websocket: WebSocketPort,
+    ) -> Result<AgentPortConfig, String> {
+        let mut ports = self.registry.snapshot();
+        ports.extend(self.reserved.snapshot());
+        let taken = |port: u16| {
+            ports
+                .iter()
+                .filter(|(id, _)| id.as_str() != holder)
+                .any(|(_, config)| config.ports().any(|p| p == port))
+        };
+        let free = |port: u16| {
+            !taken(port)
+                && !CreateAgentParams::RESERVED_PORTS.contains(&port)
+                && is_port_available(port)
+        };
+        // Ports of an agent serving HTTP on `http_port`, if its WebSocket port can go with it
+        let config_for = |http_port: u16| {
+            let websocket_port = websocket.resolve(http_port);
+            let valid = match websocket {
+                WebSocketPort::Adjacent => websocket_port.is_some(),
+                WebSocketPort::Port(port) => port != http_port,
+                WebSocketPort::Disabled => true,
+            };
+            valid.then_some(AgentPortConfig {
+                http_port,
+                websocket_port,
+            })
+        };
+
+        match requested {
+            Some(port) => {
+                let config = config_for(port)
+                    .ok_or_else(|| format!("No WebSocket port available next to {}", port))?;
+                if let Some(busy) = config.ports().find(|&p| !free(p)) {
+                    return Err(format!("Port {} is already in use", busy));
+                }
+                Ok(config)
+            }
+            None => {
+                if let WebSocketPort::Port(port) = websocket {
+                    if !free(port) {
+                        return Err(format!("Port {} is already in use", port));
+                    }
+                }
+                // Adjacent WebSocket ports stay within the range as well
+                (self.range.start..=self.range.end)
+                    .filter_map(config_for)
+                    .filter(|config| {
+                        websocket != WebSocketPort::Adjacent || config.http_port < self.range.end
+                    })
+                    .find(|config| config.ports().all(free))
+                    .ok_or_else(|| format!("No free ports left in {}", self.range))
+            }
+        }
+    }
+
+    /// Releases an agent's ports
+    pub fn release(&self, agent_id: &str) -> Result<(), String> {
+        let _allocating = self.allocating.lock().unwrap_or_else(|e| e.into_inner());
+        if self.registry.remove(agent_id).is_some() {
+            self.persist()?;
+        }
+        Ok(())
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        if let Some(dir) = self.state_path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let content = serde_json::to_string_pretty(&self.registry.snapshot())
+            .map_err(|e| format!("Failed to serialize port allocations: {}", e))?;
+        fs::write(&self.state_path, content)
+            .map_err(|e| format!("Failed to write port allocations: {}", e))
+    }
+}
+
+/// Whether a TCP port can be bound on all of the host's interfaces right now
+pub fn is_port_available(port: u16) -> bool {
+    TcpListener::bind(("0.0.0.0", port)).is_ok()
+}
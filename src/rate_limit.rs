@@ -0,0 +1,164 @@
+use crate::config::env_or;
+use crate::ServiceContext;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Window over which job calls are counted
+const CALL_WINDOW: Duration = Duration::from_secs(3600);
+
+/// Limits the operator puts on each caller of a service
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    /// Most `create_agent` and `deploy_agent` calls per caller per hour
+    pub max_calls_per_hour: Option<u32>,
+    /// Most agents a single owner may have
+    pub max_agents_per_owner: Option<usize>,
+}
+
+impl RateLimits {
+    /// Loads the limits from `AGENT_MAX_CALLS_PER_HOUR` and `AGENT_MAX_AGENTS_PER_OWNER`
+    ///
+    /// Returns `None` when neither is set (or both are `0`).
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let max_calls_per_hour: u32 = env_or("AGENT_MAX_CALLS_PER_HOUR", 0)?;
+        let max_agents_per_owner: usize = env_or("AGENT_MAX_AGENTS_PER_OWNER", 0)?;
+        let limits = Self {
+            max_calls_per_hour: (max_calls_per_hour > 0).then_some(max_calls_per_hour),
+            max_agents_per_owner: (max_agents_per_owner > 0).then_some(max_agents_per_owner),
+        };
+        Ok((limits != Self::default()).then_some(limits))
+    }
+}
+
+/// Limit that a caller ran into
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QuotaLimit {
+    CallsPerHour { limit: u32, retry_after_secs: u64 },
+    AgentsPerOwner { limit: usize },
+}
+
+/// Error returned when a caller exceeds one of the operator's rate limits
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuotaExceeded {
+    /// Service and caller the limit applies to, e.g. `3/5Grw...`
+    pub key: String,
+    pub limit: QuotaLimit,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.limit {
+            QuotaLimit::CallsPerHour {
+                limit,
+                retry_after_secs,
+            } => write!(
+                f,
+                "Quota exceeded for {}: at most {} create and deploy calls per hour, retry in {}s",
+                self.key, limit, retry_after_secs
+            ),
+            QuotaLimit::AgentsPerOwner { limit } => write!(
+                f,
+                "Quota exceeded for {}: at most {} agents per owner",
+                self.key, limit
+            ),
+        }
+    }
+}
+
+impl From<QuotaExceeded> for String {
+    fn from(error: QuotaExceeded) -> Self {
+        error.to_string()
+    }
+}
+
+/// Counts recent calls per service and caller (shared across threads)
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    calls: Arc<Mutex<HashMap<String, VecDeque<Instant>>>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            calls: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn limits(&self) -> &RateLimits {
+        &self.limits
+    }
+
+    /// Records a call by `key` at `now`, failing if it would exceed the hourly limit
+    ///
+    /// Refused calls are not recorded, so callers regain capacity as old calls expire.
+    pub fn record_call(&self, key: &str, now: Instant) -> Result<(), QuotaExceeded> {
+        let Some(limit) = self.limits.max_calls_per_hour else {
+            return Ok(());
+        };
+        let mut calls = self.calls.lock().unwrap_or_else(|e| e.into_inner());
+        let recent = calls.entry(key.to_string()).or_default();
+        while recent
+            .front()
+            .is_some_and(|call| now.duration_since(*call) >= CALL_WINDOW)
+        {
+            recent.pop_front();
+        }
+
+        if let Some(oldest) = recent.front().filter(|_| recent.len() >= limit as usize) {
+            let retry_after = CALL_WINDOW.saturating_sub(now.duration_since(*oldest));
+            return Err(QuotaExceeded {
+                key: key.to_string(),
+                limit: QuotaLimit::CallsPerHour {
+                    limit,
+                    retry_after_secs: retry_after.as_secs().max(1),
+                },
+            });
+        }
+        recent.push_back(now);
+        Ok(())
+    }
+
+    /// Checks that an owner with `owned` agents may create `additional` more
+    pub fn check_agents(
+        &self,
+        key: &str,
+        owned: usize,
+        additional: usize,
+    ) -> Result<(), QuotaExceeded> {
+        match self.limits.max_agents_per_owner {
+            Some(limit) if owned + additional > limit => Err(QuotaExceeded {
+                key: key.to_string(),
+                limit: QuotaLimit::AgentsPerOwner { limit },
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Key the current caller is limited under: the service ID and the caller's account
+///
+/// Calls whose caller was not recorded share the `unknown` caller.
+pub fn caller_key(context: &ServiceContext) -> String {
+    let service_id = context
+        .config
+        .protocol_settings
+        .tangle()
+        .ok()
+        .and_then(|settings| settings.service_id)
+        .map(|id| id.to_string())
+        .unwrap_or_else(|| "-".to_string());
+    let caller = context.caller().unwrap_or_else(|| "unknown".to_string());
+    format!("{}/{}", service_id, caller)
+}
+
+/// Counts the current call against the caller's hourly limit, if the operator set one
+pub fn check_call(context: &ServiceContext) -> Result<(), QuotaExceeded> {
+    match &context.rate_limiter {
+        Some(limiter) => limiter.record_call(&caller_key(context), Instant::now()),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,148 @@
+use crate::templates;
+use crate::ServiceContext;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::process::Command as TokioCommand;
+
+/// Files of the starter template that every agent is created from
+const TEMPLATE_FILES: &[&str] = &[
+    "templates/starter/docker-compose.yml",
+    "templates/starter/.env.example",
+    "templates/starter/template.toml",
+];
+
+/// Longest a single readiness check may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a TEE credentials check is reused, to keep probes from hammering the Phala API
+const TEE_CHECK_TTL: Duration = Duration::from_secs(60);
+
+/// Outcome of one readiness check
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadinessCheck {
+    /// What was checked: `docker`, `template` or `tee`
+    pub name: String,
+    pub ok: bool,
+    /// Why the check failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ReadinessCheck {
+    fn new(name: &str, result: Result<(), String>) -> Self {
+        Self {
+            name: name.to_string(),
+            ok: result.is_ok(),
+            error: result.err(),
+        }
+    }
+}
+
+/// Whether the blueprint can serve jobs, with the checks that decided it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheck>,
+}
+
+/// Checks that Docker is reachable, the starter template is present and, with TEE
+/// deployments enabled, that the TEE credentials work
+pub async fn check_readiness(context: &ServiceContext) -> ReadinessReport {
+    let mut checks = vec![
+        ReadinessCheck::new("docker", check_docker().await),
+        ReadinessCheck::new("template", check_starter_template(Path::new("."))),
+    ];
+    if context.phala_cloud().is_ok() {
+        checks.push(ReadinessCheck::new("tee", check_tee(context).await));
+    }
+    ReadinessReport {
+        ready: checks.iter().all(|check| check.ok),
+        checks,
+    }
+}
+
+async fn check_docker() -> Result<(), String> {
+    let output = tokio::time::timeout(
+        CHECK_TIMEOUT,
+        TokioCommand::new("docker")
+            .args(["version", "--format", "{{.Server.Version}}"])
+            .output(),
+    )
+    .await
+    .map_err(|_| "Docker did not respond in time".to_string())?
+    .map_err(|e| format!("Failed to execute docker version command: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Docker daemon is not reachable: {}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+    Ok(())
+}
+
+/// Checks the starter template: the operator's under `root` if they override it, the one
+/// built into the binary otherwise
+pub fn check_starter_template(root: &Path) -> Result<(), String> {
+    if root
+        .join(templates::TEMPLATES_DIR)
+        .join(templates::DEFAULT_TEMPLATE)
+        .is_dir()
+    {
+        return check_template(root);
+    }
+    templates::embedded_template(
+        &root.join(templates::TEMPLATES_DIR),
+        templates::DEFAULT_TEMPLATE,
+    )
+    .map(|_| ())
+}
+
+/// Checks that the starter template is present under `root`
+pub fn check_template(root: &Path) -> Result<(), String> {
+    let missing: Vec<&str> = TEMPLATE_FILES
+        .iter()
+        .copied()
+        .filter(|file| !root.join(file).is_file())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            "Starter template is incomplete: {} missing",
+            missing.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+async fn check_tee(context: &ServiceContext) -> Result<(), String> {
+    static LAST_CHECK: Mutex<Option<(Instant, Result<(), String>)>> = Mutex::new(None);
+    if let Some((checked_at, result)) = LAST_CHECK
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+    {
+        if checked_at.elapsed() < TEE_CHECK_TTL {
+            return result.clone();
+        }
+    }
+
+    let result = discover_teepod(context).await;
+    *LAST_CHECK.lock().unwrap_or_else(|e| e.into_inner()) = Some((Instant::now(), result.clone()));
+    result
+}
+
+async fn discover_teepod(context: &ServiceContext) -> Result<(), String> {
+    let phala_cloud = context.phala_cloud()?;
+    if phala_cloud.api_key.is_empty() {
+        return Err("PHALA_CLOUD_API_KEY not set".to_string());
+    }
+    let shared_deployer = context.tee_deployer();
+    let result = tokio::time::timeout(
+        CHECK_TIMEOUT,
+        shared_deployer.get(&phala_cloud.api_key, &phala_cloud.api_endpoint),
+    )
+    .await
+    .map_err(|_| "Phala TEE API did not respond in time".to_string())?;
+    result.map(drop)
+}
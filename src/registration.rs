@@ -0,0 +1,191 @@
+use crate::config::env_or;
+use crate::error::BlueprintError;
+use crate::types::AgentMode;
+use crate::ServiceContext;
+use blueprint_sdk::macros::contexts::TangleClientContext;
+use blueprint_sdk::tangle_subxt::subxt::utils::AccountId32;
+use blueprint_sdk::tangle_subxt::tangle_testnet_runtime::api;
+use blueprint_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::tangle_primitives::services::field::Field;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// Parameters an operator advertises when registering for the blueprint
+///
+/// They are submitted as the JSON-encoded registration inputs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RegistrationParams {
+    /// Agent modes this operator can run
+    pub supported_modes: Vec<AgentMode>,
+    /// Maximum number of agents this operator hosts per service
+    pub max_agents: u32,
+    /// Price per created agent, in the smallest unit of the payment asset
+    pub price_per_agent: u64,
+}
+
+/// Parameters a service owner supplies when requesting a service instance
+///
+/// They are submitted as the JSON-encoded request inputs.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RequestParams {
+    /// Maximum number of agents the service owner expects to create
+    pub max_agents: Option<u32>,
+}
+
+/// Limits negotiated between an operator's registration and a service request
+#[derive(Clone, Debug)]
+pub struct AgentQuota {
+    pub max_agents: u32,
+    pub supported_modes: Vec<AgentMode>,
+    // Agents reserved by calls still creating them (shared across threads)
+    pending: Arc<Mutex<usize>>,
+}
+
+/// Room in a quota held for agents being created, given back when dropped
+///
+/// Hold it until the agents' directories exist, as they are counted from then on.
+#[derive(Debug)]
+pub struct QuotaReservation {
+    pending: Arc<Mutex<usize>>,
+    count: usize,
+}
+
+impl Drop for QuotaReservation {
+    fn drop(&mut self) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        *pending = pending.saturating_sub(self.count);
+    }
+}
+
+impl RegistrationParams {
+    /// Loads the operator's registration parameters from the environment
+    ///
+    /// Returns `None` if `AGENT_MAX_AGENTS` is unset, in which case no quota is enforced.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let max_agents = match std::env::var("AGENT_MAX_AGENTS") {
+            Ok(value) => value
+                .parse()
+                .map_err(|_| format!("Invalid value for AGENT_MAX_AGENTS: {}", value))?,
+            Err(_) => return Ok(None),
+        };
+
+        let supported_modes = match std::env::var("AGENT_SUPPORTED_MODES") {
+            Ok(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|mode| !mode.is_empty())
+                .map(str::parse)
+                .collect::<Result<Vec<AgentMode>, String>>()?,
+            Err(_) => AgentMode::ALL.to_vec(),
+        };
+
+        Ok(Some(Self {
+            supported_modes,
+            max_agents,
+            price_per_agent: env_or("AGENT_PRICE_PER_AGENT", 0)?,
+        }))
+    }
+
+    /// Encodes the parameters as registration inputs
+    pub fn to_registration_inputs(&self) -> Result<Vec<u8>, String> {
+        serde_json::to_vec(self).map_err(|e| format!("Failed to encode registration params: {}", e))
+    }
+
+    /// Returns the quota for a service, capped by what its owner requested
+    pub fn quota(&self, request: Option<&RequestParams>) -> AgentQuota {
+        let requested = request.and_then(|request| request.max_agents);
+        AgentQuota {
+            max_agents: requested.map_or(self.max_agents, |max| max.min(self.max_agents)),
+            supported_modes: self.supported_modes.clone(),
+            pending: Arc::new(Mutex::new(0)),
+        }
+    }
+}
+
+impl RequestParams {
+    /// Reads the service's request parameters from the inputs its instance was requested with
+    ///
+    /// Returns `None` when no service ID is configured or the request had no inputs.
+    pub async fn from_chain(context: &ServiceContext) -> Result<Option<Self>, String> {
+        let Some(service_id) = context
+            .config
+            .protocol_settings
+            .tangle()
+            .ok()
+            .and_then(|settings| settings.service_id)
+        else {
+            return Ok(None);
+        };
+        let client = context
+            .tangle_client()
+            .await
+            .map_err(|e| format!("Failed to get Tangle client: {}", e))?;
+        let instance = client
+            .subxt_client()
+            .storage()
+            .at_latest()
+            .await
+            .map_err(|e| format!("Failed to read chain state: {}", e))?
+            .fetch(&api::storage().services().instances(service_id))
+            .await
+            .map_err(|e| format!("Failed to fetch service {}: {}", service_id, e))?
+            .ok_or_else(|| format!("Service {} is not active on-chain", service_id))?;
+        Self::from_args(&instance.args)
+    }
+
+    /// Decodes the parameters from a service's request inputs, JSON in the first input
+    pub fn from_args(args: &[Field<AccountId32>]) -> Result<Option<Self>, String> {
+        let json = match args.first() {
+            None => return Ok(None),
+            Some(Field::String(json)) => json.as_bytes(),
+            Some(Field::Bytes(json)) => json.as_slice(),
+            Some(_) => return Err("Service request inputs are not JSON".to_string()),
+        };
+        serde_json::from_slice(json)
+            .map(Some)
+            .map_err(|e| format!("Invalid service request params: {}", e))
+    }
+}
+
+impl AgentQuota {
+    /// Checks that `additional` agents in `mode` fit next to the `existing` ones
+    pub fn check(
+        &self,
+        mode: &AgentMode,
+        existing: usize,
+        additional: usize,
+    ) -> Result<(), String> {
+        if !self.supported_modes.contains(mode) {
+            return Err(format!("This operator does not support {} agents", mode));
+        }
+        if existing + additional > self.max_agents as usize {
+            return Err(format!(
+                "Agent quota exceeded: {} agents exist and the quota is {}",
+                existing, self.max_agents
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reserves room for one agent per mode in `modes`, next to the existing agents and those
+    /// other calls are creating
+    ///
+    /// `count_existing` runs with the reservations locked, so concurrent calls can't both
+    /// take the last free slot.
+    pub fn reserve(
+        &self,
+        modes: &[AgentMode],
+        count_existing: impl FnOnce() -> Result<usize, BlueprintError>,
+    ) -> Result<QuotaReservation, BlueprintError> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let existing = count_existing()? + *pending;
+        for mode in modes {
+            self.check(mode, existing, modes.len())
+                .map_err(BlueprintError::Quota)?;
+        }
+        *pending += modes.len();
+        Ok(QuotaReservation {
+            pending: self.pending.clone(),
+            count: modes.len(),
+        })
+    }
+}
@@ -0,0 +1,71 @@
+use crate::AgentPortConfig;
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Ports of the agents this operator runs, keyed by agent ID (shared across threads)
+///
+/// Backed by a concurrent map, so callers never block each other for long and there is no
+/// lock that can be poisoned. Reads return copies; nothing is held across an `.await`.
+#[derive(Clone, Debug, Default)]
+pub struct AgentRegistry {
+    agents: Arc<DashMap<String, AgentPortConfig>>,
+}
+
+impl AgentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an agent's ports, returning the ones it had before
+    pub fn insert(&self, agent_id: &str, ports: AgentPortConfig) -> Option<AgentPortConfig> {
+        self.agents.insert(agent_id.to_string(), ports)
+    }
+
+    pub fn get(&self, agent_id: &str) -> Option<AgentPortConfig> {
+        self.agents.get(agent_id).map(|ports| ports.clone())
+    }
+
+    /// Forgets an agent, returning the ports it had
+    pub fn remove(&self, agent_id: &str) -> Option<AgentPortConfig> {
+        self.agents.remove(agent_id).map(|(_, ports)| ports)
+    }
+
+    pub fn contains(&self, agent_id: &str) -> bool {
+        self.agents.contains_key(agent_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.agents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.agents.is_empty()
+    }
+
+    /// Returns the IDs of all registered agents, sorted
+    pub fn agent_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .agents
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Records the ports of several agents
+    pub fn extend(&self, agents: impl IntoIterator<Item = (String, AgentPortConfig)>) {
+        for (agent_id, ports) in agents {
+            self.agents.insert(agent_id, ports);
+        }
+    }
+
+    /// Returns a copy of every agent's ports
+    pub fn snapshot(&self) -> HashMap<String, AgentPortConfig> {
+        self.agents
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+}
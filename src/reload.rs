@@ -0,0 +1,147 @@
+use crate::config::{HttpConfig, ServiceConfig};
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{signal, SignalKind};
+
+/// Operator settings that can change while the blueprint runs (shared across threads)
+///
+/// Once a context holds live settings, its agents' directory, Phala Cloud account and HTTP
+/// configuration are read from them rather than from the values the context was built with.
+#[derive(Clone, Debug)]
+pub struct LiveConfig {
+    inner: Arc<RwLock<LiveSettings>>,
+}
+
+/// Current values of the reloadable settings
+#[derive(Clone, Debug)]
+pub struct LiveSettings {
+    pub settings: ServiceConfig,
+    pub http_config: HttpConfig,
+}
+
+impl LiveConfig {
+    pub fn new(settings: ServiceConfig, http_config: HttpConfig) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(LiveSettings {
+                settings,
+                http_config,
+            })),
+        }
+    }
+
+    /// Returns the current settings
+    pub fn get(&self) -> LiveSettings {
+        self.inner.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replaces the settings, returning the previous ones
+    fn replace(&self, settings: LiveSettings) -> LiveSettings {
+        let mut current = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut current, settings)
+    }
+}
+
+/// Applies new settings to a running blueprint
+///
+/// The agents' directory, the Phala Cloud account and the health check attempts apply from
+/// the next job on, and the deployment queue and the health monitor follow their new limits
+/// right away. Deployments in flight are left running. Invalid settings are rejected as a
+/// whole, keeping the current ones.
+pub fn apply(context: &ServiceContext, settings: ServiceConfig) -> Result<(), String> {
+    let live = context
+        .live_config
+        .as_ref()
+        .ok_or("This context has no reloadable settings")?;
+    settings.validate()?;
+
+    let mut http_config = context.http_config();
+    settings.apply_to_http_config(&mut http_config);
+    if let Some(queue) = &context.deploy_queue {
+        queue.set_max_concurrent(settings.max_concurrent_deploys());
+    }
+    if let Some(monitor) = &context.health_monitor {
+        let mut policy = monitor.policy();
+        settings.apply_to_monitor_policy(&mut policy);
+        monitor.set_policy(policy);
+    }
+
+    let previous = live.replace(LiveSettings {
+        settings: settings.clone(),
+        http_config,
+    });
+    if previous.settings.agents_base_dir() != settings.agents_base_dir() {
+        logging::warn!(
+            "Agents' directory changed from {} to {}; agents left in the old one are no longer found",
+            previous.settings.agents_base_dir(),
+            settings.agents_base_dir()
+        );
+    }
+    if previous.settings.phala_cloud() != settings.phala_cloud() {
+        logging::info!(
+            tee_enabled = settings.phala_cloud().is_some(),
+            "Phala Cloud account changed"
+        );
+    }
+    logging::info!(
+        max_concurrent_deploys = settings.max_concurrent_deploys(),
+        health_max_attempts = context.http_config().health_max_attempts,
+        "Reloaded configuration"
+    );
+    Ok(())
+}
+
+/// Reloads the settings from the config file and the environment
+pub fn reload(context: &ServiceContext) -> Result<(), String> {
+    apply(context, ServiceConfig::from_env()?)
+}
+
+/// Reloads the settings on SIGHUP and, if `watch_interval` is set, whenever the config file
+/// changes, until the process exits
+pub async fn run_config_reloader(context: ServiceContext, watch_interval: Option<Duration>) {
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            logging::warn!(
+                "Failed to listen for SIGHUP, configuration won't be reloaded: {}",
+                e
+            );
+            return;
+        }
+    };
+    let config_file = ServiceConfig::file_path();
+    let modified_at = || {
+        config_file
+            .as_ref()
+            .and_then(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+    };
+    let mut modified: Option<SystemTime> = modified_at();
+    let mut ticker = watch_interval.map(tokio::time::interval);
+
+    loop {
+        let trigger = tokio::select! {
+            _ = hangups.recv() => "SIGHUP",
+            _ = async {
+                match &mut ticker {
+                    Some(ticker) => ticker.tick().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                let now = modified_at();
+                if now == modified {
+                    continue;
+                }
+                modified = now;
+                "a config file change"
+            }
+        };
+        if let Err(e) = reload(&context) {
+            logging::error!(
+                "Failed to reload configuration after {}, keeping the current one: {}",
+                trigger,
+                e
+            );
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use handlebars::Handlebars;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// Values a template is rendered with, by variable name
+pub type RenderContext = BTreeMap<String, String>;
+
+/// Renders a template with Handlebars
+///
+/// `{{NAME}}` is replaced by the value of `NAME`, or by nothing if it has none.
+/// `{{#if NAME}}...{{else}}...{{/if}}` keeps the first branch if `NAME` has a non-empty value
+/// and the `{{else}}` branch, if any, otherwise; `{{#unless NAME}}` does the opposite.
+/// `\{{` writes a literal `{{`. Values are inserted as they are, without HTML escaping, so
+/// callers escape them for the file being rendered.
+pub fn render(template: &str, context: &RenderContext) -> Result<String, String> {
+    registry()
+        .render_template(template, context)
+        .map_err(|e| e.to_string())
+}
+
+/// The Handlebars registry templates are rendered with, shared by every job
+fn registry() -> &'static Handlebars<'static> {
+    static REGISTRY: OnceLock<Handlebars<'static>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut registry = Handlebars::new();
+        registry.register_escape_fn(handlebars::no_escape);
+        registry
+    })
+}
@@ -0,0 +1,169 @@
+use crate::config::env_or;
+use crate::helpers::write_private_file;
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Directory inside an agent's directory holding the diagnostics written about it
+pub const DIAGNOSTICS_DIR: &str = ".diagnostics";
+
+/// Time between sweeps of old diagnostics
+const SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// How much of an agent's logs and diagnostics the operator keeps
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogRetention {
+    /// Size at which a container's log file is rotated, in Docker's format (e.g. `10m`)
+    pub max_size: String,
+    /// Rotated log files kept per container, including the current one
+    pub max_files: u32,
+    /// Age after which diagnostics are removed
+    pub diagnostics_max_age: Duration,
+    /// Diagnostics kept per agent; the oldest are removed first
+    pub diagnostics_max_files: usize,
+}
+
+impl Default for LogRetention {
+    fn default() -> Self {
+        Self {
+            max_size: "10m".to_string(),
+            max_files: 3,
+            diagnostics_max_age: Duration::from_secs(7 * 24 * 3600),
+            diagnostics_max_files: 20,
+        }
+    }
+}
+
+impl LogRetention {
+    /// Loads the retention from `AGENT_LOG_MAX_SIZE`, `AGENT_LOG_MAX_FILES`,
+    /// `AGENT_DIAGNOSTICS_RETENTION_DAYS` and `AGENT_DIAGNOSTICS_MAX_FILES`
+    pub fn from_env() -> Result<Self, String> {
+        let defaults = Self::default();
+        let retention = Self {
+            max_size: env_or("AGENT_LOG_MAX_SIZE", defaults.max_size)?,
+            max_files: env_or("AGENT_LOG_MAX_FILES", defaults.max_files)?,
+            diagnostics_max_age: Duration::from_secs(
+                env_or::<u64>(
+                    "AGENT_DIAGNOSTICS_RETENTION_DAYS",
+                    defaults.diagnostics_max_age.as_secs() / (24 * 3600),
+                )?
+                .saturating_mul(24 * 3600),
+            ),
+            diagnostics_max_files: env_or(
+                "AGENT_DIAGNOSTICS_MAX_FILES",
+                defaults.diagnostics_max_files,
+            )?,
+        };
+        retention.validate()?;
+        Ok(retention)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let (digits, unit) = self
+            .max_size
+            .split_at(self.max_size.trim_end_matches(['k', 'm', 'g']).len());
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) || unit.len() > 1 {
+            return Err(format!(
+                "Invalid AGENT_LOG_MAX_SIZE {}: expected a size like 10m",
+                self.max_size
+            ));
+        }
+        if self.max_files == 0 {
+            return Err("AGENT_LOG_MAX_FILES must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Returns the compose `logging` section rotating a service's logs
+    pub fn compose_logging(&self) -> serde_yaml::Value {
+        let mut options = serde_yaml::Mapping::new();
+        options.insert("max-size".into(), self.max_size.clone().into());
+        options.insert("max-file".into(), self.max_files.to_string().into());
+        let mut logging = serde_yaml::Mapping::new();
+        logging.insert("driver".into(), "json-file".into());
+        logging.insert("options".into(), serde_yaml::Value::Mapping(options));
+        serde_yaml::Value::Mapping(logging)
+    }
+}
+
+/// Writes a diagnostics file into the agent's directory, returning its path
+///
+/// Files are named after the time they were written and `file_name`, e.g.
+/// `.diagnostics/1700000000-health-check.log`, and only readable by the operator.
+pub fn write_diagnostics(
+    agent_dir: &Path,
+    file_name: &str,
+    content: impl AsRef<[u8]>,
+) -> Result<PathBuf, String> {
+    let dir = agent_dir.join(DIAGNOSTICS_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path = dir.join(format!("{}-{}", timestamp, file_name));
+    write_private_file(&path, content)?;
+    Ok(path)
+}
+
+/// Removes the diagnostics of every agent under `base_dir` that the retention doesn't keep,
+/// returning how many files were removed
+pub fn sweep_diagnostics(base_dir: &Path, retention: &LogRetention) -> Result<usize, String> {
+    let entries = match fs::read_dir(base_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(format!("Failed to read {}: {}", base_dir.display(), e)),
+    };
+
+    let mut removed = 0;
+    for entry in entries.filter_map(Result::ok) {
+        let dir = entry.path().join(DIAGNOSTICS_DIR);
+        let Ok(files) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut files: Vec<(PathBuf, SystemTime)> = files
+            .filter_map(Result::ok)
+            .filter_map(|file| {
+                let modified = file.metadata().and_then(|m| m.modified()).ok()?;
+                Some((file.path(), modified))
+            })
+            .collect();
+
+        // Newest first, so everything past the limit is the oldest
+        files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.cmp(&a.0)));
+        for (index, (path, modified)) in files.into_iter().enumerate() {
+            let expired = modified
+                .elapsed()
+                .is_ok_and(|age| age > retention.diagnostics_max_age);
+            if index < retention.diagnostics_max_files && !expired {
+                continue;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => removed += 1,
+                Err(e) => {
+                    logging::warn!(path = %path.display(), error = %e, "Failed to remove diagnostics")
+                }
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// Removes old diagnostics periodically, until the process exits
+pub async fn run_retention_sweeper(context: ServiceContext, retention: LogRetention) {
+    let base_dir = match context.agents_base_dir() {
+        Some(dir) => dir,
+        None => "./agents".to_string(),
+    };
+    let mut ticker = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        ticker.tick().await;
+        match sweep_diagnostics(Path::new(&base_dir), &retention) {
+            Ok(0) => {}
+            Ok(removed) => logging::info!(removed, "Removed old agent diagnostics"),
+            Err(e) => logging::warn!(error = %e, "Failed to sweep agent diagnostics"),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::ServiceContext;
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use blueprint_sdk::logging;
+use std::net::SocketAddr;
+
+/// Router serving every local agent under one port, as `/<agent_id>/interact` and
+/// `/<agent_id>/health`
+///
+/// Agents then only listen on localhost, so the router's port is the only one to open.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RouterConfig {
+    /// Address the router listens on, e.g. `0.0.0.0:8080`
+    pub bind: SocketAddr,
+    /// Base URL the router is reachable at, reported as agents' endpoints when set
+    pub public_url: Option<String>,
+}
+
+impl RouterConfig {
+    /// Loads the listen address from `AGENT_ROUTER_ADDR` and the public URL from
+    /// `AGENT_ROUTER_PUBLIC_URL`, if an address is set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(addr) = std::env::var("AGENT_ROUTER_ADDR")
+            .ok()
+            .filter(|addr| !addr.trim().is_empty())
+        else {
+            return Ok(None);
+        };
+        let bind = addr
+            .trim()
+            .parse()
+            .map_err(|e| format!("Invalid AGENT_ROUTER_ADDR {}: {}", addr, e))?;
+        let public_url = std::env::var("AGENT_ROUTER_PUBLIC_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty())
+            .map(|url| url.trim().trim_end_matches('/').to_string());
+        if let Some(url) = &public_url {
+            url::Url::parse(url)
+                .map_err(|e| format!("Invalid AGENT_ROUTER_PUBLIC_URL {}: {}", url, e))?;
+        }
+        Ok(Some(Self { bind, public_url }))
+    }
+
+    /// Returns the public endpoint of an agent behind the router, if its URL is known
+    pub fn agent_url(&self, agent_id: &str) -> Option<String> {
+        self.public_url
+            .as_ref()
+            .map(|url| format!("{}/{}", url, agent_id))
+    }
+}
+
+/// Routes of the agent router
+pub fn router(context: ServiceContext) -> Router {
+    Router::new()
+        .route("/:agent_id/interact", post(forward_interact))
+        .route("/:agent_id/health", get(forward_health))
+        .with_state(context)
+}
+
+/// Serves the agent router until the process exits
+pub async fn serve(context: ServiceContext, config: RouterConfig) -> Result<(), String> {
+    let listener = tokio::net::TcpListener::bind(config.bind)
+        .await
+        .map_err(|e| format!("Failed to bind agent router to {}: {}", config.bind, e))?;
+    logging::info!("Agent router listening on {}", config.bind);
+    axum::serve(listener, router(context))
+        .await
+        .map_err(|e| format!("Agent router failed: {}", e))
+}
+
+async fn forward_interact(
+    State(context): State<ServiceContext>,
+    Path(agent_id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    forward(
+        &context,
+        &agent_id,
+        reqwest::Method::POST,
+        "interact",
+        &headers,
+        body,
+    )
+    .await
+}
+
+async fn forward_health(
+    State(context): State<ServiceContext>,
+    Path(agent_id): Path<String>,
+) -> Response {
+    forward(
+        &context,
+        &agent_id,
+        reqwest::Method::GET,
+        "health",
+        &HeaderMap::new(),
+        Bytes::new(),
+    )
+    .await
+}
+
+/// Forwards a request to the agent's HTTP port, relaying its status, content type and body
+async fn forward(
+    context: &ServiceContext,
+    agent_id: &str,
+    method: reqwest::Method,
+    path: &str,
+    headers: &HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(ports) = context
+        .agent_registry
+        .as_ref()
+        .and_then(|registry| registry.get(agent_id))
+    else {
+        return (
+            StatusCode::NOT_FOUND,
+            format!("Unknown agent: {}", agent_id),
+        )
+            .into_response();
+    };
+
+    let url = format!("http://127.0.0.1:{}/{}", ports.http_port, path);
+    let client = context.http_client.clone().unwrap_or_default();
+    let mut request = client
+        .request(method, &url)
+        .timeout(context.http_config().request_timeout)
+        .body(body.to_vec());
+    if let Some(content_type) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+    {
+        request = request.header(reqwest::header::CONTENT_TYPE, content_type);
+    }
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            logging::debug!(agent_id = %agent_id, error = %e, "Failed to reach agent");
+            return (
+                StatusCode::BAD_GATEWAY,
+                format!("Agent {} is not reachable", agent_id),
+            )
+                .into_response();
+        }
+    };
+    let status =
+        StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    match response.bytes().await {
+        Ok(body) => {
+            let mut response = (status, body.to_vec()).into_response();
+            if let Some(content_type) = content_type.and_then(|value| value.parse().ok()) {
+                response
+                    .headers_mut()
+                    .insert(header::CONTENT_TYPE, content_type);
+            }
+            response
+        }
+        Err(e) => (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to read the response of agent {}: {}", agent_id, e),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,287 @@
+use crate::codec;
+use crate::error::BlueprintError;
+use crate::helpers::run_blocking;
+use crate::templates::{
+    self, VariableStage, DEFAULT_TEMPLATE, MANIFEST_FILE, TEMPLATES_DIR, TEMPLATE_VAR, VARIABLES,
+};
+use crate::types::{
+    AgentImage, AgentMode, ScaffoldTemplateParams, ScaffoldTemplateResult, ScaffoldedFile,
+};
+use crate::wallet;
+use crate::ServiceContext;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Image scaffolded templates start from unless another is asked for
+pub const DEFAULT_BASE_IMAGE: &str = "node:20-slim";
+
+/// Port scaffolded agents listen on in their container unless another is asked for
+pub const DEFAULT_HTTP_PORT: u16 = 3000;
+
+/// Variables of the contract that only make sense for templates with a WebSocket server,
+/// which scaffolded templates start without, that the compose file uses itself, or that the
+/// blueprint adds to every local agent's compose file
+const UNPASSED_VARIABLES: &[&str] = &[
+    "PORT",
+    "WEBSOCKET_PORT",
+    "WEBSOCKET_URL",
+    "AGENT_BIND_ADDRESS",
+    "CONTAINER_NAME",
+    wallet::WALLET_DATA_FILE_VAR,
+    wallet::WALLET_DATA_VAR,
+];
+
+/// Generates the files of a new template
+///
+/// The skeleton is a valid template: its manifest, `Dockerfile`, compose file and
+/// `.env.example` are wired to every variable of [`VARIABLES`], leaving the agent's code and
+/// the command starting it to be written.
+pub fn scaffold(params: &ScaffoldTemplateParams) -> Result<Vec<ScaffoldedFile>, String> {
+    templates::validate_id(&params.template_id)?;
+    let base_image = params.base_image.as_deref().unwrap_or(DEFAULT_BASE_IMAGE);
+    AgentImage::Image {
+        reference: base_image.to_string(),
+    }
+    .validate()
+    .map_err(|e| format!("Invalid base image: {}", e))?;
+    let http_port = params.http_port.unwrap_or(DEFAULT_HTTP_PORT);
+    if http_port == 0 {
+        return Err("The HTTP port must not be 0".to_string());
+    }
+    let modes = params
+        .modes
+        .clone()
+        .unwrap_or_else(|| vec![AgentMode::Chat, AgentMode::Autonomous]);
+    if modes.is_empty() {
+        return Err("modes must not be empty".to_string());
+    }
+    let name = params.name.as_deref().unwrap_or(&params.template_id);
+    if name.contains(['\n', '\r'])
+        || params
+            .description
+            .as_deref()
+            .is_some_and(|d| d.contains(['\n', '\r']))
+    {
+        return Err("The name and description must be single lines".to_string());
+    }
+
+    let file = |path: &str, content: String| ScaffoldedFile {
+        path: path.to_string(),
+        content,
+    };
+    Ok(vec![
+        file(
+            MANIFEST_FILE,
+            manifest(name, params.description.as_deref(), &modes, http_port)?,
+        ),
+        file("Dockerfile", dockerfile(base_image, http_port)),
+        file(
+            ".dockerignore",
+            ".env\n.diagnostics\n.wallet\n*.sealed\n".to_string(),
+        ),
+        file("docker-compose.yml", compose(http_port)),
+        file(".env.example", env_example()),
+        file("README.md", readme(name, &params.template_id, http_port)),
+    ])
+}
+
+fn manifest(
+    name: &str,
+    description: Option<&str>,
+    modes: &[AgentMode],
+    http_port: u16,
+) -> Result<String, String> {
+    let quote = |value: &str| toml::Value::String(value.to_string()).to_string();
+    let modes: Vec<String> = modes.iter().map(|mode| quote(&mode.to_string())).collect();
+    let mut manifest = format!("name = {}\n", quote(name));
+    if let Some(description) = description {
+        writeln!(manifest, "description = {}", quote(description)).unwrap();
+    }
+    write!(
+        manifest,
+        "version = \"0.1.0\"\n\
+         min_blueprint_version = \"{}\"\n\
+         required_env = [\"CDP_API_KEY_NAME\", \"CDP_API_KEY_PRIVATE_KEY\"]\n\
+         modes = [{}]\n\
+         \n\
+         [ports]\n\
+         http = {}\n",
+        env!("CARGO_PKG_VERSION"),
+        modes.join(", "),
+        http_port
+    )
+    .unwrap();
+
+    // Never hand out a manifest the blueprint would refuse
+    toml::from_str::<templates::TemplateManifest>(&manifest)
+        .map_err(|e| format!("Invalid manifest: {}", e))?
+        .validate()?;
+    Ok(manifest)
+}
+
+fn dockerfile(base_image: &str, http_port: u16) -> String {
+    format!(
+        "FROM {base_image}\n\
+         \n\
+         WORKDIR /app\n\
+         \n\
+         # The compose file's health check runs curl in the container; install it here if the\n\
+         # base image lacks it\n\
+         \n\
+         # Copy the agent's code; .dockerignore keeps its settings and secrets out of the image\n\
+         COPY . .\n\
+         \n\
+         # The agent serves GET /health, GET /status, GET /actions, GET /wallet,\n\
+         # POST /faucet and POST /interact on $PORT\n\
+         ENV PORT={http_port}\n\
+         EXPOSE {http_port}\n\
+         \n\
+         # Replace with the command starting the agent\n\
+         CMD [\"sh\", \"-c\", \"echo 'Set the command starting the agent in the Dockerfile' >&2; exit 1\"]\n"
+    )
+}
+
+fn compose(http_port: u16) -> String {
+    let mut compose = format!(
+        "services:\n  \
+           agent:\n    \
+             build: .\n    \
+             container_name: ${{CONTAINER_NAME:-agent}}\n    \
+             ports:\n      \
+               - \"${{AGENT_BIND_ADDRESS:-0.0.0.0}}:${{PORT:-{http_port}}}:{http_port}\"\n    \
+             environment:\n      \
+               - PORT={http_port}\n"
+    );
+    for var in VARIABLES {
+        if !UNPASSED_VARIABLES.contains(&var.name) {
+            writeln!(compose, "      - {0}=${{{0}}}", var.name).unwrap();
+        }
+    }
+    write!(
+        compose,
+        "    restart: unless-stopped\n    \
+           healthcheck:\n      \
+             test: [\"CMD\", \"curl\", \"-f\", \"http://localhost:{http_port}/health\"]\n      \
+             interval: 30s\n      \
+             timeout: 10s\n      \
+             retries: 3\n      \
+             start_period: 30s\n"
+    )
+    .unwrap();
+    compose
+}
+
+fn env_example() -> String {
+    let mut env = String::new();
+    for var in VARIABLES {
+        if UNPASSED_VARIABLES.contains(&var.name) {
+            continue;
+        }
+        writeln!(env, "# {}", var.description.replace('`', "")).unwrap();
+        match var.stage {
+            // Filled in when the agent is created
+            VariableStage::Create => writeln!(env, "{0}={{{{{0}}}}}\n", var.name).unwrap(),
+            // Written into the agent's .env when it is deployed
+            VariableStage::Deploy => writeln!(env, "{}=\n", var.name).unwrap(),
+        }
+    }
+    env
+}
+
+fn readme(name: &str, template_id: &str, http_port: u16) -> String {
+    let mut readme = format!(
+        "# {name}\n\
+         \n\
+         An agent template, created with `\"template_id\": \"{template_id}\"`. Put the agent's code\n\
+         next to this file and the command starting it in the `Dockerfile`. The agent must serve\n\
+         these routes on port {http_port}, which the blueprint uses to check its health and talk to\n\
+         it:\n\
+         \n\
+         - `GET /health`\n\
+         - `GET /status`\n\
+         - `GET /actions?since=<unix seconds>`\n\
+         - `GET /wallet`, returning `{{\"address\": \"0x...\", \"network_id\": \"...\", \"balance\": \"<wei>\"}}`\n\
+         - `POST /faucet`, requesting testnet funds and returning `{{\"tx_hashes\": [...]}}`\n\
+         - `POST /interact` with `{{\"message\": \"...\"}}`\n\
+         \n\
+         Check the template with the `validate_template` job before creating agents from it.\n\
+         \n\
+         ## Variables\n\
+         \n\
+         The agent is given these variables. `{TEMPLATE_VAR}` and the others set on creation can\n\
+         also be placed with `{{{{NAME}}}}` in `.env.example` and `docker-compose.yml`.\n\
+         \n\
+         | Variable | Set on | Description |\n\
+         |----------|--------|-------------|\n"
+    );
+    for var in VARIABLES {
+        let stage = match var.stage {
+            VariableStage::Create => "creation",
+            VariableStage::Deploy => "deployment",
+        };
+        writeln!(
+            readme,
+            "| `{}` | {} | {} |",
+            var.name, stage, var.description
+        )
+        .unwrap();
+    }
+    readme
+}
+
+/// Writes scaffolded files into a new template directory `dir`, which must not exist
+pub fn write(dir: &Path, files: &[ScaffoldedFile]) -> Result<(), String> {
+    fs::create_dir(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    for file in files {
+        let path = dir.join(&file.path);
+        if let Err(e) = fs::write(&path, &file.content) {
+            let _ = fs::remove_dir_all(dir);
+            return Err(format!("Failed to write {}: {}", path.display(), e));
+        }
+    }
+    Ok(())
+}
+
+/// Handles the scaffold_template job
+///
+/// Returns the files of the new template, writing them into the templates directory as well
+/// if asked to by the service owner.
+pub async fn handle_scaffold_template(
+    params_bytes: Vec<u8>,
+    context: &ServiceContext,
+) -> Result<Vec<u8>, BlueprintError> {
+    let (params, encoding): (ScaffoldTemplateParams, _) =
+        codec::decode(&params_bytes).map_err(BlueprintError::Params)?;
+    let files = scaffold(&params).map_err(BlueprintError::Params)?;
+
+    // Installing adds a template every caller can create agents from
+    if params.install {
+        if let Some(owner) = &context.service_owner {
+            if context.caller().as_ref() != Some(owner) {
+                return Err(BlueprintError::Unauthorized(
+                    "Only the service owner may install templates".to_string(),
+                ));
+            }
+        }
+        // The default template is built into the binary when its directory is missing
+        if params.template_id == DEFAULT_TEMPLATE {
+            return Err(BlueprintError::Params(format!(
+                "Template {} already exists",
+                DEFAULT_TEMPLATE
+            )));
+        }
+        let dir = Path::new(TEMPLATES_DIR).join(&params.template_id);
+        let written = files.clone();
+        run_blocking(move || write(&dir, &written))
+            .await
+            .map_err(BlueprintError::Template)?;
+    }
+
+    let result = ScaffoldTemplateResult {
+        template_id: params.template_id,
+        files,
+        installed: params.install,
+    };
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
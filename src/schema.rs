@@ -0,0 +1,138 @@
+use crate::codec;
+use crate::envelope::VersionedParams;
+use crate::error::BlueprintError;
+use crate::types::{
+    AgentActionsParams, AgentActionsResult, AgentBalancesResult, AgentCreationResult,
+    AgentDeploymentResult, AgentStatusParams, AgentStatusResult, AgentSwarmResult,
+    BatchCreationResult, CollectDiagnosticsParams, CreateAgentParams, CreateAgentSwarmParams,
+    CreateAgentsParams, DeployAgentParams, DiagnosticsResult, FundAgentParams, FundAgentResult,
+    GetAgentBalancesParams, ListTemplatesParams, ListTemplatesResult, ParamsSchema,
+    ParamsSchemaParams, ParamsSchemaResult, ScaffoldTemplateParams, ScaffoldTemplateResult,
+    SecretAuditParams, SecretAuditResult, TemplateValidationResult, ValidateTemplateParams,
+};
+use schemars::{schema_for, JsonSchema, Schema};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+
+/// JSON Schemas of one job's parameters and result
+#[derive(Clone, Debug)]
+pub struct JobSchema {
+    pub job: &'static str,
+    pub params: Schema,
+    pub result: Schema,
+}
+
+impl JobSchema {
+    fn of<P: JsonSchema, R: JsonSchema>(job: &'static str) -> Self {
+        Self {
+            job,
+            params: schema_for!(P),
+            result: schema_for!(R),
+        }
+    }
+}
+
+/// Returns the schemas of every job's parameters and result
+///
+/// Parameters are described in their current version; the versioned envelope that may wrap
+/// them is not part of the schema.
+pub fn job_schemas() -> Vec<JobSchema> {
+    vec![
+        JobSchema::of::<CreateAgentParams, AgentCreationResult>("create_agent"),
+        JobSchema::of::<DeployAgentParams, AgentDeploymentResult>("deploy_agent"),
+        JobSchema::of::<AgentActionsParams, AgentActionsResult>("agent_actions"),
+        JobSchema::of::<AgentStatusParams, AgentStatusResult>("agent_status"),
+        JobSchema::of::<CreateAgentSwarmParams, AgentSwarmResult>("create_agent_swarm"),
+        JobSchema::of::<SecretAuditParams, SecretAuditResult>("secret_audit_log"),
+        JobSchema::of::<CollectDiagnosticsParams, DiagnosticsResult>("collect_diagnostics"),
+        JobSchema::of::<CreateAgentsParams, BatchCreationResult>("create_agents"),
+        JobSchema::of::<ParamsSchemaParams, ParamsSchemaResult>("get_params_schema"),
+        JobSchema::of::<ValidateTemplateParams, TemplateValidationResult>("validate_template"),
+        JobSchema::of::<ListTemplatesParams, ListTemplatesResult>("list_templates"),
+        JobSchema::of::<ScaffoldTemplateParams, ScaffoldTemplateResult>("scaffold_template"),
+        JobSchema::of::<FundAgentParams, FundAgentResult>("fund_agent"),
+        JobSchema::of::<GetAgentBalancesParams, AgentBalancesResult>("get_agent_balances"),
+    ]
+}
+
+fn versioned_schema<P: VersionedParams + JsonSchema>(job: &str) -> ParamsSchema {
+    ParamsSchema {
+        job: job.to_string(),
+        version: P::CURRENT_VERSION,
+        min_version: 1,
+        schema: schema_for!(P).to_value(),
+    }
+}
+
+/// Returns the schemas and versions of the parameters of the given jobs, or of every job
+/// whose parameters are versioned if none are given
+pub fn params_schemas(jobs: &[String]) -> Result<ParamsSchemaResult, String> {
+    let versioned = [
+        versioned_schema::<CreateAgentParams>("create_agent"),
+        versioned_schema::<DeployAgentParams>("deploy_agent"),
+    ];
+    if let Some(unknown) = jobs
+        .iter()
+        .find(|job| !versioned.iter().any(|schema| &schema.job == *job))
+    {
+        return Err(format!("No versioned parameters for job {}", unknown));
+    }
+    Ok(ParamsSchemaResult {
+        blueprint_version: env!("CARGO_PKG_VERSION").to_string(),
+        schemas: versioned
+            .into_iter()
+            .filter(|schema| jobs.is_empty() || jobs.contains(&schema.job))
+            .collect(),
+    })
+}
+
+/// Handles the get_params_schema job
+///
+/// Empty parameters describe every job with versioned parameters.
+pub async fn handle_get_params_schema(params_bytes: Vec<u8>) -> Result<Vec<u8>, BlueprintError> {
+    let (params, encoding) = if params_bytes.is_empty() {
+        (ParamsSchemaParams::default(), codec::Encoding::Json)
+    } else {
+        codec::decode(&params_bytes).map_err(BlueprintError::Params)?
+    };
+    let result = params_schemas(&params.jobs).map_err(BlueprintError::Params)?;
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
+
+/// Returns the schema of the error payload failed jobs return
+pub fn error_schema() -> Schema {
+    schema_for!(BlueprintError)
+}
+
+/// Collects every schema into one document, keyed by job, with the error payload under `error`
+pub fn schema_document() -> Value {
+    let mut document = Map::new();
+    for schema in job_schemas() {
+        document.insert(
+            schema.job.to_string(),
+            serde_json::json!({ "params": schema.params, "result": schema.result }),
+        );
+    }
+    document.insert("error".to_string(), error_schema().to_value());
+    Value::Object(document)
+}
+
+/// Writes `<job>.params.json`, `<job>.result.json` and `error.json` into `dir`, returning
+/// how many files were written
+pub fn write_schemas(dir: &Path) -> Result<usize, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let mut files = vec![("error.json".to_string(), error_schema())];
+    for schema in job_schemas() {
+        files.push((format!("{}.params.json", schema.job), schema.params));
+        files.push((format!("{}.result.json", schema.job), schema.result));
+    }
+    for (name, schema) in &files {
+        let path = dir.join(name);
+        let content = serde_json::to_string_pretty(schema)
+            .map_err(|e| format!("Failed to serialize {}: {}", name, e))?;
+        fs::write(&path, content + "\n")
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(files.len())
+}
@@ -0,0 +1,261 @@
+use crate::types::Secret;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Prefix of the operator environment variables `env://` references may read
+pub const ENV_SECRET_PREFIX: &str = "AGENT_SECRET_";
+
+/// Reference to a secret held outside the blueprint, e.g. `vault://kv/agents/openai#key`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecretUri {
+    /// Backend the secret lives in, e.g. `vault`
+    pub scheme: String,
+    /// Location of the secret within the backend
+    pub path: String,
+    /// Field to read from a secret holding several values
+    pub field: Option<String>,
+}
+
+impl SecretUri {
+    /// Parses a secret reference, returning `None` for values that aren't one
+    pub fn parse(value: &str) -> Option<Self> {
+        let (scheme, rest) = value.split_once("://")?;
+        if scheme.is_empty()
+            || !scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '+')
+        {
+            return None;
+        }
+        let (path, field) = match rest.split_once('#') {
+            Some((path, field)) => (path, Some(field.to_string())),
+            None => (rest, None),
+        };
+        if path.is_empty() {
+            return None;
+        }
+        Some(Self {
+            scheme: scheme.to_lowercase(),
+            path: path.to_string(),
+            field,
+        })
+    }
+}
+
+/// Backend that resolves secret references into their values
+#[async_trait]
+pub trait SecretResolver: Send + Sync {
+    /// URI scheme of the references this resolver handles
+    fn scheme(&self) -> &'static str;
+
+    /// Fetches the value a reference points to
+    async fn resolve(&self, uri: &SecretUri) -> Result<String, String>;
+}
+
+/// Resolves `env://NAME` from the operator's `AGENT_SECRET_NAME` environment variable
+///
+/// Only prefixed variables are readable so that callers can't pull the operator's own
+/// credentials into their agents.
+#[derive(Clone, Debug, Default)]
+pub struct EnvSecretResolver;
+
+#[async_trait]
+impl SecretResolver for EnvSecretResolver {
+    fn scheme(&self) -> &'static str {
+        "env"
+    }
+
+    async fn resolve(&self, uri: &SecretUri) -> Result<String, String> {
+        let var = format!("{}{}", ENV_SECRET_PREFIX, uri.path);
+        std::env::var(&var).map_err(|_| format!("{} is not set", var))
+    }
+}
+
+/// Resolves `vault://<mount>/<path>#<field>` from a HashiCorp Vault KV v2 engine
+///
+/// The field defaults to `value`. The operator's token decides which secrets callers can
+/// reference, so it should only be allowed to read the agents' secrets.
+#[derive(Clone, Debug)]
+pub struct VaultSecretResolver {
+    addr: String,
+    token: String,
+    client: reqwest::Client,
+}
+
+impl VaultSecretResolver {
+    pub fn new(addr: impl Into<String>, token: impl Into<String>, client: reqwest::Client) -> Self {
+        Self {
+            addr: addr.into().trim_end_matches('/').to_string(),
+            token: token.into(),
+            client,
+        }
+    }
+
+    /// Creates a resolver from `VAULT_ADDR` and `VAULT_TOKEN`, if Vault is configured
+    pub fn from_env(client: reqwest::Client) -> Result<Option<Self>, String> {
+        let Ok(addr) = std::env::var("VAULT_ADDR") else {
+            return Ok(None);
+        };
+        let token = std::env::var("VAULT_TOKEN")
+            .map_err(|_| "VAULT_TOKEN is required when VAULT_ADDR is set".to_string())?;
+        Ok(Some(Self::new(addr, token, client)))
+    }
+}
+
+#[async_trait]
+impl SecretResolver for VaultSecretResolver {
+    fn scheme(&self) -> &'static str {
+        "vault"
+    }
+
+    async fn resolve(&self, uri: &SecretUri) -> Result<String, String> {
+        let (mount, path) = uri
+            .path
+            .split_once('/')
+            .ok_or_else(|| format!("Vault secret {} has no mount", uri.path))?;
+        let url = format!("{}/v1/{}/data/{}", self.addr, mount, path);
+
+        let response = self
+            .client
+            .get(&url)
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach Vault: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Vault returned {} for secret {}",
+                response.status(),
+                uri.path
+            ));
+        }
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid Vault response: {}", e))?;
+
+        let field = uri.field.as_deref().unwrap_or("value");
+        body.pointer(&format!("/data/data/{}", field))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| format!("Vault secret {} has no field {}", uri.path, field))
+    }
+}
+
+/// Resolves `aws-sm://<secret-id>#<field>` from AWS Secrets Manager
+///
+/// Without a field the whole secret string is used; with one, the secret is read as a JSON
+/// object. Credentials and region come from the standard AWS environment.
+#[cfg(feature = "aws")]
+#[derive(Clone, Debug)]
+pub struct AwsSecretResolver {
+    client: aws_sdk_secretsmanager::Client,
+}
+
+#[cfg(feature = "aws")]
+impl AwsSecretResolver {
+    /// Creates a resolver from the standard AWS configuration sources
+    pub async fn from_env() -> Self {
+        let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        Self {
+            client: aws_sdk_secretsmanager::Client::new(&config),
+        }
+    }
+}
+
+#[cfg(feature = "aws")]
+#[async_trait]
+impl SecretResolver for AwsSecretResolver {
+    fn scheme(&self) -> &'static str {
+        "aws-sm"
+    }
+
+    async fn resolve(&self, uri: &SecretUri) -> Result<String, String> {
+        let output = self
+            .client
+            .get_secret_value()
+            .secret_id(&uri.path)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch secret {}: {}", uri.path, e))?;
+        let secret = output
+            .secret_string()
+            .ok_or_else(|| format!("Secret {} has no string value", uri.path))?;
+
+        match &uri.field {
+            None => Ok(secret.to_string()),
+            Some(field) => serde_json::from_str::<Value>(secret)
+                .ok()
+                .and_then(|value| value.get(field)?.as_str().map(str::to_string))
+                .ok_or_else(|| format!("Secret {} has no field {}", uri.path, field)),
+        }
+    }
+}
+
+/// Secret resolvers available to the blueprint, keyed by URI scheme
+#[derive(Clone, Default)]
+pub struct SecretResolvers {
+    resolvers: HashMap<&'static str, Arc<dyn SecretResolver>>,
+}
+
+impl std::fmt::Debug for SecretResolvers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.resolvers.keys()).finish()
+    }
+}
+
+impl SecretResolvers {
+    /// Adds a resolver, replacing any registered for the same scheme
+    pub fn with(mut self, resolver: impl SecretResolver + 'static) -> Self {
+        self.resolvers.insert(resolver.scheme(), Arc::new(resolver));
+        self
+    }
+
+    /// Creates the resolvers configured in the operator's environment
+    ///
+    /// `env://` references are always resolved; Vault is added when `VAULT_ADDR` is set and
+    /// AWS Secrets Manager when built with the `aws` feature.
+    pub async fn from_env(client: reqwest::Client) -> Result<Self, String> {
+        let mut resolvers = Self::default().with(EnvSecretResolver);
+        if let Some(vault) = VaultSecretResolver::from_env(client)? {
+            resolvers = resolvers.with(vault);
+        }
+        #[cfg(feature = "aws")]
+        {
+            resolvers = resolvers.with(AwsSecretResolver::from_env().await);
+        }
+        Ok(resolvers)
+    }
+
+    /// Resolves a value that may be a secret reference; raw values are returned unchanged
+    pub async fn resolve(&self, value: &str) -> Result<String, String> {
+        let Some(uri) = SecretUri::parse(value) else {
+            return Ok(value.to_string());
+        };
+        match self.resolvers.get(uri.scheme.as_str()) {
+            Some(resolver) => resolver.resolve(&uri).await,
+            None => Err(format!(
+                "No secret resolver for {}:// references",
+                uri.scheme
+            )),
+        }
+    }
+
+    /// Resolves every secret reference among the given variables
+    pub async fn resolve_all(
+        &self,
+        secrets: Vec<(String, Secret<String>)>,
+    ) -> Result<Vec<(String, Secret<String>)>, String> {
+        let mut resolved = Vec::with_capacity(secrets.len());
+        for (var, value) in secrets {
+            let value = self
+                .resolve(value.expose())
+                .await
+                .map_err(|e| format!("Failed to resolve {}: {}", var, e))?;
+            resolved.push((var, Secret::new(value)));
+        }
+        Ok(resolved)
+    }
+}
@@ -0,0 +1,164 @@
+use crate::secrets::SEALED_SECRETS_FILE;
+use crate::wallet::{SEALED_WALLET_FILE, WALLET_DIR};
+use regex::Regex;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Files that hold the agent's secrets on purpose
+const IGNORED_FILES: &[&str] = &[".env", SEALED_SECRETS_FILE, SEALED_WALLET_FILE];
+
+/// Directories that are never part of the agent's own files, or hold its wallet on purpose
+const IGNORED_DIRS: &[&str] = &["node_modules", ".git", WALLET_DIR];
+
+/// Files larger than this are not scanned
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Values shorter than this are not looked for verbatim, to avoid matching common words
+const MIN_KNOWN_SECRET_LEN: usize = 12;
+
+/// Patterns of well-known credential formats
+const RULES: &[(&str, &str)] = &[
+    ("OpenAI API key", r"sk-(?:proj-)?[A-Za-z0-9_-]{20,}"),
+    ("Anthropic API key", r"sk-ant-[A-Za-z0-9_-]{20,}"),
+    ("AWS access key", r"AKIA[0-9A-Z]{16}"),
+    (
+        "CDP API key name",
+        r"organizations/[0-9a-f-]{36}/apiKeys/[0-9a-f-]{36}",
+    ),
+    (
+        "Private key",
+        r"-----BEGIN (?:EC |RSA |OPENSSH |ENCRYPTED )?PRIVATE KEY-----",
+    ),
+    ("Telegram bot token", r"\b[0-9]{8,10}:[A-Za-z0-9_-]{35}\b"),
+];
+
+fn rules() -> &'static [(&'static str, Regex)] {
+    static COMPILED: OnceLock<Vec<(&'static str, Regex)>> = OnceLock::new();
+    COMPILED.get_or_init(|| {
+        RULES
+            .iter()
+            .map(|(name, pattern)| (*name, Regex::new(pattern).expect("valid secret pattern")))
+            .collect()
+    })
+}
+
+/// Possible secret found in an agent's files
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SecretFinding {
+    /// Path relative to the agent's directory
+    pub path: PathBuf,
+    /// 1-based line number
+    pub line: usize,
+    /// Kind of secret, e.g. `OpenAI API key`
+    pub rule: String,
+}
+
+/// Secrets found in an agent's directory, reported without their values
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SecretScanReport {
+    pub findings: Vec<SecretFinding>,
+}
+
+impl SecretScanReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl fmt::Display for SecretScanReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Found {} possible secrets in the agent's files:",
+            self.findings.len()
+        )?;
+        for finding in &self.findings {
+            write!(
+                f,
+                "\n  {}:{}: {}",
+                finding.path.display(),
+                finding.line,
+                finding.rule
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Scans the files an agent image is built from for secrets outside its `.env`
+///
+/// Besides well-known credential formats, any of `known_secrets` (the values being
+/// deployed) appearing verbatim is reported.
+pub fn scan_agent_dir(
+    agent_dir: &Path,
+    known_secrets: &[&str],
+) -> Result<SecretScanReport, String> {
+    let known_secrets: Vec<&str> = known_secrets
+        .iter()
+        .copied()
+        .filter(|secret| secret.len() >= MIN_KNOWN_SECRET_LEN)
+        .collect();
+    let mut report = SecretScanReport::default();
+    scan_dir(agent_dir, agent_dir, &known_secrets, &mut report)?;
+    report
+        .findings
+        .sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+    Ok(report)
+}
+
+fn scan_dir(
+    root: &Path,
+    dir: &Path,
+    known_secrets: &[&str],
+    report: &mut SecretScanReport,
+) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let file_type = entry
+            .file_type()
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        if file_type.is_dir() {
+            if !IGNORED_DIRS.contains(&name.as_str()) {
+                scan_dir(root, &path, known_secrets, report)?;
+            }
+            continue;
+        }
+        let too_large = entry
+            .metadata()
+            .map_or(true, |metadata| metadata.len() > MAX_FILE_SIZE);
+        if !file_type.is_file() || IGNORED_FILES.contains(&name.as_str()) || too_large {
+            continue;
+        }
+        // Binary files are skipped
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+        for (index, line) in content.lines().enumerate() {
+            let mut found = |rule: &str| {
+                report.findings.push(SecretFinding {
+                    path: relative.clone(),
+                    line: index + 1,
+                    rule: rule.to_string(),
+                })
+            };
+            for (rule, pattern) in rules() {
+                if pattern.is_match(line) {
+                    found(rule);
+                }
+            }
+            if known_secrets.iter().any(|secret| line.contains(secret)) {
+                found("Deployment secret");
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use crate::helpers::write_private_file;
+use crate::types::Secret;
+use blueprint_sdk::config::GadgetConfiguration;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use sha3::{Digest, Keccak256};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use zeroize::{Zeroize, Zeroizing};
+
+/// File in an agent's directory holding its sealed secrets
+pub const SEALED_SECRETS_FILE: &str = "secrets.sealed";
+
+/// Domain separator for deriving the sealing key from the operator's key
+const SEALING_KEY_CONTEXT: &[u8] = b"coinbase-agent-kit-blueprint/secrets/v1";
+
+/// Length of the nonce prepended to every sealed value
+const NONCE_LEN: usize = 12;
+
+/// Symmetric key sealing the secrets an operator keeps at rest, wiped from memory when dropped
+#[derive(Clone)]
+pub struct SealingKey([u8; 32]);
+
+impl Drop for SealingKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for SealingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SealingKey(..)")
+    }
+}
+
+impl SealingKey {
+    /// Derives the sealing key from the operator's ECDSA key in the keystore
+    pub fn from_config(config: &GadgetConfiguration) -> Result<Self, String> {
+        let signer = config
+            .first_ecdsa_signer()
+            .map_err(|e| format!("Failed to get operator ECDSA key: {}", e))?;
+
+        let seed = Zeroizing::new(signer.signer().seed());
+        let mut hasher = Keccak256::new();
+        hasher.update(SEALING_KEY_CONTEXT);
+        hasher.update(seed.as_slice());
+        Ok(Self(hasher.finalize().into()))
+    }
+
+    /// Creates a sealing key from raw bytes
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.0))
+    }
+
+    /// Encrypts and authenticates `plaintext`, returning the nonce followed by the ciphertext
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| "Failed to seal secret".to_string())?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypts a value sealed with [`SealingKey::seal`]
+    pub fn unseal(&self, sealed: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+        if sealed.len() < NONCE_LEN {
+            return Err("Sealed secret is truncated".to_string());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher()
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map(Zeroizing::new)
+            .map_err(|_| "Failed to unseal secret: wrong key or corrupted data".to_string())
+    }
+}
+
+/// Seals an agent's secrets into its directory, replacing any sealed before
+pub fn write_sealed_secrets(
+    agent_dir: &Path,
+    key: &SealingKey,
+    secrets: &HashMap<String, Secret<String>>,
+) -> Result<(), String> {
+    let plaintext = Zeroizing::new(
+        serde_json::to_vec(secrets).map_err(|e| format!("Failed to serialize secrets: {}", e))?,
+    );
+    let sealed = key.seal(&plaintext)?;
+    write_private_file(&agent_dir.join(SEALED_SECRETS_FILE), hex::encode(sealed))
+}
+
+/// Unseals an agent's secrets, returning an empty map if it has none
+pub fn read_sealed_secrets(
+    agent_dir: &Path,
+    key: &SealingKey,
+) -> Result<HashMap<String, Secret<String>>, String> {
+    let path = agent_dir.join(SEALED_SECRETS_FILE);
+    let encoded = match fs::read_to_string(&path) {
+        Ok(encoded) => encoded,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    let sealed = hex::decode(encoded.trim())
+        .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))?;
+    let plaintext = key.unseal(&sealed)?;
+    serde_json::from_slice(&plaintext).map_err(|e| format!("Failed to parse secrets: {}", e))
+}
@@ -0,0 +1,245 @@
+use crate::ServiceContext;
+use blueprint_sdk::crypto::sp_core::{ed25519, sr25519, Pair};
+use blueprint_sdk::tangle_subxt::subxt::utils::AccountId32;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// File under the agents' directory recording the nonces of caller signatures already used
+pub const NONCES_FILE: &str = ".caller_nonces.json";
+
+/// Longest a caller signature may stay valid, in seconds
+///
+/// Bounds how long used nonces have to be remembered.
+pub const MAX_SIGNATURE_TTL: u64 = 3600;
+
+/// Operator signature over a job result
+///
+/// To verify, clear the result's `operator_signature`, serialize it to JSON and check
+/// `signature` against the keccak-256 hash of those bytes and `public_key`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct OperatorSignature {
+    /// Hex-encoded compressed ECDSA public key of the operator
+    pub public_key: String,
+    /// Hex-encoded 65-byte recoverable ECDSA signature
+    pub signature: String,
+}
+
+/// Job results that can carry an operator signature
+pub trait SignedResult: Serialize {
+    fn operator_signature_mut(&mut self) -> &mut Option<OperatorSignature>;
+}
+
+/// Returns the keccak-256 hash of a result serialized without its signature
+pub fn result_digest<T: SignedResult + Clone>(result: &T) -> Result<[u8; 32], String> {
+    let mut unsigned = result.clone();
+    *unsigned.operator_signature_mut() = None;
+    let bytes =
+        serde_json::to_vec(&unsigned).map_err(|e| format!("Failed to serialize result: {}", e))?;
+    Ok(Keccak256::digest(&bytes).into())
+}
+
+/// Signs a result with the operator's ECDSA key if the operator enabled result signing
+pub fn sign_result<T: SignedResult + Clone>(
+    context: &ServiceContext,
+    result: &mut T,
+) -> Result<(), String> {
+    if !context.sign_results.unwrap_or(false) {
+        return Ok(());
+    }
+
+    let digest = result_digest(result)?;
+    let signer = context
+        .config
+        .first_ecdsa_signer()
+        .map_err(|e| format!("Failed to get operator ECDSA key: {}", e))?;
+    let pair = signer.signer();
+
+    *result.operator_signature_mut() = Some(OperatorSignature {
+        public_key: hex::encode(pair.public().0),
+        signature: hex::encode(pair.sign_prehashed(&digest).0),
+    });
+    Ok(())
+}
+
+/// Key type of the account that signed a job's parameters
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignatureScheme {
+    Sr25519,
+    Ed25519,
+}
+
+/// Caller's signature over the payload of a parameters envelope
+///
+/// The signed message is [`signing_message`]: the payload together with the service ID,
+/// `nonce` and `expires_at`, so that a signature is only accepted once, by one service and
+/// until it expires.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallerSignature {
+    pub scheme: SignatureScheme,
+    /// Hex-encoded 64-byte signature
+    pub signature: String,
+    /// Number the caller never signs twice
+    pub nonce: u64,
+    /// Unix time in seconds after which the signature is refused, at most
+    /// [`MAX_SIGNATURE_TTL`] ahead
+    pub expires_at: u64,
+}
+
+/// Returns the message a caller signs: a JSON object of the `expires_at`, `nonce`,
+/// `payload` and `service_id`, serialized compactly with object keys sorted
+///
+/// `service_id` is null when the operator has no service ID configured.
+pub fn signing_message(
+    service_id: Option<u64>,
+    payload: &Value,
+    nonce: u64,
+    expires_at: u64,
+) -> Result<Vec<u8>, String> {
+    let message = json!({
+        "expires_at": expires_at,
+        "nonce": nonce,
+        "payload": payload,
+        "service_id": service_id,
+    });
+    serde_json::to_vec(&message).map_err(|e| format!("Failed to serialize parameters: {}", e))
+}
+
+/// Returns the ID of the service this operator runs, if configured
+pub fn service_id(context: &ServiceContext) -> Option<u64> {
+    context
+        .config
+        .protocol_settings
+        .tangle()
+        .ok()
+        .and_then(|settings| settings.service_id)
+}
+
+/// Nonces of the caller signatures already accepted, with their expiry (shared across threads)
+///
+/// Persisted to [`NONCES_FILE`] so that signed parameters can't be replayed after a restart.
+/// Nonces are forgotten once their signature expires.
+#[derive(Clone, Debug, Default)]
+pub struct NonceRegistry {
+    used: Arc<Mutex<HashMap<u64, u64>>>,
+    state_path: Option<PathBuf>,
+}
+
+impl NonceRegistry {
+    /// Creates a registry persisted at `state_path`, restoring the nonces recorded there
+    pub fn load(state_path: PathBuf) -> Result<Self, String> {
+        let used = match fs::read_to_string(&state_path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid caller nonces: {}", e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(format!("Failed to read caller nonces: {}", e)),
+        };
+        Ok(Self {
+            used: Arc::new(Mutex::new(used)),
+            state_path: Some(state_path),
+        })
+    }
+
+    /// Records `nonce` as used until `expires_at`, failing if it already was
+    pub fn claim(&self, nonce: u64, expires_at: u64, now: u64) -> Result<(), String> {
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        used.retain(|_, expiry| *expiry >= now);
+        if used.contains_key(&nonce) {
+            return Err(format!("Caller signature nonce {} was already used", nonce));
+        }
+        used.insert(nonce, expires_at);
+
+        let Some(path) = &self.state_path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let content = serde_json::to_string(&*used)
+            .map_err(|e| format!("Failed to serialize caller nonces: {}", e))?;
+        fs::write(path, content).map_err(|e| format!("Failed to write caller nonces: {}", e))
+    }
+}
+
+/// Checks that a job's payload was signed by the service owner, for this service, and that
+/// the signature is neither expired nor replayed
+///
+/// Unsigned parameters are accepted unless the operator requires caller signatures, but a
+/// signature that is present must always be valid.
+pub fn verify_caller_signature(
+    context: &ServiceContext,
+    payload: &Value,
+    signature: Option<&CallerSignature>,
+) -> Result<(), String> {
+    let Some(signature) = signature else {
+        if context.require_caller_signature.unwrap_or(false) {
+            return Err("Job parameters must be signed by the service owner".to_string());
+        }
+        return Ok(());
+    };
+
+    let owner = context
+        .service_owner
+        .as_ref()
+        .ok_or("No service owner configured to verify the caller signature against")?;
+    let owner = AccountId32::from_str(owner)
+        .map_err(|e| format!("Invalid service owner account {}: {}", owner, e))?;
+    let bytes: [u8; 64] = hex::decode(signature.signature.trim_start_matches("0x"))
+        .map_err(|e| format!("Invalid caller signature: {}", e))?
+        .try_into()
+        .map_err(|_| "Invalid caller signature: expected 64 bytes".to_string())?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    if signature.expires_at < now {
+        return Err("Caller signature has expired".to_string());
+    }
+    if signature.expires_at - now > MAX_SIGNATURE_TTL {
+        return Err(format!(
+            "Caller signature expires more than {} seconds ahead",
+            MAX_SIGNATURE_TTL
+        ));
+    }
+    let nonces = context
+        .caller_nonces
+        .as_ref()
+        .ok_or("No nonce registry configured to check the caller signature against")?;
+
+    let message = signing_message(
+        service_id(context),
+        payload,
+        signature.nonce,
+        signature.expires_at,
+    )?;
+    let message = message.as_slice();
+    let valid = match signature.scheme {
+        SignatureScheme::Sr25519 => sr25519::Pair::verify(
+            &sr25519::Signature::from_raw(bytes),
+            message,
+            &sr25519::Public::from_raw(owner.0),
+        ),
+        SignatureScheme::Ed25519 => ed25519::Pair::verify(
+            &ed25519::Signature::from_raw(bytes),
+            message,
+            &ed25519::Public::from_raw(owner.0),
+        ),
+    };
+    if !valid {
+        return Err("Caller signature does not match the service owner".to_string());
+    }
+    nonces.claim(signature.nonce, signature.expires_at, now)
+}
@@ -0,0 +1,330 @@
+use crate::agent_id::{container_name, AgentId};
+use crate::deploy_agent::get_required_ports;
+use crate::error::BlueprintError;
+use crate::helpers::{check_container_status, fan_out, run_blocking};
+use crate::monitor::MonitorState;
+use crate::readiness::{check_readiness, ReadinessReport};
+use crate::ServiceContext;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, Router};
+use blueprint_sdk::logging;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tokio::process::Command as TokioCommand;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+/// Log lines returned when the request doesn't ask for a number
+const DEFAULT_LOG_TAIL: usize = 100;
+
+/// Most log lines returned by one request
+const MAX_LOG_TAIL: usize = 5000;
+
+/// Read-only HTTP API for operators to inspect their node
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatusApiConfig {
+    /// Address the API listens on, e.g. `127.0.0.1:9090`
+    pub bind: SocketAddr,
+}
+
+impl StatusApiConfig {
+    /// Loads the listen address from `AGENT_STATUS_API_ADDR`, if set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(addr) = std::env::var("AGENT_STATUS_API_ADDR")
+            .ok()
+            .filter(|addr| !addr.trim().is_empty())
+        else {
+            return Ok(None);
+        };
+        let bind = addr
+            .trim()
+            .parse()
+            .map_err(|e| format!("Invalid AGENT_STATUS_API_ADDR {}: {}", addr, e))?;
+        Ok(Some(Self { bind }))
+    }
+}
+
+/// State of one agent as reported by the status API
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentOverview {
+    pub agent_id: String,
+    pub http_port: Option<u16>,
+    pub websocket_port: Option<u16>,
+    /// Whether the agent's container is running
+    pub running: bool,
+    /// Health according to the health monitor, if the agent is supervised
+    pub health: Option<MonitorState>,
+}
+
+/// Most recent log lines of an agent's container
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentLogs {
+    pub agent_id: String,
+    /// Lines from stdout and stderr, oldest first, each prefixed with its timestamp
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LogsQuery {
+    tail: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    agent_id: Option<String>,
+}
+
+type ApiError = (StatusCode, String);
+
+/// Builds the routes of the status API
+///
+/// Besides the agents, it serves `/healthz` and `/readyz` probes of the blueprint itself and
+/// the heartbeat's QoS metrics at `/metrics`.
+pub fn router(context: ServiceContext) -> Router {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/agents", get(list_agents))
+        .route("/agents/:id", get(get_agent))
+        .route("/agents/:id/logs", get(get_agent_logs))
+        .route("/events", get(stream_events))
+        .route("/metrics", get(metrics))
+        .with_state(context)
+}
+
+/// Serves the status API until the process exits
+pub async fn serve(context: ServiceContext, config: StatusApiConfig) -> Result<(), String> {
+    let listener = tokio::net::TcpListener::bind(config.bind)
+        .await
+        .map_err(|e| format!("Failed to bind status API to {}: {}", config.bind, e))?;
+    logging::info!("Status API listening on {}", config.bind);
+    axum::serve(listener, router(context))
+        .await
+        .map_err(|e| format!("Status API failed: {}", e))
+}
+
+fn agents_dir(context: &ServiceContext) -> PathBuf {
+    let base_dir = match context.agents_base_dir() {
+        Some(dir) => dir,
+        None => "./agents".to_string(),
+    };
+    PathBuf::from(base_dir)
+}
+
+/// Returns the IDs of all agents created on this node, sorted
+///
+/// Agents are the directories with an `.env` file; templates and the blueprint's own
+/// state (journal, audit log, ingress) are skipped.
+pub(crate) fn agent_ids(context: &ServiceContext) -> Result<Vec<String>, String> {
+    let dir = agents_dir(context);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+    };
+    let mut agent_ids: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().join(".env").is_file())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    agent_ids.sort();
+    Ok(agent_ids)
+}
+
+/// Whether `agent_id` is a well-formed ID of an existing agent
+pub(crate) fn is_known_agent(context: &ServiceContext, agent_id: &str) -> bool {
+    AgentId::parse(agent_id)
+        .is_ok_and(|agent_id| agent_id.dir(agents_dir(context)).join(".env").is_file())
+}
+
+/// Checks that `agent_id` is a well-formed ID of an existing agent
+fn find_agent(context: &ServiceContext, agent_id: &str) -> Result<(), ApiError> {
+    if !is_known_agent(context, agent_id) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Unknown agent: {}", agent_id),
+        ));
+    }
+    Ok(())
+}
+
+fn overview(context: &ServiceContext, agent_id: &str) -> AgentOverview {
+    let (http_port, websocket_port) = match get_required_ports(agent_id, context) {
+        Ok((http_port, websocket_port)) => (Some(http_port), websocket_port),
+        Err(_) => (None, None),
+    };
+    let running = check_container_status(&container_name(agent_id)).unwrap_or_else(|e| {
+        logging::debug!(agent_id = %agent_id, error = %e, "Failed to check container");
+        false
+    });
+    AgentOverview {
+        agent_id: agent_id.to_string(),
+        http_port,
+        websocket_port,
+        running,
+        health: context
+            .health_monitor
+            .as_ref()
+            .and_then(|monitor| monitor.state(agent_id)),
+    }
+}
+
+/// Answers as long as the process is serving requests
+async fn healthz() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Answers `503 Service Unavailable` while the blueprint can't serve jobs
+async fn readyz(State(context): State<ServiceContext>) -> (StatusCode, Json<ReadinessReport>) {
+    let report = check_readiness(&context).await;
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+/// Serves the latest heartbeat as Prometheus metrics, empty until one was collected
+async fn metrics(State(context): State<ServiceContext>) -> impl IntoResponse {
+    let body = context
+        .heartbeat_metrics
+        .as_ref()
+        .and_then(|metrics| metrics.latest())
+        .map(|heartbeat| heartbeat.to_metrics())
+        .unwrap_or_default();
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4",
+        )],
+        body,
+    )
+}
+
+async fn list_agents(
+    State(context): State<ServiceContext>,
+) -> Result<Json<Vec<AgentOverview>>, ApiError> {
+    let agent_ids = agent_ids(&context).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let limit = context.http_config().health_concurrency;
+    let overviews = fan_out(agent_ids, limit, |agent_id| {
+        let context = context.clone();
+        run_blocking(move || Ok(overview(&context, &agent_id)))
+    })
+    .await;
+    overviews
+        .into_iter()
+        .map(|overview| match overview {
+            Some(Ok(overview)) => Ok(overview),
+            Some(Err(e)) => Err((StatusCode::INTERNAL_SERVER_ERROR, e)),
+            None => Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Agent status task failed".to_string(),
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Json)
+}
+
+async fn get_agent(
+    State(context): State<ServiceContext>,
+    Path(agent_id): Path<String>,
+) -> Result<Json<AgentOverview>, ApiError> {
+    find_agent(&context, &agent_id)?;
+    Ok(Json(overview(&context, &agent_id)))
+}
+
+async fn get_agent_logs(
+    State(context): State<ServiceContext>,
+    Path(agent_id): Path<String>,
+    Query(query): Query<LogsQuery>,
+) -> Result<Json<AgentLogs>, ApiError> {
+    find_agent(&context, &agent_id)?;
+    agent_logs(&agent_id, query.tail)
+        .await
+        .map(Json)
+        .map_err(|e| match e {
+            BlueprintError::Docker(message) => (StatusCode::NOT_FOUND, message),
+            e => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        })
+}
+
+/// Streams agent events as Server-Sent Events, optionally only those of one agent
+///
+/// Each event is named after its kind (e.g. `deployed`) and carries the event as JSON. A
+/// client too slow to keep up gets a `lagged` event with the number of events it missed.
+async fn stream_events(
+    State(context): State<ServiceContext>,
+    Query(query): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let events = context.events.as_ref().ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Agent events are not enabled".to_string(),
+    ))?;
+    let stream = BroadcastStream::new(events.subscribe()).filter_map(move |event| {
+        let event = match event {
+            Ok(event) => event,
+            Err(BroadcastStreamRecvError::Lagged(missed)) => {
+                return Some(Ok(Event::default()
+                    .event("lagged")
+                    .data(missed.to_string())))
+            }
+        };
+        if query
+            .agent_id
+            .as_deref()
+            .is_some_and(|agent_id| agent_id != event.agent_id())
+        {
+            return None;
+        }
+        Event::default()
+            .event(event.name())
+            .json_data(&event)
+            .ok()
+            .map(Ok)
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Returns the most recent lines of an agent's container logs, [`DEFAULT_LOG_TAIL`] unless
+/// `tail` says otherwise
+pub async fn agent_logs(agent_id: &str, tail: Option<usize>) -> Result<AgentLogs, BlueprintError> {
+    let tail = tail.unwrap_or(DEFAULT_LOG_TAIL).min(MAX_LOG_TAIL);
+    let container_name = container_name(agent_id);
+    let output = TokioCommand::new("docker")
+        .args(["logs", "--timestamps", "--tail", &tail.to_string()])
+        .arg(&container_name)
+        .output()
+        .await
+        .map_err(|e| BlueprintError::Internal(format!("Failed to get container logs: {}", e)))?;
+    if !output.status.success() {
+        return Err(BlueprintError::Docker(format!(
+            "Failed to get container logs: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    // Docker splits the container's output by stream; timestamps restore the order
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let mut lines: Vec<String> = stdout
+        .lines()
+        .chain(stderr.lines())
+        .map(str::to_string)
+        .collect();
+    lines.sort();
+    let skip = lines.len().saturating_sub(tail);
+    Ok(AgentLogs {
+        agent_id: agent_id.to_string(),
+        lines: lines.split_off(skip),
+    })
+}
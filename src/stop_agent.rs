@@ -0,0 +1,96 @@
+use crate::codec;
+use crate::error::BlueprintError;
+use crate::events::{self, AgentEvent};
+use crate::helpers::{check_container_status, read_env_file, run_blocking};
+use crate::journal;
+use crate::ownership::authorize;
+use crate::types::{AgentStopResult, StopAgentParams};
+use crate::wallet;
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use tokio::process::Command as TokioCommand;
+
+/// Handles a request to stop an agent's container
+///
+/// The container is kept, so deploying the agent again starts it with its state. The wallet
+/// data the agent wrote is sealed and its plaintext removed. Only the agent's owner and the
+/// service owner may stop it.
+pub async fn handle_stop_agent(
+    params_bytes: Vec<u8>,
+    context: &ServiceContext,
+) -> Result<Vec<u8>, BlueprintError> {
+    // Deserialize the parameters from bytes
+    let (params, encoding): (StopAgentParams, _) =
+        codec::decode(&params_bytes).map_err(BlueprintError::Params)?;
+
+    // Define base directory from context or environment
+    let base_dir = match context.agents_base_dir() {
+        Some(dir) => dir,
+        None => "./agents".to_string(),
+    };
+
+    let agent_dir = params.agent_id.dir(&base_dir);
+    if !agent_dir.exists() {
+        return Err(BlueprintError::Params(format!(
+            "Unknown agent: {}",
+            params.agent_id
+        )));
+    }
+    let agent_env = read_env_file(&agent_dir.join(".env")).map_err(BlueprintError::Io)?;
+    authorize(context, &params.agent_id, &agent_env).map_err(BlueprintError::Unauthorized)?;
+
+    // Stop supervising first, so the monitor doesn't restart the container
+    if let Some(monitor) = &context.health_monitor {
+        monitor.unwatch(&params.agent_id);
+    }
+
+    let container_name = params.agent_id.container_name();
+    let was_running = {
+        let container_name = container_name.clone();
+        run_blocking(move || check_container_status(&container_name)).await
+    }
+    .unwrap_or(false);
+    if was_running {
+        logging::info!(agent_id = %params.agent_id, phase = "stop", "Stopping Docker container");
+        let output = TokioCommand::new("docker")
+            .args(["stop", &container_name])
+            .output()
+            .await
+            .map_err(|e| {
+                BlueprintError::Docker(format!("Failed to execute docker stop command: {}", e))
+            })?;
+        if !output.status.success() {
+            return Err(BlueprintError::Docker(format!(
+                "Failed to stop Docker container: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+        events::publish(
+            context,
+            AgentEvent::Stopped {
+                agent_id: params.agent_id.clone(),
+                call_id: context.call_id,
+                detail: "Stopped on request".to_string(),
+            },
+        );
+    }
+    journal::record(context, "stop_agent", &params.agent_id, "stopped");
+
+    // Seal the wallet data of the stopped agent and drop its plaintext; deploying the agent
+    // again restores it
+    if let Some(key) = &context.sealing_key {
+        let (key, dir) = (key.clone(), agent_dir.clone());
+        run_blocking(move || wallet::capture(&dir, &key, true))
+            .await
+            .map_err(BlueprintError::Secrets)?;
+    }
+
+    // Prepare the result
+    let result = AgentStopResult {
+        agent_id: params.agent_id,
+        was_running,
+    };
+
+    // Serialize the result
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
@@ -0,0 +1,701 @@
+use crate::agent_id::AgentId;
+use crate::events::AgentEvent;
+use crate::ports::PORTS_FILE;
+use crate::retention::DIAGNOSTICS_DIR;
+use crate::types::Secret;
+use crate::{AgentPortConfig, ServiceContext};
+use async_trait::async_trait;
+use blueprint_sdk::logging;
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+/// Extension of sealed agent backups
+pub const BACKUP_EXTENSION: &str = ".tar.gz.sealed";
+
+/// Store keeping agent backups and diagnostics bundles off the operator's host
+///
+/// Keys are `/`-separated paths such as `agents/<id>/backups/<timestamp>.tar.gz.sealed`.
+#[async_trait]
+pub trait ArtifactStore: Send + Sync {
+    /// Where the artifacts go, for logs
+    fn location(&self) -> String;
+
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), String>;
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, String>;
+
+    /// Returns the keys starting with `prefix`, sorted
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, String>;
+}
+
+/// Creates the artifact store configured in `AGENT_ARTIFACT_STORE`
+///
+/// `s3://<bucket>/<prefix>` stores artifacts in S3, or in an S3-compatible service such as
+/// MinIO when `AGENT_S3_ENDPOINT` is set; `file://<dir>` stores them in a directory, e.g. a
+/// mounted network volume. Returns `None` when unset.
+pub fn from_env(client: reqwest::Client) -> Result<Option<Arc<dyn ArtifactStore>>, String> {
+    let Some(url) = std::env::var("AGENT_ARTIFACT_STORE")
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+    else {
+        return Ok(None);
+    };
+    let url = url.trim();
+    if let Some(dir) = url.strip_prefix("file://") {
+        return Ok(Some(Arc::new(LocalStore::new(dir))));
+    }
+    let Some(location) = url.strip_prefix("s3://") else {
+        return Err(format!(
+            "Invalid AGENT_ARTIFACT_STORE {}: expected s3://<bucket>/<prefix> or file://<dir>",
+            url
+        ));
+    };
+    let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+    if bucket.is_empty() {
+        return Err(format!(
+            "Invalid AGENT_ARTIFACT_STORE {}: missing bucket",
+            url
+        ));
+    }
+    Ok(Some(Arc::new(S3Store::from_env(client, bucket, prefix)?)))
+}
+
+/// Artifact store in a local directory
+#[derive(Clone, Debug)]
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, String> {
+        let relative = Path::new(key);
+        if key.is_empty()
+            || !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(format!("Invalid artifact key: {}", key));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl ArtifactStore for LocalStore {
+    fn location(&self) -> String {
+        self.root.display().to_string()
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), String> {
+        let path = self.path(key)?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        tokio::fs::write(&path, data)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, String> {
+        let path = self.path(key)?;
+        tokio::fs::read(&path)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
+        let mut keys = Vec::new();
+        let mut pending = vec![self.root.clone()];
+        while let Some(dir) = pending.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+            };
+            for entry in entries.filter_map(Result::ok) {
+                let path = entry.path();
+                let Ok(relative) = path.strip_prefix(&self.root) else {
+                    continue;
+                };
+                let key = relative.to_string_lossy().replace('\\', "/");
+                if path.is_dir() {
+                    // Only descend where keys with the prefix may be
+                    if prefix.starts_with(&format!("{}/", key)) || key.starts_with(prefix) {
+                        pending.push(path);
+                    }
+                } else if key.starts_with(prefix) {
+                    keys.push(key);
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// Artifact store in an S3 bucket, signing requests with AWS Signature Version 4
+///
+/// Objects are addressed path-style (`<endpoint>/<bucket>/<key>`), which S3-compatible
+/// services such as MinIO expect.
+#[derive(Clone)]
+pub struct S3Store {
+    client: reqwest::Client,
+    endpoint: url::Url,
+    bucket: String,
+    prefix: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: Secret<String>,
+    session_token: Option<Secret<String>>,
+}
+
+impl S3Store {
+    /// Creates a store from the standard AWS environment variables
+    ///
+    /// Credentials come from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and, for temporary
+    /// ones, `AWS_SESSION_TOKEN`; the region from `AWS_REGION` (default `us-east-1`).
+    pub fn from_env(client: reqwest::Client, bucket: &str, prefix: &str) -> Result<Self, String> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+        let region = var("AWS_REGION")
+            .or_else(|| var("AWS_DEFAULT_REGION"))
+            .unwrap_or_else(|| "us-east-1".to_string());
+        let endpoint = var("AGENT_S3_ENDPOINT")
+            .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", region));
+        let endpoint = url::Url::parse(&endpoint)
+            .map_err(|e| format!("Invalid AGENT_S3_ENDPOINT {}: {}", endpoint, e))?;
+        let access_key_id = var("AWS_ACCESS_KEY_ID")
+            .ok_or("AWS_ACCESS_KEY_ID must be set for an s3:// artifact store")?;
+        let secret_access_key = var("AWS_SECRET_ACCESS_KEY")
+            .ok_or("AWS_SECRET_ACCESS_KEY must be set for an s3:// artifact store")?;
+        Ok(Self {
+            client,
+            endpoint,
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+            region,
+            access_key_id,
+            secret_access_key: Secret::new(secret_access_key),
+            session_token: var("AWS_SESSION_TOKEN").map(Secret::new),
+        })
+    }
+
+    /// Creates a store with explicit credentials, e.g. for a MinIO instance
+    pub fn new(
+        client: reqwest::Client,
+        endpoint: url::Url,
+        bucket: &str,
+        region: &str,
+        access_key_id: &str,
+        secret_access_key: &str,
+    ) -> Self {
+        Self {
+            client,
+            endpoint,
+            bucket: bucket.to_string(),
+            prefix: String::new(),
+            region: region.to_string(),
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: Secret::new(secret_access_key.to_string()),
+            session_token: None,
+        }
+    }
+
+    fn object_key(&self, key: &str) -> String {
+        if self.prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}/{}", self.prefix, key)
+        }
+    }
+
+    /// Sends a signed request for `key` (or for the bucket itself when `None`)
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        key: Option<&str>,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response, String> {
+        let path = match key {
+            Some(key) => format!(
+                "/{}/{}",
+                uri_encode(&self.bucket, true),
+                uri_encode(key, false)
+            ),
+            None => format!("/{}", uri_encode(&self.bucket, true)),
+        };
+        let mut query: Vec<(String, String)> = query
+            .iter()
+            .map(|(name, value)| (uri_encode(name, true), uri_encode(value, true)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let mut url = self.endpoint.clone();
+        url.set_path(&format!(
+            "{}{}",
+            self.endpoint.path().trim_end_matches('/'),
+            path
+        ));
+        url.set_query((!query.is_empty()).then_some(query.as_str()));
+
+        let payload_hash = hex::encode(Sha256::digest(&body));
+        let headers = self.sign(method.as_str(), &url, &query, &payload_hash, Utc::now());
+        let mut request = self.client.request(method, url.clone()).body(body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach {}: {}", self.endpoint, e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!(
+                "S3 returned {} for {}: {}",
+                status,
+                url.path(),
+                body
+            ));
+        }
+        Ok(response)
+    }
+
+    /// Returns the headers authenticating a request
+    fn sign(
+        &self,
+        method: &str,
+        url: &url::Url,
+        canonical_query: &str,
+        payload_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Vec<(&'static str, String)> {
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.to_string()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.expose().clone()));
+        }
+        let mut canonical_headers = String::new();
+        for (name, value) in &headers {
+            let _ = writeln!(canonical_headers, "{}:{}", name, value.trim());
+        }
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method,
+            url.path(),
+            canonical_query,
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+        let key = signing_key(self.secret_access_key.expose(), &date, &self.region, "s3");
+        let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        // reqwest sets the host header itself
+        headers.retain(|(name, _)| *name != "host");
+        headers.push((
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                self.access_key_id, scope, signed_headers, signature
+            ),
+        ));
+        headers
+    }
+}
+
+#[async_trait]
+impl ArtifactStore for S3Store {
+    fn location(&self) -> String {
+        format!("s3://{}/{}", self.bucket, self.prefix)
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), String> {
+        self.send(reqwest::Method::PUT, Some(&self.object_key(key)), &[], data)
+            .await
+            .map(|_| ())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, String> {
+        let response = self
+            .send(
+                reqwest::Method::GET,
+                Some(&self.object_key(key)),
+                &[],
+                Vec::new(),
+            )
+            .await?;
+        response
+            .bytes()
+            .await
+            .map(|bytes| bytes.to_vec())
+            .map_err(|e| format!("Failed to download {}: {}", key, e))
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, String> {
+        let key_pattern = regex::Regex::new("<Key>([^<]*)</Key>").expect("valid regex");
+        let token_pattern =
+            regex::Regex::new("<NextContinuationToken>([^<]*)</NextContinuationToken>")
+                .expect("valid regex");
+        let object_prefix = self.object_key(prefix);
+        let strip = if self.prefix.is_empty() {
+            0
+        } else {
+            self.prefix.len() + 1
+        };
+
+        let mut keys = Vec::new();
+        let mut continuation: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", object_prefix.as_str())];
+            if let Some(token) = &continuation {
+                query.push(("continuation-token", token.as_str()));
+            }
+            let response = self
+                .send(reqwest::Method::GET, None, &query, Vec::new())
+                .await?;
+            let body = response
+                .text()
+                .await
+                .map_err(|e| format!("Failed to list {}: {}", prefix, e))?;
+            keys.extend(key_pattern.captures_iter(&body).filter_map(|captures| {
+                xml_unescape(&captures[1]).get(strip..).map(str::to_string)
+            }));
+            continuation = token_pattern
+                .captures(&body)
+                .map(|captures| xml_unescape(&captures[1]));
+            if continuation.is_none() {
+                break;
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Derives the Signature Version 4 key of a day, region and service
+pub fn signing_key(secret_access_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac_sha256(
+        format!("AWS4{}", secret_access_key).as_bytes(),
+        date.as_bytes(),
+    );
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    hmac_sha256(&key, b"aws4_request")
+}
+
+/// Percent-encodes everything but unreserved characters and, unless `encode_slash`, `/`
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn agents_dir(context: &ServiceContext) -> PathBuf {
+    PathBuf::from(
+        context
+            .agents_base_dir()
+            .unwrap_or_else(|| "./agents".to_string()),
+    )
+}
+
+fn backups_prefix(agent_id: &str) -> String {
+    format!("agents/{}/backups/", agent_id)
+}
+
+/// Key under which a diagnostics bundle of an agent is stored
+pub fn diagnostics_key(agent_id: &str, now: DateTime<Utc>) -> String {
+    format!(
+        "agents/{}/diagnostics/{}.tar.gz",
+        agent_id,
+        now.format("%Y%m%dT%H%M%SZ")
+    )
+}
+
+/// Packs an agent's directory and ports into a tarball, sealed with the operator's key
+///
+/// Dependencies and diagnostics are left out; file modes are kept.
+pub fn build_backup(context: &ServiceContext, agent_id: &str) -> Result<Vec<u8>, String> {
+    let sealing_key = context
+        .sealing_key
+        .as_ref()
+        .ok_or("Agent backups need the operator's sealing key")?;
+    let agent_dir = agents_dir(context).join(agent_id);
+    if !agent_dir.is_dir() {
+        return Err(format!("Unknown agent: {}", agent_id));
+    }
+
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    builder.follow_symlinks(false);
+    let mut pending = vec![agent_dir.clone()];
+    while let Some(dir) = pending.pop() {
+        let entries =
+            fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            let name = entry.file_name();
+            if name == "node_modules" || (dir == agent_dir && name == DIAGNOSTICS_DIR) {
+                continue;
+            }
+            let relative = path.strip_prefix(&agent_dir).unwrap_or(&path);
+            if path.is_dir() && !path.is_symlink() {
+                pending.push(path.clone());
+                continue;
+            }
+            builder
+                .append_path_with_name(&path, relative)
+                .map_err(|e| format!("Failed to back up {}: {}", path.display(), e))?;
+        }
+    }
+
+    // The agent's compose file and environment refer to its ports, so they are restored too
+    if let Some(ports) = context
+        .agent_registry
+        .as_ref()
+        .and_then(|registry| registry.get(agent_id))
+    {
+        let ports =
+            serde_json::to_vec(&ports).map_err(|e| format!("Failed to serialize ports: {}", e))?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(ports.len() as u64);
+        header.set_mode(0o600);
+        builder
+            .append_data(&mut header, PORTS_FILE, ports.as_slice())
+            .map_err(|e| format!("Failed to back up ports: {}", e))?;
+    }
+
+    let archive = builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .map_err(|e| format!("Failed to write backup: {}", e))?;
+    sealing_key.seal(&archive)
+}
+
+/// Backs an agent up to the artifact store, returning the backup's key
+pub async fn backup_agent(context: &ServiceContext, agent_id: &str) -> Result<String, String> {
+    let store = context
+        .artifact_store
+        .clone()
+        .ok_or("No artifact store is configured")?;
+    let backup = {
+        let context = context.clone();
+        let agent_id = agent_id.to_string();
+        crate::helpers::run_blocking(move || build_backup(&context, &agent_id)).await?
+    };
+    let key = format!(
+        "{}{}{}",
+        backups_prefix(agent_id),
+        Utc::now().format("%Y%m%dT%H%M%SZ"),
+        BACKUP_EXTENSION
+    );
+    store.put(&key, backup).await?;
+    Ok(key)
+}
+
+/// Restores an agent from its latest backup, or from `key`, returning the key used
+///
+/// Refuses to overwrite an agent that already exists on this host.
+pub async fn restore_agent(
+    context: &ServiceContext,
+    agent_id: &str,
+    key: Option<&str>,
+) -> Result<String, String> {
+    let store = context
+        .artifact_store
+        .clone()
+        .ok_or("No artifact store is configured")?;
+    let sealing_key = context
+        .sealing_key
+        .clone()
+        .ok_or("Agent backups need the operator's sealing key")?;
+    let agent_id = AgentId::parse(agent_id)?;
+    let agent_dir = agent_id.dir(agents_dir(context));
+    if agent_dir.exists() {
+        return Err(format!("Agent {} already exists", agent_id));
+    }
+
+    let key = match key {
+        Some(key) => key.to_string(),
+        None => store
+            .list(&backups_prefix(&agent_id))
+            .await?
+            .into_iter()
+            .filter(|key| key.ends_with(BACKUP_EXTENSION))
+            .next_back()
+            .ok_or_else(|| format!("No backup of agent {}", agent_id))?,
+    };
+    let sealed = store.get(&key).await?;
+    let archive = sealing_key
+        .unseal(&sealed)
+        .map_err(|e| format!("Failed to unseal {}: {}", key, e))?;
+
+    let mut unpacked = tar::Archive::new(GzDecoder::new(archive.as_slice()));
+    unpacked.set_preserve_permissions(true);
+    if let Err(e) = unpacked.unpack(&agent_dir) {
+        let _ = fs::remove_dir_all(&agent_dir);
+        return Err(format!("Failed to restore {}: {}", key, e));
+    }
+
+    let ports_path = agent_dir.join(PORTS_FILE);
+    if let Ok(ports) = fs::read_to_string(&ports_path) {
+        let _ = fs::remove_file(&ports_path);
+        let ports: AgentPortConfig =
+            serde_json::from_str(&ports).map_err(|e| format!("Invalid backed up ports: {}", e))?;
+        if let Some(allocator) = &context.port_allocator {
+            allocator
+                .allocate(&agent_id, Some(ports.http_port), ports.websocket())
+                .map_err(|e| format!("Failed to restore the ports of {}: {}", agent_id, e))?;
+        } else if let Some(registry) = &context.agent_registry {
+            registry.insert(&agent_id, ports);
+        }
+    }
+    Ok(key)
+}
+
+/// Restores every backed up agent that is missing from this host, returning how many were
+///
+/// Lets a fresh operator host take over the agents of one that was lost.
+pub async fn restore_missing_agents(context: &ServiceContext) -> Result<usize, String> {
+    let store = context
+        .artifact_store
+        .clone()
+        .ok_or("No artifact store is configured")?;
+    let mut agent_ids: Vec<String> = store
+        .list("agents/")
+        .await?
+        .iter()
+        .filter(|key| key.ends_with(BACKUP_EXTENSION))
+        .filter_map(|key| key.strip_prefix("agents/")?.split_once("/backups/"))
+        .map(|(agent_id, _)| agent_id.to_string())
+        .collect();
+    agent_ids.dedup();
+
+    let base_dir = agents_dir(context);
+    let mut restored = 0;
+    for agent_id in agent_ids {
+        if base_dir.join(&agent_id).exists() {
+            continue;
+        }
+        match restore_agent(context, &agent_id, None).await {
+            Ok(key) => {
+                logging::info!(agent_id = %agent_id, phase = "storage", key = %key, "Restored agent");
+                restored += 1;
+            }
+            Err(e) => {
+                logging::warn!(agent_id = %agent_id, phase = "storage", error = %e, "Failed to restore agent")
+            }
+        }
+    }
+    Ok(restored)
+}
+
+/// Uploads an artifact in the background, only logging failures
+pub fn upload_in_background(context: &ServiceContext, key: String, data: Vec<u8>) {
+    let Some(store) = context.artifact_store.clone() else {
+        return;
+    };
+    tokio::spawn(async move {
+        match store.put(&key, data).await {
+            Ok(()) => logging::debug!(phase = "storage", key = %key, "Uploaded artifact"),
+            Err(e) => {
+                logging::warn!(phase = "storage", key = %key, error = %e, "Failed to upload artifact")
+            }
+        }
+    });
+}
+
+/// Backs up agents whenever they are created or deployed
+pub async fn run_backups(context: ServiceContext, mut events: Receiver<AgentEvent>) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(missed)) => {
+                logging::warn!(phase = "storage", missed, "Agent backups fell behind");
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if !matches!(
+            event,
+            AgentEvent::Created { .. } | AgentEvent::Deployed { .. }
+        ) {
+            continue;
+        }
+        let agent_id = event.agent_id();
+        match backup_agent(&context, agent_id).await {
+            Ok(key) => {
+                logging::info!(agent_id = %agent_id, phase = "storage", key = %key, "Backed up agent")
+            }
+            Err(e) => {
+                logging::warn!(agent_id = %agent_id, phase = "storage", error = %e, "Failed to back up agent")
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::docker;
+use blueprint_sdk::logging;
+use phala_tee_deploy_rs::TeeDeployer;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{RwLock, RwLockReadGuard};
+
+/// How long a discovered TEEPod is used before discovering again
+pub const DISCOVERY_TTL: Duration = Duration::from_secs(600);
+
+/// Phala Cloud account TEE agents are deployed through
+///
+/// Only set when the operator enabled TEE deployments, so agents can't target the TEE
+/// otherwise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PhalaCloud {
+    pub api_endpoint: String,
+    pub api_key: String,
+}
+
+/// Phala TEE deployer shared by every job (shared across threads)
+///
+/// The deployer is built on first use and kept, so jobs reuse its connections to the Phala
+/// API, and the TEEPod it discovered is reused for [`DISCOVERY_TTL`]. A default value holds
+/// no deployer yet; clones share the same one.
+#[derive(Clone, Default)]
+pub struct SharedTeeDeployer {
+    inner: Arc<RwLock<Option<CachedDeployer>>>,
+}
+
+struct CachedDeployer {
+    deployer: TeeDeployer,
+    api_key: String,
+    api_endpoint: String,
+    discovered_at: Option<Instant>,
+}
+
+impl CachedDeployer {
+    fn is_fresh(&self, api_key: &str, api_endpoint: &str) -> bool {
+        self.api_key == api_key
+            && self.api_endpoint == api_endpoint
+            && self
+                .discovered_at
+                .is_some_and(|discovered_at| discovered_at.elapsed() < DISCOVERY_TTL)
+    }
+}
+
+impl SharedTeeDeployer {
+    /// Returns the deployer for the given credentials, with a recently discovered TEEPod
+    ///
+    /// Other jobs can use the deployer at the same time; rediscovery waits for them to finish.
+    pub async fn get(
+        &self,
+        api_key: &str,
+        api_endpoint: &str,
+    ) -> Result<RwLockReadGuard<'_, TeeDeployer>, String> {
+        {
+            let cached = self.inner.read().await;
+            if cached
+                .as_ref()
+                .is_some_and(|cached| cached.is_fresh(api_key, api_endpoint))
+            {
+                return Ok(RwLockReadGuard::map(cached, |cached| {
+                    &cached.as_ref().expect("checked above").deployer
+                }));
+            }
+        }
+
+        let mut cached = self.inner.write().await;
+        // Another job may have refreshed the deployer while we waited
+        if !cached
+            .as_ref()
+            .is_some_and(|cached| cached.is_fresh(api_key, api_endpoint))
+        {
+            let reusable = cached.as_ref().is_some_and(|cached| {
+                cached.api_key == api_key && cached.api_endpoint == api_endpoint
+            });
+            if !reusable {
+                logging::info!(phase = "tee", "Initializing TeeDeployer");
+                *cached = Some(CachedDeployer {
+                    deployer: docker::init_tee_deployer(api_key, api_endpoint)?,
+                    api_key: api_key.to_string(),
+                    api_endpoint: api_endpoint.to_string(),
+                    discovered_at: None,
+                });
+            }
+
+            let entry = cached.as_mut().expect("initialized above");
+            logging::info!(phase = "tee", "Discovering available TEEPods");
+            entry
+                .deployer
+                .discover_teepod()
+                .await
+                .map_err(|e| format!("Failed to discover TEEPods: {}", e))?;
+            entry.discovered_at = Some(Instant::now());
+        }
+
+        Ok(RwLockReadGuard::map(cached.downgrade(), |cached| {
+            &cached.as_ref().expect("initialized above").deployer
+        }))
+    }
+
+    /// Forgets the discovered TEEPod, e.g. after a deployment to it failed
+    pub async fn invalidate(&self) {
+        if let Some(cached) = self.inner.write().await.as_mut() {
+            cached.discovered_at = None;
+        }
+    }
+}
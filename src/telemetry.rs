@@ -0,0 +1,297 @@
+use crate::config::env_or;
+use crate::types::StageTiming;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::runtime;
+use opentelemetry_sdk::trace::{Sampler, Tracer, TracerProvider};
+use opentelemetry_sdk::Resource;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{Instrument, Span, Subscriber};
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Format of the operator's log output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, with the fields of the job and agent it belongs to
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!(
+                "Unknown log format {}, expected text or json",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl LogFormat {
+    /// Reads the format from `AGENT_LOG_FORMAT`, defaulting to text
+    pub fn from_env() -> Result<Self, String> {
+        env_or("AGENT_LOG_FORMAT", LogFormat::Text)
+    }
+}
+
+/// Export of the blueprint's tracing spans to an OpenTelemetry collector over OTLP/HTTP
+///
+/// Every job is a trace, with a child span per phase (template copy, compose generation,
+/// TEE public key retrieval, `docker-compose up`, health wait and so on), so slow
+/// deployments can be profiled end to end.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TelemetryConfig {
+    /// Collector endpoint receiving spans, e.g. `http://localhost:4318/v1/traces`
+    pub otlp_endpoint: String,
+    /// `service.name` reported with every span
+    pub service_name: String,
+    /// Share of traces that are exported, between 0.0 and 1.0
+    pub sample_ratio: f64,
+}
+
+impl TelemetryConfig {
+    /// Service name used when `OTEL_SERVICE_NAME` is unset
+    pub const DEFAULT_SERVICE_NAME: &'static str = "coinbase-agent-kit-blueprint";
+
+    /// Loads the export from `OTEL_EXPORTER_OTLP_ENDPOINT`, `OTEL_SERVICE_NAME` and
+    /// `AGENT_TRACE_SAMPLE_RATIO`, if an endpoint is set
+    ///
+    /// As with other OpenTelemetry SDKs, `/v1/traces` is appended to the endpoint.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let Some(endpoint) = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+            .ok()
+            .filter(|endpoint| !endpoint.trim().is_empty())
+        else {
+            return Ok(None);
+        };
+        let sample_ratio: f64 = env_or("AGENT_TRACE_SAMPLE_RATIO", 1.0)?;
+        if !(0.0..=1.0).contains(&sample_ratio) {
+            return Err(format!(
+                "AGENT_TRACE_SAMPLE_RATIO must be between 0.0 and 1.0, got {}",
+                sample_ratio
+            ));
+        }
+        Ok(Some(Self {
+            otlp_endpoint: format!("{}/v1/traces", endpoint.trim().trim_end_matches('/')),
+            service_name: std::env::var("OTEL_SERVICE_NAME")
+                .unwrap_or_else(|_| Self::DEFAULT_SERVICE_NAME.to_string()),
+            sample_ratio,
+        }))
+    }
+
+    /// Creates the tracer provider batching spans to the collector
+    ///
+    /// Must be called within the Tokio runtime.
+    pub fn tracer_provider(&self) -> Result<TracerProvider, String> {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(&self.otlp_endpoint)
+            .build()
+            .map_err(|e| format!("Failed to create OTLP exporter: {}", e))?;
+        Ok(TracerProvider::builder()
+            .with_batch_exporter(exporter, runtime::Tokio)
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                self.sample_ratio,
+            ))))
+            .with_resource(Resource::new([KeyValue::new(
+                "service.name",
+                self.service_name.clone(),
+            )]))
+            .build())
+    }
+}
+
+/// Returns the span of one phase of creating or deploying an agent
+pub fn phase_span(phase: &'static str, agent_id: &str) -> Span {
+    tracing::info_span!("phase", otel.name = phase, phase, agent_id)
+}
+
+/// Runs the stages of creating or deploying an agent in their phase spans, recording how
+/// long each took for the job's result
+#[derive(Clone, Debug, Default)]
+pub struct StageTimer {
+    timings: Vec<StageTiming>,
+}
+
+impl StageTimer {
+    /// Runs a stage, recording its duration even if it fails
+    pub fn run<T>(&mut self, stage: &'static str, agent_id: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let output = phase_span(stage, agent_id).in_scope(f);
+        self.record(stage, start.elapsed());
+        output
+    }
+
+    /// Runs an asynchronous stage, recording its duration even if it fails
+    pub async fn run_async<F: Future>(
+        &mut self,
+        stage: &'static str,
+        agent_id: &str,
+        future: F,
+    ) -> F::Output {
+        let start = Instant::now();
+        let output = future.instrument(phase_span(stage, agent_id)).await;
+        self.record(stage, start.elapsed());
+        output
+    }
+
+    pub fn record(&mut self, stage: &str, elapsed: Duration) {
+        self.timings.push(StageTiming {
+            stage: stage.to_string(),
+            duration_ms: elapsed.as_millis().try_into().unwrap_or(u64::MAX),
+        });
+    }
+
+    pub fn timings(&self) -> &[StageTiming] {
+        &self.timings
+    }
+
+    pub fn into_timings(self) -> Vec<StageTiming> {
+        self.timings
+    }
+}
+
+/// Installs the global log subscriber, filtered by `RUST_LOG` (`info` by default)
+///
+/// In the JSON format every line carries the fields of the spans it was logged in, so lines
+/// logged during a job include its `job` and `call_id`. Handlers add `agent_id` and `phase`
+/// to their own lines, which lets log pipelines follow an agent from creation through
+/// deployment and health checks.
+///
+/// With `telemetry`, spans are also exported over OTLP; the returned provider should be shut
+/// down on exit to flush the last of them.
+pub fn init_logging(
+    format: LogFormat,
+    telemetry: Option<&TelemetryConfig>,
+) -> Result<Option<TracerProvider>, String> {
+    let provider = telemetry
+        .map(TelemetryConfig::tracer_provider)
+        .transpose()?;
+    let tracer = provider
+        .as_ref()
+        .map(|provider| provider.tracer(TelemetryConfig::DEFAULT_SERVICE_NAME));
+    subscriber(format, std::io::stdout.and(recent_logs().clone()), tracer)
+        .try_init()
+        .map_err(|e| format!("Failed to initialize logging: {}", e))?;
+    Ok(provider)
+}
+
+/// Most recent lines logged by the process, kept in memory for diagnostics bundles
+///
+/// Writing to it appends whole lines; the oldest lines are dropped beyond its capacity.
+#[derive(Clone, Debug)]
+pub struct RecentLogs {
+    capacity: usize,
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl RecentLogs {
+    /// Lines kept for the process's own logs
+    pub const DEFAULT_CAPACITY: usize = 2000;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            lines: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /// Returns the kept lines containing `needle`, oldest first
+    pub fn matching(&self, needle: &str) -> Vec<String> {
+        let lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        lines
+            .iter()
+            .filter(|line| line.contains(needle))
+            .cloned()
+            .collect()
+    }
+}
+
+impl io::Write for RecentLogs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        let mut lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            if lines.len() == self.capacity {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for RecentLogs {
+    type Writer = RecentLogs;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Returns the process's recent log lines, filled once logging is initialized
+pub fn recent_logs() -> &'static RecentLogs {
+    static RECENT: OnceLock<RecentLogs> = OnceLock::new();
+    RECENT.get_or_init(|| RecentLogs::new(RecentLogs::DEFAULT_CAPACITY))
+}
+
+/// Builds the log subscriber for `format`, writing to `writer` and exporting spans to
+/// `tracer` if given
+pub fn subscriber<W>(
+    format: LogFormat,
+    writer: W,
+    tracer: Option<Tracer>,
+) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let logs = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().with_writer(writer).boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_current_span(false)
+            .with_span_list(true)
+            .with_writer(writer)
+            .boxed(),
+    };
+    let spans = tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer));
+    Box::new(
+        tracing_subscriber::registry()
+            .with(logs)
+            .with(spans)
+            .with(filter),
+    )
+}
@@ -0,0 +1,1532 @@
+use crate::callbacks::is_internal;
+use crate::codec;
+use crate::docker;
+use crate::error::BlueprintError;
+use crate::helpers::run_blocking;
+use crate::hooks::{self, PostCreateHook};
+use crate::render::{self, RenderContext};
+use crate::types::{
+    is_preset_name, is_sha256_digest, AgentImage, AgentMode, AgentPolicy, CreateAgentParams,
+    EgressPolicy, ListTemplatesParams, ListTemplatesResult, TeeConfig, TemplateCheck, TemplateInfo,
+    TemplateValidationResult, ValidateTemplateParams, WalletConfig,
+};
+use crate::ServiceContext;
+use blueprint_sdk::logging;
+use include_dir::{include_dir, Dir, DirEntry};
+use semver::Version;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::process::Command as TokioCommand;
+
+/// Directory holding the agent templates, one subdirectory per template
+pub const TEMPLATES_DIR: &str = "templates";
+
+/// Template agents are created from when their parameters name none
+pub const DEFAULT_TEMPLATE: &str = "starter";
+
+/// Manifest describing a template, at the root of its directory
+pub const MANIFEST_FILE: &str = "template.toml";
+
+/// Variable recording the template an agent was created from in its .env
+pub const TEMPLATE_VAR: &str = "TEMPLATE_ID";
+
+/// Variable recording the version of the template an agent was created from in its .env
+pub const TEMPLATE_VERSION_VAR: &str = "TEMPLATE_VERSION";
+
+/// Longest accepted template ID
+pub const MAX_ID_LEN: usize = 64;
+
+/// Longest accepted git or OCI template reference
+pub const MAX_GIT_REF_LEN: usize = 512;
+
+/// Prefix of template references naming a git repository
+pub const GIT_PREFIX: &str = "git+";
+
+/// Directory under the templates directory that git templates are cached in
+///
+/// Its name can't be a template ID, so it never shadows a local template.
+pub const GIT_CACHE_DIR: &str = ".git-cache";
+
+/// Prefix of template references naming an OCI artifact
+pub const OCI_PREFIX: &str = "oci://";
+
+/// Directory under the templates directory that OCI templates are cached in
+pub const OCI_CACHE_DIR: &str = ".oci-cache";
+
+/// Variable naming the public key (a path or KMS URI) OCI templates must be signed with
+pub const COSIGN_KEY_VAR: &str = "AGENT_TEMPLATE_COSIGN_KEY";
+
+/// Variable naming the exact signing identity of keyless-signed OCI templates, e.g.
+/// `https://github.com/org/repo/.github/workflows/release.yml@refs/heads/main`
+pub const COSIGN_IDENTITY_VAR: &str = "AGENT_TEMPLATE_COSIGN_IDENTITY";
+
+/// Variable naming the OIDC issuer of the signing identity of keyless-signed OCI templates
+pub const COSIGN_ISSUER_VAR: &str = "AGENT_TEMPLATE_COSIGN_ISSUER";
+
+/// Directory under the templates directory that the embedded templates are written to
+pub const EMBEDDED_CACHE_DIR: &str = ".embedded-cache";
+
+/// Variable naming the directory the embedded templates are written to instead
+pub const CACHE_DIR_VAR: &str = "AGENT_TEMPLATE_CACHE_DIR";
+
+/// Variable listing the extra variables an agent was created with in its .env, so they are
+/// carried over when it is deployed
+pub const EXTRA_ENV_VAR: &str = "AGENT_EXTRA_ENV";
+
+/// When the blueprint gives templates a variable
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VariableStage {
+    /// Set when the agent is created, so it can be placed with `{{NAME}}` in `.env.example`
+    /// and `docker-compose.yml` as well as read as `${NAME}` by the compose file
+    Create,
+    /// Only set in the .env the agent is deployed with, so it is read as `${NAME}` by the
+    /// compose file
+    Deploy,
+}
+
+/// A variable every template is given
+#[derive(Clone, Copy, Debug)]
+pub struct TemplateVariable {
+    pub name: &'static str,
+    pub stage: VariableStage,
+    pub description: &'static str,
+}
+
+const fn created(name: &'static str, description: &'static str) -> TemplateVariable {
+    TemplateVariable {
+        name,
+        stage: VariableStage::Create,
+        description,
+    }
+}
+
+const fn deployed(name: &'static str, description: &'static str) -> TemplateVariable {
+    TemplateVariable {
+        name,
+        stage: VariableStage::Deploy,
+        description,
+    }
+}
+
+/// The variables templates are rendered and deployed with, whatever template they are
+///
+/// This is the contract between the blueprint and third-party templates: these names keep
+/// their meaning across releases. Optional settings the agent was created without are left
+/// unset. On top of these, agents get the extra variables of their
+/// `deployment_config.env`, which can't use any of these names (see [`is_reserved`]).
+pub const VARIABLES: &[TemplateVariable] = &[
+    created(
+        TEMPLATE_VAR,
+        "ID of the template the agent was created from",
+    ),
+    created(
+        "AGENT_MODE",
+        "How the agent is served: `http`, `autonomous`, `telegram` or `discord`",
+    ),
+    created(
+        "LLM_PROVIDER",
+        "`openai`, `anthropic`, `azureopenai` or `local`",
+    ),
+    created("MODEL", "Model the agent runs on"),
+    created(
+        "OPENAI_BASE_URL",
+        "OpenAI-compatible API of the local model server, for the `local` provider",
+    ),
+    created("AZURE_OPENAI_ENDPOINT", "Azure OpenAI endpoint"),
+    created(
+        "AZURE_OPENAI_API_DEPLOYMENT_NAME",
+        "Azure OpenAI deployment",
+    ),
+    created("AZURE_OPENAI_API_VERSION", "Azure OpenAI API version"),
+    created("SYSTEM_PROMPT", "System prompt, if set"),
+    created("TEMPERATURE", "Sampling temperature, if set"),
+    created("MAX_TOKENS", "Most tokens per response, if set"),
+    created(
+        "NETWORK_ID",
+        "Chain of the agent's wallet, e.g. `base-sepolia`",
+    ),
+    created(
+        "WALLET_TYPE",
+        "Kind of wallet: `cdp`, `server_signer` or `smart_wallet`",
+    ),
+    created(
+        "PAYMASTER_URL",
+        "Paymaster sponsoring a smart wallet's gas, if set",
+    ),
+    created(
+        "ACTION_PROVIDERS",
+        "Comma-separated AgentKit action providers to enable",
+    ),
+    created(
+        "ALLOWED_ACTIONS",
+        "Comma-separated on-chain actions the agent may execute, if restricted",
+    ),
+    created(
+        "BUDGET_MAX_TRANSACTION_VALUE",
+        "Largest value of a single transaction, if budgeted",
+    ),
+    created("BUDGET_DAILY_CAP", "Most value moved per day, if budgeted"),
+    created(
+        "BUDGET_ALLOWED_ASSETS",
+        "Comma-separated assets the budget allows, if budgeted",
+    ),
+    created(
+        "AUTONOMOUS_INTERVAL_SECS",
+        "Seconds between autonomous actions",
+    ),
+    created(
+        "AUTONOMOUS_ACTIVE_HOURS",
+        "UTC hours autonomous agents act in, e.g. `9-17`",
+    ),
+    created(
+        "AUTONOMOUS_MAX_ACTIONS_PER_DAY",
+        "Most autonomous actions per day",
+    ),
+    deployed("PORT", "Host port the agent's HTTP server is published on"),
+    deployed(
+        "WEBSOCKET_PORT",
+        "Host port the agent's WebSocket server is published on, unless disabled",
+    ),
+    deployed("WEBSOCKET_URL", "URL of the agent's WebSocket server"),
+    deployed(
+        "AGENT_BIND_ADDRESS",
+        "Host address the agent's ports are published on",
+    ),
+    deployed("CONTAINER_NAME", "Name of the agent's container"),
+    deployed("LOG_LEVEL", "Log level of the agent"),
+    deployed("OPENAI_API_KEY", "OpenAI API key"),
+    deployed("ANTHROPIC_API_KEY", "Anthropic API key"),
+    deployed("AZURE_OPENAI_API_KEY", "Azure OpenAI API key"),
+    deployed(
+        "CDP_API_KEY_NAME",
+        "Coinbase Developer Platform API key name",
+    ),
+    deployed(
+        "CDP_API_KEY_PRIVATE_KEY",
+        "Coinbase Developer Platform API private key",
+    ),
+    deployed(
+        "CDP_WALLET_SECRET",
+        "Private key owning the agent's smart wallet, for `smart_wallet` wallets",
+    ),
+    deployed(
+        "WALLET_PRIVATE_KEY",
+        "Private key of the agent's account, for `private_key` wallets",
+    ),
+    deployed(
+        "TELEGRAM_BOT_TOKEN",
+        "Telegram bot token, in the telegram mode",
+    ),
+    deployed(
+        "DISCORD_BOT_TOKEN",
+        "Discord bot token, in the discord mode",
+    ),
+    deployed(
+        "WALLET_DATA_FILE",
+        "File local agents write their wallet data to, for the blueprint to seal",
+    ),
+    deployed(
+        "CDP_WALLET_DATA",
+        "Wallet data to restore the agent's wallet from, once it has been captured",
+    ),
+];
+
+/// Prefixes of the variables the blueprint keeps for its own bookkeeping
+const RESERVED_PREFIXES: &[&str] = &[
+    "AGENT_",
+    "TEMPLATE_",
+    "DEPLOYMENT_",
+    "TEE_",
+    "BUDGET_",
+    "AUTONOMOUS_",
+    "DOCKER_",
+];
+
+/// Whether the blueprint sets a variable itself, so agents can't be given it as an extra one
+pub fn is_reserved(name: &str) -> bool {
+    VARIABLES.iter().any(|var| var.name == name)
+        || RESERVED_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+/// Templates built into the binary, used when the templates directory has none of that ID
+static EMBEDDED: [(&str, Dir<'static>); 2] = [
+    (
+        DEFAULT_TEMPLATE,
+        include_dir!("$CARGO_MANIFEST_DIR/templates/starter"),
+    ),
+    (
+        "python",
+        include_dir!("$CARGO_MANIFEST_DIR/templates/python"),
+    ),
+];
+
+/// Reference to a template: the ID of a local one, a git repository pinned to a ref, or a
+/// signed OCI artifact
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TemplateRef {
+    Local(String),
+    /// `git+https://host/repo#ref`, where the ref is a tag, a branch or a commit
+    Git {
+        url: String,
+        rev: String,
+    },
+    /// `oci://registry/repository:tag` or `oci://registry/repository@sha256:<digest>`
+    Oci {
+        reference: String,
+    },
+}
+
+impl TemplateRef {
+    /// Parses and validates a template reference
+    ///
+    /// Git references must use HTTPS without credentials, may not point to loopback, private
+    /// or link-local addresses, and must name a ref, which may not start with `-` so it can't
+    /// be taken for an option of `git`.
+    ///
+    /// OCI references must name a registry host, which may not be internal either, and a tag
+    /// or digest.
+    pub fn parse(reference: &str) -> Result<Self, String> {
+        if reference.len() > MAX_GIT_REF_LEN {
+            return Err(format!(
+                "Template references must be at most {} characters",
+                MAX_GIT_REF_LEN
+            ));
+        }
+        if let Some(oci) = reference.strip_prefix(OCI_PREFIX) {
+            return parse_oci_reference(oci);
+        }
+        let Some(git) = reference.strip_prefix(GIT_PREFIX) else {
+            validate_id(reference)?;
+            return Ok(Self::Local(reference.to_string()));
+        };
+        let (url, rev) = git
+            .split_once('#')
+            .ok_or("Git template references must be pinned with #<tag, branch or commit>")?;
+        let parsed = url::Url::parse(url).map_err(|e| format!("Invalid template URL: {}", e))?;
+        if parsed.scheme() != "https" {
+            return Err("Template URLs must use https".to_string());
+        }
+        if !parsed.username().is_empty() || parsed.password().is_some() {
+            return Err("Template URLs must not carry credentials".to_string());
+        }
+        let internal = match parsed.host() {
+            None => return Err("Template URLs must have a host".to_string()),
+            Some(url::Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+            Some(url::Host::Ipv4(ip)) => is_internal(IpAddr::V4(ip)),
+            Some(url::Host::Ipv6(ip)) => is_internal(IpAddr::V6(ip)),
+        };
+        if internal {
+            return Err("Template URLs must not point to an internal address".to_string());
+        }
+        if rev.is_empty()
+            || rev.starts_with('-')
+            || rev.contains("..")
+            || !rev
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+        {
+            return Err(format!("Invalid template ref {:?}", rev));
+        }
+        Ok(Self::Git {
+            url: url.to_string(),
+            rev: rev.to_string(),
+        })
+    }
+}
+
+fn parse_oci_reference(reference: &str) -> Result<TemplateRef, String> {
+    AgentImage::Image {
+        reference: reference.to_string(),
+    }
+    .validate()
+    .map_err(|e| format!("Invalid OCI template reference: {}", e))?;
+    let (host, path) = reference.split_once('/').ok_or(
+        "OCI template references must name a registry, e.g. oci://ghcr.io/org/template:1.0",
+    )?;
+    let hostname = host.split(':').next().unwrap_or(host);
+    if !host.contains(['.', ':']) && hostname != "localhost" {
+        return Err(
+            "OCI template references must name a registry, e.g. oci://ghcr.io/org/template:1.0"
+                .to_string(),
+        );
+    }
+    let internal = hostname.eq_ignore_ascii_case("localhost")
+        || hostname.parse::<IpAddr>().is_ok_and(is_internal);
+    if internal {
+        return Err("Template registries must not be internal addresses".to_string());
+    }
+    let name = path.rsplit('/').next().unwrap_or(path);
+    if !name.contains([':', '@']) {
+        return Err("OCI template references must be pinned with :<tag> or @<digest>".to_string());
+    }
+    Ok(TemplateRef::Oci {
+        reference: reference.to_string(),
+    })
+}
+
+/// Contents of a template's `template.toml`
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateManifest {
+    /// Human-readable name
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Version of the template; agents are only deployed with versions compatible with the
+    /// one they were created from
+    pub version: Version,
+    /// Oldest blueprint version the template works with
+    #[serde(default)]
+    pub min_blueprint_version: Option<Version>,
+    /// Variables that must be set in the agent's environment when it is deployed locally,
+    /// whether written by `create_agent` or given as keys to `deploy_agent`
+    #[serde(default)]
+    pub required_env: Vec<String>,
+    /// Variables required on top of `required_env` by agents with a given `WALLET_TYPE`, e.g.
+    /// the CDP API key for `cdp` wallets but not for `private_key` ones
+    #[serde(default)]
+    pub wallet_env: BTreeMap<String, Vec<String>>,
+    /// Policies the template's agents enforce, e.g. `["budget", "allowed_actions"]`; agents
+    /// asking for others are refused rather than run without them
+    #[serde(default)]
+    pub policies: Vec<AgentPolicy>,
+    /// Modes agents of the template can be created in; every mode if unset
+    #[serde(default = "all_modes")]
+    pub modes: Vec<AgentMode>,
+    #[serde(default)]
+    pub ports: TemplatePorts,
+    /// TEE size the template's agents are meant to run with
+    #[serde(default)]
+    pub resources: TeeConfig,
+    /// Sizes agents can ask for by name instead of giving numbers, e.g. `[presets.medium]`,
+    /// used for local container limits and TEE VMs alike
+    #[serde(default)]
+    pub presets: BTreeMap<String, TeeConfig>,
+    /// Variables the template's runtime needs in every agent's environment, e.g. `NODE_ENV`
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Hosts agents reach on startup, e.g. package registries, allowed whenever an agent's
+    /// egress is restricted
+    #[serde(default)]
+    pub egress_hosts: Vec<String>,
+    /// Names of files and directories that aren't copied into agents, e.g. installed
+    /// dependencies
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// Whether agents may run in the warm pool's standby containers, which run the starter
+    /// template's image and command
+    #[serde(default)]
+    pub warm_pool: bool,
+    /// Steps run in a throwaway container on each agent's directory once the template is
+    /// copied, e.g. installing dependencies; templates with hooks are copied rather than
+    /// hard-linked, so hooks can't change the template's files
+    #[serde(default)]
+    pub post_create: Vec<PostCreateHook>,
+}
+
+fn all_modes() -> Vec<AgentMode> {
+    AgentMode::ALL.to_vec()
+}
+
+impl TemplateManifest {
+    /// Checks the settings the manifest hands to agents
+    pub fn validate(&self) -> Result<(), String> {
+        if self.modes.is_empty() {
+            return Err("modes must not be empty".to_string());
+        }
+        self.resources.validate()?;
+        for (name, preset) in &self.presets {
+            if !is_preset_name(name) {
+                return Err(format!("Invalid preset name {:?}", name));
+            }
+            preset
+                .validate()
+                .map_err(|e| format!("Preset {}: {}", name, e))?;
+        }
+        for name in self.env.keys() {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+            {
+                return Err(format!("Invalid env variable name {:?}", name));
+            }
+        }
+        EgressPolicy {
+            allowed_hosts: self.egress_hosts.clone(),
+        }
+        .validate()?;
+        if self.post_create.len() > hooks::MAX_HOOKS {
+            return Err(format!(
+                "At most {} post_create hooks are allowed",
+                hooks::MAX_HOOKS
+            ));
+        }
+        for hook in &self.post_create {
+            hook.validate()?;
+        }
+        for wallet_type in self.wallet_env.keys() {
+            if !WalletConfig::TYPES.contains(&wallet_type.as_str()) {
+                return Err(format!(
+                    "wallet_env is keyed by wallet type ({}), got {:?}",
+                    WalletConfig::TYPES.join(", "),
+                    wallet_type
+                ));
+            }
+        }
+        for name in &self.ignore {
+            if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+                return Err(format!(
+                    "ignore holds file or directory names, got {:?}",
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Ports agents of a template are given when the operator doesn't allocate them and the
+/// parameters don't ask for any
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TemplatePorts {
+    pub http: u16,
+    /// `None` for templates without a WebSocket server, including `[ports]` tables leaving
+    /// it out
+    #[serde(default)]
+    pub websocket: Option<u16>,
+}
+
+impl Default for TemplatePorts {
+    fn default() -> Self {
+        Self {
+            http: 3000,
+            websocket: Some(3001),
+        }
+    }
+}
+
+/// An agent template: a directory of files copied into each agent's directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    /// The reference the template was found by
+    pub id: String,
+    pub dir: PathBuf,
+    pub manifest: TemplateManifest,
+    /// Commit git templates are pinned to
+    pub commit: Option<String>,
+}
+
+impl Template {
+    /// Loads the local template named `id` from the templates under `root`
+    ///
+    /// Templates built into the binary are used when `root` has none of the same ID, so
+    /// operators only ship a `starter` or `python` directory to override them.
+    pub fn load(root: &Path, id: &str) -> Result<Self, String> {
+        validate_id(id)?;
+        let dir = root.join(id);
+        if !dir.is_dir() {
+            if let Some(dir) = embedded_template(root, id)? {
+                return Self::read(id, dir, None);
+            }
+            return Err(format!("Unknown template {}", id));
+        }
+        Self::read(id, dir, None)
+    }
+
+    /// Finds the template a reference names, fetching git templates into the cache under
+    /// `root` the first time they are used
+    ///
+    /// A git template is checked out at the commit its ref pointed to when it was first
+    /// fetched, and keeps using that commit even if the ref moves later. Commit refs must
+    /// match the commit they resolve to.
+    pub fn resolve(root: &Path, reference: &str) -> Result<Self, String> {
+        match TemplateRef::parse(reference)? {
+            TemplateRef::Local(_) => {}
+            TemplateRef::Git { url, rev } => {
+                let cache_dir = git_cache_dir(root, &url, &rev);
+                if !cache_dir.join(PINNED_COMMIT_FILE).is_file() {
+                    fetch_git_template(&url, &rev, &cache_dir)?;
+                }
+            }
+            TemplateRef::Oci { reference } => {
+                let cache_dir = oci_cache_dir(root, &reference);
+                if !cache_dir.join(PINNED_COMMIT_FILE).is_file() {
+                    fetch_oci_template(&reference, &cache_dir)?;
+                }
+            }
+        }
+        Self::installed(root, reference)
+    }
+
+    /// Finds the template a reference names without fetching anything
+    pub fn installed(root: &Path, reference: &str) -> Result<Self, String> {
+        let cache_dir = match TemplateRef::parse(reference)? {
+            TemplateRef::Local(id) => return Self::load(root, &id),
+            TemplateRef::Git { url, rev } => git_cache_dir(root, &url, &rev),
+            TemplateRef::Oci { reference } => oci_cache_dir(root, &reference),
+        };
+        let commit = fs::read_to_string(cache_dir.join(PINNED_COMMIT_FILE))
+            .map_err(|_| format!("Template {} has not been fetched", reference))?;
+        Self::read(
+            reference,
+            cache_dir.join(CHECKOUT_DIR),
+            Some(commit.trim().to_string()),
+        )
+    }
+
+    fn read(id: &str, dir: PathBuf, commit: Option<String>) -> Result<Self, String> {
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let manifest = fs::read_to_string(&manifest_path)
+            .map_err(|e| format!("Failed to read {}: {}", manifest_path.display(), e))?;
+        let manifest: TemplateManifest = toml::from_str(&manifest)
+            .map_err(|e| format!("Invalid {}: {}", manifest_path.display(), e))?;
+        manifest
+            .validate()
+            .map_err(|e| format!("Invalid {}: {}", manifest_path.display(), e))?;
+        if let Some(min_version) = &manifest.min_blueprint_version {
+            if blueprint_version() < *min_version {
+                return Err(format!(
+                    "Template {} requires blueprint version {} or later, this is {}",
+                    id,
+                    min_version,
+                    blueprint_version()
+                ));
+            }
+        }
+        Ok(Self {
+            id: id.to_string(),
+            dir,
+            manifest,
+            commit,
+        })
+    }
+
+    /// Returns the template's compose file
+    pub fn compose_path(&self) -> PathBuf {
+        self.dir.join("docker-compose.yml")
+    }
+
+    /// Checks that agents created from `version` of the template can be deployed with it
+    pub fn check_compatible(&self, version: &Version) -> Result<(), String> {
+        if !is_compatible(version, &self.manifest.version) {
+            return Err(format!(
+                "Agent was created from template {} version {}, which is incompatible with the installed version {}",
+                self.id, version, self.manifest.version
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that agents in `mode` can be created from the template
+    pub fn check_mode(&self, mode: &AgentMode) -> Result<(), String> {
+        if !self.manifest.modes.contains(mode) {
+            return Err(format!(
+                "Template {} does not support {} agents",
+                self.id, mode
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that the template's agents enforce the policies `params` ask for
+    pub fn check_policies(&self, params: &CreateAgentParams) -> Result<(), String> {
+        let requested = [
+            (AgentPolicy::Budget, params.budget.is_some()),
+            (
+                AgentPolicy::AllowedActions,
+                params.allowed_actions.is_some(),
+            ),
+        ];
+        for (policy, _) in requested.iter().filter(|(_, requested)| *requested) {
+            if !self.manifest.policies.contains(policy) {
+                return Err(format!(
+                    "Template {} does not enforce {}, so agents asking for it can't be created from it",
+                    self.id, policy
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the resources of the preset named `size`
+    pub fn preset(&self, size: &str) -> Result<&TeeConfig, String> {
+        self.manifest.presets.get(size).ok_or_else(|| {
+            let sizes: Vec<&str> = self.manifest.presets.keys().map(String::as_str).collect();
+            if sizes.is_empty() {
+                format!("Template {} has no size presets", self.id)
+            } else {
+                format!(
+                    "Template {} has no size {:?}; it has {}",
+                    self.id,
+                    size,
+                    sizes.join(", ")
+                )
+            }
+        })
+    }
+
+    /// Describes the template for the list_templates job
+    pub fn info(&self) -> TemplateInfo {
+        TemplateInfo {
+            id: self.id.clone(),
+            name: self.manifest.name.clone(),
+            description: self.manifest.description.clone(),
+            version: self.manifest.version.to_string(),
+            modes: self.manifest.modes.clone(),
+            required_env: self.manifest.required_env.clone(),
+            wallet_env: self.manifest.wallet_env.clone(),
+            policies: self.manifest.policies.clone(),
+            http_port: self.manifest.ports.http,
+            websocket_port: self.manifest.ports.websocket,
+            resources: self.manifest.resources.clone(),
+            presets: self.manifest.presets.clone(),
+        }
+    }
+
+    /// Returns the variables the template requires of agents with `wallet_type` wallets that
+    /// `is_set` says are missing
+    pub fn missing_env(&self, wallet_type: &str, is_set: impl Fn(&str) -> bool) -> Vec<String> {
+        self.manifest
+            .required_env
+            .iter()
+            .chain(
+                self.manifest
+                    .wallet_env
+                    .get(wallet_type)
+                    .into_iter()
+                    .flatten(),
+            )
+            .filter(|var| !is_set(var))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Returns the version of this blueprint
+pub fn blueprint_version() -> Version {
+    Version::parse(env!("CARGO_PKG_VERSION")).expect("package version is semver")
+}
+
+/// Returns whether two template versions are compatible: they share their major version,
+/// or for `0.x` versions their minor version, as with Cargo's caret requirements
+pub fn is_compatible(a: &Version, b: &Version) -> bool {
+    match (a.major, b.major) {
+        (0, 0) => a.minor == b.minor,
+        (a_major, b_major) => a_major == b_major,
+    }
+}
+
+/// Lists the templates under `root` that have a manifest, sorted by ID, along with the
+/// embedded templates `root` doesn't override
+///
+/// Templates whose manifest can't be loaded are left out with a warning, so one broken
+/// template doesn't hide the others.
+pub fn list(root: &Path) -> Result<Vec<Template>, String> {
+    let ids = with_embedded_ids(local_ids(root)?);
+    let mut templates = Vec::new();
+    for id in ids {
+        match Template::load(root, &id) {
+            Ok(template) => templates.push(template),
+            Err(e) => logging::warn!(template = %id, "Skipping template: {}", e),
+        }
+    }
+    templates.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(templates)
+}
+
+/// Returns the IDs of the template directories under `root` that have a manifest; none if
+/// `root` doesn't exist
+fn local_ids(root: &Path) -> Result<Vec<String>, String> {
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = fs::read_dir(root).map_err(|e| {
+        format!(
+            "Failed to read templates directory {}: {}",
+            root.display(),
+            e
+        )
+    })?;
+    let mut ids = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read templates directory: {}", e))?;
+        let id = entry.file_name().to_string_lossy().into_owned();
+        if validate_id(&id).is_ok() && entry.path().join(MANIFEST_FILE).is_file() {
+            ids.push(id);
+        }
+    }
+    Ok(ids)
+}
+
+/// Adds the IDs of the embedded templates missing from `ids`
+fn with_embedded_ids(mut ids: Vec<String>) -> Vec<String> {
+    for (id, _) in &EMBEDDED {
+        if !ids.iter().any(|local| local == id) {
+            ids.push(id.to_string());
+        }
+    }
+    ids
+}
+
+/// Returns the directory holding the template built into the binary under `id`, writing
+/// it there if it isn't already, or `None` if no template of that ID is built in
+///
+/// The directory is named after a hash of the template's files, under
+/// `AGENT_TEMPLATE_CACHE_DIR` or the `.embedded-cache` directory of the templates under
+/// `root`, so each build of the blueprint writes its own copy once and finds it again on
+/// later runs. The cache is only accessible to the operator's user, and a copy whose files
+/// no longer match the hash is rewritten.
+pub fn embedded_template(root: &Path, id: &str) -> Result<Option<PathBuf>, String> {
+    static DIGESTS: OnceLock<Vec<String>> = OnceLock::new();
+    let Some(index) = EMBEDDED.iter().position(|(embedded, _)| *embedded == id) else {
+        return Ok(None);
+    };
+    let files = embedded_files(&EMBEDDED[index].1);
+    let digest = &DIGESTS.get_or_init(|| {
+        EMBEDDED
+            .iter()
+            .map(|(_, dir)| {
+                files_digest(
+                    embedded_files(dir)
+                        .into_iter()
+                        .map(|file| (file.path(), file.contents())),
+                )
+            })
+            .collect()
+    })[index];
+    let cache_root = match std::env::var(CACHE_DIR_VAR) {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => root.join(EMBEDDED_CACHE_DIR),
+    };
+    create_private_dir(&cache_root)?;
+    let dir = cache_root.join(format!("{}-{}", id, digest));
+    if is_intact(&dir, digest) {
+        return Ok(Some(dir));
+    }
+    if dir.exists() {
+        logging::warn!(template = %id, dir = %dir.display(), "Rewriting a changed embedded template");
+        fs::remove_dir_all(&dir)
+            .map_err(|e| format!("Failed to remove {}: {}", dir.display(), e))?;
+    }
+
+    // Write to a scratch directory renamed into place, so a copy is never seen half written
+    let scratch = cache_root.join(format!(".{}-{}.{}", id, digest, std::process::id()));
+    let _ = fs::remove_dir_all(&scratch);
+    for file in files {
+        let path = scratch.join(file.path());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        fs::write(&path, file.contents())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        #[cfg(unix)]
+        if path.extension().is_some_and(|extension| extension == "sh") {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+                .map_err(|e| format!("Failed to make {} executable: {}", path.display(), e))?;
+        }
+    }
+    if let Err(e) = fs::rename(&scratch, &dir) {
+        let _ = fs::remove_dir_all(&scratch);
+        // Another process may have written the same copy meanwhile
+        if !is_intact(&dir, digest) {
+            return Err(format!(
+                "Failed to write the embedded {} template to {}: {}",
+                id,
+                dir.display(),
+                e
+            ));
+        }
+    }
+    logging::info!(template = %id, dir = %dir.display(), "Wrote an embedded template");
+    Ok(Some(dir))
+}
+
+/// Hashes files by path, length and contents, in the order given
+fn files_digest<'a>(files: impl IntoIterator<Item = (&'a Path, &'a [u8])>) -> String {
+    let mut hasher = Sha256::new();
+    for (path, contents) in files {
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(contents);
+    }
+    hex::encode(&hasher.finalize()[..8])
+}
+
+/// Whether a copy of an embedded template holds exactly the files hashing to `digest`
+fn is_intact(dir: &Path, digest: &str) -> bool {
+    let mut files = Vec::new();
+    if read_files(dir, Path::new(""), &mut files).is_err() {
+        return false;
+    }
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    files_digest(
+        files
+            .iter()
+            .map(|(path, contents)| (path.as_path(), contents.as_slice())),
+    ) == digest
+}
+
+/// Reads the files under `dir`, by path relative to it, failing on anything but files and
+/// directories
+fn read_files(
+    dir: &Path,
+    prefix: &Path,
+    files: &mut Vec<(PathBuf, Vec<u8>)>,
+) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let file_type = entry
+            .file_type()
+            .map_err(|e| format!("Failed to read {}: {}", entry.path().display(), e))?;
+        let path = prefix.join(entry.file_name());
+        if file_type.is_dir() {
+            read_files(&entry.path(), &path, files)?;
+        } else if file_type.is_file() {
+            let contents = fs::read(entry.path())
+                .map_err(|e| format!("Failed to read {}: {}", entry.path().display(), e))?;
+            files.push((path, contents));
+        } else {
+            return Err(format!("{} is not a file", entry.path().display()));
+        }
+    }
+    Ok(())
+}
+
+/// Creates a directory only the operator's user can access, restricting it if it exists
+fn create_private_dir(dir: &Path) -> Result<(), String> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder
+        .create(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+            .map_err(|e| format!("Failed to restrict {}: {}", dir.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Returns every file of an embedded directory, sorted by path
+fn embedded_files(dir: &'static Dir<'static>) -> Vec<&'static include_dir::File<'static>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir];
+    while let Some(dir) = dirs.pop() {
+        for entry in dir.entries() {
+            match entry {
+                DirEntry::Dir(dir) => dirs.push(dir),
+                DirEntry::File(file) => files.push(file),
+            }
+        }
+    }
+    files.sort_by_key(|file| file.path());
+    files
+}
+
+/// Files every template must have besides its manifest
+const TEMPLATE_FILES: &[&str] = &["docker-compose.yml", ".env.example"];
+
+/// Longest building a template's image may take when validating it
+const BUILD_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Validates a template, running every check rather than stopping at the first failure
+///
+/// Checks that the manifest can be read, that the files agents are created from exist, that
+/// the compose file renders, parses, normalizes, passes [`docker::check_compose_policy`] and has
+/// an `agent` service, and that `.env.example`
+/// renders and has a line for every variable the manifest requires. With `build`, the
+/// template's Dockerfile is built as well. Git templates are fetched if they aren't cached.
+pub async fn validate(root: &Path, reference: &str, build: bool) -> TemplateValidationResult {
+    let template = run_blocking({
+        let (root, reference) = (root.to_path_buf(), reference.to_string());
+        move || Template::resolve(&root, &reference)
+    })
+    .await;
+    let template = match template {
+        Ok(template) => template,
+        Err(e) => {
+            return TemplateValidationResult {
+                template_id: reference.to_string(),
+                version: None,
+                valid: false,
+                checks: vec![template_check("manifest", Err(e))],
+            }
+        }
+    };
+
+    let mut checks = vec![
+        template_check("manifest", Ok(())),
+        template_check("files", check_files(&template)),
+        template_check("compose", check_compose(&template)),
+        template_check("env", check_env_example(&template)),
+    ];
+    if build {
+        checks.push(template_check("build", build_image(&template).await));
+    }
+    TemplateValidationResult {
+        template_id: template.id,
+        version: Some(template.manifest.version.to_string()),
+        valid: checks.iter().all(|check| check.ok),
+        checks,
+    }
+}
+
+fn template_check(name: &str, result: Result<(), String>) -> TemplateCheck {
+    TemplateCheck {
+        name: name.to_string(),
+        ok: result.is_ok(),
+        error: result.err(),
+    }
+}
+
+fn check_files(template: &Template) -> Result<(), String> {
+    let missing: Vec<&str> = TEMPLATE_FILES
+        .iter()
+        .copied()
+        .filter(|file| !template.dir.join(file).is_file())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("Missing {}", missing.join(", ")));
+    }
+    Ok(())
+}
+
+fn check_compose(template: &Template) -> Result<(), String> {
+    let compose = fs::read_to_string(template.compose_path())
+        .map_err(|e| format!("Failed to read docker-compose.yml: {}", e))?;
+    let compose = render::render(&compose, &RenderContext::new())
+        .map_err(|e| format!("Failed to render docker-compose.yml: {}", e))?;
+    let normalized = docker::normalize_docker_compose(&compose)?;
+    let compose: serde_yaml::Value = serde_yaml::from_str(&normalized)
+        .map_err(|e| format!("Failed to parse normalized docker-compose.yml: {}", e))?;
+    docker::check_compose_policy(&compose)?;
+    let agent = compose
+        .get("services")
+        .and_then(|services| services.get("agent"))
+        .ok_or("docker-compose.yml has no agent service")?;
+    if agent.get("build").is_some() && !template.dir.join("Dockerfile").is_file() {
+        return Err("The agent service is built, but the template has no Dockerfile".to_string());
+    }
+    Ok(())
+}
+
+fn check_env_example(template: &Template) -> Result<(), String> {
+    let env_example = fs::read_to_string(template.dir.join(".env.example"))
+        .map_err(|e| format!("Failed to read .env.example: {}", e))?;
+    render::render(&env_example, &RenderContext::new())
+        .map_err(|e| format!("Failed to render .env.example: {}", e))?;
+    let missing: BTreeSet<&str> = template
+        .manifest
+        .required_env
+        .iter()
+        .chain(template.manifest.wallet_env.values().flatten())
+        .map(String::as_str)
+        .filter(|var| {
+            !env_example.lines().any(|line| {
+                line.trim_start_matches('#')
+                    .trim_start()
+                    .starts_with(&format!("{}=", var))
+            })
+        })
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!(
+            ".env.example has no line for {}, which the manifest requires",
+            missing.into_iter().collect::<Vec<_>>().join(", ")
+        ));
+    }
+    Ok(())
+}
+
+async fn build_image(template: &Template) -> Result<(), String> {
+    let build = TokioCommand::new("docker")
+        .args(["build", "--quiet"])
+        .arg(&template.dir)
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(BUILD_TIMEOUT, build)
+        .await
+        .map_err(|_| format!("Build timed out after {}s", BUILD_TIMEOUT.as_secs()))?
+        .map_err(|e| format!("Failed to run docker build: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let tail: Vec<&str> = stderr.lines().rev().take(10).collect();
+        return Err(format!(
+            "docker build failed: {}",
+            tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+        ));
+    }
+    Ok(())
+}
+
+/// Validates every local template under `root` at startup, logging the checks that fail
+///
+/// The embedded templates are checked too unless `root` overrides them, which writes them
+/// to their cache directory. Returns whether the default template is valid,
+/// since agents are created from it unless their parameters name another.
+pub async fn self_check(root: &Path) -> bool {
+    let mut ids: Vec<String> = match fs::read_dir(root) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|id| validate_id(id).is_ok())
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => {
+            logging::error!(
+                "Failed to read templates directory {}: {}",
+                root.display(),
+                e
+            );
+            Vec::new()
+        }
+    };
+    ids = with_embedded_ids(ids);
+
+    let mut default_valid = false;
+    for id in ids {
+        let result = validate(root, &id, false).await;
+        for check in result.checks.iter().filter(|check| !check.ok) {
+            logging::error!(
+                template = %id,
+                check = %check.name,
+                "Template check failed: {}",
+                check.error.as_deref().unwrap_or_default()
+            );
+        }
+        if result.valid {
+            logging::info!(template = %id, version = ?result.version, "Template is valid");
+        }
+        if id == DEFAULT_TEMPLATE {
+            default_valid = result.valid;
+        }
+    }
+    default_valid
+}
+
+/// Handles the validate_template job
+///
+/// Empty parameters validate the default template without building it.
+pub async fn handle_validate_template(
+    params_bytes: Vec<u8>,
+    context: &ServiceContext,
+) -> Result<Vec<u8>, BlueprintError> {
+    let (params, encoding): (ValidateTemplateParams, _) = if params_bytes.is_empty() {
+        (ValidateTemplateParams::default(), codec::Encoding::Json)
+    } else {
+        codec::decode(&params_bytes).map_err(BlueprintError::Params)?
+    };
+    let reference = params.template_id.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+    TemplateRef::parse(reference).map_err(BlueprintError::Params)?;
+
+    // Building runs the template's Dockerfile on the operator's host, so nobody may unless
+    // a service owner is known
+    if params.build
+        && (context.service_owner.is_none() || context.caller() != context.service_owner)
+    {
+        return Err(BlueprintError::Unauthorized(
+            "Only the service owner may build templates".to_string(),
+        ));
+    }
+
+    let result = validate(Path::new(TEMPLATES_DIR), reference, params.build).await;
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
+
+/// Handles the list_templates job
+///
+/// Lists the local templates, without git templates, so frontends can offer them before
+/// calling `create_agent`. Empty parameters list every template.
+pub async fn handle_list_templates(params_bytes: Vec<u8>) -> Result<Vec<u8>, BlueprintError> {
+    let (params, encoding): (ListTemplatesParams, _) = if params_bytes.is_empty() {
+        (ListTemplatesParams::default(), codec::Encoding::Json)
+    } else {
+        codec::decode(&params_bytes).map_err(BlueprintError::Params)?
+    };
+
+    let templates = run_blocking(|| list(Path::new(TEMPLATES_DIR)))
+        .await
+        .map_err(BlueprintError::Template)?;
+    let result = ListTemplatesResult {
+        templates: templates
+            .iter()
+            .filter(|template| {
+                params
+                    .mode
+                    .as_ref()
+                    .map_or(true, |mode| template.manifest.modes.contains(mode))
+            })
+            .map(Template::info)
+            .collect(),
+    };
+    codec::encode(&result, encoding).map_err(BlueprintError::Internal)
+}
+
+/// Checks that a template ID is 1 to [`MAX_ID_LEN`] ASCII letters, digits, `-` or `_`, so it
+/// can only name a directory right under the templates directory
+pub fn validate_id(id: &str) -> Result<(), String> {
+    if id.is_empty()
+        || id.len() > MAX_ID_LEN
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "Template ID must be 1 to {} letters, digits, '-' or '_', got {:?}",
+            MAX_ID_LEN, id
+        ));
+    }
+    Ok(())
+}
+
+/// File of a cached git or OCI template holding the commit or digest it is pinned to
+const PINNED_COMMIT_FILE: &str = "commit";
+
+/// Directory of a cached git or OCI template holding its files
+const CHECKOUT_DIR: &str = "checkout";
+
+/// Returns the directory under `root` a git template is cached in
+fn git_cache_dir(root: &Path, url: &str, rev: &str) -> PathBuf {
+    let key = Sha256::digest(format!("{}#{}", url, rev));
+    root.join(GIT_CACHE_DIR).join(hex::encode(&key[..16]))
+}
+
+/// Clones a git template into `cache_dir` and checks out the commit `rev` resolves to
+///
+/// The clone happens in a scratch directory that is renamed into place once the commit is
+/// pinned, so an interrupted fetch never leaves a half-populated template behind. The
+/// repository's history is dropped, leaving only the files agents are created from.
+fn fetch_git_template(url: &str, rev: &str, cache_dir: &Path) -> Result<(), String> {
+    fetch_into_cache(cache_dir, &format!("{}#{}", url, rev), |scratch| {
+        checkout_git_template(url, rev, scratch)
+    })
+}
+
+/// Runs `fetch` on a scratch directory that is renamed to `cache_dir` once it succeeds
+fn fetch_into_cache(
+    cache_dir: &Path,
+    label: &str,
+    fetch: impl FnOnce(&Path) -> Result<(), String>,
+) -> Result<(), String> {
+    let parent = cache_dir
+        .parent()
+        .ok_or("Template cache directory has no parent")?;
+    fs::create_dir_all(parent)
+        .map_err(|e| format!("Failed to create template cache directory: {}", e))?;
+    let scratch = cache_dir.with_extension(format!("tmp{}", std::process::id()));
+    let _ = fs::remove_dir_all(&scratch);
+
+    let result = fetch(&scratch).and_then(|()| match fs::rename(&scratch, cache_dir) {
+        Ok(()) => Ok(()),
+        // Another job fetched the template meanwhile
+        Err(_) if cache_dir.join(PINNED_COMMIT_FILE).is_file() => Ok(()),
+        Err(e) => Err(format!("Failed to cache template {}: {}", label, e)),
+    });
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&scratch);
+    }
+    result
+}
+
+fn checkout_git_template(url: &str, rev: &str, dir: &Path) -> Result<(), String> {
+    let checkout = dir.join(CHECKOUT_DIR);
+    fs::create_dir_all(&checkout)
+        .map_err(|e| format!("Failed to create template cache directory: {}", e))?;
+
+    // Fetch from the addresses checked here, without following redirects elsewhere
+    let parsed = url::Url::parse(url).map_err(|e| format!("Invalid template URL: {}", e))?;
+    let mut pins = vec!["-c".to_string(), "http.followRedirects=false".to_string()];
+    if let Some(url::Host::Domain(host)) = parsed.host() {
+        let port = parsed.port_or_known_default().unwrap_or(443);
+        let addrs = resolve_template_host(host, port)?;
+        pins.push("-c".to_string());
+        pins.push(format!(
+            "http.curloptResolve={}:{}:{}",
+            host,
+            port,
+            addrs
+                .iter()
+                .map(|addr| pinned_ip(addr.ip()))
+                .collect::<Vec<_>>()
+                .join(",")
+        ));
+    }
+    let fetch = |args: &[&str]| {
+        let args: Vec<&str> = pins
+            .iter()
+            .map(String::as_str)
+            .chain(args.iter().copied())
+            .collect();
+        git(&checkout, &args)
+    };
+
+    git(&checkout, &["init", "--quiet"])?;
+    fetch(&["fetch", "--quiet", "--tags", "--", url])?;
+    let commit = git(
+        &checkout,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{}^{{commit}}", rev),
+        ],
+    )
+    .or_else(|_| {
+        // Branches are only known as the remote's, and commits may not be on a fetched ref
+        fetch(&["fetch", "--quiet", "--", url, rev])?;
+        git(
+            &checkout,
+            &["rev-parse", "--verify", "--quiet", "FETCH_HEAD^{commit}"],
+        )
+    })
+    .map_err(|_| format!("Template {} has no ref {}", url, rev))?;
+    if is_commit_id(rev) && !commit.starts_with(&rev.to_ascii_lowercase()) {
+        return Err(format!(
+            "Template {} ref {} resolved to commit {}",
+            url, rev, commit
+        ));
+    }
+    git(&checkout, &["checkout", "--quiet", "--detach", &commit])?;
+    fs::remove_dir_all(checkout.join(".git"))
+        .map_err(|e| format!("Failed to remove template history: {}", e))?;
+    fs::write(dir.join(PINNED_COMMIT_FILE), &commit)
+        .map_err(|e| format!("Failed to pin template commit: {}", e))?;
+    Ok(())
+}
+
+/// Resolves the host of a git or OCI template, refusing hosts with an internal address
+///
+/// Like callback URLs (see [`crate::callbacks::callback_client`]), the fetch is pinned to
+/// the addresses checked here, so the host can't be rebound to an internal address after.
+fn resolve_template_host(host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+    let addrs: Vec<SocketAddr> = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve {}: {}", host, e))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("{} does not resolve to any address", host));
+    }
+    if addrs.iter().any(|addr| is_internal(addr.ip())) {
+        return Err(format!("{} resolves to an internal address", host));
+    }
+    Ok(addrs)
+}
+
+/// Formats an address for curl's and oras' `host:port:address` overrides
+fn pinned_ip(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+    }
+}
+
+/// Directory an OCI template is cached in, named after a hash of its reference
+fn oci_cache_dir(root: &Path, reference: &str) -> PathBuf {
+    let key = Sha256::digest(reference);
+    root.join(OCI_CACHE_DIR).join(hex::encode(&key[..16]))
+}
+
+/// How OCI templates' signatures are checked, from the operator's environment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CosignPolicy {
+    /// Signed with the private half of this public key, a path or KMS URI
+    Key(String),
+    /// Signed keyless by exactly `identity`, issued by `issuer`
+    Keyless { identity: String, issuer: String },
+}
+
+impl CosignPolicy {
+    /// Reads the policy from [`COSIGN_KEY_VAR`], or [`COSIGN_IDENTITY_VAR`] and
+    /// [`COSIGN_ISSUER_VAR`]; `None` if neither is set
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        match (
+            var(COSIGN_KEY_VAR),
+            var(COSIGN_IDENTITY_VAR),
+            var(COSIGN_ISSUER_VAR),
+        ) {
+            (Some(key), None, None) => Ok(Some(Self::Key(key))),
+            (None, Some(identity), Some(issuer)) => Ok(Some(Self::Keyless { identity, issuer })),
+            (None, None, None) => Ok(None),
+            _ => Err(format!(
+                "Set either {} or both {} and {}",
+                COSIGN_KEY_VAR, COSIGN_IDENTITY_VAR, COSIGN_ISSUER_VAR
+            )),
+        }
+    }
+
+    /// Returns the arguments of the `cosign verify` checking `reference`
+    pub fn verify_args(&self, reference: &str) -> Vec<String> {
+        let mut args = vec!["verify".to_string()];
+        match self {
+            Self::Key(key) => args.extend(["--key".to_string(), key.clone()]),
+            Self::Keyless { identity, issuer } => args.extend([
+                "--certificate-identity".to_string(),
+                identity.clone(),
+                "--certificate-oidc-issuer".to_string(),
+                issuer.clone(),
+            ]),
+        }
+        args.extend(["--output".to_string(), "json".to_string()]);
+        args.extend(["--".to_string(), reference.to_string()]);
+        args
+    }
+}
+
+/// Returns the repository of an OCI reference, without its tag or digest
+fn oci_repository(reference: &str) -> &str {
+    if let Some((repository, _)) = reference.split_once('@') {
+        return repository;
+    }
+    match reference.rfind(':') {
+        Some(colon) if !reference[colon..].contains('/') => &reference[..colon],
+        _ => reference,
+    }
+}
+
+/// Pulls a signed OCI template into `cache_dir`, pinned to the digest its reference
+/// resolves to
+///
+/// The signature is checked against the operator's [`CosignPolicy`] before anything is
+/// pulled, and OCI templates can't be used without one. The registry's host is resolved
+/// once, refusing internal addresses, and `oras` is pinned to the address found.
+fn fetch_oci_template(reference: &str, cache_dir: &Path) -> Result<(), String> {
+    let policy = CosignPolicy::from_env()?.ok_or_else(|| {
+        format!(
+            "OCI templates need a signature policy; set {} or {} and {}",
+            COSIGN_KEY_VAR, COSIGN_IDENTITY_VAR, COSIGN_ISSUER_VAR
+        )
+    })?;
+    let registry = reference.split('/').next().unwrap_or(reference);
+    let (host, port) = match registry.split_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse()
+                .map_err(|_| format!("Invalid registry port in {}", reference))?,
+        ),
+        None => (registry, 443),
+    };
+    let addrs = resolve_template_host(host, port)?;
+    let resolve = format!("{}:{}:{}", host, port, pinned_ip(addrs[0].ip()));
+    fetch_into_cache(cache_dir, reference, |scratch| {
+        let digest = tool("oras", &["resolve", "--resolve", &resolve, "--", reference])?;
+        if !is_sha256_digest(&digest) {
+            return Err(format!(
+                "{} resolved to an invalid digest {:?}",
+                reference, digest
+            ));
+        }
+        if let Some((_, pinned)) = reference.split_once('@') {
+            if pinned != digest {
+                return Err(format!("{} resolved to digest {}", reference, digest));
+            }
+        }
+        let pinned = format!("{}@{}", oci_repository(reference), digest);
+        let verify_args = policy.verify_args(&pinned);
+        tool(
+            "cosign",
+            &verify_args.iter().map(String::as_str).collect::<Vec<_>>(),
+        )
+        .map_err(|e| format!("Signature of template {} is not trusted: {}", reference, e))?;
+
+        let pulled = scratch.join("pulled");
+        fs::create_dir_all(&pulled)
+            .map_err(|e| format!("Failed to create template cache directory: {}", e))?;
+        tool(
+            "oras",
+            &[
+                "pull",
+                "--resolve",
+                &resolve,
+                "--output",
+                &pulled.to_string_lossy(),
+                "--",
+                &pinned,
+            ],
+        )?;
+        // Templates are pushed as a directory, which may be pulled under its own name
+        let template_dir = if pulled.join(MANIFEST_FILE).is_file() {
+            pulled
+        } else {
+            let dirs: Vec<PathBuf> = fs::read_dir(&pulled)
+                .map_err(|e| format!("Failed to read pulled template: {}", e))?
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.join(MANIFEST_FILE).is_file())
+                .collect();
+            match <[PathBuf; 1]>::try_from(dirs) {
+                Ok([dir]) => dir,
+                Err(_) => return Err(format!("Template {} has no {}", reference, MANIFEST_FILE)),
+            }
+        };
+        fs::rename(&template_dir, scratch.join(CHECKOUT_DIR))
+            .map_err(|e| format!("Failed to cache template {}: {}", reference, e))?;
+        fs::write(scratch.join(PINNED_COMMIT_FILE), &digest)
+            .map_err(|e| format!("Failed to pin template digest: {}", e))
+    })
+}
+
+/// Runs a tool such as `oras` or `cosign`, returning its trimmed output
+fn tool(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} {} failed: {}",
+            program,
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Returns whether a ref looks like an abbreviated or full commit ID
+fn is_commit_id(rev: &str) -> bool {
+    (7..=40).contains(&rev.len()) && rev.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Runs `git` in `dir`, returning its trimmed output
+///
+/// Credential prompts are disabled, so repositories that need authentication fail instead
+/// of hanging the job.
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
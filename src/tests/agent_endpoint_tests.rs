@@ -0,0 +1,123 @@
+use crate::agent_endpoint::AgentEndpoint;
+use crate::config::{HttpConfig, ProxyConfig};
+use std::collections::HashMap;
+use std::time::Duration;
+use warp::Filter;
+
+/// Test retrieving the action history from a mock agent
+#[tokio::test]
+async fn test_get_actions() {
+    // Serve a canned action history on a random local port
+    let actions = warp::path("actions")
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|query: HashMap<String, String>| {
+            assert_eq!(query.get("since").map(String::as_str), Some("1700000000"));
+            warp::reply::json(&serde_json::json!({
+                "actions": [{
+                    "timestamp": 1700000100,
+                    "action": "transfer",
+                    "tx_hash": "0xabc",
+                    "details": "Transferred 0.1 ETH"
+                }]
+            }))
+        });
+    let (addr, server) = warp::serve(actions).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let agent = AgentEndpoint::from_port(addr.port());
+    let actions = agent
+        .get_actions(Some(1700000000), Duration::from_secs(5))
+        .await
+        .expect("Failed to get actions");
+
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0].action, "transfer");
+    assert_eq!(actions[0].tx_hash.as_deref(), Some("0xabc"));
+}
+
+/// Test retrieving the wallet of a mock agent, and rejecting invalid wallets
+#[tokio::test]
+async fn test_get_wallet() {
+    let wallet = warp::path("wallet").map(|| {
+        warp::reply::json(&serde_json::json!({
+            "address": "0x1234567890abcdef1234567890abcdef12345678",
+            "network_id": "base-sepolia"
+        }))
+    });
+    let (addr, server) = warp::serve(wallet).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let wallet = AgentEndpoint::from_port(addr.port())
+        .get_wallet(Duration::from_secs(5))
+        .await
+        .expect("Failed to get wallet");
+    assert_eq!(wallet.address, "0x1234567890abcdef1234567890abcdef12345678");
+    assert_eq!(wallet.network_id, "base-sepolia");
+
+    let invalid = warp::path("wallet").map(|| {
+        warp::reply::json(&serde_json::json!({
+            "address": "not-an-address",
+            "network_id": "base-sepolia"
+        }))
+    });
+    let (addr, server) = warp::serve(invalid).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    let error = AgentEndpoint::from_port(addr.port())
+        .get_wallet(Duration::from_secs(5))
+        .await
+        .unwrap_err();
+    assert!(error.contains("Invalid wallet address"), "{}", error);
+
+    // Agents without the route report no wallet
+    let (addr, server) =
+        warp::serve(warp::path("health").map(warp::reply)).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    assert!(AgentEndpoint::from_port(addr.port())
+        .get_wallet(Duration::from_secs(5))
+        .await
+        .is_err());
+}
+
+/// Test that builder overrides keep the operator's proxy and the shared client
+#[tokio::test]
+async fn test_endpoint_builder_overrides() {
+    // A proxy answers health checks for any agent it is asked to forward to
+    let proxy = warp::path("health").map(|| warp::reply::json(&serde_json::json!({ "ok": true })));
+    let (proxy_addr, server) = warp::serve(proxy).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    let config = HttpConfig {
+        proxy: Some(ProxyConfig {
+            url: format!("http://{}", proxy_addr),
+            no_proxy: String::new(),
+        }),
+        ..HttpConfig::default()
+    };
+
+    // A connect timeout override still goes through the configured proxy
+    let agent = AgentEndpoint::builder("http://agent.invalid:3000")
+        .config(config)
+        .connect_timeout(Duration::from_secs(2))
+        .build()
+        .unwrap();
+    let health = agent
+        .check_health(Duration::from_secs(5))
+        .await
+        .expect("Health check should go through the proxy");
+    assert_eq!(health["ok"], true);
+
+    // A request timeout override is applied on the shared client
+    let slow = warp::path("health").then(|| async {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        warp::reply::json(&serde_json::json!({ "ok": true }))
+    });
+    let (addr, server) = warp::serve(slow).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    let agent = AgentEndpoint::builder(format!("http://{}", addr))
+        .http_client(reqwest::Client::new())
+        .request_timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let started = std::time::Instant::now();
+    assert!(agent.check_health(Duration::from_secs(30)).await.is_err());
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,81 @@
+use crate::{
+    agent_actions::handle_agent_actions,
+    agent_status::handle_agent_status,
+    error::BlueprintError,
+    tests::{set_caller, setup_test_env},
+    types::{AgentActionsParams, AgentStatusParams, AgentStatusResult, AllowedAction},
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Test that the status job reports the agent's persisted action policy
+#[tokio::test]
+async fn test_agent_status_reports_policy() {
+    let (mut context, temp_dir, _missing) = setup_test_env();
+    set_caller(&mut context, "alice");
+
+    let agent_id = "agent-with-policy";
+    let agent_dir = temp_dir.join(agent_id);
+    std::fs::create_dir_all(&agent_dir).expect("Failed to create agent directory");
+    std::fs::write(
+        agent_dir.join(".env"),
+        "AGENT_OWNER=alice\nALLOWED_ACTIONS=transfer,swap\n",
+    )
+    .expect("Failed to write .env");
+
+    let params = AgentStatusParams {
+        agent_id: agent_id.parse().unwrap(),
+    };
+    let params_bytes = serde_json::to_vec(&params).expect("Failed to serialize params");
+
+    let result = handle_agent_status(params_bytes, &context)
+        .await
+        .expect("Status job failed");
+    let result: AgentStatusResult =
+        serde_json::from_slice(&result).expect("Failed to deserialize result");
+
+    assert_eq!(
+        result.allowed_actions,
+        Some(vec![AllowedAction::Transfer, AllowedAction::Swap])
+    );
+    assert!(!result.running);
+}
+
+/// Test that only the agent's owner or the service owner can read its status and actions
+#[tokio::test]
+async fn test_agent_status_requires_owner() {
+    let (mut context, temp_dir, _missing) = setup_test_env();
+    context.call_id = Some(7);
+    context.job_callers = Some(Arc::new(Mutex::new(HashMap::from([(
+        7,
+        "intruder".to_string(),
+    )]))));
+
+    let agent_id = "owned-agent";
+    let agent_dir = temp_dir.join(agent_id);
+    std::fs::create_dir_all(&agent_dir).expect("Failed to create agent directory");
+    std::fs::write(agent_dir.join(".env"), "AGENT_OWNER=alice\n").expect("Failed to write .env");
+
+    let status = serde_json::to_vec(&AgentStatusParams {
+        agent_id: agent_id.parse().unwrap(),
+    })
+    .unwrap();
+    let result = handle_agent_status(status, &context).await;
+    assert!(
+        matches!(result, Err(BlueprintError::Unauthorized(_))),
+        "Status should be refused: {:?}",
+        result
+    );
+
+    let actions = serde_json::to_vec(&AgentActionsParams {
+        agent_id: agent_id.parse().unwrap(),
+        since: None,
+    })
+    .unwrap();
+    let result = handle_agent_actions(actions, &context).await;
+    assert!(
+        matches!(result, Err(BlueprintError::Unauthorized(_))),
+        "Actions should be refused: {:?}",
+        result
+    );
+}
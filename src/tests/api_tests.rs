@@ -0,0 +1,591 @@
+use crate::{
+    callbacks::{self, signature_header, validate_callback_url, CallbackConfig, SIGNATURE_HEADER},
+    error::BlueprintError,
+    events::{AgentEvent, EventBus},
+    exposure::{self, CloudflareTunnel, ExposureConfig},
+    lifecycle::AgentLifecycleStatus,
+    mcp::{McpServer, PROTOCOL_VERSION},
+    router::{self, RouterConfig},
+    status_api::{self, AgentLogs, AgentOverview},
+    tests::setup_test_env,
+    types::{DeployAgentParams, Secret},
+    AgentPortConfig, HttpConfig, IngressConfig,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::time::Duration;
+
+#[test]
+fn test_ingress_routes() {
+    let ingress = IngressConfig {
+        host: "agents.example.com".to_string(),
+        port: 8443,
+        tls: Some(("/etc/ssl/agents.pem".into(), "/etc/ssl/agents.key".into())),
+    };
+    assert_eq!(
+        ingress.agent_url("agent-1"),
+        "https://agents.example.com:8443/agents/agent-1/"
+    );
+    assert_eq!(
+        ingress.agent_websocket_url("agent-1"),
+        "wss://agents.example.com:8443/agents/agent-1/ws"
+    );
+
+    let routes = HashMap::from([(
+        "agent-1".to_string(),
+        AgentPortConfig {
+            http_port: 3000,
+            websocket_port: Some(3001),
+        },
+    )]);
+    let caddyfile = ingress.render_caddyfile(&routes);
+    assert!(caddyfile.starts_with("agents.example.com:8443 {"));
+    assert!(caddyfile.contains("tls /certs/agents.pem /certs/agents.key"));
+    assert!(caddyfile
+        .contains("handle_path /agents/agent-1/ws* {\n        reverse_proxy 127.0.0.1:3001"));
+    assert!(
+        caddyfile.contains("handle_path /agents/agent-1/* {\n        reverse_proxy 127.0.0.1:3000")
+    );
+}
+
+#[tokio::test]
+async fn test_job_callbacks() {
+    assert!(validate_callback_url("https://dapp.example/hooks/agents").is_ok());
+    assert!(validate_callback_url("https://100.128.0.1/hook").is_ok());
+    for url in [
+        "http://dapp.example/hook",
+        "https://localhost/hook",
+        "https://127.0.0.1/hook",
+        "https://10.0.0.5/hook",
+        "https://169.254.169.254/latest",
+        "https://[::1]/hook",
+        "https://100.64.0.1/hook",
+        "https://0.1.2.3/hook",
+        "https://[::ffff:127.0.0.1]/hook",
+        "https://[::ffff:10.0.0.5]/hook",
+        "https://[64:ff9b::a00:5]/hook",
+        "not a url",
+    ] {
+        assert!(validate_callback_url(url).is_err(), "{} accepted", url);
+    }
+    let params: DeployAgentParams = serde_json::from_value(serde_json::json!({
+        "agent_id": "some-agent",
+        "callback_url": "https://192.168.1.1/hook"
+    }))
+    .unwrap();
+    assert!(params.validate().unwrap_err().contains("callback_url"));
+
+    // HMAC-SHA256 test vector from RFC 4231
+    assert_eq!(
+        signature_header("Jefe", b"what do ya want for nothing?"),
+        "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+
+    // Each caller signs with its own key
+    let config = CallbackConfig::new("callback-secret");
+    let key = config.caller_key("-/alice");
+    assert_eq!(key.len(), 64);
+    assert_ne!(key, config.caller_key("-/bob"));
+    assert_eq!(
+        config.sign("-/alice", b"body"),
+        signature_header(&key, b"body")
+    );
+
+    let error = BlueprintError::Docker("no such image".to_string());
+    let body = callbacks::callback_body::<serde_json::Value>("deploy_agent", Some(4), Err(&error));
+    assert_eq!(body["status"], "failed");
+    assert_eq!(body["call_id"], 4);
+    assert_eq!(body["error"]["kind"], "Docker");
+
+    // Callbacks are posted with the signature of their exact body
+    let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let app = axum::Router::new()
+        .route(
+            "/hook",
+            axum::routing::post(
+                move |headers: axum::http::HeaderMap, body: axum::body::Bytes| async move {
+                    let signature = headers[SIGNATURE_HEADER].to_str().unwrap().to_string();
+                    sender.send((signature, body.to_vec())).unwrap();
+                },
+            ),
+        )
+        .route(
+            "/moved",
+            axum::routing::post(|| async { axum::response::Redirect::temporary("/hook") }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let result = serde_json::json!({ "agent_id": "some-agent" });
+    let body = callbacks::callback_body("create_agent", Some(9), Ok(&result));
+    let body = serde_json::to_vec(&body).unwrap();
+    let signature = config.sign("-/alice", &body);
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    callbacks::post_callback(
+        &client,
+        &format!("http://{}/hook", addr),
+        &signature,
+        body.clone(),
+    )
+    .await
+    .unwrap();
+    let (received_signature, received_body) =
+        tokio::time::timeout(Duration::from_secs(5), received.recv())
+            .await
+            .unwrap()
+            .unwrap();
+    assert_eq!((received_signature, received_body), (signature, body));
+
+    // Redirects count as failures rather than being followed
+    let moved = format!("http://{}/moved", addr);
+    assert!(
+        callbacks::post_callback(&client, &moved, "sha256=00", Vec::new())
+            .await
+            .is_err_and(|e| e.contains("307"))
+    );
+
+    // Hosts are resolved and checked again when posting, not only when the URL is accepted
+    let http_config = HttpConfig::default();
+    for url in [
+        format!("http://{}/hook", addr),
+        "https://localhost/hook".to_string(),
+    ] {
+        assert!(callbacks::callback_client(&url, &http_config)
+            .await
+            .is_err_and(|e| e.contains("internal address")));
+    }
+}
+
+#[tokio::test]
+async fn test_status_api() {
+    let (context, temp_dir, _missing) = setup_test_env();
+    fs::create_dir_all(temp_dir.join("agent-1")).unwrap();
+    fs::write(temp_dir.join("agent-1/.env"), "AGENT_MODE=chat\n").unwrap();
+    context.agent_registry.as_ref().unwrap().insert(
+        "agent-1",
+        AgentPortConfig {
+            http_port: 3001,
+            websocket_port: Some(3002),
+        },
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, status_api::router(context))
+            .await
+            .unwrap()
+    });
+    let client = reqwest::Client::new();
+
+    // Templates and other directories without an .env are not agents
+    let agents: Vec<AgentOverview> = client
+        .get(format!("http://{}/agents", addr))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(agents.len(), 1);
+    assert_eq!(agents[0].agent_id, "agent-1");
+    assert_eq!(agents[0].http_port, Some(3001));
+    assert_eq!(agents[0].health, None);
+
+    let agent: AgentOverview = client
+        .get(format!("http://{}/agents/agent-1", addr))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(agent, agents[0]);
+
+    for path in [
+        "agents/unknown",
+        "agents/templates/logs",
+        "agents/..%2Fetc/logs",
+    ] {
+        let response = client
+            .get(format!("http://{}/{}", addr, path))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::NOT_FOUND,
+            "{}",
+            path
+        );
+    }
+
+    let logs = client
+        .get(format!("http://{}/agents/agent-1/logs?tail=10", addr))
+        .send()
+        .await
+        .unwrap();
+    if logs.status().is_success() {
+        let logs: AgentLogs = logs.json().await.unwrap();
+        assert!(logs.lines.len() <= 10);
+    }
+}
+
+#[tokio::test]
+async fn test_agent_router() {
+    // A fake agent answering on its own port
+    let agent = axum::Router::new()
+        .route(
+            "/interact",
+            axum::routing::post(|body: String| async move {
+                axum::Json(serde_json::json!({ "reply": body }))
+            }),
+        )
+        .route(
+            "/health",
+            axum::routing::get(|| async { axum::Json(serde_json::json!({ "status": "ok" })) }),
+        );
+    let agent_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let agent_port = agent_listener.local_addr().unwrap().port();
+    tokio::spawn(async move { axum::serve(agent_listener, agent).await.unwrap() });
+
+    let (mut context, _temp_dir, _missing) = setup_test_env();
+    assert_eq!(context.agent_bind_address(), "0.0.0.0");
+    let config = RouterConfig {
+        bind: "127.0.0.1:0".parse().unwrap(),
+        public_url: Some("https://agents.example.com".to_string()),
+    };
+    assert_eq!(
+        config.agent_url("routed").as_deref(),
+        Some("https://agents.example.com/routed")
+    );
+    context.agent_router = Some(config);
+    assert_eq!(context.agent_bind_address(), "127.0.0.1");
+    let registry = context.agent_registry.clone().unwrap();
+    registry.insert(
+        "routed",
+        AgentPortConfig {
+            http_port: agent_port,
+            websocket_port: Some(agent_port + 1),
+        },
+    );
+    let closed_port = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    };
+    registry.insert(
+        "stopped",
+        AgentPortConfig {
+            http_port: closed_port,
+            websocket_port: Some(closed_port + 1),
+        },
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, router::router(context))
+            .await
+            .unwrap()
+    });
+
+    let client = reqwest::Client::new();
+    let reply: serde_json::Value = client
+        .post(format!("http://{}/routed/interact", addr))
+        .body("hello")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(reply["reply"], "hello");
+    let health = client
+        .get(format!("http://{}/routed/health", addr))
+        .send()
+        .await
+        .unwrap();
+    assert!(health.status().is_success());
+    assert_eq!(
+        health.headers()["content-type"].to_str().unwrap(),
+        "application/json"
+    );
+
+    let unknown = client
+        .get(format!("http://{}/missing/health", addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unknown.status(), reqwest::StatusCode::NOT_FOUND);
+    let unreachable = client
+        .get(format!("http://{}/stopped/health", addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unreachable.status(), reqwest::StatusCode::BAD_GATEWAY);
+}
+
+#[tokio::test]
+async fn test_agent_exposure() {
+    let logs = "INF |  Your quick Tunnel has been created! Visit it at:\nINF |  https://brave-owl-42.trycloudflare.com\n";
+    assert_eq!(
+        exposure::parse_quick_tunnel_url(logs).as_deref(),
+        Some("https://brave-owl-42.trycloudflare.com")
+    );
+    assert_eq!(
+        exposure::parse_quick_tunnel_url("INF Starting tunnel"),
+        None
+    );
+
+    // Rules for other hosts and the operator's catch-all are kept
+    let ingress = vec![
+        serde_json::json!({ "hostname": "other.agents.example.com", "service": "http://127.0.0.1:3002" }),
+        serde_json::json!({ "hostname": "a1.agents.example.com", "service": "http://127.0.0.1:3000" }),
+        serde_json::json!({ "service": "http_status:418" }),
+    ];
+    let updated = exposure::apply_ingress_rule(
+        ingress.clone(),
+        "a1.agents.example.com",
+        Some("http://127.0.0.1:3010"),
+    );
+    assert_eq!(updated.len(), 3);
+    assert_eq!(updated[1]["service"], "http://127.0.0.1:3010");
+    assert_eq!(updated[2]["service"], "http_status:418");
+    let removed = exposure::apply_ingress_rule(ingress, "a1.agents.example.com", None);
+    assert_eq!(removed.len(), 2);
+    assert_eq!(
+        exposure::apply_ingress_rule(Vec::new(), "a1.agents.example.com", None),
+        vec![serde_json::json!({ "service": "http_status:404" })]
+    );
+
+    // Unexposing removes the agent's route and DNS record through the Cloudflare API
+    let (sender, mut requests) = tokio::sync::mpsc::unbounded_channel();
+    let app = axum::Router::new().fallback(
+        move |method: axum::http::Method, uri: axum::http::Uri, body: axum::body::Bytes| async move {
+            let path = uri.path().to_string();
+            sender
+                .send((method.to_string(), path.clone(), body.to_vec()))
+                .unwrap();
+            let result = if path.ends_with("/configurations") {
+                serde_json::json!({ "config": { "ingress": [
+                    { "hostname": "a1.agents.example.com", "service": "http://127.0.0.1:3000" },
+                    { "service": "http_status:404" },
+                ] } })
+            } else if method == axum::http::Method::GET {
+                serde_json::json!([{ "id": "record-1", "content": "tunnel-1.cfargotunnel.com" }])
+            } else {
+                serde_json::json!({})
+            };
+            axum::Json(serde_json::json!({ "success": true, "errors": [], "result": result }))
+        },
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let tunnel = CloudflareTunnel {
+        api_base: format!("http://{}", addr),
+        api_token: Secret::new("cf-token".to_string()),
+        account_id: "account-1".to_string(),
+        zone_id: "zone-1".to_string(),
+        tunnel_id: "tunnel-1".to_string(),
+        tunnel_token: Secret::new("tunnel-token".to_string()),
+        domain: "agents.example.com".to_string(),
+    };
+    assert_eq!(tunnel.hostname("A1").unwrap(), "a1.agents.example.com");
+    assert!(tunnel.hostname(&"a".repeat(64)).is_err());
+    let (context, _temp_dir, _missing) = setup_test_env();
+    exposure::unexpose(&context, &ExposureConfig::Tunnel(tunnel), "a1")
+        .await
+        .unwrap();
+
+    let mut calls = Vec::new();
+    while let Ok((method, path, body)) = requests.try_recv() {
+        calls.push((method, path, body));
+    }
+    let summary: Vec<(&str, &str)> = calls
+        .iter()
+        .map(|(method, path, _)| (method.as_str(), path.as_str()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (
+                "GET",
+                "/accounts/account-1/cfd_tunnel/tunnel-1/configurations"
+            ),
+            (
+                "PUT",
+                "/accounts/account-1/cfd_tunnel/tunnel-1/configurations"
+            ),
+            ("GET", "/zones/zone-1/dns_records"),
+            ("DELETE", "/zones/zone-1/dns_records/record-1"),
+        ]
+    );
+    let config: serde_json::Value = serde_json::from_slice(&calls[1].2).unwrap();
+    assert_eq!(
+        config["config"]["ingress"],
+        serde_json::json!([{ "service": "http_status:404" }])
+    );
+}
+
+#[tokio::test]
+async fn test_event_stream() {
+    let (mut context, _temp_dir, _missing) = setup_test_env();
+    let events = EventBus::default();
+    context.events = Some(events.clone());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, status_api::router(context))
+            .await
+            .unwrap()
+    });
+
+    // The stream is subscribed once the response has started
+    let mut response = reqwest::get(format!("http://{}/events?agent_id=watched", addr))
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()["content-type"].to_str().unwrap(),
+        "text/event-stream"
+    );
+    events.publish(AgentEvent::Created {
+        agent_id: "other".parse().unwrap(),
+        call_id: None,
+    });
+    events.publish(AgentEvent::Deployed {
+        agent_id: "watched".parse().unwrap(),
+        call_id: Some(3),
+        endpoint: None,
+        tee_app_id: None,
+    });
+
+    let mut received = String::new();
+    while !received.contains("\n\n") {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        received.push_str(std::str::from_utf8(&chunk).unwrap());
+    }
+    assert!(received.starts_with("event: deployed\n"), "{}", received);
+    assert!(received.contains(r#""agent_id":"watched""#));
+    assert!(!received.contains("other"));
+}
+
+#[tokio::test]
+async fn test_mcp_server() {
+    let (context, _temp_dir, _missing) = setup_test_env();
+    let server = McpServer::new(context);
+
+    let initialized = server
+        .handle(
+            serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(initialized["id"], 1);
+    assert_eq!(initialized["result"]["protocolVersion"], PROTOCOL_VERSION);
+    assert!(initialized["result"]["capabilities"]["tools"].is_object());
+
+    // Notifications are not answered
+    assert!(server
+        .handle(serde_json::json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+        .await
+        .is_none());
+
+    // Agents without a running container are not listed
+    let tools = server
+        .handle(serde_json::json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }))
+        .await
+        .unwrap();
+    assert_eq!(tools["result"]["tools"], serde_json::json!([]));
+
+    let unknown_tool = server
+        .handle(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "tools/call",
+            "params": { "name": "deploy_everything", "arguments": {} },
+        }))
+        .await
+        .unwrap();
+    assert_eq!(unknown_tool["error"]["code"], -32602);
+
+    // Failures of the agent are tool errors the client can show
+    let missing_agent = server
+        .handle(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 4,
+            "method": "tools/call",
+            "params": { "name": "message_missing", "arguments": { "message": "hi" } },
+        }))
+        .await
+        .unwrap();
+    assert_eq!(missing_agent["result"]["isError"], true);
+    assert!(missing_agent["result"]["content"][0]["text"]
+        .as_str()
+        .unwrap()
+        .contains("No port configuration"));
+
+    let unknown_method = server
+        .handle(serde_json::json!({ "jsonrpc": "2.0", "id": 5, "method": "resources/list" }))
+        .await
+        .unwrap();
+    assert_eq!(unknown_method["error"]["code"], -32601);
+}
+
+#[tokio::test]
+async fn test_event_bus() {
+    let (mut context, temp_dir, _missing) = setup_test_env();
+    let bus = EventBus::default();
+    context.events = Some(bus.clone());
+    context.call_id = Some(9);
+    let mut events = bus.subscribe();
+
+    fs::create_dir_all(temp_dir.join("agent-1")).unwrap();
+    crate::create_agent::remove_agent(&"agent-1".parse().unwrap(), &context).unwrap();
+    let event = events.recv().await.unwrap();
+    assert_eq!(
+        event,
+        AgentEvent::Deleted {
+            agent_id: "agent-1".parse().unwrap(),
+            call_id: Some(9),
+        }
+    );
+    assert_eq!(AgentLifecycleStatus::from_event(&event), None);
+
+    // Subscribers see events in order, and only lifecycle transitions are reported on-chain
+    bus.publish(AgentEvent::Unhealthy {
+        agent_id: "agent-2".parse().unwrap(),
+        call_id: None,
+        detail: "Health check failed".to_string(),
+    });
+    bus.publish(AgentEvent::Healthy {
+        agent_id: "agent-2".parse().unwrap(),
+    });
+    let unhealthy = events.recv().await.unwrap();
+    assert_eq!(unhealthy.agent_id(), "agent-2");
+    assert_eq!(
+        AgentLifecycleStatus::from_event(&unhealthy),
+        Some((
+            AgentLifecycleStatus::Unhealthy,
+            Some("Health check failed".to_string())
+        ))
+    );
+    assert_eq!(
+        AgentLifecycleStatus::from_event(&events.recv().await.unwrap()),
+        Some((AgentLifecycleStatus::Running, None))
+    );
+    assert_eq!(
+        serde_json::to_value(&unhealthy).unwrap()["event"],
+        "Unhealthy"
+    );
+}
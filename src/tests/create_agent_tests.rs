@@ -1,10 +1,17 @@
 use crate::{
-    create_agent::handle_create_agent,
+    create_agent::{handle_create_agent, render_env_file},
+    create_agent_swarm::handle_create_agent_swarm,
+    create_agents::handle_create_agents,
+    error::BlueprintError,
+    helpers::parse_env_content,
+    ports::{PortAllocator, PortRange},
     tests::{log, setup_test_env},
     types::{
-        AgentConfig, AgentCreationResult, AgentMode, ApiKeyConfig, CreateAgentParams,
-        DeploymentConfig,
+        ActionProvider, AgentConfig, AgentCreationResult, AgentMode, ApiKeyConfig,
+        BatchCreationResult, CreateAgentParams, CreateAgentSwarmParams, CreateAgentsParams,
+        DeploymentConfig, DeploymentTarget, LlmProvider, TeeConfig,
     },
+    PhalaCloud, RateLimiter, RateLimits,
 };
 use std::env;
 
@@ -28,17 +35,41 @@ async fn test_create_agent_no_tee() {
         agent_config: AgentConfig {
             mode: AgentMode::Autonomous,
             model: "gpt-4o-mini".to_string(),
+            llm_provider: LlmProvider::OpenAI,
+            azure_openai: None,
+            local_model: None,
+            system_prompt: None,
+            temperature: None,
+            max_tokens: None,
+            network_id: "base-sepolia".to_string(),
+            schedule: None,
+            wallet: Default::default(),
         },
+        action_providers: vec![ActionProvider::Wallet],
+        budget: None,
+        allowed_actions: None,
         deployment_config: DeploymentConfig {
-            tee_enabled: false,
+            target: DeploymentTarget::local(Some(3000)),
             docker_compose_path: None,
-            http_port: Some(3000),
+            egress: None,
+            image: Default::default(),
+            env: Default::default(),
+            size: None,
+            resources: None,
         },
+        template_id: None,
         api_key_config: ApiKeyConfig {
-            openai_api_key: Some(env::var("OPENAI_API_KEY").unwrap()),
-            cdp_api_key_name: Some(env::var("CDP_API_KEY_NAME").unwrap()),
-            cdp_api_key_private_key: Some(env::var("CDP_API_KEY_PRIVATE_KEY").unwrap()),
+            openai_api_key: Some(env::var("OPENAI_API_KEY").unwrap().into()),
+            anthropic_api_key: None,
+            azure_openai_api_key: None,
+            cdp_api_key_name: Some(env::var("CDP_API_KEY_NAME").unwrap().into()),
+            cdp_api_key_private_key: Some(env::var("CDP_API_KEY_PRIVATE_KEY").unwrap().into()),
+            cdp_wallet_secret: None,
+            wallet_private_key: None,
+            telegram_bot_token: None,
+            discord_bot_token: None,
         },
+        callback_url: None,
     };
 
     // Serialize params
@@ -58,7 +89,14 @@ async fn test_create_agent_no_tee() {
     // Assertions
     assert!(!result.agent_id.is_empty(), "Agent ID should not be empty");
     assert_eq!(result.files_created.len(), 3, "Should have created 3 files");
-    assert!(result.tee_pubkey.is_none(), "TEE public key should be None");
+    assert!(result.tee.is_none(), "TEE app should be None");
+    assert_eq!(result.network_id, "base-sepolia");
+    let template = result.template.expect("Template should be recorded");
+    assert_eq!(template.id, "starter");
+    assert_eq!(template.version, "1.0.0");
+    assert!(result
+        .checksums
+        .is_some_and(|checksums| checksums.compose.starts_with("sha256:")));
 }
 
 /// Test agent creation with TEE enabled
@@ -76,13 +114,11 @@ async fn test_create_agent_with_tee() {
     }
 
     // Enable TEE and set required config
-    context.tee_enabled = Some(true);
-    context.phala_tee_api_key =
-        Some(env::var("PHALA_CLOUD_API_KEY").unwrap_or_else(|_| "test-tee-key".to_string()));
-    context.phala_tee_api_endpoint = Some(
-        env::var("PHALA_CLOUD_API_ENDPOINT")
+    context.phala_cloud = Some(PhalaCloud {
+        api_key: env::var("PHALA_CLOUD_API_KEY").unwrap_or_else(|_| "test-tee-key".to_string()),
+        api_endpoint: env::var("PHALA_CLOUD_API_ENDPOINT")
             .unwrap_or_else(|_| "https://cloud-api.phala.network/api/v1".to_string()),
-    );
+    });
 
     // Create agent parameters with TEE enabled
     let params = CreateAgentParams {
@@ -90,17 +126,41 @@ async fn test_create_agent_with_tee() {
         agent_config: AgentConfig {
             mode: AgentMode::Autonomous,
             model: "gpt-4o-mini".to_string(),
+            llm_provider: LlmProvider::OpenAI,
+            azure_openai: None,
+            local_model: None,
+            system_prompt: None,
+            temperature: None,
+            max_tokens: None,
+            network_id: "base-sepolia".to_string(),
+            schedule: None,
+            wallet: Default::default(),
         },
+        action_providers: vec![ActionProvider::Wallet],
+        budget: None,
+        allowed_actions: None,
         deployment_config: DeploymentConfig {
-            tee_enabled: true,
+            target: DeploymentTarget::Tee(TeeConfig::default()),
             docker_compose_path: None,
-            http_port: Some(3000),
+            egress: None,
+            image: Default::default(),
+            env: Default::default(),
+            size: None,
+            resources: None,
         },
+        template_id: None,
         api_key_config: ApiKeyConfig {
             openai_api_key: None,
+            anthropic_api_key: None,
+            azure_openai_api_key: None,
             cdp_api_key_name: None,
             cdp_api_key_private_key: None,
+            cdp_wallet_secret: None,
+            wallet_private_key: None,
+            telegram_bot_token: None,
+            discord_bot_token: None,
         },
+        callback_url: None,
     };
 
     // Serialize params
@@ -120,8 +180,243 @@ async fn test_create_agent_with_tee() {
     // Assertions
     assert!(!result.agent_id.is_empty(), "Agent ID should not be empty");
     assert_eq!(result.files_created.len(), 3, "Should have created 3 files");
-    assert!(
-        result.tee_pubkey.is_some(),
-        "TEE public key should be present"
-    );
+    assert!(result.tee.is_some(), "TEE app should be present");
+}
+
+/// Test that swarm agents get sequential, non-overlapping ports
+#[test]
+fn test_swarm_port_allocation() {
+    let mut params = CreateAgentSwarmParams {
+        name: "Swarm".to_string(),
+        count: 3,
+        agent_config: AgentConfig {
+            mode: AgentMode::Chat,
+            model: "gpt-4o-mini".to_string(),
+            llm_provider: LlmProvider::OpenAI,
+            azure_openai: None,
+            local_model: None,
+            system_prompt: None,
+            temperature: None,
+            max_tokens: None,
+            network_id: "base-sepolia".to_string(),
+            schedule: None,
+            wallet: Default::default(),
+        },
+        action_providers: vec![ActionProvider::Wallet],
+        budget: None,
+        allowed_actions: None,
+        deployment_config: DeploymentConfig {
+            target: DeploymentTarget::local(Some(4000)),
+            docker_compose_path: None,
+            egress: None,
+            image: Default::default(),
+            env: Default::default(),
+            size: None,
+            resources: None,
+        },
+        template_id: None,
+        api_key_config: ApiKeyConfig {
+            openai_api_key: None,
+            anthropic_api_key: None,
+            azure_openai_api_key: None,
+            cdp_api_key_name: None,
+            cdp_api_key_private_key: None,
+            cdp_wallet_secret: None,
+            wallet_private_key: None,
+            telegram_bot_token: None,
+            discord_bot_token: None,
+        },
+        per_agent_api_keys: None,
+    };
+
+    let agents = params.agent_params().expect("Swarm should expand");
+    let ports: Vec<_> = agents
+        .iter()
+        .map(|agent| agent.deployment_config.target.http_port())
+        .collect();
+    assert_eq!(ports, vec![Some(4000), Some(4002), Some(4004)]);
+    assert_eq!(agents[2].name, "Swarm-3");
+
+    params.per_agent_api_keys = Some(vec![params.api_key_config.clone()]);
+    assert!(params.agent_params().is_err(), "Key count must match");
+
+    params.per_agent_api_keys = None;
+    params.deployment_config.target = DeploymentTarget::local(Some(u16::MAX - 2));
+    assert!(params.agent_params().is_err(), "Ports must not overflow");
+}
+
+/// Test that swarms and batches are held to the same caller signature policy as single agents
+#[tokio::test]
+async fn test_bulk_creation_requires_signature() {
+    let (mut context, _temp_dir, _missing) = setup_test_env();
+    context.require_caller_signature = Some(true);
+    let swarm = serde_json::json!({
+        "name": "swarm",
+        "count": 2,
+        "agent_config": { "mode": "Chat", "model": "gpt-4o-mini" },
+        "api_key_config": { "openai_api_key": "sk-test" }
+    });
+
+    let result = handle_create_agent_swarm(serde_json::to_vec(&swarm).unwrap(), &context).await;
+    assert!(matches!(
+        result,
+        Err(BlueprintError::Params(e)) if e.contains("must be signed")
+    ));
+
+    let batch = serde_json::json!({ "agents": [{
+        "name": "batch",
+        "agent_config": { "mode": "Chat", "model": "gpt-4o-mini" },
+        "api_key_config": { "openai_api_key": "sk-test" }
+    }] });
+    let result = handle_create_agents(serde_json::to_vec(&batch).unwrap(), &context).await;
+    assert!(matches!(
+        result,
+        Err(BlueprintError::Params(e)) if e.contains("must be signed")
+    ));
+}
+
+/// Test that swarms count against the caller's hourly call limit
+#[tokio::test]
+async fn test_swarm_rate_limit() {
+    let (mut context, _temp_dir, _missing) = setup_test_env();
+    context.rate_limiter = Some(RateLimiter::new(RateLimits {
+        max_calls_per_hour: Some(1),
+        max_agents_per_owner: None,
+    }));
+
+    // The first call is counted even though its swarm is invalid
+    let swarm = serde_json::to_vec(&serde_json::json!({ "name": "swarm", "count": 0 })).unwrap();
+    let result = handle_create_agent_swarm(swarm.clone(), &context).await;
+    assert!(matches!(result, Err(BlueprintError::Params(_))));
+    let result = handle_create_agent_swarm(swarm, &context).await;
+    assert!(matches!(result, Err(BlueprintError::Quota(_))));
+}
+
+/// Test that a batch creates its valid agents on distinct ports and reports the others
+#[tokio::test]
+async fn test_create_agents_batch() {
+    let (mut context, temp_dir, _missing) = setup_test_env();
+    let range = PortRange {
+        start: 43000,
+        end: 43020,
+    };
+    context.port_allocator = Some(PortAllocator::new(
+        range,
+        temp_dir.join(".ports.json"),
+        context.agent_registry.clone().unwrap(),
+    ));
+
+    let agent = |name: &str| CreateAgentParams {
+        name: name.to_string(),
+        agent_config: AgentConfig {
+            mode: AgentMode::Chat,
+            model: "gpt-4o-mini".to_string(),
+            llm_provider: LlmProvider::OpenAI,
+            azure_openai: None,
+            local_model: None,
+            system_prompt: None,
+            temperature: None,
+            max_tokens: None,
+            network_id: "base-sepolia".to_string(),
+            schedule: None,
+            wallet: Default::default(),
+        },
+        action_providers: vec![ActionProvider::Wallet],
+        budget: None,
+        allowed_actions: None,
+        deployment_config: DeploymentConfig {
+            target: DeploymentTarget::local(None),
+            docker_compose_path: None,
+            egress: None,
+            image: Default::default(),
+            env: Default::default(),
+            size: None,
+            resources: None,
+        },
+        template_id: None,
+        api_key_config: ApiKeyConfig {
+            openai_api_key: Some("sk-test".to_string().into()),
+            anthropic_api_key: None,
+            azure_openai_api_key: None,
+            cdp_api_key_name: Some("cdp-key".to_string().into()),
+            cdp_api_key_private_key: Some("cdp-secret".to_string().into()),
+            cdp_wallet_secret: None,
+            wallet_private_key: None,
+            telegram_bot_token: None,
+            discord_bot_token: None,
+        },
+        callback_url: None,
+    };
+    let agents = vec![agent("First"), agent(""), agent("Third")];
+    let params = serde_json::to_vec(&CreateAgentsParams { agents }).unwrap();
+
+    let result: BatchCreationResult =
+        serde_json::from_slice(&handle_create_agents(params, &context).await.unwrap()).unwrap();
+    assert_eq!((result.created, result.failed), (2, 1));
+    assert!(matches!(
+        result.items[1].error,
+        Some(BlueprintError::Params(_))
+    ));
+
+    // Created agents hold distinct ports from the range; the failed one's are freed
+    let registry = context.agent_registry.as_ref().unwrap();
+    let ports: Vec<u16> = [&result.items[0], &result.items[2]]
+        .iter()
+        .map(|item| {
+            let agent_id = &item.agent.as_ref().unwrap().agent_id;
+            registry.get(agent_id).unwrap().http_port
+        })
+        .collect();
+    assert_ne!(ports[0], ports[1]);
+    assert!(ports
+        .iter()
+        .all(|port| (range.start..range.end).contains(port)));
+    assert_eq!(registry.len(), 2);
+
+    // Oversized batches are rejected outright
+    let agents = vec![agent("Agent"); CreateAgentsParams::MAX_BATCH_SIZE + 1];
+    let params = serde_json::to_vec(&CreateAgentsParams { agents }).unwrap();
+    assert!(handle_create_agents(params, &context).await.is_err());
+
+    // So are batches naming a template that doesn't exist, checked for each agent
+    let mut unknown = agent("Unknown");
+    unknown.template_id = Some("missing".to_string());
+    let mut python = agent("Python");
+    python.template_id = Some("python".to_string());
+    let params = serde_json::to_vec(&CreateAgentsParams {
+        agents: vec![python, unknown],
+    })
+    .unwrap();
+    assert!(matches!(
+        handle_create_agents(params, &context).await,
+        Err(BlueprintError::Params(e)) if e.contains("Unknown template missing")
+    ));
+}
+
+/// Test that the agent's model is written to the starter template's .env
+#[test]
+fn test_starter_env_model() {
+    let params: CreateAgentParams = serde_json::from_value(serde_json::json!({
+        "name": "model",
+        "agent_config": { "mode": "Chat", "model": "gpt-4o", "network_id": "base-sepolia" },
+        "deployment_config": {
+            "target": { "type": "local", "http_port": null },
+            "docker_compose_path": null
+        },
+        "api_key_config": { "openai_api_key": null, "cdp_api_key_name": null, "cdp_api_key_private_key": null }
+    }))
+    .unwrap();
+
+    let template = std::fs::read_to_string(
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("templates/starter/.env.example"),
+    )
+    .expect("Failed to read the starter .env.example");
+    let env_content = render_env_file(&template, &params, None).unwrap();
+
+    let model_lines: Vec<&str> = env_content
+        .lines()
+        .filter(|line| line.starts_with("MODEL="))
+        .collect();
+    assert_eq!(model_lines, ["MODEL=gpt-4o"]);
+    assert_eq!(parse_env_content(&env_content)["MODEL"], "gpt-4o");
 }
@@ -0,0 +1,544 @@
+use crate::{
+    create_agent::{env_context, render_compose_file},
+    docker,
+    envelope::decode_params,
+    error::BlueprintError,
+    helpers::compose_command,
+    ports::{PortAllocator, PortRange},
+    retention::LogRetention,
+    stop_agent::handle_stop_agent,
+    templates::{self, Template},
+    tests::setup_test_env,
+    types::{
+        AgentConfig, AgentImage, AgentMode, AgentStopResult, AzureOpenAIConfig, CreateAgentParams,
+        CreateAgentSwarmParams, CreateAgentsParams, DeployAgentParams, DeploymentTarget,
+        EgressPolicy, LlmProvider, TeeConfig, WebSocketPort,
+    },
+    wallet,
+    warm_pool::{self, StandbyContainer, WarmPool, WarmPoolConfig},
+    DeployQueue,
+};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use tempfile::tempdir;
+
+#[test]
+fn test_deployment_target() {
+    // Legacy flags are folded into the target
+    let bare = serde_json::json!({
+        "name": "legacy-tee",
+        "agent_config": { "mode": "Chat", "model": "gpt-4o-mini" },
+        "deployment_config": { "tee_enabled": true, "docker_compose_path": null, "http_port": null },
+        "api_key_config": { "openai_api_key": "sk-test", "cdp_api_key_name": null, "cdp_api_key_private_key": null }
+    });
+    let params: CreateAgentParams = decode_params(&serde_json::to_vec(&bare).unwrap()).unwrap();
+    assert_eq!(
+        params.deployment_config.target,
+        DeploymentTarget::Tee(TeeConfig::default())
+    );
+
+    let deploy = serde_json::json!({
+        "agent_id": "agent-1",
+        "encrypted_env": "ciphertext",
+        "tee_pubkey": "pubkey",
+        "tee_app_id": "app-1",
+        "tee_salt": "salt"
+    });
+    let params: DeployAgentParams = decode_params(&serde_json::to_vec(&deploy).unwrap()).unwrap();
+    let tee = params.tee.unwrap();
+    assert_eq!(tee.app_id, "app-1");
+    assert_eq!(tee.encrypted_env.expose(), "ciphertext");
+
+    let swarm = serde_json::json!({
+        "name": "legacy-swarm",
+        "count": 2,
+        "agent_config": { "mode": "Chat", "model": "gpt-4o-mini" },
+        "deployment_config": { "tee_enabled": false, "http_port": 4000 },
+        "api_key_config": { "openai_api_key": "sk-test" }
+    });
+    let params: CreateAgentSwarmParams =
+        decode_params(&serde_json::to_vec(&swarm).unwrap()).unwrap();
+    assert_eq!(
+        params.deployment_config.target,
+        DeploymentTarget::local(Some(4000))
+    );
+
+    let batch = serde_json::json!({ "agents": [bare] });
+    let params: CreateAgentsParams = decode_params(&serde_json::to_vec(&batch).unwrap()).unwrap();
+    assert_eq!(
+        params.agents[0].deployment_config.target,
+        DeploymentTarget::Tee(TeeConfig::default())
+    );
+
+    let partial = serde_json::json!({ "agent_id": "agent-1", "tee_app_id": "app-1" });
+    assert!(decode_params::<DeployAgentParams>(&serde_json::to_vec(&partial).unwrap()).is_err());
+
+    // The target is recorded in the agent's variables
+    let target = DeploymentTarget::Tee(TeeConfig {
+        vcpu: 4,
+        ..Default::default()
+    });
+    let vars = target
+        .to_env_vars()
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+    assert_eq!(
+        DeploymentTarget::from_env_vars(&vars).unwrap(),
+        Some(target)
+    );
+    assert_eq!(
+        DeploymentTarget::from_env_vars(&Default::default()).unwrap(),
+        None
+    );
+
+    assert!(TeeConfig::default().validate().is_ok());
+    assert!(TeeConfig {
+        vcpu: TeeConfig::MAX_VCPU + 1,
+        ..Default::default()
+    }
+    .validate()
+    .is_err());
+}
+
+#[test]
+fn test_egress_policy() {
+    let mut config = AgentConfig {
+        mode: AgentMode::Telegram,
+        model: "claude-3-5-sonnet".to_string(),
+        llm_provider: LlmProvider::Anthropic,
+        azure_openai: None,
+        local_model: None,
+        system_prompt: None,
+        temperature: None,
+        max_tokens: None,
+        network_id: "base-sepolia".to_string(),
+        schedule: None,
+        wallet: Default::default(),
+    };
+    let policy = EgressPolicy {
+        allowed_hosts: vec!["base-sepolia.g.alchemy.com".to_string()],
+    };
+    assert!(policy.validate().is_ok());
+    let hosts = policy.hosts(&config);
+    for host in [
+        "api.anthropic.com",
+        "api.telegram.org",
+        "api.cdp.coinbase.com",
+        "base-sepolia.g.alchemy.com",
+    ] {
+        assert!(hosts.contains(&host.to_string()), "{} missing", host);
+    }
+    assert!(!hosts.contains(&"api.openai.com".to_string()));
+
+    config.llm_provider = LlmProvider::AzureOpenAI;
+    config.azure_openai = Some(AzureOpenAIConfig {
+        endpoint: "https://my-resource.openai.azure.com".to_string(),
+        deployment_name: "gpt-4o".to_string(),
+        api_version: "2024-06-01".to_string(),
+    });
+    assert!(policy
+        .hosts(&config)
+        .contains(&"my-resource.openai.azure.com".to_string()));
+
+    // Host names end up in the firewall script, so nothing else is accepted
+    let invalid = EgressPolicy {
+        allowed_hosts: vec!["example.com; rm -rf /".to_string()],
+    };
+    assert!(invalid.validate().is_err());
+
+    let temp_dir = tempdir().unwrap();
+    let compose_path = temp_dir.path().join("docker-compose.yml");
+    fs::write(&compose_path, "services:\n  agent:\n    image: agent\n").unwrap();
+    docker::add_egress_firewall(&compose_path, &hosts).unwrap();
+    let compose = fs::read_to_string(&compose_path).unwrap();
+    assert!(compose.contains("egress-firewall"));
+    assert!(compose.contains("service:agent"));
+    assert!(compose.contains("api.anthropic.com"));
+
+    // The firewall sidecar is the one service allowed a capability
+    assert!(docker::check_compose_file(&compose_path).is_ok());
+}
+
+#[test]
+fn test_compose_command_env() {
+    // docker-compose only sees the operator's variables needed to reach Docker
+    std::env::set_var("AGENTKIT_TEST_OPERATOR_KEY", "secret");
+    std::env::set_var("DOCKER_CONFIG", "/tmp/docker-config");
+    let command = compose_command(Path::new("."));
+    let envs: Vec<_> = command
+        .as_std()
+        .get_envs()
+        .map(|(name, _)| name.to_owned())
+        .collect();
+    assert!(envs.iter().any(|name| name == "DOCKER_CONFIG"));
+    assert!(!envs.iter().any(|name| name == "AGENTKIT_TEST_OPERATOR_KEY"));
+    std::env::remove_var("AGENTKIT_TEST_OPERATOR_KEY");
+    std::env::remove_var("DOCKER_CONFIG");
+}
+
+#[test]
+fn test_compose_policy() {
+    let check = |services: &str| {
+        let compose = format!("services:\n  agent:\n    image: agent\n{}", services);
+        docker::check_compose_policy(&serde_yaml::from_str(&compose).unwrap())
+    };
+    for allowed in [
+        "",
+        "    volumes:\n      - data:/data\n      - ./wallet:/wallet\n      - /cache\n",
+        "    volumes:\n      - type: bind\n        source: ./config\n        target: /config\n",
+        "    network_mode: bridge\n    privileged: false\n    build: .\n",
+        "    env_file: .env\n",
+    ] {
+        assert!(check(allowed).is_ok(), "{:?}", allowed);
+    }
+    for denied in [
+        "    privileged: true\n",
+        "    network_mode: host\n",
+        "    pid: host\n",
+        "    ipc: host\n",
+        "    network_mode: container:other-agent\n",
+        "    cap_add:\n      - SYS_ADMIN\n",
+        "    devices:\n      - /dev/kvm:/dev/kvm\n",
+        "    security_opt:\n      - seccomp:unconfined\n",
+        "    volumes:\n      - /var/run/docker.sock:/var/run/docker.sock\n",
+        "    volumes:\n      - ../other-agent:/other\n",
+        "    volumes:\n      - ./data/../../etc:/etc\n",
+        "    volumes:\n      - ~/.ssh:/ssh\n",
+        "    volumes:\n      - ${HOME}:/home\n",
+        "    volumes:\n      - type: bind\n        source: /\n        target: /host\n",
+        "    build:\n      context: /\n",
+        "    env_file: /etc/environment\n",
+        "  helper:\n    image: helper\n    cap_add:\n      - NET_ADMIN\n",
+        "  egress-firewall:\n    image: alpine\n    network_mode: host\n    cap_add:\n      - NET_ADMIN\n",
+        "volumes:\n  host:\n    driver_opts:\n      type: none\n      o: bind\n      device: /\n",
+    ] {
+        assert!(check(denied).is_err(), "{:?}", denied);
+    }
+
+    // Agents can't be created from a template that breaks the policy
+    let params = CreateAgentParams::builder()
+        .name("escape")
+        .chat()
+        .model("gpt-4o-mini")
+        .build()
+        .unwrap();
+    let template = "services:\n  agent:\n    image: agent\n    privileged: true\n";
+    assert!(
+        render_compose_file(template, &params, &LogRetention::default())
+            .is_err_and(|e| e.contains("privileged"))
+    );
+}
+
+#[test]
+fn test_agent_image() {
+    let digest = format!("sha256:{}", "ab".repeat(32));
+    for (image, valid) in [
+        (AgentImage::Template, true),
+        (
+            AgentImage::Dockerfile {
+                path: "docker/Dockerfile.gpu".to_string(),
+            },
+            true,
+        ),
+        (
+            AgentImage::Dockerfile {
+                path: "../Dockerfile".to_string(),
+            },
+            false,
+        ),
+        (
+            AgentImage::Dockerfile {
+                path: "/etc/Dockerfile".to_string(),
+            },
+            false,
+        ),
+        (
+            AgentImage::Image {
+                reference: "ghcr.io/org/agent:1.2".to_string(),
+            },
+            true,
+        ),
+        (
+            AgentImage::Image {
+                reference: format!("localhost:5000/agent@{}", digest),
+            },
+            true,
+        ),
+        (
+            AgentImage::Image {
+                reference: "ghcr.io/org/agent@sha256:abc".to_string(),
+            },
+            false,
+        ),
+        (
+            AgentImage::Image {
+                reference: "--help".to_string(),
+            },
+            false,
+        ),
+    ] {
+        assert_eq!(image.validate().is_ok(), valid, "{:?}", image);
+    }
+
+    // Builds and prebuilt images replace the template's image in the agent's compose file
+    let template = "services:\n  agent:\n    image: agent\n";
+    let params = CreateAgentParams::builder()
+        .name("gpu")
+        .chat()
+        .model("gpt-4o-mini")
+        .dockerfile("Dockerfile.gpu")
+        .build()
+        .unwrap();
+    let rendered = render_compose_file(template, &params, &LogRetention::default()).unwrap();
+    assert!(rendered.contains("dockerfile: Dockerfile.gpu"));
+    assert!(!rendered.contains("image: agent"));
+    let env = env_context(&params, None).unwrap();
+    assert_eq!(env["AGENT_DOCKERFILE"], "Dockerfile.gpu");
+
+    let reference = format!("ghcr.io/org/agent@{}", digest);
+    let params = CreateAgentParams::builder()
+        .name("prebuilt")
+        .chat()
+        .model("gpt-4o-mini")
+        .image(&reference)
+        .build()
+        .unwrap();
+    let rendered = render_compose_file(template, &params, &LogRetention::default()).unwrap();
+    assert!(rendered.contains(&format!("image: {}", reference)));
+    assert!(!rendered.contains("build:"));
+
+    // TEE deployments can't build images
+    assert!(CreateAgentParams::builder()
+        .name("tee")
+        .chat()
+        .model("gpt-4o-mini")
+        .tee()
+        .dockerfile("Dockerfile.gpu")
+        .build()
+        .is_err());
+
+    // Pulled images are pinned to their repository's digest
+    let repo_digests = format!("other/agent@{}\nghcr.io/org/agent@{}\n", digest, digest);
+    assert_eq!(
+        docker::select_repo_digest("ghcr.io/org/agent:1.2", &repo_digests),
+        Some(reference)
+    );
+    assert_eq!(
+        docker::select_repo_digest("docker.io/library/redis:7", &format!("redis@{}", digest)),
+        Some(format!("redis@{}", digest))
+    );
+    assert_eq!(
+        docker::select_repo_digest("localhost:5000/agent", &repo_digests),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_deploy_queue() {
+    let queue = DeployQueue::new(1);
+    let first = queue.acquire("agent-1").await;
+    assert_eq!(queue.running(), 1);
+
+    // Other agents wait for a slot
+    let waiting = tokio::spawn({
+        let queue = queue.clone();
+        async move {
+            let _permit = queue.acquire("agent-2").await;
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(queue.waiting(), 1);
+    assert!(!waiting.is_finished());
+    drop(first);
+    waiting.await.unwrap();
+    assert_eq!((queue.running(), queue.waiting()), (0, 0));
+
+    // Deployments of the same agent never overlap, even with slots to spare
+    let queue = DeployQueue::new(4);
+    let first = queue.acquire("agent-1").await;
+    let second = tokio::spawn({
+        let queue = queue.clone();
+        async move {
+            let _permit = queue.acquire("agent-1").await;
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!second.is_finished());
+    assert_eq!(queue.running(), 1);
+    drop(first);
+    second.await.unwrap();
+    assert_eq!(queue.running(), 0);
+}
+
+#[test]
+fn test_resource_presets() {
+    // Templates name their sizes; unknown sizes list the ones there are
+    let starter = Template::load(Path::new(templates::TEMPLATES_DIR), "starter").unwrap();
+    let medium = starter.preset("medium").unwrap();
+    assert_eq!((medium.vcpu, medium.memory_mb), (2, 2048));
+    assert!(starter
+        .preset("huge")
+        .is_err_and(|e| e.contains("small") && e.contains("large")));
+    assert!(starter.info().presets.contains_key("large"));
+
+    // Local agents' containers are limited to their resources
+    let template = "services:\n  agent:\n    image: agent\n";
+    let params = CreateAgentParams::builder()
+        .name("sized")
+        .chat()
+        .model("gpt-4o-mini")
+        .resources(medium.clone())
+        .build()
+        .unwrap();
+    let rendered = render_compose_file(template, &params, &LogRetention::default()).unwrap();
+    assert!(rendered.contains("cpus: '2'") || rendered.contains("cpus: \"2\""));
+    assert!(rendered.contains("mem_limit: 2048m"));
+
+    // A size is a preset name, and is never combined with numbers given by hand
+    let builder = || {
+        CreateAgentParams::builder()
+            .name("sized")
+            .chat()
+            .model("gpt-4o-mini")
+    };
+    assert!(builder().size("medium").build().is_ok());
+    assert!(builder().size("Medium!").build().is_err());
+    assert!(builder()
+        .size("medium")
+        .resources(TeeConfig::default())
+        .build()
+        .is_err());
+    assert!(builder()
+        .resources(TeeConfig {
+            vcpu: 64,
+            ..TeeConfig::default()
+        })
+        .build()
+        .is_err());
+    assert!(builder()
+        .target(DeploymentTarget::Tee(TeeConfig {
+            vcpu: 4,
+            ..TeeConfig::default()
+        }))
+        .size("medium")
+        .build()
+        .is_err());
+}
+
+#[test]
+fn test_warm_pool() {
+    let (mut context, temp_dir, _missing) = setup_test_env();
+    let range = PortRange {
+        start: 42000,
+        end: 42010,
+    };
+    let allocator = PortAllocator::new(
+        range,
+        temp_dir.join(".ports.json"),
+        context.agent_registry.clone().unwrap(),
+    );
+    context.port_allocator = Some(allocator.clone());
+
+    // The agent's ports from creation are replaced by those the standby container was made with
+    let created = allocator
+        .allocate("agent-1", None, WebSocketPort::Adjacent)
+        .unwrap();
+    let reserved = allocator.reserve("coinbase-agent-pool-1").unwrap();
+    assert_ne!(created, reserved);
+    let pool = WarmPool::new(WarmPoolConfig {
+        size: 1,
+        image: "agent:latest".to_string(),
+    });
+    let standby = StandbyContainer {
+        container_name: "coinbase-agent-pool-1".to_string(),
+        config_dir: temp_dir.join(".pool/coinbase-agent-pool-1"),
+        ports: reserved.clone(),
+    };
+    pool.add(standby.clone());
+    assert_eq!(
+        pool.claim("agent-1", &context).unwrap(),
+        Some(standby.clone())
+    );
+    assert_eq!(
+        context.agent_registry.as_ref().unwrap().get("agent-1"),
+        Some(reserved.clone())
+    );
+    assert!(pool.claim("agent-2", &context).unwrap().is_none());
+
+    // Standby containers publish their ports as they are, with the operator's log rotation
+    let args = standby
+        .create_args("agent:latest", "127.0.0.1", &LogRetention::default())
+        .join(" ");
+    assert!(args.contains(&format!("-p 127.0.0.1:{0}:{0}", reserved.http_port)));
+    assert!(args.contains("--log-opt max-size=10m"));
+    assert!(args.contains(&format!("-v {}:/wallet", standby.wallet_dir().display())));
+    assert!(args.contains("-e WALLET_DATA_FILE=/wallet/wallet_data.json"));
+
+    // The agent's wallet directory becomes the container's, keeping wallet data it already had
+    let agent_dir = temp_dir.join("agent-1");
+    wallet::create_dir(&agent_dir).unwrap();
+    wallet::create_dir(&standby.config_dir).unwrap();
+    fs::write(
+        agent_dir.join(".wallet/wallet_data.json"),
+        r#"{"walletId":"w1"}"#,
+    )
+    .unwrap();
+    warm_pool::link_wallet_dir(&standby, &agent_dir).unwrap();
+    assert!(agent_dir.join(".wallet").is_symlink());
+    assert!(standby.wallet_dir().join("wallet_data.json").exists());
+    fs::write(
+        standby.wallet_dir().join("wallet_data.json"),
+        r#"{"walletId":"w2"}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(agent_dir.join(".wallet/wallet_data.json")).unwrap(),
+        r#"{"walletId":"w2"}"#
+    );
+
+    // Agents with sidecars or an egress firewall need docker-compose
+    let compose_path = temp_dir.join("docker-compose.yml");
+    fs::write(&compose_path, "services:\n  agent:\n    image: agent\n").unwrap();
+    assert!(warm_pool::supports_compose(&compose_path));
+    fs::write(
+        &compose_path,
+        "services:\n  agent:\n    image: agent\n  ollama:\n    image: ollama\n",
+    )
+    .unwrap();
+    assert!(!warm_pool::supports_compose(&compose_path));
+}
+
+#[tokio::test]
+async fn test_stop_agent() {
+    let (mut context, temp_dir, _missing) = setup_test_env();
+    let agent_dir = temp_dir.join("stopped-agent");
+    fs::create_dir_all(&agent_dir).unwrap();
+    fs::write(agent_dir.join(".env"), "AGENT_OWNER=alice\n").unwrap();
+    let params =
+        |agent_id: &str| serde_json::to_vec(&serde_json::json!({ "agent_id": agent_id })).unwrap();
+
+    assert!(matches!(
+        handle_stop_agent(params("missing-agent"), &context).await,
+        Err(BlueprintError::Params(_))
+    ));
+
+    // Requests from the management API act as its configured caller
+    context.api_caller = Some("mallory".to_string());
+    assert_eq!(context.caller().as_deref(), Some("mallory"));
+    assert!(matches!(
+        handle_stop_agent(params("stopped-agent"), &context).await,
+        Err(BlueprintError::Unauthorized(_))
+    ));
+
+    context.api_caller = Some("alice".to_string());
+    let result: AgentStopResult = serde_json::from_slice(
+        &handle_stop_agent(params("stopped-agent"), &context)
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(result.agent_id, "stopped-agent");
+    assert!(!result.was_running);
+}
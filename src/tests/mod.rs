@@ -1,19 +1,43 @@
 use crate::{
-    types::{AgentConfig, AgentMode},
-    ServiceContext,
+    signing::NonceRegistry,
+    types::{AgentConfig, AgentMode, LlmProvider},
+    AgentRegistry, SealingKey, ServiceContext,
 };
 use blueprint_sdk::config::GadgetConfiguration;
 use dotenv::dotenv;
+use std::collections::HashMap;
 use std::env;
 use std::fs;
+use std::path::Path;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::{collections::HashMap, path::Path};
 use tempfile::tempdir;
 use tokio::process::Command as TokioCommand;
 
+pub mod agent_endpoint_tests;
+pub mod agent_status_tests;
+pub mod api_tests;
+pub mod config_tests;
 pub mod create_agent_tests;
 pub mod deploy_agent_tests;
+pub mod deployment_tests;
+pub mod monitoring_tests;
+pub mod params_tests;
+pub mod ports_tests;
+pub mod request_tests;
+pub mod secrets_tests;
+pub mod storage_tests;
+pub mod templates_tests;
+pub mod wallet_tests;
+
+/// Makes `caller` the caller of the context's job, as the caller pre-processor would
+pub fn set_caller(context: &mut ServiceContext, caller: &str) {
+    context.call_id = Some(1);
+    context.job_callers = Some(Arc::new(Mutex::new(HashMap::from([(
+        1,
+        caller.to_string(),
+    )]))));
+}
 
 /// Log a message with timestamp for test output
 pub fn log(msg: &str) {
@@ -24,7 +48,7 @@ pub fn log(msg: &str) {
 async fn clean_existing_container(agent_dir: &Path) -> Result<(), String> {
     log("Cleaning up any existing containers");
     let cleanup_output = TokioCommand::new("docker-compose")
-        .args(&["down", "--remove-orphans"])
+        .args(["down", "--remove-orphans"])
         .current_dir(agent_dir)
         .output()
         .await;
@@ -98,6 +122,13 @@ pub fn setup_test_env() -> (ServiceContext, PathBuf, Vec<String>) {
         "version: '3'\nservices:\n  agent:\n    build: .\n    ports:\n      - '3000:3000'\n    environment:\n      - PORT=3000\n      - OPENAI_API_KEY=${OPENAI_API_KEY}\n      - CDP_API_KEY_NAME=${CDP_API_KEY_NAME}\n      - CDP_API_KEY_PRIVATE_KEY=${CDP_API_KEY_PRIVATE_KEY}\n"
     ).expect("Failed to create docker-compose.yml");
 
+    // Describe the template
+    fs::write(
+        template_dir.join("template.toml"),
+        "name = \"Starter\"\nversion = \"1.0.0\"\nrequired_env = [\"CDP_API_KEY_NAME\"]\n",
+    )
+    .expect("Failed to create template.toml");
+
     // Create dummy files needed for the tests
     fs::write(
         template_dir.join("Dockerfile"),
@@ -105,18 +136,46 @@ pub fn setup_test_env() -> (ServiceContext, PathBuf, Vec<String>) {
     )
     .expect("Failed to create Dockerfile");
 
-    // Create an agent port map
-    let agent_ports = Arc::new(Mutex::new(HashMap::new()));
-
     // Create a minimal service context
     let context = ServiceContext {
         config: GadgetConfiguration::default(),
         call_id: None,
-        agent_ports: Some(agent_ports),
+        agent_registry: Some(AgentRegistry::new()),
+        tee_deployer: None,
+        port_allocator: None,
         agents_base_dir: Some(temp_dir.to_string_lossy().to_string()),
-        tee_enabled: Some(false),
-        phala_tee_api_key: Some("mock_api_key".to_string()),
-        phala_tee_api_endpoint: Some("https://example.com/api".to_string()),
+        phala_cloud: None,
+        http_config: None,
+        http_client: None,
+        require_budget: None,
+        report_lifecycle: None,
+        agent_quota: None,
+        job_callers: None,
+        service_owner: None,
+        api_caller: None,
+        sign_results: None,
+        coordination: None,
+        require_payment: None,
+        sealing_key: Some(SealingKey::from_bytes([7; 32])),
+        secret_resolvers: None,
+        require_caller_signature: None,
+        caller_nonces: Some(NonceRegistry::default()),
+        rate_limiter: None,
+        ingress: None,
+        agent_router: None,
+        exposure: None,
+        rpc_endpoints: None,
+        telemetry: None,
+        alerts: None,
+        callbacks: None,
+        health_monitor: None,
+        events: None,
+        heartbeat_metrics: None,
+        log_retention: None,
+        deploy_queue: None,
+        warm_pool: None,
+        artifact_store: None,
+        live_config: None,
     };
 
     (context, temp_dir, missing_requirements)
@@ -127,6 +186,15 @@ fn test_agent_config() {
     let config = AgentConfig {
         mode: AgentMode::Autonomous,
         model: "gpt-4o-mini".to_string(),
+        llm_provider: LlmProvider::OpenAI,
+        azure_openai: None,
+        local_model: None,
+        system_prompt: None,
+        temperature: None,
+        max_tokens: None,
+        network_id: "base-sepolia".to_string(),
+        schedule: None,
+        wallet: Default::default(),
     };
 
     assert!(matches!(config.mode, AgentMode::Autonomous));
@@ -217,11 +285,11 @@ services:
 
             // Verify pubkey response structure
             assert!(
-                pubkey_response.app_env_encrypt_pubkey.len() > 0,
+                !pubkey_response.app_env_encrypt_pubkey.is_empty(),
                 "Response should contain app_env_encrypt_pubkey"
             );
             assert!(
-                pubkey_response.app_id_salt.len() > 0,
+                !pubkey_response.app_id_salt.is_empty(),
                 "Response should contain app_id_salt"
             );
         }
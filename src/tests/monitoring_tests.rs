@@ -0,0 +1,444 @@
+use crate::{
+    alerts::{Alert, AlertConfig, AlertKind, AlertTracker, WebhookFormat},
+    diagnostics::handle_collect_diagnostics,
+    docker,
+    error::BlueprintError,
+    heartbeat::{Heartbeat, MAX_REPORTED_UNHEALTHY},
+    helpers::{fan_out, probe_agents},
+    monitor::{AgentHealth, MonitorAction, MonitorPolicy, MonitorState},
+    readiness::{self, ReadinessReport},
+    retention::{self, LogRetention},
+    status_api,
+    telemetry::{self, LogFormat, StageTimer},
+    tests::{set_caller, setup_test_env},
+    types::{AgentDeploymentResult, DeploymentTarget, DiagnosticsResult},
+    HealthBackoff, HttpConfig, PhalaCloud,
+};
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tempfile::tempdir;
+
+#[test]
+fn test_heartbeat_aggregation() {
+    let heartbeat = Heartbeat::from_health(vec![
+        ("agent-1".to_string(), true),
+        ("agent-2".to_string(), false),
+        ("agent-3".to_string(), true),
+        ("agent-4".to_string(), true),
+    ]);
+    assert_eq!(heartbeat.total_agents, 4);
+    assert_eq!(heartbeat.healthy_agents, 3);
+    assert_eq!(heartbeat.unhealthy_agents, vec!["agent-2".to_string()]);
+    assert_eq!(heartbeat.health_ratio(), 0.75);
+
+    assert_eq!(Heartbeat::from_health(Vec::new()).health_ratio(), 1.0);
+
+    // Heartbeats submitted on-chain name a bounded number of unhealthy agents
+    let many = Heartbeat::from_health((0..40).map(|i| (format!("agent-{}", i), false)).collect());
+    let capped = many.capped(MAX_REPORTED_UNHEALTHY);
+    assert_eq!(capped.unhealthy_agents.len(), MAX_REPORTED_UNHEALTHY);
+    assert_eq!(capped.omitted_unhealthy, 40 - MAX_REPORTED_UNHEALTHY);
+    assert_eq!(heartbeat.capped(MAX_REPORTED_UNHEALTHY), heartbeat);
+    assert!(!serde_json::to_string(&heartbeat)
+        .unwrap()
+        .contains("omitted_unhealthy"));
+
+    // They are also served as metrics
+    let metrics = heartbeat.to_metrics();
+    assert!(metrics.contains("# TYPE agentkit_agents gauge\nagentkit_agents 4\n"));
+    assert!(metrics.contains("agentkit_healthy_agents 3\n"));
+}
+
+#[test]
+fn test_log_retention() {
+    let retention = LogRetention {
+        diagnostics_max_files: 2,
+        ..LogRetention::default()
+    };
+    assert!(retention.validate().is_ok());
+    for max_size in ["", "m", "10mb", "ten"] {
+        let invalid = LogRetention {
+            max_size: max_size.to_string(),
+            ..LogRetention::default()
+        };
+        assert!(invalid.validate().is_err(), "{:?} was accepted", max_size);
+    }
+
+    // Every service's logs are rotated, sidecars included
+    let temp_dir = tempdir().unwrap();
+    let compose_path = temp_dir.path().join("docker-compose.yml");
+    fs::write(
+        &compose_path,
+        "services:\n  agent:\n    image: agent\n  ollama:\n    image: ollama\n",
+    )
+    .unwrap();
+    docker::add_log_rotation(&compose_path, &retention).unwrap();
+    let compose: serde_yaml::Value =
+        serde_yaml::from_str(&fs::read_to_string(&compose_path).unwrap()).unwrap();
+    for service in ["agent", "ollama"] {
+        let logging = &compose["services"][service]["logging"];
+        assert_eq!(logging["driver"], "json-file");
+        assert_eq!(logging["options"]["max-size"], "10m");
+        assert_eq!(logging["options"]["max-file"], "3");
+    }
+
+    // Only the newest diagnostics of each agent are kept
+    let agent_dir = temp_dir.path().join("agent-1");
+    for name in ["first", "second", "third"] {
+        retention::write_diagnostics(&agent_dir, &format!("{}.log", name), "logs").unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(
+        retention::sweep_diagnostics(temp_dir.path(), &retention).unwrap(),
+        1
+    );
+    let mut kept: Vec<String> = fs::read_dir(agent_dir.join(retention::DIAGNOSTICS_DIR))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    kept.sort();
+    assert_eq!(kept.len(), 2);
+    assert!(kept.iter().all(|name| !name.ends_with("-first.log")));
+}
+
+#[tokio::test]
+async fn test_collect_diagnostics() {
+    let (mut context, temp_dir, _missing) = setup_test_env();
+    set_caller(&mut context, "alice");
+    let agent_dir = temp_dir.join("diagnosed-agent");
+    fs::create_dir_all(&agent_dir).unwrap();
+    fs::write(
+        agent_dir.join(".env"),
+        "AGENT_OWNER=alice\nMODEL=gpt-4o-mini\nOPENAI_API_KEY=sk-leaked\n",
+    )
+    .unwrap();
+    fs::write(agent_dir.join("docker-compose.yml"), "services: {}\n").unwrap();
+    retention::write_diagnostics(&agent_dir, "health-check.log", "connection refused").unwrap();
+
+    let params = serde_json::to_vec(&serde_json::json!({ "agent_id": "diagnosed-agent" })).unwrap();
+    let result: DiagnosticsResult =
+        serde_json::from_slice(&handle_collect_diagnostics(params, &context).await.unwrap())
+            .unwrap();
+    for file in [
+        "container.txt",
+        "inspect.json",
+        "env.txt",
+        "docker-compose.yml",
+        "health.json",
+    ] {
+        assert!(result.files.contains(&file.to_string()), "{} missing", file);
+    }
+    assert!(result
+        .files
+        .iter()
+        .any(|file| file.starts_with("diagnostics/") && file.ends_with("-health-check.log")));
+
+    // The bundle unpacks to the listed files, with secrets redacted
+    let archive = fs::read(&result.archive_path).unwrap();
+    assert_eq!(archive.len() as u64, result.size_bytes);
+    let mut bundle = tar::Archive::new(flate2::read::GzDecoder::new(archive.as_slice()));
+    let mut env_listing = String::new();
+    let mut names = Vec::new();
+    for entry in bundle.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let name = entry.path().unwrap().display().to_string();
+        if name == "env.txt" {
+            std::io::Read::read_to_string(&mut entry, &mut env_listing).unwrap();
+        }
+        names.push(name);
+    }
+    assert_eq!(names, result.files);
+    assert!(env_listing.contains("MODEL=gpt-4o-mini"));
+    assert!(env_listing.contains("OPENAI_API_KEY=[REDACTED]"));
+    assert!(!env_listing.contains("sk-leaked"));
+
+    // Agent IDs name directories, so paths are rejected
+    let params = serde_json::to_vec(&serde_json::json!({ "agent_id": "../etc" })).unwrap();
+    assert!(matches!(
+        handle_collect_diagnostics(params, &context).await,
+        Err(BlueprintError::Params(_))
+    ));
+}
+
+#[test]
+fn test_json_logs_carry_job_and_agent_fields() {
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+    assert!("xml".parse::<LogFormat>().is_err());
+
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let subscriber = telemetry::subscriber(LogFormat::Json, move || writer.clone(), None);
+    tracing::subscriber::with_default(subscriber, || {
+        let _job = crate::job_span("deploy_agent", Some(42)).entered();
+        let _phase = telemetry::phase_span("docker_up", "agent-1").entered();
+        blueprint_sdk::logging::info!(agent_id = "agent-1", phase = "start", "Container started");
+    });
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let line: serde_json::Value = serde_json::from_str(output.lines().next().unwrap()).unwrap();
+    assert_eq!(line["message"], "Container started");
+    assert_eq!(line["agent_id"], "agent-1");
+    assert_eq!(line["phase"], "start");
+    assert_eq!(line["spans"][0]["job"], "deploy_agent");
+    assert_eq!(line["spans"][0]["call_id"], 42);
+    assert_eq!(line["spans"][1]["phase"], "docker_up");
+    assert_eq!(line["spans"][1]["agent_id"], "agent-1");
+}
+
+#[test]
+fn test_alert_payloads() {
+    let alert = Alert::new(
+        AlertKind::DeploymentFailed,
+        "agent-1",
+        Some(7),
+        "Health check failed",
+    );
+    let mut config = AlertConfig {
+        webhook_url: "https://hooks.example.com/alerts".to_string(),
+        format: WebhookFormat::Generic,
+        pagerduty_routing_key: None,
+        restart_threshold: 3,
+    };
+
+    let generic = config.payload(&alert);
+    assert_eq!(generic["kind"], "deployment_failed");
+    assert_eq!(generic["agent_id"], "agent-1");
+    assert_eq!(generic["call_id"], 7);
+    assert_eq!(
+        generic["text"],
+        "Agent agent-1: deployment failed: Health check failed"
+    );
+
+    config.format = WebhookFormat::Slack;
+    assert_eq!(config.payload(&alert)["text"], generic["text"]);
+    config.format = WebhookFormat::Discord;
+    assert_eq!(config.payload(&alert)["content"], generic["text"]);
+
+    config.format = WebhookFormat::PagerDuty;
+    config.pagerduty_routing_key = Some("routing-key".to_string());
+    let pagerduty = config.payload(&alert);
+    assert_eq!(pagerduty["routing_key"], "routing-key");
+    assert_eq!(pagerduty["event_action"], "trigger");
+    assert_eq!(pagerduty["payload"]["severity"], "error");
+    assert_eq!(pagerduty["payload"]["source"], "coinbase-agent-agent-1");
+
+    // Each agent is alerted once when it turns unhealthy, and again only after recovering
+    let mut tracker = AlertTracker::default();
+    let unhealthy = vec!["agent-1".to_string()];
+    assert_eq!(tracker.newly_unhealthy(&unhealthy), unhealthy);
+    assert!(tracker.newly_unhealthy(&unhealthy).is_empty());
+    assert!(tracker.newly_unhealthy(&[]).is_empty());
+    assert_eq!(tracker.newly_unhealthy(&unhealthy), unhealthy);
+
+    // Restarts are counted from the first observation and from each alert
+    assert_eq!(tracker.restarts_since_alert("agent-1", 5, 3), None);
+    assert_eq!(tracker.restarts_since_alert("agent-1", 7, 3), None);
+    assert_eq!(tracker.restarts_since_alert("agent-1", 8, 3), Some(3));
+    assert_eq!(tracker.restarts_since_alert("agent-1", 9, 3), None);
+    assert_eq!(tracker.restarts_since_alert("agent-1", 0, 3), None);
+    assert_eq!(tracker.restarts_since_alert("agent-1", 3, 3), Some(3));
+}
+
+#[test]
+fn test_health_monitor_policy() {
+    let policy = MonitorPolicy {
+        interval: Duration::from_secs(30),
+        failure_threshold: 2,
+        max_restarts: 2,
+        restart_backoff: Duration::from_secs(10),
+    };
+    assert_eq!(policy.backoff_for(1), Duration::from_secs(10));
+    assert_eq!(policy.backoff_for(3), Duration::from_secs(40));
+
+    let mut health = AgentHealth::default();
+    assert_eq!(health.observe(false, &policy), MonitorAction::None);
+    assert_eq!(
+        health.state,
+        MonitorState::Failing {
+            consecutive_failures: 1
+        }
+    );
+    assert_eq!(health.observe(false, &policy), MonitorAction::Restart);
+    assert_eq!(health.state, MonitorState::Restarting { restarts: 1 });
+
+    // Recovering resets the restart budget
+    assert_eq!(health.observe(true, &policy), MonitorAction::None);
+    assert_eq!(health, AgentHealth::default());
+
+    for _ in 0..2 {
+        health.observe(false, &policy);
+        assert_eq!(health.observe(false, &policy), MonitorAction::Restart);
+    }
+    assert_eq!(health.observe(false, &policy), MonitorAction::None);
+    assert_eq!(health.observe(false, &policy), MonitorAction::GiveUp);
+    assert_eq!(health.state, MonitorState::Failed);
+}
+
+#[tokio::test]
+async fn test_parallel_health_checks() {
+    let backoff = HealthBackoff {
+        retry_delay: Duration::from_secs(1),
+        factor: 2.0,
+    };
+    assert_eq!(backoff.delay_for(1), Duration::from_secs(1));
+    assert_eq!(backoff.delay_for(3), Duration::from_secs(4));
+    assert_eq!(
+        HttpConfig::default().health_retry_delay_for(4),
+        Duration::from_secs(3)
+    );
+
+    // Tasks run at most `limit` at a time and their outputs keep the items' order
+    let running = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let outputs = fan_out(0..10, 3, |n| {
+        let (running, peak) = (running.clone(), peak.clone());
+        async move {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+            n * 2
+        }
+    })
+    .await;
+    assert_eq!(outputs, (0..10).map(|n| Some(n * 2)).collect::<Vec<_>>());
+    assert_eq!(peak.load(Ordering::SeqCst), 3);
+
+    // Agents that can't be reached are unhealthy
+    let (context, _temp_dir, _missing) = setup_test_env();
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let results = probe_agents(
+        &context,
+        vec![("agent-1".to_string(), format!("http://127.0.0.1:{}", port))],
+    )
+    .await;
+    assert_eq!(results, vec![("agent-1".to_string(), false)]);
+}
+
+#[tokio::test]
+async fn test_readiness_probes() {
+    let (mut context, temp_dir, _missing) = setup_test_env();
+    context.phala_cloud = Some(PhalaCloud {
+        api_endpoint: "https://example.com/api".to_string(),
+        api_key: String::new(),
+    });
+
+    // The test environment's template is complete; an empty directory has none
+    assert!(readiness::check_template(&temp_dir).is_ok());
+    let empty = tempdir().unwrap();
+    assert!(readiness::check_template(empty.path())
+        .is_err_and(|e| e.contains("templates/starter/docker-compose.yml")));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, status_api::router(context))
+            .await
+            .unwrap()
+    });
+    let client = reqwest::Client::new();
+
+    let healthz = client
+        .get(format!("http://{}/healthz", addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(healthz.status(), reqwest::StatusCode::OK);
+
+    // Without TEE credentials the blueprint is not ready, whatever the state of Docker
+    let readyz = client
+        .get(format!("http://{}/readyz", addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(readyz.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    let report: ReadinessReport = readyz.json().await.unwrap();
+    assert!(!report.ready);
+    let names: Vec<&str> = report.checks.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["docker", "template", "tee"]);
+    let tee = &report.checks[2];
+    assert!(!tee.ok);
+    assert_eq!(tee.error.as_deref(), Some("PHALA_CLOUD_API_KEY not set"));
+}
+
+#[tokio::test]
+async fn test_stage_timings() {
+    let mut timer = StageTimer::default();
+    let copied: Result<(), String> = timer.run("template_copy", "agent-1", || Ok(()));
+    assert!(copied.is_ok());
+    let waited: Result<(), String> = timer
+        .run_async("health_wait", "agent-1", async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err("Health check failed".to_string())
+        })
+        .await;
+    assert!(waited.is_err());
+
+    // Failed stages are timed too, in the order they ran
+    let timings = timer.into_timings();
+    let stages: Vec<&str> = timings.iter().map(|t| t.stage.as_str()).collect();
+    assert_eq!(stages, ["template_copy", "health_wait"]);
+    assert!(timings[1].duration_ms >= 20);
+
+    // Results without timings (e.g. delegated deployments) omit the field
+    let result = AgentDeploymentResult {
+        agent_id: "agent-1".parse().unwrap(),
+        target: DeploymentTarget::default(),
+        tee_pubkey: None,
+        tee_app_id: None,
+        endpoint_url: None,
+        websocket_url: None,
+        container_name: None,
+        cvm_id: None,
+        deployed_at: 0,
+        deployed_by: Some(1),
+        call_id: None,
+        stage_timings: Vec::new(),
+        public_url: None,
+        wallet_address: None,
+        network_id: None,
+        operator_signature: None,
+    };
+    let json = serde_json::to_value(&result).unwrap();
+    assert!(json.get("stage_timings").is_none());
+    let json = serde_json::to_value(AgentDeploymentResult {
+        stage_timings: timings,
+        ..result
+    })
+    .unwrap();
+    assert_eq!(json["stage_timings"][0]["stage"], "template_copy");
+
+    // Results of older blueprints still deserialize
+    let result: AgentDeploymentResult = serde_json::from_value(serde_json::json!({
+        "agent_id": "agent-1",
+        "tee_pubkey": null,
+        "tee_app_id": null,
+        "endpoint": "http://localhost:3000"
+    }))
+    .unwrap();
+    assert_eq!(
+        result.endpoint_url.as_deref(),
+        Some("http://localhost:3000")
+    );
+    assert_eq!(result.target, DeploymentTarget::default());
+}
@@ -0,0 +1,320 @@
+use crate::{
+    agent_id::{self, AgentId},
+    envelope::{decode_params, MAX_PARAMS_SIZE},
+    error::BlueprintError,
+    schema,
+    types::{
+        ActionProvider, ActiveHours, AgentConfig, AgentMode, ApiKeyConfig, AutonomousSchedule,
+        BudgetConfig, CreateAgentParams, DeployAgentParams, DeploymentConfig, DeploymentTarget,
+        LlmProvider, ParamsSchemaResult, TeeConfig,
+    },
+};
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_llm_provider_model_validation() {
+    assert!(LlmProvider::OpenAI.validate_model("gpt-4o-mini").is_ok());
+    assert!(LlmProvider::Anthropic
+        .validate_model("claude-3-5-sonnet-latest")
+        .is_ok());
+    assert!(LlmProvider::Anthropic
+        .validate_model("gpt-4o-mini")
+        .is_err());
+    assert!(LlmProvider::OpenAI
+        .validate_model("claude-3-5-sonnet-latest")
+        .is_err());
+}
+
+#[test]
+fn test_create_agent_params_validation() {
+    let mut params = CreateAgentParams {
+        name: "Telegram Agent".to_string(),
+        agent_config: AgentConfig {
+            mode: AgentMode::Telegram,
+            model: "gpt-4o-mini".to_string(),
+            llm_provider: LlmProvider::OpenAI,
+            azure_openai: None,
+            local_model: None,
+            system_prompt: None,
+            temperature: None,
+            max_tokens: None,
+            network_id: "base-sepolia".to_string(),
+            schedule: None,
+            wallet: Default::default(),
+        },
+        action_providers: vec![ActionProvider::Wallet],
+        budget: None,
+        allowed_actions: None,
+        deployment_config: DeploymentConfig {
+            target: DeploymentTarget::local(None),
+            docker_compose_path: None,
+            egress: None,
+            image: Default::default(),
+            env: Default::default(),
+            size: None,
+            resources: None,
+        },
+        template_id: None,
+        api_key_config: ApiKeyConfig {
+            openai_api_key: None,
+            anthropic_api_key: None,
+            azure_openai_api_key: None,
+            cdp_api_key_name: None,
+            cdp_api_key_private_key: None,
+            cdp_wallet_secret: None,
+            wallet_private_key: None,
+            telegram_bot_token: None,
+            discord_bot_token: Some("discord-token".to_string().into()),
+        },
+        callback_url: None,
+    };
+    assert!(params.validate().is_err());
+
+    // Every violation is reported at once
+    params.name = String::new();
+    params.deployment_config.target = DeploymentTarget::local(Some(80));
+    let error = params.validate().unwrap_err();
+    for field in [
+        "name",
+        "deployment_config.target.http_port",
+        "api_key_config",
+    ] {
+        assert!(error.contains(field), "{} missing from {}", field, error);
+    }
+
+    params.name = "Telegram Agent".to_string();
+    params.deployment_config.target = DeploymentTarget::default();
+    params.api_key_config.telegram_bot_token = Some("telegram-token".into());
+    assert!(params.validate().is_ok());
+
+    // Ports of the operator's own services and oversized values are refused
+    params.deployment_config.target = DeploymentTarget::local(Some(9943));
+    params.agent_config.model = format!("gpt-{}", "4".repeat(AgentConfig::MAX_MODEL_LEN));
+    params.api_key_config.openai_api_key = Some("k".repeat(ApiKeyConfig::MAX_KEY_LEN + 1).into());
+    let error = params.validate().unwrap_err();
+    for field in [
+        "deployment_config.target.http_port",
+        "agent_config.model",
+        "api_key_config.openai_api_key",
+    ] {
+        assert!(error.contains(field), "{} missing from {}", field, error);
+    }
+}
+
+#[test]
+fn test_params_builders() {
+    let params = CreateAgentParams::builder()
+        .name("trader")
+        .chat()
+        .model("claude-3-5-sonnet-latest")
+        .anthropic_api_key("sk-ant-test")
+        .action_provider(ActionProvider::Wallet)
+        .action_provider(ActionProvider::Erc20)
+        .tee()
+        .build()
+        .unwrap();
+    assert_eq!(params.agent_config.mode, AgentMode::Chat);
+    assert_eq!(params.agent_config.llm_provider, LlmProvider::Anthropic);
+    assert_eq!(
+        params.agent_config.network_id,
+        AgentConfig::DEFAULT_NETWORK_ID
+    );
+    assert_eq!(
+        params.action_providers,
+        vec![ActionProvider::Wallet, ActionProvider::Erc20]
+    );
+    assert_eq!(
+        params.deployment_config.target,
+        DeploymentTarget::Tee(TeeConfig::default())
+    );
+    assert_eq!(
+        params.api_key_config.anthropic_api_key.unwrap().expose(),
+        "sk-ant-test"
+    );
+
+    // Defaults match those of the job's parameters
+    let params = CreateAgentParams::builder()
+        .name("default")
+        .chat()
+        .model("gpt-4o-mini")
+        .build()
+        .unwrap();
+    assert_eq!(params.action_providers, vec![ActionProvider::Wallet]);
+    assert_eq!(params.agent_config.llm_provider, LlmProvider::OpenAI);
+
+    // Missing and invalid fields are reported when building
+    let result = CreateAgentParams::builder().name("no-model").chat().build();
+    assert!(result.is_err_and(|e| e.contains("A model is required")));
+    let result = CreateAgentParams::builder()
+        .name("bad/name")
+        .chat()
+        .model("gpt-4o-mini")
+        .build();
+    assert!(result.is_err_and(|e| e.contains("name")));
+
+    let params = DeployAgentParams::builder("agent-1")
+        .tee("encrypted", "pubkey", "app-1", "salt")
+        .build()
+        .unwrap();
+    let tee = params.tee.unwrap();
+    assert_eq!(tee.app_id, "app-1");
+    assert_eq!(tee.salt.expose(), "salt");
+    let result = DeployAgentParams::builder("agent-1")
+        .tee("encrypted", "pubkey", "app-1", "salt")
+        .api_key_config(ApiKeyConfig::default())
+        .build();
+    assert!(result.is_err_and(|e| e.contains("cannot be combined")));
+    assert!(DeployAgentParams::builder("../agent").build().is_err());
+}
+
+#[test]
+fn test_params_limits() {
+    let params = DeployAgentParams {
+        agent_id: "0b7e1f4c-5d3a-4c8e-9f2b-1a6d8e3c7b90".parse().unwrap(),
+        api_key_config: None,
+        tee: None,
+        callback_url: None,
+    };
+    assert!(params.validate().is_ok());
+
+    // Agent IDs name directories, so they can't reach outside the agents directory
+    for agent_id in ["", "../other", "agent/../../etc", &"a".repeat(65)] {
+        assert!(
+            AgentId::parse(agent_id).is_err(),
+            "{:?} was accepted",
+            agent_id
+        );
+    }
+
+    // Oversized parameters are rejected before they are parsed
+    let oversized = vec![b' '; MAX_PARAMS_SIZE + 1];
+    let result: Result<DeployAgentParams, String> = decode_params(&oversized);
+    assert!(result.is_err_and(|e| e.contains("at most")));
+}
+
+#[test]
+fn test_agent_id() {
+    let agent_id = AgentId::generate();
+    assert_eq!(agent_id.len(), 36);
+    assert_eq!(agent_id.as_str().parse::<AgentId>(), Ok(agent_id.clone()));
+    assert_eq!(
+        agent_id.container_name(),
+        format!("coinbase-agent-{}", agent_id)
+    );
+    assert_eq!(
+        agent_id.dir("/agents"),
+        std::path::Path::new("/agents").join(agent_id.as_str())
+    );
+    assert_eq!(
+        agent_id::container_name("agent-1"),
+        "coinbase-agent-agent-1"
+    );
+
+    // Invalid IDs are rejected while deserializing, before any handler sees them
+    let result: Result<DeployAgentParams, _> = serde_json::from_str(r#"{"agent_id": "../../etc"}"#);
+    assert!(result.is_err_and(|e| e.to_string().contains("Agent ID must be")));
+    let result: Result<AgentId, _> = serde_json::from_str(r#""agent --privileged""#);
+    assert!(result.is_err());
+    assert_eq!(
+        serde_json::to_string(&"agent-1".parse::<AgentId>().unwrap()).unwrap(),
+        r#""agent-1""#
+    );
+}
+
+#[test]
+fn test_autonomous_schedule_validation() {
+    let mut schedule = AutonomousSchedule {
+        interval_secs: 600,
+        active_hours: Some(ActiveHours { start: 22, end: 6 }),
+        max_actions_per_day: Some(24),
+    };
+    assert!(schedule.validate().is_ok());
+
+    schedule.interval_secs = 1;
+    assert!(schedule.validate().is_err());
+
+    schedule.interval_secs = 600;
+    schedule.active_hours = Some(ActiveHours { start: 9, end: 9 });
+    assert!(schedule.validate().is_err());
+}
+
+#[test]
+fn test_budget_validation() {
+    let mut budget = BudgetConfig {
+        max_transaction_value: 0.1,
+        daily_cap: 1.0,
+        allowed_assets: vec!["eth".to_string(), "usdc".to_string()],
+    };
+    assert!(budget.validate().is_ok());
+
+    budget.max_transaction_value = 2.0;
+    assert!(budget.validate().is_err());
+
+    budget.max_transaction_value = 0.1;
+    budget.allowed_assets.clear();
+    assert!(budget.validate().is_err());
+}
+
+#[test]
+fn test_job_schemas() {
+    let document = schema::schema_document();
+    let create = &document["create_agent"]["params"];
+    let required: Vec<&str> = create["required"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|field| field.as_str().unwrap())
+        .collect();
+    assert!(required.contains(&"agent_config") && required.contains(&"api_key_config"));
+    // Fields with defaults may be left out
+    assert!(!required.contains(&"callback_url") && !required.contains(&"action_providers"));
+    assert!(create["properties"]["callback_url"].is_object());
+    assert!(document["deploy_agent"]["result"]["properties"]["stage_timings"].is_object());
+    assert!(document["error"].to_string().contains("Unauthorized"));
+
+    let temp_dir = tempdir().unwrap();
+    let written = schema::write_schemas(temp_dir.path()).unwrap();
+    assert_eq!(written, 2 * schema::job_schemas().len() + 1);
+    let params: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(temp_dir.path().join("create_agents.params.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(params["title"], "CreateAgentsParams");
+}
+
+#[tokio::test]
+async fn test_get_params_schema() {
+    let result: ParamsSchemaResult =
+        serde_json::from_slice(&schema::handle_get_params_schema(Vec::new()).await.unwrap())
+            .unwrap();
+    assert_eq!(result.blueprint_version, env!("CARGO_PKG_VERSION"));
+    let jobs: Vec<&str> = result.schemas.iter().map(|s| s.job.as_str()).collect();
+    assert_eq!(jobs, ["create_agent", "deploy_agent"]);
+    assert_eq!(result.schemas[0].version, 3);
+    assert_eq!(result.schemas[0].schema["title"], "CreateAgentParams");
+
+    let params = serde_json::to_vec(&serde_json::json!({ "jobs": ["deploy_agent"] })).unwrap();
+    let result: ParamsSchemaResult =
+        serde_json::from_slice(&schema::handle_get_params_schema(params).await.unwrap()).unwrap();
+    assert_eq!(result.schemas.len(), 1);
+    assert_eq!(result.schemas[0].version, 2);
+    assert!(result.schemas[0].schema["properties"]["tee"].is_object());
+
+    let params = serde_json::to_vec(&serde_json::json!({ "jobs": ["agent_status"] })).unwrap();
+    assert!(matches!(
+        schema::handle_get_params_schema(params).await,
+        Err(BlueprintError::Params(_))
+    ));
+}
+
+#[test]
+fn test_action_providers() {
+    let providers: Vec<ActionProvider> =
+        serde_json::from_str(r#"["Wallet", "Erc20", "Pyth"]"#).unwrap();
+    assert_eq!(
+        ActionProvider::to_env_value(&providers),
+        "wallet,erc20,pyth"
+    );
+    assert!(serde_json::from_str::<Vec<ActionProvider>>(r#"["Uniswap"]"#).is_err());
+}
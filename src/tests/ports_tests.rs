@@ -0,0 +1,146 @@
+use crate::{
+    create_agent::render_compose_file,
+    ports::{PortAllocator, PortRange},
+    retention::LogRetention,
+    types::{CreateAgentParams, CreateAgentSwarmParams, WebSocketPort},
+    AgentRegistry,
+};
+use tempfile::tempdir;
+
+#[test]
+fn test_port_allocator() {
+    assert_eq!(
+        "4000-4099".parse::<PortRange>().unwrap(),
+        PortRange {
+            start: 4000,
+            end: 4099
+        }
+    );
+    for invalid in ["4000", "4000-4000", "80-90", "4099-4000"] {
+        assert!(
+            invalid.parse::<PortRange>().is_err(),
+            "{} was accepted",
+            invalid
+        );
+    }
+
+    let temp_dir = tempdir().unwrap();
+    let state_path = temp_dir.path().join(".ports.json");
+    let range = PortRange {
+        start: 41000,
+        end: 41020,
+    };
+    let allocator = PortAllocator::new(range, state_path.clone(), AgentRegistry::new());
+
+    // Agents never share a port
+    let first = allocator
+        .allocate("agent-1", None, WebSocketPort::Adjacent)
+        .unwrap();
+    let second = allocator
+        .allocate("agent-2", None, WebSocketPort::Adjacent)
+        .unwrap();
+    assert_eq!(first.websocket_port, Some(first.http_port + 1));
+    assert!(second.http_port > first.http_port + 1);
+    assert!(allocator
+        .allocate(
+            "agent-3",
+            Some(first.http_port + 1),
+            WebSocketPort::Adjacent
+        )
+        .is_err());
+
+    // Ports something else on the host is listening on are skipped
+    let listener = std::net::TcpListener::bind(("0.0.0.0", second.http_port + 2)).unwrap();
+    let third = allocator
+        .allocate("agent-3", None, WebSocketPort::Adjacent)
+        .unwrap();
+    assert!(third.http_port > second.http_port + 2);
+    drop(listener);
+
+    // Released ports are handed out again, and allocations survive a restart
+    allocator.release("agent-2").unwrap();
+    assert_eq!(
+        allocator
+            .allocate("agent-4", None, WebSocketPort::Adjacent)
+            .unwrap(),
+        second
+    );
+    let restored = AgentRegistry::new();
+    let restarted = PortAllocator::new(range, state_path, restored.clone());
+    assert_eq!(restarted.load().unwrap(), 3);
+    assert_eq!(restored.get("agent-1"), Some(first));
+    assert!(!restored.contains("agent-2"));
+}
+
+#[test]
+fn test_websocket_port() {
+    let temp_dir = tempdir().unwrap();
+    let range = PortRange {
+        start: 43000,
+        end: 43020,
+    };
+    let allocator = PortAllocator::new(
+        range,
+        temp_dir.path().join(".ports.json"),
+        AgentRegistry::new(),
+    );
+
+    // Agents without a WebSocket server only take an HTTP port
+    let disabled = allocator
+        .allocate("agent-1", None, WebSocketPort::Disabled)
+        .unwrap();
+    assert_eq!(disabled.websocket_port, None);
+    assert_eq!(disabled.websocket(), WebSocketPort::Disabled);
+    let adjacent = allocator
+        .allocate("agent-2", None, WebSocketPort::Adjacent)
+        .unwrap();
+    assert_eq!(adjacent.http_port, disabled.http_port + 1);
+
+    // A chosen WebSocket port must be free
+    let explicit = allocator
+        .allocate("agent-3", None, WebSocketPort::Port(43010))
+        .unwrap();
+    assert_eq!(explicit.websocket_port, Some(43010));
+    assert_eq!(explicit.websocket(), WebSocketPort::Port(43010));
+    assert!(allocator
+        .allocate("agent-4", None, WebSocketPort::Port(43010))
+        .is_err());
+
+    // The WebSocket port can't be the HTTP one
+    let error = CreateAgentParams::builder()
+        .name("trader")
+        .chat()
+        .model("gpt-4o-mini")
+        .openai_api_key("sk-test")
+        .http_port(4000)
+        .websocket_port(WebSocketPort::Port(4000))
+        .build()
+        .unwrap_err();
+    assert!(error.contains("deployment_config.target.websocket_port"));
+
+    // Swarms without WebSocket servers take one port per agent
+    let swarm: CreateAgentSwarmParams = serde_json::from_value(serde_json::json!({
+        "name": "swarm",
+        "count": 3,
+        "agent_config": { "mode": "Chat", "model": "gpt-4o-mini", "network_id": "base-sepolia" },
+        "deployment_config": {
+            "target": { "type": "local", "http_port": 4000, "websocket_port": "disabled" }
+        },
+        "api_key_config": { "openai_api_key": "sk-test" }
+    }))
+    .unwrap();
+    let ports: Vec<_> = swarm
+        .agent_params()
+        .unwrap()
+        .iter()
+        .map(|agent| agent.deployment_config.target.http_port())
+        .collect();
+    assert_eq!(ports, [Some(4000), Some(4001), Some(4002)]);
+
+    // Their containers don't publish a WebSocket port
+    let agent = &swarm.agent_params().unwrap()[0];
+    let template = "services:\n  agent:\n    image: agent\n    ports:\n      - \"${PORT:-3000}:3000\"\n      - \"${WEBSOCKET_PORT:-3001}:3001\"\n    environment:\n      - WEBSOCKET_PORT=${WEBSOCKET_PORT:-3001}\n      - MODEL=${MODEL}\n";
+    let rendered = render_compose_file(template, agent, &LogRetention::default()).unwrap();
+    assert!(!rendered.contains("WEBSOCKET_PORT"));
+    assert!(rendered.contains("${PORT:-3000}:3000"));
+}
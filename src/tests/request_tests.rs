@@ -0,0 +1,507 @@
+use crate::{
+    audit::handle_secret_audit_log,
+    coordination::{deterministic_agent_id, CoordinationConfig},
+    envelope::{decode_params, decode_signed_params, ParamsEnvelope},
+    error::BlueprintError,
+    journal,
+    rate_limit::QuotaLimit,
+    signing::{
+        result_digest, CallerSignature, NonceRegistry, OperatorSignature, SignatureScheme,
+        MAX_SIGNATURE_TTL, NONCES_FILE,
+    },
+    tests::setup_test_env,
+    types::{
+        ActionProvider, AgentDeploymentResult, AgentMode, AgentStopResult, CreateAgentParams,
+        DeployAgentParams, DeploymentTarget, TeeConfig,
+    },
+    RateLimiter, RateLimits, RegistrationParams, RequestParams,
+};
+use blueprint_sdk::crypto::sp_core::{sr25519, Pair};
+use blueprint_sdk::tangle_subxt::subxt::utils::AccountId32;
+use blueprint_sdk::tangle_subxt::tangle_testnet_runtime::api::runtime_types::tangle_primitives::services::field::Field;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[test]
+fn test_agent_quota() {
+    let registration = RegistrationParams {
+        supported_modes: vec![AgentMode::Chat, AgentMode::Autonomous],
+        max_agents: 10,
+        price_per_agent: 1_000,
+    };
+
+    let quota = registration.quota(Some(&RequestParams {
+        max_agents: Some(3),
+    }));
+    assert_eq!(quota.max_agents, 3);
+    assert!(quota.check(&AgentMode::Chat, 2, 1).is_ok());
+    assert!(quota.check(&AgentMode::Chat, 2, 2).is_err());
+    assert!(quota.check(&AgentMode::Telegram, 0, 1).is_err());
+
+    // Agents being created count against the quota until they exist
+    let reservation = quota
+        .reserve(&[AgentMode::Chat, AgentMode::Chat], || Ok(1))
+        .unwrap();
+    let result = quota.reserve(&[AgentMode::Chat], || Ok(1));
+    assert!(matches!(result, Err(BlueprintError::Quota(_))));
+    drop(reservation);
+    let concurrent = quota.clone();
+    let reservation = quota.reserve(&[AgentMode::Chat], || Ok(2)).unwrap();
+    assert!(concurrent.reserve(&[AgentMode::Chat], || Ok(2)).is_err());
+    drop(reservation);
+    assert!(concurrent.reserve(&[AgentMode::Chat], || Ok(2)).is_ok());
+
+    // A request can only lower the operator's limit
+    let quota = registration.quota(Some(&RequestParams {
+        max_agents: Some(50),
+    }));
+    assert_eq!(quota.max_agents, 10);
+
+    // Request params are the JSON of the service's first request input
+    let request = RequestParams::from_args(&[Field::Bytes(br#"{"max_agents": 4}"#.to_vec())])
+        .unwrap()
+        .unwrap();
+    assert_eq!(request.max_agents, Some(4));
+    let request = RequestParams::from_args(&[Field::String(r#"{"max_agents": 2}"#.to_string())])
+        .unwrap()
+        .unwrap();
+    assert_eq!(request.max_agents, Some(2));
+    assert!(RequestParams::from_args(&[]).unwrap().is_none());
+    assert!(RequestParams::from_args(&[Field::String("5".to_string())]).is_err());
+
+    let inputs = registration.to_registration_inputs().unwrap();
+    let decoded: RegistrationParams = serde_json::from_slice(&inputs).unwrap();
+    assert_eq!(decoded.supported_modes, registration.supported_modes);
+    assert_eq!("discord".parse::<AgentMode>(), Ok(AgentMode::Discord));
+}
+
+#[test]
+fn test_result_digest_ignores_signature() {
+    let mut result = AgentDeploymentResult {
+        agent_id: "agent-1".parse().unwrap(),
+        target: DeploymentTarget::default(),
+        tee_pubkey: None,
+        tee_app_id: None,
+        endpoint_url: None,
+        websocket_url: None,
+        container_name: None,
+        cvm_id: None,
+        deployed_at: 0,
+        deployed_by: None,
+        call_id: Some(1),
+        stage_timings: Vec::new(),
+        public_url: None,
+        wallet_address: None,
+        network_id: None,
+        operator_signature: None,
+    };
+    let digest = result_digest(&result).unwrap();
+
+    result.operator_signature = Some(OperatorSignature {
+        public_key: "02ab".to_string(),
+        signature: "cd".to_string(),
+    });
+    assert_eq!(result_digest(&result).unwrap(), digest);
+
+    result.agent_id = "agent-2".parse().unwrap();
+    assert_ne!(result_digest(&result).unwrap(), digest);
+
+    // Unsigned results serialize without the field
+    result.operator_signature = None;
+    let json = serde_json::to_string(&result).unwrap();
+    assert!(!json.contains("operator_signature"));
+}
+
+#[test]
+fn test_operator_coordination() {
+    let operators: Vec<CoordinationConfig> = (0..3)
+        .map(|operator_index| CoordinationConfig {
+            operator_index,
+            operator_count: 3,
+        })
+        .collect();
+
+    // Every operator derives the same agent ID for a call, and exactly one deploys it
+    for call_id in 0..20 {
+        let agent_id = deterministic_agent_id(call_id, "my-agent");
+        assert_eq!(agent_id, deterministic_agent_id(call_id, "my-agent"));
+        assert_ne!(agent_id, deterministic_agent_id(call_id, "my-agent-2"));
+        let responsible = operators
+            .iter()
+            .filter(|operator| operator.is_responsible(&agent_id))
+            .count();
+        assert_eq!(responsible, 1);
+    }
+
+    let out_of_range = CoordinationConfig {
+        operator_index: 3,
+        operator_count: 3,
+    };
+    assert!(out_of_range.validate().is_err());
+}
+
+#[test]
+fn test_journal_indexed_by_call_id() {
+    let (mut context, _temp_dir, _missing) = setup_test_env();
+
+    // Calls without an ID aren't journaled
+    journal::record(&context, "create_agent", "agent-0", "created");
+
+    context.call_id = Some(41);
+    journal::record(&context, "create_agent", "agent-1", "created");
+    context.call_id = Some(42);
+    journal::record(&context, "deploy_agent", "agent-1", "deployed");
+
+    let entries = journal::entries_for_call(&context, 41).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].job, "create_agent");
+    assert_eq!(entries[0].agent_id, "agent-1");
+    assert_eq!(
+        journal::entries_for_call(&context, 42).unwrap()[0].action,
+        "deployed"
+    );
+    assert!(journal::entries_for_call(&context, 43).unwrap().is_empty());
+}
+
+#[test]
+fn test_params_envelope() {
+    // Version 1 parameters, sent bare as before envelopes existed
+    let bare = serde_json::json!({
+        "name": "legacy",
+        "agent_config": { "mode": "Chat", "model": "gpt-4o-mini" },
+        "deployment_config": { "tee_enabled": false, "docker_compose_path": null, "http_port": 3000 },
+        "api_key_config": { "openai_api_key": "sk-test", "cdp_api_key_name": null, "cdp_api_key_private_key": null }
+    });
+    let params: CreateAgentParams = decode_params(&serde_json::to_vec(&bare).unwrap()).unwrap();
+    assert_eq!(params.name, "legacy");
+    assert_eq!(params.agent_config.network_id, "base-sepolia");
+
+    let wrapped = serde_json::json!({ "version": 1, "payload": bare });
+    let params: CreateAgentParams = decode_params(&serde_json::to_vec(&wrapped).unwrap()).unwrap();
+    assert_eq!(params.name, "legacy");
+
+    // Current envelopes round-trip
+    let bytes = ParamsEnvelope::new(params).to_bytes().unwrap();
+    let params: CreateAgentParams = decode_params(&bytes).unwrap();
+    assert_eq!(params.action_providers, vec![ActionProvider::Wallet]);
+
+    let future = serde_json::json!({ "version": 99, "payload": bare });
+    let result: Result<CreateAgentParams, String> =
+        decode_params(&serde_json::to_vec(&future).unwrap());
+    assert!(result.is_err_and(|e| e.contains("Unsupported parameters version 99")));
+}
+
+#[test]
+fn test_params_field_policy() {
+    let params = serde_json::json!({
+        "name": "policy",
+        "agent_config": { "mode": "Chat", "model": "gpt-4o-mini" },
+        "api_key_config": { "openai_api_key": "sk-test" }
+    });
+    let envelope = |payload: &serde_json::Value| {
+        serde_json::to_vec(&serde_json::json!({ "version": 3, "payload": payload })).unwrap()
+    };
+
+    // Fields with defaults may be left out
+    let decoded: CreateAgentParams = decode_params(&envelope(&params)).unwrap();
+    assert_eq!(
+        decoded.deployment_config.target,
+        DeploymentTarget::default()
+    );
+    assert!(decoded.api_key_config.cdp_api_key_name.is_none());
+
+    // Unknown fields are rejected, however deeply nested
+    let mut unknown = params.clone();
+    unknown["http_prot"] = serde_json::json!(3000);
+    assert!(decode_params::<CreateAgentParams>(&envelope(&unknown))
+        .unwrap_err()
+        .contains("unknown field `http_prot`"));
+    let mut unknown = params.clone();
+    unknown["deployment_config"] =
+        serde_json::json!({ "target": { "type": "tee", "vcpu": 2, "gpus": 1 } });
+    assert!(decode_params::<CreateAgentParams>(&envelope(&unknown)).is_err());
+    let mut tee = params.clone();
+    tee["deployment_config"] = serde_json::json!({ "target": { "type": "tee", "vcpu": 4 } });
+    let decoded: CreateAgentParams = decode_params(&envelope(&tee)).unwrap();
+    assert_eq!(
+        decoded.deployment_config.target,
+        DeploymentTarget::Tee(TeeConfig {
+            vcpu: 4,
+            ..Default::default()
+        })
+    );
+
+    // Renamed fields keep their old names
+    let tee = serde_json::json!({
+        "encrypted_env_vars": "ciphertext",
+        "pubkey": "pubkey",
+        "app_id": "app-1",
+        "salt": "salt"
+    });
+    let deploy =
+        serde_json::json!({ "version": 2, "payload": { "agent_id": "agent-1", "tee": tee } });
+    let decoded: DeployAgentParams = decode_params(&serde_json::to_vec(&deploy).unwrap()).unwrap();
+    assert_eq!(decoded.tee.unwrap().encrypted_env.expose(), "ciphertext");
+    let bare = serde_json::json!({
+        "agent_id": "agent-1",
+        "encrypted_env_vars": "ciphertext",
+        "tee_pubkey": "pubkey",
+        "tee_app_id": "app-1",
+        "tee_salt": "salt"
+    });
+    let decoded: DeployAgentParams = decode_params(&serde_json::to_vec(&bare).unwrap()).unwrap();
+    assert_eq!(decoded.tee.unwrap().encrypted_env.expose(), "ciphertext");
+
+    // Results of newer blueprints still deserialize
+    let result: AgentStopResult = serde_json::from_value(serde_json::json!({
+        "agent_id": "agent-1",
+        "was_running": true,
+        "stopped_at": 1700000000
+    }))
+    .unwrap();
+    assert!(result.was_running);
+}
+
+#[test]
+fn test_caller_signature() {
+    let (mut context, _temp_dir, _missing) = setup_test_env();
+    let owner = sr25519::Pair::from_seed(&[3; 32]);
+    context.service_owner = Some(AccountId32(owner.public().0).to_string());
+
+    let params = ParamsEnvelope::new(DeployAgentParams {
+        agent_id: "agent".parse().unwrap(),
+        api_key_config: None,
+        tee: None,
+        callback_url: None,
+    });
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let sign_for = |pair: &sr25519::Pair, service_id: Option<u64>, nonce: u64, expires_at: u64| {
+        let message = params
+            .signing_message(service_id, nonce, expires_at)
+            .unwrap();
+        let mut signed = params.clone();
+        signed.signature = Some(CallerSignature {
+            scheme: SignatureScheme::Sr25519,
+            signature: hex::encode(pair.sign(&message).0),
+            nonce,
+            expires_at,
+        });
+        signed.to_bytes().unwrap()
+    };
+    let sign = |pair: &sr25519::Pair, nonce: u64| sign_for(pair, None, nonce, now + 60);
+
+    let decoded: DeployAgentParams = decode_signed_params(&sign(&owner, 1), &context).unwrap();
+    assert_eq!(decoded.agent_id, "agent");
+
+    // A signature is only accepted once
+    let result: Result<DeployAgentParams, String> =
+        decode_signed_params(&sign(&owner, 1), &context);
+    assert!(result.is_err_and(|e| e.contains("already used")));
+
+    // Signatures for another service, expired ones and ones valid for too long are rejected
+    let result: Result<DeployAgentParams, String> =
+        decode_signed_params(&sign_for(&owner, Some(7), 2, now + 60), &context);
+    assert!(result.is_err_and(|e| e.contains("does not match the service owner")));
+    let result: Result<DeployAgentParams, String> =
+        decode_signed_params(&sign_for(&owner, None, 3, now - 1), &context);
+    assert!(result.is_err_and(|e| e.contains("expired")));
+    let result: Result<DeployAgentParams, String> = decode_signed_params(
+        &sign_for(&owner, None, 4, now + MAX_SIGNATURE_TTL + 60),
+        &context,
+    );
+    assert!(result.is_err_and(|e| e.contains("seconds ahead")));
+
+    // Anyone else's signature is rejected
+    let other = sr25519::Pair::from_seed(&[4; 32]);
+    let result: Result<DeployAgentParams, String> =
+        decode_signed_params(&sign(&other, 5), &context);
+    assert!(result.is_err_and(|e| e.contains("does not match the service owner")));
+
+    // Unsigned params are only accepted while signatures aren't required
+    let unsigned = params.to_bytes().unwrap();
+    assert!(decode_signed_params::<DeployAgentParams>(&unsigned, &context).is_ok());
+    context.require_caller_signature = Some(true);
+    assert!(decode_signed_params::<DeployAgentParams>(&unsigned, &context).is_err());
+    assert!(decode_signed_params::<DeployAgentParams>(&sign(&owner, 6), &context).is_ok());
+
+    // Used nonces survive a restart
+    let path = _temp_dir.join(NONCES_FILE);
+    let nonces = NonceRegistry::load(path.clone()).unwrap();
+    nonces.claim(8, now + 60, now).unwrap();
+    let restored = NonceRegistry::load(path).unwrap();
+    assert!(restored.claim(8, now + 60, now).is_err());
+    assert!(restored.claim(8, now + 60, now + 61).is_ok());
+}
+
+#[cfg(feature = "scale")]
+#[tokio::test]
+async fn test_scale_params() {
+    use crate::codec::{self, Encoding};
+    use crate::types::{SecretAuditParams, SecretAuditResult};
+    use parity_scale_codec::{Decode, Encode};
+    use std::path::PathBuf;
+
+    let (mut context, _temp_dir, _missing) = setup_test_env();
+    let params: CreateAgentParams = serde_json::from_value(serde_json::json!({
+        "name": "scale",
+        "agent_config": { "mode": "Chat", "model": "gpt-4o-mini", "temperature": 0.25 },
+        "deployment_config": { "target": { "type": "local", "http_port": 3000 }, "docker_compose_path": "compose.yml" },
+        "api_key_config": { "openai_api_key": "sk-test", "cdp_api_key_name": null, "cdp_api_key_private_key": null },
+        "budget": { "max_transaction_value": 0.5, "daily_cap": 2.5, "allowed_assets": ["eth"] }
+    }))
+    .unwrap();
+
+    // SCALE parameters are tried first, JSON ones still decode
+    let (decoded, encoding): (CreateAgentParams, _) = codec::decode(&params.encode()).unwrap();
+    assert_eq!(encoding, Encoding::Scale);
+    assert_eq!(decoded.agent_config.temperature, Some(0.25));
+    assert_eq!(decoded.budget.as_ref().unwrap().daily_cap, 2.5);
+    assert_eq!(
+        decoded.deployment_config.docker_compose_path,
+        Some(PathBuf::from("compose.yml"))
+    );
+    assert_eq!(
+        decoded.api_key_config.openai_api_key.unwrap().expose(),
+        "sk-test"
+    );
+    let (_, encoding): (CreateAgentParams, _) =
+        codec::decode(&serde_json::to_vec(&params).unwrap()).unwrap();
+    assert_eq!(encoding, Encoding::Json);
+
+    // Results come back in the encoding of the parameters
+    context.service_owner = Some("owner".to_string());
+    context.api_caller = Some("owner".to_string());
+    let scale_params = SecretAuditParams::default().encode();
+    let result = handle_secret_audit_log(scale_params.clone(), &context)
+        .await
+        .unwrap();
+    let result = SecretAuditResult::decode(&mut &result[..]).unwrap();
+    assert!(result.chain_valid);
+    let json_params = serde_json::to_vec(&SecretAuditParams::default()).unwrap();
+    let result = handle_secret_audit_log(json_params, &context)
+        .await
+        .unwrap();
+    assert!(serde_json::from_slice::<SecretAuditResult>(&result).is_ok());
+
+    // SCALE parameters cannot carry a signature
+    let deploy = DeployAgentParams {
+        agent_id: "agent".parse().unwrap(),
+        api_key_config: None,
+        tee: None,
+        callback_url: None,
+    }
+    .encode();
+    assert!(decode_signed_params::<DeployAgentParams>(&deploy, &context).is_ok());
+    context.require_caller_signature = Some(true);
+    assert!(decode_signed_params::<DeployAgentParams>(&deploy, &context).is_err());
+}
+
+#[test]
+fn test_rate_limiter() {
+    let limiter = RateLimiter::new(RateLimits {
+        max_calls_per_hour: Some(2),
+        max_agents_per_owner: Some(3),
+    });
+    let start = Instant::now();
+    limiter.record_call("1/alice", start).unwrap();
+    limiter
+        .record_call("1/alice", start + Duration::from_secs(600))
+        .unwrap();
+
+    // The third call within the hour is refused until the first one expires
+    let err = limiter
+        .record_call("1/alice", start + Duration::from_secs(1200))
+        .unwrap_err();
+    assert_eq!(
+        err.limit,
+        QuotaLimit::CallsPerHour {
+            limit: 2,
+            retry_after_secs: 2400
+        }
+    );
+    assert!(String::from(err).starts_with("Quota exceeded for 1/alice"));
+    limiter.record_call("1/bob", start).unwrap();
+    limiter
+        .record_call("1/alice", start + Duration::from_secs(3600))
+        .unwrap();
+
+    assert!(limiter.check_agents("1/alice", 2, 1).is_ok());
+    assert_eq!(
+        limiter.check_agents("1/alice", 2, 2).unwrap_err().limit,
+        QuotaLimit::AgentsPerOwner { limit: 3 }
+    );
+}
+
+#[cfg(feature = "nats")]
+#[tokio::test]
+async fn test_nats_requests() {
+    use crate::nats::{self, NatsConfig, ERROR_CODE_HEADER, ERROR_HEADER};
+    use std::env;
+
+    env::set_var("AGENT_NATS_URL", "nats://127.0.0.1:4222");
+    env::set_var("AGENT_NATS_SUBJECT", "agents.requests");
+    env::set_var("AGENT_NATS_ONLY", "true");
+    let config = NatsConfig::from_env().unwrap().unwrap();
+    assert_eq!(config.url, "nats://127.0.0.1:4222");
+    assert!(config.exclusive);
+    assert_eq!(config.queue_group, None);
+    assert_eq!(
+        config.job_subject("deploy_agent"),
+        "agents.requests.deploy_agent"
+    );
+    env::set_var("AGENT_NATS_SUBJECT", "agents.>");
+    assert!(NatsConfig::from_env().is_err());
+    env::remove_var("AGENT_NATS_URL");
+    env::remove_var("AGENT_NATS_SUBJECT");
+    env::remove_var("AGENT_NATS_ONLY");
+    assert_eq!(NatsConfig::from_env().unwrap(), None);
+
+    // Requests run the job their subject names, with the same parameters
+    let (context, _temp_dir, _missing) = setup_test_env();
+    let result = nats::handle_request(
+        &context,
+        &config,
+        "agents.requests.create_agent",
+        b"not json".to_vec(),
+    )
+    .await;
+    assert!(matches!(result, Err(BlueprintError::Params(_))));
+    let result =
+        nats::handle_request(&context, &config, "agents.requests.stop_agent", Vec::new()).await;
+    assert!(result.is_err_and(|e| e.message().contains("No job is served")));
+
+    let headers = nats::error_headers(&BlueprintError::Unauthorized("not the owner".into()));
+    assert_eq!(headers.get(ERROR_HEADER).unwrap().as_str(), "not the owner");
+    assert_eq!(headers.get(ERROR_CODE_HEADER).unwrap().as_str(), "403");
+}
+
+#[tokio::test]
+async fn test_error_payloads() {
+    let error = BlueprintError::Docker("Failed to start Docker container: no such image".into());
+    let payload: serde_json::Value = serde_json::from_str(&error.to_payload()).unwrap();
+    assert_eq!(payload["kind"], "Docker");
+    assert_eq!(
+        payload["message"],
+        "Failed to start Docker container: no such image"
+    );
+    assert_eq!(
+        serde_json::from_value::<BlueprintError>(payload).unwrap(),
+        error
+    );
+
+    // Context keeps the kind of the underlying error
+    let error =
+        BlueprintError::Template("template missing".into()).context("Failed to create swarm s");
+    assert!(matches!(&error, BlueprintError::Template(_)));
+    assert_eq!(
+        error.message(),
+        "Failed to create swarm s: template missing"
+    );
+
+    // Handlers classify their failures
+    let (context, _temp_dir, _missing) = setup_test_env();
+    let result = handle_secret_audit_log(b"not json".to_vec(), &context).await;
+    assert!(matches!(result, Err(BlueprintError::Params(_))));
+}
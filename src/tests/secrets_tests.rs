@@ -0,0 +1,221 @@
+use crate::{
+    audit::{self, handle_secret_audit_log, SecretAccess},
+    error::BlueprintError,
+    secret_resolver::{EnvSecretResolver, SecretResolvers, SecretUri},
+    secret_scan::scan_agent_dir,
+    secrets::{read_sealed_secrets, write_sealed_secrets, SEALED_SECRETS_FILE},
+    tests::setup_test_env,
+    types::{AgentMode, ApiKeyConfig, SecretAuditParams, SecretAuditResult},
+    SealingKey,
+};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn test_secret_audit_log() {
+    let (mut context, _temp_dir, _missing) = setup_test_env();
+    audit::record(
+        &context,
+        "agent-1",
+        SecretAccess::Write,
+        ["OPENAI_API_KEY", "CDP_API_KEY_NAME"],
+        "sealed",
+    )
+    .unwrap();
+    audit::record(
+        &context,
+        "agent-2",
+        SecretAccess::Inject,
+        ["encrypted_env"],
+        "tee",
+    )
+    .unwrap();
+
+    let records = audit::records(&context).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(
+        records[0].secrets,
+        vec!["CDP_API_KEY_NAME", "OPENAI_API_KEY"]
+    );
+    assert_eq!(records[1].prev_hash, records[0].hash);
+    assert!(audit::verify_chain(&records).is_ok());
+
+    let params = serde_json::to_vec(&SecretAuditParams {
+        agent_id: Some("agent-2".parse().unwrap()),
+        since: None,
+    })
+    .unwrap();
+
+    // Nobody may read the log until a service owner is configured, and then only them
+    let result = handle_secret_audit_log(params.clone(), &context).await;
+    assert!(matches!(result, Err(BlueprintError::Unauthorized(_))));
+    context.service_owner = Some("owner".to_string());
+    context.api_caller = Some("someone".to_string());
+    let result = handle_secret_audit_log(params.clone(), &context).await;
+    assert!(matches!(result, Err(BlueprintError::Unauthorized(_))));
+    context.api_caller = Some("owner".to_string());
+
+    let result: SecretAuditResult =
+        serde_json::from_slice(&handle_secret_audit_log(params, &context).await.unwrap()).unwrap();
+    assert!(result.chain_valid);
+    assert_eq!(result.records.len(), 1);
+    assert_eq!(result.records[0].access, SecretAccess::Inject);
+
+    // Rewriting history breaks the chain
+    let mut tampered = records.clone();
+    tampered[0].secrets.pop();
+    assert!(audit::verify_chain(&tampered).is_err());
+    assert!(audit::verify_chain(&records[1..]).is_err());
+
+    // Appends only read the end of the log, however long it grows
+    for _ in 0..40 {
+        audit::record(
+            &context,
+            "agent-1",
+            SecretAccess::Read,
+            ["OPENAI_API_KEY"],
+            "sealed",
+        )
+        .unwrap();
+    }
+    let records = audit::records(&context).unwrap();
+    assert_eq!(records.last().unwrap().seq, 41);
+    assert!(audit::verify_chain(&records).is_ok());
+    assert!(audit::verify_checkpoint(&context, &records).is_ok());
+
+    // Records removed from the end of the log keep a valid chain but miss the checkpoint
+    let log_path = _temp_dir.join(".audit/secrets.jsonl");
+    let content = fs::read_to_string(&log_path).unwrap();
+    let truncated: Vec<&str> = content.lines().take(41).collect();
+    fs::write(&log_path, truncated.join("\n") + "\n").unwrap();
+    let params = serde_json::to_vec(&SecretAuditParams::default()).unwrap();
+    let result: SecretAuditResult =
+        serde_json::from_slice(&handle_secret_audit_log(params, &context).await.unwrap()).unwrap();
+    assert!(!result.chain_valid);
+    assert!(result.chain_error.unwrap().contains("checkpoint"));
+}
+
+#[test]
+fn test_secret_scan() {
+    let temp_dir = tempdir().unwrap();
+    let agent_dir = temp_dir.path();
+    fs::create_dir_all(agent_dir.join("src")).unwrap();
+    fs::create_dir_all(agent_dir.join("node_modules/pkg")).unwrap();
+    fs::write(
+        agent_dir.join(".env"),
+        "OPENAI_API_KEY=sk-abcdefghijklmnopqrstuvwx\n",
+    )
+    .unwrap();
+    fs::write(
+        agent_dir.join("node_modules/pkg/index.js"),
+        "const key = 'sk-abcdefghijklmnopqrstuvwx';\n",
+    )
+    .unwrap();
+    fs::write(
+        agent_dir.join("src/agent.ts"),
+        "export const port = 3000;\n",
+    )
+    .unwrap();
+
+    // The intentional .env and dependencies are not scanned
+    let report = scan_agent_dir(agent_dir, &["my-deployed-token-value"]).unwrap();
+    assert!(report.is_clean(), "{}", report);
+
+    fs::write(
+        agent_dir.join("src/config.ts"),
+        "// TODO\nconst key = 'sk-abcdefghijklmnopqrstuvwx';\nconst token = 'my-deployed-token-value';\n",
+    )
+    .unwrap();
+    let report = scan_agent_dir(agent_dir, &["my-deployed-token-value"]).unwrap();
+    let rules: Vec<(usize, &str)> = report
+        .findings
+        .iter()
+        .map(|finding| (finding.line, finding.rule.as_str()))
+        .collect();
+    assert_eq!(rules, vec![(2, "OpenAI API key"), (3, "Deployment secret")]);
+    assert!(report.findings[0].path.ends_with("src/config.ts"));
+    assert!(!report.to_string().contains("sk-abcdefghijklmnopqrstuvwx"));
+}
+
+#[test]
+fn test_sealed_secrets() {
+    let temp_dir = tempdir().unwrap();
+    let key = SealingKey::from_bytes([7; 32]);
+
+    // Agents without sealed secrets unseal to nothing
+    assert!(read_sealed_secrets(temp_dir.path(), &key)
+        .unwrap()
+        .is_empty());
+
+    let secrets = HashMap::from([("OPENAI_API_KEY".to_string(), "sk-secret".into())]);
+    write_sealed_secrets(temp_dir.path(), &key, &secrets).unwrap();
+    let on_disk = fs::read_to_string(temp_dir.path().join(SEALED_SECRETS_FILE)).unwrap();
+    assert!(!on_disk.contains("sk-secret"));
+    assert_eq!(read_sealed_secrets(temp_dir.path(), &key).unwrap(), secrets);
+
+    // Another operator's key cannot unseal them
+    let other = SealingKey::from_bytes([8; 32]);
+    assert!(read_sealed_secrets(temp_dir.path(), &other).is_err());
+}
+
+#[tokio::test]
+async fn test_secret_resolvers() {
+    assert_eq!(
+        SecretUri::parse("vault://kv/agents/openai#key"),
+        Some(SecretUri {
+            scheme: "vault".to_string(),
+            path: "kv/agents/openai".to_string(),
+            field: Some("key".to_string()),
+        })
+    );
+    assert_eq!(SecretUri::parse("sk-raw-key"), None);
+
+    let resolvers = SecretResolvers::default().with(EnvSecretResolver);
+    env::set_var("AGENT_SECRET_TEST_RESOLVER_KEY", "sk-resolved");
+    let secrets = resolvers
+        .resolve_all(vec![
+            (
+                "OPENAI_API_KEY".to_string(),
+                "env://TEST_RESOLVER_KEY".into(),
+            ),
+            ("CDP_API_KEY_NAME".to_string(), "cdp-name".into()),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(secrets[0].1.expose(), "sk-resolved");
+    assert_eq!(secrets[1].1.expose(), "cdp-name");
+
+    // Only prefixed variables and configured backends can be referenced
+    assert!(resolvers.resolve("env://PATH").await.is_err());
+    assert!(resolvers
+        .resolve("vault://kv/agents/openai")
+        .await
+        .is_err_and(|e| e.contains("No secret resolver for vault://")));
+}
+
+#[test]
+fn test_secrets_are_redacted() {
+    let keys: ApiKeyConfig = serde_json::from_value(serde_json::json!({
+        "openai_api_key": "sk-secret",
+        "cdp_api_key_name": "cdp-name",
+        "cdp_api_key_private_key": "cdp-private-key",
+        "discord_bot_token": "discord-token"
+    }))
+    .unwrap();
+
+    let debug = format!("{:?}", keys);
+    for secret in ["sk-secret", "cdp-name", "cdp-private-key", "discord-token"] {
+        assert!(!debug.contains(secret), "{} leaked: {}", secret, debug);
+    }
+    assert_eq!(
+        keys.openai_api_key.as_ref().unwrap().to_string(),
+        "[REDACTED]"
+    );
+    assert_eq!(keys.bot_token(&AgentMode::Discord), Some("discord-token"));
+
+    // Secrets still serialize as their bare values
+    let json = serde_json::to_value(&keys).unwrap();
+    assert_eq!(json["openai_api_key"], "sk-secret");
+}
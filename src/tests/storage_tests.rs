@@ -0,0 +1,123 @@
+use crate::{
+    storage::{self, ArtifactStore, LocalStore, S3Store, BACKUP_EXTENSION},
+    tests::setup_test_env,
+    AgentPortConfig,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn test_artifact_storage() {
+    // Signing key example from the AWS Signature Version 4 documentation
+    assert_eq!(
+        hex::encode(storage::signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam"
+        )),
+        "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+    );
+
+    // Backups are sealed and restore the agent's files and ports
+    let (mut context, temp_dir, _missing) = setup_test_env();
+    let store_dir = tempdir().unwrap();
+    context.artifact_store = Some(Arc::new(LocalStore::new(store_dir.path())));
+    let agent_dir = temp_dir.join("backed-up");
+    fs::create_dir_all(agent_dir.join("src")).unwrap();
+    fs::write(agent_dir.join(".env"), "OPENAI_API_KEY=sk-backed-up\n").unwrap();
+    fs::write(agent_dir.join("src/index.ts"), "run();\n").unwrap();
+    fs::create_dir_all(agent_dir.join("node_modules/dep")).unwrap();
+    fs::write(agent_dir.join("node_modules/dep/index.js"), "").unwrap();
+    let ports = AgentPortConfig {
+        http_port: 41000,
+        websocket_port: Some(41001),
+    };
+    context
+        .agent_registry
+        .as_ref()
+        .unwrap()
+        .insert("backed-up", ports.clone());
+
+    let key = storage::backup_agent(&context, "backed-up").await.unwrap();
+    assert!(key.starts_with("agents/backed-up/backups/") && key.ends_with(BACKUP_EXTENSION));
+    let stored = fs::read(store_dir.path().join(&key)).unwrap();
+    assert!(!String::from_utf8_lossy(&stored).contains("sk-backed-up"));
+
+    fs::remove_dir_all(&agent_dir).unwrap();
+    context.agent_registry.as_ref().unwrap().remove("backed-up");
+    assert_eq!(storage::restore_missing_agents(&context).await.unwrap(), 1);
+    assert_eq!(
+        fs::read_to_string(agent_dir.join("src/index.ts")).unwrap(),
+        "run();\n"
+    );
+    assert!(!agent_dir.join("node_modules").exists());
+    assert!(!agent_dir.join(".ports.json").exists());
+    assert_eq!(
+        context.agent_registry.as_ref().unwrap().get("backed-up"),
+        Some(ports)
+    );
+    assert!(storage::restore_agent(&context, "backed-up", None)
+        .await
+        .unwrap_err()
+        .contains("already exists"));
+    assert!(storage::restore_agent(&context, "../escape", None)
+        .await
+        .is_err());
+
+    // S3 requests are signed and addressed path-style
+    let objects: Arc<Mutex<HashMap<String, Vec<u8>>>> = Arc::default();
+    let app = axum::Router::new().fallback({
+        let objects = objects.clone();
+        move |method: axum::http::Method,
+              uri: axum::http::Uri,
+              headers: axum::http::HeaderMap,
+              body: axum::body::Bytes| async move {
+            let authorization = headers["authorization"].to_str().unwrap();
+            assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=minio/"));
+            assert!(authorization.contains("/eu-west-1/s3/aws4_request"));
+            let mut objects = objects.lock().unwrap();
+            let path = uri.path().to_string();
+            match (method.as_str(), path.strip_prefix("/artifacts/")) {
+                ("PUT", Some(key)) => {
+                    objects.insert(key.to_string(), body.to_vec());
+                    String::new()
+                }
+                ("GET", Some(key)) => String::from_utf8(objects[key].clone()).unwrap(),
+                _ => {
+                    assert!(uri.query().unwrap().contains("list-type=2"));
+                    let keys: Vec<String> = objects
+                        .keys()
+                        .map(|key| format!("<Contents><Key>{}</Key></Contents>", key))
+                        .collect();
+                    format!("<ListBucketResult>{}</ListBucketResult>", keys.concat())
+                }
+            }
+        }
+    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let s3 = S3Store::new(
+        reqwest::Client::new(),
+        url::Url::parse(&format!("http://{}", addr)).unwrap(),
+        "artifacts",
+        "eu-west-1",
+        "minio",
+        "minio-secret",
+    );
+    s3.put("agents/a/diagnostics/1.tar.gz", b"bundle".to_vec())
+        .await
+        .unwrap();
+    assert_eq!(
+        s3.get("agents/a/diagnostics/1.tar.gz").await.unwrap(),
+        b"bundle"
+    );
+    assert_eq!(
+        s3.list("agents/").await.unwrap(),
+        vec!["agents/a/diagnostics/1.tar.gz".to_string()]
+    );
+}
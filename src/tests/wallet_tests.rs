@@ -0,0 +1,333 @@
+use crate::{
+    balances::{handle_get_agent_balances, RpcEndpoints},
+    create_agent::{env_context, render_compose_file},
+    error::BlueprintError,
+    fund_agent::handle_fund_agent,
+    retention::LogRetention,
+    tests::setup_test_env,
+    types::{
+        AgentBalancesResult, CreateAgentParams, DeploymentTarget, FundAgentResult, TeeConfig,
+        WalletConfig,
+    },
+    wallet, AgentPortConfig, SealingKey,
+};
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tempfile::tempdir;
+
+#[test]
+fn test_wallet_config() {
+    let secret = "env://WALLET_SECRET".to_string();
+    let smart_wallet = |paymaster_url: Option<&str>| {
+        CreateAgentParams::builder()
+            .name("smart")
+            .chat()
+            .model("gpt-4o-mini")
+            .smart_wallet(&secret, paymaster_url.map(str::to_string))
+    };
+    let params = smart_wallet(Some("https://paymaster.example.com/rpc"))
+        .build()
+        .unwrap();
+    let env = env_context(&params, None).unwrap();
+    assert_eq!(env["WALLET_TYPE"], "smart_wallet");
+    assert_eq!(env["PAYMASTER_URL"], "https://paymaster.example.com/rpc");
+
+    // Smart wallets need a paymaster over https and a supported network
+    assert!(smart_wallet(Some("http://paymaster.example.com"))
+        .build()
+        .is_err());
+    assert!(smart_wallet(None)
+        .network("ethereum-mainnet")
+        .build()
+        .is_err());
+
+    // Wallet keys are only taken by reference, since job parameters are public, and the
+    // wallet secret is only used by smart wallets
+    let mut params = smart_wallet(None).build().unwrap();
+    params.api_key_config.cdp_wallet_secret = Some(format!("0x{}", "ab".repeat(32)).into());
+    let error = params.validate().unwrap_err();
+    assert!(error.contains("api_key_config.cdp_wallet_secret"));
+    params.api_key_config.cdp_wallet_secret = Some(secret.clone().into());
+    params.agent_config.wallet = WalletConfig::ServerSigner;
+    assert!(params.validate().is_err());
+    params.api_key_config.cdp_wallet_secret = None;
+    assert!(params.validate().is_ok());
+    let env = env_context(&params, None).unwrap();
+    assert_eq!(env["WALLET_TYPE"], "server_signer");
+    assert!(!env.contains_key("PAYMASTER_URL"));
+
+    // Callers can bring their own account, whose key is only used by private-key wallets
+    params.api_key_config.wallet_private_key = Some(secret.clone().into());
+    assert!(params.validate().is_err());
+    params.agent_config.wallet = WalletConfig::PrivateKey;
+    assert!(params.validate().is_ok());
+    params.api_key_config.cdp_wallet_secret = Some(secret.clone().into());
+    assert!(params.validate().is_err());
+    params.api_key_config.cdp_wallet_secret = None;
+    params.deployment_config.target = DeploymentTarget::Tee(TeeConfig::default());
+    assert!(params.validate().is_err());
+    let params = CreateAgentParams::builder()
+        .name("eoa")
+        .chat()
+        .model("gpt-4o-mini")
+        .network("ethereum-mainnet")
+        .private_key_wallet(&secret)
+        .build()
+        .unwrap();
+    let env = env_context(&params, None).unwrap();
+    assert_eq!(env["WALLET_TYPE"], "private_key");
+    assert!(!env.contains_key("WALLET_PRIVATE_KEY"));
+}
+
+#[test]
+fn test_wallet_persistence() {
+    // Local agents mount their wallet directory and are handed captured wallet data
+    let template = "services:\n  agent:\n    image: agent\n    environment:\n      - WALLET_DATA_FILE=${WALLET_DATA_FILE}\n";
+    let builder = || {
+        CreateAgentParams::builder()
+            .name("wallet")
+            .chat()
+            .model("gpt-4o-mini")
+    };
+    let rendered = render_compose_file(
+        template,
+        &builder().build().unwrap(),
+        &LogRetention::default(),
+    )
+    .unwrap();
+    assert!(rendered.contains("./.wallet:/wallet"));
+    assert!(rendered.contains("WALLET_DATA_FILE=/wallet/wallet_data.json"));
+    assert!(!rendered.contains("WALLET_DATA_FILE=${WALLET_DATA_FILE}"));
+    assert!(rendered.contains("CDP_WALLET_DATA=${CDP_WALLET_DATA}"));
+    let rendered = render_compose_file(
+        template,
+        &builder().tee().build().unwrap(),
+        &LogRetention::default(),
+    )
+    .unwrap();
+    assert!(!rendered.contains("/wallet:"));
+
+    // Wallet data the agent wrote is sealed, and only resealed when it changes
+    let dir = tempdir().unwrap();
+    let agent_dir = dir.path();
+    let key = SealingKey::from_bytes([7; 32]);
+    assert!(!wallet::capture(agent_dir, &key, false).unwrap());
+    assert!(wallet::read_sealed(agent_dir, &key).unwrap().is_none());
+    wallet::create_dir(agent_dir).unwrap();
+    let data_path = agent_dir
+        .join(wallet::WALLET_DIR)
+        .join(wallet::WALLET_DATA_FILE);
+    let data = r#"{"walletId":"w-1","seed":"00ff","networkId":"base-sepolia"}"#;
+    fs::write(&data_path, data).unwrap();
+    assert!(wallet::capture(agent_dir, &key, false).unwrap());
+    let sealed_path = agent_dir.join(wallet::SEALED_WALLET_FILE);
+    let sealed = fs::read_to_string(&sealed_path).unwrap();
+    assert!(!sealed.contains("w-1"));
+    assert!(wallet::capture(agent_dir, &key, false).unwrap());
+    assert_eq!(fs::read_to_string(&sealed_path).unwrap(), sealed);
+    assert_eq!(
+        wallet::read_sealed(agent_dir, &key)
+            .unwrap()
+            .unwrap()
+            .expose(),
+        data
+    );
+
+    // Stopping drops the plaintext; other keys can't unseal and garbage isn't captured
+    assert!(wallet::capture(agent_dir, &key, true).unwrap());
+    assert!(!data_path.exists());
+    assert!(wallet::read_sealed(agent_dir, &SealingKey::from_bytes([8; 32])).is_err());
+    fs::write(&data_path, "not json").unwrap();
+    assert!(wallet::capture(agent_dir, &key, false).is_err());
+}
+
+#[tokio::test]
+async fn test_fund_agent() {
+    use warp::Filter;
+
+    let (mut context, temp_dir, _missing) = setup_test_env();
+    for agent_id in ["testnet-agent", "mainnet-agent"] {
+        let agent_dir = temp_dir.join(agent_id);
+        fs::create_dir_all(&agent_dir).unwrap();
+        fs::write(agent_dir.join(".env"), "AGENT_OWNER=alice\n").unwrap();
+    }
+    context.api_caller = Some("alice".to_string());
+
+    // A mock agent whose balance rises once the faucet is asked
+    let serve = |network_id: &'static str| {
+        let requested = Arc::new(AtomicUsize::new(0));
+        let counter = requested.clone();
+        let wallet = warp::path("wallet").map(move || {
+            let balance = if counter.load(Ordering::SeqCst) > 0 {
+                "1000"
+            } else {
+                "0"
+            };
+            warp::reply::json(&serde_json::json!({
+                "address": "0x1234567890abcdef1234567890abcdef12345678",
+                "network_id": network_id,
+                "balance": balance
+            }))
+        });
+        let counter = requested.clone();
+        let faucet = warp::path("faucet").and(warp::post()).map(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            warp::reply::json(&serde_json::json!({ "tx_hashes": ["0xfeed"] }))
+        });
+        let (addr, server) = warp::serve(wallet.or(faucet)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (addr.port(), requested)
+    };
+    let registry = context.agent_registry.clone().unwrap();
+    let (port, requested) = serve("base-sepolia");
+    registry.insert(
+        "testnet-agent",
+        AgentPortConfig {
+            http_port: port,
+            websocket_port: None,
+        },
+    );
+    let (port, mainnet_requested) = serve("base-mainnet");
+    registry.insert(
+        "mainnet-agent",
+        AgentPortConfig {
+            http_port: port,
+            websocket_port: None,
+        },
+    );
+    let params = |agent_id: &str, wait_secs: u64| {
+        serde_json::to_vec(&serde_json::json!({ "agent_id": agent_id, "wait_secs": wait_secs }))
+            .unwrap()
+    };
+
+    let result: FundAgentResult = serde_json::from_slice(
+        &handle_fund_agent(params("testnet-agent", 10), &context)
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(result.tx_hashes, vec!["0xfeed".to_string()]);
+    assert_eq!(result.network_id, "base-sepolia");
+    assert_eq!(result.balance.as_deref(), Some("1000"));
+    assert!(result.funded);
+    assert_eq!(requested.load(Ordering::SeqCst), 1);
+
+    // Mainnet wallets are never sent to the faucet
+    assert!(matches!(
+        handle_fund_agent(params("mainnet-agent", 0), &context).await,
+        Err(BlueprintError::Params(_))
+    ));
+    assert_eq!(mainnet_requested.load(Ordering::SeqCst), 0);
+
+    // Only the agent's owner may fund it, and waits are bounded
+    assert!(matches!(
+        handle_fund_agent(params("testnet-agent", 301), &context).await,
+        Err(BlueprintError::Params(_))
+    ));
+    context.api_caller = Some("mallory".to_string());
+    assert!(matches!(
+        handle_fund_agent(params("testnet-agent", 0), &context).await,
+        Err(BlueprintError::Unauthorized(_))
+    ));
+    assert_eq!(requested.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_get_agent_balances() {
+    use warp::Filter;
+
+    let (mut context, temp_dir, _missing) = setup_test_env();
+    let agent_dir = temp_dir.join("funded-agent");
+    fs::create_dir_all(&agent_dir).unwrap();
+    fs::write(agent_dir.join(".env"), "AGENT_OWNER=alice\n").unwrap();
+    context.api_caller = Some("alice".to_string());
+
+    let address = "0x1234567890abcdef1234567890abcdef12345678";
+    let token = "0x036cbd53842c5426634e7929541ec2318f3dcf7e";
+    let wallet = warp::path("wallet").map(move || {
+        warp::reply::json(&serde_json::json!({
+            "address": address,
+            "network_id": "base-sepolia",
+            "balance": "42"
+        }))
+    });
+    let (addr, server) = warp::serve(wallet).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    context.agent_registry.as_ref().unwrap().insert(
+        "funded-agent",
+        AgentPortConfig {
+            http_port: addr.port(),
+            websocket_port: None,
+        },
+    );
+
+    // A JSON-RPC node holding 1 ETH and 2^128 of the token
+    let rpc = warp::post()
+        .and(warp::body::json())
+        .map(move |request: serde_json::Value| {
+            let result = match request["method"].as_str() {
+                Some("eth_getBalance") => {
+                    assert_eq!(request["params"][0], address);
+                    "0xde0b6b3a7640000".to_string()
+                }
+                Some("eth_call") => {
+                    assert_eq!(request["params"][0]["to"], token);
+                    let data = request["params"][0]["data"].as_str().unwrap();
+                    assert_eq!(data, format!("0x70a08231{:0>64}", &address[2..]));
+                    format!("0x{:0>64}", format!("1{}", "0".repeat(32)))
+                }
+                _ => unreachable!(),
+            };
+            warp::reply::json(&serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+        });
+    let (rpc_addr, server) = warp::serve(rpc).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    context.rpc_endpoints = Some(RpcEndpoints::new([(
+        "base-sepolia".to_string(),
+        format!("http://{}", rpc_addr),
+    )]));
+
+    let params = |tokens: Vec<&str>| {
+        serde_json::to_vec(&serde_json::json!({ "agent_id": "funded-agent", "tokens": tokens }))
+            .unwrap()
+    };
+    let result: AgentBalancesResult = serde_json::from_slice(
+        &handle_get_agent_balances(params(vec![token]), &context)
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(result.wallet_address, address);
+    assert_eq!(result.native_balance, "1000000000000000000");
+    assert_eq!(result.tokens.len(), 1);
+    assert_eq!(
+        result.tokens[0].balance,
+        "340282366920938463463374607431768211456"
+    );
+
+    assert!(matches!(
+        handle_get_agent_balances(params(vec!["0x1234"]), &context).await,
+        Err(BlueprintError::Params(_))
+    ));
+
+    // Without an RPC endpoint, the agent's own native balance is reported
+    context.rpc_endpoints = Some(RpcEndpoints::new([]));
+    let result: AgentBalancesResult = serde_json::from_slice(
+        &handle_get_agent_balances(params(vec![]), &context)
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(result.native_balance, "42");
+    assert!(matches!(
+        handle_get_agent_balances(params(vec![token]), &context).await,
+        Err(BlueprintError::Params(_))
+    ));
+
+    context.api_caller = Some("mallory".to_string());
+    assert!(matches!(
+        handle_get_agent_balances(params(vec![]), &context).await,
+        Err(BlueprintError::Unauthorized(_))
+    ));
+}
@@ -1,12 +1,31 @@
+use crate::agent_id::AgentId;
+use crate::audit::AuditRecord;
+use crate::callbacks::validate_callback_url;
+use crate::error::BlueprintError;
+use crate::secret_resolver::SecretUri;
+use crate::signing::{OperatorSignature, SignedResult};
+use crate::templates::{self, TemplateRef};
+use crate::validation::Violations;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::path::PathBuf;
+use zeroize::Zeroize;
 
 // Agent configuration types
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
 pub enum AgentMode {
     Autonomous,
     Chat,
+    /// Serves the agent as a Telegram bot
+    Telegram,
+    /// Serves the agent as a Discord bot
+    Discord,
 }
 
 // Implement Display for AgentMode
@@ -15,61 +34,2482 @@ impl fmt::Display for AgentMode {
         match self {
             AgentMode::Autonomous => write!(f, "Autonomous"),
             AgentMode::Chat => write!(f, "Chat"),
+            AgentMode::Telegram => write!(f, "Telegram"),
+            AgentMode::Discord => write!(f, "Discord"),
         }
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl std::str::FromStr for AgentMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AgentMode::ALL
+            .into_iter()
+            .find(|mode| mode.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Unknown agent mode: {}", s))
+    }
+}
+
+impl AgentMode {
+    /// Every agent mode, in declaration order
+    pub const ALL: [AgentMode; 4] = [
+        AgentMode::Autonomous,
+        AgentMode::Chat,
+        AgentMode::Telegram,
+        AgentMode::Discord,
+    ];
+
+    /// Returns the environment variable holding the bot token this mode needs, if any
+    pub fn bot_token_env_var(&self) -> Option<&'static str> {
+        match self {
+            AgentMode::Telegram => Some("TELEGRAM_BOT_TOKEN"),
+            AgentMode::Discord => Some("DISCORD_BOT_TOKEN"),
+            AgentMode::Autonomous | AgentMode::Chat => None,
+        }
+    }
+}
+
+/// LLM provider backing the agent
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub enum LlmProvider {
+    #[default]
+    OpenAI,
+    Anthropic,
+    AzureOpenAI,
+    /// Self-hosted model served through Ollama's OpenAI-compatible API
+    Local,
+}
+
+impl fmt::Display for LlmProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LlmProvider::OpenAI => write!(f, "OpenAI"),
+            LlmProvider::Anthropic => write!(f, "Anthropic"),
+            LlmProvider::AzureOpenAI => write!(f, "AzureOpenAI"),
+            LlmProvider::Local => write!(f, "Local"),
+        }
+    }
+}
+
+impl LlmProvider {
+    /// Returns the environment variable holding this provider's API key, if it needs one
+    pub fn api_key_env_var(&self) -> Option<&'static str> {
+        match self {
+            LlmProvider::OpenAI => Some("OPENAI_API_KEY"),
+            LlmProvider::Anthropic => Some("ANTHROPIC_API_KEY"),
+            LlmProvider::AzureOpenAI => Some("AZURE_OPENAI_API_KEY"),
+            LlmProvider::Local => None,
+        }
+    }
+
+    /// Checks that the model name belongs to this provider
+    pub fn validate_model(&self, model: &str) -> Result<(), String> {
+        let known_prefixes: &[&str] = match self {
+            // Ollama model names are free-form but end up in the sidecar's pull command
+            LlmProvider::Local => {
+                let valid = !model.is_empty()
+                    && model
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "-_.:/".contains(c));
+                return if valid {
+                    Ok(())
+                } else {
+                    Err(format!("Invalid local model name: {}", model))
+                };
+            }
+            LlmProvider::OpenAI | LlmProvider::AzureOpenAI => {
+                &["gpt-", "o1", "o3", "o4", "chatgpt-"]
+            }
+            LlmProvider::Anthropic => &["claude-"],
+        };
+
+        if known_prefixes
+            .iter()
+            .any(|prefix| model.starts_with(prefix))
+        {
+            Ok(())
+        } else {
+            Err(format!(
+                "Model {} is not supported by the {} provider",
+                model, self
+            ))
+        }
+    }
+}
+
+/// UTC hours during which an autonomous agent may act
+///
+/// The window starts at `start` and ends before `end`, wrapping past midnight when
+/// `start` is greater than `end`.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct ActiveHours {
+    pub start: u8,
+    pub end: u8,
+}
+
+/// When an `Autonomous` agent acts on its own
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct AutonomousSchedule {
+    /// Seconds between autonomous actions
+    pub interval_secs: u64,
+    /// Restricts actions to these hours; the agent may act at any time if unset
+    #[serde(default)]
+    pub active_hours: Option<ActiveHours>,
+    /// Maximum number of autonomous actions per UTC day
+    #[serde(default)]
+    pub max_actions_per_day: Option<u32>,
+}
+
+impl AutonomousSchedule {
+    /// Shortest accepted interval between actions
+    pub const MIN_INTERVAL_SECS: u64 = 30;
+    /// Longest accepted interval between actions
+    pub const MAX_INTERVAL_SECS: u64 = 86_400;
+    /// Highest accepted daily action cap
+    pub const MAX_ACTIONS_PER_DAY: u32 = 10_000;
+
+    /// Checks that the schedule is within sane bounds
+    pub fn validate(&self) -> Result<(), String> {
+        if !(Self::MIN_INTERVAL_SECS..=Self::MAX_INTERVAL_SECS).contains(&self.interval_secs) {
+            return Err(format!(
+                "Autonomous interval must be between {} and {} seconds, got {}",
+                Self::MIN_INTERVAL_SECS,
+                Self::MAX_INTERVAL_SECS,
+                self.interval_secs
+            ));
+        }
+        if let Some(hours) = &self.active_hours {
+            if hours.start > 23 || hours.end > 23 {
+                return Err("Active hours must be between 0 and 23".to_string());
+            }
+            if hours.start == hours.end {
+                return Err("Active hours must not start and end at the same hour".to_string());
+            }
+        }
+        if let Some(max) = self.max_actions_per_day {
+            if max == 0 || max > Self::MAX_ACTIONS_PER_DAY {
+                return Err(format!(
+                    "Max actions per day must be between 1 and {}, got {}",
+                    Self::MAX_ACTIONS_PER_DAY,
+                    max
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Azure OpenAI resource the agent talks to instead of api.openai.com
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct AzureOpenAIConfig {
+    /// Resource endpoint, e.g. `https://my-resource.openai.azure.com`
+    pub endpoint: String,
+    pub deployment_name: String,
+    pub api_version: String,
+}
+
+/// Where a `Local` provider agent reaches its model server
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct LocalModelConfig {
+    /// Run an Ollama sidecar container next to the agent
+    #[serde(default = "default_true")]
+    pub sidecar: bool,
+    /// OpenAI-compatible base URL of an existing model server, required without a sidecar
+    #[serde(default)]
+    pub base_url: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl LocalModelConfig {
+    /// Base URL of the sidecar's OpenAI-compatible API inside the compose network
+    pub const SIDECAR_BASE_URL: &'static str = "http://ollama:11434/v1";
+
+    /// Returns the OpenAI-compatible base URL the agent should use
+    pub fn openai_base_url(&self) -> Result<String, String> {
+        match (&self.base_url, self.sidecar) {
+            (Some(base_url), _) => Ok(base_url.clone()),
+            (None, true) => Ok(Self::SIDECAR_BASE_URL.to_string()),
+            (None, false) => {
+                Err("A base URL is required for local models without a sidecar".to_string())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
 pub struct AgentConfig {
     pub mode: AgentMode,
     pub model: String,
+    #[serde(default)]
+    pub llm_provider: LlmProvider,
+    /// Required when `llm_provider` is `AzureOpenAI`
+    #[serde(default)]
+    pub azure_openai: Option<AzureOpenAIConfig>,
+    /// Model server for the `Local` provider; defaults to an Ollama sidecar
+    #[serde(default)]
+    pub local_model: Option<LocalModelConfig>,
+    /// Replaces the template's default system prompt
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Sampling temperature, between 0.0 and 2.0
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "scale",
+        codec(encoded_as = "crate::codec::scale::OptionF32Bits")
+    )]
+    pub temperature: Option<f32>,
+    /// Maximum number of tokens generated per response
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    /// Chain the agent's wallet lives on, one of [`AgentConfig::SUPPORTED_NETWORKS`]
+    #[serde(default = "default_network_id")]
+    pub network_id: String,
+    /// How often an `Autonomous` agent acts; only valid in that mode
+    #[serde(default)]
+    pub schedule: Option<AutonomousSchedule>,
+    /// Kind of wallet the agent holds
+    #[serde(default)]
+    pub wallet: WalletConfig,
+}
+
+/// Kind of wallet an agent holds, rendered into its `.env` as `WALLET_TYPE`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum WalletConfig {
+    /// MPC wallet whose key is derived from the CDP API key
+    #[default]
+    Cdp,
+    /// MPC wallet signed by the Server-Signer the CDP project runs
+    ServerSigner,
+    /// ERC-4337 smart wallet owned by the key in `api_key_config.cdp_wallet_secret`
+    SmartWallet {
+        /// Paymaster sponsoring the wallet's gas, if any
+        #[serde(default)]
+        paymaster_url: Option<String>,
+    },
+    /// Externally owned account of the key in `api_key_config.wallet_private_key`, brought by
+    /// the caller instead of managed by CDP
+    PrivateKey,
+}
+
+impl WalletConfig {
+    /// Values of `WALLET_TYPE`, one per kind of wallet
+    pub const TYPES: &'static [&'static str] =
+        &["cdp", "server_signer", "smart_wallet", "private_key"];
+
+    /// Networks smart wallets can be created on
+    pub const SMART_WALLET_NETWORKS: &'static [&'static str] = &["base-sepolia", "base-mainnet"];
+
+    /// Value of `WALLET_TYPE` selecting this wallet in the agent
+    pub fn env_value(&self) -> &'static str {
+        match self {
+            Self::Cdp => "cdp",
+            Self::ServerSigner => "server_signer",
+            Self::SmartWallet { .. } => "smart_wallet",
+            Self::PrivateKey => "private_key",
+        }
+    }
+
+    /// Whether the wallet is owned by the key in `cdp_wallet_secret` rather than derived from
+    /// the CDP API key
+    pub fn uses_wallet_secret(&self) -> bool {
+        matches!(self, Self::SmartWallet { .. })
+    }
+
+    /// Whether the wallet is the account of the key in `wallet_private_key`
+    pub fn uses_private_key(&self) -> bool {
+        matches!(self, Self::PrivateKey)
+    }
+
+    /// Checks the wallet can be created on `network_id`
+    pub fn validate(&self, network_id: &str) -> Result<(), String> {
+        let Self::SmartWallet { paymaster_url } = self else {
+            return Ok(());
+        };
+        if !Self::SMART_WALLET_NETWORKS.contains(&network_id) {
+            return Err(format!(
+                "Smart wallets are only available on {}, not {}",
+                Self::SMART_WALLET_NETWORKS.join(", "),
+                network_id
+            ));
+        }
+        if let Some(url) = paymaster_url {
+            if !url.starts_with("https://") || url.len() > AgentConfig::MAX_URL_LEN {
+                return Err(format!(
+                    "Paymaster URL must be an https URL of at most {} bytes",
+                    AgentConfig::MAX_URL_LEN
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks that a wallet secret or private key is `0x` followed by 64 hex digits
+pub fn validate_wallet_secret(secret: &str) -> Result<(), String> {
+    let hex = secret.trim().strip_prefix("0x").unwrap_or_default();
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("Wallet key must be a private key, 0x followed by 64 hex digits".to_string());
+    }
+    Ok(())
+}
+
+fn default_network_id() -> String {
+    AgentConfig::DEFAULT_NETWORK_ID.to_string()
+}
+
+impl AgentConfig {
+    /// Longest accepted system prompt, in characters
+    pub const MAX_SYSTEM_PROMPT_LEN: usize = 16_384;
+
+    /// Longest accepted model name, in bytes
+    pub const MAX_MODEL_LEN: usize = 128;
+
+    /// Longest accepted endpoint URL, in bytes
+    pub const MAX_URL_LEN: usize = 2048;
+
+    /// Longest accepted Azure deployment name or API version, in bytes
+    pub const MAX_SETTING_LEN: usize = 256;
+
+    /// CDP network IDs agents can be deployed on
+    pub const SUPPORTED_NETWORKS: &'static [&'static str] =
+        &["base-sepolia", "base-mainnet", "ethereum-mainnet"];
+
+    /// Supported networks whose wallets the CDP faucet funds, through `fund_agent`
+    pub const TESTNET_NETWORKS: &'static [&'static str] = &["base-sepolia"];
+
+    /// Network used when none is specified
+    pub const DEFAULT_NETWORK_ID: &'static str = "base-sepolia";
+
+    /// Validates the LLM provider, generation, network and schedule settings
+    pub fn validate(&self) -> Result<(), String> {
+        let mut violations = Violations::new();
+        self.collect_violations("agent_config", &mut violations);
+        violations.into_result()
+    }
+
+    /// Records every invalid setting, with field paths under `prefix`
+    pub fn collect_violations(&self, prefix: &str, violations: &mut Violations) {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+
+        violations.check(
+            field("model"),
+            self.llm_provider.validate_model(&self.model),
+        );
+        violations.check_len(field("model"), &self.model, Self::MAX_MODEL_LEN);
+        violations.check(field("azure_openai"), self.validate_azure_openai());
+        if let Some(azure) = &self.azure_openai {
+            violations.check_len(
+                field("azure_openai.endpoint"),
+                &azure.endpoint,
+                Self::MAX_URL_LEN,
+            );
+            violations.check_len(
+                field("azure_openai.deployment_name"),
+                &azure.deployment_name,
+                Self::MAX_SETTING_LEN,
+            );
+            violations.check_len(
+                field("azure_openai.api_version"),
+                &azure.api_version,
+                Self::MAX_SETTING_LEN,
+            );
+        }
+        violations.check(field("local_model"), self.validate_local_model());
+        if let Some(base_url) = self
+            .local_model
+            .as_ref()
+            .and_then(|local| local.base_url.as_deref())
+        {
+            violations.check_len(field("local_model.base_url"), base_url, Self::MAX_URL_LEN);
+        }
+
+        if let Some(prompt) = &self.system_prompt {
+            if prompt.trim().is_empty() {
+                violations.push(field("system_prompt"), "System prompt must not be empty");
+            } else if prompt.chars().count() > Self::MAX_SYSTEM_PROMPT_LEN {
+                violations.push(
+                    field("system_prompt"),
+                    format!(
+                        "System prompt must be at most {} characters",
+                        Self::MAX_SYSTEM_PROMPT_LEN
+                    ),
+                );
+            }
+        }
+        if let Some(temperature) = self.temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                violations.push(
+                    field("temperature"),
+                    format!(
+                        "Temperature must be between 0.0 and 2.0, got {}",
+                        temperature
+                    ),
+                );
+            }
+        }
+        if self.max_tokens == Some(0) {
+            violations.push(field("max_tokens"), "Max tokens must be greater than zero");
+        }
+
+        if !Self::SUPPORTED_NETWORKS.contains(&self.network_id.as_str()) {
+            violations.push(
+                field("network_id"),
+                format!(
+                    "Unsupported network {}, expected one of: {}",
+                    self.network_id,
+                    Self::SUPPORTED_NETWORKS.join(", ")
+                ),
+            );
+        }
+
+        violations.check(field("wallet"), self.wallet.validate(&self.network_id));
+
+        match (&self.mode, &self.schedule) {
+            (AgentMode::Autonomous, Some(schedule)) => {
+                violations.check(field("schedule"), schedule.validate())
+            }
+            (_, Some(_)) => violations.push(
+                field("schedule"),
+                format!(
+                    "A schedule can only be set for autonomous agents, not {} agents",
+                    self.mode
+                ),
+            ),
+            (_, None) => {}
+        }
+    }
+
+    fn validate_azure_openai(&self) -> Result<(), String> {
+        match (&self.llm_provider, &self.azure_openai) {
+            (LlmProvider::AzureOpenAI, None) => Err(
+                "Azure OpenAI configuration is required for the AzureOpenAI provider".to_string(),
+            ),
+            (LlmProvider::AzureOpenAI, Some(azure)) => {
+                if !azure.endpoint.starts_with("https://") {
+                    return Err(format!(
+                        "Azure OpenAI endpoint must be an https URL: {}",
+                        azure.endpoint
+                    ));
+                }
+                if azure.deployment_name.trim().is_empty() || azure.api_version.trim().is_empty() {
+                    return Err(
+                        "Azure OpenAI deployment name and API version must not be empty"
+                            .to_string(),
+                    );
+                }
+                Ok(())
+            }
+            (_, Some(_)) => Err(format!(
+                "Azure OpenAI configuration is not used by the {} provider",
+                self.llm_provider
+            )),
+            (_, None) => Ok(()),
+        }
+    }
+
+    fn validate_local_model(&self) -> Result<(), String> {
+        match (&self.llm_provider, &self.local_model) {
+            (LlmProvider::Local, Some(local)) => local.openai_base_url().map(|_| ()),
+            (LlmProvider::Local, None) => Ok(()),
+            (_, Some(_)) => Err(format!(
+                "Local model configuration is not used by the {} provider",
+                self.llm_provider
+            )),
+            (_, None) => Ok(()),
+        }
+    }
+
+    /// Returns the local model configuration, defaulting to an Ollama sidecar
+    pub fn local_model_config(&self) -> LocalModelConfig {
+        self.local_model.clone().unwrap_or(LocalModelConfig {
+            sidecar: true,
+            base_url: None,
+        })
+    }
+}
+
+/// AgentKit action provider that can be enabled for an agent
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub enum ActionProvider {
+    /// Native asset balances and transfers
+    Wallet,
+    Erc20,
+    Weth,
+    /// Pyth price feeds
+    Pyth,
+    /// Actions defined in the template's `src/actions.ts`
+    Custom,
+}
+
+impl fmt::Display for ActionProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionProvider::Wallet => write!(f, "Wallet"),
+            ActionProvider::Erc20 => write!(f, "Erc20"),
+            ActionProvider::Weth => write!(f, "Weth"),
+            ActionProvider::Pyth => write!(f, "Pyth"),
+            ActionProvider::Custom => write!(f, "Custom"),
+        }
+    }
+}
+
+impl ActionProvider {
+    /// Renders providers as the comma-separated `ACTION_PROVIDERS` value the template reads
+    pub fn to_env_value(providers: &[ActionProvider]) -> String {
+        providers
+            .iter()
+            .map(|provider| provider.to_string().to_lowercase())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Category of on-chain action an agent can be permitted to execute
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub enum AllowedAction {
+    Transfer,
+    Swap,
+    /// Wrapping and unwrapping ETH
+    Wrap,
+    DeployToken,
+    DeployNft,
+}
+
+impl AllowedAction {
+    /// Returns the name the template uses in `ALLOWED_ACTIONS`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AllowedAction::Transfer => "transfer",
+            AllowedAction::Swap => "swap",
+            AllowedAction::Wrap => "wrap",
+            AllowedAction::DeployToken => "deploy_token",
+            AllowedAction::DeployNft => "deploy_nft",
+        }
+    }
+
+    /// Value of `ALLOWED_ACTIONS` for a policy that allows no actions
+    ///
+    /// An empty value would be indistinguishable from an unset one, which allows everything.
+    pub const NONE: &'static str = "none";
+
+    /// Renders actions as the comma-separated `ALLOWED_ACTIONS` value the template reads
+    pub fn to_env_value(actions: &[AllowedAction]) -> String {
+        if actions.is_empty() {
+            return Self::NONE.to_string();
+        }
+        actions
+            .iter()
+            .map(AllowedAction::as_str)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Parses an `ALLOWED_ACTIONS` value, returning `None` if every action is allowed
+    pub fn from_env_value(value: &str) -> Result<Option<Vec<AllowedAction>>, String> {
+        if value.trim().is_empty() {
+            return Ok(None);
+        }
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty() && *name != Self::NONE)
+            .map(|name| name.parse())
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some)
+    }
+}
+
+impl fmt::Display for AllowedAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for AllowedAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "transfer" => Ok(AllowedAction::Transfer),
+            "swap" => Ok(AllowedAction::Swap),
+            "wrap" => Ok(AllowedAction::Wrap),
+            "deploy_token" => Ok(AllowedAction::DeployToken),
+            "deploy_nft" => Ok(AllowedAction::DeployNft),
+            _ => Err(format!("Unknown action: {}", s)),
+        }
+    }
+}
+
+pub(crate) fn default_action_providers() -> Vec<ActionProvider> {
+    vec![ActionProvider::Wallet]
+}
+
+/// Spending limits the agent enforces on every value transfer
+///
+/// Amounts are in whole units of the asset being moved (e.g. `0.5` ETH).
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct BudgetConfig {
+    /// Largest amount a single transaction may move
+    #[cfg_attr(feature = "scale", codec(encoded_as = "crate::codec::scale::F64Bits"))]
+    pub max_transaction_value: f64,
+    /// Total amount the agent may move per UTC day
+    #[cfg_attr(feature = "scale", codec(encoded_as = "crate::codec::scale::F64Bits"))]
+    pub daily_cap: f64,
+    /// Asset IDs (e.g. `eth`, `usdc`) the agent may move
+    pub allowed_assets: Vec<String>,
+}
+
+impl BudgetConfig {
+    /// Most assets a budget may allow
+    pub const MAX_ALLOWED_ASSETS: usize = 32;
+
+    /// Longest accepted asset ID
+    pub const MAX_ASSET_LEN: usize = 16;
+
+    /// Checks that the limits are positive and consistent
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [
+            ("Max transaction value", self.max_transaction_value),
+            ("Daily cap", self.daily_cap),
+        ] {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(format!("{} must be a positive number, got {}", name, value));
+            }
+        }
+        if self.max_transaction_value > self.daily_cap {
+            return Err("Max transaction value must not exceed the daily cap".to_string());
+        }
+        if self.allowed_assets.is_empty() {
+            return Err("At least one allowed asset is required".to_string());
+        }
+        if self.allowed_assets.len() > Self::MAX_ALLOWED_ASSETS {
+            return Err(format!(
+                "At most {} allowed assets may be listed",
+                Self::MAX_ALLOWED_ASSETS
+            ));
+        }
+        if let Some(asset) = self.allowed_assets.iter().find(|asset| {
+            asset.is_empty()
+                || asset.len() > Self::MAX_ASSET_LEN
+                || !asset.chars().all(|c| c.is_ascii_alphanumeric())
+        }) {
+            return Err(format!("Invalid asset ID: {:?}", asset));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
 pub struct DeploymentConfig {
-    pub tee_enabled: bool,
+    /// Where the agent runs; a local container on a free port by default
+    #[serde(default)]
+    pub target: DeploymentTarget,
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "scale",
+        codec(encoded_as = "crate::codec::scale::OptionPath")
+    )]
     pub docker_compose_path: Option<PathBuf>,
-    pub http_port: Option<u16>,
+    /// Restricts the hosts the agent may connect to; unrestricted if unset
+    #[serde(default)]
+    pub egress: Option<EgressPolicy>,
+    /// What the agent's container runs; the template's image by default
+    #[serde(default)]
+    pub image: AgentImage,
+    /// Extra variables the agent is given on top of the template's, e.g. an RPC endpoint
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Resource preset of the agent's template, e.g. `medium`, sizing the agent's container
+    /// locally and its VM in a TEE
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<String>,
+    /// CPU and memory limits of a local agent's container; unlimited if unset. Disk isn't
+    /// limited locally.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<TeeConfig>,
+}
+
+impl DeploymentConfig {
+    /// Most extra variables an agent can be given
+    pub const MAX_ENV_VARS: usize = 32;
+    /// Longest value of an extra variable, in bytes
+    pub const MAX_ENV_VALUE_LEN: usize = 4096;
+
+    /// Checks the extra variables: at most [`Self::MAX_ENV_VARS`] of them, named with upper
+    /// case ASCII letters, digits and `_`, and none that the blueprint sets itself
+    pub fn validate_env(&self) -> Result<(), String> {
+        if self.env.len() > Self::MAX_ENV_VARS {
+            return Err(format!(
+                "At most {} extra variables are allowed",
+                Self::MAX_ENV_VARS
+            ));
+        }
+        for (name, value) in &self.env {
+            if name.is_empty()
+                || name.starts_with(|c: char| c.is_ascii_digit())
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+            {
+                return Err(format!("Invalid variable name {:?}", name));
+            }
+            if templates::is_reserved(name) {
+                return Err(format!("{} is set by the blueprint", name));
+            }
+            if value.len() > Self::MAX_ENV_VALUE_LEN || value.contains('\0') {
+                return Err(format!(
+                    "{} must be at most {} bytes, without NUL characters",
+                    name,
+                    Self::MAX_ENV_VALUE_LEN
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the size the agent asks for: a preset name or explicit limits, not both, and
+    /// never both a preset and a TEE target sized by hand
+    pub fn validate_size(&self) -> Result<(), String> {
+        if let Some(size) = &self.size {
+            if !is_preset_name(size) {
+                return Err(format!(
+                    "Sizes are 1 to 32 lowercase letters, digits, - or _, got {:?}",
+                    size
+                ));
+            }
+            if self.resources.is_some() {
+                return Err("Set either size or resources, not both".to_string());
+            }
+        }
+        match &self.target {
+            DeploymentTarget::Tee(tee) => {
+                if self.resources.is_some() {
+                    return Err(
+                        "TEE agents are sized by their target or size, not resources".to_string(),
+                    );
+                }
+                if self.size.is_some() && *tee != TeeConfig::default() {
+                    return Err(
+                        "Set either size or the TEE target's resources, not both".to_string()
+                    );
+                }
+            }
+            DeploymentTarget::Local { .. } => {
+                if let Some(resources) = &self.resources {
+                    resources.validate()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether `name` can name a resource preset, e.g. `medium`
+pub fn is_preset_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 32
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// What an agent's container runs
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AgentImage {
+    /// The image the template's compose file names
+    #[default]
+    Template,
+    /// An image built from another Dockerfile of the template, at `path` within it; not
+    /// available for TEE deployments, which can't build images
+    Dockerfile { path: String },
+    /// A prebuilt image, e.g. `ghcr.io/org/agent:1.2`; references without a digest are pinned
+    /// to the digest they resolve to when the agent is created
+    Image { reference: String },
+}
+
+impl AgentImage {
+    /// Variable recording a prebuilt image in the agent's .env
+    pub const IMAGE_VAR: &'static str = "AGENT_IMAGE";
+    /// Variable recording a custom Dockerfile in the agent's .env
+    pub const DOCKERFILE_VAR: &'static str = "AGENT_DOCKERFILE";
+
+    /// Longest accepted image reference or Dockerfile path
+    pub const MAX_LEN: usize = 255;
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            AgentImage::Template => Ok(()),
+            AgentImage::Dockerfile { path } => {
+                let valid = !path.is_empty()
+                    && path.len() <= Self::MAX_LEN
+                    && path
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'))
+                    && path
+                        .split('/')
+                        .all(|part| !part.is_empty() && part != "." && part != "..");
+                if !valid {
+                    return Err(format!(
+                        "Dockerfile path must be a relative path within the template, got {:?}",
+                        path
+                    ));
+                }
+                Ok(())
+            }
+            AgentImage::Image { reference } => {
+                let valid = !reference.is_empty()
+                    && reference.len() <= Self::MAX_LEN
+                    && !reference.starts_with(['-', '.', '/', ':', '@'])
+                    && reference.chars().all(|c| {
+                        c.is_ascii_lowercase()
+                            || c.is_ascii_digit()
+                            || matches!(c, '.' | '_' | '-' | '/' | ':' | '@')
+                    });
+                if !valid {
+                    return Err(format!("Invalid image reference {:?}", reference));
+                }
+                match reference.split_once('@') {
+                    Some((_, digest)) if !is_sha256_digest(digest) => Err(format!(
+                        "Image digest must be sha256:<64 hex digits>, got {:?}",
+                        digest
+                    )),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Returns whether a prebuilt image is pinned by digest
+    pub fn is_pinned(&self) -> bool {
+        match self {
+            AgentImage::Image { reference } => reference.contains('@'),
+            _ => true,
+        }
+    }
+
+    /// Returns the variables recording the image in the agent's .env
+    pub fn to_env_vars(&self) -> Vec<(&'static str, String)> {
+        match self {
+            AgentImage::Template => Vec::new(),
+            AgentImage::Dockerfile { path } => vec![(Self::DOCKERFILE_VAR, path.clone())],
+            AgentImage::Image { reference } => vec![(Self::IMAGE_VAR, reference.clone())],
+        }
+    }
+}
+
+pub(crate) fn is_sha256_digest(digest: &str) -> bool {
+    digest.strip_prefix("sha256:").is_some_and(|hex| {
+        hex.len() == 64 && hex.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+    })
+}
+
+/// Where an agent runs, recorded in its `.env` as `DEPLOYMENT_TARGET` so that `deploy_agent`
+/// deploys it where it was created for
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum DeploymentTarget {
+    /// A container on the operator's host, serving HTTP on `http_port`; a free port is
+    /// allocated if unset
+    Local {
+        #[serde(default)]
+        http_port: Option<u16>,
+        #[serde(default)]
+        websocket_port: WebSocketPort,
+    },
+    /// A confidential VM on Phala Cloud, which the operator must have enabled
+    Tee(TeeConfig),
+}
+
+impl Default for DeploymentTarget {
+    fn default() -> Self {
+        DeploymentTarget::local(None)
+    }
+}
+
+impl DeploymentTarget {
+    /// Agent variable recording the target
+    pub const ENV_VAR: &'static str = "DEPLOYMENT_TARGET";
+
+    /// A local container on `http_port`, with its WebSocket server on the port after it
+    pub fn local(http_port: Option<u16>) -> Self {
+        DeploymentTarget::Local {
+            http_port,
+            websocket_port: WebSocketPort::default(),
+        }
+    }
+
+    pub fn is_tee(&self) -> bool {
+        matches!(self, DeploymentTarget::Tee(_))
+    }
+
+    /// Returns the requested HTTP port of a local agent
+    pub fn http_port(&self) -> Option<u16> {
+        match self {
+            DeploymentTarget::Local { http_port, .. } => *http_port,
+            DeploymentTarget::Tee(_) => None,
+        }
+    }
+
+    /// Returns where a local agent serves WebSockets; TEE agents keep the default layout
+    pub fn websocket_port(&self) -> WebSocketPort {
+        match self {
+            DeploymentTarget::Local { websocket_port, .. } => *websocket_port,
+            DeploymentTarget::Tee(_) => WebSocketPort::default(),
+        }
+    }
+
+    /// Returns the name of the target, e.g. `local`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeploymentTarget::Local { .. } => "local",
+            DeploymentTarget::Tee(_) => "tee",
+        }
+    }
+
+    /// Returns the agent variables recording the target; ports are recorded separately
+    pub fn to_env_vars(&self) -> Vec<(&'static str, String)> {
+        let mut vars = vec![(Self::ENV_VAR, self.as_str().to_string())];
+        if let DeploymentTarget::Tee(tee) = self {
+            vars.push((TeeConfig::VCPU_VAR, tee.vcpu.to_string()));
+            vars.push((TeeConfig::MEMORY_VAR, tee.memory_mb.to_string()));
+            vars.push((TeeConfig::DISK_VAR, tee.disk_gb.to_string()));
+        }
+        vars
+    }
+
+    /// Reads the target back from an agent's variables, `None` for agents created before it
+    /// was recorded
+    pub fn from_env_vars(vars: &HashMap<String, String>) -> Result<Option<Self>, String> {
+        let parse = |name: &str, default: u64| -> Result<u64, String> {
+            vars.get(name).map_or(Ok(default), |value| {
+                value
+                    .parse()
+                    .map_err(|_| format!("Invalid {} {:?}", name, value))
+            })
+        };
+        match vars.get(Self::ENV_VAR).map(String::as_str) {
+            None => Ok(None),
+            Some("local") => Ok(Some(DeploymentTarget::local(None))),
+            Some("tee") => {
+                let default = TeeConfig::default();
+                Ok(Some(DeploymentTarget::Tee(TeeConfig {
+                    vcpu: parse(TeeConfig::VCPU_VAR, default.vcpu)?,
+                    memory_mb: parse(TeeConfig::MEMORY_VAR, default.memory_mb)?,
+                    disk_gb: parse(TeeConfig::DISK_VAR, default.disk_gb)?,
+                })))
+            }
+            Some(other) => Err(format!("Unknown {} {:?}", Self::ENV_VAR, other)),
+        }
+    }
+}
+
+/// Host port a local agent serves WebSockets on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(rename_all = "snake_case")]
+pub enum WebSocketPort {
+    /// The port after the HTTP port
+    #[default]
+    Adjacent,
+    /// A port of its own, e.g. to pack agents densely
+    Port(u16),
+    /// No WebSocket server, so the agent only takes its HTTP port
+    Disabled,
+}
+
+impl WebSocketPort {
+    /// Returns the WebSocket port of an agent serving HTTP on `http_port`, `None` if disabled
+    /// or if no port follows `http_port`
+    pub fn resolve(&self, http_port: u16) -> Option<u16> {
+        match self {
+            WebSocketPort::Adjacent => http_port.checked_add(1),
+            WebSocketPort::Port(port) => Some(*port),
+            WebSocketPort::Disabled => None,
+        }
+    }
+}
+
+/// Size of the confidential VM a TEE agent runs in
+///
+/// The VM configuration is part of what the environment encryption key is derived for, so
+/// the size is fixed when the agent is created.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(default, deny_unknown_fields)]
+pub struct TeeConfig {
+    pub vcpu: u64,
+    pub memory_mb: u64,
+    pub disk_gb: u64,
+}
+
+impl Default for TeeConfig {
+    fn default() -> Self {
+        Self {
+            vcpu: 2,
+            memory_mb: 2048,
+            disk_gb: 10,
+        }
+    }
+}
+
+impl TeeConfig {
+    const VCPU_VAR: &'static str = "TEE_VCPU";
+    const MEMORY_VAR: &'static str = "TEE_MEMORY_MB";
+    const DISK_VAR: &'static str = "TEE_DISK_GB";
+
+    /// Most virtual CPUs a TEE agent may request
+    pub const MAX_VCPU: u64 = 8;
+
+    /// Most memory a TEE agent may request, in MB
+    pub const MAX_MEMORY_MB: u64 = 16 * 1024;
+
+    /// Most disk a TEE agent may request, in GB
+    pub const MAX_DISK_GB: u64 = 100;
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=Self::MAX_VCPU).contains(&self.vcpu) {
+            return Err(format!("vCPUs must be between 1 and {}", Self::MAX_VCPU));
+        }
+        if !(512..=Self::MAX_MEMORY_MB).contains(&self.memory_mb) {
+            return Err(format!(
+                "Memory must be between 512 and {} MB",
+                Self::MAX_MEMORY_MB
+            ));
+        }
+        if !(1..=Self::MAX_DISK_GB).contains(&self.disk_gb) {
+            return Err(format!(
+                "Disk size must be between 1 and {} GB",
+                Self::MAX_DISK_GB
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Allowlist of the hosts an agent may reach, enforced by a firewall sidecar
+///
+/// The hosts the agent needs for its LLM provider, bot platform and CDP are always allowed,
+/// as are those its template lists (e.g. package registries); `allowed_hosts` adds others
+/// such as chosen RPC endpoints.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct EgressPolicy {
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+}
+
+impl EgressPolicy {
+    /// Most hosts a policy may list
+    pub const MAX_ALLOWED_HOSTS: usize = 32;
+
+    /// Hosts every agent needs: CDP
+    pub const DEFAULT_ALLOWED_HOSTS: &'static [&'static str] =
+        &["api.cdp.coinbase.com", "api.developer.coinbase.com"];
+
+    /// Checks that every host is a plain host name
+    pub fn validate(&self) -> Result<(), String> {
+        if self.allowed_hosts.len() > Self::MAX_ALLOWED_HOSTS {
+            return Err(format!(
+                "At most {} allowed hosts may be listed",
+                Self::MAX_ALLOWED_HOSTS
+            ));
+        }
+        if let Some(host) = self.allowed_hosts.iter().find(|host| !is_host_name(host)) {
+            return Err(format!("Invalid allowed host: {:?}", host));
+        }
+        Ok(())
+    }
+
+    /// Returns every host the agent may reach, including those its configuration needs
+    pub fn hosts(&self, agent_config: &AgentConfig) -> Vec<String> {
+        let mut hosts: Vec<String> = Self::DEFAULT_ALLOWED_HOSTS
+            .iter()
+            .map(|host| host.to_string())
+            .collect();
+        match agent_config.llm_provider {
+            LlmProvider::OpenAI => hosts.push("api.openai.com".to_string()),
+            LlmProvider::Anthropic => hosts.push("api.anthropic.com".to_string()),
+            LlmProvider::AzureOpenAI => hosts.extend(
+                agent_config
+                    .azure_openai
+                    .as_ref()
+                    .and_then(|azure| url_host(&azure.endpoint)),
+            ),
+            LlmProvider::Local => hosts.extend(
+                agent_config
+                    .local_model
+                    .as_ref()
+                    .and_then(|local| local.base_url.as_deref())
+                    .and_then(url_host),
+            ),
+        }
+        match agent_config.mode {
+            AgentMode::Telegram => hosts.push("api.telegram.org".to_string()),
+            AgentMode::Discord => {
+                hosts.extend(["discord.com", "gateway.discord.gg"].map(String::from))
+            }
+            AgentMode::Autonomous | AgentMode::Chat => {}
+        }
+        hosts.extend(self.allowed_hosts.iter().cloned());
+        hosts.sort();
+        hosts.dedup();
+        hosts
+    }
+}
+
+/// Whether `host` is a DNS name made of letters, digits, '-' and '.'
+fn is_host_name(host: &str) -> bool {
+    !host.is_empty()
+        && host.len() <= 253
+        && host.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// Returns the host of a URL if it is a valid host name
+fn url_host(url: &str) -> Option<String> {
+    let host = url::Url::parse(url).ok()?.host_str()?.to_string();
+    is_host_name(&host).then_some(host)
+}
+
+/// Sensitive value that is never shown by `Debug` or `Display` and is wiped from memory when
+/// dropped
+///
+/// Serializes as the bare value; use [`Secret::expose`] where the value itself is needed.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(transparent)]
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Returns the secret value
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl<T: Zeroize> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl From<String> for Secret<String> {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret<String> {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(default, deny_unknown_fields)]
 pub struct ApiKeyConfig {
-    pub openai_api_key: Option<String>,
-    pub cdp_api_key_name: Option<String>,
-    pub cdp_api_key_private_key: Option<String>,
+    pub openai_api_key: Option<Secret<String>>,
+    pub anthropic_api_key: Option<Secret<String>>,
+    pub azure_openai_api_key: Option<Secret<String>>,
+    pub cdp_api_key_name: Option<Secret<String>>,
+    pub cdp_api_key_private_key: Option<Secret<String>>,
+    /// Reference to the private key owning a smart wallet; only used by `SmartWallet` wallets
+    pub cdp_wallet_secret: Option<Secret<String>>,
+    /// Reference to the private key of the agent's account; only used by `PrivateKey` wallets
+    pub wallet_private_key: Option<Secret<String>>,
+    /// Required for the `Telegram` agent mode
+    pub telegram_bot_token: Option<Secret<String>>,
+    /// Required for the `Discord` agent mode
+    pub discord_bot_token: Option<Secret<String>>,
+}
+
+impl ApiKeyConfig {
+    /// Longest accepted key, token or secret reference, in bytes
+    pub const MAX_KEY_LEN: usize = 8192;
+
+    /// Records every key that is too long, with field paths under `prefix`
+    pub fn collect_violations(&self, prefix: &str, violations: &mut Violations) {
+        for (name, value) in [
+            ("openai_api_key", &self.openai_api_key),
+            ("anthropic_api_key", &self.anthropic_api_key),
+            ("azure_openai_api_key", &self.azure_openai_api_key),
+            ("cdp_api_key_name", &self.cdp_api_key_name),
+            ("cdp_api_key_private_key", &self.cdp_api_key_private_key),
+            ("cdp_wallet_secret", &self.cdp_wallet_secret),
+            ("wallet_private_key", &self.wallet_private_key),
+            ("telegram_bot_token", &self.telegram_bot_token),
+            ("discord_bot_token", &self.discord_bot_token),
+        ] {
+            if let Some(value) = value {
+                violations.check_len(
+                    format!("{}.{}", prefix, name),
+                    value.expose(),
+                    Self::MAX_KEY_LEN,
+                );
+            }
+        }
+
+        // Wallet keys give away the wallet's funds, and job parameters are public, so only
+        // references to them are accepted
+        for (name, value) in [
+            ("cdp_wallet_secret", &self.cdp_wallet_secret),
+            ("wallet_private_key", &self.wallet_private_key),
+        ] {
+            if value
+                .as_ref()
+                .is_some_and(|value| SecretUri::parse(value.expose()).is_none())
+            {
+                violations.push(
+                    format!("{}.{}", prefix, name),
+                    "Must be a secret reference such as `vault://kv/agents/wallet`, since job \
+                     parameters are public",
+                );
+            }
+        }
+    }
+
+    /// Returns the bot token for the given agent mode, if the mode needs one and it is set
+    pub fn bot_token(&self, mode: &AgentMode) -> Option<&str> {
+        let token = match mode {
+            AgentMode::Telegram => self.telegram_bot_token.as_ref(),
+            AgentMode::Discord => self.discord_bot_token.as_ref(),
+            AgentMode::Autonomous | AgentMode::Chat => None,
+        };
+        token
+            .map(|token| token.expose().as_str())
+            .filter(|token| !token.trim().is_empty())
+    }
 }
 
 // Job parameters and results
-#[derive(Clone, Debug, Serialize, Deserialize)]
+//
+// Parameters reject unknown fields, so that a misspelled or outdated field fails the job
+// instead of being ignored, and renamed fields keep accepting their old name as an alias.
+// Results accept unknown fields, so that clients keep reading those of newer blueprints.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
 pub struct CreateAgentParams {
     pub name: String,
     pub agent_config: AgentConfig,
+    /// Action providers the agent may use; unknown providers are rejected
+    #[serde(default = "default_action_providers")]
+    pub action_providers: Vec<ActionProvider>,
+    /// Spending limits; operators may refuse to deploy agents without one
+    #[serde(default)]
+    pub budget: Option<BudgetConfig>,
+    /// On-chain actions the agent may execute; all actions are allowed if unset
+    #[serde(default)]
+    pub allowed_actions: Option<Vec<AllowedAction>>,
+    /// Deploys locally on free ports if unset
+    #[serde(default)]
+    pub deployment_config: DeploymentConfig,
+    /// Template the agent is created from: one under the operator's `templates` directory,
+    /// or a pinned git repository such as `git+https://github.com/org/template#v1.2.0`;
+    /// `starter` if unset
+    #[serde(default)]
+    pub template_id: Option<String>,
+    pub api_key_config: ApiKeyConfig,
+    /// HTTPS URL the job's outcome is posted to when it finishes
+    #[serde(default)]
+    pub callback_url: Option<String>,
+}
+
+impl CreateAgentParams {
+    /// Longest accepted agent name, in characters
+    pub const MAX_NAME_LEN: usize = 64;
+
+    /// Lowest HTTP port an agent may be given; privileged ports are refused
+    pub const MIN_HTTP_PORT: u16 = 1024;
+
+    /// Ports of the operator's own services (node RPC, p2p and metrics) that an agent's HTTP
+    /// and WebSocket ports may not take
+    pub const RESERVED_PORTS: &'static [u16] = &[8545, 8546, 9615, 9933, 9944, 30333];
+
+    /// Returns the ID of the template the agent is created from
+    pub fn template_id(&self) -> &str {
+        self.template_id
+            .as_deref()
+            .unwrap_or(templates::DEFAULT_TEMPLATE)
+    }
+
+    /// Validates every field, reporting all violations in a single error
+    pub fn validate(&self) -> Result<(), String> {
+        let mut violations = Violations::new();
+
+        if self.name.trim().is_empty() {
+            violations.push("name", "Name must not be empty");
+        } else if self.name.chars().count() > Self::MAX_NAME_LEN {
+            violations.push(
+                "name",
+                format!("Name must be at most {} characters", Self::MAX_NAME_LEN),
+            );
+        } else if !self
+            .name
+            .chars()
+            .all(|c| c.is_alphanumeric() || " -_.".contains(c))
+        {
+            violations.push(
+                "name",
+                "Name may only contain letters, digits, spaces, '-', '_' and '.'",
+            );
+        }
+
+        self.agent_config
+            .collect_violations("agent_config", &mut violations);
+
+        if self.action_providers.is_empty() {
+            violations.push(
+                "action_providers",
+                "At least one action provider must be enabled",
+            );
+        }
+        for (i, provider) in self.action_providers.iter().enumerate() {
+            if self.action_providers[..i].contains(provider) {
+                violations.push(
+                    "action_providers",
+                    format!("Action provider {} is listed twice", provider),
+                );
+            }
+        }
+
+        if let Some(budget) = &self.budget {
+            violations.check("budget", budget.validate());
+        }
+
+        // The WebSocket port is the one after the HTTP port by default, so it must fit too
+        let websocket_port = self.deployment_config.target.websocket_port();
+        if let Some(port) = self.deployment_config.target.http_port() {
+            let adjacent = websocket_port == WebSocketPort::Adjacent;
+            if port < Self::MIN_HTTP_PORT || (adjacent && port == u16::MAX) {
+                violations.push(
+                    "deployment_config.target.http_port",
+                    format!(
+                        "HTTP port must be between {} and {}, got {}",
+                        Self::MIN_HTTP_PORT,
+                        if adjacent { u16::MAX - 1 } else { u16::MAX },
+                        port
+                    ),
+                );
+            } else if adjacent
+                && (Self::RESERVED_PORTS.contains(&port)
+                    || Self::RESERVED_PORTS.contains(&(port + 1)))
+            {
+                violations.push(
+                    "deployment_config.target.http_port",
+                    format!(
+                        "Ports {} and {} must not include one reserved by the operator: {:?}",
+                        port,
+                        port + 1,
+                        Self::RESERVED_PORTS
+                    ),
+                );
+            } else if Self::RESERVED_PORTS.contains(&port) {
+                violations.push(
+                    "deployment_config.target.http_port",
+                    format!(
+                        "Port {} is reserved by the operator: {:?}",
+                        port,
+                        Self::RESERVED_PORTS
+                    ),
+                );
+            }
+        }
+        if let WebSocketPort::Port(port) = websocket_port {
+            if port < Self::MIN_HTTP_PORT || Self::RESERVED_PORTS.contains(&port) {
+                violations.push(
+                    "deployment_config.target.websocket_port",
+                    format!(
+                        "WebSocket port must be at least {} and not one reserved by the operator: {:?}",
+                        Self::MIN_HTTP_PORT,
+                        Self::RESERVED_PORTS
+                    ),
+                );
+            } else if self.deployment_config.target.http_port() == Some(port) {
+                violations.push(
+                    "deployment_config.target.websocket_port",
+                    "WebSocket port must differ from the HTTP port",
+                );
+            }
+        }
+        if let Some(egress) = &self.deployment_config.egress {
+            violations.check("deployment_config.egress", egress.validate());
+        }
+        violations.check(
+            "deployment_config.image",
+            self.deployment_config.image.validate(),
+        );
+        violations.check(
+            "deployment_config.env",
+            self.deployment_config.validate_env(),
+        );
+        violations.check(
+            "deployment_config.size",
+            self.deployment_config.validate_size(),
+        );
+        if let Some(template_id) = &self.template_id {
+            violations.check("template_id", TemplateRef::parse(template_id).map(|_| ()));
+        }
+        if let DeploymentTarget::Tee(tee) = &self.deployment_config.target {
+            violations.check("deployment_config.target", tee.validate());
+            if self.deployment_config.docker_compose_path.is_some() {
+                violations.push(
+                    "deployment_config.docker_compose_path",
+                    "A custom compose file cannot be used for TEE deployments",
+                );
+            }
+            if let AgentImage::Dockerfile { .. } = self.deployment_config.image {
+                violations.push(
+                    "deployment_config.image",
+                    "TEE deployments can't build images, so they take a prebuilt image instead",
+                );
+            }
+        }
+
+        self.api_key_config
+            .collect_violations("api_key_config", &mut violations);
+        let mode = &self.agent_config.mode;
+        if let Some(var) = mode.bot_token_env_var() {
+            if self.api_key_config.bot_token(mode).is_none() {
+                violations.push(
+                    "api_key_config",
+                    format!("{} is required for the {} mode", var, mode),
+                );
+            }
+        }
+
+        // Wallet keys are references, resolved and checked when the agent is deployed; TEE
+        // agents take them in their encrypted environment instead
+        let wallet = &self.agent_config.wallet;
+        let tee = self.deployment_config.target.is_tee();
+        for (name, key, used, wallet_type) in [
+            (
+                "api_key_config.cdp_wallet_secret",
+                &self.api_key_config.cdp_wallet_secret,
+                wallet.uses_wallet_secret(),
+                "smart_wallet",
+            ),
+            (
+                "api_key_config.wallet_private_key",
+                &self.api_key_config.wallet_private_key,
+                wallet.uses_private_key(),
+                "private_key",
+            ),
+        ] {
+            match key {
+                Some(_) if !used => violations.push(
+                    name,
+                    format!(
+                        "Only used by {} wallets, not {} wallets",
+                        wallet_type,
+                        wallet.env_value()
+                    ),
+                ),
+                Some(_) if tee => violations.push(
+                    name,
+                    "TEE agents take their wallet key in their encrypted environment",
+                ),
+                _ => {}
+            }
+        }
+
+        if let Some(callback_url) = &self.callback_url {
+            violations.check("callback_url", validate_callback_url(callback_url));
+        }
+
+        violations.into_result()
+    }
+}
+
+/// Parameters for creating several agents that share one configuration
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct CreateAgentSwarmParams {
+    /// Agents are named `{name}-{n}`, counting from 1
+    pub name: String,
+    pub count: u32,
+    pub agent_config: AgentConfig,
+    #[serde(default = "default_action_providers")]
+    pub action_providers: Vec<ActionProvider>,
+    #[serde(default)]
+    pub budget: Option<BudgetConfig>,
+    #[serde(default)]
+    pub allowed_actions: Option<Vec<AllowedAction>>,
+    /// `http_port` is the first agent's port and each agent takes the next HTTP/WebSocket pair,
+    /// or the next HTTP port without WebSockets; without it, every agent is given free ports
+    #[serde(default)]
     pub deployment_config: DeploymentConfig,
+    /// Template every agent is created from; `starter` if unset
+    #[serde(default)]
+    pub template_id: Option<String>,
+    /// Keys shared by every agent
     pub api_key_config: ApiKeyConfig,
+    /// Per-agent keys used instead of the shared keys; needs exactly `count` entries
+    #[serde(default)]
+    pub per_agent_api_keys: Option<Vec<ApiKeyConfig>>,
+}
+
+impl CreateAgentSwarmParams {
+    /// Largest swarm a single job may create
+    pub const MAX_SWARM_SIZE: u32 = 32;
+
+    /// Expands the swarm into the parameters of each agent, with sequential ports if the
+    /// swarm asks for a starting port
+    pub fn agent_params(&self) -> Result<Vec<CreateAgentParams>, String> {
+        if self.count == 0 || self.count > Self::MAX_SWARM_SIZE {
+            return Err(format!(
+                "Swarm size must be between 1 and {}, got {}",
+                Self::MAX_SWARM_SIZE,
+                self.count
+            ));
+        }
+        if let Some(keys) = &self.per_agent_api_keys {
+            if keys.len() != self.count as usize {
+                return Err(format!(
+                    "Expected {} per-agent API key configurations, got {}",
+                    self.count,
+                    keys.len()
+                ));
+            }
+        }
+
+        // Each agent uses an HTTP port and, by default, the WebSocket port right after it
+        let websocket_port = self.deployment_config.target.websocket_port();
+        let stride = match websocket_port {
+            WebSocketPort::Adjacent => 2,
+            WebSocketPort::Disabled => 1,
+            WebSocketPort::Port(_) if self.count > 1 => {
+                return Err("Agents of a swarm can't share one WebSocket port".to_string());
+            }
+            WebSocketPort::Port(_) => 1,
+        };
+        let base_port = self.deployment_config.target.http_port();
+        if let Some(base_port) = base_port {
+            let last_port = u32::from(base_port) + stride * self.count - 1;
+            if last_port > u32::from(u16::MAX) {
+                return Err(format!(
+                    "A swarm of {} agents starting at port {} runs out of ports",
+                    self.count, base_port
+                ));
+            }
+        }
+
+        Ok((0..self.count)
+            .map(|i| {
+                let api_key_config = match &self.per_agent_api_keys {
+                    Some(keys) => keys[i as usize].clone(),
+                    None => self.api_key_config.clone(),
+                };
+                CreateAgentParams {
+                    name: format!("{}-{}", self.name, i + 1),
+                    agent_config: self.agent_config.clone(),
+                    action_providers: self.action_providers.clone(),
+                    budget: self.budget.clone(),
+                    allowed_actions: self.allowed_actions.clone(),
+                    deployment_config: DeploymentConfig {
+                        target: match &self.deployment_config.target {
+                            DeploymentTarget::Local { .. } => DeploymentTarget::Local {
+                                http_port: base_port.map(|port| port + (stride * i) as u16),
+                                websocket_port,
+                            },
+                            tee => tee.clone(),
+                        },
+                        ..self.deployment_config.clone()
+                    },
+                    template_id: self.template_id.clone(),
+                    api_key_config,
+                    callback_url: None,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Parameters for creating several independently configured agents in one job
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct CreateAgentsParams {
+    pub agents: Vec<CreateAgentParams>,
+}
+
+impl CreateAgentsParams {
+    /// Largest batch a single job may create
+    pub const MAX_BATCH_SIZE: usize = 32;
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.agents.is_empty() || self.agents.len() > Self::MAX_BATCH_SIZE {
+            return Err(format!(
+                "Batch size must be between 1 and {}, got {}",
+                Self::MAX_BATCH_SIZE,
+                self.agents.len()
+            ));
+        }
+        Ok(())
+    }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
 pub struct DeployAgentParams {
-    pub agent_id: String,
+    pub agent_id: AgentId,
+    /// Replaces the keys given at creation
+    #[serde(default)]
     pub api_key_config: Option<ApiKeyConfig>,
-    pub encrypted_env: Option<String>,
-    pub tee_pubkey: Option<String>,
-    pub tee_app_id: Option<String>,
-    pub tee_salt: Option<String>,
+    /// Encrypted environment of an agent created for the TEE
+    #[serde(default)]
+    pub tee: Option<TeeEnvironment>,
+    /// HTTPS URL the job's outcome is posted to when it finishes
+    #[serde(default)]
+    pub callback_url: Option<String>,
+}
+
+impl DeployAgentParams {
+    /// Longest accepted agent ID
+    pub const MAX_AGENT_ID_LEN: usize = AgentId::MAX_LEN;
+
+    /// Longest accepted encrypted TEE environment, in bytes
+    pub const MAX_ENCRYPTED_ENV_LEN: usize = 128 * 1024;
+
+    /// Longest accepted TEE public key, app ID or salt, in bytes
+    pub const MAX_TEE_FIELD_LEN: usize = 512;
+
+    /// Validates field sizes, and that the encrypted TEE environment is not mixed with
+    /// plaintext keys
+    pub fn validate(&self) -> Result<(), String> {
+        let mut violations = Violations::new();
+
+        if let Some(tee) = &self.tee {
+            violations.check_len(
+                "tee.encrypted_env",
+                tee.encrypted_env.expose(),
+                Self::MAX_ENCRYPTED_ENV_LEN,
+            );
+            for (field, value) in [
+                ("tee.pubkey", tee.pubkey.expose()),
+                ("tee.app_id", &tee.app_id),
+                ("tee.salt", tee.salt.expose()),
+            ] {
+                violations.check_len(field, value, Self::MAX_TEE_FIELD_LEN);
+            }
+            if self.api_key_config.is_some() {
+                violations.push(
+                    "api_key_config",
+                    "Plaintext API keys cannot be combined with an encrypted TEE environment",
+                );
+            }
+        }
+        if let Some(api_key_config) = &self.api_key_config {
+            api_key_config.collect_violations("api_key_config", &mut violations);
+        }
+
+        if let Some(callback_url) = &self.callback_url {
+            violations.check("callback_url", validate_callback_url(callback_url));
+        }
+
+        violations.into_result()
+    }
+}
+
+/// Environment of a TEE agent, encrypted to the key of the app it was created with
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct TeeEnvironment {
+    #[serde(alias = "encrypted_env_vars")]
+    pub encrypted_env: Secret<String>,
+    pub pubkey: Secret<String>,
+    pub app_id: String,
+    pub salt: Secret<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Phala Cloud app a TEE agent is bound to; callers encrypt the agent's environment to
+/// `pubkey` before deploying it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct TeeApp {
+    pub pubkey: String,
+    pub app_id: String,
+    pub salt: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct AgentActionsParams {
+    pub agent_id: AgentId,
+    /// Only return actions at or after this unix timestamp (seconds)
+    #[serde(default)]
+    pub since: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecretAuditParams {
+    /// Only return records of this agent
+    pub agent_id: Option<AgentId>,
+    /// Only return records at or after this unix timestamp (seconds)
+    pub since: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct SecretAuditResult {
+    pub records: Vec<AuditRecord>,
+    /// Whether the whole log still forms an unbroken hash chain
+    pub chain_valid: bool,
+    /// Where the chain is broken, if it is
+    pub chain_error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct AgentStatusParams {
+    pub agent_id: AgentId,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct CollectDiagnosticsParams {
+    pub agent_id: AgentId,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct StopAgentParams {
+    pub agent_id: AgentId,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct AgentStopResult {
+    pub agent_id: AgentId,
+    /// Whether the agent's container was running before it was stopped
+    pub was_running: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct DiagnosticsResult {
+    pub agent_id: AgentId,
+    /// Path of the `.tar.gz` bundle on the operator's machine
+    pub archive_path: String,
+    pub size_bytes: u64,
+    /// Hex-encoded keccak-256 hash of the bundle, to check the copy handed over to support
+    pub archive_hash: String,
+    /// Names of the files in the bundle
+    pub files: Vec<String>,
+    /// Job call that produced this result
+    #[serde(default)]
+    pub call_id: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
 pub struct AgentCreationResult {
-    pub agent_id: String,
+    pub agent_id: AgentId,
     pub files_created: Vec<String>,
-    pub tee_pubkey: Option<String>,
-    pub tee_app_id: Option<String>,
-    pub tee_salt: Option<String>,
+    /// App the agent's environment must be encrypted for, for TEE agents
+    #[serde(default)]
+    pub tee: Option<TeeApp>,
+    /// Network the agent's wallet lives on
+    pub network_id: String,
+    /// Generation settings rendered into the agent's environment
+    pub system_prompt: Option<String>,
+    #[cfg_attr(
+        feature = "scale",
+        codec(encoded_as = "crate::codec::scale::OptionF32Bits")
+    )]
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    /// Account that created the agent, if the caller was recorded
+    pub owner: Option<String>,
+    /// Prebuilt image the agent runs, pinned by digest, if it doesn't run the template's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// Template the agent was created from
+    #[serde(default)]
+    pub template: Option<AgentTemplate>,
+    /// Checksums of the agent's compose file and other files, which `deploy_agent` checks
+    /// before deploying it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksums: Option<AgentChecksums>,
+    /// Post-create hooks the template ran on the agent's directory, with their logs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<HookRun>,
+    /// Job call that produced this result
+    #[serde(default)]
+    pub call_id: Option<u64>,
+    /// Time spent in each stage, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stage_timings: Vec<StageTiming>,
+    /// Operator signature over this result, if the operator signs results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator_signature: Option<OperatorSignature>,
+}
+
+/// Template an agent was created from
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct AgentTemplate {
+    /// Local template ID or git reference
+    pub id: String,
+    /// Version from the template's manifest
+    pub version: String,
+    /// Commit git templates were checked out at, or digest OCI templates were pulled at
+    #[serde(default)]
+    pub commit: Option<String>,
+}
+
+/// Checksums of an agent's files when it was created, each `sha256:<hex>`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct AgentChecksums {
+    /// Of the rendered `docker-compose.yml`, which TEE keys are bound to
+    pub compose: String,
+    /// Of the files copied from the template, and those its post-create hooks made
+    pub files: String,
+}
+
+/// A post-create hook a template ran on an agent's directory
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct HookRun {
+    pub name: String,
+    pub duration_ms: u64,
+    /// End of the hook's output, stdout then stderr
+    pub log: String,
+}
+
+/// Time spent in one stage of creating or deploying an agent
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct StageTiming {
+    /// Stage name, e.g. `template_copy`, `image_pull` or `health_wait`
+    pub stage: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct AgentSwarmResult {
+    /// One result per agent, in creation order
+    pub agents: Vec<AgentCreationResult>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Outcome of creating one agent of a batch
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct BatchItemResult {
+    /// Position of the agent in the batch
+    #[cfg_attr(feature = "scale", codec(encoded_as = "crate::codec::scale::Usize"))]
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<AgentCreationResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<BlueprintError>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct BatchCreationResult {
+    #[cfg_attr(feature = "scale", codec(encoded_as = "crate::codec::scale::Usize"))]
+    pub created: usize,
+    #[cfg_attr(feature = "scale", codec(encoded_as = "crate::codec::scale::Usize"))]
+    pub failed: usize,
+    /// One result per agent, in batch order
+    pub items: Vec<BatchItemResult>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
 pub struct AgentDeploymentResult {
-    pub agent_id: String,
+    pub agent_id: AgentId,
+    /// Where the agent runs; local targets carry the HTTP port the agent was given
+    #[serde(default)]
+    pub target: DeploymentTarget,
     pub tee_pubkey: Option<String>,
     pub tee_app_id: Option<String>,
+    /// URL the agent serves on; behind the operator's TLS proxy when one is configured
+    #[serde(default, alias = "endpoint")]
+    pub endpoint_url: Option<String>,
+    /// URL of the agent's WebSocket server
+    #[serde(default)]
+    pub websocket_url: Option<String>,
+    /// Name of the agent's local container
+    #[serde(default)]
+    pub container_name: Option<String>,
+    /// ID of the confidential VM running a TEE agent
+    #[serde(default)]
+    pub cvm_id: Option<u64>,
+    /// Unix timestamp (seconds) of the deployment, or of the hand-off for agents another
+    /// operator deploys
+    #[serde(default)]
+    pub deployed_at: u64,
+    /// Index of the operator running the agent, when operators coordinate deployments
+    #[serde(default)]
+    pub deployed_by: Option<u32>,
+    /// Job call that produced this result
+    #[serde(default)]
+    pub call_id: Option<u64>,
+    /// Time spent in each stage, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stage_timings: Vec<StageTiming>,
+    /// Public URL of the agent when the operator exposes agents through a tunnel
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_url: Option<String>,
+    /// Address of the agent's wallet, as reported by the agent once healthy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wallet_address: Option<String>,
+    /// Chain the agent's wallet lives on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_id: Option<String>,
+    /// Operator signature over this result, if the operator signs results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator_signature: Option<OperatorSignature>,
+}
+
+impl SignedResult for AgentCreationResult {
+    fn operator_signature_mut(&mut self) -> &mut Option<OperatorSignature> {
+        &mut self.operator_signature
+    }
+}
+
+impl SignedResult for AgentDeploymentResult {
+    fn operator_signature_mut(&mut self) -> &mut Option<OperatorSignature> {
+        &mut self.operator_signature
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct AgentStatusResult {
+    pub agent_id: AgentId,
+    /// Ports assigned at creation, if this operator still knows them
+    pub http_port: Option<u16>,
+    pub websocket_port: Option<u16>,
+    /// Whether the agent's local container is running
+    pub running: bool,
+    /// Active action policy; `None` means all actions are allowed
+    pub allowed_actions: Option<Vec<AllowedAction>>,
+}
+
+/// A single on-chain action (trade, transfer, deploy, ...) executed by an agent
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct AgentAction {
+    pub timestamp: u64,
+    pub action: String,
+    pub tx_hash: Option<String>,
+    pub details: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct AgentActionsResult {
+    pub agent_id: AgentId,
+    pub actions: Vec<AgentAction>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct GetAgentBalancesParams {
+    pub agent_id: AgentId,
+    /// ERC-20 contract addresses whose balances to read, besides the native balance
+    #[serde(default)]
+    pub tokens: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct TokenBalance {
+    /// ERC-20 contract address
+    pub token: String,
+    /// Balance in the token's smallest unit, as a decimal string
+    pub balance: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct AgentBalancesResult {
+    pub agent_id: AgentId,
+    pub wallet_address: String,
+    pub network_id: String,
+    /// Native balance in wei, as a decimal string
+    pub native_balance: String,
+    pub tokens: Vec<TokenBalance>,
+}
+
+/// Wallet an agent serves at `GET /wallet`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentWallet {
+    /// Address of the agent's wallet, e.g. `0x` followed by 40 hex digits
+    pub address: String,
+    /// Chain the wallet lives on, e.g. `base-sepolia`
+    pub network_id: String,
+    /// Native balance in wei, as a decimal string; agents predating it leave it out
+    #[serde(default)]
+    pub balance: Option<String>,
+}
+
+impl AgentWallet {
+    /// Checks that the wallet an agent reported can be passed on in results
+    pub fn validate(&self) -> Result<(), String> {
+        let hex = self.address.strip_prefix("0x").unwrap_or_default();
+        if hex.len() != 40 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid wallet address: {}", self.address));
+        }
+        if self.network_id.is_empty()
+            || self.network_id.len() > 64
+            || !self
+                .network_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!("Invalid network ID: {}", self.network_id));
+        }
+        if let Some(balance) = &self.balance {
+            if balance.is_empty()
+                || balance.len() > 78
+                || !balance.chars().all(|c| c.is_ascii_digit())
+            {
+                return Err(format!("Invalid balance: {}", balance));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(deny_unknown_fields)]
+pub struct FundAgentParams {
+    pub agent_id: AgentId,
+    /// Seconds to wait for the funds to show in the wallet's balance, up to
+    /// [`MAX_FUND_WAIT_SECS`](crate::fund_agent::MAX_FUND_WAIT_SECS); 0 returns once the funds
+    /// are requested
+    #[serde(default)]
+    pub wait_secs: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct FundAgentResult {
+    pub agent_id: AgentId,
+    /// Address of the funded wallet
+    pub wallet_address: String,
+    pub network_id: String,
+    /// Hashes of the faucet's transactions
+    pub tx_hashes: Vec<String>,
+    /// Last balance the agent reported, in wei
+    pub balance: Option<String>,
+    /// Whether the balance rose before the wait ended
+    pub funded: bool,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParamsSchemaParams {
+    /// Jobs to describe, e.g. `create_agent`; every job with versioned parameters if empty
+    pub jobs: Vec<String>,
+}
+
+/// Parameters a job accepts, as served by the running blueprint
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct ParamsSchema {
+    pub job: String,
+    /// Version to put in the parameters' envelope
+    pub version: u32,
+    /// Oldest version still accepted and upgraded
+    pub min_version: u32,
+    /// JSON Schema of the parameters in their current version
+    #[cfg_attr(feature = "scale", codec(encoded_as = "crate::codec::scale::JsonText"))]
+    pub schema: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct ParamsSchemaResult {
+    /// Semantic version of the blueprint, e.g. `0.1.0`
+    pub blueprint_version: String,
+    pub schemas: Vec<ParamsSchema>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValidateTemplateParams {
+    /// Template to validate, as in `create_agent`; `starter` if unset
+    pub template_id: Option<String>,
+    /// Also build the template's Dockerfile, which only the service owner may ask for
+    pub build: bool,
+}
+
+/// Outcome of one check of a template
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct TemplateCheck {
+    /// What was checked: `manifest`, `files`, `compose`, `env` or `build`
+    pub name: String,
+    pub ok: bool,
+    /// Why the check failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct TemplateValidationResult {
+    pub template_id: String,
+    /// Version from the template's manifest, if it could be read
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Whether every check passed
+    pub valid: bool,
+    pub checks: Vec<TemplateCheck>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListTemplatesParams {
+    /// Only list the templates supporting this mode
+    pub mode: Option<AgentMode>,
+}
+
+/// Policy an agent's runtime enforces on itself, which templates declare support for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentPolicy {
+    /// Spending limits, from `budget`
+    Budget,
+    /// Restricted on-chain actions, from `allowed_actions`
+    AllowedActions,
+}
+
+impl fmt::Display for AgentPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentPolicy::Budget => write!(f, "budget"),
+            AgentPolicy::AllowedActions => write!(f, "allowed_actions"),
+        }
+    }
+}
+
+/// A template agents can be created from, as described by its manifest
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct TemplateInfo {
+    /// ID to pass as `template_id` to `create_agent`
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub version: String,
+    /// Modes agents of the template can be created in
+    pub modes: Vec<AgentMode>,
+    /// Variables, such as API keys, that must be set when the agent is deployed locally
+    pub required_env: Vec<String>,
+    /// Variables also required for agents with a given kind of wallet, by `WALLET_TYPE`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub wallet_env: BTreeMap<String, Vec<String>>,
+    /// Policies the template's agents enforce; agents asking for others are refused
+    #[serde(default)]
+    pub policies: Vec<AgentPolicy>,
+    /// Ports agents get unless they ask for others or the operator allocates them
+    pub http_port: u16,
+    #[serde(default)]
+    pub websocket_port: Option<u16>,
+    /// TEE size the template's agents are meant to run with
+    pub resources: TeeConfig,
+    /// Sizes agents of the template can ask for by name
+    #[serde(default)]
+    pub presets: BTreeMap<String, TeeConfig>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct ListTemplatesResult {
+    /// Installed templates, sorted by ID
+    pub templates: Vec<TemplateInfo>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScaffoldTemplateParams {
+    /// ID of the new template, as later passed as `template_id` to `create_agent`
+    pub template_id: String,
+    /// Human-readable name; the ID if unset
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Modes agents of the template can be created in; `Chat` and `Autonomous` if unset
+    pub modes: Option<Vec<AgentMode>>,
+    /// Image the template's Dockerfile starts from; `node:20-slim` if unset
+    pub base_image: Option<String>,
+    /// Port the agent's HTTP server listens on in its container; 3000 if unset
+    pub http_port: Option<u16>,
+    /// Also write the template into the operator's templates directory, which only the
+    /// service owner may ask for
+    pub install: bool,
+}
+
+/// A file of a scaffolded template
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct ScaffoldedFile {
+    /// Path relative to the template's directory
+    pub path: String,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(
+    feature = "scale",
+    derive(parity_scale_codec::Encode, parity_scale_codec::Decode)
+)]
+pub struct ScaffoldTemplateResult {
+    pub template_id: String,
+    /// The template's files, to be filled in with the agent's code
+    pub files: Vec<ScaffoldedFile>,
+    /// Whether the template was written into the operator's templates directory
+    pub installed: bool,
 }
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+/// A single invalid field in a job's parameters
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Violation {
+    /// Path of the offending field, e.g. `agent_config.model`
+    pub field: String,
+    pub message: String,
+}
+
+/// Violations collected over a validation pass
+///
+/// Validation keeps going after the first problem so callers can fix every field in
+/// one round trip. [`Violations::into_result`] reports them all in a single error.
+#[derive(Clone, Debug, Default)]
+pub struct Violations(Vec<Violation>);
+
+impl Violations {
+    /// Creates an empty set of violations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a violation of `field`
+    pub fn push(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.0.push(Violation {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Records the error of a check against `field`, if it failed
+    pub fn check(&mut self, field: impl Into<String>, result: Result<(), String>) {
+        if let Err(message) = result {
+            self.push(field, message);
+        }
+    }
+
+    /// Records a violation of `field` if `value` is longer than `max` bytes
+    pub fn check_len(&mut self, field: impl Into<String>, value: &str, max: usize) {
+        if value.len() > max {
+            self.push(field, format!("Must be at most {} bytes", max));
+        }
+    }
+
+    /// Returns the violations recorded so far
+    pub fn as_slice(&self) -> &[Violation] {
+        &self.0
+    }
+
+    /// Returns `Ok` if nothing was violated, or an error listing every violation as JSON
+    pub fn into_result(self) -> Result<(), String> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        let violations = serde_json::to_string(&self.0)
+            .unwrap_or_else(|_| format!("{} violations", self.0.len()));
+        Err(format!("Invalid parameters: {}", violations))
+    }
+}
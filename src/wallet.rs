@@ -0,0 +1,95 @@
+use crate::helpers::write_private_file;
+use crate::secrets::SealingKey;
+use crate::types::Secret;
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Directory in an agent's directory mounted into its container, where the agent writes its
+/// wallet data
+pub const WALLET_DIR: &str = ".wallet";
+
+/// Where the wallet directory is mounted in the agent's container
+pub const WALLET_MOUNT: &str = "/wallet";
+
+/// File of the wallet directory the agent writes its wallet data to
+pub const WALLET_DATA_FILE: &str = "wallet_data.json";
+
+/// Variable telling the agent where to write its wallet data
+pub const WALLET_DATA_FILE_VAR: &str = "WALLET_DATA_FILE";
+
+/// Variable the agent's captured wallet data is restored from when it is deployed again
+pub const WALLET_DATA_VAR: &str = "CDP_WALLET_DATA";
+
+/// File in an agent's directory holding its sealed wallet data
+pub const SEALED_WALLET_FILE: &str = "wallet.sealed";
+
+/// Longest wallet data that is captured, in bytes
+const MAX_WALLET_DATA_LEN: u64 = 64 * 1024;
+
+/// Creates the wallet directory of a new agent, only accessible to the operator
+pub fn create_dir(agent_dir: &Path) -> Result<(), String> {
+    let dir = agent_dir.join(WALLET_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))
+            .map_err(|e| format!("Failed to restrict {}: {}", dir.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Seals the wallet data the agent wrote into its directory, so the same wallet is restored
+/// when it is deployed again
+///
+/// Returns whether wallet data was found; the sealed copy is only rewritten when the data
+/// changed. With `remove`, the plaintext is deleted once sealed.
+pub fn capture(agent_dir: &Path, key: &SealingKey, remove: bool) -> Result<bool, String> {
+    let path = agent_dir.join(WALLET_DIR).join(WALLET_DATA_FILE);
+    let size = match fs::metadata(&path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    if size > MAX_WALLET_DATA_LEN {
+        return Err(format!(
+            "{} is larger than {} bytes",
+            path.display(),
+            MAX_WALLET_DATA_LEN
+        ));
+    }
+    let data = Zeroizing::new(
+        fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+    );
+    serde_json::from_slice::<serde_json::Value>(&data)
+        .map_err(|e| format!("{} is not JSON: {}", path.display(), e))?;
+
+    let unchanged = read_sealed(agent_dir, key)?
+        .is_some_and(|sealed| sealed.expose().as_bytes() == data.as_slice());
+    if !unchanged {
+        let sealed = key.seal(&data)?;
+        write_private_file(&agent_dir.join(SEALED_WALLET_FILE), hex::encode(sealed))?;
+    }
+    if remove {
+        fs::remove_file(&path)
+            .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+    }
+    Ok(true)
+}
+
+/// Unseals an agent's captured wallet data, if it has any
+pub fn read_sealed(agent_dir: &Path, key: &SealingKey) -> Result<Option<Secret<String>>, String> {
+    let path = agent_dir.join(SEALED_WALLET_FILE);
+    let encoded = match fs::read_to_string(&path) {
+        Ok(encoded) => encoded,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    let sealed = hex::decode(encoded.trim())
+        .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))?;
+    let data = key.unseal(&sealed)?;
+    String::from_utf8(data.to_vec())
+        .map(|data| Some(Secret::new(data)))
+        .map_err(|_| format!("{} holds invalid wallet data", path.display()))
+}
@@ -0,0 +1,367 @@
+use crate::config::env_or;
+use crate::docker;
+use crate::helpers::{run_blocking, write_private_file};
+use crate::retention::LogRetention;
+use crate::wallet;
+use crate::{AgentPortConfig, ServiceContext};
+use blueprint_sdk::logging;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::process::Command as TokioCommand;
+use uuid::Uuid;
+
+/// Directory under the agents base directory holding the standby containers' env files
+pub const POOL_DIR: &str = ".pool";
+
+/// Prefix of the names of standby containers
+pub const STANDBY_CONTAINER_PREFIX: &str = "coinbase-agent-pool-";
+
+/// Image standby containers are created from unless `AGENT_WARM_POOL_IMAGE` says otherwise
+const DEFAULT_IMAGE: &str = "tanglenetwork/coinbase-agent:latest";
+
+/// Command of a standby container: loads the env file written when it is claimed, then runs
+/// the agent like the starter template does
+const STANDBY_COMMAND: &str = "set -a && . /config/.env && set +a && yarn install && yarn dev";
+
+/// Size and image of the pool of standby containers
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WarmPoolConfig {
+    /// Standby containers kept ready
+    pub size: usize,
+    pub image: String,
+}
+
+impl WarmPoolConfig {
+    /// Loads the pool from `AGENT_WARM_POOL_SIZE` and `AGENT_WARM_POOL_IMAGE`
+    ///
+    /// Returns `None` when the size is unset or `0`.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let size: usize = env_or("AGENT_WARM_POOL_SIZE", 0)?;
+        if size == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            size,
+            image: env_or("AGENT_WARM_POOL_IMAGE", DEFAULT_IMAGE.to_string())?,
+        }))
+    }
+}
+
+/// Stopped container waiting to be claimed by an agent
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StandbyContainer {
+    pub container_name: String,
+    /// Directory mounted at `/config`, where the agent's env file is written when claimed
+    pub config_dir: PathBuf,
+    pub ports: AgentPortConfig,
+}
+
+impl StandbyContainer {
+    /// Returns the directory mounted as the agent's wallet directory
+    pub fn wallet_dir(&self) -> PathBuf {
+        self.config_dir.join(wallet::WALLET_DIR)
+    }
+
+    /// Returns the `docker create` arguments of the container
+    pub fn create_args(
+        &self,
+        image: &str,
+        bind_address: &str,
+        retention: &LogRetention,
+    ) -> Vec<String> {
+        let publish = |port: u16| format!("{}:{}:{}", bind_address, port, port);
+        let mut args = vec![
+            "create".to_string(),
+            "--name".to_string(),
+            self.container_name.clone(),
+        ];
+        for port in self.ports.ports() {
+            args.push("-p".to_string());
+            args.push(publish(port));
+        }
+        args.extend([
+            "-v".to_string(),
+            format!("{}:/config:ro", self.config_dir.display()),
+            "-v".to_string(),
+            format!("{}:{}", self.wallet_dir().display(), wallet::WALLET_MOUNT),
+            "-e".to_string(),
+            format!(
+                "{}={}/{}",
+                wallet::WALLET_DATA_FILE_VAR,
+                wallet::WALLET_MOUNT,
+                wallet::WALLET_DATA_FILE
+            ),
+            "--restart".to_string(),
+            "unless-stopped".to_string(),
+            "--log-driver".to_string(),
+            "json-file".to_string(),
+            "--log-opt".to_string(),
+            format!("max-size={}", retention.max_size),
+            "--log-opt".to_string(),
+            format!("max-file={}", retention.max_files),
+            image.to_string(),
+            "sh".to_string(),
+            "-c".to_string(),
+            STANDBY_COMMAND.to_string(),
+        ]);
+        args
+    }
+}
+
+/// Pool of stopped agent containers created ahead of time (shared across threads)
+///
+/// `deploy_agent` claims one instead of creating a container with docker-compose: it writes
+/// the agent's env file into the container's config directory, renames the container after
+/// the agent and starts it. The container keeps its ports, which are moved to the agent.
+#[derive(Clone, Debug)]
+pub struct WarmPool {
+    config: WarmPoolConfig,
+    standby: Arc<Mutex<VecDeque<StandbyContainer>>>,
+    // Keeps concurrent refills from overshooting the pool size
+    filling: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl WarmPool {
+    pub fn new(config: WarmPoolConfig) -> Self {
+        Self {
+            config,
+            standby: Arc::new(Mutex::new(VecDeque::new())),
+            filling: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    pub fn config(&self) -> &WarmPoolConfig {
+        &self.config
+    }
+
+    /// Returns how many standby containers are ready
+    pub fn len(&self) -> usize {
+        self.standby.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds a created standby container to the pool
+    pub fn add(&self, standby: StandbyContainer) {
+        self.standby
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push_back(standby);
+    }
+
+    /// Takes a standby container for an agent, giving the agent the container's ports
+    ///
+    /// Returns `None` when the pool is empty.
+    pub fn claim(
+        &self,
+        agent_id: &str,
+        context: &ServiceContext,
+    ) -> Result<Option<StandbyContainer>, String> {
+        let Some(standby) = self
+            .standby
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front()
+        else {
+            return Ok(None);
+        };
+        match &context.port_allocator {
+            Some(allocator) => {
+                allocator.assign_reserved(&standby.container_name, agent_id)?;
+            }
+            None => {
+                if let Some(registry) = &context.agent_registry {
+                    registry.insert(agent_id, standby.ports.clone());
+                }
+            }
+        }
+        Ok(Some(standby))
+    }
+
+    /// Creates standby containers until the pool is full, returning how many were created
+    pub async fn fill(&self, context: &ServiceContext) -> Result<usize, String> {
+        let _filling = self.filling.lock().await;
+        let mut created = 0;
+        while self.len() < self.config.size {
+            let standby = self.create_standby(context).await?;
+            logging::info!(
+                container = %standby.container_name,
+                http_port = standby.ports.http_port,
+                "Created standby agent container"
+            );
+            self.add(standby);
+            created += 1;
+        }
+        Ok(created)
+    }
+
+    async fn create_standby(&self, context: &ServiceContext) -> Result<StandbyContainer, String> {
+        let allocator = context
+            .port_allocator
+            .as_ref()
+            .ok_or("The warm pool needs the port allocator")?;
+        let container_name = format!("{}{}", STANDBY_CONTAINER_PREFIX, Uuid::new_v4());
+
+        // Docker only mounts absolute paths
+        let config_dir = pool_dir(context).join(&container_name);
+        fs::create_dir_all(&config_dir)
+            .map_err(|e| format!("Failed to create {}: {}", config_dir.display(), e))?;
+        let config_dir = fs::canonicalize(&config_dir)
+            .map_err(|e| format!("Failed to resolve {}: {}", config_dir.display(), e))?;
+        if let Err(e) = wallet::create_dir(&config_dir) {
+            let _ = fs::remove_dir_all(&config_dir);
+            return Err(e);
+        }
+
+        let standby = match allocator.reserve(&container_name) {
+            Ok(ports) => StandbyContainer {
+                container_name,
+                config_dir,
+                ports,
+            },
+            Err(e) => {
+                let _ = fs::remove_dir_all(&config_dir);
+                return Err(e);
+            }
+        };
+
+        let bind_address = context.agent_bind_address();
+        let output = TokioCommand::new("docker")
+            .args(standby.create_args(&self.config.image, bind_address, &context.log_retention()))
+            .output()
+            .await;
+        let result = match output {
+            Ok(output) if output.status.success() => Ok(()),
+            Ok(output) => Err(format!(
+                "Failed to create standby container: {}",
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            )),
+            Err(e) => Err(format!("Failed to execute docker create command: {}", e)),
+        };
+        if let Err(e) = result {
+            allocator.unreserve(&standby.container_name);
+            let _ = fs::remove_dir_all(&standby.config_dir);
+            return Err(e);
+        }
+        Ok(standby)
+    }
+}
+
+/// Returns the directory holding the standby containers' config directories
+fn pool_dir(context: &ServiceContext) -> PathBuf {
+    let base_dir = match context.agents_base_dir() {
+        Some(dir) => dir,
+        None => "./agents".to_string(),
+    };
+    PathBuf::from(base_dir).join(POOL_DIR)
+}
+
+/// Whether an agent's compose file can run in a standby container: only the agent service,
+/// without sidecars or an egress firewall
+pub fn supports_compose(compose_path: &Path) -> bool {
+    let Ok(content) = fs::read_to_string(compose_path) else {
+        return false;
+    };
+    let Ok(compose) = serde_yaml::from_str::<serde_yaml::Value>(&content) else {
+        return false;
+    };
+    compose["services"]
+        .as_mapping()
+        .is_some_and(|services| services.len() == 1 && services.contains_key("agent"))
+}
+
+/// Makes the agent's wallet directory the one mounted into a claimed standby container
+///
+/// The agent's directory links to the container's, so its wallet data is captured from there
+/// like from any other agent. Wallet data the agent already had is moved over first.
+pub fn link_wallet_dir(standby: &StandbyContainer, agent_dir: &Path) -> Result<(), String> {
+    let agent_wallet_dir = agent_dir.join(wallet::WALLET_DIR);
+    if agent_wallet_dir.is_dir() && !agent_wallet_dir.is_symlink() {
+        let data_path = agent_wallet_dir.join(wallet::WALLET_DATA_FILE);
+        if data_path.exists() {
+            let moved_path = standby.wallet_dir().join(wallet::WALLET_DATA_FILE);
+            fs::rename(&data_path, &moved_path)
+                .map_err(|e| format!("Failed to move {}: {}", data_path.display(), e))?;
+        }
+        fs::remove_dir(&agent_wallet_dir)
+            .map_err(|e| format!("Failed to remove {}: {}", agent_wallet_dir.display(), e))?;
+    } else if agent_wallet_dir.is_symlink() {
+        fs::remove_file(&agent_wallet_dir)
+            .map_err(|e| format!("Failed to remove {}: {}", agent_wallet_dir.display(), e))?;
+    }
+
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(standby.wallet_dir(), &agent_wallet_dir);
+    #[cfg(windows)]
+    let linked = std::os::windows::fs::symlink_dir(standby.wallet_dir(), &agent_wallet_dir);
+    linked.map_err(|e| format!("Failed to link {}: {}", agent_wallet_dir.display(), e))
+}
+
+/// Starts a claimed standby container as the agent's container
+///
+/// `env_content` is the agent's full environment, secrets included, as they can't be handed
+/// to an existing container any other way. It is only readable by the operator. The agent's
+/// wallet directory is linked to the container's first (see [`link_wallet_dir`]).
+pub async fn start_standby(
+    standby: &StandbyContainer,
+    container_name: &str,
+    agent_dir: &Path,
+    env_content: String,
+) -> Result<(), String> {
+    let env_path = standby.config_dir.join(".env");
+    run_blocking(move || write_private_file(&env_path, env_content)).await?;
+    run_blocking({
+        let (standby, agent_dir) = (standby.clone(), agent_dir.to_path_buf());
+        move || link_wallet_dir(&standby, &agent_dir)
+    })
+    .await?;
+
+    for args in [
+        vec!["rename", standby.container_name.as_str(), container_name],
+        vec!["start", container_name],
+    ] {
+        let output = TokioCommand::new("docker")
+            .args(&args)
+            .output()
+            .await
+            .map_err(|e| format!("Failed to execute docker {} command: {}", args[0], e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Docker {} command failed: {}",
+                args[0],
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Removes the standby containers left over from an earlier run and fills the pool
+pub async fn run_warm_pool(context: ServiceContext, pool: WarmPool) {
+    let removed = run_blocking(|| Ok(docker::cleanup_containers(STANDBY_CONTAINER_PREFIX)))
+        .await
+        .unwrap_or(0);
+    if removed > 0 {
+        logging::info!(removed, "Removed standby containers of an earlier run");
+    }
+    // Config directories of claimed containers hold an env file and are still mounted
+    if let Ok(entries) = fs::read_dir(pool_dir(&context)) {
+        for entry in entries.filter_map(Result::ok) {
+            let dir = entry.path();
+            if dir.is_dir() && !dir.join(".env").exists() {
+                if let Err(e) = fs::remove_dir_all(&dir) {
+                    logging::warn!(path = %dir.display(), error = %e, "Failed to remove standby config");
+                }
+            }
+        }
+    }
+
+    match pool.fill(&context).await {
+        Ok(created) => logging::info!(created, "Warm pool ready"),
+        Err(e) => logging::warn!(error = %e, "Failed to fill the warm pool"),
+    }
+}
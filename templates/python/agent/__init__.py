@@ -0,0 +1 @@
+"""AgentKit chat agent on the Python SDK, served over HTTP like the starter template."""
@@ -0,0 +1,120 @@
+"""Serves the agent over HTTP, acting on its own schedule in autonomous mode.
+
+The blueprint talks to every template's agents through the same routes: `GET /health`,
+`GET /status`, `GET /actions?since=<unix seconds>` and `POST /interact` with
+`{"message": "..."}`, and optionally `GET /wallet` and `POST /faucet`.
+"""
+
+import asyncio
+import logging
+from contextlib import asynccontextmanager
+from datetime import datetime, timezone
+
+import uvicorn
+from fastapi import FastAPI, HTTPException, Query
+from pydantic import BaseModel
+
+from .agent import Agent
+from .config import config
+
+logging.basicConfig(level=config.log_level, format="%(asctime)s %(levelname)s %(message)s")
+logger = logging.getLogger("agent")
+
+AUTONOMOUS_PROMPT = (
+    "Be creative and do something interesting on the blockchain. "
+    "Choose an action or set of actions and execute it that highlights your abilities."
+)
+
+
+def _is_active_hour(hour: int) -> bool:
+    if not config.autonomous_active_hours:
+        return True
+    start, end = (int(part) for part in config.autonomous_active_hours.split("-"))
+    if start < end:
+        return start <= hour < end
+    return hour >= start or hour < end
+
+
+async def _autonomous_loop(agent: Agent) -> None:
+    day, actions_today = None, 0
+    while True:
+        await asyncio.sleep(config.autonomous_interval_secs)
+        now = datetime.now(timezone.utc)
+        if now.date() != day:
+            day, actions_today = now.date(), 0
+        if not _is_active_hour(now.hour):
+            continue
+        limit = config.autonomous_max_actions_per_day
+        if limit is not None and actions_today >= limit:
+            continue
+        actions_today += 1
+        try:
+            result = await asyncio.to_thread(agent.process_message, AUTONOMOUS_PROMPT)
+            logger.info("Autonomous action: %s", result["response"])
+        except Exception:
+            logger.exception("Autonomous action failed")
+
+
+class Message(BaseModel):
+    message: str
+
+
+agent: Agent | None = None
+
+
+@asynccontextmanager
+async def lifespan(_: FastAPI):
+    global agent
+    agent = Agent()
+    logger.info("Agent initialized in %s mode", config.agent_mode)
+    task = None
+    if config.agent_mode == "autonomous":
+        task = asyncio.create_task(_autonomous_loop(agent))
+    yield
+    if task:
+        task.cancel()
+
+
+app = FastAPI(lifespan=lifespan)
+
+
+@app.get("/health")
+def health() -> dict:
+    return {"status": "ok"}
+
+
+@app.get("/status")
+def status() -> dict:
+    return agent.get_status()
+
+
+@app.get("/wallet")
+def wallet() -> dict:
+    return agent.get_wallet()
+
+
+@app.post("/faucet")
+def faucet() -> dict:
+    try:
+        return {"tx_hashes": agent.request_faucet_funds()}
+    except Exception:
+        logger.exception("Error requesting faucet funds")
+        raise HTTPException(status_code=502, detail="Failed to request faucet funds")
+
+
+@app.get("/actions")
+def actions(since: int | None = Query(default=None)) -> dict:
+    return {"actions": agent.get_actions(since)}
+
+
+@app.post("/interact")
+async def interact(body: Message) -> dict:
+    try:
+        return await asyncio.to_thread(agent.process_message, body.message)
+    except Exception:
+        logger.exception("Error processing message")
+        raise HTTPException(status_code=500, detail="Failed to process message")
+
+
+if __name__ == "__main__":
+    uvicorn.run(app, host="0.0.0.0", port=config.port, log_level=config.log_level.lower())
@@ -0,0 +1,242 @@
+"""The AgentKit agent: a LangGraph ReAct agent with the wallet's tools."""
+
+import json
+import logging
+import os
+import time
+from dataclasses import asdict, dataclass
+
+from cdp import Cdp, ExternalAddress, Wallet, WalletData
+from coinbase_agentkit import (
+    AgentKit,
+    AgentKitConfig,
+    CdpWalletProvider,
+    CdpWalletProviderConfig,
+    EthAccountWalletProvider,
+    EthAccountWalletProviderConfig,
+    EvmWalletProvider,
+    SmartWalletProvider,
+    SmartWalletProviderConfig,
+    erc20_action_provider,
+    pyth_action_provider,
+    wallet_action_provider,
+    weth_action_provider,
+)
+from coinbase_agentkit_langchain import get_langchain_tools
+from eth_account import Account
+from langchain_core.messages import HumanMessage
+from langgraph.checkpoint.memory import MemorySaver
+from langgraph.prebuilt import create_react_agent
+
+from .config import config
+
+logger = logging.getLogger(__name__)
+
+DEFAULT_PROMPT = (
+    "You are a helpful agent that can interact onchain using the Coinbase Developer "
+    "Platform AgentKit. Before executing your first action, get the wallet details to "
+    "see what network you're on. Be concise and helpful."
+)
+
+ACTION_PROVIDERS = {
+    "wallet": wallet_action_provider,
+    "erc20": erc20_action_provider,
+    "weth": weth_action_provider,
+    "pyth": pyth_action_provider,
+}
+
+# Chain IDs of the networks an agent's own account can be used on
+CHAIN_IDS = {
+    "base-sepolia": "84532",
+    "base-mainnet": "8453",
+    "ethereum-mainnet": "1",
+}
+
+
+@dataclass
+class ActionRecord:
+    """On-chain action record kept for the blueprint's `agent_actions` job."""
+
+    timestamp: int
+    action: str
+    details: str
+    tx_hash: str | None = None
+
+
+def _create_llm():
+    options = {"model": config.model}
+    if config.temperature is not None:
+        options["temperature"] = config.temperature
+    if config.max_tokens is not None:
+        options["max_tokens"] = config.max_tokens
+
+    if config.llm_provider == "anthropic":
+        from langchain_anthropic import ChatAnthropic
+
+        return ChatAnthropic(**options)
+    if config.llm_provider == "azureopenai":
+        from langchain_openai import AzureChatOpenAI
+
+        options["azure_deployment"] = options.pop("model")
+        return AzureChatOpenAI(**options)
+
+    # OpenAI, or a local server with an OpenAI-compatible API at OPENAI_BASE_URL
+    from langchain_openai import ChatOpenAI
+
+    return ChatOpenAI(**options)
+
+
+def _load_wallet_data() -> str | None:
+    """Wallet data this container wrote before restarting, or that the blueprint captured
+    from an earlier deployment; a new wallet is created when there is neither."""
+    if config.wallet_data_file and os.path.exists(config.wallet_data_file):
+        with open(config.wallet_data_file, encoding="utf-8") as file:
+            return file.read()
+    return config.cdp_wallet_data
+
+
+def _create_wallet_provider() -> EvmWalletProvider:
+    """Restores the agent's wallet, or creates one, as the kind selected by WALLET_TYPE."""
+    wallet_data = _load_wallet_data()
+    if config.wallet_type == "smart_wallet":
+        if not config.cdp_wallet_secret:
+            raise ValueError("CDP_WALLET_SECRET is required for smart wallets")
+        Cdp.configure(config.cdp_api_key_name, config.cdp_api_key_private_key)
+        return SmartWalletProvider(
+            SmartWalletProviderConfig(
+                network_id=config.network_id,
+                signer=Account.from_key(config.cdp_wallet_secret),
+                smart_wallet_address=json.loads(wallet_data)["address"] if wallet_data else None,
+                paymaster_url=config.paymaster_url,
+            )
+        )
+
+    if config.wallet_type == "private_key":
+        if not config.wallet_private_key:
+            raise ValueError("WALLET_PRIVATE_KEY is required for private_key wallets")
+        return EthAccountWalletProvider(
+            EthAccountWalletProviderConfig(
+                account=Account.from_key(config.wallet_private_key),
+                chain_id=CHAIN_IDS[config.network_id],
+            )
+        )
+
+    if config.wallet_type == "server_signer":
+        # The wallet's transactions are signed by the project's Server-Signer, so the wallet
+        # is created here with it enabled, and it is enabled again once the provider has
+        # configured the SDK
+        Cdp.configure(
+            config.cdp_api_key_name, config.cdp_api_key_private_key, use_server_signer=True
+        )
+        wallet = (
+            Wallet.import_data(WalletData.from_dict(json.loads(wallet_data)))
+            if wallet_data
+            else Wallet.create(network_id=config.network_id)
+        )
+        wallet_provider = CdpWalletProvider(
+            CdpWalletProviderConfig(
+                api_key_name=config.cdp_api_key_name,
+                api_key_private_key=config.cdp_api_key_private_key,
+                network_id=config.network_id,
+                wallet=wallet,
+            )
+        )
+        Cdp.use_server_signer = True
+        return wallet_provider
+
+    return CdpWalletProvider(
+        CdpWalletProviderConfig(
+            api_key_name=config.cdp_api_key_name,
+            api_key_private_key=config.cdp_api_key_private_key,
+            network_id=config.network_id,
+            wallet_data=wallet_data,
+        )
+    )
+
+
+def _save_wallet_data(wallet_provider: EvmWalletProvider) -> None:
+    """Writes the wallet's data where the blueprint captures and seals it; smart wallets are
+    restored from their address, since they are owned by CDP_WALLET_SECRET, and private-key
+    accounts only record theirs."""
+    if not config.wallet_data_file:
+        return
+    if isinstance(wallet_provider, CdpWalletProvider):
+        data = wallet_provider.export_wallet().to_dict()
+    else:
+        data = {"address": wallet_provider.get_address()}
+    os.makedirs(os.path.dirname(config.wallet_data_file), exist_ok=True)
+    descriptor = os.open(config.wallet_data_file, os.O_WRONLY | os.O_CREAT | os.O_TRUNC, 0o600)
+    with os.fdopen(descriptor, "w", encoding="utf-8") as file:
+        json.dump(data, file)
+
+
+class Agent:
+    def __init__(self) -> None:
+        self.started_at = time.time()
+        self.actions: list[ActionRecord] = []
+
+        providers = []
+        for name in config.action_providers:
+            if name not in ACTION_PROVIDERS:
+                logger.warning("Skipping unsupported action provider %s", name)
+                continue
+            providers.append(ACTION_PROVIDERS[name]())
+
+        wallet_provider = _create_wallet_provider()
+        _save_wallet_data(wallet_provider)
+        self._wallet_provider = wallet_provider
+        agentkit = AgentKit(
+            AgentKitConfig(wallet_provider=wallet_provider, action_providers=providers)
+        )
+        self._agent = create_react_agent(
+            _create_llm(),
+            tools=get_langchain_tools(agentkit),
+            checkpointer=MemorySaver(),
+            prompt=config.system_prompt or DEFAULT_PROMPT,
+        )
+        self._thread = {"configurable": {"thread_id": "agent"}}
+
+    def process_message(self, message: str) -> dict:
+        """Runs the agent on a message, recording the tools it calls as actions."""
+        content = []
+        for chunk in self._agent.stream(
+            {"messages": [HumanMessage(content=message)]}, self._thread
+        ):
+            if "agent" in chunk:
+                content.append(chunk["agent"]["messages"][0].content)
+            elif "tools" in chunk:
+                for tool_message in chunk["tools"]["messages"]:
+                    self.actions.append(
+                        ActionRecord(
+                            timestamp=int(time.time()),
+                            action=tool_message.name,
+                            details=str(tool_message.content)[:1000],
+                        )
+                    )
+        return {"response": "".join(str(part) for part in content) or "No response generated"}
+
+    def get_actions(self, since: int | None = None) -> list[dict]:
+        return [
+            asdict(action)
+            for action in self.actions
+            if since is None or action.timestamp >= since
+        ]
+
+    def get_wallet(self) -> dict:
+        return {
+            "address": self._wallet_provider.get_address(),
+            "network_id": config.network_id,
+            "balance": str(self._wallet_provider.get_balance()),
+        }
+
+    def request_faucet_funds(self) -> list[str]:
+        """Requests testnet ETH for the wallet from the CDP faucet, returning its tx hashes."""
+        address = ExternalAddress(config.network_id, self._wallet_provider.get_address())
+        return [address.faucet().transaction_hash]
+
+    def get_status(self) -> dict:
+        return {
+            "status": "running",
+            "uptime": int(time.time() - self.started_at),
+            "mode": config.agent_mode,
+        }
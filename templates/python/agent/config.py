@@ -0,0 +1,50 @@
+"""Agent settings read from the environment the blueprint writes."""
+
+import os
+from dataclasses import dataclass, field
+
+
+def _optional_int(name: str) -> int | None:
+    value = os.environ.get(name, "").strip()
+    return int(value) if value else None
+
+
+def _optional_float(name: str) -> float | None:
+    value = os.environ.get(name, "").strip()
+    return float(value) if value else None
+
+
+@dataclass(frozen=True)
+class Config:
+    port: int = int(os.environ.get("PORT", "3000"))
+    agent_mode: str = os.environ.get("AGENT_MODE", "http")
+    llm_provider: str = os.environ.get("LLM_PROVIDER", "openai").lower()
+    model: str = os.environ.get("MODEL", "gpt-4o-mini")
+    system_prompt: str | None = os.environ.get("SYSTEM_PROMPT") or None
+    temperature: float | None = _optional_float("TEMPERATURE")
+    max_tokens: int | None = _optional_int("MAX_TOKENS")
+    network_id: str = os.environ.get("NETWORK_ID", "base-sepolia")
+    action_providers: list[str] = field(
+        default_factory=lambda: [
+            provider.strip()
+            for provider in os.environ.get("ACTION_PROVIDERS", "wallet").split(",")
+            if provider.strip()
+        ]
+    )
+    cdp_api_key_name: str = os.environ.get("CDP_API_KEY_NAME", "")
+    cdp_api_key_private_key: str = os.environ.get("CDP_API_KEY_PRIVATE_KEY", "").replace(
+        "\\n", "\n"
+    )
+    cdp_wallet_data: str | None = os.environ.get("CDP_WALLET_DATA") or None
+    wallet_type: str = os.environ.get("WALLET_TYPE", "cdp")
+    cdp_wallet_secret: str | None = os.environ.get("CDP_WALLET_SECRET") or None
+    paymaster_url: str | None = os.environ.get("PAYMASTER_URL") or None
+    wallet_private_key: str | None = os.environ.get("WALLET_PRIVATE_KEY") or None
+    wallet_data_file: str | None = os.environ.get("WALLET_DATA_FILE") or None
+    autonomous_interval_secs: int = int(os.environ.get("AUTONOMOUS_INTERVAL_SECS", "300"))
+    autonomous_active_hours: str | None = os.environ.get("AUTONOMOUS_ACTIVE_HOURS") or None
+    autonomous_max_actions_per_day: int | None = _optional_int("AUTONOMOUS_MAX_ACTIONS_PER_DAY")
+    log_level: str = os.environ.get("LOG_LEVEL", "info").upper()
+
+
+config = Config()
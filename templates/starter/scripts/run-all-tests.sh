@@ -12,6 +12,7 @@ NC='\033[0m' # No Color
 test_groups=(
   "HTTP:src/__tests__/agent-system.test.ts src/__tests__/server.test.ts"
   "WebSocket:src/__tests__/websocket.test.ts"
+  "Actions:src/__tests__/actions.test.ts"
 )
 
 # Real test configurations with longer timeouts
@@ -0,0 +1,57 @@
+/**
+ * Tests for the filtering of AgentKit tools by the agent's allowed actions
+ */
+
+import { StructuredToolInterface } from "@langchain/core/tools";
+import { filterAllowedTools, isReadOnlyTool } from "../actions";
+
+const tool = (name: string) => ({ name }) as StructuredToolInterface;
+
+const TOOLS = [
+  tool("WalletActionProvider_get_wallet_details"),
+  tool("WalletActionProvider_native_transfer"),
+  tool("ERC20ActionProvider_get_balance"),
+  tool("ERC20ActionProvider_transfer"),
+  tool("WethActionProvider_wrap_eth"),
+  tool("PythActionProvider_fetch_price"),
+  tool("SomeNewProvider_approve"),
+  tool("get_wallet_address"),
+];
+
+const names = (tools: StructuredToolInterface[]) => tools.map((t) => t.name);
+
+describe("filterAllowedTools", () => {
+  it("keeps every tool without a policy", () => {
+    expect(filterAllowedTools(TOOLS, undefined)).toHaveLength(TOOLS.length);
+  });
+
+  it("keeps only read-only tools when no action is allowed", () => {
+    expect(names(filterAllowedTools(TOOLS, []))).toEqual([
+      "WalletActionProvider_get_wallet_details",
+      "ERC20ActionProvider_get_balance",
+      "PythActionProvider_fetch_price",
+      "get_wallet_address",
+    ]);
+  });
+
+  it("adds the tools of allowed actions and drops unknown ones", () => {
+    const kept = names(filterAllowedTools(TOOLS, ["transfer"]));
+    expect(kept).toContain("WalletActionProvider_native_transfer");
+    expect(kept).toContain("ERC20ActionProvider_transfer");
+    expect(kept).not.toContain("WethActionProvider_wrap_eth");
+    expect(kept).not.toContain("SomeNewProvider_approve");
+  });
+});
+
+describe("isReadOnlyTool", () => {
+  it("recognizes read-only actions with or without their provider", () => {
+    expect(isReadOnlyTool("get_wallet_address")).toBe(true);
+    expect(isReadOnlyTool("ERC20ActionProvider_get_balance")).toBe(true);
+  });
+
+  it("treats every other tool as acting on-chain", () => {
+    expect(isReadOnlyTool("WalletActionProvider_native_transfer")).toBe(false);
+    expect(isReadOnlyTool("SomeNewProvider_approve")).toBe(false);
+    expect(isReadOnlyTool("unknown")).toBe(false);
+  });
+});
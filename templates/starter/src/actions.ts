@@ -0,0 +1,100 @@
+import {
+  ActionProvider,
+  customActionProvider,
+  erc20ActionProvider,
+  pythActionProvider,
+  walletActionProvider,
+  wethActionProvider,
+  WalletProvider,
+} from "@coinbase/agentkit";
+import { StructuredToolInterface } from "@langchain/core/tools";
+import { z } from "zod";
+import { ActionProviderName, AllowedActionName } from "./types";
+
+// Tool name fragments identifying each gated action category
+const ACTION_TOOL_PATTERNS: Record<AllowedActionName, RegExp> = {
+  transfer: /transfer/,
+  swap: /swap|trade/,
+  wrap: /wrap/,
+  deploy_token: /deploy_token/,
+  deploy_nft: /deploy_nft/,
+};
+
+// Actions that only read state, kept whatever the agent's policy
+const READ_ONLY_ACTIONS = [
+  "get_wallet_details",
+  "get_balance",
+  "fetch_price_feed",
+  "fetch_price",
+  "get_wallet_address",
+];
+
+/**
+ * Whether a tool only reads state; tools are named after their action, optionally
+ * prefixed with their provider (e.g. `ERC20ActionProvider_get_balance`)
+ * @param name The tool's name
+ * @returns True if the tool's action is known to be read-only
+ */
+export function isReadOnlyTool(name: string): boolean {
+  return READ_ONLY_ACTIONS.some(
+    (action) => name === action || name.endsWith(`_${action}`)
+  );
+}
+
+/**
+ * Example custom action; replace it with the actions your agent needs
+ */
+const customActions = customActionProvider<WalletProvider>({
+  name: "get_wallet_address",
+  description: "Returns the address of the agent's wallet",
+  schema: z.object({}),
+  invoke: async (walletProvider) => {
+    return `The agent's wallet address is ${walletProvider.getAddress()}`;
+  },
+});
+
+/**
+ * Build the action providers enabled for this agent
+ * @param names The providers listed in ACTION_PROVIDERS
+ * @returns The action providers to register with AgentKit
+ */
+export function createActionProviders(
+  names: ActionProviderName[]
+): ActionProvider[] {
+  return names.map((name) => {
+    switch (name) {
+      case "wallet":
+        return walletActionProvider();
+      case "erc20":
+        return erc20ActionProvider();
+      case "weth":
+        return wethActionProvider();
+      case "pyth":
+        return pythActionProvider();
+      case "custom":
+        return customActions;
+    }
+  });
+}
+
+/**
+ * Keep only the tools that the agent's policy allows
+ * @param tools The AgentKit LangChain tools
+ * @param allowed The actions listed in ALLOWED_ACTIONS, or undefined to allow all
+ * @returns The read-only tools and those of allowed actions; any other tool, including
+ * ones this template doesn't know, is dropped
+ */
+export function filterAllowedTools(
+  tools: StructuredToolInterface[],
+  allowed: AllowedActionName[] | undefined
+): StructuredToolInterface[] {
+  if (!allowed) {
+    return tools;
+  }
+
+  return tools.filter(
+    (tool) =>
+      isReadOnlyTool(tool.name) ||
+      allowed.some((action) => ACTION_TOOL_PATTERNS[action].test(tool.name))
+  );
+}
@@ -1,14 +1,30 @@
-import { AgentKit } from "@coinbase/agentkit";
+import { AgentKit, EvmWalletProvider } from "@coinbase/agentkit";
 import { getLangChainTools } from "@coinbase/agentkit-langchain";
 import { createReactAgent } from "@langchain/langgraph/prebuilt";
 import { HumanMessage } from "@langchain/core/messages";
-import { ChatOpenAI } from "@langchain/openai";
+import { AzureChatOpenAI, ChatOpenAI } from "@langchain/openai";
+import { ChatAnthropic } from "@langchain/anthropic";
+import { BaseChatModel } from "@langchain/core/language_models/chat_models";
 import * as dotenv from "dotenv";
 import * as readline from "readline";
+import {
+  createActionProviders,
+  filterAllowedTools,
+  isReadOnlyTool,
+} from "./actions";
+import { BudgetGuard } from "./budget";
 import { config } from "./config";
 import {
+  createWalletProvider,
+  describeWallet,
+  requestFaucetFunds,
+  saveWalletData,
+} from "./wallet";
+import {
+  ActionRecord,
   AgentResponse,
   AgentStatus,
+  AgentWallet,
   LangChainAgent,
   LangChainAgentConfig,
   InitializeResult,
@@ -31,27 +47,97 @@ const AGENT_PROMPT = `
   Refrain from restating your tools' descriptions unless explicitly requested.
 `;
 
+/**
+ * Create the chat model for the configured LLM provider
+ * @returns The chat model used by the agent
+ */
+function createLlm(): BaseChatModel {
+  const temperature = config.TEMPERATURE ?? 0;
+  const maxTokens = config.MAX_TOKENS;
+
+  switch (config.LLM_PROVIDER) {
+    case "anthropic":
+      if (!config.ANTHROPIC_API_KEY) {
+        throw new Error("ANTHROPIC_API_KEY is required for the anthropic provider");
+      }
+      return new ChatAnthropic({
+        model: config.MODEL,
+        temperature,
+        maxTokens,
+        anthropicApiKey: config.ANTHROPIC_API_KEY,
+      });
+    case "azureopenai":
+      if (
+        !config.AZURE_OPENAI_API_KEY ||
+        !config.AZURE_OPENAI_ENDPOINT ||
+        !config.AZURE_OPENAI_API_DEPLOYMENT_NAME
+      ) {
+        throw new Error(
+          "AZURE_OPENAI_API_KEY, AZURE_OPENAI_ENDPOINT and AZURE_OPENAI_API_DEPLOYMENT_NAME are required for the azureopenai provider"
+        );
+      }
+      return new AzureChatOpenAI({
+        model: config.MODEL,
+        temperature,
+        maxTokens,
+        azureOpenAIApiKey: config.AZURE_OPENAI_API_KEY,
+        azureOpenAIEndpoint: config.AZURE_OPENAI_ENDPOINT,
+        azureOpenAIApiDeploymentName: config.AZURE_OPENAI_API_DEPLOYMENT_NAME,
+        azureOpenAIApiVersion: config.AZURE_OPENAI_API_VERSION,
+      });
+    case "local":
+      if (!config.OPENAI_BASE_URL) {
+        throw new Error("OPENAI_BASE_URL is required for the local provider");
+      }
+      // Ollama serves an OpenAI-compatible API and ignores the API key
+      return new ChatOpenAI({
+        modelName: config.MODEL,
+        temperature,
+        maxTokens,
+        openAIApiKey: config.OPENAI_API_KEY || "ollama",
+        configuration: { baseURL: config.OPENAI_BASE_URL },
+      });
+    case "openai":
+    default:
+      if (!config.OPENAI_API_KEY) {
+        throw new Error("OPENAI_API_KEY is required for the openai provider");
+      }
+      return new ChatOpenAI({
+        modelName: config.MODEL,
+        temperature,
+        maxTokens,
+        openAIApiKey: config.OPENAI_API_KEY,
+      });
+  }
+}
+
 /**
  * Initialize the agent with the Coinbase Agent Kit
  * @returns A fully configured LangChain agent and config
  */
 async function initialize(): Promise<InitializeResult> {
+  // Restore the agent's wallet, or create one on the configured network, and keep its data
+  // for the next deployment
+  const walletProvider = await createWalletProvider();
+  await saveWalletData(walletProvider);
+
   // Initialize AgentKit with configuration
   const agentkit = await AgentKit.from({
-    cdpApiKeyName: config.CDP_API_KEY_NAME,
-    cdpApiKeyPrivateKey: config.CDP_API_KEY_PRIVATE_KEY,
-    actionProviders: [], // Add your action providers here
+    walletProvider,
+    actionProviders: createActionProviders(config.ACTION_PROVIDERS),
   });
 
-  // Get LangChain tools with AgentKit integration
-  const tools = await getLangChainTools(agentkit);
+  // Get LangChain tools with AgentKit integration, limited to the allowed actions and
+  // checking transfers against the budget
+  const agentkitTools = filterAllowedTools(
+    await getLangChainTools(agentkit),
+    config.ALLOWED_ACTIONS
+  );
+  const budget = BudgetGuard.fromConfig();
+  const tools = budget ? budget.guard(agentkitTools) : agentkitTools;
 
   // Initialize LLM
-  const llm = new ChatOpenAI({
-    modelName: config.MODEL,
-    temperature: 0,
-    openAIApiKey: config.OPENAI_API_KEY,
-  });
+  const llm = createLlm();
 
   // Create the agent configuration
   const agentConfig: LangChainAgentConfig = {
@@ -64,10 +150,14 @@ async function initialize(): Promise<InitializeResult> {
   const agent = await createReactAgent({
     llm,
     tools,
-    messageModifier: AGENT_PROMPT,
+    messageModifier: config.SYSTEM_PROMPT || AGENT_PROMPT,
   });
 
-  return { agent, config: agentConfig };
+  return {
+    agent,
+    config: agentConfig,
+    walletProvider,
+  };
 }
 
 /**
@@ -110,6 +200,40 @@ function processStreamChunk(chunk: any): string | null {
   return null;
 }
 
+/**
+ * Build an audit record from a tool execution chunk
+ * @param chunk The chunk from the stream
+ * @returns The action record, or null if the chunk is not the execution of a tool that
+ * may act on-chain
+ */
+function extractActionRecord(chunk: any): ActionRecord | null {
+  if (!chunk || typeof chunk !== "object" || !("tools" in chunk)) {
+    return null;
+  }
+
+  const messages = chunk.tools?.messages;
+  if (!Array.isArray(messages) || messages.length === 0) {
+    return null;
+  }
+
+  const message = messages[0];
+  const action = String(message?.name ?? "unknown");
+  // Reads such as balance checks are not part of the on-chain audit trail
+  if (isReadOnlyTool(action)) {
+    return null;
+  }
+
+  const details = String(message?.content ?? "");
+  const txHash = details.match(/0x[a-fA-F0-9]{64}/);
+
+  return {
+    timestamp: Math.floor(Date.now() / 1000),
+    action,
+    tx_hash: txHash ? txHash[0] : undefined,
+    details,
+  };
+}
+
 /**
  * Run the agent in chat mode
  * @param agent The LangChain agent instance
@@ -224,6 +348,8 @@ function extractResponseContent(result: unknown): string {
 export class Agent {
   private _agent: LangChainAgent | null = null;
   private _agentConfig: LangChainAgentConfig | null = null;
+  private _walletProvider: EvmWalletProvider | null = null;
+  private readonly _actions: ActionRecord[] = [];
   private readonly startTime: number;
 
   constructor() {
@@ -235,9 +361,10 @@ export class Agent {
    */
   async initialize(): Promise<void> {
     try {
-      const { agent, config } = await initialize();
+      const { agent, config, walletProvider } = await initialize();
       this._agent = agent;
       this._agentConfig = config;
+      this._walletProvider = walletProvider;
     } catch (error) {
       const errorMsg = error instanceof Error ? error.message : String(error);
       console.error(`Failed to initialize agent: ${errorMsg}`);
@@ -284,6 +411,11 @@ export class Agent {
         );
 
         for await (const chunk of stream) {
+          const action = extractActionRecord(chunk);
+          if (action) {
+            this._actions.push(action);
+          }
+
           const chunkContent = processStreamChunk(chunk);
           if (chunkContent && typeof chunkContent === "string") {
             content += chunkContent;
@@ -315,6 +447,41 @@ export class Agent {
     }
   }
 
+  /**
+   * Get the actions executed by the agent
+   * @param since Only return actions at or after this unix timestamp (seconds)
+   * @returns The recorded actions, oldest first
+   */
+  getActions(since?: number): ActionRecord[] {
+    if (since === undefined) {
+      return [...this._actions];
+    }
+    return this._actions.filter((action) => action.timestamp >= since);
+  }
+
+  /**
+   * Get the address, network and balance of the agent's wallet
+   * @throws Error if agent is not initialized
+   */
+  async getWallet(): Promise<AgentWallet> {
+    if (!this._walletProvider) {
+      throw new Error("Agent not initialized");
+    }
+    return describeWallet(this._walletProvider);
+  }
+
+  /**
+   * Request testnet funds for the agent's wallet from the CDP faucet
+   * @returns The hashes of the faucet's transactions
+   * @throws Error if agent is not initialized or the faucet refuses the request
+   */
+  async requestFaucetFunds(): Promise<string[]> {
+    if (!this._walletProvider) {
+      throw new Error("Agent not initialized");
+    }
+    return requestFaucetFunds(this._walletProvider);
+  }
+
   /**
    * Get the current status of the agent
    * @returns Agent status information
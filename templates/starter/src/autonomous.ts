@@ -0,0 +1,84 @@
+import { Agent } from "./agent";
+import { config } from "./config";
+import { createLogger } from "./logger";
+
+const logger = createLogger();
+
+const AUTONOMOUS_PROMPT =
+  "Be creative and do something interesting on the blockchain. " +
+  "Choose an action or set of actions and execute it that highlights your abilities.";
+
+/**
+ * Parse an AUTONOMOUS_ACTIVE_HOURS value such as "9-17"
+ * @param value The start and end UTC hours separated by a dash
+ * @returns The start and end hours
+ */
+function parseActiveHours(value: string): [number, number] {
+  const [start, end] = value.split("-").map((hour) => parseInt(hour, 10));
+  if (isNaN(start) || isNaN(end)) {
+    throw new Error(`Invalid AUTONOMOUS_ACTIVE_HOURS: ${value}`);
+  }
+  return [start, end];
+}
+
+/**
+ * Check whether an hour falls within the active window, which may wrap past midnight
+ * @param hour The current UTC hour
+ * @param start The first active hour
+ * @param end The hour at which the window closes
+ */
+function isActiveHour(hour: number, start: number, end: number): boolean {
+  if (start < end) {
+    return hour >= start && hour < end;
+  }
+  return hour >= start || hour < end;
+}
+
+/**
+ * Run the agent autonomously on the configured schedule
+ * @param agent The initialized agent
+ */
+export function startAutonomousLoop(agent: Agent): void {
+  const intervalMs = config.AUTONOMOUS_INTERVAL_SECS * 1000;
+  const activeHours = config.AUTONOMOUS_ACTIVE_HOURS
+    ? parseActiveHours(config.AUTONOMOUS_ACTIVE_HOURS)
+    : undefined;
+  const maxActionsPerDay = config.AUTONOMOUS_MAX_ACTIONS_PER_DAY;
+
+  let day = new Date().toISOString().slice(0, 10);
+  let actionsToday = 0;
+  let running = false;
+
+  const tick = async () => {
+    const now = new Date();
+    const today = now.toISOString().slice(0, 10);
+    if (today !== day) {
+      day = today;
+      actionsToday = 0;
+    }
+
+    if (running) {
+      return;
+    }
+    if (activeHours && !isActiveHour(now.getUTCHours(), ...activeHours)) {
+      return;
+    }
+    if (maxActionsPerDay !== undefined && actionsToday >= maxActionsPerDay) {
+      return;
+    }
+
+    running = true;
+    actionsToday += 1;
+    try {
+      const response = await agent.processMessage(AUTONOMOUS_PROMPT);
+      logger.info(`Autonomous action completed: ${response.response}`);
+    } catch (error) {
+      logger.error("Autonomous action failed:", error);
+    } finally {
+      running = false;
+    }
+  };
+
+  setInterval(tick, intervalMs);
+  logger.info(`Autonomous mode acting every ${config.AUTONOMOUS_INTERVAL_SECS}s`);
+}
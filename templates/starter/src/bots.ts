@@ -0,0 +1,111 @@
+import { Telegraf } from "telegraf";
+import { Client, Events, GatewayIntentBits, Message } from "discord.js";
+import { Agent } from "./agent";
+import { config } from "./config";
+import { createLogger } from "./logger";
+
+const logger = createLogger();
+
+// Telegram rejects messages longer than 4096 characters and Discord longer than 2000
+const TELEGRAM_MESSAGE_LIMIT = 4096;
+const DISCORD_MESSAGE_LIMIT = 2000;
+
+/**
+ * Split a response into chunks that fit a chat platform's message limit
+ * @param text The text to split
+ * @param limit The maximum length of a chunk
+ * @returns The chunks, in order
+ */
+function splitMessage(text: string, limit: number): string[] {
+  const chunks: string[] = [];
+  for (let i = 0; i < text.length; i += limit) {
+    chunks.push(text.slice(i, i + limit));
+  }
+  return chunks.length > 0 ? chunks : ["No response generated"];
+}
+
+/**
+ * Start a Telegram bot that relays text messages to the agent
+ * @param agent The initialized agent
+ */
+export async function startTelegramBot(agent: Agent): Promise<void> {
+  if (!config.TELEGRAM_BOT_TOKEN) {
+    throw new Error("TELEGRAM_BOT_TOKEN is required for the telegram mode");
+  }
+
+  const bot = new Telegraf(config.TELEGRAM_BOT_TOKEN);
+
+  bot.on("text", async (ctx) => {
+    try {
+      const response = await agent.processMessage(ctx.message.text);
+      const chunks = splitMessage(response.response, TELEGRAM_MESSAGE_LIMIT);
+      for (const chunk of chunks) {
+        await ctx.reply(chunk);
+      }
+    } catch (error) {
+      logger.error("Error processing Telegram message:", error);
+      await ctx.reply("Failed to process message");
+    }
+  });
+
+  // launch() only resolves once the bot stops, so don't wait for it
+  bot.launch().catch((error) => {
+    logger.error("Telegram bot stopped:", error);
+    process.exit(1);
+  });
+  process.once("SIGINT", () => bot.stop("SIGINT"));
+  process.once("SIGTERM", () => bot.stop("SIGTERM"));
+
+  logger.info("Telegram bot started");
+}
+
+/**
+ * Start a Discord bot that answers direct messages and mentions
+ * @param agent The initialized agent
+ */
+export async function startDiscordBot(agent: Agent): Promise<void> {
+  if (!config.DISCORD_BOT_TOKEN) {
+    throw new Error("DISCORD_BOT_TOKEN is required for the discord mode");
+  }
+
+  const client = new Client({
+    intents: [
+      GatewayIntentBits.Guilds,
+      GatewayIntentBits.GuildMessages,
+      GatewayIntentBits.DirectMessages,
+      GatewayIntentBits.MessageContent,
+    ],
+  });
+
+  client.once(Events.ClientReady, (ready) => {
+    logger.info(`Discord bot logged in as ${ready.user.tag}`);
+  });
+
+  client.on(Events.MessageCreate, async (message: Message) => {
+    if (message.author.bot || !client.user) {
+      return;
+    }
+    // In servers, only answer when mentioned
+    if (message.guild && !message.mentions.has(client.user)) {
+      return;
+    }
+
+    const content = message.content.replace(/<@!?\d+>/g, "").trim();
+    if (!content) {
+      return;
+    }
+
+    try {
+      const response = await agent.processMessage(content);
+      const chunks = splitMessage(response.response, DISCORD_MESSAGE_LIMIT);
+      for (const chunk of chunks) {
+        await message.reply(chunk);
+      }
+    } catch (error) {
+      logger.error("Error processing Discord message:", error);
+      await message.reply("Failed to process message");
+    }
+  });
+
+  await client.login(config.DISCORD_BOT_TOKEN);
+}
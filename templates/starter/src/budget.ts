@@ -0,0 +1,98 @@
+import {
+  DynamicStructuredTool,
+  StructuredToolInterface,
+} from "@langchain/core/tools";
+import { config } from "./config";
+import { createLogger } from "./logger";
+
+const logger = createLogger();
+
+/**
+ * Tracks spending against the limits in BUDGET_* and rejects transfers that exceed them
+ */
+export class BudgetGuard {
+  private day = new Date().toISOString().slice(0, 10);
+  private spentToday = 0;
+
+  constructor(
+    private readonly maxTransactionValue: number,
+    private readonly dailyCap: number,
+    private readonly allowedAssets: string[]
+  ) {}
+
+  /**
+   * Create a guard from the environment, if a budget is configured
+   * @returns The guard, or undefined if the agent has no budget
+   */
+  static fromConfig(): BudgetGuard | undefined {
+    if (
+      config.BUDGET_MAX_TRANSACTION_VALUE === undefined ||
+      config.BUDGET_DAILY_CAP === undefined
+    ) {
+      return undefined;
+    }
+    return new BudgetGuard(
+      config.BUDGET_MAX_TRANSACTION_VALUE,
+      config.BUDGET_DAILY_CAP,
+      config.BUDGET_ALLOWED_ASSETS
+    );
+  }
+
+  /**
+   * Reserve an amount of an asset, throwing if it breaks the budget
+   * @param asset The asset ID being moved
+   * @param amount The amount in whole units of the asset
+   */
+  reserve(asset: string, amount: number): void {
+    const today = new Date().toISOString().slice(0, 10);
+    if (today !== this.day) {
+      this.day = today;
+      this.spentToday = 0;
+    }
+
+    if (!this.allowedAssets.includes(asset.toLowerCase())) {
+      throw new Error(`Asset ${asset} is not allowed by the agent's budget`);
+    }
+    if (amount > this.maxTransactionValue) {
+      throw new Error(
+        `Amount ${amount} exceeds the per-transaction limit of ${this.maxTransactionValue}`
+      );
+    }
+    if (this.spentToday + amount > this.dailyCap) {
+      throw new Error(
+        `Amount ${amount} exceeds the remaining daily budget of ${this.dailyCap - this.spentToday}`
+      );
+    }
+    this.spentToday += amount;
+  }
+
+  /**
+   * Wrap tools so that every value transfer is checked against the budget
+   * @param tools The AgentKit LangChain tools
+   * @returns The guarded tools
+   */
+  guard(tools: StructuredToolInterface[]): StructuredToolInterface[] {
+    return tools.map(
+      (tool) =>
+        new DynamicStructuredTool({
+          name: tool.name,
+          description: tool.description,
+          schema: tool.schema as any,
+          func: async (args: Record<string, unknown>) => {
+            const amount = Number(args.amount ?? args.value);
+            if (!isNaN(amount)) {
+              // Native transfers carry no asset ID
+              const asset = String(args.assetId ?? "eth");
+              try {
+                this.reserve(asset, amount);
+              } catch (error) {
+                logger.warn(`Blocked ${tool.name}: ${error}`);
+                return String(error);
+              }
+            }
+            return tool.invoke(args);
+          },
+        })
+    );
+  }
+}
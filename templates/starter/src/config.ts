@@ -29,11 +29,17 @@ export function loadConfig(): EnvVars {
       console.warn("⚠️ Running with default test values");
       // Provide default test config as fallback
       return {
+        LLM_PROVIDER: "openai",
         OPENAI_API_KEY: "test-api-key",
         PORT: "3000",
         WEBSOCKET_PORT: "3001",
         AGENT_MODE: "http",
         MODEL: "gpt-4o-mini",
+        ACTION_PROVIDERS: ["wallet"],
+        NETWORK_ID: "base-sepolia",
+        WALLET_TYPE: "cdp",
+        AUTONOMOUS_INTERVAL_SECS: 300,
+        BUDGET_ALLOWED_ASSETS: [],
         LOG_LEVEL: "info",
         NODE_ENV: "test",
       } as EnvVars;
@@ -2,6 +2,8 @@ import express, { Request, Response } from "express";
 import { createServer } from "http";
 import { Server, Socket } from "socket.io";
 import { Agent } from "./agent";
+import { startAutonomousLoop } from "./autonomous";
+import { startDiscordBot, startTelegramBot } from "./bots";
 import { config } from "./config";
 import { createLogger } from "./logger";
 
@@ -14,13 +16,16 @@ interface MessageData {
 
 /**
  * Start the HTTP server
+ * @param sharedAgent An initialized agent to serve, e.g. one shared with a chat bot
  */
-async function startServer() {
+async function startServer(sharedAgent?: Agent) {
   // Create and initialize agent
-  const agent = new Agent();
-  await agent.initialize();
-
-  logger.info("Agent initialized successfully");
+  let agent = sharedAgent;
+  if (!agent) {
+    agent = new Agent();
+    await agent.initialize();
+    logger.info("Agent initialized successfully");
+  }
 
   // Set up HTTP server
   const app = express();
@@ -36,6 +41,39 @@ async function startServer() {
     res.json(agent.getStatus());
   });
 
+  // Wallet endpoint
+  app.get("/wallet", async (_: Request, res: Response) => {
+    try {
+      return res.json(await agent.getWallet());
+    } catch (error) {
+      logger.error("Error getting wallet:", error);
+      return res.status(503).json({ error: "Failed to get wallet" });
+    }
+  });
+
+  // Faucet endpoint, funding the wallet on testnets
+  app.post("/faucet", async (_: Request, res: Response) => {
+    try {
+      return res.json({ tx_hashes: await agent.requestFaucetFunds() });
+    } catch (error) {
+      logger.error("Error requesting faucet funds:", error);
+      return res.status(502).json({ error: "Failed to request faucet funds" });
+    }
+  });
+
+  // Action history endpoint
+  app.get("/actions", (req: Request, res: Response) => {
+    const since =
+      typeof req.query.since === "string"
+        ? parseInt(req.query.since, 10)
+        : undefined;
+    if (since !== undefined && isNaN(since)) {
+      return res.status(400).json({ error: "Invalid since parameter" });
+    }
+
+    return res.json({ actions: agent.getActions(since) });
+  });
+
   // Interaction endpoint
   app.post("/interact", async (req: Request, res: Response) => {
     try {
@@ -131,6 +169,29 @@ async function main() {
 
     if (config.AGENT_MODE === "http") {
       await startServer();
+    } else if (config.AGENT_MODE === "autonomous") {
+      // The HTTP server keeps serving health checks and interactions
+      const agent = new Agent();
+      await agent.initialize();
+      logger.info("Agent initialized successfully");
+
+      await startServer(agent);
+      startAutonomousLoop(agent);
+    } else if (
+      config.AGENT_MODE === "telegram" ||
+      config.AGENT_MODE === "discord"
+    ) {
+      // Bots share the agent with the HTTP server, which keeps serving health checks
+      const agent = new Agent();
+      await agent.initialize();
+      logger.info("Agent initialized successfully");
+
+      await startServer(agent);
+      if (config.AGENT_MODE === "telegram") {
+        await startTelegramBot(agent);
+      } else {
+        await startDiscordBot(agent);
+      }
     } else {
       await startWebSocketServer();
     }
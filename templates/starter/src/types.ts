@@ -1,16 +1,109 @@
 import { z } from "zod";
+import type { EvmWalletProvider } from "@coinbase/agentkit";
 import { BaseMessage } from "@langchain/core/messages";
 
+// On-chain action categories that can be allowed through ALLOWED_ACTIONS
+export const allowedActionNames = [
+  "transfer",
+  "swap",
+  "wrap",
+  "deploy_token",
+  "deploy_nft",
+] as const;
+export type AllowedActionName = (typeof allowedActionNames)[number];
+
+// Optional numeric variable; docker-compose passes unset variables as ""
+const optionalNumber = (schema: z.ZodNumber) =>
+  z.preprocess(
+    (v) => (v === "" ? undefined : v),
+    z.coerce.number().pipe(schema).optional()
+  );
+
+// AgentKit action providers that can be enabled through ACTION_PROVIDERS
+export const actionProviderNames = [
+  "wallet",
+  "erc20",
+  "weth",
+  "pyth",
+  "custom",
+] as const;
+export type ActionProviderName = (typeof actionProviderNames)[number];
+
 // Environment variable schema
 export const envSchema = z.object({
-  OPENAI_API_KEY: z.string(),
+  LLM_PROVIDER: z
+    .enum(["openai", "anthropic", "azureopenai", "local"])
+    .default("openai"),
+  OPENAI_API_KEY: z.string().optional(),
+  OPENAI_BASE_URL: z.string().optional(),
+  ANTHROPIC_API_KEY: z.string().optional(),
+  AZURE_OPENAI_API_KEY: z.string().optional(),
+  AZURE_OPENAI_ENDPOINT: z.string().optional(),
+  AZURE_OPENAI_API_DEPLOYMENT_NAME: z.string().optional(),
+  AZURE_OPENAI_API_VERSION: z.string().optional(),
   CDP_API_KEY_NAME: z.string().optional(),
   CDP_API_KEY_PRIVATE_KEY: z.string().optional(),
+  CDP_WALLET_DATA: z.string().optional(),
+  WALLET_TYPE: z
+    .enum(["cdp", "server_signer", "smart_wallet", "private_key"])
+    .default("cdp"),
+  CDP_WALLET_SECRET: z.string().optional(),
+  PAYMASTER_URL: z.string().optional(),
+  WALLET_PRIVATE_KEY: z.string().optional(),
+  WALLET_DATA_FILE: z.string().optional(),
+  NETWORK_ID: z
+    .enum(["base-sepolia", "base-mainnet", "ethereum-mainnet"])
+    .default("base-sepolia"),
   PORT: z.string().default("3000"),
   WEBSOCKET_PORT: z.string().default("3001"),
   WEBSOCKET_URL: z.string().optional(),
-  AGENT_MODE: z.enum(["http", "cli-chat"]).default("http"),
+  AGENT_MODE: z
+    .enum(["http", "cli-chat", "autonomous", "telegram", "discord"])
+    .default("http"),
+  AUTONOMOUS_INTERVAL_SECS: z.coerce.number().int().positive().default(300),
+  AUTONOMOUS_ACTIVE_HOURS: z.string().optional(),
+  AUTONOMOUS_MAX_ACTIONS_PER_DAY: optionalNumber(z.number().int().positive()),
+  TELEGRAM_BOT_TOKEN: z.string().optional(),
+  DISCORD_BOT_TOKEN: z.string().optional(),
   MODEL: z.string().default("gpt-4o-mini"),
+  SYSTEM_PROMPT: z.string().optional(),
+  TEMPERATURE: optionalNumber(z.number().min(0).max(2)),
+  MAX_TOKENS: optionalNumber(z.number().int().positive()),
+  ACTION_PROVIDERS: z
+    .string()
+    .default("wallet")
+    .transform((value) =>
+      value
+        .split(",")
+        .map((name) => name.trim())
+        .filter((name) => name.length > 0)
+    )
+    .pipe(z.array(z.enum(actionProviderNames))),
+  // Unset or empty allows every action; "none" allows only read-only tools
+  ALLOWED_ACTIONS: z
+    .string()
+    .optional()
+    .transform((value) => {
+      if (!value) {
+        return undefined;
+      }
+      return value
+        .split(",")
+        .map((name) => name.trim())
+        .filter((name) => name.length > 0 && name !== "none");
+    })
+    .pipe(z.array(z.enum(allowedActionNames)).optional()),
+  BUDGET_MAX_TRANSACTION_VALUE: optionalNumber(z.number().positive()),
+  BUDGET_DAILY_CAP: optionalNumber(z.number().positive()),
+  BUDGET_ALLOWED_ASSETS: z
+    .string()
+    .default("")
+    .transform((value) =>
+      value
+        .split(",")
+        .map((asset) => asset.trim().toLowerCase())
+        .filter((asset) => asset.length > 0)
+    ),
   LOG_LEVEL: z.enum(["error", "warn", "info", "debug"]).default("info"),
   NODE_ENV: z
     .enum(["development", "production", "test"])
@@ -22,7 +115,7 @@ export type EnvVars = z.infer<typeof envSchema>;
 // Agent configuration
 export interface AgentConfig {
   model: string;
-  mode: "http" | "cli-chat";
+  mode: "http" | "cli-chat" | "autonomous" | "telegram" | "discord";
   port: number;
   websocketPort: number;
   websocketUrl?: string;
@@ -34,6 +127,14 @@ export interface AgentResponse {
   metadata?: Record<string, unknown>;
 }
 
+// On-chain action record kept for the audit trail
+export interface ActionRecord {
+  timestamp: number;
+  action: string;
+  tx_hash?: string;
+  details: string;
+}
+
 export interface AgentStatus {
   status: "running" | "error";
   uptime: number;
@@ -91,4 +192,13 @@ export interface LangChainAgent {
 export interface InitializeResult {
   agent: LangChainAgent;
   config: LangChainAgentConfig;
+  walletProvider: EvmWalletProvider;
+}
+
+// The agent's wallet, served at /wallet so the blueprint can report where to fund it
+export interface AgentWallet {
+  address: string;
+  network_id: string;
+  // Native balance in wei, as a decimal string
+  balance: string;
 }
@@ -0,0 +1,136 @@
+import * as fs from "fs";
+import * as path from "path";
+import {
+  CdpWalletProvider,
+  EvmWalletProvider,
+  SmartWalletProvider,
+  ViemWalletProvider,
+} from "@coinbase/agentkit";
+import { Coinbase, ExternalAddress, Wallet } from "@coinbase/coinbase-sdk";
+import { createWalletClient, Hex, http } from "viem";
+import { privateKeyToAccount } from "viem/accounts";
+import { base, baseSepolia, mainnet } from "viem/chains";
+import { config } from "./config";
+import { AgentWallet } from "./types";
+
+// Chains of the networks an agent's own account can be used on
+const chains = {
+  "base-sepolia": baseSepolia,
+  "base-mainnet": base,
+  "ethereum-mainnet": mainnet,
+};
+
+/**
+ * Returns the wallet data to restore the agent's wallet from: the data this container wrote
+ * before restarting, or the data the blueprint captured from an earlier deployment.
+ * A new wallet is created when there is neither.
+ */
+export function loadWalletData(): string | undefined {
+  const file = config.WALLET_DATA_FILE;
+  if (file && fs.existsSync(file)) {
+    return fs.readFileSync(file, "utf8");
+  }
+  return config.CDP_WALLET_DATA || undefined;
+}
+
+/**
+ * Restores the agent's wallet, or creates one on the configured network, as the kind of
+ * wallet selected by WALLET_TYPE
+ */
+export async function createWalletProvider(): Promise<EvmWalletProvider> {
+  const walletData = loadWalletData();
+  const apiKey = {
+    apiKeyName: config.CDP_API_KEY_NAME,
+    privateKey: config.CDP_API_KEY_PRIVATE_KEY?.replace(/\\n/g, "\n"),
+  };
+
+  switch (config.WALLET_TYPE) {
+    case "smart_wallet": {
+      if (!config.CDP_WALLET_SECRET) {
+        throw new Error("CDP_WALLET_SECRET is required for smart wallets");
+      }
+      Coinbase.configure(apiKey);
+      return SmartWalletProvider.configureWithWallet({
+        networkId: config.NETWORK_ID,
+        signer: privateKeyToAccount(config.CDP_WALLET_SECRET as Hex),
+        smartWalletAddress: walletData ? JSON.parse(walletData).address : undefined,
+        paymasterUrl: config.PAYMASTER_URL || undefined,
+      });
+    }
+    case "private_key": {
+      if (!config.WALLET_PRIVATE_KEY) {
+        throw new Error("WALLET_PRIVATE_KEY is required for private_key wallets");
+      }
+      const client = createWalletClient({
+        account: privateKeyToAccount(config.WALLET_PRIVATE_KEY as Hex),
+        chain: chains[config.NETWORK_ID],
+        transport: http(),
+      });
+      return new ViemWalletProvider(client);
+    }
+    case "server_signer": {
+      // The wallet's transactions are signed by the project's Server-Signer, so the wallet
+      // is created here with it enabled, and it is enabled again once the provider has
+      // configured the SDK
+      Coinbase.configure({ ...apiKey, useServerSigner: true });
+      const wallet = walletData
+        ? await Wallet.import(JSON.parse(walletData))
+        : await Wallet.create({ networkId: config.NETWORK_ID });
+      const walletProvider = await CdpWalletProvider.configureWithWallet({
+        apiKeyName: config.CDP_API_KEY_NAME,
+        apiKeyPrivateKey: config.CDP_API_KEY_PRIVATE_KEY,
+        networkId: config.NETWORK_ID,
+        wallet,
+      });
+      Coinbase.useServerSigner = true;
+      return walletProvider;
+    }
+    default:
+      return CdpWalletProvider.configureWithWallet({
+        apiKeyName: config.CDP_API_KEY_NAME,
+        apiKeyPrivateKey: config.CDP_API_KEY_PRIVATE_KEY,
+        networkId: config.NETWORK_ID,
+        cdpWalletData: walletData,
+      });
+  }
+}
+
+/**
+ * Writes the wallet's data where the blueprint captures and seals it, so the same wallet is
+ * restored when the agent is redeployed. Smart wallets are restored from their address,
+ * since they are owned by CDP_WALLET_SECRET, and private-key accounts only record theirs.
+ */
+export async function saveWalletData(walletProvider: EvmWalletProvider): Promise<void> {
+  const file = config.WALLET_DATA_FILE;
+  if (!file) {
+    return;
+  }
+  const data = JSON.stringify(
+    walletProvider instanceof CdpWalletProvider
+      ? await walletProvider.exportWallet()
+      : { address: walletProvider.getAddress() }
+  );
+  fs.mkdirSync(path.dirname(file), { recursive: true });
+  fs.writeFileSync(file, data, { mode: 0o600 });
+}
+
+/**
+ * Describes the agent's wallet for the blueprint, which reports where to fund the agent
+ */
+export async function describeWallet(walletProvider: EvmWalletProvider): Promise<AgentWallet> {
+  return {
+    address: walletProvider.getAddress(),
+    network_id: config.NETWORK_ID,
+    balance: (await walletProvider.getBalance()).toString(),
+  };
+}
+
+/**
+ * Requests testnet ETH for the agent's wallet from the CDP faucet
+ * @returns The hashes of the faucet's transactions
+ */
+export async function requestFaucetFunds(walletProvider: EvmWalletProvider): Promise<string[]> {
+  const address = new ExternalAddress(config.NETWORK_ID, walletProvider.getAddress());
+  const transaction = await address.faucet();
+  return [transaction.getTransactionHash()];
+}